@@ -0,0 +1,14 @@
+#![no_main]
+
+use depc_bridge::depc::decode_script_hex;
+use libfuzzer_sys::fuzz_target;
+
+// The OP_RETURN scripts come from the chain, any bytes are either decoded or refused with an
+// error, the decoder never panics
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_script_hex(&hex::encode(data));
+    // the hex typed to `inspect-script` can be anything as well
+    if let Ok(hex_str) = std::str::from_utf8(data) {
+        let _ = decode_script_hex(hex_str);
+    }
+});
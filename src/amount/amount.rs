@@ -0,0 +1,255 @@
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// How the amounts are serialized to JSON
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AmountFormat {
+    /// Decimal strings, they're not truncated by the clients those parse the numbers as f64
+    #[default]
+    String,
+    /// Integers, for the legacy clients those are not migrated to the strings yet
+    Number,
+}
+
+tokio::task_local! {
+    static AMOUNT_FORMAT: AmountFormat;
+}
+
+impl AmountFormat {
+    /// The format of the current task, `String` unless it's run in `AmountFormat::scope`
+    pub fn current() -> AmountFormat {
+        AMOUNT_FORMAT.try_with(|format| *format).unwrap_or_default()
+    }
+
+    /// Run `f` with the amounts serialized in this format
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        AMOUNT_FORMAT.scope(self, f).await
+    }
+}
+
+/// The amount of DePC in value64 (1 DePC = 10^8 value64)
+///
+/// The arithmetic is checked, an overflow makes `None` instead of wrapping around. The amount is
+/// serialized as a string in JSON by default, so it's not truncated by the clients those parse
+/// the numbers as f64, see `AmountFormat`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DepcAmount(u64);
+
+/// The amount of a Solana token (lamports of SOL or the base units of an spl-token), the
+/// decimals belong to the mint so they are given explicitly when it's converted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenAmount(u64);
+
+impl DepcAmount {
+    pub const ZERO: DepcAmount = DepcAmount(0);
+    pub const DECIMALS: u8 = 8;
+
+    pub const fn from_value64(value64: u64) -> DepcAmount {
+        DepcAmount(value64)
+    }
+
+    pub const fn value64(self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: DepcAmount) -> Option<DepcAmount> {
+        self.0.checked_add(other.0).map(DepcAmount)
+    }
+
+    pub fn checked_sub(self, other: DepcAmount) -> Option<DepcAmount> {
+        self.0.checked_sub(other.0).map(DepcAmount)
+    }
+
+    /// Convert the amount to the token of a mint with `decimals`
+    ///
+    /// Returns `None` when the amount overflows, or the amount cannot be represented exactly
+    /// by the mint (the digits would be dropped)
+    pub fn to_token_amount(self, decimals: u8) -> Option<TokenAmount> {
+        rescale(self.0, Self::DECIMALS, decimals).map(TokenAmount)
+    }
+}
+
+impl TokenAmount {
+    pub const ZERO: TokenAmount = TokenAmount(0);
+
+    pub const fn new(raw: u64) -> TokenAmount {
+        TokenAmount(raw)
+    }
+
+    /// The amount in the base units of the token
+    pub const fn raw(self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: TokenAmount) -> Option<TokenAmount> {
+        self.0.checked_add(other.0).map(TokenAmount)
+    }
+
+    pub fn checked_sub(self, other: TokenAmount) -> Option<TokenAmount> {
+        self.0.checked_sub(other.0).map(TokenAmount)
+    }
+
+    /// Convert the amount of the token of a mint with `decimals` to DePC, see
+    /// [`DepcAmount::to_token_amount`]
+    pub fn to_depc_amount(self, decimals: u8) -> Option<DepcAmount> {
+        rescale(self.0, decimals, DepcAmount::DECIMALS).map(DepcAmount)
+    }
+}
+
+/// Move the decimal point of `value` from `from_decimals` to `to_decimals`, `None` when it
+/// overflows or the dropped digits are not zero
+pub(super) fn rescale(value: u64, from_decimals: u8, to_decimals: u8) -> Option<u64> {
+    if to_decimals >= from_decimals {
+        let factor = 10u64.checked_pow((to_decimals - from_decimals) as u32)?;
+        value.checked_mul(factor)
+    } else {
+        let factor = 10u64.checked_pow((from_decimals - to_decimals) as u32)?;
+        if value % factor != 0 {
+            return None;
+        }
+        Some(value / factor)
+    }
+}
+
+macro_rules! impl_amount_traits {
+    ($t:ident) => {
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $t {
+            type Err = std::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok($t(s.parse()?))
+            }
+        }
+
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match AmountFormat::current() {
+                    AmountFormat::String => serializer.collect_str(&self.0),
+                    AmountFormat::Number => serializer.serialize_u64(self.0),
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct AmountVisitor;
+
+                impl<'de> de::Visitor<'de> for AmountVisitor {
+                    type Value = $t;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "an amount as a string or an unsigned integer")
+                    }
+
+                    fn visit_str<E: de::Error>(self, v: &str) -> Result<$t, E> {
+                        v.parse().map_err(E::custom)
+                    }
+
+                    // the legacy clients still send the amounts as numbers
+                    fn visit_u64<E: de::Error>(self, v: u64) -> Result<$t, E> {
+                        Ok($t(v))
+                    }
+                }
+
+                deserializer.deserialize_any(AmountVisitor)
+            }
+        }
+
+        impl ToSql for $t {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                self.0.to_sql()
+            }
+        }
+
+        impl FromSql for $t {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                u64::column_result(value).map($t)
+            }
+        }
+    };
+}
+
+impl_amount_traits!(DepcAmount);
+impl_amount_traits!(TokenAmount);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a = DepcAmount::from_value64(300);
+        let b = DepcAmount::from_value64(200);
+        assert_eq!(a.checked_add(b), Some(DepcAmount::from_value64(500)));
+        assert_eq!(a.checked_sub(b), Some(DepcAmount::from_value64(100)));
+        assert_eq!(b.checked_sub(a), None);
+        assert_eq!(DepcAmount::from_value64(u64::MAX).checked_add(b), None);
+        assert_eq!(TokenAmount::new(1).checked_sub(TokenAmount::new(2)), None);
+    }
+
+    #[test]
+    fn test_convert_with_decimals() {
+        let amount = DepcAmount::from_value64(5 * 100_000_000);
+        assert_eq!(
+            amount.to_token_amount(8),
+            Some(TokenAmount::new(500_000_000))
+        );
+        assert_eq!(
+            amount.to_token_amount(9),
+            Some(TokenAmount::new(5_000_000_000))
+        );
+        assert_eq!(amount.to_token_amount(6), Some(TokenAmount::new(5_000_000)));
+        // the last 2 digits cannot be kept by a mint with 6 decimals
+        assert_eq!(DepcAmount::from_value64(123).to_token_amount(6), None);
+        assert_eq!(DepcAmount::from_value64(u64::MAX).to_token_amount(9), None);
+        assert_eq!(TokenAmount::new(5_000_000).to_depc_amount(6), Some(amount));
+    }
+
+    #[test]
+    fn test_serde_as_string() {
+        let amount = DepcAmount::from_value64(u64::MAX);
+        let json = serde_json::to_string(&amount).unwrap();
+        assert_eq!(json, "\"18446744073709551615\"");
+        assert_eq!(serde_json::from_str::<DepcAmount>(&json).unwrap(), amount);
+        assert_eq!(
+            serde_json::from_str::<TokenAmount>("1000").unwrap(),
+            TokenAmount::new(1000)
+        );
+        assert!(serde_json::from_str::<TokenAmount>("\"-1\"").is_err());
+    }
+
+    #[test]
+    fn test_serde_as_number() {
+        let amount = DepcAmount::from_value64(u64::MAX);
+        let json = AMOUNT_FORMAT.sync_scope(AmountFormat::Number, || {
+            assert_eq!(AmountFormat::current(), AmountFormat::Number);
+            serde_json::to_string(&amount).unwrap()
+        });
+        assert_eq!(json, "18446744073709551615");
+        assert_eq!(serde_json::from_str::<DepcAmount>(&json).unwrap(), amount);
+        // the scope is left
+        assert_eq!(AmountFormat::current(), AmountFormat::String);
+        assert_eq!(
+            serde_json::to_string(&TokenAmount::new(1000)).unwrap(),
+            "\"1000\""
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use super::amount::rescale;
+use super::{DepcAmount, TokenAmount};
+
+/// The most decimals of an amount, 10^19 is the largest power of 10 fits in u64
+pub const MAX_DECIMALS: u8 = 19;
+
+/// Converting the amounts between DePC and the bridged token, the deposits are minted and the
+/// returns are paid out with it
+///
+/// The decimals of the mint are queried from the chain when the bridge is run, the ones of DePC
+/// are configured (`DepcAmount::DECIMALS` by default). The math is checked, an amount overflows
+/// or loses its digits makes `None` instead of being truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmountConverter {
+    depc_decimals: u8,
+    mint_decimals: u8,
+}
+
+impl AmountConverter {
+    /// Returns `None` when any of the decimals is over `MAX_DECIMALS`
+    pub fn new(depc_decimals: u8, mint_decimals: u8) -> Option<AmountConverter> {
+        if depc_decimals > MAX_DECIMALS || mint_decimals > MAX_DECIMALS {
+            return None;
+        }
+        Some(AmountConverter {
+            depc_decimals,
+            mint_decimals,
+        })
+    }
+
+    pub fn depc_decimals(&self) -> u8 {
+        self.depc_decimals
+    }
+
+    pub fn mint_decimals(&self) -> u8 {
+        self.mint_decimals
+    }
+
+    /// The amount of the token minted for `amount` of DePC
+    ///
+    /// Returns `None` when it overflows, or the mint has less decimals and the digits would be
+    /// dropped
+    pub fn to_token(&self, amount: DepcAmount) -> Option<TokenAmount> {
+        rescale(amount.value64(), self.depc_decimals, self.mint_decimals).map(TokenAmount::new)
+    }
+
+    /// The amount of DePC paid out for `amount` of the token, see `to_token`
+    pub fn to_depc(&self, amount: TokenAmount) -> Option<DepcAmount> {
+        rescale(amount.raw(), self.mint_decimals, self.depc_decimals).map(DepcAmount::from_value64)
+    }
+
+    /// The smallest amount of DePC converted exactly, the amounts are multiples of it, e.g.
+    /// 100 value64 for a mint with 6 decimals
+    pub fn depc_unit(&self) -> DepcAmount {
+        let unit = self
+            .depc_decimals
+            .checked_sub(self.mint_decimals)
+            .map_or(1, |digits| 10u64.pow(digits as u32));
+        DepcAmount::from_value64(unit)
+    }
+
+    /// Split `amount` into the multiple of `depc_unit` converted exactly and the rest under
+    /// the unit, the caller books the rest
+    pub fn round_to_unit(&self, amount: DepcAmount) -> (DepcAmount, DepcAmount) {
+        let rest = amount.value64() % self.depc_unit().value64();
+        (
+            DepcAmount::from_value64(amount.value64() - rest),
+            DepcAmount::from_value64(rest),
+        )
+    }
+
+    /// The amount of DePC paid out for `amount` of the token, the digits of a mint with more
+    /// decimals DePC cannot hold are dropped. Returns `None` when it overflows
+    pub fn to_depc_rounded(&self, amount: TokenAmount) -> Option<DepcAmount> {
+        let unit = self
+            .mint_decimals
+            .checked_sub(self.depc_decimals)
+            .map_or(1, |digits| 10u64.pow(digits as u32));
+        self.to_depc(TokenAmount::new(amount.raw() - amount.raw() % unit))
+    }
+}
+
+impl Default for AmountConverter {
+    /// The mint is made with the same decimals as DePC
+    fn default() -> Self {
+        AmountConverter {
+            depc_decimals: DepcAmount::DECIMALS,
+            mint_decimals: DepcAmount::DECIMALS,
+        }
+    }
+}
+
+impl fmt::Display for AmountConverter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} decimals of DePC to {} decimals of the mint",
+            self.depc_decimals, self.mint_decimals
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_amounts() {
+        let same = AmountConverter::default();
+        let amount = DepcAmount::from_value64(123_456_789);
+        assert_eq!(same.to_token(amount), Some(TokenAmount::new(123_456_789)));
+        assert_eq!(same.to_depc(TokenAmount::new(123_456_789)), Some(amount));
+        assert_eq!(same.depc_unit(), DepcAmount::from_value64(1));
+
+        // the mint has more decimals, every amount is converted back and forth
+        let more = AmountConverter::new(8, 9).unwrap();
+        assert_eq!(more.to_token(amount), Some(TokenAmount::new(1_234_567_890)));
+        assert_eq!(more.to_depc(TokenAmount::new(1_234_567_890)), Some(amount));
+        // the last digit of the token is dropped
+        assert_eq!(more.to_depc(TokenAmount::new(1_234_567_891)), None);
+        assert_eq!(more.to_token(DepcAmount::from_value64(u64::MAX)), None);
+
+        // the mint has less decimals, only the multiples of the unit are converted
+        let less = AmountConverter::new(8, 6).unwrap();
+        assert_eq!(less.depc_unit(), DepcAmount::from_value64(100));
+        assert_eq!(less.to_token(amount), None);
+        assert_eq!(
+            less.to_token(DepcAmount::from_value64(123_456_700)),
+            Some(TokenAmount::new(1_234_567))
+        );
+        assert_eq!(
+            less.to_depc(TokenAmount::new(1_234_567)),
+            Some(DepcAmount::from_value64(123_456_700))
+        );
+        // the token has more than DePC can hold
+        assert_eq!(less.to_depc(TokenAmount::new(u64::MAX / 100 + 1)), None);
+        assert_eq!(
+            less.to_depc_rounded(TokenAmount::new(u64::MAX / 100 + 1)),
+            None
+        );
+        // the rest under the unit is split off
+        assert_eq!(
+            less.round_to_unit(amount),
+            (
+                DepcAmount::from_value64(123_456_700),
+                DepcAmount::from_value64(89)
+            )
+        );
+        assert_eq!(
+            less.round_to_unit(DepcAmount::from_value64(99)),
+            (DepcAmount::from_value64(0), DepcAmount::from_value64(99))
+        );
+        assert_eq!(
+            same.round_to_unit(amount),
+            (amount, DepcAmount::from_value64(0))
+        );
+        // the digits DePC cannot hold are dropped
+        assert_eq!(
+            more.to_depc_rounded(TokenAmount::new(1_234_567_891)),
+            Some(amount)
+        );
+        assert_eq!(
+            more.to_depc_rounded(TokenAmount::new(9)),
+            Some(DepcAmount::from_value64(0))
+        );
+        assert_eq!(
+            less.to_depc_rounded(TokenAmount::new(1_234_567)),
+            Some(DepcAmount::from_value64(123_456_700))
+        );
+
+        // DePC is configured with other decimals
+        let other = AmountConverter::new(6, 9).unwrap();
+        assert_eq!(
+            other.to_token(DepcAmount::from_value64(1)),
+            Some(TokenAmount::new(1000))
+        );
+    }
+
+    #[test]
+    fn test_invalid_decimals() {
+        assert!(AmountConverter::new(MAX_DECIMALS, 0).is_some());
+        assert!(AmountConverter::new(MAX_DECIMALS + 1, 8).is_none());
+        assert!(AmountConverter::new(8, u8::MAX).is_none());
+        // the factor of 19 digits still fits
+        let widest = AmountConverter::new(0, MAX_DECIMALS).unwrap();
+        assert_eq!(
+            widest.to_token(DepcAmount::from_value64(1)),
+            Some(TokenAmount::new(10u64.pow(19)))
+        );
+        assert_eq!(widest.to_token(DepcAmount::from_value64(2)), None);
+    }
+}
@@ -0,0 +1,5 @@
+mod amount;
+mod converter;
+
+pub use amount::*;
+pub use converter::*;
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::BridgeError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterReport {
+    /// The number of the clustered addresses
+    pub addresses: u64,
+    pub clusters: u64,
+    /// The number of the outputs joined to the clusters of the inputs as the change
+    pub change_outputs: u64,
+}
+
+/// The disjoint sets of the addresses, the root of a set is always its first seen address
+#[derive(Default)]
+struct AddressSets {
+    index: HashMap<String, usize>,
+    addresses: Vec<String>,
+    parent: Vec<usize>,
+}
+
+impl AddressSets {
+    fn add(&mut self, address: &str) -> usize {
+        if let Some(&i) = self.index.get(address) {
+            return i;
+        }
+        let i = self.addresses.len();
+        self.index.insert(address.to_owned(), i);
+        self.addresses.push(address.to_owned());
+        self.parent.push(i);
+        i
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+/// Rebuild table `address_clusters` from the indexed coins with the heuristics:
+///
+/// * Common-input-ownership - the input addresses of a transaction are owned by the same entity
+/// * Change output - when a transaction pays to exactly 2 addresses, none of them is in the
+///   cluster of the inputs, and exactly one of them is seen for the first time, the new address
+///   is the change and it's owned by the entity of the inputs
+///
+/// The cluster ids are numbered from 1 in the order the clusters are first seen.
+pub fn build_address_clusters(conn: &db::Conn) -> Result<ClusterReport, BridgeError> {
+    let mut report = ClusterReport::default();
+    let mut sets = AddressSets::default();
+    // the first input of each spending transaction, the others are joined to it
+    let mut first_inputs: HashMap<String, usize> = HashMap::new();
+    for (spent_txid, owner) in conn.query_spent_inputs()? {
+        let i = sets.add(&owner);
+        match first_inputs.get(&spent_txid) {
+            Some(&first) => sets.union(first, i),
+            None => {
+                first_inputs.insert(spent_txid, i);
+            }
+        }
+    }
+
+    let outputs = conn.query_outputs_of_two_output_txs()?;
+    for pair in outputs.chunks(2) {
+        let [(txid, owner0, first_seen0), (_, owner1, first_seen1)] = pair else {
+            continue;
+        };
+        // the coinbase and the transactions spend the unknown coins have no inputs
+        let Some(&input) = first_inputs.get(txid) else {
+            continue;
+        };
+        let input_root = sets.find(input);
+        let in_input_cluster = |sets: &mut AddressSets, owner: &str| {
+            sets.index
+                .get(owner)
+                .copied()
+                .is_some_and(|i| sets.find(i) == input_root)
+        };
+        // the change goes back to the entity already, the other output is the payment
+        if in_input_cluster(&mut sets, owner0.as_str())
+            || in_input_cluster(&mut sets, owner1.as_str())
+        {
+            continue;
+        }
+        let change = match (first_seen0, first_seen1) {
+            (true, false) => owner0,
+            (false, true) => owner1,
+            _ => continue,
+        };
+        let i = sets.add(change);
+        sets.union(input, i);
+        report.change_outputs += 1;
+    }
+
+    let roots: Vec<usize> = (0..sets.addresses.len()).map(|i| sets.find(i)).collect();
+    let mut cluster_ids = HashMap::new();
+    let mut clusters = Vec::with_capacity(roots.len());
+    for (address, root) in sets.addresses.iter().zip(roots) {
+        let next_id = cluster_ids.len() as u64 + 1;
+        let cluster_id = *cluster_ids.entry(root).or_insert(next_id);
+        clusters.push((address.clone(), cluster_id));
+    }
+    conn.replace_address_clusters(&clusters)?;
+    report.addresses = clusters.len() as u64;
+    report.clusters = cluster_ids.len() as u64;
+    info!(
+        "{} address(es) are grouped into {} cluster(s), {} change output(s) found",
+        report.addresses, report.clusters, report.change_outputs
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::DepcAmount;
+
+    fn add_coin(conn: &db::Conn, txid: &str, n: u32, owner: &str) {
+        conn.add_coin(txid, n, DepcAmount::from_value64(1000), owner, "")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_build_address_clusters() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        // `f` funds `a`, `b`, `c`, `x` and `y`
+        for (n, owner) in ["a", "b", "c", "x", "y"].iter().enumerate() {
+            add_coin(&conn, "f", n as u32, owner);
+        }
+        // `t1` spends `a` and `b`, pays `p` and the new change address `ch`
+        conn.mark_coin_to_spent("f", 0, "t1", 1).unwrap();
+        conn.mark_coin_to_spent("f", 1, "t1", 1).unwrap();
+        add_coin(&conn, "f2", 0, "p");
+        add_coin(&conn, "t1", 0, "p");
+        add_coin(&conn, "t1", 1, "ch");
+        // `t2` spends `c` and `ch`, so `c` joins the cluster of `a` through the change
+        conn.mark_coin_to_spent("f", 2, "t2", 2).unwrap();
+        conn.mark_coin_to_spent("t1", 1, "t2", 2).unwrap();
+        add_coin(&conn, "t2", 0, "q");
+        // `t3` spends `x` and pays 2 new addresses, the change cannot be told
+        conn.mark_coin_to_spent("f", 3, "t3", 3).unwrap();
+        add_coin(&conn, "t3", 0, "n1");
+        add_coin(&conn, "t3", 1, "n2");
+        // `t4` spends `y` and pays the new `n3` and `y` itself, `y` is the change
+        conn.mark_coin_to_spent("f", 4, "t4", 4).unwrap();
+        add_coin(&conn, "t4", 0, "n3");
+        add_coin(&conn, "t4", 1, "y");
+
+        let report = build_address_clusters(&conn).unwrap();
+        assert_eq!(
+            report,
+            ClusterReport {
+                addresses: 6,
+                clusters: 3,
+                change_outputs: 1,
+            }
+        );
+        let cluster_id = conn.query_cluster_id_of_address("a").unwrap().unwrap();
+        assert_eq!(cluster_id, 1);
+        assert_eq!(
+            conn.query_cluster_members(cluster_id).unwrap(),
+            vec!["a", "b", "c", "ch"]
+        );
+        assert_eq!(conn.query_cluster_id_of_address("x").unwrap(), Some(2));
+        assert_eq!(conn.query_cluster_id_of_address("n1").unwrap(), None);
+        assert_eq!(conn.query_cluster_id_of_address("n3").unwrap(), None);
+
+        // the rebuild replaces the clusters
+        assert_eq!(build_address_clusters(&conn).unwrap(), report);
+    }
+}
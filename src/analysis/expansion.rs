@@ -0,0 +1,289 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use log::{debug, info, warn};
+use tokio::time::{sleep, Duration};
+
+use crate::db;
+use crate::BridgeError;
+
+/// The bounds of an exchange analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpansionLimits {
+    /// The addresses more than `max_depth` hops away from the inputs of the seed transaction
+    /// are not visited, 1 only expands the inputs of the seed transaction
+    pub max_depth: u32,
+    /// The analysis stops after so many addresses are visited, it's resumed by analyzing the
+    /// same seed transaction with a larger limit
+    pub max_addresses: usize,
+}
+
+impl Default for ExpansionLimits {
+    fn default() -> Self {
+        ExpansionLimits {
+            max_depth: 1,
+            max_addresses: 10000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpansionReport {
+    /// The number of the addresses visited by the analysis of the seed, including the ones
+    /// visited before it's resumed
+    pub visited: u64,
+    /// The number of the addresses saved to table `exchange_addresses` by this call
+    pub saved: u64,
+    /// `false` when the analysis is stopped by `max_addresses` or the exit signal
+    pub complete: bool,
+}
+
+/// Expand the exchange addresses from the inputs of `seed_txid` in breadth-first order
+///
+/// The addresses spent in the same transaction are owned by the same exchange
+/// (common-input-ownership), so the transactions spend the coins of a visited address bring
+/// their input addresses in as the next hop. The visited addresses are saved to table
+/// `analysis_frontier` as the analysis goes, analyzing the same seed again resumes from the
+/// addresses those are not expanded yet. The addresses saved by the analysis are first seen at
+/// `now`.
+pub async fn expand_exchange_addresses(
+    conn: &db::Conn,
+    seed_txid: &str,
+    limits: ExpansionLimits,
+    now: u64,
+    exit_sig: &Mutex<bool>,
+) -> Result<ExpansionReport, BridgeError> {
+    let mut report = ExpansionReport::default();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    for record in conn.query_analysis_frontier(seed_txid)? {
+        if !record.expanded {
+            queue.push_back((record.address.clone(), record.depth));
+        }
+        visited.insert(record.address);
+    }
+    if visited.is_empty() {
+        for address in conn.query_inputs(seed_txid)? {
+            if visited.contains(&address) {
+                continue;
+            }
+            conn.add_analysis_frontier(seed_txid, &address, 0)?;
+            report.saved += save_exchange_address(conn, &address, seed_txid, now);
+            visited.insert(address.clone());
+            queue.push_back((address, 0));
+        }
+    } else {
+        info!(
+            "resume analysis of {seed_txid}, {} address(es) visited, {} left to expand",
+            visited.len(),
+            queue.len()
+        );
+    }
+    // the transactions are not persisted, querying one again only finds the visited addresses
+    let mut visited_txids = HashSet::from([seed_txid.to_owned()]);
+
+    report.complete = 'bfs: loop {
+        let Some((address, depth)) = queue.pop_front() else {
+            break true;
+        };
+        if depth >= limits.max_depth {
+            // kept unexpanded, so a deeper analysis of the same seed goes on from here
+            continue;
+        }
+        sleep(Duration::from_millis(3)).await;
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break false;
+            }
+        }
+        let txids = conn.query_txids_those_inputs_contain_address(&address)?;
+        for txid in txids {
+            if !visited_txids.insert(txid.clone()) {
+                continue;
+            }
+            for input in conn.query_inputs(&txid)? {
+                if visited.contains(&input) {
+                    continue;
+                }
+                if visited.len() >= limits.max_addresses {
+                    // `address` is not marked, it's expanded again when the analysis resumes
+                    info!(
+                        "analysis of {seed_txid} stops at {} address(es)",
+                        visited.len()
+                    );
+                    break 'bfs false;
+                }
+                conn.add_analysis_frontier(seed_txid, &input, depth + 1)?;
+                report.saved += save_exchange_address(conn, &input, &txid, now);
+                visited.insert(input.clone());
+                queue.push_back((input, depth + 1));
+            }
+        }
+        conn.mark_analysis_frontier_expanded(seed_txid, &address)?;
+    };
+    report.visited = visited.len() as u64;
+    Ok(report)
+}
+
+/// Returns 1 when the address is saved, the address found by another analysis is kept and
+/// counted as rediscovered
+fn save_exchange_address(conn: &db::Conn, address: &str, txid: &str, now: u64) -> u64 {
+    match conn.add_analyzed_exchange_address_from_tx(address, txid, now) {
+        Ok(true) => {
+            info!("saved {} from tx {} into database", address, txid);
+            1
+        }
+        Ok(false) => {
+            debug!("{} from tx {} is saved already", address, txid);
+            0
+        }
+        Err(e) => {
+            warn!(
+                "append related address {} from tx {} is failed, reason: {}",
+                address, txid, e
+            );
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::DepcAmount;
+
+    /// `t1` spends the coins of `a` and `b`, `t2` spends `b` and `c`, `t3` spends `c` and `d`,
+    /// and `t4` spends `d` and `a` which closes the cycle
+    fn make_chain_conn() -> db::Conn {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let spends = [
+            ("t1", ["a", "b"]),
+            ("t2", ["b", "c"]),
+            ("t3", ["c", "d"]),
+            ("t4", ["d", "a"]),
+        ];
+        for (n, (txid, addresses)) in spends.iter().enumerate() {
+            for (vout, address) in addresses.iter().enumerate() {
+                let funding_txid = format!("f{n}");
+                conn.add_coin(
+                    &funding_txid,
+                    vout as u32,
+                    DepcAmount::from_value64(1000),
+                    address,
+                    "",
+                )
+                .unwrap();
+                conn.mark_coin_to_spent(&funding_txid, vout as u32, txid, 1)
+                    .unwrap();
+            }
+        }
+        conn
+    }
+
+    fn frontier(conn: &db::Conn) -> Vec<(String, u32)> {
+        conn.query_analysis_frontier("t1")
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.address, record.depth))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_expand_with_depth_and_cycle() {
+        let conn = make_chain_conn();
+        let exit_sig = Mutex::new(false);
+        let limits = ExpansionLimits {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let report = expand_exchange_addresses(&conn, "t1", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!(report.visited, 4);
+        assert!(report.complete);
+        assert_eq!(
+            frontier(&conn),
+            vec![
+                ("a".to_owned(), 0),
+                ("b".to_owned(), 0),
+                ("d".to_owned(), 1),
+                ("c".to_owned(), 1),
+            ]
+        );
+
+        // the cycle back to `a` is not visited again
+        let limits = ExpansionLimits {
+            max_depth: 5,
+            ..Default::default()
+        };
+        let report = expand_exchange_addresses(&conn, "t1", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!(report.visited, 4);
+        assert_eq!(report.saved, 0);
+        assert!(report.complete);
+        assert_eq!(conn.query_num_exchange_addresses().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_expand_resumes_after_limit() {
+        let conn = make_chain_conn();
+        let exit_sig = Mutex::new(false);
+        let limits = ExpansionLimits {
+            max_depth: 5,
+            max_addresses: 3,
+        };
+        let report = expand_exchange_addresses(&conn, "t1", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!(report.visited, 3);
+        assert!(!report.complete);
+
+        let limits = ExpansionLimits {
+            max_depth: 5,
+            max_addresses: 10,
+        };
+        let report = expand_exchange_addresses(&conn, "t1", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!(report.visited, 4);
+        assert_eq!(report.saved, 1);
+        assert!(report.complete);
+        assert!(conn
+            .query_analysis_frontier("t1")
+            .unwrap()
+            .iter()
+            .all(|record| record.expanded));
+    }
+
+    #[tokio::test]
+    async fn test_expand_counts_saved_addresses_only() {
+        let conn = make_chain_conn();
+        let exit_sig = Mutex::new(false);
+        let limits = ExpansionLimits {
+            max_depth: 0,
+            ..Default::default()
+        };
+        let report = expand_exchange_addresses(&conn, "t1", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!(report.saved, 2);
+        // `b` and `c` are saved by the other analyses, no failure and nothing saved
+        expand_exchange_addresses(&conn, "t3", limits, 1000, &exit_sig)
+            .await
+            .unwrap();
+        let report = expand_exchange_addresses(&conn, "t2", limits, 2000, &exit_sig)
+            .await
+            .unwrap();
+        assert_eq!((report.visited, report.saved), (2, 0));
+
+        let records = conn.query_exchange_addresses_after("", 10).unwrap();
+        let b = records.iter().find(|record| record.address == "b").unwrap();
+        assert_eq!((b.analyzed_txid.as_str(), b.first_seen), ("t1", Some(1000)));
+        assert_eq!(b.rediscoveries, 1);
+        let a = records.iter().find(|record| record.address == "a").unwrap();
+        assert_eq!(a.rediscoveries, 0);
+    }
+}
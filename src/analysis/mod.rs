@@ -0,0 +1,5 @@
+mod cluster;
+mod expansion;
+
+pub use cluster::*;
+pub use expansion::*;
@@ -1,11 +1,24 @@
 use clap::{Parser, Subcommand};
 
-use super::cmds::{Deploy, Run};
+#[cfg(feature = "testing")]
+use super::cmds::BenchSync;
+use super::cmds::{CatchUp, Deploy, InspectScript, Run, Status, TrialBalance};
 
 #[derive(Subcommand)]
 pub enum Commands {
     Run(Run),
     Deploy(Deploy),
+    /// Print the trial balance of the ledger of a bridge
+    TrialBalance(TrialBalance),
+    /// Print the syncing state and the data gaps of the index of a bridge
+    Status(Status),
+    /// Print the catch-up plan of a bridge after its downtime, or confirm it
+    CatchUp(CatchUp),
+    /// Decode the bridge payload of an OP_RETURN script, or tell where it cannot be decoded
+    InspectScript(InspectScript),
+    /// Measure the syncing throughput with synthetic DePC blocks
+    #[cfg(feature = "testing")]
+    BenchSync(BenchSync),
 }
 
 #[derive(Parser)]
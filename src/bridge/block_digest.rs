@@ -0,0 +1,99 @@
+use solana_sdk::hash::{hashv, Hash};
+
+use crate::amount::DepcAmount;
+
+/// An output of a block to a custody address or a deposit address of a pair, the bridge acts on
+/// it or records it as an inflow. The outputs recorded by an earlier scan are events as well
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEvent {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub value: DepcAmount,
+    /// The OP_RETURN data of a deposit or a withdrawal is in it
+    pub script_hex: String,
+}
+
+impl BlockEvent {
+    pub fn new(txid: &str, vout: u32, address: &str, value: DepcAmount, script_hex: &str) -> Self {
+        BlockEvent {
+            txid: txid.to_owned(),
+            vout,
+            address: address.to_owned(),
+            value,
+            script_hex: script_hex.to_owned(),
+        }
+    }
+
+    /// `{txid}:{vout}:{address}:{value in satoshis}:{script hex}`
+    pub fn data(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            self.txid,
+            self.vout,
+            self.address,
+            self.value.value64(),
+            self.script_hex
+        )
+    }
+}
+
+/// The digest of the events of the block `block_hash` at `height`, the SHA-256 of
+/// `{height}:{block_hash}\n` followed by the data of each event and `\n`, the events are in the
+/// order of the transactions in the block and of the outputs in them
+///
+/// A verifier extracting the events from the same block gets the same digest, it differs when
+/// an event is skipped or made up.
+pub fn digest_block_events(height: u32, block_hash: &str, events: &[BlockEvent]) -> Hash {
+    let mut data = format!("{height}:{block_hash}\n");
+    for event in events {
+        data.push_str(&event.data());
+        data.push('\n');
+    }
+    hashv(&[data.as_bytes()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_event(txid: &str, vout: u32, value64: u64) -> BlockEvent {
+        BlockEvent::new(
+            txid,
+            vout,
+            "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ",
+            DepcAmount::from_value64(value64),
+            "6a0474657374",
+        )
+    }
+
+    #[test]
+    fn test_digest_block_events() {
+        let events = vec![make_event("aa", 0, 500), make_event("bb", 1, 0)];
+        assert_eq!(
+            events[0].data(),
+            "aa:0:2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ:500:6a0474657374"
+        );
+        let digest = digest_block_events(100, "hash", &events);
+        let data = format!("100:hash\n{}\n{}\n", events[0].data(), events[1].data());
+        assert_eq!(digest, hashv(&[data.as_bytes()]));
+        assert_eq!(
+            hex::encode(digest_block_events(100, "hash", &[])),
+            "ab2a72c88673c019af14453726d6b6a8bd5126bcbf3a00361b656d5d11e534f3"
+        );
+
+        // an event skipped, made up, reordered or changed makes another digest
+        let others = vec![
+            events[..1].to_vec(),
+            vec![events[0].clone(), events[1].clone(), make_event("cc", 0, 1)],
+            vec![events[1].clone(), events[0].clone()],
+            vec![make_event("aa", 0, 501), events[1].clone()],
+        ];
+        for other in others {
+            assert_ne!(digest_block_events(100, "hash", &other), digest);
+        }
+        // so does another block
+        assert_ne!(digest_block_events(101, "hash", &events), digest);
+        assert_ne!(digest_block_events(100, "other", &events), digest);
+    }
+}
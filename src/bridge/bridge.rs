@@ -1,71 +1,311 @@
-use std::fmt;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
 
-use log::{error, info};
+use log::{debug, error, info, warn};
 use solana_sdk::signature::Signature;
 use tokio::{
-    sync::mpsc::{channel, Receiver, Sender},
-    time::{sleep, Duration},
+    sync::{
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        Mutex as AsyncMutex,
+    },
+    time::{sleep, timeout, Duration},
 };
 
+use super::{
+    check_transfer_limits, collect_multisig_signatures, digest_block_events, fee_bump_processing,
+    find_pending_fee_sweep, finish_catch_up_indexing, is_catching_up, parameter_value,
+    query_custody_coverage, recover_payout_bumps, rotate_custody_address, settle_fee_sweep,
+    start_catch_up, start_multisig_payout, BlockEvent, BridgeParameter, CatchUpConfig, CustodyEras,
+    FeeBumpConfig, FeeConfig, MultisigPayoutStart, TransferLimits, DEFAULT_CUSTODY_OVERLAP,
+    WITHDRAW_ORIGIN_OFFCHAIN, WITHDRAW_REQUEST_ACCEPTED, WITHDRAW_REQUEST_PENDING,
+    WITHDRAW_REQUEST_REJECTED,
+};
+use crate::amount::{AmountConverter, DepcAmount, TokenAmount};
+use crate::clock::{system_clock, SharedClock};
 use crate::db;
 use crate::depc::{
-    extract_string_from_script_hex, Address as DePCAddress, Client as DePCClient,
+    self, address_script_pubkey_hex, extract_string_from_script_hex, Address as DePCAddress,
+    Client as DePCClient, MultisigScript, DEFAULT_TX_FEE,
 };
-use crate::solana::TokenClient;
-const DEPOSIT_THRESHOLD: u64 = 1000;
-const WITHDRAW_THRESHOLD: u64 = 1000;
-pub struct WithdrawInfo {
-    sender_address: DePCAddress,
-    recipient_address: DePCAddress,
-    amount: u64,
-}
+use crate::ledger::Ledger;
+use crate::solana::{ErrorKind, TokenClient, TransferStatus};
+use crate::watch::{WatchChain, Watchlist};
+use crate::BridgeError;
+/// The interval to sync again after the block fails or no more block is found
+const SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// The transaction (or the bridge output) is dead-lettered after the retries
+const MAX_ITEM_RETRIES: u32 = 3;
+const ITEM_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// The indexed blocks are checked against the chain this deep for a reorg, the syncing stops
+/// when they differ below it
+const MAX_REORG_DEPTH: u32 = 100;
+/// The workers look for the pending actions in the database after waiting for a notification
+/// for this long, the notification is dropped when the channel is full
+const PENDING_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// The actions of table `pending_actions`
+pub const ACTION_DEPOSIT: &str = "deposit";
+pub const ACTION_WITHDRAW: &str = "withdraw";
+/// The reasons of table `custody_inflows`, why a payment to the custody address makes no action
+pub const INFLOW_NO_RECIPIENT: &str = "no_recipient";
+pub const INFLOW_BELOW_THRESHOLD: &str = "below_threshold";
+pub const INFLOW_DUPLICATE_OUTPUT: &str = "duplicate_output";
+pub const INFLOW_UNRECOGNIZED: &str = "unrecognized";
+/// The deposit is too small to be bridged and cannot be refunded, see
+/// `BridgeError::is_below_minimum`
+pub const INFLOW_BELOW_MINIMUM: &str = "below_minimum";
 
-pub struct DepositInfo<Address, Amount> {
-    sender_address: Address,
-    recipient_address: Address,
-    amount: Amount,
-}
 pub struct DepcScriptData<Address> {
     pub recipient: Address,
-    pub signature: Signature,
+    /// The Solana transactions transfer the tokens of a withdrawal, empty for a deposit
+    pub signatures: Vec<Signature>,
+}
+
+/// Sweeping the DePC exceeds the hot-wallet ceiling from the custody address to a cold address,
+/// the custody address of every pair is swept with the same config
+#[derive(Clone)]
+pub struct SweepConfig {
+    pub cold_address: DePCAddress,
+    pub hot_ceiling: DepcAmount,
+    /// Only make unsigned transactions, the operator signs and broadcasts them offline
+    pub unsigned_only: bool,
+    pub interval: Duration,
+}
+
+/// Returning the deposits can never be bridged (the recipient cannot be parsed) to an input
+/// address of the deposit transaction, the network fee is paid from the deposit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefundConfig {
+    /// The refund is made so long after the invalid deposit is found, the operator can still
+    /// bridge it by hand before that
+    pub delay: Duration,
+    pub interval: Duration,
 }
 
-#[derive(Debug)]
-pub enum Error {
-    General,
+/// What the bridge runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunMode {
+    /// Sync the chains and bridge the deposits and withdrawals
+    #[default]
+    Bridge,
+    /// Only sync the chain index, nothing is minted or paid out, so the service runs as an
+    /// explorer without the keys of the custody
+    IndexOnly,
 }
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+impl RunMode {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Error::General => Ok(error!("error{}","error"))
+            RunMode::Bridge => "bridge",
+            RunMode::IndexOnly => "index-only",
+        }
+    }
+}
+
+impl FromStr for RunMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bridge" => Ok(RunMode::Bridge),
+            "index-only" => Ok(RunMode::IndexOnly),
+            _ => Err(format!(
+                "unknown run mode `{s}`, it's either `bridge` or `index-only`"
+            )),
+        }
+    }
+}
+
+/// The tuning knobs of the bridge pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// The number of the tasks send the tokens for the deposits
+    pub deposit_workers: usize,
+    /// The number of the tasks make the DePC transfers for the withdrawals, the transfers are
+    /// all made from the custody address, more than 1 worker might pick the same coins
+    pub withdraw_workers: usize,
+    pub deposit_channel_capacity: usize,
+    pub withdraw_channel_capacity: usize,
+    /// The number of the blocks those are synced in one database transaction, they're fetched
+    /// into the memory before it opens
+    pub db_batch_size: u32,
+    /// The number of the deposits those are minted by one Solana transaction
+    pub mint_batch_size: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            deposit_workers: 1,
+            withdraw_workers: 1,
+            deposit_channel_capacity: 1,
+            withdraw_channel_capacity: 1,
+            db_batch_size: 1,
+            mint_batch_size: 1,
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub const MAX_WORKERS: usize = 64;
+    pub const MAX_CHANNEL_CAPACITY: usize = 65536;
+    pub const MAX_DB_BATCH_SIZE: u32 = 10000;
+    /// The transfers of more deposits don't fit in the size limit of a Solana transaction
+    pub const MAX_MINT_BATCH_SIZE: usize = 16;
+
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        let checks = [
+            ("deposit_workers", self.deposit_workers, Self::MAX_WORKERS),
+            ("withdraw_workers", self.withdraw_workers, Self::MAX_WORKERS),
+            (
+                "deposit_channel_capacity",
+                self.deposit_channel_capacity,
+                Self::MAX_CHANNEL_CAPACITY,
+            ),
+            (
+                "withdraw_channel_capacity",
+                self.withdraw_channel_capacity,
+                Self::MAX_CHANNEL_CAPACITY,
+            ),
+            (
+                "db_batch_size",
+                self.db_batch_size as usize,
+                Self::MAX_DB_BATCH_SIZE as usize,
+            ),
+            (
+                "mint_batch_size",
+                self.mint_batch_size,
+                Self::MAX_MINT_BATCH_SIZE,
+            ),
+        ];
+        for (name, value, max) in checks {
+            if value == 0 || value > max {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "`{name}` is {value}, it should be in 1..={max}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Retrying the mints failed for a transient reason (e.g. the Solana node is unreachable), the
+/// deposits are deferred in table `pending_actions`, so the retries survive the restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintRetryConfig {
+    /// The delay before the first retry, it's doubled by each failure after it
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// The deposit is dead-lettered after it fails so many times, it's retried until it's
+    /// minted if it's `None`
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for MintRetryConfig {
+    fn default() -> Self {
+        MintRetryConfig {
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(600),
+            max_attempts: None,
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl MintRetryConfig {
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        if self.base_delay.is_zero() || self.max_delay < self.base_delay {
+            return Err(BridgeError::InvalidConfig(format!(
+                "the mint retry delays are {:?} to {:?}, the base delay should be positive and \
+                 not more than the max one",
+                self.base_delay, self.max_delay
+            )));
+        }
+        if self.max_attempts == Some(0) {
+            return Err(BridgeError::InvalidConfig(
+                "the max mint attempts should be positive".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The delay before the retry after the deposit fails `attempts` times
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 1_u32
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+/// The receiver is shared by the workers, the worker holds the lock only while it's waiting
+pub type SharedReceiver<T> = Arc<AsyncMutex<Receiver<T>>>;
+
+/// The id of a row of table `pending_actions`, the payload is read from the database
+pub type PendingActionId = u64;
+
+/// A DePC custody address and the token it's bridged to, the rows of the pair are kept with the
+/// bridge id of `conn`
+#[derive(Clone)]
+pub struct BridgePair<C> {
+    pub conn: db::Conn,
+    /// The custody address of the current era, the payouts are made from it
+    pub depc_owner_address: DePCAddress,
+    pub solana_owner_address: String,
+    pub contract_client: C,
+    /// The custody addresses of all the eras, they're recorded when the bridge is run
+    pub custody_eras: CustodyEras,
+    /// Converting the amounts of the deposits and the withdrawals, the decimals of the mint
+    /// are queried when the bridge is run
+    pub converter: AmountConverter,
+    /// The bridge fees charged on the transfers, see `Bridge::set_fee_config`
+    pub fees: FeeConfig,
+    /// The limits of the transfers, see `Bridge::set_transfer_limits`
+    pub limits: TransferLimits,
+}
+
+/// A bridge pair the syncer processes the outputs to its custody address for, the workers of the
+/// pair are notified through the channels
+pub struct SyncPair<C> {
+    pub pair: BridgePair<C>,
+    pub tx_deposit: Sender<PendingActionId>,
+    pub tx_withdraw: Sender<PendingActionId>,
+}
 
 pub struct Bridge<C>
 where
     C: TokenClient,
 {
     exit_sig: Arc<Mutex<bool>>,
-    conn: db::Conn,
     depc_client: DePCClient,
-    depc_owner_address: DePCAddress,
-    solana_owner_address: String,
-    contract_client: C,
-    tx_deposit: Sender<DepositInfo<C::Address, C::Amount>>,
-    rx_deposit: Receiver<DepositInfo<C::Address, C::Amount>>,
-    tx_withdraw: Sender<WithdrawInfo>,
-    rx_withdraw: Receiver<WithdrawInfo>,
+    /// The first pair is the one made by `new`, the others are added by `add_pair`
+    pairs: Vec<BridgePair<C>>,
+    pipeline_config: PipelineConfig,
+    mint_retry_config: MintRetryConfig,
+    sweep_config: Option<SweepConfig>,
+    refund_config: Option<RefundConfig>,
+    fee_bump_config: Option<FeeBumpConfig>,
+    shadow: bool,
+    deposit_intent_window: Option<Duration>,
+    deposit_addresses: bool,
+    inflow_alert_amount: DepcAmount,
+    fee_config: FeeConfig,
+    transfer_limits: TransferLimits,
+    catch_up_config: Option<CatchUpConfig>,
+    custody_overlap: Duration,
+    /// The custody address of the first pair is the P2SH of the script, its payouts collect
+    /// the signatures of the co-signers
+    custody_multisig: Option<MultisigScript>,
+    depc_decimals: u8,
+    mode: RunMode,
+    clock: SharedClock,
 }
 
 impl<C> Bridge<C>
 where
-    C: TokenClient + 'static + Send + Clone,
+    C: TokenClient + 'static + Send + Sync + Clone,
 {
     pub fn new(
         conn: db::Conn,
@@ -74,64 +314,542 @@ where
         solana_owner_address: String,
         contract_client: C,
     ) -> Self {
-        let (tx_deposit, rx_deposit) = channel::<DepositInfo<C::Address, C::Amount>>(1);
-        let (tx_withdraw, rx_withdraw) = channel::<WithdrawInfo>(1);
         Bridge::<C> {
             exit_sig: Arc::new(Mutex::new(false)),
-            conn,
             depc_client,
+            pairs: vec![BridgePair {
+                conn,
+                depc_owner_address,
+                solana_owner_address,
+                contract_client,
+                custody_eras: CustodyEras::default(),
+                converter: AmountConverter::default(),
+                fees: FeeConfig::default(),
+                limits: TransferLimits::default(),
+            }],
+            pipeline_config: PipelineConfig::default(),
+            mint_retry_config: MintRetryConfig::default(),
+            sweep_config: None,
+            refund_config: None,
+            fee_bump_config: None,
+            shadow: false,
+            deposit_intent_window: None,
+            deposit_addresses: false,
+            inflow_alert_amount: DepcAmount::ZERO,
+            fee_config: FeeConfig::default(),
+            transfer_limits: TransferLimits::default(),
+            catch_up_config: None,
+            custody_overlap: DEFAULT_CUSTODY_OVERLAP,
+            custody_multisig: None,
+            depc_decimals: DepcAmount::DECIMALS,
+            mode: RunMode::Bridge,
+            clock: system_clock(),
+        }
+    }
+
+    /// Run another bridge instance in the same process, it shares the DePC node and the chain
+    /// index with the others, and its rows of the bridge tables are kept with `bridge_id`
+    pub fn add_pair(
+        mut self,
+        bridge_id: &str,
+        depc_owner_address: DePCAddress,
+        solana_owner_address: String,
+        contract_client: C,
+    ) -> Self {
+        let conn = self.pairs[0].conn.scoped(bridge_id);
+        self.pairs.push(BridgePair {
+            conn,
             depc_owner_address,
             solana_owner_address,
             contract_client,
-            tx_deposit,
-            rx_deposit,
-            tx_withdraw,
-            rx_withdraw,
+            custody_eras: CustodyEras::default(),
+            converter: AmountConverter::default(),
+            fees: FeeConfig::default(),
+            limits: TransferLimits::default(),
+        });
+        self
+    }
+
+    pub fn set_exit_sig(mut self, exit_sig: Arc<Mutex<bool>>) -> Self {
+        self.exit_sig = exit_sig;
+        self
+    }
+
+    pub fn set_pipeline_config(mut self, pipeline_config: PipelineConfig) -> Self {
+        self.pipeline_config = pipeline_config;
+        self
+    }
+
+    pub fn set_mint_retry_config(mut self, mint_retry_config: MintRetryConfig) -> Self {
+        self.mint_retry_config = mint_retry_config;
+        self
+    }
+
+    /// The custody address of the first pair is the P2SH address of `multisig`, it's checked
+    /// when the bridge is run
+    pub fn set_custody_multisig(mut self, multisig: MultisigScript) -> Self {
+        self.custody_multisig = Some(multisig);
+        self
+    }
+
+    pub fn set_sweep_config(mut self, sweep_config: SweepConfig) -> Self {
+        self.sweep_config = Some(sweep_config);
+        self
+    }
+
+    /// Without it the invalid deposits are only recorded to table `dead_letters`
+    pub fn set_refund_config(mut self, refund_config: RefundConfig) -> Self {
+        self.refund_config = Some(refund_config);
+        self
+    }
+
+    /// Bump the fees of the payouts left unconfirmed, the payouts of the multisig custody are
+    /// never bumped since the co-signers have to sign the bumps
+    pub fn set_fee_bump_config(mut self, fee_bump_config: FeeBumpConfig) -> Self {
+        self.fee_bump_config = Some(fee_bump_config);
+        self
+    }
+
+    /// In shadow mode the chains are synced as usual, but the mints, payouts and sweeps are
+    /// only recorded to table `shadow_actions` instead of being sent
+    pub fn set_shadow(mut self, shadow: bool) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Match the plain payments (without OP_RETURN) to the custody address with the deposit
+    /// intents of their senders, an intent is valid for `window` after it's registered
+    pub fn set_deposit_intent_window(mut self, window: Duration) -> Self {
+        self.deposit_intent_window = Some(window);
+        self
+    }
+
+    /// Credit the payments to the deposit addresses registered for the users besides the
+    /// custody addresses, the addresses are derived from the xpubs of the pairs (see
+    /// `set_deposit_xpub`)
+    pub fn set_deposit_addresses(mut self, enabled: bool) -> Self {
+        self.deposit_addresses = enabled;
+        self
+    }
+
+    /// The payments to the custody addresses which make no bridge action are recorded as the
+    /// unclassified inflows, the ones of `amount` or more are alerted as errors instead of
+    /// warnings
+    pub fn set_inflow_alert_amount(mut self, amount: DepcAmount) -> Self {
+        self.inflow_alert_amount = amount;
+        self
+    }
+
+    /// The bridge fees of every pair, a fee is kept from the amount of the deposit before it's
+    /// minted and from the withdrawal before it's paid out
+    pub fn set_fee_config(mut self, fee_config: FeeConfig) -> Self {
+        self.fee_config = fee_config;
+        self
+    }
+
+    /// The limits of the deposits and the withdrawals of every pair, the ones over the limits
+    /// are held until an admin releases them
+    pub fn set_transfer_limits(mut self, transfer_limits: TransferLimits) -> Self {
+        self.transfer_limits = transfer_limits;
+        self
+    }
+
+    /// Catch up after the downtime instead of acting on the blocks while syncing them, see
+    /// `CatchUpConfig`
+    pub fn set_catch_up_config(mut self, catch_up_config: CatchUpConfig) -> Self {
+        self.catch_up_config = Some(catch_up_config);
+        self
+    }
+
+    /// The old custody address of a pair is still watched for `overlap` after it's rotated, see
+    /// `rotate_custody_address`
+    pub fn set_custody_overlap(mut self, overlap: Duration) -> Self {
+        self.custody_overlap = overlap;
+        self
+    }
+
+    /// The decimals of the DePC amounts (value64), the amounts are converted to the tokens with
+    /// the decimals of the mints, see `AmountConverter`
+    pub fn set_depc_decimals(mut self, decimals: u8) -> Self {
+        self.depc_decimals = decimals;
+        self
+    }
+
+    /// In `RunMode::IndexOnly` only the chain index is synced, the outputs to the custody
+    /// addresses are not processed and no worker is run
+    pub fn set_mode(mut self, mode: RunMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The bridge ids and the custody addresses of the pairs must be unique, an output is
+    /// bridged by the only pair of its address
+    fn validate_pairs(&self) -> Result<(), BridgeError> {
+        let mut bridge_ids = HashSet::new();
+        let mut owner_addresses = HashSet::new();
+        for pair in self.pairs.iter() {
+            let bridge_id = pair.conn.bridge_id();
+            if !bridge_ids.insert(bridge_id) {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "bridge id `{bridge_id}` is used by more than 1 pair"
+                )));
+            }
+            if !owner_addresses.insert(pair.depc_owner_address.as_str()) {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "custody address `{}` is used by more than 1 pair",
+                    pair.depc_owner_address
+                )));
+            }
+        }
+        if let Some(multisig) = self.custody_multisig.as_ref() {
+            let address = &self.pairs[0].depc_owner_address;
+            if !multisig.is_address(address) {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "custody address `{address}` is not the P2SH address of the {multisig} script"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the custody eras of the pairs, a new era is started for a rotated custody address.
+    /// An address of any era is never the custody address of another pair
+    fn rotate_custody_addresses(&mut self) -> Result<(), BridgeError> {
+        for pair in self.pairs.iter_mut() {
+            pair.custody_eras = rotate_custody_address(
+                &pair.conn,
+                &pair.depc_owner_address,
+                self.custody_overlap,
+                self.clock.now(),
+            )?;
+        }
+        // the addresses of a pair are never rotated back, they're unique within the pair
+        let mut addresses = HashSet::new();
+        for pair in self.pairs.iter() {
+            for era in pair.custody_eras.eras() {
+                if !addresses.insert(era.address.as_str()) {
+                    return Err(BridgeError::InvalidConfig(format!(
+                        "custody address `{}` is used by more than 1 pair",
+                        era.address
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the converters of the pairs with the decimals of their mints queried from the chain
+    fn make_converters(&mut self) -> Result<(), BridgeError> {
+        for pair in self.pairs.iter_mut() {
+            let mint_decimals = pair
+                .contract_client
+                .mint_decimals()
+                .map_err(BridgeError::token_client::<C>)?;
+            let Some(converter) = AmountConverter::new(self.depc_decimals, mint_decimals) else {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "cannot convert {} decimals of DePC to {mint_decimals} decimals of the mint",
+                    self.depc_decimals
+                )));
+            };
+            pair.converter = converter;
+            let unit = pair.converter.depc_unit();
+            if unit.value64() > 1 {
+                warn!(
+                    "the mint of bridge {} has less decimals than DePC, the rest of the \
+                     deposits under {unit} value64 is kept as the bridge fee",
+                    pair.conn.bridge_id()
+                );
+            }
+            info!(
+                "bridge {} converts {}",
+                pair.conn.bridge_id(),
+                pair.converter
+            );
+        }
+        Ok(())
+    }
+
+    /// Sync the blocks to the chain index without any pair, so nothing is recorded to the tables
+    /// of the bridges and the token clients are never called
+    async fn run_index_only(self) -> Result<(), BridgeError> {
+        info!("index-only mode, the blocks are indexed but nothing is bridged");
+        let local_db = self.pairs[0].conn.clone();
+        run_depc_syncing::<C>(
+            self.exit_sig,
+            local_db,
+            self.depc_client,
+            vec![],
+            self.pipeline_config.db_batch_size,
+            None,
+            false,
+            false,
+            DepcAmount::ZERO,
+            None,
+            self.clock,
+        )
+        .await
+    }
+
+    /// The custody addresses must be valid for the network of the DePC node, and the syncing
+    /// waits until the node finishes the initial block download
+    ///
+    /// Returns `false` if the bridge exits while waiting
+    async fn check_depc_node(&self) -> Result<bool, BridgeError> {
+        let network_info = self.depc_client.get_network_info()?;
+        info!(
+            "DePC node {} (protocol {}) with {} peers",
+            network_info.subversion, network_info.protocol_version, network_info.connections
+        );
+        for pair in self.pairs.iter() {
+            if !self
+                .depc_client
+                .validate_address(&pair.depc_owner_address)?
+                .is_valid
+            {
+                error!(
+                    "custody address {} of bridge {} is invalid for the network of the DePC node",
+                    pair.depc_owner_address,
+                    pair.conn.bridge_id()
+                );
+                return Err(BridgeError::InvalidAddress(pair.depc_owner_address.clone()));
+            }
+        }
+        loop {
+            let blockchain_info = self.depc_client.get_blockchain_info()?;
+            if !blockchain_info.initial_block_download {
+                info!(
+                    "DePC node is on chain {} at height {}",
+                    blockchain_info.chain, blockchain_info.blocks
+                );
+                return Ok(true);
+            }
+            info!(
+                "DePC node is in the initial block download, {}/{} blocks, wait for it",
+                blockchain_info.blocks, blockchain_info.headers
+            );
+            sleep(SYNC_RETRY_INTERVAL).await;
+            if *self.exit_sig.lock().unwrap() {
+                return Ok(false);
+            }
         }
     }
 
-    pub async fn run(self) -> Result<(), Error> {
+    pub async fn run(mut self) -> Result<(), BridgeError> {
+        self.pipeline_config.validate()?;
+        self.fee_config.validate()?;
+        self.transfer_limits.validate()?;
+        if let Some(fee_bump_config) = self.fee_bump_config.as_ref() {
+            fee_bump_config.validate()?;
+        }
+        self.validate_pairs()?;
+        if !self.check_depc_node().await? {
+            return Ok(());
+        }
+        if self.mode == RunMode::IndexOnly {
+            return self.run_index_only().await;
+        }
+        self.rotate_custody_addresses()?;
+        self.make_converters()?;
+        if let Some(catch_up_config) = self.catch_up_config.as_ref() {
+            let chain_height = self.depc_client.get_height()?;
+            for pair in self.pairs.iter() {
+                start_catch_up(&pair.conn, catch_up_config, chain_height, self.clock.now())?;
+            }
+        }
         let mut tasks = vec![];
+        let mut sync_pairs = vec![];
+        for (index, mut pair) in self.pairs.into_iter().enumerate() {
+            let bridge_id = pair.conn.bridge_id().to_owned();
+            pair.fees = self.fee_config;
+            pair.limits = self.transfer_limits;
+            if !pair.fees.deposit.is_free() || !pair.fees.withdraw.is_free() {
+                info!(
+                    "bridge {bridge_id} charges {} on the deposits and {} on the withdrawals",
+                    pair.fees.deposit, pair.fees.withdraw
+                );
+            }
+            // the deposits minted by the transfers of the last run are done before the claimed
+            // ones are released
+            recover_mints(
+                &pair.contract_client,
+                &pair.conn,
+                &pair.converter,
+                &self.clock,
+            )?;
+            // so are the withdrawals paid by the last run, the ones in doubt are left claimed
+            recover_payouts(&pair.conn, &self.depc_client, &self.clock)?;
+            recover_payout_bumps(&pair.conn, &self.depc_client, &self.clock)?;
+            // the process stopped while the workers were processing them, nothing is sent for
+            // them yet
+            let released = pair.conn.release_claimed_pending_actions()?;
+            if released > 0 {
+                warn!(
+                    "{released} pending actions of bridge {bridge_id} are left unfinished by the \
+                     last run, retry them"
+                );
+            }
+            let (tx_deposit, rx_deposit) =
+                channel::<PendingActionId>(self.pipeline_config.deposit_channel_capacity);
+            let (tx_withdraw, rx_withdraw) =
+                channel::<PendingActionId>(self.pipeline_config.withdraw_channel_capacity);
+            let rx_deposit = Arc::new(AsyncMutex::new(rx_deposit));
+            let rx_withdraw = Arc::new(AsyncMutex::new(rx_withdraw));
 
-        let withdraw_making_task = tokio::spawn(withdraw_processing(
-            Arc::clone(&self.exit_sig),
-            self.rx_withdraw,
-            self.depc_owner_address.clone(),
-            self.depc_client.clone(),
-        ));
-        tasks.push(withdraw_making_task);
+            let multisig = match index {
+                0 => self.custody_multisig.clone(),
+                _ => None,
+            };
+            // the multisig payouts are made one by one
+            let withdraw_workers = match multisig {
+                Some(_) => 1,
+                None => self.pipeline_config.withdraw_workers,
+            };
+            for _ in 0..withdraw_workers {
+                let withdraw_making_task = tokio::spawn(withdraw_processing(
+                    Arc::clone(&self.exit_sig),
+                    Arc::clone(&rx_withdraw),
+                    pair.depc_owner_address.clone(),
+                    self.depc_client.clone(),
+                    pair.conn.clone(),
+                    multisig.clone(),
+                    self.shadow,
+                    Arc::clone(&self.clock),
+                ));
+                tasks.push(withdraw_making_task);
+            }
 
-        let deposit_making_task = tokio::spawn(deposit_processing(
-            Arc::clone(&self.exit_sig),
-            self.rx_deposit,
-            self.contract_client.clone(),
-            self.conn.clone(),
-        ));
-        tasks.push(deposit_making_task);
+            for _ in 0..self.pipeline_config.deposit_workers {
+                let deposit_making_task = tokio::spawn(deposit_processing(
+                    Arc::clone(&self.exit_sig),
+                    Arc::clone(&rx_deposit),
+                    pair.contract_client.clone(),
+                    pair.depc_owner_address.clone(),
+                    pair.conn.clone(),
+                    pair.converter,
+                    self.pipeline_config.mint_batch_size,
+                    self.mint_retry_config,
+                    self.shadow,
+                    Arc::clone(&self.clock),
+                ));
+                tasks.push(deposit_making_task);
+            }
+
+            if let Some(sweep_config) = self.sweep_config.as_ref() {
+                let sweep_task = tokio::spawn(sweep_processing(
+                    Arc::clone(&self.exit_sig),
+                    pair.conn.clone(),
+                    self.depc_client.clone(),
+                    pair.depc_owner_address.clone(),
+                    sweep_config.clone(),
+                    self.shadow,
+                    Arc::clone(&self.clock),
+                ));
+                tasks.push(sweep_task);
+            }
+
+            // nothing is paid out in shadow mode
+            if let Some(fee_bump_config) = self.fee_bump_config {
+                if multisig.is_none() && !self.shadow {
+                    let fee_bump_task = tokio::spawn(fee_bump_processing(
+                        Arc::clone(&self.exit_sig),
+                        pair.conn.clone(),
+                        self.depc_client.clone(),
+                        pair.depc_owner_address.clone(),
+                        fee_bump_config,
+                        Arc::clone(&self.clock),
+                    ));
+                    tasks.push(fee_bump_task);
+                }
+            }
 
+            if let Some(refund_config) = self.refund_config {
+                let refund_task = tokio::spawn(refund_processing(
+                    Arc::clone(&self.exit_sig),
+                    pair.conn.clone(),
+                    self.depc_client.clone(),
+                    pair.depc_owner_address.clone(),
+                    refund_config,
+                    self.shadow,
+                    Arc::clone(&self.clock),
+                ));
+                tasks.push(refund_task);
+            }
+
+            sync_pairs.push(SyncPair {
+                pair,
+                tx_deposit,
+                tx_withdraw,
+            });
+        }
+
+        // the chain index is shared, the blocks are synced once for all the pairs
+        let local_db = sync_pairs[0].pair.conn.clone();
         let depc_syncing_task = tokio::spawn(run_depc_syncing::<C>(
             Arc::clone(&self.exit_sig),
-            self.conn.clone(),
+            local_db,
             self.depc_client,
-            self.contract_client,
-            self.depc_owner_address,
-            self.solana_owner_address,
-            self.tx_deposit,
-            self.tx_withdraw,
+            sync_pairs,
+            self.pipeline_config.db_batch_size,
+            self.deposit_intent_window,
+            self.deposit_addresses,
+            self.refund_config.is_some(),
+            self.inflow_alert_amount,
+            self.catch_up_config,
+            Arc::clone(&self.clock),
         ));
         tasks.push(depc_syncing_task);
 
-        futures::future::join_all(tasks).await;
-        Ok(())
+        let mut result = Ok(());
+        for res in futures::future::join_all(tasks).await {
+            match res {
+                Ok(Err(e)) => {
+                    error!("bridge task exits with error: {}", e.full_message());
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+                Err(e) => error!("bridge task is aborted, reason: {e}"),
+                Ok(Ok(())) => {}
+            }
+        }
+        result
+    }
+}
+
+/// Wait for the id of a new pending action, or return the unclaimed actions from the database
+/// when nothing is received for `PENDING_POLL_INTERVAL`
+async fn next_pending_actions(
+    rx: &SharedReceiver<PendingActionId>,
+    conn: &db::Conn,
+    action: &str,
+) -> Result<Vec<PendingActionId>, BridgeError> {
+    let received = timeout(PENDING_POLL_INTERVAL, async {
+        rx.lock().await.recv().await
+    })
+    .await;
+    match received {
+        Ok(Some(id)) => Ok(vec![id]),
+        // timed out, or the syncing task is gone
+        Ok(None) | Err(_) => Ok(conn.query_unclaimed_pending_action_ids(action)?),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn withdraw_processing(
     exit_sig: Arc<Mutex<bool>>,
-    mut rx_withdraw: Receiver<WithdrawInfo>,
+    rx_withdraw: SharedReceiver<PendingActionId>,
     depc_owner_address: DePCAddress,
     depc_client: DePCClient,
-) -> Result<(), Error> {
+    conn: db::Conn,
+    multisig: Option<MultisigScript>,
+    shadow: bool,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
     loop {
         {
             let exit = exit_sig.lock().unwrap();
@@ -139,33 +857,194 @@ pub async fn withdraw_processing(
                 break;
             }
         }
-        if let Some(withdraw) = rx_withdraw.recv().await {
-            let res = depc_client.transfer(
+        if is_catching_up(&conn)? {
+            sleep(PENDING_POLL_INTERVAL).await;
+            continue;
+        }
+        if multisig.is_some() && !shadow {
+            match collect_multisig_signatures(&conn, &depc_client, clock.now()) {
+                Ok(sent) => {
+                    for (payout_id, txid) in sent {
+                        info!("multisig payout {payout_id} is broadcast, txid {txid}");
+                    }
+                }
+                Err(e) => warn!(
+                    "cannot collect the signatures of the multisig payouts, reason: {}",
+                    e.full_message()
+                ),
+            }
+        }
+        for id in next_pending_actions(&rx_withdraw, &conn, ACTION_WITHDRAW).await? {
+            let Some(withdraw) = conn.claim_pending_action(id, clock.now())? else {
+                continue;
+            };
+            if shadow {
+                record_shadow_action(
+                    &conn,
+                    clock.now(),
+                    "payout",
+                    &withdraw.source_txid,
+                    &withdraw.recipient,
+                    withdraw.amount,
+                );
+                conn.complete_pending_action(id, None, clock.now())?;
+                continue;
+            }
+            if let Some(multisig) = multisig.as_ref() {
+                match start_multisig_payout(
+                    &conn,
+                    &depc_client,
+                    &depc_owner_address,
+                    multisig,
+                    &withdraw,
+                    clock.now(),
+                ) {
+                    // the withdrawal is left claimed until the payout is broadcast
+                    Ok(MultisigPayoutStart::Collecting(payout_id)) => debug!(
+                        "withdrawal {} to {} is collecting the signatures, payout {payout_id}",
+                        withdraw.amount, withdraw.recipient
+                    ),
+                    Ok(MultisigPayoutStart::Sent(_)) => {}
+                    Ok(MultisigPayoutStart::Waiting) => conn.release_pending_action(id)?,
+                    Err(e) => {
+                        error!(
+                            "cannot make multisig payout {} to {}, retry later, reason: {}",
+                            withdraw.amount, withdraw.recipient, e
+                        );
+                        conn.release_pending_action(id)?;
+                    }
+                }
+                continue;
+            }
+            // the payout is saved before it's sent, the coins are not picked by the others
+            // until it's broadcast
+            let _spending = depc_client.lock_spending();
+            let (txid, signed_hex) = match depc_client.make_signed_transfer(
                 &depc_owner_address,
-                &withdraw.recipient_address,
-                withdraw.amount,
-            );
-            if res.is_err() {
-                return Err(Error::General);
+                &withdraw.recipient,
+                withdraw.amount.value64(),
+            ) {
+                Ok(signed) => signed,
+                Err(e) => {
+                    error!(
+                        "cannot make withdrawal {} to {}, retry later, reason: {}",
+                        withdraw.amount, withdraw.recipient, e
+                    );
+                    conn.release_pending_action(id)?;
+                    continue;
+                }
+            };
+            let payout_id = conn.add_depc_payout(id, &txid, &signed_hex, clock.now())?;
+            match depc_client.send_raw_transaction(&signed_hex) {
+                Ok(_) => {
+                    // TODO verify the transaction is confirmed
+                    complete_payout(&conn, payout_id, &withdraw, &txid, clock.now())?;
+                    info!(
+                        "withdrawal {} to {} is made, txid {}",
+                        withdraw.amount, withdraw.recipient, txid
+                    );
+                }
+                // the transaction might be broadcast already, the withdrawal is left claimed
+                // and the payout is reconciled on the next start, see `recover_payouts`
+                Err(e) => error!(
+                    "withdrawal {} to {} is in doubt, payout {txid} is checked on the next \
+                     start, reason: {e}",
+                    withdraw.amount, withdraw.recipient
+                ),
             }
-            let _txid = res.unwrap();
-            todo!() // TODO The transaction is processed, we might need to record it to local
-            // database and verify it
         }
         sleep(Duration::from_secs(1)).await;
     }
     Ok(())
 }
 
+/// The withdrawal is done by the payout sent, the ledger posts it under the txid
+fn complete_payout(
+    conn: &db::Conn,
+    payout_id: u64,
+    withdraw: &db::PendingActionRecord,
+    txid: &str,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    conn.complete_depc_payout(payout_id, timestamp)?;
+    conn.complete_pending_action(withdraw.id, Some(txid), timestamp)?;
+    Ledger::new(conn.clone()).payout(
+        txid,
+        withdraw.amount,
+        DepcAmount::from_value64(DEFAULT_TX_FEE),
+        timestamp,
+    )?;
+    Ok(())
+}
+
+/// The payouts signed by the last run but not known to be sent, the process stopped or the
+/// node failed to take them
+///
+/// The payout the node knows is sent, its withdrawal is done. The others are sent again, the
+/// same transaction never pays twice, and the withdrawal of the one the node refuses is left
+/// claimed for the operator.
+fn recover_payouts(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    clock: &SharedClock,
+) -> Result<(), BridgeError> {
+    for payout in conn.query_signed_depc_payouts()? {
+        let Some(withdraw) = conn.query_pending_action(payout.action_id)? else {
+            error!(
+                "payout {} of the last run pays no pending action {}, the operator should \
+                 check it",
+                payout.txid, payout.action_id
+            );
+            continue;
+        };
+        let sent = match depc_client.get_transaction(&payout.txid) {
+            Ok(_) => true,
+            Err(_) => match depc_client.send_raw_transaction(&payout.raw_tx) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!(
+                        "payout {} of withdrawal {} to {} cannot be sent again, the withdrawal \
+                         is left claimed, the operator should check it, reason: {e}",
+                        payout.txid, withdraw.source_txid, withdraw.recipient
+                    );
+                    false
+                }
+            },
+        };
+        if sent {
+            warn!(
+                "payout {} of withdrawal {} to {} of the last run is sent",
+                payout.txid, withdraw.source_txid, withdraw.recipient
+            );
+            complete_payout(conn, payout.id, &withdraw, &payout.txid, clock.now())?;
+        }
+    }
+    Ok(())
+}
+
+/// The deposits are minted only when the custody DePC in the index covers them, see
+/// `CustodyCoverage`, the minting is paused until it does
+///
+/// The deposits received together are minted by one Solana transaction, up to
+/// `mint_batch_size` of them, see `mint_deposits`. The failed ones are retried by
+/// `retry_config`.
+#[allow(clippy::too_many_arguments)]
 pub async fn deposit_processing<C>(
     exit_sig: Arc<Mutex<bool>>,
-    mut rx_deposit: Receiver<DepositInfo<C::Address, C::Amount>>,
+    rx_deposit: SharedReceiver<PendingActionId>,
     contract_client: C,
+    depc_owner_address: DePCAddress,
     conn: db::Conn,
-) -> Result<(), Error>
+    converter: AmountConverter,
+    mint_batch_size: usize,
+    retry_config: MintRetryConfig,
+    shadow: bool,
+    clock: SharedClock,
+) -> Result<(), BridgeError>
 where
     C: TokenClient,
 {
+    let mut paused = false;
     loop {
         {
             let exit = exit_sig.lock().unwrap();
@@ -173,199 +1052,4472 @@ where
                 break;
             }
         }
-        if let Some(deposit) = rx_deposit.recv().await {
-            match contract_client.send_token(&deposit.recipient_address, deposit.amount) {
-                Ok(txid) => {
-                    // update database
-                    conn.confirm_deposit(&txid.to_string(), get_curr_timestamp(), "")
-                        .unwrap();
-                }
-                Err(e) => {
+        if is_catching_up(&conn)? {
+            sleep(PENDING_POLL_INTERVAL).await;
+            continue;
+        }
+        let mut batch: Vec<MintItem<C>> = vec![];
+        for id in next_pending_actions(&rx_deposit, &conn, ACTION_DEPOSIT).await? {
+            let Some(deposit) = conn.claim_pending_action(id, clock.now())? else {
+                continue;
+            };
+            if shadow {
+                record_shadow_action(
+                    &conn,
+                    clock.now(),
+                    "mint",
+                    &deposit.source_txid,
+                    &deposit.recipient,
+                    deposit.amount,
+                );
+                conn.complete_pending_action(id, None, clock.now())?;
+                continue;
+            }
+            // the deposits of the batch are not minted yet, they're counted in as well
+            let batched = batch
+                .iter()
+                .try_fold(deposit.amount, |total, item| {
+                    total.checked_add(item.deposit.amount)
+                })
+                .ok_or(BridgeError::AmountOverflow)?;
+            let coverage = query_custody_coverage(&conn, &depc_owner_address)?;
+            if let Some(shortfall) = coverage.shortfall(batched)? {
+                if !paused {
                     error!(
-                        "cannot send transaction to solana to make deposit, reason: {}",
-                        e
+                        "minting is paused, the custody lacks {shortfall} to back the wrapped \
+                         supply {} with deposit {} of {}, {coverage:?}, check the syncing of \
+                         the index",
+                        coverage.wrapped_supply, deposit.source_txid, deposit.amount
                     );
+                    paused = true;
+                }
+                conn.release_pending_action(id)?;
+                continue;
+            }
+            if paused {
+                info!("minting is resumed, the custody covers the wrapped supply");
+                paused = false;
+            }
+            // both are checked while syncing, the deposit of the recipient the token client
+            // refuses is never minted
+            let recipient_address = match parse_address::<C>(&deposit.recipient) {
+                Ok(recipient_address) => recipient_address,
+                Err(e) => {
+                    dead_letter_deposit(&conn, &deposit, &e, clock.now())?;
+                    continue;
                 }
+            };
+            // the amounts are rounded to the unit while syncing
+            let Some(token_amount) = converter.to_token(deposit.amount) else {
+                dead_letter_deposit(&conn, &deposit, &BridgeError::AmountOverflow, clock.now())?;
+                continue;
+            };
+            batch.push(MintItem {
+                id,
+                deposit,
+                recipient_address,
+                amount: token_amount.raw().into(),
+            });
+            if batch.len() >= mint_batch_size {
+                let batch = std::mem::take(&mut batch);
+                mint_deposits(&contract_client, &conn, batch, &retry_config, &clock)?;
             }
         }
+        if !batch.is_empty() {
+            mint_deposits(&contract_client, &conn, batch, &retry_config, &clock)?;
+        }
         sleep(Duration::from_secs(1)).await;
     }
     Ok(())
 }
 
-pub async fn run_depc_syncing<C>(
-    exit_sig: Arc<Mutex<bool>>,
-    local_db: db::Conn,
-    depc_client: DePCClient,
-    contract_client: C,
-    depc_owner_address: DePCAddress,
-    solana_owner_address: String,
-    tx_deposit: Sender<DepositInfo<C::Address, C::Amount>>,
-    tx_withdraw: Sender<WithdrawInfo>, // TODO matthew: deliver the withdrawal to this channel
-) -> Result<(), Error>
+/// A claimed deposit waits to be minted with the others of its batch
+struct MintItem<C: TokenClient> {
+    id: PendingActionId,
+    deposit: db::PendingActionRecord,
+    recipient_address: C::Address,
+    amount: C::Amount,
+}
+
+/// Mint the batch by one Solana transaction, the deposits are confirmed together and the
+/// ledger posts the total of the batch under the signature, so neither the deposits nor the
+/// ledger ever hold a part of the batch. The batch is deferred for a retry by `retry_config`
+/// when the transaction cannot be sent, unless the token client tells it never can be: the
+/// deposits of such a batch are minted one by one, and the one refused alone is dead-lettered.
+/// The deposit failed `max_attempts` times is dead-lettered too. The batch of a transaction in
+/// doubt is left claimed, it's recovered on the next start, see `recover_mints`.
+fn mint_deposits<C>(
+    contract_client: &C,
+    conn: &db::Conn,
+    batch: Vec<MintItem<C>>,
+    retry_config: &MintRetryConfig,
+    clock: &SharedClock,
+) -> Result<(), BridgeError>
 where
-    C: TokenClient + Send + 'static,
-    C::Error: Send + 'static,
+    C: TokenClient,
 {
-    //TODO:1. As shown in Figure 4, a separate table (height(height int)) should be used to record the block height when scanning blocks; otherwise, as the data increases later, it may cause the system to freeze. As shown in Figure 5, the processed height should be written back to the database.
-    let mut sync_height = if let Some(height) = local_db.query_best_height() {
-        height + 1
+    let recipients: Vec<_> = batch
+        .iter()
+        .map(|item| (item.recipient_address.clone(), item.amount.clone()))
+        .collect();
+    let res = if let [(recipient_address, amount)] = recipients.as_slice() {
+        contract_client.send_token(recipient_address, amount.clone())
     } else {
-        0
+        contract_client.send_tokens(&recipients)
     };
-    local_db.begin_transaction().unwrap();
-
-    loop {
-        {
-            let exit = exit_sig.lock().unwrap();
-            if *exit {
-                break;
+    let receipt = match res {
+        Ok(receipt) => receipt,
+        Err(e) if C::error_kind(&e) == ErrorKind::InDoubt => {
+            error!(
+                "the transaction to mint {} deposit(s) might be made, they're left claimed until \
+                 it's recovered on the next start, reason: {e}",
+                batch.len()
+            );
+            return Ok(());
+        }
+        Err(e) if C::error_kind(&e) != ErrorKind::Permanent => {
+            error!(
+                "cannot send transaction to solana to mint {} deposit(s), retry later, reason: {}",
+                batch.len(),
+                e
+            );
+            let reason = BridgeError::token_client::<C>(e);
+            for item in batch.iter() {
+                let attempts = conn
+                    .query_pending_action_attempts(item.id)?
+                    .saturating_add(1);
+                if retry_config
+                    .max_attempts
+                    .is_some_and(|max_attempts| attempts >= max_attempts)
+                {
+                    dead_letter_deposit(conn, &item.deposit, &reason, clock.now())?;
+                    continue;
+                }
+                let delay = retry_config.delay(attempts);
+                debug!(
+                    "deposit {} failed {attempts} time(s), retry in {}s",
+                    item.deposit.source_txid,
+                    delay.as_secs()
+                );
+                conn.defer_pending_action(item.id, attempts, clock.now() + delay.as_secs())?;
             }
+            return Ok(());
         }
-        let chain_height = depc_client.get_height().unwrap();
-        if sync_height > chain_height {
-            // there is no more block left to sync, wait for 5 seconds...
-            sleep(Duration::from_secs(5)).await;
-            continue;
+        Err(e) if batch.len() > 1 => {
+            warn!(
+                "the transaction to mint {} deposits is refused, minting them one by one, \
+                 reason: {e}",
+                batch.len()
+            );
+            for item in batch {
+                mint_deposits(contract_client, conn, vec![item], retry_config, clock)?;
+            }
+            return Ok(());
         }
-        info!(
-            "syncing from height {sync_height} to chain height {chain_height}, distance {}",
-            chain_height - sync_height
-        );
-
-        // block
-        let block_hash = depc_client.get_block_hash(sync_height).unwrap();
-        let block = depc_client.get_block(&block_hash).unwrap();
-        assert_eq!(block.height, sync_height);
-        local_db
-            .add_block(&block.hash, sync_height, &block.miner, block.time)
-            .unwrap();
-
-        if sync_height > 0 {
-            // transactions
-            for txid in block.tx.iter() {
-                let transaction = depc_client.get_transaction(txid).unwrap();
-                // information should be
-                // extracted from txouts
-                assert_eq!(transaction.txid, *txid);
-                local_db.add_transaction(&block_hash, txid).unwrap();
-                for txin in transaction.vin.iter() {
-                    if !txin.is_coinbase() {
-                        // TODO maybe we need to check the validity of the txin?
-                        local_db
-                            .mark_coin_to_spent(
-                                &txin.txid.clone().unwrap(),
-                                txin.vout.unwrap(),
-                                txid,
-                                sync_height,
-                            )
-                            .unwrap();
-                    }
-                }
-                for txout in transaction.vout.iter() {
-                    // save the txout anyway
-                    if let Some(address) = txout.get_address() {
-                        local_db
-                            .add_coin(
-                                txid,
-                                txout.n,
-                                txout.value64,
-                                &address,
-                                &txout.script_pubkey.hex,
-                            )
-                            .unwrap();
-                        // is our address,start processing
-                        if address == depc_owner_address {
-                            if let Ok(script_data) =
-                                extract_string_from_script_hex(&txout.script_pubkey.hex)
-                            {
-                                //TODO:2. As shown in Figure 6, a new table called recorded_transactions can be created to record the processed transactions that meet the criteria, and a check should be performed before each processing to prevent duplicate handling.
-                                if txout.value64 > DEPOSIT_THRESHOLD && script_data.recipient != ""
-                                {
-                                    //deposit
-                                    local_db
-                                        .save_deposit(
-                                            txid,
-                                            &script_data.recipient,
-                                            txout.value64,
-                                            block.time,
-                                        )
-                                        .unwrap();
-                                    let sender_address =
-                                        C::Address::from_str(&*solana_owner_address)
-                                            .unwrap_or_else(|_| {
-                                                panic!("invalid address");
-                                            });
-                                    let recipient_address =
-                                        C::Address::from_str(&script_data.recipient)
-                                            .unwrap_or_else(|_| {
-                                                panic!("invalid address");
-                                            });
-                                    tx_deposit          //send deposit info to the channel
-                                        .send(DepositInfo::<C::Address, C::Amount> {
-                                            sender_address,
-                                            recipient_address,
-                                            amount: txout.value64.into(),
-                                        })
-                                        .await
-                                        .unwrap();
-                                }
-                                //withdraw
-                                else if txout.value64 == 0
-                                    && script_data.recipient != ""
-                                    && script_data.signature != "".parse().unwrap()
-                                {
-                                    let res = C::Address::from_str(&solana_owner_address);
-                                    if res.is_err() {
-                                        // TODO the string cannot be converted into address object, need to handle the error
-                                        todo!()
-                                    }
-                                    let owner_address = res.unwrap();
-                                    let res = contract_client
-                                        .verify(&script_data.signature, &owner_address);
-                                    if res.is_err() {
-                                        // TODO the signature cannot be confirmed from solana network
-                                        todo!()
-                                    }
-                                    let amount = res.unwrap();
-                                    if amount > WITHDRAW_THRESHOLD {
-                                        tx_withdraw
-                                            .send(WithdrawInfo {
-                                                sender_address: depc_owner_address.to_string(),
-                                                recipient_address: script_data.recipient,
-                                                amount,
-                                            })
-                                            .await.unwrap();
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        Err(e) => {
+            let reason = BridgeError::token_client::<C>(e);
+            for item in batch.iter() {
+                dead_letter_deposit(conn, &item.deposit, &reason, clock.now())?;
             }
+            return Ok(());
         }
-
-        sync_height += 1;
+    };
+    let txid = receipt.txid.to_string();
+    debug!(
+        "{} deposit(s) are minted by {txid} in slot {:?}, fee: {:?}",
+        batch.len(),
+        receipt.slot,
+        receipt.fee
+    );
+    // the receipt is unknown when the confirmed transaction cannot be fetched
+    let mint_receipt = receipt.slot.map(|slot| db::MintReceiptRecord {
+        fee: receipt.fee,
+        slot,
+        created_ata: receipt.created_ata,
+        raw_logs: receipt.raw_logs,
+    });
+    let deposits: Vec<_> = batch.into_iter().map(|item| item.deposit).collect();
+    record_mint(conn, &deposits, &txid, mint_receipt, clock.now())?;
+    if let Err(e) = contract_client.acknowledge_transfer(&receipt.txid) {
+        // it's recovered again on the next start, the deposits are found minted then
+        warn!("cannot acknowledge transaction {txid}, reason: {e}");
     }
-    local_db.commit_transaction().unwrap();
-
     Ok(())
 }
 
-fn get_curr_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
+/// Confirm the deposits minted by `txid` together, and post the total of them to the ledger
+/// with the fee of the transaction if the receipt has it, all of it in one database
+/// transaction
+fn record_mint(
+    conn: &db::Conn,
+    deposits: &[db::PendingActionRecord],
+    txid: &str,
+    mint_receipt: Option<db::MintReceiptRecord>,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    let depc_txids: Vec<&str> = deposits
+        .iter()
+        .map(|deposit| deposit.source_txid.as_str())
+        .collect();
+    let fee = mint_receipt.as_ref().and_then(|receipt| receipt.fee);
+    conn.transaction(|| {
+        conn.confirm_deposits_batch(&depc_txids, txid, timestamp, mint_receipt)?;
+        let mut total = DepcAmount::default();
+        for deposit in deposits {
+            conn.complete_pending_action(deposit.id, Some(txid), timestamp)?;
+            // the transaction is confirmed when it's sent
+            conn.confirm_pending_action(deposit.id, timestamp)?;
+            total = total
+                .checked_add(deposit.amount)
+                .ok_or(BridgeError::AmountOverflow)?;
+        }
+        let ledger = Ledger::new(conn.clone());
+        ledger.wrapped_minted(txid, total, timestamp)?;
+        match fee {
+            Some(fee) => ledger.mint_fee(txid, fee, timestamp),
+            None => Ok(()),
+        }
+    })
 }
 
-#[cfg(test)]
+/// Record the mints of the transfers the token client recovers, i.e. the ones sent by the last
+/// run but never recorded, the process stopped or the transfer was in doubt
+///
+/// A confirmed transfer mints the claimed deposits it pays, they're matched by the recipients
+/// and the amounts, so they're not minted again after the claimed deposits are released. The
+/// transfers failed or expired mint nothing. The transfer of a pending sweep of the bridge fees
+/// settles the sweep instead, see `sweep_fees`.
+fn recover_mints<C: TokenClient>(
+    contract_client: &C,
+    conn: &db::Conn,
+    converter: &AmountConverter,
+    clock: &SharedClock,
+) -> Result<(), BridgeError> {
+    let transfers = contract_client
+        .recover_transfers()
+        .map_err(BridgeError::token_client::<C>)?;
+    for transfer in transfers {
+        let txid = transfer.txid.to_string();
+        let recipients: Vec<(String, u64)> = transfer
+            .recipients
+            .into_iter()
+            .map(|(recipient_address, amount)| (recipient_address.to_string(), amount.into()))
+            .collect();
+        let confirmed = transfer.status == TransferStatus::Confirmed;
+        if let Some(sweep) = find_pending_fee_sweep(conn, converter, &recipients)? {
+            warn!(
+                "transfer {txid} of the last run is {}, it settles sweep {} of the bridge fees",
+                transfer.status.as_str(),
+                sweep.id
+            );
+            let signature = confirmed.then_some(txid.as_str());
+            settle_fee_sweep(conn, sweep.id, sweep.amount, signature, clock.now())?;
+        } else if !confirmed {
+            warn!(
+                "transfer {txid} of the last run is {}, nothing is minted by it",
+                transfer.status.as_str()
+            );
+        } else if conn.query_pending_action_exists_by_txid(&txid)?
+            || conn.query_fee_sweep_exists_by_signature(&txid)?
+        {
+            info!("transfer {txid} of the last run is recorded already");
+        } else {
+            let mut claimed = conn.query_claimed_pending_actions(ACTION_DEPOSIT)?;
+            let mut deposits = vec![];
+            for (recipient, amount) in recipients {
+                let found = claimed.iter().position(|deposit| {
+                    deposit.recipient == recipient
+                        && converter
+                            .to_token(deposit.amount)
+                            .is_some_and(|token_amount| token_amount.raw() == amount)
+                });
+                match found {
+                    Some(i) => deposits.push(claimed.remove(i)),
+                    None => error!(
+                        "transfer {txid} of the last run pays {amount} to {recipient}, but no \
+                         claimed deposit is found for it, the operator should check it"
+                    ),
+                }
+            }
+            warn!(
+                "transfer {txid} of the last run is confirmed, {} deposit(s) are minted by it",
+                deposits.len()
+            );
+            if !deposits.is_empty() {
+                record_mint(conn, &deposits, &txid, None, clock.now())?;
+            }
+        }
+        contract_client
+            .acknowledge_transfer(&transfer.txid)
+            .map_err(BridgeError::token_client::<C>)?;
+    }
+    for sweep in conn.query_pending_fee_sweeps()? {
+        error!(
+            "sweep {} of the bridge fees {} to {} is in doubt and no transfer of the last run \
+             pays it, its fees are not swept again, the operator should check the treasury",
+            sweep.id, sweep.amount, sweep.treasury
+        );
+    }
+    Ok(())
+}
+
+/// The deposit the token client refuses to mint for good is dead-lettered, it's done without
+/// a transaction and never retried
+fn dead_letter_deposit(
+    conn: &db::Conn,
+    deposit: &db::PendingActionRecord,
+    reason: &BridgeError,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    let height = conn
+        .query_transaction_height(&deposit.source_txid)?
+        .unwrap_or_default();
+    let reason = reason.full_message();
+    error!(
+        "deposit {} of {} to {} is never minted, reason: {reason}",
+        deposit.source_txid, deposit.amount, deposit.recipient
+    );
+    conn.add_dead_letter(height, &deposit.source_txid, None, &reason, timestamp)?;
+    conn.complete_pending_action(deposit.id, None, timestamp)?;
+    Ok(())
+}
+
+pub async fn sweep_processing(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    depc_client: DePCClient,
+    depc_owner_address: DePCAddress,
+    sweep_config: SweepConfig,
+    shadow: bool,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    // nothing is swept in shadow mode, so the same excess shows up again on the next check
+    let mut last_shadow_sweep = None;
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        if let Some(height) = conn.query_best_height() {
+            let balance = conn
+                .query_balance(&depc_owner_address, height)
+                .unwrap_or_default();
+            // the fee is paid from the excess, so the hot wallet keeps exactly the ceiling
+            let excess = balance
+                .checked_sub(sweep_config.hot_ceiling)
+                .and_then(|excess| excess.checked_sub(DepcAmount::from_value64(DEFAULT_TX_FEE)))
+                .filter(|excess| !excess.is_zero());
+            if let Some(amount) = excess {
+                if shadow {
+                    if last_shadow_sweep != Some(amount) {
+                        record_shadow_action(
+                            &conn,
+                            clock.now(),
+                            "sweep",
+                            "",
+                            &sweep_config.cold_address,
+                            amount,
+                        );
+                        last_shadow_sweep = Some(amount);
+                    }
+                } else if let Err(e) = sweep_excess(
+                    &conn,
+                    &depc_client,
+                    &depc_owner_address,
+                    &sweep_config,
+                    amount,
+                    clock.now(),
+                ) {
+                    error!(
+                        "cannot sweep {amount} to cold address, reason: {}",
+                        e.full_message()
+                    );
+                }
+            }
+        }
+        sleep(sweep_config.interval).await;
+    }
+    Ok(())
+}
+
+fn sweep_excess(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    depc_owner_address: &DePCAddress,
+    sweep_config: &SweepConfig,
+    amount: DepcAmount,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    // the unsigned transaction is still waiting for the operator, don't make another one
+    // spending the other coins
+    if sweep_config.unsigned_only && is_unsigned_sweep_pending(conn, depc_client)? {
+        return Ok(());
+    }
+    // the withdraw workers pick their coins after the sweep is broadcast
+    let _spending = depc_client.lock_spending();
+    let (raw_tx, spent) = depc_client.make_transfer_spending(
+        depc_owner_address,
+        &sweep_config.cold_address,
+        amount.value64(),
+    )?;
+    let inputs: Vec<(depc::TxID, u32)> = spent
+        .iter()
+        .map(|unspent| (unspent.txid.clone(), unspent.vout))
+        .collect();
+    let (txid, raw_tx) = if sweep_config.unsigned_only {
+        depc_client.reserve_coins(&inputs);
+        warn!(
+            "unsigned sweep of {amount} to {} is made, sign and broadcast it offline",
+            sweep_config.cold_address
+        );
+        (None, raw_tx)
+    } else {
+        let signed_tx = depc_client.sign_raw_transaction(&raw_tx)?;
+        let txid = depc_client.send_raw_transaction(&signed_tx)?;
+        info!(
+            "swept {amount} to cold address {}, txid {txid}",
+            sweep_config.cold_address
+        );
+        Ledger::new(conn.clone()).sweep(
+            &txid,
+            amount,
+            DepcAmount::from_value64(DEFAULT_TX_FEE),
+            timestamp,
+        )?;
+        (Some(txid), signed_tx)
+    };
+    conn.add_sweep(
+        depc_owner_address,
+        &sweep_config.cold_address,
+        amount,
+        txid.as_deref(),
+        &raw_tx,
+        &serde_json::to_string(&inputs).unwrap(),
+        timestamp,
+    )?;
+    Ok(())
+}
+
+/// Whether an unsigned sweep is still waiting for the operator, its coins are kept out of the
+/// transfers (see `depc::Client::reserve_coins`) until any of them is spent in the index, the
+/// sweep is settled with the spending transaction then
+fn is_unsigned_sweep_pending(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+) -> Result<bool, BridgeError> {
+    let mut pending = false;
+    for sweep in conn.query_unsigned_sweeps()? {
+        // made before the inputs are recorded, there is nothing to wait for
+        let Some(inputs) = sweep.inputs.as_deref() else {
+            continue;
+        };
+        let inputs: Vec<(depc::TxID, u32)> = serde_json::from_str(inputs).unwrap_or_default();
+        let mut spent_txid = None;
+        for (txid, vout) in inputs.iter() {
+            spent_txid = conn.query_coin_spent_txid(txid, *vout)?;
+            if spent_txid.is_some() {
+                break;
+            }
+        }
+        match spent_txid {
+            Some(txid) => {
+                conn.settle_unsigned_sweep(sweep.id, &txid)?;
+                depc_client.unreserve_coins(&inputs);
+                info!("unsigned sweep {} is broadcast, txid {txid}", sweep.id);
+            }
+            None => {
+                // the reservations are gone after a restart
+                depc_client.reserve_coins(&inputs);
+                debug!("unsigned sweep {} is still pending", sweep.id);
+                pending = true;
+            }
+        }
+    }
+    Ok(pending)
+}
+
+/// Return the invalid deposits those are recorded for `refund_config.delay` to their senders,
+/// or to the addresses they claimed the refunds to, see `verify_refund_claims`
+pub async fn refund_processing(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    depc_client: DePCClient,
+    depc_owner_address: DePCAddress,
+    refund_config: RefundConfig,
+    shadow: bool,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        verify_refund_claims(&conn, &depc_client)?;
+        let due = clock.now().saturating_sub(refund_config.delay.as_secs());
+        for refund in conn.query_due_refunds(due)? {
+            let Some(refund_address) = refund_destination(&refund) else {
+                debug!("the claim of refund {} is not verified yet", refund.id);
+                continue;
+            };
+            // the deposit covers the fee, it's checked while syncing
+            let Some(amount) = refund
+                .amount
+                .checked_sub(DepcAmount::from_value64(DEFAULT_TX_FEE))
+            else {
+                error!("refund {} cannot pay the fee, skip it", refund.id);
+                continue;
+            };
+            if shadow {
+                record_shadow_action(
+                    &conn,
+                    clock.now(),
+                    "refund",
+                    &refund.depc_txid,
+                    &refund_address,
+                    amount,
+                );
+                conn.complete_refund(refund.id, None, clock.now())?;
+                continue;
+            }
+            match depc_client.transfer(&depc_owner_address, &refund_address, amount.value64()) {
+                Ok(txid) => {
+                    conn.complete_refund(refund.id, Some(&txid), clock.now())?;
+                    Ledger::new(conn.clone()).refund(
+                        &txid,
+                        amount,
+                        DepcAmount::from_value64(DEFAULT_TX_FEE),
+                        clock.now(),
+                    )?;
+                    info!(
+                        "invalid deposit {} is refunded to {refund_address}, amount {amount}, \
+                         txid {txid}",
+                        refund.depc_txid
+                    );
+                }
+                Err(e) => {
+                    error!("cannot refund {amount} to {refund_address}, retry later, reason: {e}");
+                }
+            }
+        }
+        sleep(refund_config.interval).await;
+    }
+    Ok(())
+}
+
+/// The bridge action found from a synced block, it's saved to table `pending_actions` with the
+/// block and the worker is notified only after the block is committed, so a block synced again
+/// never makes the action twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BridgeAction {
+    Deposit(PendingActionId),
+    Withdraw(PendingActionId),
+}
+
+impl BridgeAction {
+    fn id(self) -> PendingActionId {
+        match self {
+            BridgeAction::Deposit(id) | BridgeAction::Withdraw(id) => id,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BridgeAction::Deposit(_) => ACTION_DEPOSIT,
+            BridgeAction::Withdraw(_) => ACTION_WITHDRAW,
+        }
+    }
+}
+
+/// A block fetched from the node before it's synced, see `DepcSyncer::fetch_block`
+struct FetchedBlock {
+    height: u32,
+    hash: String,
+    /// The error of the block pruned on the node
+    block: Result<depc::Block, depc::Error>,
+    /// The transactions of the block in order, the error of the ones the node cannot return
+    transactions: Vec<(String, Result<depc::Transaction, BridgeError>)>,
+    /// The tokens transferred by the signatures of the withdrawals by the outputs (txid, vout)
+    withdrawals: HashMap<(String, u32), Result<u64, BridgeError>>,
+}
+
+struct DepcSyncer<C>
+where
+    C: TokenClient,
+{
+    /// The chain index and the dead letters of the transactions
+    local_db: db::Conn,
+    depc_client: DePCClient,
+    pairs: Vec<SyncPair<C>>,
+    deposit_intent_window: Option<Duration>,
+    /// Look up the outputs to the other addresses in table `deposit_addresses`
+    deposit_addresses: bool,
+    refund_invalid_deposits: bool,
+    /// The unclassified inflows of this amount or more are alerted as errors
+    inflow_alert_amount: DepcAmount,
+    /// The custody addresses by the hex of their scripts, see `custody_scripts`
+    custody_scripts: HashMap<String, DePCAddress>,
+    clock: SharedClock,
+}
+
+impl<C> DepcSyncer<C>
+where
+    C: TokenClient,
+{
+    /// Sync the blocks `from..=to` in a database transaction, nothing of the blocks is kept
+    /// when any of them fails. The blocks are fetched before the transaction opens, it never
+    /// waits for the nodes
+    async fn sync_blocks_in_transaction(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<(usize, BridgeAction)>, BridgeError> {
+        let mut blocks = vec![];
+        for sync_height in from..=to {
+            blocks.push(self.fetch_block(sync_height).await?);
+        }
+        self.local_db.transaction(|| {
+            let mut actions = vec![];
+            for block in blocks {
+                actions.extend(self.sync_block(block)?);
+            }
+            Ok::<_, BridgeError>(actions)
+        })
+    }
+
+    /// Fetch the block with its transactions, and verify the transfers of the withdrawals to
+    /// the custody addresses. The transient errors are returned after the retries, the block
+    /// is fetched again later
+    async fn fetch_block(&self, sync_height: u32) -> Result<FetchedBlock, BridgeError> {
+        let hash = self.depc_client.get_block_hash(sync_height)?;
+        let block = match self.depc_client.get_block(&hash) {
+            Ok(block) => block,
+            Err(e @ depc::Error::DataUnavailable(_)) => {
+                return Ok(FetchedBlock {
+                    height: sync_height,
+                    hash,
+                    block: Err(e),
+                    transactions: vec![],
+                    withdrawals: HashMap::new(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if block.height != sync_height {
+            return Err(BridgeError::BlockNotFound(sync_height));
+        }
+        let mut transactions = vec![];
+        let mut withdrawals = HashMap::new();
+        // the transactions of the genesis are not indexed
+        let txids = if sync_height == 0 {
+            &[][..]
+        } else {
+            &block.tx[..]
+        };
+        for txid in txids.iter() {
+            let transaction =
+                match retry_transient(|| Ok(self.depc_client.get_transaction(txid)?)).await {
+                    // the coins of the block are incomplete without the transaction, fetch the
+                    // whole block again
+                    Err(e) if e.is_transient() => return Err(e),
+                    res => res,
+                };
+            for txout in transaction
+                .iter()
+                .flat_map(|transaction| transaction.vout.iter())
+            {
+                let address = txout
+                    .get_address()
+                    .or_else(|| self.custody_scripts.get(&txout.script_pubkey.hex).cloned());
+                let Some((index, _)) =
+                    address.and_then(|address| self.find_custody_address(&address, block.time))
+                else {
+                    continue;
+                };
+                let value = DepcAmount::from_value64(txout.value64);
+                if let Some(signatures) = withdraw_signatures(value, &txout.script_pubkey.hex) {
+                    let pair = &self.pairs[index].pair;
+                    let transferred = self.verify_transfers(pair, &signatures, None).await;
+                    withdrawals.insert((txid.clone(), txout.n), transferred);
+                }
+            }
+            transactions.push((txid.clone(), transaction));
+        }
+        Ok(FetchedBlock {
+            height: sync_height,
+            hash,
+            block: Ok(block),
+            transactions,
+            withdrawals,
+        })
+    }
+
+    /// Returns the actions with the index of their pairs
+    fn sync_block(&self, fetched: FetchedBlock) -> Result<Vec<(usize, BridgeAction)>, BridgeError> {
+        let FetchedBlock {
+            height: sync_height,
+            hash: block_hash,
+            block,
+            transactions,
+            mut withdrawals,
+        } = fetched;
+        let mut actions = vec![];
+        let block = match block {
+            Ok(block) => block,
+            // the block is pruned, skip it so the newer blocks are still synced
+            Err(e) => {
+                self.mark_height_unavailable(sync_height, &format!("block {block_hash}"), &e)?;
+                return Ok(actions);
+            }
+        };
+        // the block must follow the indexed one, the chain is reorganized since it's indexed
+        if let (Some(prev_hash), Some(prev_height)) = (&block.prev_hash, sync_height.checked_sub(1))
+        {
+            if let Some(indexed) = self.local_db.query_block_hash_by_height(prev_height)? {
+                if &indexed != prev_hash {
+                    return Err(BridgeError::ChainReorganized(sync_height));
+                }
+            }
+        }
+        self.local_db
+            .add_block(&block.hash, sync_height, &block.miner, block.time)?;
+
+        if sync_height == 0 {
+            return Ok(actions);
+        }
+        let watchlist = Watchlist::new(self.local_db.clone());
+        let watched = watchlist.watched(WatchChain::Depc)?;
+        // the outputs to the addresses of each pair, the ones recorded already included
+        let mut events = vec![vec![]; self.pairs.len()];
+        // transactions
+        for (txid, transaction) in transactions {
+            let txid = &txid;
+            let transaction = match transaction {
+                Ok(transaction) => transaction,
+                Err(BridgeError::Depc(e @ depc::Error::DataUnavailable(_))) => {
+                    let subject = format!("transaction {txid}");
+                    self.mark_height_unavailable(sync_height, &subject, &e)?;
+                    continue;
+                }
+                Err(e) => {
+                    self.add_dead_letter(&self.local_db, sync_height, txid, None, &e)?;
+                    continue;
+                }
+            };
+            self.local_db.add_transaction(&block_hash, txid)?;
+            for txin in transaction.vin.iter() {
+                if let (Some(spent_txid), Some(vout)) = (&txin.txid, txin.vout) {
+                    // TODO maybe we need to check the validity of the txin?
+                    self.local_db
+                        .mark_coin_to_spent(spent_txid, vout, txid, sync_height)?;
+                }
+            }
+            let mut outputs = vec![];
+            for txout in transaction.vout.iter() {
+                // the outputs to the custody script are recognized without the address
+                let address = txout
+                    .get_address()
+                    .or_else(|| self.custody_scripts.get(&txout.script_pubkey.hex).cloned());
+                // save the txout anyway
+                if let Some(address) = address {
+                    let value = DepcAmount::from_value64(txout.value64);
+                    self.local_db.add_coin(
+                        txid,
+                        txout.n,
+                        value,
+                        &address,
+                        &txout.script_pubkey.hex,
+                    )?;
+                    if watched.contains(&address) {
+                        outputs.push((address.clone(), value));
+                    }
+                    // is one of our addresses, start processing
+                    let (index, custody_era, is_custody, res) = if let Some((index, custody_era)) =
+                        self.find_custody_address(&address, block.time)
+                    {
+                        events[index].push(BlockEvent::new(
+                            txid,
+                            txout.n,
+                            &address,
+                            value,
+                            &txout.script_pubkey.hex,
+                        ));
+                        if self.is_output_recorded(&self.pairs[index].pair, txid, txout.n)? {
+                            continue;
+                        }
+                        let res = self.process_bridge_output(
+                            &self.pairs[index].pair,
+                            txid,
+                            txout.n,
+                            value,
+                            &txout.script_pubkey.hex,
+                            block.time,
+                            custody_era,
+                            withdrawals.remove(&(txid.clone(), txout.n)),
+                        );
+                        (index, custody_era, true, res)
+                    } else if let Some((index, deposit_address)) =
+                        self.find_deposit_address(&address)?
+                    {
+                        events[index].push(BlockEvent::new(
+                            txid,
+                            txout.n,
+                            &address,
+                            value,
+                            &txout.script_pubkey.hex,
+                        ));
+                        if self.is_output_recorded(&self.pairs[index].pair, txid, txout.n)? {
+                            continue;
+                        }
+                        let res = self.process_deposit_address_output(
+                            &self.pairs[index].pair,
+                            txid,
+                            txout.n,
+                            value,
+                            &deposit_address,
+                            block.time,
+                        );
+                        (index, None, false, res)
+                    } else {
+                        continue;
+                    };
+                    let pair = &self.pairs[index].pair;
+                    match res {
+                        Ok(Some(action)) => {
+                            if let Some(custody_era) = custody_era {
+                                pair.conn
+                                    .set_pending_action_custody_era(action.id(), custody_era)?;
+                            }
+                            pair.conn.add_recorded_transaction(
+                                txid,
+                                txout.n,
+                                action.name(),
+                                action.id(),
+                                self.clock.now(),
+                            )?;
+                            actions.push((index, action))
+                        }
+                        Ok(None) if is_custody => self.record_custody_inflow(
+                            pair,
+                            txid,
+                            txout.n,
+                            &address,
+                            value,
+                            &txout.script_pubkey.hex,
+                            sync_height,
+                            None,
+                        )?,
+                        Ok(None) => {}
+                        // the withdrawals are zero outputs, they're dead-lettered
+                        Err(e) if is_custody && !value.is_zero() && e.is_below_minimum() => self
+                            .keep_small_deposit(
+                                pair,
+                                txid,
+                                txout.n,
+                                &address,
+                                value,
+                                &txout.script_pubkey.hex,
+                                sync_height,
+                                custody_era,
+                                e,
+                            )?,
+                        // the node is still unavailable after the retries, sync the whole block
+                        // again instead of losing the output
+                        Err(
+                            e @ (BridgeError::Database(_)
+                            | BridgeError::TokenClient(_, ErrorKind::Transient)),
+                        ) => return Err(e),
+                        Err(e) => {
+                            self.add_dead_letter(&pair.conn, sync_height, txid, Some(txout.n), &e)?
+                        }
+                    }
+                }
+            }
+            watchlist.record_depc_transaction(&watched, txid, &outputs, block.time)?;
+        }
+        for (sync_pair, events) in self.pairs.iter().zip(events) {
+            let digest = digest_block_events(sync_height, &block.hash, &events);
+            sync_pair.pair.conn.add_block_digest(
+                sync_height,
+                &block.hash,
+                events.len() as u32,
+                &hex::encode(digest),
+                self.clock.now(),
+            )?;
+        }
+        // the payouts made by the bridge are confirmed by the block
+        for sync_pair in self.pairs.iter() {
+            sync_pair
+                .pair
+                .conn
+                .confirm_pending_actions_in_block(&block.hash, self.clock.now())?;
+        }
+        Ok(actions)
+    }
+
+    /// The height of the last indexed block the chain still has when the indexed blocks up to
+    /// `best_height` are replaced by a reorg, `None` if they're still on the chain
+    ///
+    /// The heights without an indexed block (e.g. the pruned ones) are skipped.
+    fn find_fork_height(
+        &self,
+        best_height: u32,
+        chain_height: u32,
+    ) -> Result<Option<u32>, BridgeError> {
+        // a node behind the index is waited for, the blocks above its height are checked once
+        // it reaches them
+        let mut reorganized = false;
+        let lowest = best_height.saturating_sub(MAX_REORG_DEPTH);
+        let mut height = best_height.min(chain_height);
+        loop {
+            if let Some(indexed) = self.local_db.query_block_hash_by_height(height)? {
+                if indexed == self.depc_client.get_block_hash(height)? {
+                    return Ok(reorganized.then_some(height));
+                }
+                reorganized = true;
+            }
+            if height <= lowest {
+                break;
+            }
+            height -= 1;
+        }
+        if reorganized {
+            return Err(BridgeError::ReorgTooDeep(MAX_REORG_DEPTH));
+        }
+        Ok(None)
+    }
+
+    /// Unwind the index above `fork_height`, the blocks are synced again from the chain
+    fn roll_back_reorg(&self, best_height: u32, fork_height: u32) -> Result<(), BridgeError> {
+        warn!(
+            "the chain is reorganized, roll back blocks {}..={best_height}",
+            fork_height + 1
+        );
+        let record = self.local_db.rollback_blocks_above(fork_height)?;
+        info!(
+            "{} blocks, {} transactions and {} coins are rolled back, {} deposits and {} refunds \
+             are removed",
+            record.blocks,
+            record.transactions,
+            record.coins,
+            record.removed_deposits.len(),
+            record.removed_refunds
+        );
+        for (bridge_id, txid) in record.kept_deposits.iter() {
+            error!(
+                "deposit {txid} of bridge {bridge_id} is rolled back after it's picked by the \
+                 worker, check it on the chain"
+            );
+        }
+        Ok(())
+    }
+
+    /// An output to a custody address of `custody_era`, it's `None` before the eras are recorded.
+    /// The tokens transferred by the signatures of a withdrawal are verified by `fetch_block`
+    #[allow(clippy::too_many_arguments)]
+    fn process_bridge_output(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        value: DepcAmount,
+        script_hex: &str,
+        block_time: u64,
+        custody_era: Option<u32>,
+        transferred: Option<Result<u64, BridgeError>>,
+    ) -> Result<Option<BridgeAction>, BridgeError> {
+        let deposit_threshold = parameter_value(
+            &pair.conn,
+            BridgeParameter::DepositThreshold,
+            self.clock.now(),
+        )?;
+        let script_data = match extract_string_from_script_hex(script_hex) {
+            Ok(script_data) => script_data,
+            // a truncated or poisoned recipient is neither paid nor matched to an intent
+            Err(depc::Error::NonCanonicalRecipient(recipient)) => {
+                let e = BridgeError::InvalidAddress(recipient);
+                if value > deposit_threshold && self.refund_invalid_deposits {
+                    self.add_refund(pair, txid, vout, value, e, custody_era)?;
+                    return Ok(None);
+                }
+                return Err(e);
+            }
+            Err(_) => return self.match_deposit_intent(pair, txid, vout, value, block_time),
+        };
+        if value > deposit_threshold && !script_data.recipient.is_empty() {
+            //deposit
+            // a deposit is made once for a transaction, the ones picked by the workers are kept
+            // through a reorg and aren't made again when the transaction is found again
+            if pair.conn.query_deposit(txid)?.is_some() {
+                warn!("deposit {txid} is made already, output {vout} is not credited");
+                return Ok(None);
+            }
+            return match self.add_deposit(pair, txid, &script_data.recipient, value, block_time) {
+                Err(e @ (BridgeError::InvalidAddress(_) | BridgeError::ReservedRecipient(..)))
+                    if self.refund_invalid_deposits =>
+                {
+                    self.add_refund(pair, txid, vout, value, e, custody_era)?;
+                    Ok(None)
+                }
+                res => res.map(Some),
+            };
+        }
+        //withdraw
+        else if value.is_zero()
+            && !script_data.recipient.is_empty()
+            && !script_data.signatures.is_empty()
+        {
+            // sync the block again when it's not verified, see `withdraw_signatures`
+            let transferred = transferred.unwrap_or_else(|| {
+                Err(BridgeError::TokenClient(
+                    format!("the transfers of withdrawal {txid} are not verified"),
+                    ErrorKind::Transient,
+                ))
+            });
+            return self.add_withdraw(
+                pair,
+                txid,
+                &script_data.recipient,
+                &script_data.signatures,
+                transferred,
+                block_time,
+            );
+        }
+        Ok(None)
+    }
+
+    /// The sum of the tokens transferred to the owner by the transactions of `signatures`, only
+    /// the ones from `sender` are counted when it's given
+    async fn verify_transfers(
+        &self,
+        pair: &BridgePair<C>,
+        signatures: &[Signature],
+        sender: Option<&C::Address>,
+    ) -> Result<u64, BridgeError> {
+        let owner_address = parse_address::<C>(&pair.solana_owner_address)?;
+        let mut amount = 0u64;
+        for signature in signatures.iter() {
+            let owner_address = owner_address.clone();
+            // a signature which can never be verified is dead-lettered at once, and the one of
+            // an unknown error after the retries
+            let transferred = retry_transient(move || {
+                match sender {
+                    Some(sender) => {
+                        pair.contract_client
+                            .verify_from(signature, sender, &owner_address)
+                    }
+                    None => pair.contract_client.verify(signature, &owner_address),
+                }
+                .map_err(BridgeError::token_client::<C>)
+            })
+            .await?;
+            amount = amount
+                .checked_add(transferred)
+                .ok_or(BridgeError::AmountOverflow)?;
+        }
+        Ok(amount)
+    }
+
+    /// The withdrawal of the tokens `transferred` to the owner by the transactions of
+    /// `signatures`, see `verify_transfers`. A transaction is never used by another withdrawal.
+    /// The withdrawal is made at `transfer_time`, the time of its block or its request
+    fn add_withdraw(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        recipient: &str,
+        signatures: &[Signature],
+        transferred: Result<u64, BridgeError>,
+        transfer_time: u64,
+    ) -> Result<Option<BridgeAction>, BridgeError> {
+        for signature in signatures.iter() {
+            let signature = signature.to_string();
+            match pair.conn.query_withdraw_signature(&signature)? {
+                // the same withdrawal is synced again
+                Some(used_by) if used_by != txid => {
+                    return Err(BridgeError::SignatureReused(signature, used_by))
+                }
+                _ => {}
+            }
+        }
+        let amount = transferred?;
+        // the digits DePC cannot hold are left to the owner, they're under one value64
+        let amount = pair
+            .converter
+            .to_depc_rounded(TokenAmount::new(amount))
+            .ok_or(BridgeError::AmountOverflow)?;
+        let withdraw_threshold = parameter_value(
+            &pair.conn,
+            BridgeParameter::WithdrawThreshold,
+            self.clock.now(),
+        )?;
+        if amount <= withdraw_threshold {
+            return Ok(None);
+        }
+        let (net, fee) = pair
+            .fees
+            .withdraw
+            .split(amount, DepcAmount::from_value64(1))
+            .ok_or(BridgeError::FeeExceedsAmount(amount))?;
+        let hold = check_transfer_limits(
+            &pair.conn,
+            &pair.limits,
+            ACTION_WITHDRAW,
+            recipient,
+            net,
+            transfer_time,
+        )?;
+        for signature in signatures.iter() {
+            pair.conn
+                .add_withdraw_signature(&signature.to_string(), txid, self.clock.now())?;
+        }
+        // the returns under the threshold are never paid out, they're not posted
+        let ledger = Ledger::new(pair.conn.clone());
+        ledger.wrapped_returned(txid, amount, self.clock.now())?;
+        let id = pair.conn.add_pending_action(
+            ACTION_WITHDRAW,
+            txid,
+            recipient,
+            net,
+            self.clock.now(),
+        )?;
+        pair.conn
+            .set_pending_action_transfer_time(id, transfer_time)?;
+        if !fee.is_zero() {
+            ledger.withdraw_fee(txid, fee, self.clock.now())?;
+            pair.conn
+                .add_transfer_fee(ACTION_WITHDRAW, txid, id, amount, fee, self.clock.now())?;
+        }
+        if let Some(reason) = hold {
+            warn!("withdrawal {id} to {recipient} is held, reason: {reason}");
+            pair.conn
+                .hold_pending_action(id, &reason, self.clock.now())?;
+        }
+        Ok(Some(BridgeAction::Withdraw(id)))
+    }
+
+    /// Pay out the withdrawals requested off-chain through the same pipeline as the ones of
+    /// the DePC transactions, a request is left pending when the database or the token client
+    /// fails transiently and rejected with the other errors, the token client is retried by
+    /// `verify_transfers` already
+    async fn process_withdraw_requests(&self) -> Result<Vec<(usize, BridgeAction)>, BridgeError> {
+        let mut actions = vec![];
+        for (index, sync_pair) in self.pairs.iter().enumerate() {
+            let pair = &sync_pair.pair;
+            for request in pair
+                .conn
+                .query_withdraw_requests_by_status(WITHDRAW_REQUEST_PENDING)?
+            {
+                // the request is verified before its transaction opens
+                let res = match self.verify_withdraw_request(pair, &request).await {
+                    Ok((signature, transferred)) => pair.conn.transaction(|| {
+                        self.accept_withdraw_request(pair, &request, signature, transferred)
+                    }),
+                    Err(e) => Err(e),
+                };
+                let e = match res {
+                    Ok(action) => {
+                        info!("withdraw request {} is accepted", request.id);
+                        actions.push((index, action));
+                        continue;
+                    }
+                    Err(e) => e,
+                };
+                let reason = e.full_message();
+                if matches!(
+                    e,
+                    BridgeError::Database(_) | BridgeError::TokenClient(_, ErrorKind::Transient)
+                ) {
+                    warn!(
+                        "withdraw request {} is left pending, reason: {reason}",
+                        request.id
+                    );
+                    continue;
+                }
+                warn!(
+                    "withdraw request {} is rejected, reason: {reason}",
+                    request.id
+                );
+                pair.conn.complete_withdraw_request(
+                    request.id,
+                    WITHDRAW_REQUEST_REJECTED,
+                    Some(&reason),
+                    None,
+                    self.clock.now(),
+                )?;
+            }
+        }
+        Ok(actions)
+    }
+
+    /// The signature of the request and the tokens it transferred from the signer
+    async fn verify_withdraw_request(
+        &self,
+        pair: &BridgePair<C>,
+        request: &db::WithdrawRequestRecord,
+    ) -> Result<(Signature, Result<u64, BridgeError>), BridgeError> {
+        let signature = Signature::from_str(&request.signature).map_err(|_| {
+            BridgeError::InvalidWithdrawRequest(format!(
+                "cannot parse signature from string '{}'",
+                request.signature
+            ))
+        })?;
+        let signer = parse_address::<C>(&request.signer)?;
+        let transferred = self
+            .verify_transfers(pair, &[signature], Some(&signer))
+            .await;
+        Ok((signature, transferred))
+    }
+
+    /// The payout of the request is referred as `offchain:{id}` instead of a DePC transaction
+    fn accept_withdraw_request(
+        &self,
+        pair: &BridgePair<C>,
+        request: &db::WithdrawRequestRecord,
+        signature: Signature,
+        transferred: Result<u64, BridgeError>,
+    ) -> Result<BridgeAction, BridgeError> {
+        let source_txid = format!("{WITHDRAW_ORIGIN_OFFCHAIN}:{}", request.id);
+        let Some(BridgeAction::Withdraw(id)) = self.add_withdraw(
+            pair,
+            &source_txid,
+            &request.recipient,
+            &[signature],
+            transferred,
+            request.created_at,
+        )?
+        else {
+            return Err(BridgeError::InvalidWithdrawRequest(
+                "the tokens the signer transferred to the owner are under the withdraw threshold"
+                    .to_owned(),
+            ));
+        };
+        pair.conn.complete_withdraw_request(
+            request.id,
+            WITHDRAW_REQUEST_ACCEPTED,
+            None,
+            Some(id),
+            self.clock.now(),
+        )?;
+        Ok(BridgeAction::Withdraw(id))
+    }
+
+    fn add_deposit(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        recipient: &str,
+        value: DepcAmount,
+        block_time: u64,
+    ) -> Result<BridgeAction, BridgeError> {
+        // a bad recipient or amount is dead-lettered here instead of failing the worker
+        let recipient_address = parse_address::<C>(recipient)?.to_string();
+        if let Some((_, description)) = pair
+            .contract_client
+            .reserved_addresses()
+            .into_iter()
+            .find(|(address, _)| address.to_string() == recipient_address)
+        {
+            return Err(BridgeError::ReservedRecipient(
+                recipient.to_owned(),
+                description,
+            ));
+        }
+        // the fee is a multiple of the unit, the rest is minted exactly
+        let (net, fee) = pair
+            .fees
+            .deposit
+            .split(value, pair.converter.depc_unit())
+            .ok_or(BridgeError::FeeExceedsAmount(value))?;
+        // the digits the mint cannot keep are booked to the bridge fees instead of dropped
+        let (net, rest) = pair.converter.round_to_unit(net);
+        if net.is_zero() {
+            return Err(BridgeError::BelowConversionUnit(
+                value,
+                pair.converter.depc_unit(),
+            ));
+        }
+        let fee = fee.checked_add(rest).ok_or(BridgeError::AmountOverflow)?;
+        pair.converter
+            .to_token(net)
+            .ok_or(BridgeError::AmountOverflow)?;
+        let hold = check_transfer_limits(
+            &pair.conn,
+            &pair.limits,
+            ACTION_DEPOSIT,
+            recipient,
+            net,
+            block_time,
+        )?;
+        pair.conn.save_deposit(txid, recipient, value, block_time)?;
+        let ledger = Ledger::new(pair.conn.clone());
+        ledger.custody_in(txid, value, self.clock.now())?;
+        let id =
+            pair.conn
+                .add_pending_action(ACTION_DEPOSIT, txid, recipient, net, self.clock.now())?;
+        pair.conn.set_pending_action_transfer_time(id, block_time)?;
+        if !fee.is_zero() {
+            ledger.deposit_fee(txid, fee, self.clock.now())?;
+            pair.conn
+                .add_transfer_fee(ACTION_DEPOSIT, txid, id, value, fee, self.clock.now())?;
+        }
+        if let Some(reason) = hold {
+            warn!("deposit {id} to {recipient} is held, reason: {reason}");
+            pair.conn
+                .hold_pending_action(id, &reason, self.clock.now())?;
+        }
+        Ok(BridgeAction::Deposit(id))
+    }
+
+    /// Record the deposit can never be bridged, it's returned to its sender by the refund worker,
+    /// or `reason` is returned when there is nowhere to refund it
+    fn add_refund(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        value: DepcAmount,
+        reason: BridgeError,
+        custody_era: Option<u32>,
+    ) -> Result<(), BridgeError> {
+        if value <= DepcAmount::from_value64(DEFAULT_TX_FEE) {
+            return Err(reason);
+        }
+        // the coinbase and the transactions spend the coins are not indexed have no inputs
+        let refund_address = match self.local_db.query_inputs(txid)?.into_iter().next() {
+            Some(address) if !self.is_custody_address(&address) => address,
+            _ => return Err(reason),
+        };
+        let reason = reason.full_message();
+        warn!("deposit {txid}:{vout} is invalid, refund it to {refund_address}, reason: {reason}");
+        pair.conn.add_refund(
+            txid,
+            vout,
+            &refund_address,
+            value,
+            &reason,
+            self.clock.now(),
+        )?;
+        if let Some(custody_era) = custody_era {
+            pair.conn.set_refund_custody_era(txid, vout, custody_era)?;
+        }
+        Ledger::new(pair.conn.clone()).custody_in(txid, value, self.clock.now())?;
+        Ok(())
+    }
+
+    /// Match a plain payment to the oldest open deposit intent of its senders, the intent must
+    /// be registered within the window before the block
+    fn match_deposit_intent(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        value: DepcAmount,
+        block_time: u64,
+    ) -> Result<Option<BridgeAction>, BridgeError> {
+        let Some(window) = self.deposit_intent_window else {
+            return Ok(None);
+        };
+        let deposit_threshold = parameter_value(
+            &pair.conn,
+            BridgeParameter::DepositThreshold,
+            self.clock.now(),
+        )?;
+        if value <= deposit_threshold {
+            return Ok(None);
+        }
+        // a deposit is made once for a transaction, the other outputs are not matched
+        if pair.conn.query_deposit(txid)?.is_some() {
+            warn!("deposit {txid} is made already, output {vout} is not matched");
+            return Ok(None);
+        }
+        let senders = self.local_db.query_inputs(txid)?;
+        // the change of the payouts and the sweeps, never match them even though somebody
+        // registers a custody address as the sender
+        if senders.iter().any(|sender| self.is_custody_address(sender)) {
+            return Ok(None);
+        }
+        let created_from = block_time.saturating_sub(window.as_secs());
+        for sender in senders {
+            let Some(intent) =
+                pair.conn
+                    .query_open_deposit_intent(&sender, created_from, block_time)?
+            else {
+                continue;
+            };
+            let action = self.add_deposit(pair, txid, &intent.recipient, value, block_time)?;
+            pair.conn
+                .match_deposit_intent(intent.id, txid, vout, self.clock.now())?;
+            info!(
+                "payment {txid}:{vout} from {sender} is matched to deposit intent {}",
+                intent.id
+            );
+            return Ok(Some(action));
+        }
+        Ok(None)
+    }
+
+    /// The deposit to a custody address too small to be bridged is refunded like an invalid
+    /// one, or kept as an unclassified inflow like the ones under the threshold when it cannot
+    /// be refunded
+    #[allow(clippy::too_many_arguments)]
+    fn keep_small_deposit(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        address: &str,
+        value: DepcAmount,
+        script_hex: &str,
+        height: u32,
+        custody_era: Option<u32>,
+        reason: BridgeError,
+    ) -> Result<(), BridgeError> {
+        if self.refund_invalid_deposits {
+            match self.add_refund(pair, txid, vout, value, reason, custody_era) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_below_minimum() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.record_custody_inflow(
+            pair,
+            txid,
+            vout,
+            address,
+            value,
+            script_hex,
+            height,
+            Some(INFLOW_BELOW_MINIMUM),
+        )
+    }
+
+    /// Record the payment to a custody address which makes no bridge action as an unclassified
+    /// inflow and alert it, the operator investigates and resolves it. The change of the
+    /// payouts and the sweeps, the refunded deposits and the zero outputs of the withdrawals
+    /// are not inflows. The reason is told by the payment unless it's given
+    #[allow(clippy::too_many_arguments)]
+    fn record_custody_inflow(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        address: &str,
+        value: DepcAmount,
+        script_hex: &str,
+        height: u32,
+        reason: Option<&'static str>,
+    ) -> Result<(), BridgeError> {
+        if value.is_zero() {
+            return Ok(());
+        }
+        let senders = self.local_db.query_inputs(txid)?;
+        if senders.iter().any(|sender| self.is_custody_address(sender)) {
+            return Ok(());
+        }
+        if pair.conn.query_refund_of_output(txid, vout)?.is_some() {
+            return Ok(());
+        }
+        let deposit_threshold = parameter_value(
+            &pair.conn,
+            BridgeParameter::DepositThreshold,
+            self.clock.now(),
+        )?;
+        let reason = if let Some(reason) = reason {
+            reason
+        } else if value <= deposit_threshold {
+            INFLOW_BELOW_THRESHOLD
+        } else if pair.conn.query_deposit(txid)?.is_some() {
+            INFLOW_DUPLICATE_OUTPUT
+        } else if extract_string_from_script_hex(script_hex)
+            .is_ok_and(|script_data| !script_data.recipient.is_empty())
+        {
+            INFLOW_UNRECOGNIZED
+        } else {
+            INFLOW_NO_RECIPIENT
+        };
+        let added = pair.conn.add_custody_inflow(
+            txid,
+            vout,
+            address,
+            value,
+            reason,
+            height,
+            self.clock.now(),
+        )?;
+        if !added {
+            return Ok(());
+        }
+        if value >= self.inflow_alert_amount {
+            error!(
+                "unclassified inflow of {value} to custody address {address} in {txid}:{vout}, \
+                 reason: {reason}, resolve it after it's investigated"
+            );
+        } else {
+            warn!(
+                "unclassified inflow of {value} to custody address {address} in {txid}:{vout}, \
+                 reason: {reason}"
+            );
+        }
+        Ok(())
+    }
+
+    /// The pair `address` is registered as a deposit address for, and the registration
+    fn find_deposit_address(
+        &self,
+        address: &str,
+    ) -> Result<Option<(usize, db::DepositAddressRecord)>, BridgeError> {
+        if !self.deposit_addresses {
+            return Ok(None);
+        }
+        for (index, sync_pair) in self.pairs.iter().enumerate() {
+            if let Some(deposit_address) = sync_pair.pair.conn.query_deposit_address(address)? {
+                return Ok(Some((index, deposit_address)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Credit a payment to a deposit address to the recipient the address is registered for,
+    /// the payment needs no OP_RETURN
+    fn process_deposit_address_output(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+        value: DepcAmount,
+        deposit_address: &db::DepositAddressRecord,
+        block_time: u64,
+    ) -> Result<Option<BridgeAction>, BridgeError> {
+        let deposit_threshold = parameter_value(
+            &pair.conn,
+            BridgeParameter::DepositThreshold,
+            self.clock.now(),
+        )?;
+        if value <= deposit_threshold {
+            return Ok(None);
+        }
+        // a deposit is made once for a transaction, the other outputs are not credited
+        if pair.conn.query_deposit(txid)?.is_some() {
+            warn!(
+                "deposit {txid} is made already, output {vout} to deposit address {} is not \
+                 credited",
+                deposit_address.address
+            );
+            return Ok(None);
+        }
+        let action = self.add_deposit(pair, txid, &deposit_address.recipient, value, block_time)?;
+        info!(
+            "payment {txid}:{vout} to deposit address {} is credited to {}",
+            deposit_address.address, deposit_address.recipient
+        );
+        Ok(Some(action))
+    }
+
+    /// The pair `address` is a watched custody address of at `block_time`, and the era of the
+    /// address
+    fn find_custody_address(&self, address: &str, block_time: u64) -> Option<(usize, Option<u32>)> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .find_map(|(index, sync_pair)| {
+                let pair = &sync_pair.pair;
+                if pair.depc_owner_address == address {
+                    Some((index, pair.custody_eras.current()))
+                } else {
+                    let era = pair.custody_eras.watched_era(address, block_time)?;
+                    Some((index, Some(era)))
+                }
+            })
+    }
+
+    /// The custody address of any era of any pair
+    fn is_custody_address(&self, address: &str) -> bool {
+        self.pairs.iter().any(|sync_pair| {
+            sync_pair.pair.depc_owner_address == address
+                || sync_pair.pair.custody_eras.contains(address)
+        })
+    }
+
+    /// Whether the output is processed into a pending action already, e.g. by the run before a
+    /// restart in the middle of the block, see table `recorded_transactions`
+    fn is_output_recorded(
+        &self,
+        pair: &BridgePair<C>,
+        txid: &str,
+        vout: u32,
+    ) -> Result<bool, BridgeError> {
+        match pair.conn.query_recorded_transaction(txid, vout)? {
+            Some(recorded) => {
+                debug!(
+                    "output {txid}:{vout} is recorded for pending action {} already, skip it",
+                    recorded.action_id
+                );
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn add_dead_letter(
+        &self,
+        conn: &db::Conn,
+        height: u32,
+        txid: &str,
+        vout: Option<u32>,
+        reason: &BridgeError,
+    ) -> Result<(), BridgeError> {
+        let reason = reason.full_message();
+        warn!("skip transaction {txid} at height {height}, reason: {reason}");
+        conn.add_dead_letter(height, txid, vout, &reason, self.clock.now())?;
+        Ok(())
+    }
+
+    /// The coins of the height cannot be indexed completely, it's reported as a data gap
+    fn mark_height_unavailable(
+        &self,
+        height: u32,
+        subject: &str,
+        reason: &depc::Error,
+    ) -> Result<(), BridgeError> {
+        let reason = format!("{subject}: {reason}");
+        warn!("height {height} is unavailable on the DePC node, {reason}");
+        self.local_db
+            .mark_height_unavailable(height, &reason, self.clock.now())?;
+        Ok(())
+    }
+
+    /// Notify the worker without waiting, the action is picked from the database later when
+    /// the channel is full
+    fn notify_action(&self, index: usize, action: BridgeAction) -> Result<(), BridgeError> {
+        let sync_pair = &self.pairs[index];
+        let (tx, id, name) = match action {
+            BridgeAction::Deposit(id) => (&sync_pair.tx_deposit, id, ACTION_DEPOSIT),
+            BridgeAction::Withdraw(id) => (&sync_pair.tx_withdraw, id, ACTION_WITHDRAW),
+        };
+        match tx.try_send(id) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                debug!("{name} channel is full, pending action {id} is left in the database");
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(BridgeError::ChannelClosed(name)),
+        }
+    }
+}
+
+/// Call `f` again when it fails with a transient error, at most `MAX_ITEM_RETRIES` times
+async fn retry_transient<T, F>(mut f: F) -> Result<T, BridgeError>
+where
+    F: FnMut() -> Result<T, BridgeError>,
+{
+    let mut retries = 0;
+    loop {
+        match f() {
+            Err(e) if e.is_transient() && retries < MAX_ITEM_RETRIES => {
+                retries += 1;
+                debug!("retry {retries}, reason: {}", e.full_message());
+                sleep(ITEM_RETRY_INTERVAL).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// The custody addresses of all the eras of the pairs by the hex of their scripts, the outputs
+/// to the P2SH multisig custody come without the address from some nodes
+fn custody_scripts<C>(pairs: &[SyncPair<C>]) -> HashMap<String, DePCAddress> {
+    let mut scripts = HashMap::new();
+    for sync_pair in pairs.iter() {
+        let pair = &sync_pair.pair;
+        let addresses = std::iter::once(pair.depc_owner_address.as_str())
+            .chain(pair.custody_eras.retired_addresses());
+        for address in addresses {
+            match address_script_pubkey_hex(address) {
+                Ok(script) => {
+                    scripts.insert(script, address.to_owned());
+                }
+                Err(e) => warn!("the outputs to {address} are matched by the address only, {e}"),
+            }
+        }
+    }
+    scripts
+}
+
+/// The signatures of the withdrawal made by the output to a custody address, see
+/// `DepcSyncer::process_bridge_output`
+fn withdraw_signatures(value: DepcAmount, script_hex: &str) -> Option<Vec<Signature>> {
+    let script_data = extract_string_from_script_hex(script_hex).ok()?;
+    (value.is_zero() && !script_data.recipient.is_empty() && !script_data.signatures.is_empty())
+        .then_some(script_data.signatures)
+}
+
+fn parse_address<C: TokenClient>(address: &str) -> Result<C::Address, BridgeError> {
+    C::Address::from_str(address).map_err(|_| BridgeError::InvalidAddress(address.to_owned()))
+}
+
+/// Sync the blocks to the chain index of `local_db`, and process the outputs to the custody
+/// addresses of `pairs`, and to their deposit addresses when `deposit_addresses` is set
+#[allow(clippy::too_many_arguments)]
+pub async fn run_depc_syncing<C>(
+    exit_sig: Arc<Mutex<bool>>,
+    local_db: db::Conn,
+    depc_client: DePCClient,
+    pairs: Vec<SyncPair<C>>,
+    db_batch_size: u32,
+    deposit_intent_window: Option<Duration>,
+    deposit_addresses: bool,
+    refund_invalid_deposits: bool,
+    inflow_alert_amount: DepcAmount,
+    catch_up_config: Option<CatchUpConfig>,
+    clock: SharedClock,
+) -> Result<(), BridgeError>
+where
+    C: TokenClient + Send + Sync + 'static,
+    C::Error: Send + 'static,
+{
+    //TODO:1. As shown in Figure 4, a separate table (height(height int)) should be used to record the block height when scanning blocks; otherwise, as the data increases later, it may cause the system to freeze. As shown in Figure 5, the processed height should be written back to the database.
+    let mut sync_height = if let Some(height) = local_db.query_best_height() {
+        height + 1
+    } else {
+        0
+    };
+    let custody_scripts = custody_scripts(&pairs);
+    let syncer = DepcSyncer {
+        local_db,
+        depc_client,
+        pairs,
+        deposit_intent_window,
+        deposit_addresses,
+        refund_invalid_deposits,
+        inflow_alert_amount,
+        custody_scripts,
+        clock,
+    };
+
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        match syncer.process_withdraw_requests().await {
+            Ok(actions) => {
+                for (index, action) in actions {
+                    if let Err(e) = syncer.notify_action(index, action) {
+                        error!("syncing stops at the withdraw requests, reason: {e}");
+                        return Err(e);
+                    }
+                }
+            }
+            Err(e) => warn!(
+                "cannot process the withdraw requests, reason: {}",
+                e.full_message()
+            ),
+        }
+        let chain_height = match syncer.depc_client.get_height() {
+            Ok(chain_height) => chain_height,
+            Err(e) => {
+                warn!("cannot query the chain height, reason: {e}");
+                sleep(SYNC_RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+        if sync_height > 0 {
+            match syncer.find_fork_height(sync_height - 1, chain_height) {
+                Ok(None) => {}
+                Ok(Some(fork_height)) => {
+                    if let Err(e) = syncer.roll_back_reorg(sync_height - 1, fork_height) {
+                        warn!("cannot roll back the reorg, reason: {}", e.full_message());
+                        sleep(SYNC_RETRY_INTERVAL).await;
+                        continue;
+                    }
+                    sync_height = fork_height + 1;
+                }
+                Err(e @ BridgeError::ReorgTooDeep(_)) => {
+                    error!("syncing stops at height {sync_height}, reason: {e}");
+                    return Err(e);
+                }
+                Err(e) => {
+                    warn!(
+                        "cannot check the indexed blocks against the chain, reason: {}",
+                        e.full_message()
+                    );
+                    sleep(SYNC_RETRY_INTERVAL).await;
+                    continue;
+                }
+            }
+        }
+        if sync_height > chain_height {
+            if let Some(catch_up_config) = catch_up_config.as_ref() {
+                for sync_pair in syncer.pairs.iter() {
+                    if let Err(e) = finish_catch_up_indexing(
+                        &sync_pair.pair.conn,
+                        catch_up_config,
+                        sync_height - 1,
+                        syncer.clock.now(),
+                    ) {
+                        warn!("cannot plan the catch-up, reason: {}", e.full_message());
+                    }
+                }
+            }
+            // there is no more block left to sync, wait for 5 seconds...
+            sleep(SYNC_RETRY_INTERVAL).await;
+            continue;
+        }
+        info!(
+            "syncing from height {sync_height} to chain height {chain_height}, distance {}",
+            chain_height - sync_height
+        );
+        let batch_end = chain_height.min(sync_height.saturating_add(db_batch_size.max(1) - 1));
+        match syncer
+            .sync_blocks_in_transaction(sync_height, batch_end)
+            .await
+        {
+            Ok(actions) => {
+                for (index, action) in actions {
+                    if let Err(e) = syncer.notify_action(index, action) {
+                        error!("syncing stops at height {batch_end}, reason: {e}");
+                        return Err(e);
+                    }
+                }
+                sync_height = batch_end + 1;
+            }
+            Err(e) => {
+                // the blocks are rolled back, sync them again later
+                warn!(
+                    "cannot sync blocks {sync_height}..={batch_end}, retry later, reason: {}",
+                    e.full_message()
+                );
+                sleep(SYNC_RETRY_INTERVAL).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn record_shadow_action(
+    conn: &db::Conn,
+    timestamp: u64,
+    action: &str,
+    source_txid: &str,
+    recipient: &str,
+    amount: DepcAmount,
+) {
+    info!("shadow {action} of {amount} to {recipient}, source txid {source_txid}");
+    if let Err(e) = conn.add_shadow_action(action, source_txid, recipient, amount, timestamp) {
+        error!("cannot record shadow {action} for {source_txid}, reason: {e}");
+    }
+}
+
+#[cfg(test)]
 mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
     use super::*;
+    use crate::bridge::{
+        confirm_catch_up, register_deposit_address, set_deposit_xpub, sweep_fees, FeeSchedule,
+        CATCH_UP_AWAITING_CONFIRMATION, MAX_FEE_RATE_BPS,
+    };
+    use crate::depc::{make_script_hex, COIN};
+    use crate::ledger::{
+        ACCOUNT_BRIDGE_FEES, ACCOUNT_CUSTODY, ACCOUNT_DEPOSITS_DUE, ACCOUNT_WRAPPED_SUPPLY,
+    };
+    use crate::solana::RecoveredTransfer;
+    use crate::testing::{
+        make_transaction, FakeDepcNode, MockClock, MockError, MockTokenClient, MOCK_TRANSFER_FEE,
+    };
+
+    const TEST_DEPC_OWNER_ADDRESS: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_DEPC_RECIPIENT_ADDRESS: &str = "323nLaJTdaNjHMrtyVc7kXsDm448FJTmi9";
+    const TEST_NOW: u64 = 1731000000;
+    /// The 2-of-3 multisig script of the keys is the P2SH address
+    const TEST_MULTISIG_PUBKEYS: [&str; 3] = [
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    ];
+    const TEST_MULTISIG_CUSTODY_ADDRESS: &str = "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS";
+
+    /// Push the blocks carry a deposit to `solana_recipient_address` and a withdrawal verified
+    /// by `withdraw_signature`, returns (deposit txid, withdraw txid)
+    fn push_bridge_blocks(
+        node: &FakeDepcNode,
+        solana_recipient_address: &Pubkey,
+        withdraw_signature: &Signature,
+    ) -> (String, String) {
+        // block 0 is never scanned, the bridge transactions are packed in block 1
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let deposit_txid = format!("{:064x}", 0xd1);
+        let withdraw_txid = format!("{:064x}", 0xd2);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                make_transaction(
+                    &deposit_txid,
+                    &[],
+                    &[(
+                        TEST_DEPC_OWNER_ADDRESS,
+                        5 * COIN,
+                        &make_script_hex(&solana_recipient_address.to_string()),
+                    )],
+                ),
+                make_transaction(
+                    &withdraw_txid,
+                    &[],
+                    &[(
+                        TEST_DEPC_OWNER_ADDRESS,
+                        0,
+                        &make_script_hex(&format!(
+                            "{}:{}",
+                            TEST_DEPC_RECIPIENT_ADDRESS, withdraw_signature
+                        )),
+                    )],
+                ),
+            ],
+        );
+        (deposit_txid, withdraw_txid)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposit_and_withdraw_offline() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_owner_address = Pubkey::new_unique();
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+
+        let (deposit_txid, _) =
+            push_bridge_blocks(&node, &solana_recipient_address, &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            solana_owner_address.to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        // wait until both of the deposit and the withdrawal are processed
+        for _ in 0..100 {
+            let deposited = conn
+                .query_deposit(&deposit_txid)
+                .unwrap()
+                .is_some_and(|deposit| deposit.erc20_txid.is_some());
+            if deposited && !node.broadcasted_transactions().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, solana_recipient_address);
+        assert_eq!(sent_tokens[0].1, 5 * COIN);
+        let deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(deposit.erc20_txid, Some(sent_tokens[0].2.to_string()));
+        assert_eq!(deposit.erc20_timestamp, Some(TEST_NOW));
+        let receipt = deposit.mint_receipt.unwrap();
+        assert_eq!(receipt.fee, Some(MOCK_TRANSFER_FEE));
+        assert!(receipt.created_ata);
+        assert_eq!(receipt.raw_logs, Some(vec![]));
+        // the lamports of the mint are in the event feed, not in the accounts
+        let mint_fees: Vec<_> = conn
+            .query_events(0, 10)
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.event == "mint_fee")
+            .map(|event| (event.reference, event.amount.value64()))
+            .collect();
+        assert_eq!(
+            mint_fees,
+            vec![(sent_tokens[0].2.to_string(), MOCK_TRANSFER_FEE)]
+        );
+        assert_eq!(node.broadcasted_transactions().len(), 1);
+        assert!(conn
+            .query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+            .unwrap()
+            .is_empty());
+        let trial_balance = Ledger::new(conn.clone()).trial_balance().unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_WRAPPED_SUPPLY),
+            -((2 * COIN) as i128)
+        );
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_CUSTODY),
+            (2 * COIN - DEFAULT_TX_FEE) as i128
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_amounts_converted_with_mint_decimals() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        token_client.set_mint_decimals(6);
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        // 3 DePC in the token of 6 decimals
+        token_client.set_verify_result(withdraw_signature, Ok(3_000_000));
+        let (deposit_txid, withdraw_txid) =
+            push_bridge_blocks(&node, &solana_recipient_address, &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() && !node.broadcasted_transactions().is_empty()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].1, 5_000_000);
+        let withdraw = conn
+            .query_pending_actions_between(0, u64::MAX)
+            .unwrap()
+            .into_iter()
+            .find(|action| action.source_txid == withdraw_txid)
+            .unwrap();
+        assert_eq!(withdraw.amount, DepcAmount::from_value64(3 * COIN));
+
+        // the decimals of DePC cannot be converted
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_depc_decimals(20);
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposit_rounded_to_mint_decimals() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        token_client.set_mint_decimals(6);
+        let solana_recipient_address = Pubkey::new_unique();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let deposit_txid = format!("{:064x}", 0xd1);
+        let dust_txid = format!("{:064x}", 0xd3);
+        let script_hex = make_script_hex(&solana_recipient_address.to_string());
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                make_transaction(
+                    &deposit_txid,
+                    &[],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN + 55, &script_hex)],
+                ),
+                // 50 value64 is left after the fee, nothing to mint after the rounding
+                make_transaction(
+                    &dust_txid,
+                    &[],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 1050, &script_hex)],
+                ),
+            ],
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let fee_config = FeeConfig {
+            deposit: FeeSchedule {
+                flat: DepcAmount::from_value64(1000),
+                rate_bps: 0,
+            },
+            ..Default::default()
+        };
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_fee_config(fee_config)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // the rest under 100 value64 is kept as the bridge fee
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].1, 5_000_000 - 10);
+        let fees = conn.query_transfer_fees(10).unwrap();
+        assert_eq!(fees.len(), 1);
+        assert_eq!(
+            (fees[0].amount.value64(), fees[0].fee.value64()),
+            (5 * COIN + 55, 1055)
+        );
+        let ledger = Ledger::new(conn.clone());
+        assert_eq!(
+            ledger.unswept_fees().unwrap(),
+            DepcAmount::from_value64(1055)
+        );
+        assert!(ledger.trial_balance().unwrap().is_balanced());
+        // nothing is left to mint, it's kept as an inflow instead of dead-lettered
+        assert!(conn.query_deposit(&dust_txid).unwrap().is_none());
+        let inflows = conn.query_unclassified_custody_inflows(10).unwrap();
+        assert_eq!(inflows.len(), 1);
+        assert_eq!(
+            (inflows[0].txid.as_str(), inflows[0].reason.as_str()),
+            (dust_txid.as_str(), INFLOW_BELOW_MINIMUM)
+        );
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_bridge_fees_charged() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        token_client.set_mint_decimals(6);
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3_000_000));
+        let (deposit_txid, withdraw_txid) =
+            push_bridge_blocks(&node, &solana_recipient_address, &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let fee_config = FeeConfig {
+            deposit: FeeSchedule {
+                flat: DepcAmount::from_value64(1000),
+                rate_bps: 30,
+            },
+            withdraw: FeeSchedule {
+                flat: DepcAmount::ZERO,
+                rate_bps: 10,
+            },
+        };
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_fee_config(fee_config)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() && !node.broadcasted_transactions().is_empty()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // 5 DePC less 1000 + 1_500_000 value64, minted in the token of 6 decimals
+        let deposit_fee = 1_501_000;
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].1, (5 * COIN - deposit_fee) / 100);
+        let deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(deposit.amount, DepcAmount::from_value64(5 * COIN));
+        let withdraw_fee = 300_000;
+        let withdraw = conn
+            .query_pending_actions_between(0, u64::MAX)
+            .unwrap()
+            .into_iter()
+            .find(|action| action.source_txid == withdraw_txid)
+            .unwrap();
+        assert_eq!(
+            withdraw.amount,
+            DepcAmount::from_value64(3 * COIN - withdraw_fee)
+        );
+
+        let fees = conn.query_transfer_fees(10).unwrap();
+        assert_eq!(fees.len(), 2);
+        let charged: Vec<_> = fees
+            .iter()
+            .rev()
+            .map(|fee| (fee.action.as_str(), fee.amount.value64(), fee.fee.value64()))
+            .collect();
+        assert_eq!(
+            charged,
+            vec![
+                (ACTION_DEPOSIT, 5 * COIN, deposit_fee),
+                (ACTION_WITHDRAW, 3 * COIN, withdraw_fee)
+            ]
+        );
+        let trial_balance = Ledger::new(conn.clone()).trial_balance().unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_BRIDGE_FEES),
+            -((deposit_fee + withdraw_fee) as i128)
+        );
+        assert_eq!(trial_balance.balance_of(ACCOUNT_DEPOSITS_DUE), 0);
+
+        // the rate is over all of the amount
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_fee_config(FeeConfig {
+            withdraw: FeeSchedule {
+                flat: DepcAmount::ZERO,
+                rate_bps: MAX_FEE_RATE_BPS + 1,
+            },
+            ..FeeConfig::default()
+        });
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_bridge_transfer_held() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let (deposit_txid, _) =
+            push_bridge_blocks(&node, &Pubkey::new_unique(), &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_transfer_limits(TransferLimits {
+            min: DepcAmount::from_value64(COIN),
+            max: Some(DepcAmount::from_value64(4 * COIN)),
+            daily_cap: None,
+        })
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if !node.broadcasted_transactions().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // the withdrawal of 3 DePC is paid out, the deposit of 5 DePC is held
+        assert_eq!(node.broadcasted_transactions().len(), 1);
+        assert!(token_client.sent_tokens().is_empty());
+        let held = conn.query_held_pending_actions(10).unwrap();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].action, ACTION_DEPOSIT);
+        assert_eq!(held[0].source_txid, deposit_txid);
+        assert_eq!(held[0].amount, DepcAmount::from_value64(5 * COIN));
+        assert!(conn
+            .claim_pending_action(held[0].id, TEST_NOW)
+            .unwrap()
+            .is_none());
+        // the custody is credited as usual, only the mint waits
+        assert!(conn.query_deposit(&deposit_txid).unwrap().is_some());
+
+        // it's made after it's released
+        assert!(conn
+            .release_held_pending_action(held[0].id, TEST_NOW)
+            .unwrap());
+        assert!(!conn
+            .release_held_pending_action(held[0].id, TEST_NOW)
+            .unwrap());
+        assert!(conn.query_held_pending_actions(10).unwrap().is_empty());
+        assert!(conn
+            .claim_pending_action(held[0].id, TEST_NOW)
+            .unwrap()
+            .is_some());
+
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_transfer_limits(TransferLimits {
+            min: DepcAmount::from_value64(COIN),
+            max: Some(DepcAmount::ZERO),
+            daily_cap: None,
+        });
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_index_only_mode() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let (deposit_txid, _) =
+            push_bridge_blocks(&node, &Pubkey::new_unique(), &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_mode(RunMode::IndexOnly)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if conn.query_best_height() == Some(1) {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // the blocks are indexed, but the bridge outputs are left alone
+        assert_eq!(conn.query_best_height(), Some(1));
+        assert!(conn
+            .query_pending_actions_between(0, u64::MAX)
+            .unwrap()
+            .is_empty());
+        assert!(conn.query_deposit(&deposit_txid).unwrap().is_none());
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+        assert!(token_client.sent_tokens().is_empty());
+        assert!(node.broadcasted_transactions().is_empty());
+        assert_eq!("index-only".parse(), Ok(RunMode::IndexOnly));
+        assert!("explorer".parse::<RunMode>().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_catch_up_awaits_confirmation() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let (deposit_txid, _) =
+            push_bridge_blocks(&node, &solana_recipient_address, &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_catch_up_config(CatchUpConfig {
+            min_blocks: 2,
+            confirm_actions: 1,
+            confirm_amount: DepcAmount::from_value64(100 * COIN),
+        })
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        let mut plan = None;
+        for _ in 0..100 {
+            plan = conn
+                .query_latest_catch_up_plan()
+                .unwrap()
+                .filter(|plan| plan.planned_at.is_some());
+            if plan.is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        let plan = plan.unwrap();
+        assert_eq!((plan.from_height, plan.to_height), (0, 1));
+        assert_eq!(plan.status, CATCH_UP_AWAITING_CONFIRMATION);
+        assert_eq!((plan.deposits, plan.withdrawals), (1, 1));
+        assert_eq!(plan.deposit_amount, DepcAmount::from_value64(5 * COIN));
+        // nothing is made before the operator confirms the plan
+        sleep(Duration::from_millis(500)).await;
+        assert!(token_client.sent_tokens().is_empty());
+        assert!(node.broadcasted_transactions().is_empty());
+
+        confirm_catch_up(&conn, TEST_NOW).unwrap().unwrap();
+        for _ in 0..200 {
+            if !token_client.sent_tokens().is_empty() && !node.broadcasted_transactions().is_empty()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+        assert_eq!(token_client.sent_tokens().len(), 1);
+        assert_eq!(node.broadcasted_transactions().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_rolls_back_reorg() {
+        let node = FakeDepcNode::start();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        // no worker picks the deposits, they're left pending
+        let (tx_deposit, _rx_deposit) = channel::<PendingActionId>(10);
+        let (tx_withdraw, _rx_withdraw) = channel::<PendingActionId>(10);
+        let syncing_task = tokio::spawn(run_depc_syncing(
+            Arc::clone(&exit_sig),
+            conn.clone(),
+            node.client(),
+            vec![SyncPair {
+                pair: BridgePair {
+                    conn: conn.clone(),
+                    depc_owner_address: TEST_DEPC_OWNER_ADDRESS.to_owned(),
+                    solana_owner_address: Pubkey::new_unique().to_string(),
+                    contract_client: MockTokenClient::new(),
+                    custody_eras: CustodyEras::default(),
+                    converter: AmountConverter::default(),
+                    fees: FeeConfig::default(),
+                    limits: TransferLimits::default(),
+                },
+                tx_deposit,
+                tx_withdraw,
+            }],
+            1,
+            None,
+            false,
+            false,
+            DepcAmount::ZERO,
+            None,
+            Arc::new(MockClock::new(TEST_NOW)),
+        ));
+        let wait_for_height = |height: u32| {
+            let conn = conn.clone();
+            async move {
+                for _ in 0..100 {
+                    if conn.query_best_height() == Some(height) {
+                        return;
+                    }
+                    sleep(Duration::from_millis(100)).await;
+                }
+                panic!("height {height} is never synced");
+            }
+        };
+
+        let deposit_txid = format!("{:064x}", 0xd1);
+        let deposit = make_transaction(
+            &deposit_txid,
+            &[],
+            &[(
+                TEST_DEPC_OWNER_ADDRESS,
+                5 * COIN,
+                &make_script_hex(&Pubkey::new_unique().to_string()),
+            )],
+        );
+        let orphaned_txid = format!("{:064x}", 0xa1);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let orphaned_hash = node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![deposit.clone()]);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &orphaned_txid,
+                &[],
+                &[(TEST_DEPC_RECIPIENT_ADDRESS, COIN, "")],
+            )],
+        );
+        wait_for_height(2).await;
+        let orphaned_deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // the deposit is packed in a later block of the longer chain
+        node.reorg(1);
+        let new_hash = node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![deposit]);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        wait_for_height(3).await;
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        syncing_task.await.unwrap().unwrap();
+
+        assert_ne!(new_hash, orphaned_hash);
+        assert_eq!(conn.query_block_hash_by_height(1).unwrap(), Some(new_hash));
+        assert_eq!(conn.query_transaction_height(&orphaned_txid).unwrap(), None);
+        assert_eq!(
+            conn.query_address_balance(TEST_DEPC_RECIPIENT_ADDRESS)
+                .unwrap(),
+            DepcAmount::default()
+        );
+        // the deposit is made again from the new block only once
+        assert_eq!(
+            conn.query_transaction_height(&deposit_txid).unwrap(),
+            Some(2)
+        );
+        let deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert!(deposit.depc_timestamp > orphaned_deposit.depc_timestamp);
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+                .unwrap()
+                .len(),
+            1
+        );
+        let trial_balance = Ledger::new(conn.clone()).trial_balance().unwrap();
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_CUSTODY),
+            (5 * COIN) as i128
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_skips_recorded_outputs() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        // no worker picks the actions, they're left pending
+        let (tx_deposit, _rx_deposit) = channel::<PendingActionId>(10);
+        let (tx_withdraw, _rx_withdraw) = channel::<PendingActionId>(10);
+        let syncing_task = tokio::spawn(run_depc_syncing(
+            Arc::clone(&exit_sig),
+            conn.clone(),
+            node.client(),
+            vec![SyncPair {
+                pair: BridgePair {
+                    conn: conn.clone(),
+                    depc_owner_address: TEST_DEPC_OWNER_ADDRESS.to_owned(),
+                    solana_owner_address: Pubkey::new_unique().to_string(),
+                    contract_client: token_client,
+                    custody_eras: CustodyEras::default(),
+                    converter: AmountConverter::default(),
+                    fees: FeeConfig::default(),
+                    limits: TransferLimits::default(),
+                },
+                tx_deposit,
+                tx_withdraw,
+            }],
+            1,
+            None,
+            false,
+            false,
+            DepcAmount::ZERO,
+            None,
+            Arc::new(MockClock::new(TEST_NOW)),
+        ));
+        let wait_for_height = |height: u32| {
+            let conn = conn.clone();
+            async move {
+                for _ in 0..100 {
+                    if conn.query_best_height() == Some(height) {
+                        return;
+                    }
+                    sleep(Duration::from_millis(100)).await;
+                }
+                panic!("height {height} is never synced");
+            }
+        };
+
+        let (deposit_txid, withdraw_txid) =
+            push_bridge_blocks(&node, &Pubkey::new_unique(), &withdraw_signature);
+        wait_for_height(1).await;
+        let withdraw_ids = conn
+            .query_unclaimed_pending_action_ids(ACTION_WITHDRAW)
+            .unwrap();
+        assert_eq!(withdraw_ids.len(), 1);
+        let recorded = conn
+            .query_recorded_transaction(&withdraw_txid, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (recorded.action.as_str(), recorded.action_id),
+            (ACTION_WITHDRAW, withdraw_ids[0])
+        );
+        let digests = conn.query_block_digests(0, 10).unwrap();
+        assert_eq!(digests.len(), 1);
+        assert_eq!((digests[0].height, digests[0].event_count), (1, 2));
+
+        // the block is scanned again after a reorg, the withdrawal is kept and never made
+        // again, the deposit not picked yet is removed and made again
+        let transactions = [&deposit_txid, &withdraw_txid].map(|txid| {
+            let transaction = node.client().get_transaction(txid).unwrap();
+            make_transaction(
+                txid,
+                &[],
+                &[(
+                    TEST_DEPC_OWNER_ADDRESS,
+                    transaction.vout[0].value64,
+                    &transaction.vout[0].script_pubkey.hex,
+                )],
+            )
+        });
+        node.reorg(1);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, transactions.to_vec());
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        wait_for_height(2).await;
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        syncing_task.await.unwrap().unwrap();
+
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids(ACTION_WITHDRAW)
+                .unwrap(),
+            withdraw_ids
+        );
+        let deposit_ids = conn
+            .query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+            .unwrap();
+        assert_eq!(deposit_ids.len(), 1);
+        assert_eq!(
+            conn.query_recorded_transaction(&deposit_txid, 0)
+                .unwrap()
+                .map(|recorded| recorded.action_id),
+            Some(deposit_ids[0])
+        );
+
+        // the recorded outputs are still the events of the block, the digest is made of the
+        // block on the chain
+        let block_hash = node.client().get_block_hash(1).unwrap();
+        let events = [&deposit_txid, &withdraw_txid].map(|txid| {
+            let txout = &node.client().get_transaction(txid).unwrap().vout[0];
+            BlockEvent::new(
+                txid,
+                0,
+                TEST_DEPC_OWNER_ADDRESS,
+                DepcAmount::from_value64(txout.value64),
+                &txout.script_pubkey.hex,
+            )
+        });
+        let digests = conn.query_block_digests(0, 10).unwrap();
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests[0].block_hash, block_hash);
+        assert_eq!(
+            digests[0].digest,
+            hex::encode(digest_block_events(1, &block_hash, &events))
+        );
+        assert_eq!((digests[1].height, digests[1].event_count), (2, 0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposits_of_multiple_pairs() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let other_token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let other_solana_recipient_address = Pubkey::new_unique();
+        // a transaction pays to the custody addresses of both pairs
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let deposit_txid = format!("{:064x}", 0xd1);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &deposit_txid,
+                &[],
+                &[
+                    (
+                        TEST_DEPC_OWNER_ADDRESS,
+                        5 * COIN,
+                        &make_script_hex(&solana_recipient_address.to_string()),
+                    ),
+                    (
+                        TEST_DEPC_RECIPIENT_ADDRESS,
+                        7 * COIN,
+                        &make_script_hex(&other_solana_recipient_address.to_string()),
+                    ),
+                ],
+            )],
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let other_conn = conn.scoped("other");
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .add_pair(
+            "other",
+            TEST_DEPC_RECIPIENT_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            other_token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        let deposited = |conn: &db::Conn| {
+            conn.query_deposit(&deposit_txid)
+                .unwrap()
+                .is_some_and(|deposit| deposit.erc20_txid.is_some())
+        };
+        for _ in 0..100 {
+            if deposited(&conn) && deposited(&other_conn) {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // each pair mints its own token for the output to its custody address
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, solana_recipient_address);
+        assert_eq!(sent_tokens[0].1, 5 * COIN);
+        let sent_tokens = other_token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, other_solana_recipient_address);
+        assert_eq!(sent_tokens[0].1, 7 * COIN);
+        let deposit = other_conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(deposit.amount, DepcAmount::from_value64(7 * COIN));
+        assert_eq!(deposit.erc20_txid, Some(sent_tokens[0].2.to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_custody_address_rotation() {
+        const OLD_OWNER_ADDRESS: &str = "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc";
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let deposit = |txid: &str, address: &str| {
+            make_transaction(
+                txid,
+                &[],
+                &[(
+                    address,
+                    5 * COIN,
+                    &make_script_hex(&solana_recipient_address.to_string()),
+                )],
+            )
+        };
+        // the old address is watched until block 1, a deposit and a withdrawal are sent to it
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let withdraw = make_transaction(
+            &format!("{:064x}", 0xd2),
+            &[],
+            &[(
+                OLD_OWNER_ADDRESS,
+                0,
+                &make_script_hex(&format!(
+                    "{TEST_DEPC_RECIPIENT_ADDRESS}:{withdraw_signature}"
+                )),
+            )],
+        );
+        let block_time = node
+            .client()
+            .get_block(&node.push_block(
+                TEST_DEPC_OWNER_ADDRESS,
+                vec![
+                    deposit(&format!("{:064x}", 0xd1), OLD_OWNER_ADDRESS),
+                    withdraw,
+                ],
+            ))
+            .unwrap()
+            .time;
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                deposit(&format!("{:064x}", 0xd3), OLD_OWNER_ADDRESS),
+                deposit(&format!("{:064x}", 0xd4), TEST_DEPC_OWNER_ADDRESS),
+            ],
+        );
+        node.add_unspent(OLD_OWNER_ADDRESS, &format!("{:064x}", 0xe1), 0, 5 * COIN);
+        node.add_unspent(
+            TEST_DEPC_OWNER_ADDRESS,
+            &format!("{:064x}", 0xe2),
+            0,
+            5 * COIN,
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        rotate_custody_address(&conn, OLD_OWNER_ADDRESS, Duration::ZERO, block_time - 600).unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_custody_overlap(Duration::from_secs(600))
+        .set_clock(Arc::new(MockClock::new(block_time - 600)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if token_client.sent_tokens().len() == 2 && !node.broadcasted_transactions().is_empty()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // the deposit to the old address after the overlap is not credited
+        assert!(conn
+            .query_deposit(&format!("{:064x}", 0xd3))
+            .unwrap()
+            .is_none());
+        let eras: Vec<_> = conn
+            .query_pending_actions_between(0, TEST_NOW)
+            .unwrap()
+            .into_iter()
+            .map(|action| (action.source_txid, action.custody_era))
+            .collect();
+        assert_eq!(
+            eras,
+            vec![
+                (format!("{:064x}", 0xd1), Some(1)),
+                (format!("{:064x}", 0xd2), Some(1)),
+                (format!("{:064x}", 0xd4), Some(2))
+            ]
+        );
+        // the withdrawal received by the old address is paid from the new one
+        let payouts = node.broadcasted_transactions();
+        assert_eq!(payouts.len(), 1);
+        let payout = String::from_utf8(hex::decode(&payouts[0]).unwrap()).unwrap();
+        assert!(payout.contains(&format!("{:064x}", 0xe2)));
+        assert!(!payout.contains(&format!("{:064x}", 0xe1)));
+    }
+
+    #[tokio::test]
+    async fn test_pairs_are_unique() {
+        let node = FakeDepcNode::start();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let bridge = Bridge::new(
+            conn,
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        )
+        .add_pair(
+            "other",
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        );
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_multisig_custody_script_recognized() {
+        let node = FakeDepcNode::start();
+        let multisig = MultisigScript::new(2, &TEST_MULTISIG_PUBKEYS.map(str::to_owned)).unwrap();
+        // the custody address must be the P2SH address of the script
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let bridge = Bridge::new(
+            conn,
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        )
+        .set_custody_multisig(multisig.clone());
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidConfig(_))
+        ));
+
+        // the node doesn't decode the address of the output
+        node.push_block(TEST_MULTISIG_CUSTODY_ADDRESS, vec![]);
+        let txid = format!("{:064x}", 0xe1);
+        node.push_block(
+            TEST_MULTISIG_CUSTODY_ADDRESS,
+            vec![make_transaction(
+                &txid,
+                &[],
+                &[("", 5 * COIN, &multisig.script_pubkey_hex())],
+            )],
+        );
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_MULTISIG_CUSTODY_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        )
+        .set_custody_multisig(multisig)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        for _ in 0..100 {
+            if conn.query_best_height() == Some(1) {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let coins = conn
+            .query_unspent_coins(TEST_MULTISIG_CUSTODY_ADDRESS)
+            .unwrap();
+        assert_eq!(coins.len(), 1);
+        assert_eq!(coins[0].txid, txid);
+        assert_eq!(coins[0].value, DepcAmount::from_value64(5 * COIN));
+    }
+
+    #[tokio::test]
+    async fn test_depc_node_checked_before_syncing() {
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.add_invalid_address(TEST_DEPC_OWNER_ADDRESS);
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        );
+        assert!(matches!(
+            bridge.run().await,
+            Err(BridgeError::InvalidAddress(_))
+        ));
+
+        // nothing is synced while the node is in the initial block download
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.set_initial_block_download(true);
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            MockTokenClient::new(),
+        )
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+        sleep(Duration::from_millis(200)).await;
+        *exit_sig.lock().unwrap() = true;
+        bridge_handler.await.unwrap().unwrap();
+        assert_eq!(conn.query_best_height(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pending_actions_left_by_last_run() {
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        // the last run stopped while the deposit was being processed
+        let deposit_txid = format!("{:064x}", 0xd1);
+        conn.add_coin(
+            &deposit_txid,
+            0,
+            DepcAmount::from_value64(5 * COIN),
+            TEST_DEPC_OWNER_ADDRESS,
+            "",
+        )
+        .unwrap();
+        conn.save_deposit(
+            &deposit_txid,
+            &solana_recipient_address.to_string(),
+            DepcAmount::from_value64(5 * COIN),
+            TEST_NOW,
+        )
+        .unwrap();
+        let id = conn
+            .add_pending_action(
+                ACTION_DEPOSIT,
+                &deposit_txid,
+                &solana_recipient_address.to_string(),
+                DepcAmount::from_value64(5 * COIN),
+                TEST_NOW,
+            )
+            .unwrap();
+        conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap();
+
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        // nothing is notified, the worker finds the action from the database
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, solana_recipient_address);
+        let deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(deposit.erc20_txid, Some(sent_tokens[0].2.to_string()));
+        assert!(conn.claim_pending_action(id, TEST_NOW).unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposits_minted_in_batches() {
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let token_client = MockTokenClient::new();
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mut deposits = vec![];
+        for i in 0..3u64 {
+            let deposit_txid = format!("{:064x}", 0xd1 + i);
+            let recipient = Pubkey::new_unique();
+            let amount = DepcAmount::from_value64((i + 1) * COIN);
+            conn.add_coin(&deposit_txid, 0, amount, TEST_DEPC_OWNER_ADDRESS, "")
+                .unwrap();
+            conn.save_deposit(&deposit_txid, &recipient.to_string(), amount, TEST_NOW)
+                .unwrap();
+            conn.add_pending_action(
+                ACTION_DEPOSIT,
+                &deposit_txid,
+                &recipient.to_string(),
+                amount,
+                TEST_NOW,
+            )
+            .unwrap();
+            deposits.push(deposit_txid);
+        }
+
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_pipeline_config(PipelineConfig {
+            mint_batch_size: 2,
+            ..Default::default()
+        })
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if token_client.sent_tokens().len() == 3 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        // the first two are minted by one transaction, the last one by another
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 3);
+        assert_eq!(sent_tokens[0].2, sent_tokens[1].2);
+        assert_ne!(sent_tokens[1].2, sent_tokens[2].2);
+        for (deposit_txid, sent) in deposits.iter().zip(sent_tokens.iter()) {
+            let deposit = conn.query_deposit(deposit_txid).unwrap().unwrap();
+            assert_eq!(deposit.erc20_txid, Some(sent.2.to_string()));
+            assert_eq!(deposit.amount.value64(), sent.1);
+        }
+        // the ledger posts one entry for each transaction
+        let entries = conn.query_ledger_entries(10).unwrap();
+        let minted: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.event == "wrapped_minted")
+            .map(|entry| (entry.reference.clone(), entry.amount.value64()))
+            .collect();
+        assert_eq!(
+            minted,
+            vec![
+                (sent_tokens[2].2.to_string(), 3 * COIN),
+                (sent_tokens[0].2.to_string(), 3 * COIN),
+            ]
+        );
+        let trial_balance = Ledger::new(conn.clone()).trial_balance().unwrap();
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_WRAPPED_SUPPLY),
+            -((6 * COIN) as i128)
+        );
+    }
+
+    #[test]
+    fn test_mint_refused_deposit_dead_lettered() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock: SharedClock = Arc::new(MockClock::new(TEST_NOW));
+        let token_client = MockTokenClient::new();
+        let retry_config = MintRetryConfig::default();
+        let mut items = vec![];
+        for i in 0..3u64 {
+            let deposit_txid = format!("{:064x}", 0xd1 + i);
+            let recipient = Pubkey::new_unique();
+            let amount = DepcAmount::from_value64(COIN);
+            conn.save_deposit(&deposit_txid, &recipient.to_string(), amount, TEST_NOW)
+                .unwrap();
+            let id = conn
+                .add_pending_action(
+                    ACTION_DEPOSIT,
+                    &deposit_txid,
+                    &recipient.to_string(),
+                    amount,
+                    TEST_NOW,
+                )
+                .unwrap();
+            let deposit = conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap();
+            items.push(MintItem::<MockTokenClient> {
+                id,
+                deposit,
+                recipient_address: recipient,
+                amount: COIN,
+            });
+        }
+        let third = items.pop().unwrap();
+
+        // the batch is refused for the second recipient, the first is minted alone
+        token_client.push_send_result(Err(MockError::permanent("invalid account owner")));
+        token_client.push_send_result(Ok(Signature::new_unique()));
+        token_client.push_send_result(Err(MockError::permanent("invalid account owner")));
+        mint_deposits(&token_client, &conn, items, &retry_config, &clock).unwrap();
+        assert_eq!(token_client.sent_tokens().len(), 1);
+        let deposit = conn
+            .query_deposit(&format!("{:064x}", 0xd1))
+            .unwrap()
+            .unwrap();
+        assert!(deposit.erc20_txid.is_some());
+        let letters = conn.query_dead_letters(10).unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].txid, format!("{:064x}", 0xd2));
+        assert_eq!(
+            letters[0].reason,
+            "token client error: mock error: invalid account owner"
+        );
+        assert!(conn
+            .query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+            .unwrap()
+            .is_empty());
+
+        // the node is busy, the deposit is released for a retry
+        token_client.push_send_result(Err(MockError::transient("node is busy")));
+        let third_id = third.id;
+        mint_deposits(&token_client, &conn, vec![third], &retry_config, &clock).unwrap();
+        assert_eq!(conn.query_dead_letters(10).unwrap().len(), 1);
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+                .unwrap(),
+            vec![third_id]
+        );
+    }
+
+    #[test]
+    fn test_record_mint_atomic() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mut deposits = vec![];
+        for i in 0..3u64 {
+            let id = conn
+                .add_pending_action(
+                    ACTION_DEPOSIT,
+                    &format!("{:064x}", 0xd1 + i),
+                    &Pubkey::new_unique().to_string(),
+                    DepcAmount::from_value64(i64::MAX as u64),
+                    TEST_NOW,
+                )
+                .unwrap();
+            deposits.push(conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap());
+        }
+        // the total overflows after the deposits are completed, none of them is kept
+        assert!(matches!(
+            record_mint(&conn, &deposits, "mint_txid", None, TEST_NOW),
+            Err(BridgeError::AmountOverflow)
+        ));
+        for deposit in deposits.iter() {
+            let action = conn.query_pending_action(deposit.id).unwrap().unwrap();
+            assert_eq!(action.txid, None);
+        }
+        assert!(conn.query_events(0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recover_payouts() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock: SharedClock = Arc::new(MockClock::new(TEST_NOW));
+        let mut withdrawals = vec![];
+        for i in 0..2u64 {
+            let id = conn
+                .add_pending_action(
+                    ACTION_WITHDRAW,
+                    &format!("{:064x}", 0xd1 + i),
+                    TEST_DEPC_RECIPIENT_ADDRESS,
+                    DepcAmount::from_value64(COIN),
+                    TEST_NOW,
+                )
+                .unwrap();
+            withdrawals.push(conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap());
+        }
+        // the first payout is broadcast before the process stopped, the second is not
+        let sent_txid = format!("{:064x}", 0xe1);
+        node.add_mempool_transaction(make_transaction(
+            &sent_txid,
+            &[],
+            &[(TEST_DEPC_RECIPIENT_ADDRESS, COIN, "")],
+        ));
+        conn.add_depc_payout(withdrawals[0].id, &sent_txid, "signed1", TEST_NOW)
+            .unwrap();
+        let unsent_txid = format!("{:064x}", 0xe2);
+        conn.add_depc_payout(withdrawals[1].id, &unsent_txid, "signed2", TEST_NOW)
+            .unwrap();
+        assert_eq!(conn.release_claimed_pending_actions().unwrap(), 0);
+
+        recover_payouts(&conn, &client, &clock).unwrap();
+        assert_eq!(node.broadcasted_transactions(), vec!["signed2".to_owned()]);
+        assert!(conn.query_signed_depc_payouts().unwrap().is_empty());
+        for (withdraw, txid) in withdrawals.iter().zip([sent_txid, unsent_txid]) {
+            let action = conn.query_pending_action(withdraw.id).unwrap().unwrap();
+            assert_eq!(action.txid, Some(txid));
+        }
+        assert!(conn
+            .query_unclaimed_pending_action_ids(ACTION_WITHDRAW)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mint_retry_backoff() {
+        let retry_config = MintRetryConfig {
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(20),
+            max_attempts: Some(4),
+        };
+        retry_config.validate().unwrap();
+        let delays: Vec<u64> = [1, 2, 3, 4, 100]
+            .iter()
+            .map(|attempts| retry_config.delay(*attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![5, 10, 20, 20, 20]);
+        for invalid in [
+            MintRetryConfig {
+                max_attempts: Some(0),
+                ..retry_config
+            },
+            MintRetryConfig {
+                base_delay: Duration::ZERO,
+                ..retry_config
+            },
+            MintRetryConfig {
+                max_delay: Duration::from_secs(1),
+                ..retry_config
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mock_clock = MockClock::new(TEST_NOW);
+        let clock: SharedClock = Arc::new(mock_clock.clone());
+        let token_client = MockTokenClient::new();
+        let deposit_txid = format!("{:064x}", 0xd1);
+        let recipient = Pubkey::new_unique();
+        let amount = DepcAmount::from_value64(COIN);
+        conn.save_deposit(&deposit_txid, &recipient.to_string(), amount, TEST_NOW)
+            .unwrap();
+        let id = conn
+            .add_pending_action(
+                ACTION_DEPOSIT,
+                &deposit_txid,
+                &recipient.to_string(),
+                amount,
+                TEST_NOW,
+            )
+            .unwrap();
+
+        // the deposit is not claimed again before the delay, it's doubled by each failure
+        for (attempts, delay) in [(1, 5), (2, 10), (3, 20)] {
+            let deposit = conn.claim_pending_action(id, clock.now()).unwrap().unwrap();
+            token_client.push_send_result(Err(MockError::transient("node is busy")));
+            let item = MintItem::<MockTokenClient> {
+                id,
+                deposit,
+                recipient_address: recipient,
+                amount: COIN,
+            };
+            mint_deposits(&token_client, &conn, vec![item], &retry_config, &clock).unwrap();
+            assert_eq!(conn.query_pending_action_attempts(id).unwrap(), attempts);
+            mock_clock.advance(delay - 1);
+            assert!(conn
+                .claim_pending_action(id, clock.now())
+                .unwrap()
+                .is_none());
+            mock_clock.advance(1);
+        }
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+
+        // it's given up after the max attempts
+        let deposit = conn.claim_pending_action(id, clock.now()).unwrap().unwrap();
+        token_client.push_send_result(Err(MockError::transient("node is busy")));
+        let item = MintItem::<MockTokenClient> {
+            id,
+            deposit,
+            recipient_address: recipient,
+            amount: COIN,
+        };
+        mint_deposits(&token_client, &conn, vec![item], &retry_config, &clock).unwrap();
+        let letters = conn.query_dead_letters(10).unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].txid, deposit_txid);
+        assert!(conn
+            .query_unclaimed_pending_action_ids(ACTION_DEPOSIT)
+            .unwrap()
+            .is_empty());
+        assert!(token_client.sent_tokens().is_empty());
+    }
+
+    #[test]
+    fn test_mint_in_doubt_recovered() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock: SharedClock = Arc::new(MockClock::new(TEST_NOW));
+        let token_client = MockTokenClient::new();
+        let retry_config = MintRetryConfig::default();
+        let mut items = vec![];
+        for i in 0..2u64 {
+            let deposit_txid = format!("{:064x}", 0xd1 + i);
+            let recipient = Pubkey::new_unique();
+            let amount = DepcAmount::from_value64(COIN);
+            conn.save_deposit(&deposit_txid, &recipient.to_string(), amount, TEST_NOW)
+                .unwrap();
+            let id = conn
+                .add_pending_action(
+                    ACTION_DEPOSIT,
+                    &deposit_txid,
+                    &recipient.to_string(),
+                    amount,
+                    TEST_NOW,
+                )
+                .unwrap();
+            let deposit = conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap();
+            items.push(MintItem::<MockTokenClient> {
+                id,
+                deposit,
+                recipient_address: recipient,
+                amount: COIN,
+            });
+        }
+        let recipients: Vec<_> = items
+            .iter()
+            .map(|item| (item.recipient_address, item.amount))
+            .collect();
+
+        // the transaction might be made, the batch is neither released nor dead-lettered
+        token_client.push_send_result(Err(MockError(
+            "cannot settle".to_owned(),
+            ErrorKind::InDoubt,
+        )));
+        mint_deposits(&token_client, &conn, items, &retry_config, &clock).unwrap();
+        assert_eq!(
+            conn.query_claimed_pending_actions(ACTION_DEPOSIT)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+
+        // it's confirmed after all, the expired one of the retries mints nothing
+        let expired = Signature::new_unique();
+        token_client.push_recovered_transfer(RecoveredTransfer {
+            txid: expired,
+            status: TransferStatus::Expired,
+            recipients: recipients.clone(),
+        });
+        let confirmed = Signature::new_unique();
+        token_client.push_recovered_transfer(RecoveredTransfer {
+            txid: confirmed,
+            status: TransferStatus::Confirmed,
+            recipients,
+        });
+        recover_mints(&token_client, &conn, &AmountConverter::default(), &clock).unwrap();
+        for i in 0..2u64 {
+            let deposit = conn
+                .query_deposit(&format!("{:064x}", 0xd1 + i))
+                .unwrap()
+                .unwrap();
+            assert_eq!(deposit.erc20_txid, Some(confirmed.to_string()));
+        }
+        assert!(conn
+            .query_claimed_pending_actions(ACTION_DEPOSIT)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            token_client.acknowledged_transfers(),
+            vec![expired, confirmed]
+        );
+        assert!(token_client.sent_tokens().is_empty());
+    }
+
+    #[test]
+    fn test_recover_fee_sweep() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let token_client = MockTokenClient::new();
+        let clock: SharedClock = Arc::new(MockClock::new(TEST_NOW));
+        let converter = AmountConverter::default();
+        let treasury = Pubkey::new_unique();
+        let ledger = Ledger::new(conn.clone());
+        ledger
+            .deposit_fee("d1", DepcAmount::from_value64(1500), TEST_NOW)
+            .unwrap();
+        token_client.push_send_result(Err(MockError(
+            "cannot settle".to_owned(),
+            ErrorKind::InDoubt,
+        )));
+        assert!(sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).is_err());
+
+        // the sweep in doubt is confirmed after all, it's recorded instead of minting anything
+        let confirmed = Signature::new_unique();
+        token_client.push_recovered_transfer(RecoveredTransfer {
+            txid: confirmed,
+            status: TransferStatus::Confirmed,
+            recipients: vec![(treasury, 1500)],
+        });
+        recover_mints(&token_client, &conn, &converter, &clock).unwrap();
+        let sweeps = conn.query_fee_sweeps(10).unwrap();
+        assert_eq!(sweeps.len(), 1);
+        assert_eq!(sweeps[0].signature, confirmed.to_string());
+        assert!(conn.query_pending_fee_sweeps().unwrap().is_empty());
+        assert!(ledger.unswept_fees().unwrap().is_zero());
+        assert_eq!(token_client.acknowledged_transfers(), vec![confirmed]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_minting_paused_until_custody_covers() {
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        // the deposit is found but its coin is missing in the index
+        let deposit_txid = format!("{:064x}", 0xd1);
+        let amount = DepcAmount::from_value64(5 * COIN);
+        conn.save_deposit(
+            &deposit_txid,
+            &solana_recipient_address.to_string(),
+            amount,
+            TEST_NOW,
+        )
+        .unwrap();
+        conn.add_pending_action(
+            ACTION_DEPOSIT,
+            &deposit_txid,
+            &solana_recipient_address.to_string(),
+            amount,
+            TEST_NOW,
+        )
+        .unwrap();
+
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        // the worker finds the action from the database after the poll interval
+        sleep(PENDING_POLL_INTERVAL + Duration::from_secs(1)).await;
+        assert!(token_client.sent_tokens().is_empty());
+        // the deposit is left for the next try
+        assert!(conn
+            .query_deposit(&deposit_txid)
+            .unwrap()
+            .unwrap()
+            .erc20_txid
+            .is_none());
+
+        // the index catches up
+        conn.add_coin(&deposit_txid, 0, amount, TEST_DEPC_OWNER_ADDRESS, "")
+            .unwrap();
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+        assert_eq!(token_client.sent_tokens().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_shadow_mode_records_actions_only() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let withdraw_signature = Signature::new_unique();
+        token_client.set_verify_result(withdraw_signature, Ok(3 * COIN));
+        let (deposit_txid, withdraw_txid) =
+            push_bridge_blocks(&node, &solana_recipient_address, &withdraw_signature);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &deposit_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_shadow(true)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if conn.query_shadow_actions(10).unwrap().len() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let mut actions = conn.query_shadow_actions(10).unwrap();
+        actions.sort_by(|a, b| a.action.cmp(&b.action));
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].action, "mint");
+        assert_eq!(actions[0].source_txid, deposit_txid);
+        assert_eq!(actions[0].recipient, solana_recipient_address.to_string());
+        assert_eq!(actions[0].amount, DepcAmount::from_value64(5 * COIN));
+        assert_eq!(actions[1].action, "payout");
+        assert_eq!(actions[1].source_txid, withdraw_txid);
+        assert_eq!(actions[1].recipient, TEST_DEPC_RECIPIENT_ADDRESS);
+        assert_eq!(actions[1].amount, DepcAmount::from_value64(3 * COIN));
+        assert!(actions.iter().all(|action| action.timestamp == TEST_NOW));
+        // nothing touches the chains
+        assert!(token_client.sent_tokens().is_empty());
+        assert!(node.broadcasted_transactions().is_empty());
+        let deposit = conn.query_deposit(&deposit_txid).unwrap().unwrap();
+        assert_eq!(deposit.erc20_txid, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_dead_letters_bad_outputs() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        // the signature is unknown to the token client, it can never be verified
+        let (first_deposit_txid, bad_withdraw_txid) =
+            push_bridge_blocks(&node, &Pubkey::new_unique(), &Signature::new_unique());
+        let invalid_txid = format!("{:064x}", 0xd3);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &invalid_txid,
+                &[],
+                &[(
+                    TEST_DEPC_OWNER_ADDRESS,
+                    5 * COIN,
+                    &make_script_hex("not-a-solana-address"),
+                )],
+            )],
+        );
+        let deposit_txid = format!("{:064x}", 0xd4);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &deposit_txid,
+                &[],
+                &[(
+                    TEST_DEPC_OWNER_ADDRESS,
+                    5 * COIN,
+                    &make_script_hex(&solana_recipient_address.to_string()),
+                )],
+            )],
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        // the deposit after the bad outputs is still processed
+        for _ in 0..100 {
+            if token_client.sent_tokens().len() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 2);
+        assert_eq!(sent_tokens[1].0, solana_recipient_address);
+        assert!(conn.query_deposit(&first_deposit_txid).unwrap().is_some());
+        assert!(conn.query_deposit(&invalid_txid).unwrap().is_none());
+        assert!(node.broadcasted_transactions().is_empty());
+
+        let mut letters = conn.query_dead_letters(10).unwrap();
+        letters.sort_by(|a, b| a.txid.cmp(&b.txid));
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].txid, bad_withdraw_txid);
+        assert_eq!(letters[0].height, 1);
+        assert_eq!(letters[0].vout, Some(0));
+        assert_eq!(letters[1].txid, invalid_txid);
+        assert_eq!(letters[1].height, 2);
+        assert!(letters[1].reason.contains("not-a-solana-address"));
+        assert!(letters.iter().all(|letter| letter.timestamp == TEST_NOW));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_skips_unavailable_heights() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let script_hex = make_script_hex(&solana_recipient_address.to_string());
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        // block 1 is pruned, a transaction of block 2 is missing
+        let (pruned_txid, missing_txid, deposit_txid) = (
+            format!("{:064x}", 0xd1),
+            format!("{:064x}", 0xd2),
+            format!("{:064x}", 0xd3),
+        );
+        let pruned_hash = node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &pruned_txid,
+                &[],
+                &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN, &script_hex)],
+            )],
+        );
+        node.prune_block(&pruned_hash);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                make_transaction(
+                    &missing_txid,
+                    &[],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN, &script_hex)],
+                ),
+                make_transaction(
+                    &deposit_txid,
+                    &[],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN, &script_hex)],
+                ),
+            ],
+        );
+        node.remove_transaction(&missing_txid);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        // the deposit after the unavailable data is still processed
+        for _ in 0..100 {
+            if token_client.sent_tokens().len() == 1 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        assert_eq!(token_client.sent_tokens().len(), 1);
+        assert!(conn.query_deposit(&deposit_txid).unwrap().is_some());
+        assert!(conn.query_deposit(&pruned_txid).unwrap().is_none());
+        assert!(conn.query_deposit(&missing_txid).unwrap().is_none());
+        assert_eq!(conn.query_best_height(), Some(2));
+        let unavailable = conn.query_unavailable_heights().unwrap();
+        assert_eq!(unavailable.len(), 2);
+        assert_eq!(unavailable[0].height, 1);
+        assert!(unavailable[0].reason.contains(&pruned_hash));
+        assert!(unavailable[0].reason.contains("pruned data"));
+        assert_eq!(unavailable[1].height, 2);
+        assert!(unavailable[1].reason.contains(&missing_txid));
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposit_to_reserved_address_dead_lettered() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let mint_pubkey = Pubkey::new_unique();
+        token_client.add_reserved_address(mint_pubkey, "the mint");
+        let solana_recipient_address = Pubkey::new_unique();
+        let deposit = |txid: &str, recipient: &Pubkey| {
+            make_transaction(
+                txid,
+                &[],
+                &[(
+                    TEST_DEPC_OWNER_ADDRESS,
+                    5 * COIN,
+                    &make_script_hex(&recipient.to_string()),
+                )],
+            )
+        };
+        let reserved_txid = format!("{:064x}", 0xd7);
+        let deposit_txid = format!("{:064x}", 0xd8);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                deposit(&reserved_txid, &mint_pubkey),
+                deposit(&deposit_txid, &solana_recipient_address),
+            ],
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, solana_recipient_address);
+        assert!(conn.query_deposit(&reserved_txid).unwrap().is_none());
+        let letters = conn.query_dead_letters(10).unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].txid, reserved_txid);
+        assert!(letters[0].reason.contains("is the mint"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_withdraw_of_multiple_transactions() {
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let signatures: Vec<Signature> = (0..3).map(|_| Signature::new_unique()).collect();
+        for (signature, amount) in signatures.iter().zip([2 * COIN, COIN, 4 * COIN]) {
+            token_client.set_verify_result(*signature, Ok(amount));
+        }
+        let withdraw = |txid: &str, signatures: &[Signature]| {
+            let signatures: Vec<String> = signatures.iter().map(|s| s.to_string()).collect();
+            let content = format!("{TEST_DEPC_RECIPIENT_ADDRESS}:{}", signatures.join(","));
+            make_transaction(
+                txid,
+                &[],
+                &[(TEST_DEPC_OWNER_ADDRESS, 0, &make_script_hex(&content))],
+            )
+        };
+        let withdraw_txid = format!("{:064x}", 0xd5);
+        let reusing_txid = format!("{:064x}", 0xd6);
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![withdraw(&withdraw_txid, &signatures[..2])],
+        );
+        // the second transaction is used by the withdrawal above already
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![withdraw(&reusing_txid, &signatures[1..])],
+        );
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_shadow(true)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if !conn.query_dead_letters(10).unwrap().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let actions = conn.query_pending_actions_between(0, u64::MAX).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action, ACTION_WITHDRAW);
+        assert_eq!(actions[0].source_txid, withdraw_txid);
+        assert_eq!(actions[0].amount, DepcAmount::from_value64(3 * COIN));
+        for signature in signatures[..2].iter() {
+            assert_eq!(
+                conn.query_withdraw_signature(&signature.to_string())
+                    .unwrap(),
+                Some(withdraw_txid.clone())
+            );
+        }
+        assert_eq!(
+            conn.query_withdraw_signature(&signatures[2].to_string())
+                .unwrap(),
+            None
+        );
+        let letters = conn.query_dead_letters(10).unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].txid, reusing_txid);
+        assert!(letters[0].reason.contains("is used by withdrawal"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_offchain_withdraw_requests() {
+        let node = FakeDepcNode::start();
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        let token_client = MockTokenClient::new();
+        let signer = Pubkey::new_unique();
+        let (paid, foreign) = (Signature::new_unique(), Signature::new_unique());
+        token_client.set_verify_result(paid, Ok(2 * COIN));
+        token_client.set_sender(paid, signer);
+        // the tokens are sent by another wallet, nothing is counted for the signer
+        token_client.set_verify_result(foreign, Ok(4 * COIN));
+        token_client.set_sender(foreign, Pubkey::new_unique());
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mut ids = vec![];
+        for signature in [paid, foreign] {
+            let id = conn
+                .add_withdraw_request(
+                    &signature.to_string(),
+                    TEST_DEPC_RECIPIENT_ADDRESS,
+                    &signer.to_string(),
+                    WITHDRAW_ORIGIN_OFFCHAIN,
+                    WITHDRAW_REQUEST_PENDING,
+                    TEST_NOW,
+                )
+                .unwrap()
+                .unwrap();
+            ids.push(id);
+        }
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client,
+        )
+        .set_shadow(true)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if conn
+                .query_withdraw_requests_by_status(WITHDRAW_REQUEST_PENDING)
+                .unwrap()
+                .is_empty()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let accepted = conn.query_withdraw_request(ids[0]).unwrap().unwrap();
+        assert_eq!(accepted.status, WITHDRAW_REQUEST_ACCEPTED);
+        let actions = conn.query_pending_actions_between(0, u64::MAX).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(Some(actions[0].id), accepted.pending_action_id);
+        assert_eq!(actions[0].action, ACTION_WITHDRAW);
+        assert_eq!(actions[0].source_txid, format!("offchain:{}", ids[0]));
+        assert_eq!(actions[0].recipient, TEST_DEPC_RECIPIENT_ADDRESS);
+        assert_eq!(actions[0].amount, DepcAmount::from_value64(2 * COIN));
+        assert_eq!(
+            conn.query_withdraw_signature(&paid.to_string()).unwrap(),
+            Some(actions[0].source_txid.clone())
+        );
+
+        let rejected = conn.query_withdraw_request(ids[1]).unwrap().unwrap();
+        assert_eq!(rejected.status, WITHDRAW_REQUEST_REJECTED);
+        assert!(rejected
+            .reason
+            .unwrap()
+            .contains("under the withdraw threshold"));
+        assert_eq!(
+            conn.query_withdraw_signature(&foreign.to_string()).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposit_intent_matches_plain_payment() {
+        const TEST_SENDER_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let solana_recipient_address = Pubkey::new_unique();
+        let (funding_txid, payment_txid, stale_txid, change_txid, dust_txid) = (
+            format!("{:064x}", 0xf1),
+            format!("{:064x}", 0xd5),
+            format!("{:064x}", 0xd6),
+            format!("{:064x}", 0xd7),
+            format!("{:064x}", 0xd8),
+        );
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &funding_txid,
+                &[],
+                &[
+                    (TEST_SENDER_ADDRESS, 10 * COIN, ""),
+                    (TEST_DEPC_RECIPIENT_ADDRESS, 10 * COIN, ""),
+                    (TEST_DEPC_OWNER_ADDRESS, 10 * COIN, ""),
+                ],
+            )],
+        );
+        // all pay without OP_RETURN, only the sender of the first one registered in time and
+        // the last one is made by the custody itself
+        let block_hash = node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                make_transaction(
+                    &payment_txid,
+                    &[(&funding_txid, 0)],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN, "")],
+                ),
+                make_transaction(
+                    &stale_txid,
+                    &[(&funding_txid, 1)],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 5 * COIN, "")],
+                ),
+                make_transaction(
+                    &change_txid,
+                    &[(&funding_txid, 2)],
+                    &[(TEST_DEPC_OWNER_ADDRESS, 9 * COIN, "")],
+                ),
+                make_transaction(&dust_txid, &[], &[(TEST_DEPC_OWNER_ADDRESS, 500, "")]),
+            ],
+        );
+        let block_time = node.client().get_block(&block_hash).unwrap().time;
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let intent_id = conn
+            .add_deposit_intent(
+                TEST_SENDER_ADDRESS,
+                &solana_recipient_address.to_string(),
+                block_time - 600,
+            )
+            .unwrap();
+        let stale_intent_id = conn
+            .add_deposit_intent(
+                TEST_DEPC_RECIPIENT_ADDRESS,
+                &Pubkey::new_unique().to_string(),
+                block_time - 7200,
+            )
+            .unwrap();
+        let custody_intent_id = conn
+            .add_deposit_intent(
+                TEST_DEPC_OWNER_ADDRESS,
+                &Pubkey::new_unique().to_string(),
+                block_time - 600,
+            )
+            .unwrap();
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_deposit_intent_window(Duration::from_secs(3600))
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, solana_recipient_address);
+        assert_eq!(sent_tokens[0].1, 5 * COIN);
+        let intent = conn.query_deposit_intent(intent_id).unwrap().unwrap();
+        assert_eq!(intent.matched_txid, Some(payment_txid.clone()));
+        assert_eq!(intent.matched_vout, Some(0));
+        assert_eq!(intent.matched_at, Some(TEST_NOW));
+        let deposit = conn.query_deposit(&payment_txid).unwrap().unwrap();
+        assert_eq!(deposit.to_address, solana_recipient_address.to_string());
+        // the intent is expired before the payment
+        let stale_intent = conn.query_deposit_intent(stale_intent_id).unwrap().unwrap();
+        assert_eq!(stale_intent.matched_txid, None);
+        assert!(conn.query_deposit(&stale_txid).unwrap().is_none());
+        let custody_intent = conn
+            .query_deposit_intent(custody_intent_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(custody_intent.matched_txid, None);
+        assert!(conn.query_deposit(&change_txid).unwrap().is_none());
+
+        // the payments matched to no intent are left to the operator, the change is not
+        let inflows: Vec<(String, u32, String)> = conn
+            .query_unclassified_custody_inflows(10)
+            .unwrap()
+            .into_iter()
+            .map(|inflow| (inflow.txid, inflow.vout, inflow.reason))
+            .collect();
+        assert_eq!(
+            inflows,
+            vec![
+                (dust_txid, 0, INFLOW_BELOW_THRESHOLD.to_owned()),
+                (stale_txid, 0, INFLOW_NO_RECIPIENT.to_owned()),
+                (funding_txid, 2, INFLOW_NO_RECIPIENT.to_owned()),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_deposit_address_credits_payment() {
+        const TEST_XPUB: &str =
+            "tpubDCBWBScQPGv4Xk3JSbhw6wYYpayMjb2eAYyArpbSqQTbLDpphHGAetB6VQgVeftLML8vDSUEWcC2xDi3qJJ3YCDChJDvqVzpgoYSuT52MhJ";
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        set_deposit_xpub(&conn, &TEST_XPUB.parse().unwrap()).unwrap();
+        let (recipient, other_recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let deposit_address = register_deposit_address(&conn, &recipient.to_string(), TEST_NOW)
+            .unwrap()
+            .unwrap();
+        register_deposit_address(&conn, &other_recipient.to_string(), TEST_NOW).unwrap();
+
+        let (payment_txid, other_txid) = (format!("{:064x}", 0xd8), format!("{:064x}", 0xd9));
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        // the payment to the deposit address needs no OP_RETURN, the one to an address which
+        // is not registered is not credited
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![
+                make_transaction(
+                    &payment_txid,
+                    &[],
+                    &[(deposit_address.address.as_str(), 3 * COIN, "")],
+                ),
+                make_transaction(
+                    &other_txid,
+                    &[],
+                    &[(TEST_DEPC_RECIPIENT_ADDRESS, 4 * COIN, "")],
+                ),
+            ],
+        );
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_deposit_addresses(true)
+        .set_clock(Arc::new(MockClock::new(TEST_NOW)))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if !token_client.sent_tokens().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!(sent_tokens[0].0, recipient);
+        assert_eq!(sent_tokens[0].1, 3 * COIN);
+        let deposit = conn.query_deposit(&payment_txid).unwrap().unwrap();
+        assert_eq!(deposit.to_address, recipient.to_string());
+        assert!(conn.query_deposit(&other_txid).unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_refund_invalid_deposit_after_delay() {
+        const TEST_SENDER_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+        let node = FakeDepcNode::start();
+        let token_client = MockTokenClient::new();
+        let (funding_txid, invalid_txid) = (format!("{:064x}", 0xf1), format!("{:064x}", 0xd3));
+        node.push_block(TEST_DEPC_OWNER_ADDRESS, vec![]);
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &funding_txid,
+                &[],
+                &[(TEST_SENDER_ADDRESS, 10 * COIN, "")],
+            )],
+        );
+        node.push_block(
+            TEST_DEPC_OWNER_ADDRESS,
+            vec![make_transaction(
+                &invalid_txid,
+                &[(&funding_txid, 0)],
+                &[(
+                    TEST_DEPC_OWNER_ADDRESS,
+                    5 * COIN,
+                    &make_script_hex("not-a-solana-address"),
+                )],
+            )],
+        );
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &invalid_txid, 0, 5 * COIN);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = MockClock::new(TEST_NOW);
+        let exit_sig = Arc::new(Mutex::new(false));
+        let bridge = Bridge::new(
+            conn.clone(),
+            node.client(),
+            TEST_DEPC_OWNER_ADDRESS.to_owned(),
+            Pubkey::new_unique().to_string(),
+            token_client.clone(),
+        )
+        .set_refund_config(RefundConfig {
+            delay: Duration::from_secs(600),
+            interval: Duration::from_millis(100),
+        })
+        .set_clock(Arc::new(clock.clone()))
+        .set_exit_sig(Arc::clone(&exit_sig));
+        let bridge_handler = tokio::spawn(bridge.run());
+
+        for _ in 0..100 {
+            if !conn.query_refunds(10).unwrap().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        // nothing is refunded before the delay
+        sleep(Duration::from_millis(300)).await;
+        assert!(node.broadcasted_transactions().is_empty());
+        clock.advance(600);
+        for _ in 0..100 {
+            if !node.broadcasted_transactions().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        {
+            let mut exit = exit_sig.lock().unwrap();
+            *exit = true;
+        }
+        bridge_handler.await.unwrap().unwrap();
+
+        let refunds = conn.query_refunds(10).unwrap();
+        assert_eq!(refunds.len(), 1);
+        assert_eq!(refunds[0].depc_txid, invalid_txid);
+        assert_eq!(refunds[0].vout, 0);
+        assert_eq!(refunds[0].refund_address, TEST_SENDER_ADDRESS);
+        assert_eq!(refunds[0].amount, DepcAmount::from_value64(5 * COIN));
+        assert!(refunds[0].reason.contains("not-a-solana-address"));
+        assert_eq!(refunds[0].timestamp, TEST_NOW);
+        assert_eq!(refunds[0].refunded_at, Some(TEST_NOW + 600));
+        assert!(refunds[0].txid.is_some());
+        let broadcasted = node.broadcasted_transactions();
+        assert_eq!(broadcasted.len(), 1);
+        // the fake node makes the raw transaction from the parameters
+        let raw_tx = String::from_utf8(hex::decode(&broadcasted[0]).unwrap()).unwrap();
+        assert!(raw_tx.contains(TEST_SENDER_ADDRESS));
+        assert!(conn.query_deposit(&invalid_txid).unwrap().is_none());
+        assert!(conn.query_dead_letters(10).unwrap().is_empty());
+        assert!(token_client.sent_tokens().is_empty());
+        // the whole deposit is returned, the fee is paid from it
+        let trial_balance = Ledger::new(conn.clone()).trial_balance().unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(trial_balance.balance_of(ACCOUNT_CUSTODY), 0);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_DEPOSITS_DUE), 0);
+    }
+
+    #[test]
+    fn test_unsigned_sweep_reserves_coins() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let owner_address = TEST_DEPC_OWNER_ADDRESS.to_owned();
+        let swept_txid = format!("{:064x}", 0xc1);
+        node.add_unspent(TEST_DEPC_OWNER_ADDRESS, &swept_txid, 0, 5 * COIN);
+        node.add_unspent(
+            TEST_DEPC_OWNER_ADDRESS,
+            &format!("{:064x}", 0xc2),
+            0,
+            5 * COIN,
+        );
+        let sweep_config = SweepConfig {
+            cold_address: TEST_DEPC_RECIPIENT_ADDRESS.to_owned(),
+            hot_ceiling: DepcAmount::from_value64(COIN),
+            unsigned_only: true,
+            interval: Duration::from_secs(60),
+        };
+        let sweep = |amount: u64| {
+            sweep_excess(
+                &conn,
+                &client,
+                &owner_address,
+                &sweep_config,
+                DepcAmount::from_value64(amount),
+                TEST_NOW,
+            )
+            .unwrap()
+        };
+        sweep(4 * COIN);
+        // the excess changes while the operator is signing, no other sweep is made
+        sweep(3 * COIN);
+        let sweeps = conn.query_sweeps(10).unwrap();
+        assert_eq!(sweeps.len(), 1);
+        assert_eq!(sweeps[0].txid, None);
+
+        // the payouts never spend the coin of the unsigned sweep
+        let payout = client
+            .make_transfer(&owner_address, TEST_DEPC_RECIPIENT_ADDRESS, 2 * COIN)
+            .unwrap();
+        let payout = String::from_utf8(hex::decode(payout).unwrap()).unwrap();
+        assert!(!payout.contains(&swept_txid));
+
+        // the operator broadcasts it, the sweep is settled and the next one is made
+        conn.add_coin(
+            &swept_txid,
+            0,
+            DepcAmount::from_value64(5 * COIN),
+            TEST_DEPC_OWNER_ADDRESS,
+            "a914",
+        )
+        .unwrap();
+        conn.mark_coin_to_spent(&swept_txid, 0, "signed_sweep", 2)
+            .unwrap();
+        sweep(3 * COIN);
+        let sweeps = conn.query_sweeps(10).unwrap();
+        assert_eq!(sweeps.len(), 2);
+        assert_eq!(sweeps[1].txid.as_deref(), Some("signed_sweep"));
+        assert_eq!(sweeps[0].txid, None);
+    }
 
     #[test]
-    fn test_get_curr_timestamp() {
-        let timestamp = get_curr_timestamp();
-        assert!(timestamp > 0);
+    fn test_pipeline_config_validate() {
+        assert!(PipelineConfig::default().validate().is_ok());
+        let config = PipelineConfig {
+            deposit_workers: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(BridgeError::InvalidConfig(message)) if message.contains("deposit_workers")
+        ));
+        let config = PipelineConfig {
+            db_batch_size: PipelineConfig::MAX_DB_BATCH_SIZE + 1,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        let config = PipelineConfig {
+            mint_batch_size: PipelineConfig::MAX_MINT_BATCH_SIZE + 1,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
     }
-}
\ No newline at end of file
+}
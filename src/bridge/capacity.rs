@@ -0,0 +1,136 @@
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::ledger::{
+    Ledger, ACCOUNT_COLD_STORAGE, ACCOUNT_WITHDRAWALS_DUE, ACCOUNT_WRAPPED_SUPPLY,
+};
+use crate::BridgeError;
+
+/// The DePC held for a bridge against the wrapped tokens it owes, a mint is made only when the
+/// custody covers the wrapped supply with the new deposit
+///
+/// The custody is read from the local index (the custody addresses of all the eras and the
+/// deposit addresses), so a mint isn't made from an index out of sync, e.g. after a reorg. The
+/// DePC swept to the cold address is counted by the ledger since the cold address may hold
+/// other coins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustodyCoverage {
+    /// The DePC of the custody addresses and the deposit addresses in the index
+    pub indexed: DepcAmount,
+    /// The DePC swept to the cold address
+    pub cold_storage: DepcAmount,
+    /// The wrapped tokens not returned, and the returned ones not paid out yet
+    pub wrapped_supply: DepcAmount,
+}
+
+impl CustodyCoverage {
+    /// The DePC the custody lacks to back the wrapped supply after `amount` is minted, `None`
+    /// when it's covered
+    pub fn shortfall(&self, amount: DepcAmount) -> Result<Option<DepcAmount>, BridgeError> {
+        let required = self.wrapped_supply.checked_add(amount);
+        let custody = self.indexed.checked_add(self.cold_storage);
+        match (required, custody) {
+            (Some(required), Some(custody)) => {
+                Ok(required.checked_sub(custody).filter(|lack| !lack.is_zero()))
+            }
+            _ => Err(BridgeError::AmountOverflow),
+        }
+    }
+}
+
+pub fn query_custody_coverage(
+    conn: &db::Conn,
+    custody_address: &str,
+) -> Result<CustodyCoverage, BridgeError> {
+    let mut indexed = conn
+        .query_address_balance(custody_address)?
+        .checked_add(conn.query_deposit_addresses_balance()?)
+        .ok_or(BridgeError::AmountOverflow)?;
+    // the coins left on the rotated addresses are still held for the bridge
+    for era in conn.query_custody_eras()? {
+        if era.address != custody_address {
+            indexed = indexed
+                .checked_add(conn.query_address_balance(&era.address)?)
+                .ok_or(BridgeError::AmountOverflow)?;
+        }
+    }
+    let trial_balance = Ledger::new(conn.clone()).trial_balance()?;
+    // the liabilities are credit balances
+    let owed = -(trial_balance.balance_of(ACCOUNT_WRAPPED_SUPPLY)
+        + trial_balance.balance_of(ACCOUNT_WITHDRAWALS_DUE));
+    Ok(CustodyCoverage {
+        indexed,
+        cold_storage: to_amount(trial_balance.balance_of(ACCOUNT_COLD_STORAGE))?,
+        wrapped_supply: to_amount(owed)?,
+    })
+}
+
+/// A negative balance is none
+fn to_amount(balance: i128) -> Result<DepcAmount, BridgeError> {
+    u64::try_from(balance.max(0))
+        .map(DepcAmount::from_value64)
+        .map_err(|_| BridgeError::AmountOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::bridge::rotate_custody_address;
+
+    const TEST_CUSTODY_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+    const TEST_NOW: u64 = 1731000600;
+    const COIN: u64 = 100000000;
+
+    fn amount(coins: u64) -> DepcAmount {
+        DepcAmount::from_value64(coins * COIN)
+    }
+
+    #[test]
+    fn test_custody_coverage() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let ledger = Ledger::new(conn.clone());
+        // 10 coins are deposited and minted
+        conn.add_coin("a1", 0, amount(10), TEST_CUSTODY_ADDRESS, "")
+            .unwrap();
+        ledger.custody_in("a1", amount(10), TEST_NOW).unwrap();
+        let coverage = query_custody_coverage(&conn, TEST_CUSTODY_ADDRESS).unwrap();
+        assert_eq!(coverage.shortfall(amount(10)).unwrap(), None);
+        ledger.wrapped_minted("s1", amount(10), TEST_NOW).unwrap();
+
+        // the next deposit is found but the index misses its coin
+        let coverage = query_custody_coverage(&conn, TEST_CUSTODY_ADDRESS).unwrap();
+        assert_eq!(coverage.wrapped_supply, amount(10));
+        assert_eq!(coverage.shortfall(amount(4)).unwrap(), Some(amount(4)));
+        conn.add_coin("a2", 0, amount(4), TEST_CUSTODY_ADDRESS, "")
+            .unwrap();
+        let coverage = query_custody_coverage(&conn, TEST_CUSTODY_ADDRESS).unwrap();
+        assert_eq!(coverage.shortfall(amount(4)).unwrap(), None);
+
+        // the returned tokens are owed until they're paid out
+        ledger.wrapped_returned("a3", amount(3), TEST_NOW).unwrap();
+        let coverage = query_custody_coverage(&conn, TEST_CUSTODY_ADDRESS).unwrap();
+        assert_eq!(coverage.wrapped_supply, amount(10));
+
+        // the swept coins are counted by the ledger
+        conn.mark_coin_to_spent("a1", 0, "b1", 1).unwrap();
+        ledger
+            .sweep("b1", amount(10), DepcAmount::ZERO, TEST_NOW)
+            .unwrap();
+        let coverage = query_custody_coverage(&conn, TEST_CUSTODY_ADDRESS).unwrap();
+        assert_eq!(coverage.indexed, amount(4));
+        assert_eq!(coverage.cold_storage, amount(10));
+        assert_eq!(coverage.shortfall(amount(4)).unwrap(), None);
+        assert_eq!(coverage.shortfall(amount(5)).unwrap(), Some(amount(1)));
+
+        // the coins left on the rotated custody address still count
+        let new_address = "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc";
+        for address in [TEST_CUSTODY_ADDRESS, new_address] {
+            rotate_custody_address(&conn, address, Duration::ZERO, TEST_NOW).unwrap();
+        }
+        conn.add_coin("a4", 0, amount(1), new_address, "").unwrap();
+        let coverage = query_custody_coverage(&conn, new_address).unwrap();
+        assert_eq!(coverage.indexed, amount(5));
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+
+use log::{info, warn};
+
+use super::{ACTION_DEPOSIT, ACTION_WITHDRAW};
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::BridgeError;
+
+/// The statuses of table `catch_up_plans`, the workers don't mint or pay out while the latest
+/// plan of the bridge is indexing or awaiting the confirmation
+pub const CATCH_UP_INDEXING: &str = "indexing";
+pub const CATCH_UP_AWAITING_CONFIRMATION: &str = "awaiting_confirmation";
+pub const CATCH_UP_EXECUTING: &str = "executing";
+
+/// The bridge catches up when it starts `min_blocks` or more behind the DePC node, the blocks
+/// are indexed without acting on the deposits and withdrawals found, then the backlog of them is
+/// planned, and it's executed after the operator confirms it if the backlog has more than
+/// `confirm_actions` actions or `confirm_amount` DePC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatchUpConfig {
+    pub min_blocks: u32,
+    pub confirm_actions: u64,
+    pub confirm_amount: DepcAmount,
+}
+
+impl CatchUpConfig {
+    fn needs_confirmation(&self, plan: &db::CatchUpPlanRecord) -> Result<bool, BridgeError> {
+        let amount = plan
+            .deposit_amount
+            .checked_add(plan.withdraw_amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+        Ok(plan.deposits + plan.withdrawals > self.confirm_actions || amount > self.confirm_amount)
+    }
+}
+
+/// The latest catch-up of a bridge and the syncing of its index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatchUpReport {
+    pub bridge_id: String,
+    pub best_height: Option<u32>,
+    /// The totals of the plan being indexed are the ones found so far
+    pub plan: Option<db::CatchUpPlanRecord>,
+}
+
+/// Start a catch-up plan when the index of `conn` is `config.min_blocks` or more behind
+/// `chain_height`, it's called before the workers are started. The plan left unfinished by the
+/// last run is resumed instead, the one still indexing is extended to `chain_height`.
+pub fn start_catch_up(
+    conn: &db::Conn,
+    config: &CatchUpConfig,
+    chain_height: u32,
+    now: u64,
+) -> Result<Option<db::CatchUpPlanRecord>, BridgeError> {
+    if let Some(mut plan) = conn.query_latest_catch_up_plan()? {
+        if plan.status == CATCH_UP_INDEXING {
+            if conn.extend_catch_up_plan(plan.id, chain_height)? {
+                plan.to_height = chain_height;
+            }
+            info!(
+                "catch-up {} of bridge {} is resumed, indexing blocks {}..={}",
+                plan.id,
+                conn.bridge_id(),
+                plan.from_height,
+                plan.to_height
+            );
+            return Ok(Some(plan));
+        }
+        if plan.status == CATCH_UP_AWAITING_CONFIRMATION {
+            warn!(
+                "catch-up {} of bridge {} still awaits the confirmation of the operator",
+                plan.id,
+                conn.bridge_id()
+            );
+            return Ok(Some(plan));
+        }
+    }
+    let from_height = conn.query_best_height().map_or(0, |height| height + 1);
+    if chain_height < from_height || chain_height - from_height + 1 < config.min_blocks {
+        return Ok(None);
+    }
+    let id = conn.add_catch_up_plan(from_height, chain_height, CATCH_UP_INDEXING, now)?;
+    warn!(
+        "bridge {} is {} blocks behind, catch-up {id} indexes blocks \
+         {from_height}..={chain_height} before acting on them",
+        conn.bridge_id(),
+        chain_height - from_height + 1
+    );
+    Ok(Some(db::CatchUpPlanRecord {
+        id,
+        from_height,
+        to_height: chain_height,
+        status: CATCH_UP_INDEXING.to_owned(),
+        deposits: 0,
+        deposit_amount: DepcAmount::ZERO,
+        withdrawals: 0,
+        withdraw_amount: DepcAmount::ZERO,
+        started_at: now,
+        planned_at: None,
+        confirmed_at: None,
+    }))
+}
+
+/// Plan the backlog once the index of `conn` is synced to the end of the catch-up, the backlog
+/// is every pending action not processed yet. Returns the plan made, `None` if there's no plan
+/// being indexed or the index is not there yet.
+pub fn finish_catch_up_indexing(
+    conn: &db::Conn,
+    config: &CatchUpConfig,
+    synced_height: u32,
+    now: u64,
+) -> Result<Option<db::CatchUpPlanRecord>, BridgeError> {
+    let Some(mut plan) = conn.query_latest_catch_up_plan()? else {
+        return Ok(None);
+    };
+    if plan.status != CATCH_UP_INDEXING || synced_height < plan.to_height {
+        return Ok(None);
+    }
+    fill_backlog(conn, &mut plan)?;
+    plan.status = if config.needs_confirmation(&plan)? {
+        CATCH_UP_AWAITING_CONFIRMATION.to_owned()
+    } else {
+        CATCH_UP_EXECUTING.to_owned()
+    };
+    plan.planned_at = Some(now);
+    if !conn.plan_catch_up(
+        plan.id,
+        &plan.status,
+        plan.deposits,
+        plan.deposit_amount,
+        plan.withdrawals,
+        plan.withdraw_amount,
+        now,
+    )? {
+        return Ok(None);
+    }
+    let summary = format!(
+        "catch-up {} of bridge {} is planned, {} deposits of {} and {} withdrawals of {}",
+        plan.id,
+        conn.bridge_id(),
+        plan.deposits,
+        plan.deposit_amount,
+        plan.withdrawals,
+        plan.withdraw_amount
+    );
+    if plan.status == CATCH_UP_AWAITING_CONFIRMATION {
+        warn!("{summary}, it's executed after the operator confirms it");
+    } else {
+        info!("{summary}, execute it");
+    }
+    Ok(Some(plan))
+}
+
+/// Confirm the plan awaiting the confirmation, the workers execute the backlog after it.
+/// `None` if no plan awaits it.
+pub fn confirm_catch_up(
+    conn: &db::Conn,
+    now: u64,
+) -> Result<Option<db::CatchUpPlanRecord>, BridgeError> {
+    let Some(mut plan) = conn.query_latest_catch_up_plan()? else {
+        return Ok(None);
+    };
+    if plan.status != CATCH_UP_AWAITING_CONFIRMATION
+        || !conn.confirm_catch_up_plan(
+            plan.id,
+            CATCH_UP_AWAITING_CONFIRMATION,
+            CATCH_UP_EXECUTING,
+            now,
+        )?
+    {
+        return Ok(None);
+    }
+    warn!(
+        "catch-up {} of bridge {} is confirmed, execute it",
+        plan.id,
+        conn.bridge_id()
+    );
+    plan.status = CATCH_UP_EXECUTING.to_owned();
+    plan.confirmed_at = Some(now);
+    Ok(Some(plan))
+}
+
+/// Whether the workers of `conn` wait for the catch-up
+pub fn is_catching_up(conn: &db::Conn) -> Result<bool, BridgeError> {
+    Ok(conn
+        .query_latest_catch_up_plan()?
+        .is_some_and(|plan| plan.status != CATCH_UP_EXECUTING))
+}
+
+pub fn query_catch_up_report(conn: &db::Conn) -> Result<CatchUpReport, BridgeError> {
+    let mut plan = conn.query_latest_catch_up_plan()?;
+    if let Some(plan) = plan
+        .as_mut()
+        .filter(|plan| plan.status == CATCH_UP_INDEXING)
+    {
+        fill_backlog(conn, plan)?;
+    }
+    Ok(CatchUpReport {
+        bridge_id: conn.bridge_id().to_owned(),
+        best_height: conn.query_best_height(),
+        plan,
+    })
+}
+
+fn fill_backlog(conn: &db::Conn, plan: &mut db::CatchUpPlanRecord) -> Result<(), BridgeError> {
+    for backlog in conn.query_pending_action_backlog()? {
+        if backlog.action == ACTION_DEPOSIT {
+            plan.deposits = backlog.count;
+            plan.deposit_amount = backlog.amount;
+        } else if backlog.action == ACTION_WITHDRAW {
+            plan.withdrawals = backlog.count;
+            plan.withdraw_amount = backlog.amount;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for CatchUpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bridge:       {}", self.bridge_id)?;
+        match self.best_height {
+            Some(height) => writeln!(f, "best height:  {height}")?,
+            None => writeln!(f, "best height:  no block is synced")?,
+        }
+        let Some(plan) = self.plan.as_ref() else {
+            return writeln!(f, "catch-up:     none");
+        };
+        writeln!(
+            f,
+            "catch-up:     {} ({}), blocks {}..={}",
+            plan.id, plan.status, plan.from_height, plan.to_height
+        )?;
+        writeln!(
+            f,
+            "deposits:     {} of {}",
+            plan.deposits, plan.deposit_amount
+        )?;
+        writeln!(
+            f,
+            "withdrawals:  {} of {}",
+            plan.withdrawals, plan.withdraw_amount
+        )?;
+        if plan.status == CATCH_UP_AWAITING_CONFIRMATION {
+            writeln!(f, "confirm it with `catch-up --confirm`")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+    const COIN: u64 = 100_000_000;
+
+    fn make_config() -> CatchUpConfig {
+        CatchUpConfig {
+            min_blocks: 10,
+            confirm_actions: 2,
+            confirm_amount: DepcAmount::from_value64(100 * COIN),
+        }
+    }
+
+    fn add_blocks(conn: &db::Conn, to_height: u32) {
+        for height in 0..=to_height {
+            conn.add_block(&format!("{height:064x}"), height, "miner", TEST_NOW)
+                .unwrap();
+        }
+    }
+
+    fn add_pending_action(conn: &db::Conn, action: &str, value64: u64) -> u64 {
+        conn.add_pending_action(
+            action,
+            &format!("{:064x}", value64),
+            "recipient",
+            DepcAmount::from_value64(value64),
+            TEST_NOW,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_catch_up_plan() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let config = make_config();
+        add_blocks(&conn, 4);
+
+        // not far enough behind
+        assert_eq!(start_catch_up(&conn, &config, 13, TEST_NOW).unwrap(), None);
+        assert!(!is_catching_up(&conn).unwrap());
+
+        let plan = start_catch_up(&conn, &config, 14, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert_eq!((plan.from_height, plan.to_height), (5, 14));
+        assert_eq!(plan.status, CATCH_UP_INDEXING);
+        assert!(is_catching_up(&conn).unwrap());
+        // the plan is resumed and extended by the next run
+        let plan = start_catch_up(&conn, &config, 20, TEST_NOW + 60)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.to_height, 20);
+        assert_eq!(
+            conn.query_latest_catch_up_plan().unwrap(),
+            Some(plan.clone())
+        );
+
+        add_pending_action(&conn, ACTION_DEPOSIT, 5 * COIN);
+        add_pending_action(&conn, ACTION_WITHDRAW, 2 * COIN);
+        // the totals found so far are reported while indexing
+        let report = query_catch_up_report(&conn).unwrap();
+        assert_eq!(report.plan.as_ref().unwrap().deposits, 1);
+        assert_eq!(
+            report.plan.unwrap().withdraw_amount,
+            DepcAmount::from_value64(2 * COIN)
+        );
+
+        // not synced to the end yet
+        assert_eq!(
+            finish_catch_up_indexing(&conn, &config, 19, TEST_NOW).unwrap(),
+            None
+        );
+        let planned = finish_catch_up_indexing(&conn, &config, 20, TEST_NOW + 120)
+            .unwrap()
+            .unwrap();
+        assert_eq!(planned.status, CATCH_UP_EXECUTING);
+        assert_eq!((planned.deposits, planned.withdrawals), (1, 1));
+        assert_eq!(planned.planned_at, Some(TEST_NOW + 120));
+        assert_eq!(conn.query_latest_catch_up_plan().unwrap(), Some(planned));
+        assert!(!is_catching_up(&conn).unwrap());
+        // nothing to confirm
+        assert_eq!(confirm_catch_up(&conn, TEST_NOW).unwrap(), None);
+    }
+
+    #[test]
+    fn test_catch_up_confirmation() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let config = make_config();
+        start_catch_up(&conn, &config, 100, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        // the amount is above the threshold
+        add_pending_action(&conn, ACTION_DEPOSIT, 150 * COIN);
+        let plan = finish_catch_up_indexing(&conn, &config, 100, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.status, CATCH_UP_AWAITING_CONFIRMATION);
+        assert!(is_catching_up(&conn).unwrap());
+        // the plan waits for the confirmation after a restart as well
+        assert_eq!(
+            start_catch_up(&conn, &config, 200, TEST_NOW).unwrap(),
+            Some(plan.clone())
+        );
+
+        let confirmed = confirm_catch_up(&conn, TEST_NOW + 60).unwrap().unwrap();
+        assert_eq!(confirmed.status, CATCH_UP_EXECUTING);
+        assert_eq!(confirmed.confirmed_at, Some(TEST_NOW + 60));
+        assert!(!is_catching_up(&conn).unwrap());
+        assert_eq!(confirm_catch_up(&conn, TEST_NOW + 60).unwrap(), None);
+
+        // the plans of the other bridges are kept apart
+        assert!(!is_catching_up(&conn.scoped("other")).unwrap());
+    }
+}
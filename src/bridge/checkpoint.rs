@@ -0,0 +1,101 @@
+use log::info;
+
+use crate::db;
+use crate::depc::Client as DePCClient;
+use crate::BridgeError;
+
+/// Bootstrap the chain index of `conn` from the trusted block `height` of `hash` instead of
+/// syncing from the genesis, the syncing continues from the block after it
+///
+/// The checkpoint is recorded once, so the index bootstrapped from the same checkpoint can be
+/// started again with the same options. An index bootstrapped from another checkpoint, or
+/// synced from the genesis already, is refused.
+pub fn bootstrap_from_checkpoint(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    height: u32,
+    hash: &str,
+    now: u64,
+) -> Result<db::CheckpointRecord, BridgeError> {
+    if let Some(checkpoint) = conn.query_checkpoint()? {
+        if checkpoint.height == height && checkpoint.hash == hash {
+            return Ok(checkpoint);
+        }
+        return Err(BridgeError::InvalidConfig(format!(
+            "the index is bootstrapped from block {} ({}) already",
+            checkpoint.height, checkpoint.hash
+        )));
+    }
+    if let Some(best_height) = conn.query_best_height() {
+        return Err(BridgeError::InvalidConfig(format!(
+            "the index has the blocks up to height {best_height}, only an empty index can be \
+             bootstrapped from a checkpoint"
+        )));
+    }
+    let node_hash = depc_client.get_block_hash(height)?;
+    if node_hash != hash {
+        return Err(BridgeError::InvalidConfig(format!(
+            "block {height} of the DePC node is {node_hash}, not the checkpoint {hash}"
+        )));
+    }
+    let block = depc_client.get_block(hash)?;
+    if block.height != height {
+        return Err(BridgeError::BlockNotFound(height));
+    }
+    conn.transaction(|| {
+        conn.add_block(&block.hash, height, &block.miner, block.time)?;
+        conn.save_checkpoint(height, hash, block.time, now)
+    })?;
+    info!("the index is bootstrapped from block {height} ({hash})");
+    Ok(db::CheckpointRecord {
+        height,
+        hash: hash.to_owned(),
+        time: block.time,
+        created_at: now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeDepcNode;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    #[test]
+    fn test_bootstrap_from_checkpoint() {
+        let node = FakeDepcNode::start();
+        let hashes: Vec<String> = (0..5).map(|_| node.push_block("miner", vec![])).collect();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        // the hash doesn't match the node
+        assert!(bootstrap_from_checkpoint(&conn, &client, 3, &hashes[2], TEST_NOW).is_err());
+        assert_eq!(conn.query_checkpoint().unwrap(), None);
+        assert_eq!(conn.query_best_height(), None);
+
+        let checkpoint =
+            bootstrap_from_checkpoint(&conn, &client, 3, &hashes[3], TEST_NOW).unwrap();
+        assert_eq!(conn.query_checkpoint().unwrap(), Some(checkpoint.clone()));
+        // the syncing continues from height 4
+        assert_eq!(conn.query_best_height(), Some(3));
+        assert_eq!(conn.query_block_time_by_height(3).unwrap(), checkpoint.time);
+
+        // the same checkpoint is accepted again, another one is not
+        assert_eq!(
+            bootstrap_from_checkpoint(&conn, &client, 3, &hashes[3], TEST_NOW + 60).unwrap(),
+            checkpoint
+        );
+        assert!(bootstrap_from_checkpoint(&conn, &client, 4, &hashes[4], TEST_NOW).is_err());
+
+        // the index synced from the genesis is not bootstrapped
+        let synced = db::Conn::open_in_mem().unwrap();
+        synced.init().unwrap();
+        synced
+            .add_block(&hashes[0], 0, "miner", 1531292789)
+            .unwrap();
+        assert!(bootstrap_from_checkpoint(&synced, &client, 3, &hashes[3], TEST_NOW).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use tokio::time::sleep;
+
+use crate::amount::DepcAmount;
+use crate::clock::SharedClock;
+use crate::db;
+use crate::depc::Client as DePCClient;
+use crate::BridgeError;
+
+/// The label of the custody addresses in the wallet of the DePC node
+pub const WATCH_ONLY_LABEL: &str = "depc-bridge-custody";
+
+/// How the custody addresses are imported to the wallet of the DePC node as watch-only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchOnlyImport {
+    /// `importaddress` of the legacy wallets
+    Address,
+    /// `importdescriptors` of the descriptor wallets, the descriptor is `addr(<address>)`
+    Descriptor,
+}
+
+impl FromStr for WatchOnlyImport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(WatchOnlyImport::Address),
+            "descriptor" => Ok(WatchOnlyImport::Descriptor),
+            _ => Err(format!(
+                "unknown watch-only import `{s}`, it's either `address` or `descriptor`"
+            )),
+        }
+    }
+}
+
+/// Import the custody address to the wallet of the DePC node as watch-only, the node answers
+/// `listunspent` and `getreceivedbyaddress` of it then. Importing it again is harmless
+///
+/// The node rescans the blocks for the coins of the address when `rescan` is set, it takes a
+/// while on a long chain. The coins received before the import are not known to the node
+/// otherwise.
+pub fn import_custody_watch_only(
+    depc_client: &DePCClient,
+    address: &str,
+    import: WatchOnlyImport,
+    rescan: bool,
+) -> Result<(), BridgeError> {
+    match import {
+        WatchOnlyImport::Address => {
+            depc_client.import_address(address, WATCH_ONLY_LABEL, rescan)?
+        }
+        WatchOnlyImport::Descriptor => {
+            depc_client.import_descriptor(&format!("addr({address})"), WATCH_ONLY_LABEL, rescan)?
+        }
+    }
+    Ok(())
+}
+
+/// The coins of a custody address in the index compared with the ones in the wallet of the
+/// DePC node, at the same height
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustodyCrossCheck {
+    pub address: String,
+    pub height: u32,
+    pub checked_at: u64,
+    pub index_balance: DepcAmount,
+    pub node_balance: DepcAmount,
+    /// The total of the coins ever paid to the address
+    pub index_received: DepcAmount,
+    pub node_received: DepcAmount,
+    /// The unspent coins (txid, index) the node has but the index doesn't
+    pub missing_in_index: Vec<(String, u32)>,
+    /// The unspent coins (txid, index) the index has but the node doesn't
+    pub missing_on_node: Vec<(String, u32)>,
+}
+
+impl CustodyCrossCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.index_balance == self.node_balance
+            && self.index_received == self.node_received
+            && self.missing_in_index.is_empty()
+            && self.missing_on_node.is_empty()
+    }
+}
+
+impl fmt::Display for CustodyCrossCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "address:  {}", self.address)?;
+        writeln!(f, "height:   {}", self.height)?;
+        writeln!(
+            f,
+            "balance:  {} in the index, {} on the node",
+            self.index_balance, self.node_balance
+        )?;
+        write!(
+            f,
+            "received: {} in the index, {} on the node",
+            self.index_received, self.node_received
+        )?;
+        for (txid, n) in self.missing_in_index.iter() {
+            write!(f, "\n  {txid}:{n} is missing in the index")?;
+        }
+        for (txid, n) in self.missing_on_node.iter() {
+            write!(f, "\n  {txid}:{n} is missing on the node")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the coins of `address` in the index with the wallet of the DePC node, the address
+/// must be imported to the wallet, see `import_custody_watch_only`
+///
+/// Returns `None` when the index is not at the height of the node, the coins of the blocks
+/// not synced yet would differ.
+pub fn cross_check_custody(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    address: &str,
+    now: u64,
+) -> Result<Option<CustodyCrossCheck>, BridgeError> {
+    let height = depc_client.get_height()?;
+    if conn.query_best_height() != Some(height) {
+        return Ok(None);
+    }
+    let unspents = depc_client.list_unspent(&address.to_owned())?;
+    let node_received = depc_client.get_received_by_address(&address.to_owned())?;
+    let coins = conn.query_unspent_coins(address)?;
+    let index_received = conn.query_received_by_address(address)?;
+    // a block is found while they're queried
+    if depc_client.get_height()? != height || conn.query_best_height() != Some(height) {
+        return Ok(None);
+    }
+
+    let node_coins: BTreeSet<(String, u32)> = unspents
+        .iter()
+        .map(|unspent| (unspent.txid.clone(), unspent.vout))
+        .collect();
+    let index_coins: BTreeSet<(String, u32)> = coins
+        .iter()
+        .map(|coin| (coin.txid.clone(), coin.n))
+        .collect();
+    Ok(Some(CustodyCrossCheck {
+        address: address.to_owned(),
+        height,
+        checked_at: now,
+        index_balance: DepcAmount::from_value64(
+            coins.iter().map(|coin| coin.value.value64()).sum(),
+        ),
+        node_balance: DepcAmount::from_value64(
+            unspents.iter().map(|unspent| unspent.value64()).sum(),
+        ),
+        index_received,
+        node_received: DepcAmount::from_value64(node_received),
+        missing_in_index: node_coins.difference(&index_coins).cloned().collect(),
+        missing_on_node: index_coins.difference(&node_coins).cloned().collect(),
+    }))
+}
+
+/// Cross-check the coins of the custody addresses with the wallet of the DePC node every
+/// `interval`, the discrepancies are logged as warnings
+pub async fn run_custody_cross_check(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    depc_client: DePCClient,
+    addresses: Vec<String>,
+    interval: Duration,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        for address in addresses.iter() {
+            let (conn, depc_client, address, now) = (
+                conn.clone(),
+                depc_client.clone(),
+                address.clone(),
+                clock.now(),
+            );
+            // the DePC node is called by the blocking client
+            let res = tokio::task::spawn_blocking(move || {
+                cross_check_custody(&conn, &depc_client, &address, now)
+                    .map(|report| (address, report))
+            })
+            .await
+            .map_err(|_| BridgeError::ChannelClosed("custody_cross_check"))?;
+            match res {
+                Ok((address, None)) => {
+                    debug!("the index is not at the height of the node, {address} is not checked")
+                }
+                Ok((_, Some(report))) if report.is_consistent() => {
+                    info!(
+                        "custody address {} matches the node at height {}",
+                        report.address, report.height
+                    )
+                }
+                Ok((_, Some(report))) => {
+                    warn!("custody address differs from the wallet of the node\n{report}")
+                }
+                Err(e) => warn!(
+                    "cannot cross-check the custody address, reason: {}",
+                    e.full_message()
+                ),
+            }
+        }
+        sleep(interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::depc::COIN;
+    use crate::testing::{make_transaction, FakeDepcNode};
+
+    const TEST_CUSTODY_ADDRESS: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_NOW: u64 = 1731000000;
+
+    #[test]
+    fn test_watch_only_import_from_str() {
+        assert_eq!("address".parse(), Ok(WatchOnlyImport::Address));
+        assert_eq!("descriptor".parse(), Ok(WatchOnlyImport::Descriptor));
+        assert!("xpub".parse::<WatchOnlyImport>().is_err());
+    }
+
+    #[test]
+    fn test_cross_check_custody() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        // the node doesn't know the coins of the address before it's imported
+        assert!(client
+            .get_received_by_address(&TEST_CUSTODY_ADDRESS.to_owned())
+            .is_err());
+        import_custody_watch_only(
+            &client,
+            TEST_CUSTODY_ADDRESS,
+            WatchOnlyImport::Descriptor,
+            true,
+        )
+        .unwrap();
+        import_custody_watch_only(
+            &client,
+            TEST_CUSTODY_ADDRESS,
+            WatchOnlyImport::Address,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            node.watch_only_addresses(),
+            vec![TEST_CUSTODY_ADDRESS.to_owned(); 2]
+        );
+
+        let txid = format!("{:064x}", 0xc1);
+        node.push_block(TEST_CUSTODY_ADDRESS, vec![]);
+        let hash = node.push_block(
+            TEST_CUSTODY_ADDRESS,
+            vec![make_transaction(
+                &txid,
+                &[],
+                &[(TEST_CUSTODY_ADDRESS, 5 * COIN, "")],
+            )],
+        );
+        node.add_unspent(TEST_CUSTODY_ADDRESS, &txid, 0, 5 * COIN);
+        // the index is behind the node
+        assert_eq!(
+            cross_check_custody(&conn, &client, TEST_CUSTODY_ADDRESS, TEST_NOW).unwrap(),
+            None
+        );
+
+        conn.add_block("genesis", 0, TEST_CUSTODY_ADDRESS, 0)
+            .unwrap();
+        conn.add_block(&hash, 1, TEST_CUSTODY_ADDRESS, 180).unwrap();
+        conn.add_transaction(&hash, &txid).unwrap();
+        conn.add_coin(
+            &txid,
+            0,
+            DepcAmount::from_value64(5 * COIN),
+            TEST_CUSTODY_ADDRESS,
+            "",
+        )
+        .unwrap();
+        let report = cross_check_custody(&conn, &client, TEST_CUSTODY_ADDRESS, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert!(report.is_consistent(), "{report}");
+        assert_eq!(report.height, 1);
+        assert_eq!(report.node_balance, DepcAmount::from_value64(5 * COIN));
+
+        // a coin the index misses
+        let missing_txid = format!("{:064x}", 0xc2);
+        node.add_unspent(TEST_CUSTODY_ADDRESS, &missing_txid, 1, COIN);
+        let report = cross_check_custody(&conn, &client, TEST_CUSTODY_ADDRESS, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert!(!report.is_consistent());
+        assert_eq!(report.missing_in_index, vec![(missing_txid, 1)]);
+        assert!(report.missing_on_node.is_empty());
+        assert_eq!(report.node_balance, DepcAmount::from_value64(6 * COIN));
+        assert_eq!(report.index_received, report.node_received);
+    }
+}
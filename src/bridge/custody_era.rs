@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::db;
+use crate::BridgeError;
+
+/// The old custody address is still watched for so long after it's rotated unless it's set
+pub const DEFAULT_CUSTODY_OVERLAP: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// The custody addresses of a bridge pair by era, see `rotate_custody_address`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustodyEras(Vec<db::CustodyEraRecord>);
+
+impl CustodyEras {
+    pub fn new(eras: Vec<db::CustodyEraRecord>) -> CustodyEras {
+        CustodyEras(eras)
+    }
+
+    /// The era of the current custody address, `None` before the eras are recorded
+    pub fn current(&self) -> Option<u32> {
+        self.0.last().map(|era| era.era)
+    }
+
+    /// The era an output to `address` in a block of `block_time` is credited to, the address
+    /// of an old era is watched only until the overlap of the rotation ends
+    pub fn watched_era(&self, address: &str, block_time: u64) -> Option<u32> {
+        self.0
+            .iter()
+            .rev()
+            .find(|era| {
+                era.address == address
+                    && era
+                        .watched_until
+                        .is_none_or(|watched_until| block_time <= watched_until)
+            })
+            .map(|era| era.era)
+    }
+
+    /// The custody address is of any era, watched or not
+    pub fn contains(&self, address: &str) -> bool {
+        self.0.iter().any(|era| era.address == address)
+    }
+
+    /// The custody addresses of the old eras, the coins left on them still back the supply
+    pub fn retired_addresses(&self) -> impl Iterator<Item = &str> {
+        let current = self.0.len().saturating_sub(1);
+        self.0[..current].iter().map(|era| era.address.as_str())
+    }
+
+    pub fn eras(&self) -> &[db::CustodyEraRecord] {
+        &self.0
+    }
+}
+
+/// Record `address` as the custody address of the bridge of `conn`, a new era is started when
+/// it's not the address of the current era
+///
+/// The address of the current era is watched for `overlap` more, the deposits and the
+/// withdrawals sent to it meanwhile are still processed and recorded with its era, but the
+/// payouts are made only from the new address. An address of an old era cannot be the custody
+/// address again.
+pub fn rotate_custody_address(
+    conn: &db::Conn,
+    address: &str,
+    overlap: Duration,
+    now: u64,
+) -> Result<CustodyEras, BridgeError> {
+    let eras = CustodyEras::new(conn.query_custody_eras()?);
+    match eras.eras().last() {
+        Some(current) if current.address == address => return Ok(eras),
+        Some(_) if eras.contains(address) => {
+            return Err(BridgeError::InvalidConfig(format!(
+                "custody address {address} of bridge `{}` is retired, it cannot be used again",
+                conn.bridge_id()
+            )));
+        }
+        Some(current) => {
+            let watched_until = now.saturating_add(overlap.as_secs());
+            warn!(
+                "custody address of bridge {} is rotated from {} to {address}, the old one is \
+                 watched until {watched_until}",
+                conn.bridge_id(),
+                current.address
+            );
+            conn.start_custody_era(address, watched_until, now)?;
+        }
+        None => {
+            info!(
+                "custody address {address} of bridge {} starts the first era",
+                conn.bridge_id()
+            );
+            // there's no era to retire
+            conn.start_custody_era(address, now, now)?;
+        }
+    }
+    Ok(CustodyEras::new(conn.query_custody_eras()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_NOW: u64 = 1731000600;
+    const OVERLAP: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_rotate_custody_address() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let eras = rotate_custody_address(&conn, "old", OVERLAP, TEST_NOW).unwrap();
+        assert_eq!(eras.current(), Some(1));
+        assert_eq!(eras.watched_era("old", TEST_NOW + 1_000_000), Some(1));
+        // the same address on restart
+        assert_eq!(
+            rotate_custody_address(&conn, "old", OVERLAP, TEST_NOW + 10).unwrap(),
+            eras
+        );
+
+        let rotated_at = TEST_NOW + 100;
+        let eras = rotate_custody_address(&conn, "new", OVERLAP, rotated_at).unwrap();
+        assert_eq!(eras.current(), Some(2));
+        assert_eq!(eras.eras()[0].watched_until, Some(rotated_at + 3600));
+        assert_eq!(eras.eras()[1].started_at, rotated_at);
+        // both are watched in the overlap, only the new one after it
+        assert_eq!(eras.watched_era("old", rotated_at + 3600), Some(1));
+        assert_eq!(eras.watched_era("new", rotated_at + 3600), Some(2));
+        assert_eq!(eras.watched_era("old", rotated_at + 3601), None);
+        assert!(eras.contains("old"));
+        assert_eq!(eras.retired_addresses().collect::<Vec<_>>(), vec!["old"]);
+
+        assert!(matches!(
+            rotate_custody_address(&conn, "old", OVERLAP, rotated_at + 200),
+            Err(BridgeError::InvalidConfig(_))
+        ));
+        // the eras are kept by bridge
+        let other = rotate_custody_address(&conn.scoped("other"), "old", OVERLAP, TEST_NOW);
+        assert_eq!(other.unwrap().current(), Some(1));
+    }
+}
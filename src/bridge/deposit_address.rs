@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use log::{info, warn};
+
+use crate::db;
+use crate::depc::{DerivationPath, ExtendedPubKey, DEFAULT_GAP_LIMIT, EXTERNAL_CHAIN};
+use crate::BridgeError;
+
+/// The times a registration is made again when the index is taken by another one meanwhile
+const MAX_REGISTRATION_ATTEMPTS: u32 = 3;
+
+/// Derive the deposit addresses of the bridge of `conn` from `xpub`, it's saved so the REST
+/// service derives the same addresses as the syncer credits
+///
+/// The indexes given out are of the saved xpub, it cannot be changed once an address is
+/// registered. The addresses of the xpub paid on the chain but not registered are warned about,
+/// e.g. when the database is restored from an old backup, they're not credited.
+pub fn set_deposit_xpub(conn: &db::Conn, xpub: &ExtendedPubKey) -> Result<(), BridgeError> {
+    warn_unregistered_deposit_addresses(conn, xpub)?;
+    let xpub = xpub.to_string();
+    match conn.query_deposit_xpub()? {
+        Some(saved) if saved == xpub => return Ok(()),
+        Some(_) if conn.query_next_deposit_address_index()? > 0 => {
+            return Err(BridgeError::InvalidConfig(format!(
+                "the deposit addresses of bridge `{}` are derived from another xpub",
+                conn.bridge_id()
+            )));
+        }
+        _ => {}
+    }
+    conn.save_deposit_xpub(&xpub).map_err(|e| {
+        BridgeError::InvalidConfig(format!(
+            "cannot save the deposit xpub of bridge `{}`, it might be used by another bridge, \
+             {e}",
+            conn.bridge_id()
+        ))
+    })
+}
+
+fn warn_unregistered_deposit_addresses(
+    conn: &db::Conn,
+    xpub: &ExtendedPubKey,
+) -> Result<(), BridgeError> {
+    let used = xpub.scan_addresses(EXTERNAL_CHAIN, DEFAULT_GAP_LIMIT, |address| {
+        conn.query_address_has_coins(address)
+            .map_err(BridgeError::from)
+    })?;
+    for (path, address) in used {
+        if conn.query_deposit_address(&address)?.is_none() {
+            warn!(
+                "deposit address {address} ({path}) is paid but not registered, it's not credited"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Give out the next deposit address of the bridge of `conn` for `recipient`, `None` if the
+/// bridge has no deposit xpub
+///
+/// Any payment to the address above the deposit threshold is credited to the recipient while
+/// syncing, without OP_RETURN.
+pub fn register_deposit_address(
+    conn: &db::Conn,
+    recipient: &str,
+    now: u64,
+) -> Result<Option<db::DepositAddressRecord>, BridgeError> {
+    let Some(xpub) = conn.query_deposit_xpub()? else {
+        return Ok(None);
+    };
+    let xpub = ExtendedPubKey::from_str(&xpub)?;
+    let mut attempts = 0;
+    loop {
+        let derivation_index = conn.query_next_deposit_address_index()?;
+        let path = DerivationPath::external(derivation_index);
+        let address = xpub.derive_address_at(path)?;
+        let derivation_path = path.to_string();
+        match conn.add_deposit_address(&address, derivation_index, &derivation_path, recipient, now)
+        {
+            Ok(()) => {
+                info!(
+                    "deposit address {address} ({derivation_path}) is registered for {recipient}"
+                );
+                return Ok(Some(db::DepositAddressRecord {
+                    address,
+                    derivation_index,
+                    derivation_path,
+                    recipient: recipient.to_owned(),
+                    created_at: now,
+                }));
+            }
+            // another registration takes the index
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::ConstraintViolation
+                    && attempts + 1 < MAX_REGISTRATION_ATTEMPTS =>
+            {
+                attempts += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_XPUB: &str =
+        "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+    const TEST_TPUB: &str =
+        "tpubDCBWBScQPGv4Xk3JSbhw6wYYpayMjb2eAYyArpbSqQTbLDpphHGAetB6VQgVeftLML8vDSUEWcC2xDi3qJJ3YCDChJDvqVzpgoYSuT52MhJ";
+
+    #[test]
+    fn test_register_deposit_addresses() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        assert_eq!(
+            register_deposit_address(&conn, "recipient", 1000).unwrap(),
+            None
+        );
+
+        let xpub = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
+        set_deposit_xpub(&conn, &xpub).unwrap();
+        let first = register_deposit_address(&conn, "recipient1", 1000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.address, "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        assert_eq!(first.derivation_index, 0);
+        assert_eq!(first.derivation_path, "0/0");
+        // a recipient registers again for another address
+        let second = register_deposit_address(&conn, "recipient1", 1100)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.address, "1Ak8PffB2meyfYnbXZR9EGfLfFZVpzJvQP");
+        assert_eq!(
+            conn.query_deposit_address(&second.address).unwrap(),
+            Some(second)
+        );
+
+        // the same xpub is set again on restart, another one is refused
+        set_deposit_xpub(&conn, &xpub).unwrap();
+        let tpub = ExtendedPubKey::from_str(TEST_TPUB).unwrap();
+        assert!(matches!(
+            set_deposit_xpub(&conn, &tpub),
+            Err(BridgeError::InvalidConfig(_))
+        ));
+        // and the xpub of a bridge is not used by another
+        assert!(matches!(
+            set_deposit_xpub(&conn.scoped("other"), &xpub),
+            Err(BridgeError::InvalidConfig(_))
+        ));
+        set_deposit_xpub(&conn.scoped("other"), &tpub).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+use log::error;
+use solana_sdk::hash::{hashv, Hash};
+
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::BridgeError;
+
+/// The prefixes keep a leaf from being taken for a node of the tree
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// The side the sibling is on at a level of the proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofSide {
+    Left,
+    Right,
+}
+
+impl ProofSide {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProofSide::Left => "left",
+            ProofSide::Right => "right",
+        }
+    }
+}
+
+/// A step of an inclusion proof from the leaf up to the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofStep {
+    pub sibling: Hash,
+    pub side: ProofSide,
+}
+
+/// A binary SHA-256 Merkle tree, a leaf is `sha256(0x00 || data)` and a node is
+/// `sha256(0x01 || left || right)`, the last node of an odd level is moved up as it is
+pub struct MerkleTree {
+    /// The leaves first and the root last
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    _ => pair[0],
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { levels }
+    }
+
+    /// The root of no leaf is all zeros
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// The siblings from the leaf at `index` up to the root, `None` if there's no such leaf
+    pub fn proof(&self, mut index: usize) -> Option<Vec<ProofStep>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+        let mut steps = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            let step = if index % 2 == 1 {
+                Some(ProofStep {
+                    sibling: level[index - 1],
+                    side: ProofSide::Left,
+                })
+            } else {
+                level.get(index + 1).map(|sibling| ProofStep {
+                    sibling: *sibling,
+                    side: ProofSide::Right,
+                })
+            };
+            steps.extend(step);
+            index /= 2;
+        }
+        Some(steps)
+    }
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    hashv(&[NODE_PREFIX, left.as_ref(), right.as_ref()])
+}
+
+/// Check the proof leads from `leaf` to `root`
+pub fn verify_merkle_proof(leaf: Hash, proof: &[ProofStep], root: Hash) -> bool {
+    let hash = proof.iter().fold(leaf, |hash, step| match step.side {
+        ProofSide::Left => hash_node(&step.sibling, &hash),
+        ProofSide::Right => hash_node(&hash, &step.sibling),
+    });
+    hash == root
+}
+
+/// The data of the leaf of a deposit, `{depc_txid}:{recipient}:{amount in satoshis}`
+pub fn make_deposit_leaf_data(depc_txid: &str, recipient: &str, amount: DepcAmount) -> String {
+    format!("{depc_txid}:{recipient}:{}", amount.value64())
+}
+
+pub fn hash_deposit_leaf(deposit: &db::DepositRecord) -> Hash {
+    let data = make_deposit_leaf_data(&deposit.depc_txid, &deposit.to_address, deposit.amount);
+    hashv(&[LEAF_PREFIX, data.as_bytes()])
+}
+
+fn make_deposit_tree(deposits: &[db::DepositRecord]) -> MerkleTree {
+    MerkleTree::new(deposits.iter().map(hash_deposit_leaf).collect())
+}
+
+/// Build the tree of the deposits minted before `now` and save its root, `None` when there's
+/// no deposit or none is minted since the latest root
+pub fn publish_deposit_root(
+    conn: &db::Conn,
+    now: u64,
+) -> Result<Option<db::DepositRootRecord>, BridgeError> {
+    let deposits = conn.query_confirmed_deposits(now)?;
+    if deposits.is_empty() {
+        return Ok(None);
+    }
+    let root = hex::encode(make_deposit_tree(&deposits).root());
+    if let Some(latest) = conn.query_latest_deposit_root()? {
+        if latest.root == root {
+            return Ok(None);
+        }
+    }
+    let leaf_count = deposits.len() as u64;
+    let id = conn.add_deposit_root(&root, leaf_count, now, now)?;
+    Ok(Some(db::DepositRootRecord {
+        id,
+        root,
+        leaf_count,
+        covered_until: now,
+        created_at: now,
+        memo_signature: None,
+    }))
+}
+
+/// The memo the root is published by on Solana
+pub fn make_deposit_root_memo(bridge_id: &str, root: &db::DepositRootRecord) -> String {
+    format!(
+        "depc-bridge deposit root bridge={bridge_id} root={} leaves={} until={}",
+        root.root, root.leaf_count, root.covered_until
+    )
+}
+
+/// The inclusion proof of a deposit in the latest root
+#[derive(Debug, Clone)]
+pub struct DepositProof {
+    pub deposit: db::DepositRecord,
+    /// The position of the leaf in the tree
+    pub index: usize,
+    pub leaf: Hash,
+    pub steps: Vec<ProofStep>,
+    pub root: db::DepositRootRecord,
+}
+
+/// `None` when there's no root yet or the deposit is minted after the latest root
+pub fn query_deposit_proof(
+    conn: &db::Conn,
+    depc_txid: &str,
+) -> Result<Option<DepositProof>, BridgeError> {
+    let Some(root) = conn.query_latest_deposit_root()? else {
+        return Ok(None);
+    };
+    let deposits = conn.query_confirmed_deposits(root.covered_until)?;
+    let Some(index) = deposits
+        .iter()
+        .position(|deposit| deposit.depc_txid == depc_txid)
+    else {
+        return Ok(None);
+    };
+    let tree = make_deposit_tree(&deposits);
+    if hex::encode(tree.root()) != root.root {
+        // the deposits are changed after the root is built
+        error!(
+            "the deposits before {} don't make deposit root {} any more",
+            root.covered_until, root.root
+        );
+        return Ok(None);
+    }
+    let steps = tree.proof(index).unwrap();
+    Ok(Some(DepositProof {
+        leaf: hash_deposit_leaf(&deposits[index]),
+        deposit: deposits[index].clone(),
+        index,
+        steps,
+        root,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_NOW: u64 = 1731000600;
+
+    fn leaf(n: u8) -> Hash {
+        hashv(&[LEAF_PREFIX, &[n]])
+    }
+
+    #[test]
+    fn test_merkle_tree() {
+        assert_eq!(MerkleTree::new(vec![]).root(), Hash::default());
+        assert_eq!(MerkleTree::new(vec![leaf(0)]).root(), leaf(0));
+        assert_eq!(MerkleTree::new(vec![leaf(0)]).proof(0), Some(vec![]));
+
+        // the third leaf is moved up to the second level
+        let tree = MerkleTree::new(vec![leaf(0), leaf(1), leaf(2)]);
+        assert_eq!(
+            tree.root(),
+            hash_node(&hash_node(&leaf(0), &leaf(1)), &leaf(2))
+        );
+        assert_eq!(
+            tree.proof(2),
+            Some(vec![ProofStep {
+                sibling: hash_node(&leaf(0), &leaf(1)),
+                side: ProofSide::Left,
+            }])
+        );
+        assert_eq!(tree.proof(3), None);
+
+        for count in 1..=9 {
+            let leaves: Vec<_> = (0..count).map(leaf).collect();
+            let tree = MerkleTree::new(leaves.clone());
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(verify_merkle_proof(*leaf, &proof, tree.root()));
+                assert!(!verify_merkle_proof(hashv(&[b"x"]), &proof, tree.root()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_deposit_proof() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        assert_eq!(publish_deposit_root(&conn, TEST_NOW).unwrap(), None);
+        for (i, depc_txid) in ["t1", "t2", "t3"].into_iter().enumerate() {
+            conn.save_deposit(depc_txid, "r", DepcAmount::from_value64(10), TEST_NOW)
+                .unwrap();
+            conn.confirm_deposit("sig", TEST_NOW + i as u64, depc_txid)
+                .unwrap();
+        }
+
+        // the deposit minted at the time of the root is left to the next one
+        let root = publish_deposit_root(&conn, TEST_NOW + 2).unwrap().unwrap();
+        assert_eq!(root.leaf_count, 2);
+        assert_eq!(publish_deposit_root(&conn, TEST_NOW + 2).unwrap(), None);
+        assert!(query_deposit_proof(&conn, "t3").unwrap().is_none());
+
+        let proof = query_deposit_proof(&conn, "t2").unwrap().unwrap();
+        assert_eq!(proof.index, 1);
+        assert_eq!(proof.root, root);
+        let data = make_deposit_leaf_data("t2", "r", DepcAmount::from_value64(10));
+        assert_eq!(data, "t2:r:10");
+        assert_eq!(proof.leaf, hashv(&[&[0u8][..], data.as_bytes()]));
+        let root_hash = Hash::new(&hex::decode(&root.root).unwrap());
+        assert!(verify_merkle_proof(proof.leaf, &proof.steps, root_hash));
+
+        let root = publish_deposit_root(&conn, TEST_NOW + 3).unwrap().unwrap();
+        assert_eq!(root.leaf_count, 3);
+        assert!(query_deposit_proof(&conn, "t3").unwrap().is_some());
+        assert!(query_deposit_proof(&conn, "t4").unwrap().is_none());
+        assert_eq!(
+            make_deposit_root_memo("default", &root),
+            format!(
+                "depc-bridge deposit root bridge=default root={} leaves=3 until={}",
+                root.root,
+                TEST_NOW + 3
+            )
+        );
+    }
+}
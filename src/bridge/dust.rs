@@ -0,0 +1,167 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use tokio::time::sleep;
+
+use crate::amount::DepcAmount;
+use crate::clock::SharedClock;
+use crate::db;
+use crate::ledger::Ledger;
+use crate::solana::TokenClient;
+use crate::BridgeError;
+
+/// The most auxiliary accounts consolidated by one transaction, the transaction must fit in a
+/// packet. The rest are swept by the next sweep
+pub const MAX_DUST_SWEEP_ACCOUNTS: usize = 16;
+
+/// Consolidate the tokens stranded in the auxiliary accounts of the authority (see
+/// `TokenClient::stranded_balances`) back to its token account by one Solana transaction. The
+/// accounts are recorded to table `dust_sweeps` and the total to the event feed of the ledger.
+/// Nothing is returned if no account holds any token
+pub fn sweep_dust<C: TokenClient>(
+    conn: &db::Conn,
+    contract_client: &C,
+    timestamp: u64,
+) -> Result<Vec<db::DustSweepRecord>, BridgeError> {
+    let accounts: Vec<(C::Address, C::Amount)> = contract_client
+        .stranded_balances()
+        .map_err(BridgeError::token_client::<C>)?
+        .into_iter()
+        .filter(|(_, amount)| Into::<u64>::into(amount.clone()) > 0)
+        .take(MAX_DUST_SWEEP_ACCOUNTS)
+        .collect();
+    if accounts.is_empty() {
+        return Ok(vec![]);
+    }
+    let signature = contract_client
+        .consolidate(&accounts)
+        .map_err(BridgeError::token_client::<C>)?
+        .txid
+        .to_string();
+
+    let accounts: Vec<(String, DepcAmount)> = accounts
+        .into_iter()
+        .map(|(account, amount)| (account.to_string(), DepcAmount::from_value64(amount.into())))
+        .collect();
+    let mut total = DepcAmount::from_value64(0);
+    for (_, amount) in accounts.iter() {
+        total = total
+            .checked_add(*amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+    }
+    warn!(
+        "the tokens {total} stranded in {} auxiliary accounts are swept by {signature}",
+        accounts.len()
+    );
+    let ids = conn.add_dust_sweeps(&accounts, &signature, timestamp)?;
+    Ledger::new(conn.clone()).dust_sweep(&signature, total, timestamp)?;
+    Ok(ids
+        .into_iter()
+        .zip(accounts)
+        .map(|(id, (account, amount))| db::DustSweepRecord {
+            id,
+            account,
+            amount,
+            signature: signature.clone(),
+            created_at: timestamp,
+        })
+        .collect())
+}
+
+/// Sweep the stranded tokens every `interval`, see `sweep_dust`
+pub async fn run_dust_sweep<C>(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    contract_client: C,
+    interval: Duration,
+    clock: SharedClock,
+) -> Result<(), BridgeError>
+where
+    C: TokenClient + Clone + Send + Sync + 'static,
+{
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        let (conn, contract_client, now) = (conn.clone(), contract_client.clone(), clock.now());
+        // the token client is blocking
+        let res = tokio::task::spawn_blocking(move || sweep_dust(&conn, &contract_client, now))
+            .await
+            .map_err(|_| BridgeError::ChannelClosed("dust_sweep"))?;
+        match res {
+            Ok(sweeps) if sweeps.is_empty() => debug!("no token is stranded"),
+            Ok(sweeps) => info!("{} auxiliary accounts are swept", sweeps.len()),
+            Err(e) => warn!(
+                "cannot sweep the stranded tokens, reason: {}",
+                e.full_message()
+            ),
+        }
+        sleep(interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::ledger::LedgerEvent;
+    use crate::testing::{MockError, MockTokenClient};
+
+    const TEST_NOW: u64 = 1731000600;
+
+    #[test]
+    fn test_sweep_dust() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let contract_client = MockTokenClient::new();
+        assert!(sweep_dust(&conn, &contract_client, TEST_NOW)
+            .unwrap()
+            .is_empty());
+
+        let (first, second, empty) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        contract_client.add_stranded_balance(first, 1500);
+        contract_client.add_stranded_balance(empty, 0);
+        contract_client.add_stranded_balance(second, 20);
+
+        // nothing is recorded when the transaction fails
+        contract_client.push_send_result(Err(MockError::transient("node is busy")));
+        assert!(sweep_dust(&conn, &contract_client, TEST_NOW).is_err());
+        assert!(conn.query_dust_sweeps(10).unwrap().is_empty());
+
+        let sweeps = sweep_dust(&conn, &contract_client, TEST_NOW).unwrap();
+        assert_eq!(
+            sweeps
+                .iter()
+                .map(|sweep| (sweep.account.clone(), sweep.amount.value64()))
+                .collect::<Vec<_>>(),
+            vec![(first.to_string(), 1500), (second.to_string(), 20)]
+        );
+        assert_eq!(sweeps[0].signature, sweeps[1].signature);
+        let mut recorded = conn.query_dust_sweeps(10).unwrap();
+        recorded.reverse();
+        assert_eq!(recorded, sweeps);
+        let events = conn.query_events(0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, LedgerEvent::DustSweep.as_str());
+        assert_eq!(events[0].amount, DepcAmount::from_value64(1520));
+        assert!(Ledger::new(conn.clone())
+            .trial_balance()
+            .unwrap()
+            .is_balanced());
+
+        // the accounts are emptied
+        assert!(sweep_dust(&conn, &contract_client, TEST_NOW)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,582 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use tokio::time::sleep;
+
+use crate::amount::DepcAmount;
+use crate::clock::SharedClock;
+use crate::db;
+use crate::depc::{Address as DePCAddress, Client as DePCClient, TxID, DEFAULT_TX_FEE};
+use crate::ledger::Ledger;
+use crate::BridgeError;
+
+/// Bumping the fees of the payouts those are left unconfirmed, see `bump_stuck_payouts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBumpConfig {
+    /// A payout is bumped when it's still unconfirmed so many blocks after it's sent, or after
+    /// its last bump
+    pub after_blocks: u32,
+    /// The fee added by each bump
+    pub fee_step: DepcAmount,
+    /// The most fee paid for a payout, the fee it's made with and the bumps, it's left stuck
+    /// when the next bump exceeds it
+    pub max_fee: DepcAmount,
+    pub interval: Duration,
+}
+
+impl FeeBumpConfig {
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        if self.after_blocks == 0 || self.fee_step.is_zero() {
+            return Err(BridgeError::InvalidConfig(
+                "the fee bump blocks and step should be positive".to_owned(),
+            ));
+        }
+        let first_fee = DepcAmount::from_value64(DEFAULT_TX_FEE).checked_add(self.fee_step);
+        if !first_fee.is_some_and(|fee| fee <= self.max_fee) {
+            return Err(BridgeError::InvalidConfig(format!(
+                "the max fee {} leaves no room for a bump of {}",
+                self.max_fee, self.fee_step
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Bump the fees of the withdrawal payouts still unconfirmed `after_blocks` after they're
+/// sent (see `db::Conn::query_stuck_payouts`)
+///
+/// A payout signals replaceability (see `depc::ClientBuilder::set_replaceable`) is replaced by
+/// the same transaction with its change reduced by `fee_step`, the withdrawal takes the txid
+/// of the replacement. Otherwise a child spends the change back to the custody paying the fee
+/// for both (CPFP), the later bumps extend the chain from the last child. The bumps are
+/// recorded to table `payout_bumps` and only the added fees are posted to the ledger, the
+/// payout itself is counted once.
+///
+/// Returns the bumps made
+pub fn bump_stuck_payouts(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    custody_address: &DePCAddress,
+    config: &FeeBumpConfig,
+    now: u64,
+) -> Result<Vec<db::PayoutBumpRecord>, BridgeError> {
+    let Some(height) = conn.query_best_height() else {
+        return Ok(vec![]);
+    };
+    let Some(max_sent_height) = height.checked_sub(config.after_blocks) else {
+        return Ok(vec![]);
+    };
+    let mut bumps = vec![];
+    for payout in conn.query_stuck_payouts(max_sent_height)? {
+        // a payout failed to bump doesn't hold the others
+        match bump_payout(
+            conn,
+            depc_client,
+            custody_address,
+            config,
+            &payout,
+            height,
+            now,
+        ) {
+            Ok(Some(bump)) => bumps.push(bump),
+            Ok(None) => {}
+            Err(e) => warn!(
+                "cannot bump the payout of withdrawal {}, reason: {}",
+                payout.id,
+                e.full_message()
+            ),
+        }
+    }
+    Ok(bumps)
+}
+
+fn bump_payout(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    custody_address: &DePCAddress,
+    config: &FeeBumpConfig,
+    payout: &db::PendingActionRecord,
+    height: u32,
+    now: u64,
+) -> Result<Option<db::PayoutBumpRecord>, BridgeError> {
+    let Some(payout_txid) = payout.txid.clone() else {
+        return Ok(None);
+    };
+    let bumps = conn.query_payout_bumps(payout.id)?;
+    let mut paid = DepcAmount::from_value64(DEFAULT_TX_FEE);
+    for bump in bumps.iter() {
+        paid = paid
+            .checked_add(bump.fee)
+            .ok_or(BridgeError::AmountOverflow)?;
+    }
+    let fee = paid
+        .checked_add(config.fee_step)
+        .ok_or(BridgeError::AmountOverflow)?;
+    if fee > config.max_fee {
+        warn!(
+            "the payout {payout_txid} of withdrawal {} is stuck with fee {paid}, it's not bumped \
+             over {}",
+            payout.id, config.max_fee
+        );
+        return Ok(None);
+    }
+
+    // the next child spends the change of the last one
+    let tip = match bumps.last() {
+        Some(bump) if bump.kind == "cpfp" => bump.txid.clone(),
+        _ => payout_txid.clone(),
+    };
+    let tx = depc_client.get_transaction(&tip)?;
+    let Some(change) = tx
+        .vout
+        .iter()
+        .find(|txout| txout.get_address().as_ref() == Some(custody_address))
+        .filter(|txout| txout.value64 > config.fee_step.value64())
+    else {
+        warn!(
+            "the payout {payout_txid} of withdrawal {} has no change to pay the fee {} from",
+            payout.id, config.fee_step
+        );
+        return Ok(None);
+    };
+
+    let kind = if tip == payout_txid && tx.signals_rbf() {
+        "rbf"
+    } else {
+        "cpfp"
+    };
+    let (inputs, outputs) = if kind == "rbf" {
+        let inputs: Vec<(TxID, u32)> = tx
+            .vin
+            .iter()
+            .filter_map(|txin| Some((txin.txid.clone()?, txin.vout?)))
+            .collect();
+        let mut outputs = vec![];
+        for txout in tx.vout.iter() {
+            let Some(address) = txout.get_address() else {
+                warn!("the payout {payout_txid} pays to a script without address, keep it");
+                return Ok(None);
+            };
+            let value64 = if txout.n == change.n {
+                txout.value64 - config.fee_step.value64()
+            } else {
+                txout.value64
+            };
+            outputs.push((address, value64));
+        }
+        (inputs, outputs)
+    } else {
+        // the change is unconfirmed, the payouts never pick it, see `depc::Client::list_unspent`
+        (
+            vec![(tip.clone(), change.n)],
+            vec![(
+                custody_address.clone(),
+                change.value64 - config.fee_step.value64(),
+            )],
+        )
+    };
+    let hex = depc_client.create_raw_transaction(&inputs, &outputs, kind == "rbf")?;
+    let signed_tx = depc_client.sign_raw_transaction(&hex)?;
+    let txid = depc_client.decode_raw_transaction_txid(&signed_tx)?;
+
+    // the bump is saved before it's sent, the payout isn't bumped again until it's sent
+    let id = conn.add_payout_bump(
+        payout.id,
+        kind,
+        &tip,
+        &txid,
+        &signed_tx,
+        config.fee_step,
+        now,
+    )?;
+    let mut bump = db::PayoutBumpRecord {
+        id,
+        action_id: payout.id,
+        kind: kind.to_owned(),
+        bumped_txid: tip,
+        txid,
+        raw_tx: signed_tx,
+        fee: config.fee_step,
+        status: "pending".to_owned(),
+        created_at: now,
+    };
+    if let Err(e) = depc_client.send_raw_transaction(&bump.raw_tx) {
+        // the bump might be broadcast already, it's reconciled on the next start, see
+        // `recover_payout_bumps`
+        error!(
+            "bump {} of the payout {payout_txid} of withdrawal {} is in doubt, it's checked on \
+             the next start, reason: {e}",
+            bump.txid, payout.id
+        );
+        return Ok(None);
+    }
+    complete_payout_bump(conn, &bump, height, now)?;
+    bump.status = "sent".to_owned();
+    info!(
+        "the payout {payout_txid} of withdrawal {} is bumped by {kind} {}, the fee is {fee} now",
+        payout.id, bump.txid
+    );
+    Ok(Some(bump))
+}
+
+/// The bump is sent, the payout takes it and the ledger posts the added fee together
+fn complete_payout_bump(
+    conn: &db::Conn,
+    bump: &db::PayoutBumpRecord,
+    height: u32,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    conn.transaction(|| {
+        if conn.complete_payout_bump(bump, height)? {
+            Ledger::new(conn.clone()).fee_bump(&bump.txid, bump.fee, timestamp)?;
+        }
+        Ok(())
+    })
+}
+
+/// The bumps signed by the last run but not known to be sent, the process stopped or the node
+/// failed to take them
+///
+/// The bump the node knows is sent, the payout takes it. The others are sent again, and the
+/// one the node refuses is left pending for the operator, its payout isn't bumped again.
+pub fn recover_payout_bumps(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    clock: &SharedClock,
+) -> Result<(), BridgeError> {
+    for bump in conn.query_pending_payout_bumps()? {
+        let sent = match depc_client.get_transaction(&bump.txid) {
+            Ok(_) => true,
+            Err(_) => match depc_client.send_raw_transaction(&bump.raw_tx) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!(
+                        "bump {} of the payout of withdrawal {} cannot be sent again, the \
+                         operator should check it, reason: {e}",
+                        bump.txid, bump.action_id
+                    );
+                    false
+                }
+            },
+        };
+        if sent {
+            warn!(
+                "bump {} of the payout of withdrawal {} of the last run is sent",
+                bump.txid, bump.action_id
+            );
+            let height = conn.query_best_height().unwrap_or_default();
+            complete_payout_bump(conn, &bump, height, clock.now())?;
+        }
+    }
+    Ok(())
+}
+
+/// Bump the stuck payouts of the custody address every `interval`, see `bump_stuck_payouts`
+pub async fn fee_bump_processing(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    depc_client: DePCClient,
+    depc_owner_address: DePCAddress,
+    config: FeeBumpConfig,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        let (conn, depc_client, depc_owner_address, now) = (
+            conn.clone(),
+            depc_client.clone(),
+            depc_owner_address.clone(),
+            clock.now(),
+        );
+        // the DePC client is blocking
+        let res = tokio::task::spawn_blocking(move || {
+            bump_stuck_payouts(&conn, &depc_client, &depc_owner_address, &config, now)
+        })
+        .await
+        .map_err(|_| BridgeError::ChannelClosed("fee_bump"))?;
+        match res {
+            Ok(bumps) if bumps.is_empty() => debug!("no payout is bumped"),
+            Ok(bumps) => info!("{} stuck payouts are bumped", bumps.len()),
+            Err(e) => warn!(
+                "cannot bump the stuck payouts, reason: {}",
+                e.full_message()
+            ),
+        }
+        sleep(config.interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::depc::COIN;
+    use crate::ledger::LedgerEvent;
+    use crate::testing::{make_transaction, FakeDepcNode, MockClock};
+
+    const TEST_CUSTODY_ADDRESS: &str = "2N3oefVeg6stiTb5Kh3ozCSkaqmx91FDbsm";
+    const TEST_RECIPIENT: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_NOW: u64 = 1731000000;
+
+    fn test_config() -> FeeBumpConfig {
+        FeeBumpConfig {
+            after_blocks: 3,
+            fee_step: DepcAmount::from_value64(50_000),
+            max_fee: DepcAmount::from_value64(200_000),
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Index the blocks to `height`, the payouts sent before them are stuck
+    fn index_blocks(conn: &db::Conn, height: u32) {
+        let next = conn.query_best_height().map_or(0, |best| best + 1);
+        for height in next..=height {
+            conn.add_block(&format!("block_{height}"), height, "miner", TEST_NOW)
+                .unwrap();
+        }
+    }
+
+    /// Add a withdrawal paid out by `txid` at the best height, the payout is in the mempool
+    fn add_payout(conn: &db::Conn, node: &FakeDepcNode, txid: &str, replaceable: bool) -> u64 {
+        let id = conn
+            .add_pending_action(
+                "withdraw",
+                &format!("source_{txid}"),
+                TEST_RECIPIENT,
+                DepcAmount::from_value64(2 * COIN),
+                TEST_NOW,
+            )
+            .unwrap();
+        conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap();
+        conn.complete_pending_action(id, Some(txid), TEST_NOW)
+            .unwrap();
+        Ledger::new(conn.clone())
+            .payout(
+                txid,
+                DepcAmount::from_value64(2 * COIN),
+                DepcAmount::from_value64(DEFAULT_TX_FEE),
+                TEST_NOW,
+            )
+            .unwrap();
+        let mut tx = make_transaction(
+            txid,
+            &[(&format!("{:064x}", 0xc0), 0)],
+            &[
+                (TEST_RECIPIENT, 2 * COIN, "a914"),
+                (TEST_CUSTODY_ADDRESS, 3 * COIN, "a914"),
+            ],
+        );
+        if replaceable {
+            tx["vin"][0]["sequence"] = json!(0xfffffffdu32);
+        }
+        node.add_mempool_transaction(tx);
+        id
+    }
+
+    #[test]
+    fn test_fee_bump_config_validate() {
+        assert!(test_config().validate().is_ok());
+        let mut config = test_config();
+        config.after_blocks = 0;
+        assert!(config.validate().is_err());
+        let mut config = test_config();
+        config.max_fee = DepcAmount::from_value64(DEFAULT_TX_FEE);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_bump_stuck_payouts() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let custody_address = TEST_CUSTODY_ADDRESS.to_owned();
+        let config = test_config();
+        index_blocks(&conn, 1);
+        let rbf_id = add_payout(&conn, &node, &format!("{:064x}", 0xa1), true);
+        let cpfp_id = add_payout(&conn, &node, &format!("{:064x}", 0xa2), false);
+
+        // not stuck yet
+        index_blocks(&conn, 3);
+        assert!(
+            bump_stuck_payouts(&conn, &client, &custody_address, &config, TEST_NOW)
+                .unwrap()
+                .is_empty()
+        );
+
+        index_blocks(&conn, 4);
+        let bumps =
+            bump_stuck_payouts(&conn, &client, &custody_address, &config, TEST_NOW).unwrap();
+        assert_eq!(
+            bumps
+                .iter()
+                .map(|bump| (bump.action_id, bump.kind.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(rbf_id, "rbf"), (cpfp_id, "cpfp")]
+        );
+        let sent = node.broadcasted_transactions();
+        assert_eq!(sent.len(), 2);
+        // the replacement pays the same recipient and less change, the child spends the change
+        let replacement = String::from_utf8(hex::decode(&sent[0]).unwrap()).unwrap();
+        assert!(replacement.contains("\"replaceable\":true"));
+        assert!(replacement.contains(&format!("\"{TEST_RECIPIENT}\":\"2.00000000\"")));
+        assert!(replacement.contains(&format!("\"{TEST_CUSTODY_ADDRESS}\":\"2.99950000\"")));
+        let child = String::from_utf8(hex::decode(&sent[1]).unwrap()).unwrap();
+        assert!(child.contains(&format!("{:064x}", 0xa2)));
+        assert!(!child.contains("replaceable"));
+
+        // the withdrawal takes the replacement, the parent is kept for the child
+        let rbf_payout = conn.query_pending_action(rbf_id).unwrap().unwrap();
+        assert_eq!(rbf_payout.txid.as_ref(), Some(&bumps[0].txid));
+        let cpfp_payout = conn.query_pending_action(cpfp_id).unwrap().unwrap();
+        assert_eq!(cpfp_payout.txid, Some(bumps[1].bumped_txid.clone()));
+        assert_eq!(
+            conn.query_payout_bumps(rbf_id).unwrap(),
+            vec![bumps[0].clone()]
+        );
+
+        // the fees are posted once for each bump, the payouts are never posted again
+        let events = conn.query_events(0, 10).unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.event == LedgerEvent::FeeBump.as_str())
+                .count(),
+            2
+        );
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.event == LedgerEvent::Payout.as_str())
+                .count(),
+            2
+        );
+
+        // bumped again only after the blocks from the last bump
+        assert!(
+            bump_stuck_payouts(&conn, &client, &custody_address, &config, TEST_NOW)
+                .unwrap()
+                .is_empty()
+        );
+        node.add_mempool_transaction(make_transaction(
+            &bumps[0].txid,
+            &[(&format!("{:064x}", 0xc0), 0)],
+            &[
+                (TEST_RECIPIENT, 2 * COIN, "a914"),
+                (TEST_CUSTODY_ADDRESS, 3 * COIN - 50_000, "a914"),
+            ],
+        ));
+        node.add_mempool_transaction(make_transaction(
+            &bumps[1].txid,
+            &[(&bumps[1].bumped_txid, 1)],
+            &[(TEST_CUSTODY_ADDRESS, 3 * COIN - 50_000, "a914")],
+        ));
+        index_blocks(&conn, 7);
+        let next_bumps =
+            bump_stuck_payouts(&conn, &client, &custody_address, &config, TEST_NOW).unwrap();
+        assert_eq!(next_bumps.len(), 2);
+        // the replacement doesn't signal any more, it's bumped by a child
+        assert_eq!(next_bumps[0].kind, "cpfp");
+        assert_eq!(next_bumps[0].bumped_txid, bumps[0].txid);
+        // the chain of the children is extended
+        assert_eq!(next_bumps[1].kind, "cpfp");
+        assert_eq!(next_bumps[1].bumped_txid, bumps[1].txid);
+
+        // the max fee is reached
+        index_blocks(&conn, 10);
+        assert!(
+            bump_stuck_payouts(&conn, &client, &custody_address, &config, TEST_NOW)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(Ledger::new(conn.clone())
+            .trial_balance()
+            .unwrap()
+            .is_balanced());
+    }
+
+    #[test]
+    fn test_recover_payout_bumps() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock: SharedClock = Arc::new(MockClock::new(TEST_NOW));
+        let custody_address = TEST_CUSTODY_ADDRESS.to_owned();
+        index_blocks(&conn, 1);
+        let original = format!("{:064x}", 0xa1);
+        let id = add_payout(&conn, &node, &original, true);
+        // the process stopped before the bump is sent
+        let replacement = format!("{:064x}", 0xb1);
+        conn.add_payout_bump(
+            id,
+            "rbf",
+            &original,
+            &replacement,
+            "signed_bump",
+            DepcAmount::from_value64(50_000),
+            TEST_NOW,
+        )
+        .unwrap();
+        index_blocks(&conn, 4);
+        // the payout in doubt isn't bumped again
+        assert!(
+            bump_stuck_payouts(&conn, &client, &custody_address, &test_config(), TEST_NOW)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(conn.query_payout_bumps(id).unwrap().is_empty());
+
+        recover_payout_bumps(&conn, &client, &clock).unwrap();
+        assert_eq!(
+            node.broadcasted_transactions(),
+            vec!["signed_bump".to_owned()]
+        );
+        assert!(conn.query_pending_payout_bumps().unwrap().is_empty());
+        let payout = conn.query_pending_action(id).unwrap().unwrap();
+        assert_eq!(payout.txid, Some(replacement));
+        let events = conn.query_events(0, 10).unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.event == LedgerEvent::FeeBump.as_str())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_replaced_payout_confirmed() {
+        let node = FakeDepcNode::start();
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let custody_address = TEST_CUSTODY_ADDRESS.to_owned();
+        index_blocks(&conn, 1);
+        let original = format!("{:064x}", 0xa1);
+        let id = add_payout(&conn, &node, &original, true);
+        index_blocks(&conn, 4);
+        let bumps =
+            bump_stuck_payouts(&conn, &client, &custody_address, &test_config(), TEST_NOW).unwrap();
+        assert_eq!(bumps.len(), 1);
+
+        // the original is mined before the replacement is relayed
+        conn.add_block("block_5", 5, "miner", TEST_NOW).unwrap();
+        conn.add_transaction("block_5", &original).unwrap();
+        assert_eq!(
+            conn.confirm_pending_actions_in_block("block_5", TEST_NOW)
+                .unwrap(),
+            1
+        );
+        let payout = conn.query_pending_action(id).unwrap().unwrap();
+        assert_eq!(payout.txid, Some(original));
+    }
+}
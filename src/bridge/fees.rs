@@ -0,0 +1,433 @@
+use std::fmt;
+
+use log::{error, warn};
+
+use crate::amount::{AmountConverter, DepcAmount};
+use crate::db;
+use crate::ledger::{Ledger, LedgerEvent, ACCOUNT_BRIDGE_FEES};
+use crate::solana::{ErrorKind, TokenClient};
+use crate::BridgeError;
+
+/// The rate of a fee is in basis points of the amount, at most all of it
+pub const MAX_FEE_RATE_BPS: u32 = 10_000;
+
+/// The bridge fee charged on a transfer, `flat` plus `rate_bps` basis points of the amount.
+/// The rate part is rounded up, so a fee is never charged less than the schedule tells
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub flat: DepcAmount,
+    pub rate_bps: u32,
+}
+
+impl FeeSchedule {
+    pub fn is_free(&self) -> bool {
+        self.flat.is_zero() && self.rate_bps == 0
+    }
+
+    /// The fee of `amount`, it's rounded up to a multiple of `unit` so the rest is still
+    /// converted exactly to the token (see `AmountConverter::depc_unit`)
+    pub fn fee_of(&self, amount: DepcAmount, unit: DepcAmount) -> Option<DepcAmount> {
+        let bps = MAX_FEE_RATE_BPS as u128;
+        let rate = (amount.value64() as u128 * self.rate_bps as u128).div_ceil(bps);
+        let fee = self
+            .flat
+            .checked_add(DepcAmount::from_value64(rate.try_into().ok()?))?;
+        let unit = unit.value64().max(1);
+        let fee = fee.value64().checked_next_multiple_of(unit)?;
+        Some(DepcAmount::from_value64(fee))
+    }
+
+    /// Split `amount` into the part forwarded and the fee, `None` when the fee takes all of it
+    pub fn split(&self, amount: DepcAmount, unit: DepcAmount) -> Option<(DepcAmount, DepcAmount)> {
+        let fee = self.fee_of(amount, unit)?;
+        match amount.checked_sub(fee) {
+            Some(net) if !net.is_zero() => Some((net, fee)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FeeSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} + {} bps", self.flat, self.rate_bps)
+    }
+}
+
+/// The bridge fees charged on the deposits and the withdrawals of a pair, they're free by
+/// default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeConfig {
+    pub deposit: FeeSchedule,
+    pub withdraw: FeeSchedule,
+}
+
+impl FeeConfig {
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        for (name, schedule) in [("deposit", self.deposit), ("withdraw", self.withdraw)] {
+            if schedule.rate_bps > MAX_FEE_RATE_BPS {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "the {name} fee rate {} bps is over {MAX_FEE_RATE_BPS}",
+                    schedule.rate_bps
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The bridge fees kept on a day (UTC)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DailyFees {
+    /// The days since the epoch
+    pub day: u64,
+    pub deposits: DepcAmount,
+    pub withdrawals: DepcAmount,
+}
+
+/// The bridge fees kept in a range of time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeesSummary {
+    pub from: u64,
+    pub to: u64,
+    pub deposits: DepcAmount,
+    pub withdrawals: DepcAmount,
+    /// The days any fee is kept on, in order
+    pub days: Vec<DailyFees>,
+    /// All the fees not swept to the treasury yet, not only the ones of the range
+    pub unswept: DepcAmount,
+}
+
+/// Summarize the bridge fees posted to the ledger from `from` to `to` (inclusive) by the day and
+/// the direction
+pub fn summarize_fees(conn: &db::Conn, from: u64, to: u64) -> Result<FeesSummary, BridgeError> {
+    let mut summary = FeesSummary {
+        from,
+        to,
+        unswept: Ledger::new(conn.clone()).unswept_fees()?,
+        ..Default::default()
+    };
+    for total in conn.query_ledger_daily_totals(ACCOUNT_BRIDGE_FEES, from, to)? {
+        if summary.days.last().map(|daily| daily.day) != Some(total.day) {
+            summary.days.push(DailyFees {
+                day: total.day,
+                ..Default::default()
+            });
+        }
+        let daily = summary.days.last_mut().unwrap();
+        let (daily, overall) = if total.event == LedgerEvent::DepositFee.as_str() {
+            (&mut daily.deposits, &mut summary.deposits)
+        } else if total.event == LedgerEvent::WithdrawFee.as_str() {
+            (&mut daily.withdrawals, &mut summary.withdrawals)
+        } else {
+            continue;
+        };
+        *daily = daily
+            .checked_add(total.amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+        *overall = overall
+            .checked_add(total.amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+    }
+    Ok(summary)
+}
+
+/// Transfer all the bridge fees not swept yet to `treasury` by one Solana transaction, the
+/// sweep is recorded to table `fee_sweeps` and posted to the ledger. `None` if there's nothing
+/// to sweep.
+///
+/// The fees are converted to the token by `converter`, the rest under its unit is swept later.
+/// The sweep is recorded as pending before it's sent, the one in doubt is left pending and its
+/// fees are never swept again until it's recovered on the next start (see
+/// `find_pending_fee_sweep`). The caller makes sure no other sweep of the bridge is made
+/// meanwhile, or the same fees would be transferred twice.
+pub fn sweep_fees<C: TokenClient>(
+    conn: &db::Conn,
+    contract_client: &C,
+    converter: &AmountConverter,
+    treasury: &C::Address,
+    timestamp: u64,
+) -> Result<Option<db::FeeSweepRecord>, BridgeError> {
+    let mut unswept = Ledger::new(conn.clone()).unswept_fees()?;
+    for sweep in conn.query_pending_fee_sweeps()? {
+        unswept = unswept.checked_sub(sweep.amount).unwrap_or_default();
+    }
+    let (amount, _) = converter.round_to_unit(unswept);
+    if amount.is_zero() {
+        return Ok(None);
+    }
+    let token_amount = converter
+        .to_token(amount)
+        .ok_or(BridgeError::AmountOverflow)?;
+    let recipient = treasury.to_string();
+    let id = conn.add_pending_fee_sweep(&recipient, amount, timestamp)?;
+    let receipt = match contract_client.send_token(treasury, token_amount.raw().into()) {
+        Ok(receipt) => receipt,
+        Err(e) if C::error_kind(&e) == ErrorKind::InDoubt => {
+            error!(
+                "sweep {id} of the bridge fees {amount} to {recipient} might be made, it's left \
+                 pending until it's recovered on the next start, reason: {e}"
+            );
+            return Err(BridgeError::token_client::<C>(e));
+        }
+        Err(e) => {
+            conn.fail_fee_sweep(id)?;
+            return Err(BridgeError::token_client::<C>(e));
+        }
+    };
+    let signature = receipt.txid.to_string();
+    warn!("the bridge fees {amount} are swept to {recipient} by {signature}");
+    settle_fee_sweep(conn, id, amount, Some(&signature), timestamp)?;
+    if let Err(e) = contract_client.acknowledge_transfer(&receipt.txid) {
+        // it's recovered again on the next start, the sweep is found recorded then
+        warn!("cannot acknowledge transaction {signature}, reason: {e}");
+    }
+    Ok(Some(db::FeeSweepRecord {
+        id,
+        treasury: recipient,
+        amount,
+        signature,
+        created_at: timestamp,
+    }))
+}
+
+/// The pending sweep a transfer of the last run pays, it's matched by the treasury and the
+/// amount of the only recipient of the transfer
+pub fn find_pending_fee_sweep(
+    conn: &db::Conn,
+    converter: &AmountConverter,
+    recipients: &[(String, u64)],
+) -> Result<Option<db::FeeSweepRecord>, BridgeError> {
+    let [(recipient, amount)] = recipients else {
+        return Ok(None);
+    };
+    Ok(conn.query_pending_fee_sweeps()?.into_iter().find(|sweep| {
+        &sweep.treasury == recipient
+            && converter
+                .to_token(sweep.amount)
+                .is_some_and(|token_amount| token_amount.raw() == *amount)
+    }))
+}
+
+/// The pending sweep `id` is sent by Solana transaction `signature` and posted to the ledger,
+/// or it's failed when it's `None` and its fees are swept again
+pub fn settle_fee_sweep(
+    conn: &db::Conn,
+    id: u64,
+    amount: DepcAmount,
+    signature: Option<&str>,
+    timestamp: u64,
+) -> Result<(), BridgeError> {
+    let Some(signature) = signature else {
+        conn.fail_fee_sweep(id)?;
+        return Ok(());
+    };
+    conn.transaction(|| {
+        if conn.complete_fee_sweep(id, signature)? {
+            Ledger::new(conn.clone()).fee_sweep(signature, amount, timestamp)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::testing::{MockError, MockTokenClient};
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+    const DAY: u64 = 24 * 3600;
+    const COIN: u64 = 100_000_000;
+
+    fn depc(value64: u64) -> DepcAmount {
+        DepcAmount::from_value64(value64)
+    }
+
+    #[test]
+    fn test_fee_schedule() {
+        let unit = depc(1);
+        assert!(FeeSchedule::default().is_free());
+        assert_eq!(
+            FeeSchedule::default().split(depc(COIN), unit),
+            Some((depc(COIN), depc(0)))
+        );
+
+        let schedule = FeeSchedule {
+            flat: depc(1000),
+            rate_bps: 30,
+        };
+        assert_eq!(schedule.to_string(), "1000 + 30 bps");
+        assert_eq!(schedule.fee_of(depc(COIN), unit), Some(depc(301_000)));
+        assert_eq!(
+            schedule.split(depc(COIN), unit),
+            Some((depc(COIN - 301_000), depc(301_000)))
+        );
+        // the rate part is rounded up
+        assert_eq!(schedule.fee_of(depc(10_001), unit), Some(depc(1031)));
+        // rounded up to the unit of a mint with less decimals
+        assert_eq!(schedule.fee_of(depc(10_001), depc(100)), Some(depc(1100)));
+        // nothing is left
+        assert_eq!(schedule.split(depc(1000), unit), None);
+        assert_eq!(schedule.split(depc(500), unit), None);
+        let all = FeeSchedule {
+            flat: depc(0),
+            rate_bps: MAX_FEE_RATE_BPS,
+        };
+        assert_eq!(all.split(depc(COIN), unit), None);
+        assert_eq!(all.fee_of(depc(u64::MAX), unit), Some(depc(u64::MAX)));
+
+        let mut config = FeeConfig {
+            deposit: schedule,
+            withdraw: all,
+        };
+        assert!(config.validate().is_ok());
+        config.withdraw.rate_bps = MAX_FEE_RATE_BPS + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_summarize_fees() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let ledger = Ledger::new(conn.clone());
+        ledger
+            .deposit_fee("d1", depc(1000), TEST_NOW - DAY)
+            .unwrap();
+        ledger.deposit_fee("d2", depc(2000), TEST_NOW).unwrap();
+        ledger.deposit_fee("d3", depc(3000), TEST_NOW + 60).unwrap();
+        ledger.withdraw_fee("w1", depc(500), TEST_NOW).unwrap();
+        // the network fees are not the bridge fees
+        ledger
+            .payout("w1", depc(COIN), depc(100_000), TEST_NOW)
+            .unwrap();
+        ledger.fee_sweep("sweep", depc(1000), TEST_NOW).unwrap();
+
+        let summary = summarize_fees(&conn, TEST_NOW - DAY, TEST_NOW + DAY).unwrap();
+        assert_eq!(summary.deposits, depc(6000));
+        assert_eq!(summary.withdrawals, depc(500));
+        assert_eq!(summary.unswept, depc(5500));
+        let today = TEST_NOW / DAY;
+        assert_eq!(
+            summary.days,
+            vec![
+                DailyFees {
+                    day: today - 1,
+                    deposits: depc(1000),
+                    withdrawals: depc(0),
+                },
+                DailyFees {
+                    day: today,
+                    deposits: depc(5000),
+                    withdrawals: depc(500),
+                },
+            ]
+        );
+
+        // only the range is summarized
+        let summary = summarize_fees(&conn, TEST_NOW, TEST_NOW).unwrap();
+        assert_eq!(summary.deposits, depc(2000));
+        assert_eq!(summary.days.len(), 1);
+        assert_eq!(summary.unswept, depc(5500));
+        let other = summarize_fees(&conn.scoped("other"), 0, TEST_NOW + DAY).unwrap();
+        assert!(other.days.is_empty() && other.unswept.is_zero());
+    }
+
+    #[test]
+    fn test_sweep_fees() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let token_client = MockTokenClient::new();
+        let converter = AmountConverter::default();
+        let treasury = Pubkey::new_unique();
+        assert_eq!(
+            sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).unwrap(),
+            None
+        );
+
+        let ledger = Ledger::new(conn.clone());
+        ledger.deposit_fee("d1", depc(1000), TEST_NOW).unwrap();
+        ledger.withdraw_fee("w1", depc(500), TEST_NOW).unwrap();
+        // nothing is recorded when the transfer fails
+        token_client.push_send_result(Err(MockError::transient("unavailable")));
+        assert!(matches!(
+            sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW),
+            Err(BridgeError::TokenClient(..))
+        ));
+        assert!(conn.query_fee_sweeps(10).unwrap().is_empty());
+        assert!(conn.query_pending_fee_sweeps().unwrap().is_empty());
+        assert_eq!(ledger.unswept_fees().unwrap(), depc(1500));
+
+        // the sweep in doubt is left pending, its fees are not swept again
+        token_client.push_send_result(Err(MockError(
+            "cannot settle".to_owned(),
+            ErrorKind::InDoubt,
+        )));
+        assert!(sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).is_err());
+        let pending = conn.query_pending_fee_sweeps().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].amount, depc(1500));
+        assert_eq!(
+            sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).unwrap(),
+            None
+        );
+        // the transfer of the last run pays it but it's expired, the fees are swept again
+        let recipients = vec![(treasury.to_string(), 1500)];
+        assert_eq!(
+            find_pending_fee_sweep(&conn, &converter, &[(treasury.to_string(), 1000)]).unwrap(),
+            None
+        );
+        let found = find_pending_fee_sweep(&conn, &converter, &recipients)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, pending[0]);
+        settle_fee_sweep(&conn, found.id, found.amount, None, TEST_NOW).unwrap();
+        assert!(conn.query_pending_fee_sweeps().unwrap().is_empty());
+        assert!(conn.query_fee_sweeps(10).unwrap().is_empty());
+        assert_eq!(ledger.unswept_fees().unwrap(), depc(1500));
+
+        let sweep = sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sweep.amount, depc(1500));
+        assert_eq!(sweep.treasury, treasury.to_string());
+        let sent_tokens = token_client.sent_tokens();
+        assert_eq!(sent_tokens.len(), 1);
+        assert_eq!((sent_tokens[0].0, sent_tokens[0].1), (treasury, 1500));
+        assert_eq!(sent_tokens[0].2.to_string(), sweep.signature);
+        assert_eq!(conn.query_fee_sweeps(10).unwrap(), vec![sweep.clone()]);
+        assert!(conn
+            .query_fee_sweep_exists_by_signature(&sweep.signature)
+            .unwrap());
+        assert_eq!(
+            token_client.acknowledged_transfers()[0].to_string(),
+            sweep.signature
+        );
+        assert!(ledger.unswept_fees().unwrap().is_zero());
+        let events = conn.query_events(0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].reference, sweep.signature);
+
+        // the fees are swept once
+        assert_eq!(
+            sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).unwrap(),
+            None
+        );
+        assert_eq!(token_client.sent_tokens().len(), 1);
+
+        // the fees are converted to the token of 6 decimals, the rest is swept later
+        let converter = AmountConverter::new(8, 6).unwrap();
+        ledger.deposit_fee("d2", depc(1055), TEST_NOW).unwrap();
+        let sweep = sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sweep.amount, depc(1000));
+        assert_eq!(token_client.sent_tokens()[1].1, 10);
+        assert_eq!(ledger.unswept_fees().unwrap(), depc(55));
+        assert_eq!(
+            sweep_fees(&conn, &token_client, &converter, &treasury, TEST_NOW).unwrap(),
+            None
+        );
+    }
+}
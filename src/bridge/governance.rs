@@ -0,0 +1,129 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::BridgeError;
+
+/// The delay before a parameter change takes effect by default
+pub const DEFAULT_TIMELOCK: Duration = Duration::from_secs(48 * 3600);
+pub const DEFAULT_DEPOSIT_THRESHOLD: DepcAmount = DepcAmount::from_value64(1000);
+pub const DEFAULT_WITHDRAW_THRESHOLD: DepcAmount = DepcAmount::from_value64(1000);
+
+/// The runtime parameters of a bridge those affect the funds, they're changed through the admin
+/// endpoints and a change takes effect only after the timelock, so a compromised admin token
+/// cannot change them instantly and the users can see the changes coming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeParameter {
+    /// The deposits no more than it are ignored
+    DepositThreshold,
+    /// The withdrawals no more than it are ignored
+    WithdrawThreshold,
+}
+
+impl BridgeParameter {
+    pub const ALL: [BridgeParameter; 2] = [
+        BridgeParameter::DepositThreshold,
+        BridgeParameter::WithdrawThreshold,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BridgeParameter::DepositThreshold => "deposit_threshold",
+            BridgeParameter::WithdrawThreshold => "withdraw_threshold",
+        }
+    }
+
+    pub fn default_value(self) -> DepcAmount {
+        match self {
+            BridgeParameter::DepositThreshold => DEFAULT_DEPOSIT_THRESHOLD,
+            BridgeParameter::WithdrawThreshold => DEFAULT_WITHDRAW_THRESHOLD,
+        }
+    }
+
+    fn parse_value(self, value: &str) -> Result<DepcAmount, BridgeError> {
+        DepcAmount::from_str(value).map_err(|_| {
+            BridgeError::InvalidConfig(format!(
+                "cannot parse the value of `{}` from string '{value}'",
+                self.name()
+            ))
+        })
+    }
+}
+
+impl FromStr for BridgeParameter {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BridgeParameter::ALL
+            .into_iter()
+            .find(|parameter| parameter.name() == s)
+            .ok_or_else(|| BridgeError::InvalidConfig(format!("unknown parameter `{s}`")))
+    }
+}
+
+/// Queue a change of `parameter` to `value`, it takes effect at `now + timelock`, returns the id
+/// of the change
+pub fn queue_parameter_change(
+    conn: &db::Conn,
+    parameter: BridgeParameter,
+    value: &str,
+    now: u64,
+    timelock: Duration,
+) -> Result<u64, BridgeError> {
+    let value = parameter.parse_value(value)?;
+    let activates_at = now.saturating_add(timelock.as_secs());
+    Ok(conn.add_parameter_change(parameter.name(), &value.to_string(), now, activates_at)?)
+}
+
+/// The value of `parameter` in effect at `now`, the last change activated before it or the
+/// default value
+pub fn parameter_value(
+    conn: &db::Conn,
+    parameter: BridgeParameter,
+    now: u64,
+) -> Result<DepcAmount, BridgeError> {
+    match conn.query_effective_parameter_value(parameter.name(), now)? {
+        Some(value) => parameter.parse_value(&value),
+        None => Ok(parameter.default_value()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_change_takes_effect_after_timelock() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let parameter = BridgeParameter::from_str("deposit_threshold").unwrap();
+        assert!(BridgeParameter::from_str("fee").is_err());
+        assert!(queue_parameter_change(&conn, parameter, "abc", 1000, DEFAULT_TIMELOCK).is_err());
+
+        let timelock = Duration::from_secs(600);
+        let id = queue_parameter_change(&conn, parameter, "5000", 1000, timelock).unwrap();
+        assert_eq!(
+            parameter_value(&conn, parameter, 1599).unwrap(),
+            DEFAULT_DEPOSIT_THRESHOLD
+        );
+        assert_eq!(
+            parameter_value(&conn, parameter, 1600).unwrap(),
+            DepcAmount::from_value64(5000)
+        );
+
+        // the cancelled change never takes effect
+        let cancelled = queue_parameter_change(&conn, parameter, "9000", 1100, timelock).unwrap();
+        assert!(conn.cancel_parameter_change(cancelled, 1200).unwrap());
+        assert_eq!(
+            parameter_value(&conn, parameter, 5000).unwrap(),
+            DepcAmount::from_value64(5000)
+        );
+        // and the effective one cannot be cancelled
+        assert!(!conn.cancel_parameter_change(id, 1600).unwrap());
+        assert_eq!(
+            parameter_value(&conn, BridgeParameter::WithdrawThreshold, 5000).unwrap(),
+            DEFAULT_WITHDRAW_THRESHOLD
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::BridgeError;
+
+/// The window of the cap of a recipient, the transfers made to it within it are summed
+pub const DAILY_CAP_WINDOW: u64 = 24 * 3600;
+
+/// The limits of the amount minted or paid out for a transfer, the fee taken. A transfer under
+/// `min` is never made, and the ones over `max` or over the cap of its recipient are held until
+/// an admin releases them (see `db::Conn::release_held_pending_action`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferLimits {
+    pub min: DepcAmount,
+    pub max: Option<DepcAmount>,
+    /// The most transferred to a recipient in the last `DAILY_CAP_WINDOW`, the held transfers
+    /// are not counted until they're released
+    pub daily_cap: Option<DepcAmount>,
+}
+
+impl TransferLimits {
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        if let Some(max) = self.max {
+            if max < self.min {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "the maximum transfer {max} is under the minimum {}",
+                    self.min
+                )));
+            }
+        }
+        if self.daily_cap.is_some_and(|cap| cap.is_zero()) {
+            return Err(BridgeError::InvalidConfig(
+                "the daily cap of a recipient is 0".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Check the `action` of `amount` to `recipient` against the limits before it's queued, returns
+/// why it's held or `None` when it's made as usual
+///
+/// The cap is counted back from `transfer_time`, the time of the block the transfer is made in,
+/// so the blocks synced late (e.g. catching up) are capped as they were made on the chain
+pub fn check_transfer_limits(
+    conn: &db::Conn,
+    limits: &TransferLimits,
+    action: &str,
+    recipient: &str,
+    amount: DepcAmount,
+    transfer_time: u64,
+) -> Result<Option<String>, BridgeError> {
+    if amount < limits.min {
+        return Err(BridgeError::BelowTransferMinimum(amount, limits.min));
+    }
+    if let Some(max) = limits.max {
+        if amount > max {
+            return Ok(Some(format!("{amount} is over the maximum transfer {max}")));
+        }
+    }
+    if let Some(cap) = limits.daily_cap {
+        let since = transfer_time.saturating_sub(DAILY_CAP_WINDOW);
+        let transferred = conn.query_recipient_volume_since(action, recipient, since)?;
+        let total = transferred
+            .checked_add(amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+        if total > cap {
+            return Ok(Some(format!(
+                "{transferred} is transferred to {recipient} in 24 hours, {amount} more is over \
+                 the daily cap {cap}"
+            )));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_NOW: u64 = 1731000000;
+    const TEST_RECIPIENT: &str = "recipient";
+
+    fn depc(value64: u64) -> DepcAmount {
+        DepcAmount::from_value64(value64)
+    }
+
+    #[test]
+    fn test_check_transfer_limits() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let limits = TransferLimits {
+            min: depc(100),
+            max: Some(depc(1000)),
+            daily_cap: Some(depc(1500)),
+        };
+        assert!(limits.validate().is_ok());
+        let check = |amount: u64| {
+            check_transfer_limits(
+                &conn,
+                &limits,
+                "deposit",
+                TEST_RECIPIENT,
+                depc(amount),
+                TEST_NOW,
+            )
+        };
+        assert!(matches!(
+            check(99),
+            Err(BridgeError::BelowTransferMinimum(..))
+        ));
+        assert_eq!(check(100).unwrap(), None);
+        assert!(check(1001).unwrap().is_some());
+
+        // 1000 transferred a day ago is out of the window, 800 within it
+        for (amount, timestamp) in [(1000, TEST_NOW - DAILY_CAP_WINDOW - 1), (800, TEST_NOW)] {
+            conn.add_pending_action("deposit", "txid", TEST_RECIPIENT, depc(amount), timestamp)
+                .unwrap();
+        }
+        assert_eq!(check(700).unwrap(), None);
+        assert!(check(701).unwrap().is_some());
+        // the transfer is timed by its block, not by the time it's synced
+        let late = conn
+            .add_pending_action("deposit", "late", TEST_RECIPIENT, depc(500), TEST_NOW)
+            .unwrap();
+        conn.set_pending_action_transfer_time(late, TEST_NOW - DAILY_CAP_WINDOW - 1)
+            .unwrap();
+        assert_eq!(check(700).unwrap(), None);
+        assert!(check_transfer_limits(
+            &conn,
+            &limits,
+            "deposit",
+            TEST_RECIPIENT,
+            depc(100),
+            TEST_NOW - DAILY_CAP_WINDOW
+        )
+        .unwrap()
+        .is_some());
+        // the other recipients and actions have their own caps
+        assert_eq!(
+            check_transfer_limits(
+                &conn,
+                &limits,
+                "withdraw",
+                TEST_RECIPIENT,
+                depc(1000),
+                TEST_NOW
+            )
+            .unwrap(),
+            None
+        );
+
+        // the held transfer is counted after it's released
+        let held = conn
+            .add_pending_action("deposit", "held", TEST_RECIPIENT, depc(500), TEST_NOW)
+            .unwrap();
+        conn.hold_pending_action(held, "over the cap", TEST_NOW)
+            .unwrap();
+        assert_eq!(check(700).unwrap(), None);
+        assert!(conn.release_held_pending_action(held, TEST_NOW).unwrap());
+        assert!(check(700).unwrap().is_some());
+
+        assert!(TransferLimits::default().validate().is_ok());
+        let invalid = TransferLimits {
+            min: depc(100),
+            max: Some(depc(99)),
+            daily_cap: None,
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = TransferLimits {
+            daily_cap: Some(depc(0)),
+            ..TransferLimits::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
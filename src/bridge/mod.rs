@@ -1,3 +1,47 @@
+mod block_digest;
 mod bridge;
+mod capacity;
+mod catch_up;
+mod checkpoint;
+mod custody_check;
+mod custody_era;
+mod deposit_address;
+mod deposit_root;
+mod dust;
+mod fee_bump;
+mod fees;
+mod governance;
+mod limits;
+mod multisig;
+mod preflight;
+mod refund_claim;
+mod sanity;
+mod settings;
+mod signature_scan;
+mod status;
+mod summary;
+mod withdraw_request;
 
-pub use bridge::*;
\ No newline at end of file
+pub use block_digest::*;
+pub use bridge::*;
+pub use capacity::*;
+pub use catch_up::*;
+pub use checkpoint::*;
+pub use custody_check::*;
+pub use custody_era::*;
+pub use deposit_address::*;
+pub use deposit_root::*;
+pub use dust::*;
+pub use fee_bump::*;
+pub use fees::*;
+pub use governance::*;
+pub use limits::*;
+pub use multisig::*;
+pub use preflight::*;
+pub use refund_claim::*;
+pub use sanity::*;
+pub use settings::*;
+pub use signature_scan::*;
+pub use status::*;
+pub use summary::*;
+pub use withdraw_request::*;
\ No newline at end of file
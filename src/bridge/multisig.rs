@@ -0,0 +1,274 @@
+use log::{info, warn};
+
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::depc::{
+    self, format_amount, Address as DePCAddress, Client as DePCClient, MultisigScript, PrevTx,
+    TxID, DEFAULT_TX_FEE,
+};
+use crate::ledger::Ledger;
+use crate::BridgeError;
+
+/// What becomes of a withdrawal when the custody is multisig, see `start_multisig_payout`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultisigPayoutStart {
+    /// The payout is collecting the signatures of the co-signers
+    Collecting(u64),
+    /// The payout was broadcast before the bridge stopped, the withdrawal is completed now
+    Sent(TxID),
+    /// Another payout is collecting the signatures, the withdrawal waits for it. The payouts
+    /// are made one by one, the coins of the collecting one are not spent by the next
+    Waiting,
+}
+
+/// Make the payout of the claimed withdrawal spending the coins of the multisig custody, it's
+/// partially signed by the wallet of the node and stored to collect the signatures of the
+/// co-signers, see `collect_multisig_signatures`. The withdrawal is left claimed until the
+/// payout is broadcast
+pub fn start_multisig_payout(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    custody_address: &DePCAddress,
+    multisig: &MultisigScript,
+    withdraw: &db::PendingActionRecord,
+    now: u64,
+) -> Result<MultisigPayoutStart, BridgeError> {
+    // the withdrawal is claimed again after a restart
+    if let Some(payout) = conn.query_multisig_payout_by_action(withdraw.id)? {
+        match payout.txid {
+            Some(txid) => {
+                complete_multisig_withdrawal(conn, withdraw, &txid, now)?;
+                return Ok(MultisigPayoutStart::Sent(txid));
+            }
+            None => return Ok(MultisigPayoutStart::Collecting(payout.id)),
+        }
+    }
+    if !conn.query_collecting_multisig_payouts()?.is_empty() {
+        return Ok(MultisigPayoutStart::Waiting);
+    }
+
+    let (hex, spent) = depc_client.make_transfer_spending(
+        custody_address,
+        &withdraw.recipient,
+        withdraw.amount.value64(),
+    )?;
+    let prevtxs: Vec<PrevTx> = spent
+        .iter()
+        .map(|unspent| PrevTx {
+            txid: unspent.txid.clone(),
+            vout: unspent.vout,
+            script_pubkey: multisig.script_pubkey_hex(),
+            redeem_script: multisig.redeem_script_hex(),
+            amount: format_amount(unspent.value64()),
+        })
+        .collect();
+    let signed = depc_client.sign_raw_transaction_with_prevtxs(&hex, &prevtxs)?;
+    let id = conn.add_multisig_payout(
+        withdraw.id,
+        &signed.hex,
+        &serde_json::to_string(&prevtxs).unwrap(),
+        now,
+    )?;
+    Ok(MultisigPayoutStart::Collecting(id))
+}
+
+/// Merge the signed copies submitted by the co-signers into the collecting payouts, the ones
+/// with the signatures required are broadcast and their withdrawals completed
+///
+/// A copy the node cannot combine, e.g. of another transaction, is rejected with the reason.
+/// Returns the payouts broadcast (id, txid)
+pub fn collect_multisig_signatures(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    now: u64,
+) -> Result<Vec<(u64, TxID)>, BridgeError> {
+    let mut sent = vec![];
+    for payout in conn.query_collecting_multisig_payouts()? {
+        let prevtxs: Vec<PrevTx> = serde_json::from_str(&payout.prevtxs).map_err(|e| {
+            depc::Error::InvalidMultisig(format!("prevtxs of payout {}, {e}", payout.id))
+        })?;
+        let mut raw_tx = payout.raw_tx.clone();
+        let mut results = vec![];
+        let mut failure = None;
+        for signature in conn.query_submitted_multisig_signatures(payout.id)? {
+            match depc_client.combine_raw_transaction(&[raw_tx.clone(), signature.raw_tx]) {
+                Ok(combined) => {
+                    raw_tx = combined;
+                    results.push((signature.id, None));
+                }
+                Err(depc::Error::CannotCombineTransactions(reason)) => {
+                    warn!(
+                        "the signed copy {} of payout {} is rejected, reason: {reason}",
+                        signature.id, payout.id
+                    );
+                    results.push((signature.id, Some(reason)));
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        // the copies merged before the failure are kept
+        if !results.is_empty() {
+            conn.merge_multisig_signatures(payout.id, &raw_tx, &results)?;
+        }
+        if let Some(e) = failure {
+            return Err(e.into());
+        }
+
+        // the wallet tells whether the transaction has the signatures required
+        let signed = depc_client.sign_raw_transaction_with_prevtxs(&raw_tx, &prevtxs)?;
+        if !signed.complete {
+            continue;
+        }
+        let txid = depc_client.send_raw_transaction(&signed.hex)?;
+        conn.complete_multisig_payout(payout.id, &txid, now)?;
+        if let Some(withdraw) = conn.query_pending_action(payout.action_id)? {
+            complete_multisig_withdrawal(conn, &withdraw, &txid, now)?;
+        }
+        sent.push((payout.id, txid));
+    }
+    Ok(sent)
+}
+
+fn complete_multisig_withdrawal(
+    conn: &db::Conn,
+    withdraw: &db::PendingActionRecord,
+    txid: &str,
+    now: u64,
+) -> Result<(), BridgeError> {
+    conn.complete_pending_action(withdraw.id, Some(txid), now)?;
+    Ledger::new(conn.clone()).payout(
+        txid,
+        withdraw.amount,
+        DepcAmount::from_value64(DEFAULT_TX_FEE),
+        now,
+    )?;
+    info!(
+        "withdrawal {} to {} is made from the multisig custody, txid {}",
+        withdraw.amount, withdraw.recipient, txid
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::depc::COIN;
+    use crate::testing::{sign_fake_transaction, FakeDepcNode};
+
+    const TEST_PUBKEYS: [&str; 3] = [
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    ];
+    const TEST_CUSTODY_ADDRESS: &str = "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS";
+    const TEST_RECIPIENT: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_NOW: u64 = 1731000000;
+
+    fn add_withdrawal(conn: &db::Conn, source_txid: &str, amount: u64) -> db::PendingActionRecord {
+        let id = conn
+            .add_pending_action(
+                "withdraw",
+                source_txid,
+                TEST_RECIPIENT,
+                DepcAmount::from_value64(amount),
+                TEST_NOW,
+            )
+            .unwrap();
+        conn.claim_pending_action(id, TEST_NOW).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_multisig_payout() {
+        let node = FakeDepcNode::start();
+        node.set_multisig_required(2);
+        node.add_unspent(
+            TEST_CUSTODY_ADDRESS,
+            &format!("{:064x}", 0xd1),
+            0,
+            10 * COIN,
+        );
+        let client = node.client();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let pubkeys: Vec<String> = TEST_PUBKEYS
+            .iter()
+            .map(|pubkey| pubkey.to_string())
+            .collect();
+        let multisig = MultisigScript::new(2, &pubkeys).unwrap();
+        let custody_address = TEST_CUSTODY_ADDRESS.to_owned();
+
+        let withdraw = add_withdrawal(&conn, "sig1", 2 * COIN);
+        let start = start_multisig_payout(
+            &conn,
+            &client,
+            &custody_address,
+            &multisig,
+            &withdraw,
+            TEST_NOW,
+        )
+        .unwrap();
+        let MultisigPayoutStart::Collecting(payout_id) = start else {
+            panic!("{start:?}");
+        };
+        // claimed again, the payout is kept
+        assert_eq!(
+            start_multisig_payout(
+                &conn,
+                &client,
+                &custody_address,
+                &multisig,
+                &withdraw,
+                TEST_NOW
+            )
+            .unwrap(),
+            MultisigPayoutStart::Collecting(payout_id)
+        );
+        // the next withdrawal waits
+        let next = add_withdrawal(&conn, "sig2", COIN);
+        assert_eq!(
+            start_multisig_payout(&conn, &client, &custody_address, &multisig, &next, TEST_NOW)
+                .unwrap(),
+            MultisigPayoutStart::Waiting
+        );
+
+        let payout = conn.query_multisig_payout(payout_id).unwrap().unwrap();
+        let prevtxs: Vec<PrevTx> = serde_json::from_str(&payout.prevtxs).unwrap();
+        assert_eq!(prevtxs.len(), 1);
+        assert_eq!(prevtxs[0].redeem_script, multisig.redeem_script_hex());
+        assert_eq!(prevtxs[0].amount, "10.00000000");
+        // only the wallet signed it
+        assert!(collect_multisig_signatures(&conn, &client, TEST_NOW)
+            .unwrap()
+            .is_empty());
+
+        // a copy of another transaction is rejected
+        conn.add_multisig_signature(payout_id, &hex::encode("other|sig:cosigner"), TEST_NOW)
+            .unwrap();
+        let cosigned = sign_fake_transaction(&payout.raw_tx, "cosigner");
+        conn.add_multisig_signature(payout_id, &cosigned, TEST_NOW)
+            .unwrap();
+        let sent = collect_multisig_signatures(&conn, &client, TEST_NOW).unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, payout_id);
+        assert_eq!(node.broadcasted_transactions().len(), 1);
+        assert!(conn
+            .query_submitted_multisig_signatures(payout_id)
+            .unwrap()
+            .is_empty());
+        let payout = conn.query_multisig_payout(payout_id).unwrap().unwrap();
+        assert_eq!(payout.status, "sent");
+        assert_eq!(payout.txid.as_ref(), Some(&sent[0].1));
+        let withdraw = conn.query_pending_action(withdraw.id).unwrap().unwrap();
+        assert_eq!(withdraw.txid, payout.txid);
+
+        // the next withdrawal is paid out then
+        assert!(matches!(
+            start_multisig_payout(&conn, &client, &custody_address, &multisig, &next, TEST_NOW)
+                .unwrap(),
+            MultisigPayoutStart::Collecting(id) if id != payout_id
+        ));
+    }
+}
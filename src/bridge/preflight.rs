@@ -0,0 +1,263 @@
+use std::fmt;
+
+use log::info;
+
+use crate::db::{self, SCHEMA_VERSION};
+use crate::depc::Client as DePCClient;
+use crate::solana::SolanaClient;
+
+/// A failed check of the preflight and what the operator should do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightFailure {
+    pub check: &'static str,
+    /// The bridge the check is made for, `None` for the checks shared by the bridges
+    pub bridge_id: Option<String>,
+    pub reason: String,
+    pub remedy: String,
+}
+
+impl PreflightFailure {
+    fn new(check: &'static str, bridge_id: Option<&str>, reason: String, remedy: String) -> Self {
+        PreflightFailure {
+            check,
+            bridge_id: bridge_id.map(|bridge_id| bridge_id.to_owned()),
+            reason,
+            remedy,
+        }
+    }
+}
+
+impl fmt::Display for PreflightFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bridge_id.as_ref() {
+            Some(bridge_id) => write!(f, "[{}] bridge {bridge_id}: ", self.check)?,
+            None => write!(f, "[{}] ", self.check)?,
+        }
+        write!(f, "{}, {}", self.reason, self.remedy)
+    }
+}
+
+/// Check the database, the DePC node and the Solana clients of the bridges (bridge id, client)
+/// before the bridge starts, so a misconfiguration is reported at once instead of failing in
+/// the middle of the syncing
+///
+/// Returns all the failures, the bridge should not start unless it's empty
+pub fn run_preflight(
+    conn: &db::Conn,
+    depc_client: &DePCClient,
+    solana_clients: &[(String, SolanaClient)],
+) -> Vec<PreflightFailure> {
+    let mut failures = vec![];
+    failures.extend(check_database(conn));
+    failures.extend(check_depc_rpc(depc_client));
+    for (bridge_id, solana_client) in solana_clients.iter() {
+        failures.extend(check_solana(bridge_id, solana_client));
+    }
+    failures
+}
+
+fn check_database(conn: &db::Conn) -> Option<PreflightFailure> {
+    let version = match conn.query_schema_version() {
+        Ok(version) => version,
+        Err(e) => {
+            return Some(PreflightFailure::new(
+                "database",
+                None,
+                format!("cannot read the schema version, {e}"),
+                "check --local-db points to a database of the bridge".to_owned(),
+            ));
+        }
+    };
+    if version == SCHEMA_VERSION {
+        return None;
+    }
+    let remedy = if version > SCHEMA_VERSION {
+        "upgrade depc-bridge, or point --local-db to another database"
+    } else {
+        "initialize the database by `db::Conn::init` to migrate it"
+    };
+    Some(PreflightFailure::new(
+        "database",
+        None,
+        format!("the schema version is {version} but {SCHEMA_VERSION} is expected"),
+        remedy.to_owned(),
+    ))
+}
+
+fn check_depc_rpc(depc_client: &DePCClient) -> Option<PreflightFailure> {
+    match depc_client.get_network_info() {
+        Ok(network_info) => {
+            info!("preflight: DePC node {}", network_info.subversion);
+            None
+        }
+        Err(e) => Some(PreflightFailure::new(
+            "depc_rpc",
+            None,
+            format!("cannot call the DePC node, {e}"),
+            "check --depc-rpc-endpoint and the credentials (--depc-rpc-user/--depc-rpc-passwd, \
+             or the cookie file --depc-rpc-cookie-path with --depc-rpc-use-cookie)"
+                .to_owned(),
+        )),
+    }
+}
+
+fn check_solana(bridge_id: &str, solana_client: &SolanaClient) -> Vec<PreflightFailure> {
+    let bridge_id = Some(bridge_id);
+    match solana_client.get_version() {
+        Ok(version) => info!("preflight: solana-core {version}"),
+        Err(e) => {
+            // the other checks cannot be made without the node
+            return vec![PreflightFailure::new(
+                "solana_rpc",
+                bridge_id,
+                format!("cannot call the Solana RPC node, {e}"),
+                "check --sol-endpoint is reachable".to_owned(),
+            )];
+        }
+    }
+    let mut failures = vec![];
+    if let Err(e) = solana_client.check_health() {
+        failures.push(PreflightFailure::new(
+            "solana_health",
+            bridge_id,
+            e.to_string(),
+            "wait for the node to catch up with the cluster, or use another --sol-endpoint"
+                .to_owned(),
+        ));
+    }
+
+    let mint_pubkey = solana_client.mint_pubkey();
+    let authority_pubkey = solana_client.authority_pubkey();
+    match solana_client.get_mint_authority() {
+        Ok(Some(mint_authority)) if mint_authority == authority_pubkey => {}
+        Ok(mint_authority) => failures.push(PreflightFailure::new(
+            "mint_authority",
+            bridge_id,
+            format!(
+                "the mint authority of {mint_pubkey} is {} instead of {authority_pubkey}",
+                mint_authority.map_or("none".to_owned(), |pubkey| pubkey.to_string())
+            ),
+            "use the key of the mint authority as the authority key of the bridge".to_owned(),
+        )),
+        Err(e) => failures.push(PreflightFailure::new(
+            "mint",
+            bridge_id,
+            e.to_string(),
+            "check the mint pubkey and that --sol-endpoint is on the cluster of the mint"
+                .to_owned(),
+        )),
+    }
+
+    match solana_client.has_token_account(&authority_pubkey) {
+        Ok(true) => {}
+        Ok(false) => failures.push(PreflightFailure::new(
+            "authority_token_account",
+            bridge_id,
+            format!("the authority {authority_pubkey} has no token account of {mint_pubkey}"),
+            format!(
+                "create the associated token account of the authority and fund it, e.g. \
+                 `spl-token create-account {mint_pubkey} --owner {authority_pubkey}`"
+            ),
+        )),
+        Err(e) => failures.push(PreflightFailure::new(
+            "authority_token_account",
+            bridge_id,
+            e.to_string(),
+            "check --sol-endpoint is reachable".to_owned(),
+        )),
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair,
+        signer::keypair::keypair_from_seed,
+    };
+
+    use super::*;
+    use crate::depc;
+    use crate::solana::{default_fixture_dir, make_fixture_rpc_client, DEFAULT_LOCAL_ENDPOINT};
+    use crate::testing::FakeDepcNode;
+
+    /// The mint of the fixtures, its authority is made from seed `[7; 32]` and holds the token
+    /// account `DA2kTqD55ix29Chwq1jzFr3c7R7KadykEQNa59gkXyc9`
+    const TEST_MINT_PUBKEY: &str = "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw";
+
+    fn make_solana_client(mint_pubkey: Pubkey, authority_key: Keypair) -> SolanaClient {
+        let rpc_client = make_fixture_rpc_client(
+            DEFAULT_LOCAL_ENDPOINT,
+            &default_fixture_dir(),
+            CommitmentConfig::confirmed(),
+        );
+        SolanaClient::new_with_rpc_client(rpc_client, mint_pubkey, authority_key)
+    }
+
+    #[test]
+    fn test_preflight() {
+        let node = FakeDepcNode::start();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mint_pubkey = TEST_MINT_PUBKEY.parse().unwrap();
+        let solana_client = make_solana_client(mint_pubkey, keypair_from_seed(&[7; 32]).unwrap());
+        assert_eq!(
+            run_preflight(
+                &conn,
+                &node.client(),
+                &[("default".to_owned(), solana_client)]
+            ),
+            vec![]
+        );
+
+        // another authority holds neither the mint nor a token account, and the mint of the
+        // other bridge cannot be found
+        let solana_clients = [
+            (
+                "default".to_owned(),
+                make_solana_client(mint_pubkey, Keypair::new()),
+            ),
+            (
+                "other".to_owned(),
+                make_solana_client(Pubkey::new_unique(), Keypair::new()),
+            ),
+        ];
+        let unreachable = depc::ClientBuilder::new()
+            .set_endpoint("http://127.0.0.1:1")
+            .build();
+        let failures = run_preflight(&conn, &unreachable, &solana_clients);
+        let checks: Vec<_> = failures
+            .iter()
+            .map(|failure| (failure.check, failure.bridge_id.as_deref()))
+            .collect();
+        assert_eq!(
+            checks,
+            vec![
+                ("depc_rpc", None),
+                ("mint_authority", Some("default")),
+                ("authority_token_account", Some("default")),
+                ("mint", Some("other")),
+                ("authority_token_account", Some("other")),
+            ]
+        );
+        assert!(failures[2].to_string().contains("spl-token create-account"));
+    }
+
+    #[test]
+    fn test_preflight_database_of_newer_build() {
+        let path = std::env::temp_dir().join(format!(
+            "depc-bridge-preflight-{}.sqlite3",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(&format!("pragma user_version = {}", SCHEMA_VERSION + 1))
+            .unwrap();
+        let conn = db::Conn::open_or_create(path.to_str().unwrap()).unwrap();
+        conn.init().unwrap();
+        let failure = check_database(&conn).unwrap();
+        assert!(failure.remedy.starts_with("upgrade depc-bridge"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
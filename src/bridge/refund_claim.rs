@@ -0,0 +1,130 @@
+use log::{error, info, warn};
+
+use crate::db;
+use crate::depc::{Address as DePCAddress, Client as DePCClient};
+use crate::BridgeError;
+
+/// The states of the claims of table `depc_refunds`
+pub const REFUND_CLAIM_SUBMITTED: &str = "submitted";
+pub const REFUND_CLAIM_VERIFIED: &str = "verified";
+pub const REFUND_CLAIM_REJECTED: &str = "rejected";
+
+/// The message the key of the refund address signs (`signmessage`) to claim the refund of the
+/// invalid deposit `depc_txid:vout` to `address` instead, it binds the deposit to the address
+pub fn make_refund_claim_message(depc_txid: &str, vout: u32, address: &str) -> String {
+    format!("depc-bridge refund {depc_txid}:{vout} to {address}")
+}
+
+/// Verify the submitted claims of the refunds by the DePC node, a claim not signed by the key
+/// of the refund address is rejected and the refund is paid to the refund address. The claims
+/// cannot be verified now are verified again later
+pub fn verify_refund_claims(conn: &db::Conn, depc_client: &DePCClient) -> Result<(), BridgeError> {
+    for refund in conn.query_submitted_refund_claims()? {
+        let (Some(address), Some(signature)) = (&refund.claim_address, &refund.claim_signature)
+        else {
+            continue;
+        };
+        let message = make_refund_claim_message(&refund.depc_txid, refund.vout, address);
+        match depc_client.verify_message(&refund.refund_address, signature, &message) {
+            Ok(true) => {
+                conn.set_refund_claim_status(refund.id, REFUND_CLAIM_VERIFIED)?;
+                info!(
+                    "refund {} is claimed to {address} by {}",
+                    refund.id, refund.refund_address
+                );
+            }
+            Ok(false) => {
+                conn.set_refund_claim_status(refund.id, REFUND_CLAIM_REJECTED)?;
+                warn!(
+                    "the claim of refund {} to {address} is not signed by {}, it's rejected",
+                    refund.id, refund.refund_address
+                );
+            }
+            Err(e) => error!(
+                "cannot verify the claim of refund {}, retry later, reason: {e}",
+                refund.id
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// The address `refund` is paid to, the claimed address once the claim is verified, `None`
+/// while the claim is waiting for the verification
+pub fn refund_destination(refund: &db::RefundRecord) -> Option<DePCAddress> {
+    match (refund.claim_status.as_deref(), &refund.claim_address) {
+        (Some(REFUND_CLAIM_SUBMITTED), _) => None,
+        (Some(REFUND_CLAIM_VERIFIED), Some(address)) => Some(address.clone()),
+        _ => Some(refund.refund_address.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::DepcAmount;
+    use crate::testing::FakeDepcNode;
+
+    const TEST_SENDER_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+    const TEST_CLAIM_ADDRESS: &str = "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS";
+
+    #[test]
+    fn test_verify_refund_claims() {
+        let node = FakeDepcNode::start();
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for txid in ["depc_txid1", "depc_txid2"] {
+            conn.add_refund(
+                txid,
+                0,
+                TEST_SENDER_ADDRESS,
+                DepcAmount::from_value64(5000000),
+                "invalid address: bad",
+                1000,
+            )
+            .unwrap();
+        }
+        let refunds = conn.query_refunds(10).unwrap();
+        let (second, first) = (&refunds[0], &refunds[1]);
+        assert_eq!(
+            refund_destination(first).as_deref(),
+            Some(TEST_SENDER_ADDRESS)
+        );
+
+        // the fake node signs `<address>:<message>`
+        let message = make_refund_claim_message("depc_txid1", 0, TEST_CLAIM_ADDRESS);
+        let signature = hex::encode(format!("{TEST_SENDER_ADDRESS}:{message}"));
+        assert!(conn
+            .claim_refund(first.id, TEST_CLAIM_ADDRESS, &signature)
+            .unwrap());
+        // the signature of the first claim doesn't claim the second refund
+        assert!(conn
+            .claim_refund(second.id, TEST_CLAIM_ADDRESS, &signature)
+            .unwrap());
+        let first = conn
+            .query_refund_of_output("depc_txid1", 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(refund_destination(&first), None);
+
+        verify_refund_claims(&conn, &node.client()).unwrap();
+        let first = conn
+            .query_refund_of_output("depc_txid1", 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.claim_status.as_deref(), Some(REFUND_CLAIM_VERIFIED));
+        assert_eq!(
+            refund_destination(&first).as_deref(),
+            Some(TEST_CLAIM_ADDRESS)
+        );
+        let second = conn
+            .query_refund_of_output("depc_txid2", 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.claim_status.as_deref(), Some(REFUND_CLAIM_REJECTED));
+        assert_eq!(
+            refund_destination(&second).as_deref(),
+            Some(TEST_SENDER_ADDRESS)
+        );
+    }
+}
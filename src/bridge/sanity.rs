@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::db;
+use crate::BridgeError;
+
+use super::ACTION_DEPOSIT;
+
+/// The deposits found an hour ago and still not confirmed are reported as stuck by default
+pub const DEFAULT_STUCK_DEPOSIT_AGE: u64 = 3600;
+
+/// The data of a bridge checked after the migrations, the rows of the tables are counted to
+/// tell a database restored from a wrong backup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanityReport {
+    pub bridge_id: String,
+    pub checked_at: u64,
+    /// The deposits found before it are reported when they're not confirmed yet
+    pub stuck_before: u64,
+    /// The tables of the database and the rows of them, in the order of the names
+    pub tables: Vec<(String, u64)>,
+    /// The deposits in an intermediate state found before `stuck_before` by the state
+    pub stuck_deposits: Vec<db::StuckPendingActions>,
+    /// The coins marked spent without the spending transaction, the index is corrupted by them
+    pub spent_coins_without_txid: u64,
+}
+
+impl SanityReport {
+    pub fn is_sane(&self) -> bool {
+        self.stuck_deposits.is_empty() && self.spent_coins_without_txid == 0
+    }
+}
+
+/// Check the data of the bridge of `conn`, the deposits are stuck if they're found `stuck_after`
+/// seconds before `now` and not confirmed yet
+pub fn run_sanity_check(
+    conn: &db::Conn,
+    now: u64,
+    stuck_after: u64,
+) -> Result<SanityReport, BridgeError> {
+    let stuck_before = now.saturating_sub(stuck_after);
+    Ok(SanityReport {
+        bridge_id: conn.bridge_id().to_owned(),
+        checked_at: now,
+        stuck_before,
+        tables: conn.query_table_row_counts()?,
+        stuck_deposits: conn.query_stuck_pending_actions(ACTION_DEPOSIT, stuck_before)?,
+        spent_coins_without_txid: conn.query_num_spent_coins_without_txid()?,
+    })
+}
+
+impl fmt::Display for SanityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bridge:         {}", self.bridge_id)?;
+        write!(f, "tables:         {}", self.tables.len())?;
+        for (table, count) in self.tables.iter() {
+            write!(f, "\n  {table} {count}")?;
+        }
+        if self.stuck_deposits.is_empty() {
+            writeln!(f, "\nstuck deposits: none")?;
+        } else {
+            let count: u64 = self.stuck_deposits.iter().map(|stuck| stuck.count).sum();
+            write!(f, "\nstuck deposits: {count}")?;
+            for stuck in self.stuck_deposits.iter() {
+                write!(
+                    f,
+                    "\n  {} {} since {}",
+                    stuck.state, stuck.count, stuck.oldest
+                )?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "spent coins without txid: {}",
+            self.spent_coins_without_txid
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::DepcAmount;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    #[test]
+    fn test_sanity_check() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let report = run_sanity_check(&conn, TEST_NOW, DEFAULT_STUCK_DEPOSIT_AGE).unwrap();
+        assert!(report.is_sane());
+        assert!(report.tables.contains(&("pending_actions".to_owned(), 0)));
+        assert!(report.tables.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        // a queued deposit, a claimed one, one made but not confirmed and a recent one
+        for (txid, timestamp) in [(0xc1, 7200), (0xc2, 5400), (0xc3, 4000), (0xc4, 60)] {
+            conn.add_pending_action(
+                ACTION_DEPOSIT,
+                &format!("{:064x}", txid),
+                "recipient",
+                DepcAmount::from_value64(100000000),
+                TEST_NOW - timestamp,
+            )
+            .unwrap();
+        }
+        assert!(conn
+            .claim_pending_action(2, TEST_NOW - 5000)
+            .unwrap()
+            .is_some());
+        assert!(conn
+            .claim_pending_action(3, TEST_NOW - 3900)
+            .unwrap()
+            .is_some());
+        conn.complete_pending_action(3, Some("signature"), TEST_NOW - 3800)
+            .unwrap();
+        // the coin is marked spent by no transaction
+        conn.add_transaction("hash", "a1").unwrap();
+        conn.add_coin("a1", 0, DepcAmount::from_value64(1), "owner", "")
+            .unwrap();
+        conn.mark_coin_to_spent("a1", 0, "", 1).unwrap();
+
+        let report = run_sanity_check(&conn, TEST_NOW, DEFAULT_STUCK_DEPOSIT_AGE).unwrap();
+        assert!(!report.is_sane());
+        assert!(report.tables.contains(&("pending_actions".to_owned(), 4)));
+        let stuck: Vec<_> = report
+            .stuck_deposits
+            .iter()
+            .map(|stuck| (stuck.state.as_str(), stuck.count, stuck.oldest))
+            .collect();
+        assert_eq!(
+            stuck,
+            vec![
+                ("claimed", 1, TEST_NOW - 5400),
+                ("queued", 1, TEST_NOW - 7200),
+                ("unconfirmed", 1, TEST_NOW - 4000),
+            ]
+        );
+        assert_eq!(report.spent_coins_without_txid, 1);
+        assert!(report.to_string().ends_with(
+            "stuck deposits: 3\n  claimed 1 since 1730995200\n  queued 1 since 1730993400\n  \
+             unconfirmed 1 since 1730996600\nspent coins without txid: 1"
+        ));
+
+        // the confirmed deposit is not stuck
+        conn.confirm_pending_action(3, TEST_NOW).unwrap();
+        let report = run_sanity_check(&conn, TEST_NOW, DEFAULT_STUCK_DEPOSIT_AGE).unwrap();
+        assert_eq!(report.stuck_deposits.len(), 2);
+    }
+}
@@ -0,0 +1,512 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use log::{info, warn, LevelFilter};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::db::DEFAULT_BRIDGE_ID;
+use crate::depc::ExtendedPubKey;
+use crate::BridgeError;
+
+/// The transactions `/solana/post_tx` relays for a fee payer a day unless it's set
+pub const DEFAULT_RELAY_DAILY_QUOTA: u32 = 20;
+
+/// A bridge instance of the settings file, the DePC custody address and the SPL mint the
+/// deposits to it are bridged to
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PairSettings {
+    /// The rows of the pair are kept with it, and the REST endpoints of the pair are served
+    /// under `/bridges/{bridge_id}`
+    pub bridge_id: String,
+    /// The custody address, the pair starts a new custody era when it's changed, see
+    /// `rotate_custody_address`
+    pub depc_owner_address: String,
+    pub solana_owner_address: String,
+    pub sol_mint_pubkey: String,
+    /// The base58 private key of the mint authority
+    pub sol_authority_key: String,
+    /// The account xpub the deposit addresses of the users are derived from, see
+    /// `register_deposit_address`
+    pub deposit_xpub: Option<String>,
+}
+
+/// A listener of the REST service bound with `unix:<path>` serves on a Unix domain socket
+pub const UNIX_BIND_PREFIX: &str = "unix:";
+
+/// The endpoints a listener of the REST service serves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerRoutes {
+    /// All the endpoints, the admin ones are served only when the admin token is configured
+    #[default]
+    All,
+    /// All the endpoints but `/admin/*`
+    Public,
+    /// Only `/admin/*`
+    Admin,
+    /// The public endpoints queried by GET, nothing is changed through it, e.g. the listeners of
+    /// the index-only mode
+    ReadOnly,
+}
+
+impl ListenerRoutes {
+    pub fn serves_public(self) -> bool {
+        matches!(
+            self,
+            ListenerRoutes::All | ListenerRoutes::Public | ListenerRoutes::ReadOnly
+        )
+    }
+
+    pub fn serves_admin(self) -> bool {
+        matches!(self, ListenerRoutes::All | ListenerRoutes::Admin)
+    }
+
+    /// Whether the requests other than GET are served
+    pub fn serves_writes(self) -> bool {
+        self != ListenerRoutes::ReadOnly
+    }
+}
+
+/// The PEM files of the certificate chain and the private key of a TLS listener
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// A listener of the REST service, e.g. the public endpoints on the public interface and the
+/// admin ones on a Unix domain socket
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ListenerSettings {
+    /// `host:port`, or `unix:<path>` for a Unix domain socket
+    pub bind: String,
+    #[serde(default)]
+    pub routes: ListenerRoutes,
+    /// The connections are served over TLS when it's set, only for the TCP listeners
+    pub tls: Option<TlsSettings>,
+}
+
+impl ListenerSettings {
+    /// The listener of `--bind`, it serves all the endpoints without TLS
+    pub fn new(bind: &str) -> ListenerSettings {
+        ListenerSettings {
+            bind: bind.to_owned(),
+            routes: ListenerRoutes::All,
+            tls: None,
+        }
+    }
+
+    /// The path of the Unix domain socket, `None` for a TCP listener
+    pub fn unix_path(&self) -> Option<&str> {
+        self.bind.strip_prefix(UNIX_BIND_PREFIX)
+    }
+}
+
+/// The settings applied without a restart when the settings file is reloaded, see
+/// `LiveSettings`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ReloadableSettings {
+    /// The max level of the logs (`off`, `error`, `warn`, `info`, `debug` or `trace`), the logs
+    /// filtered out by `RUST_LOG` are not printed whatever it is, the level of `RUST_LOG` is
+    /// kept when it's not set
+    pub log_level: Option<String>,
+    /// The transactions `/solana/post_tx` relays for a fee payer a day
+    pub relay_daily_quota: u32,
+    /// The fee payers `/solana/post_tx` relays for without the quota
+    pub relay_allowlist: Vec<String>,
+}
+
+impl Default for ReloadableSettings {
+    fn default() -> Self {
+        ReloadableSettings {
+            log_level: None,
+            relay_daily_quota: DEFAULT_RELAY_DAILY_QUOTA,
+            relay_allowlist: vec![],
+        }
+    }
+}
+
+impl ReloadableSettings {
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        self.log_level_filter()?;
+        for fee_payer in self.relay_allowlist.iter() {
+            if Pubkey::from_str(fee_payer).is_err() {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "relay allowlist `{fee_payer}` is not a solana address"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn log_level_filter(&self) -> Result<Option<LevelFilter>, BridgeError> {
+        self.log_level
+            .as_deref()
+            .map(|level| {
+                LevelFilter::from_str(level).map_err(|_| {
+                    BridgeError::InvalidConfig(format!("log level `{level}` is unknown"))
+                })
+            })
+            .transpose()
+    }
+
+    /// Whether the transactions paid by `fee_payer` are relayed without the quota
+    pub fn is_relay_allowed(&self, fee_payer: &str) -> bool {
+        self.relay_allowlist
+            .iter()
+            .any(|allowed| allowed == fee_payer)
+    }
+}
+
+/// The settings file of the multi-tenant mode, the pairs are run besides the one from the
+/// command line, which is the bridge `default`
+///
+/// ```json
+/// {
+///   "bridges": [
+///     {
+///       "bridge_id": "partner",
+///       "depc_owner_address": "...",
+///       "solana_owner_address": "...",
+///       "sol_mint_pubkey": "...",
+///       "sol_authority_key": "..."
+///     }
+///   ],
+///   "listeners": [
+///     {
+///       "bind": "0.0.0.0:443",
+///       "routes": "public",
+///       "tls": { "cert_path": "...", "key_path": "..." }
+///     },
+///     { "bind": "unix:/run/depc-bridge/admin.sock", "routes": "admin" }
+///   ],
+///   "reloadable": {
+///     "log_level": "info",
+///     "relay_daily_quota": 20,
+///     "relay_allowlist": []
+///   }
+/// }
+/// ```
+///
+/// The pairs and the listeners are read once on start, the REST service listens on `--bind`
+/// only when no listener is given. The section `reloadable` is read again on SIGHUP or
+/// `POST /admin/settings/reload`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct BridgeSettings {
+    pub bridges: Vec<PairSettings>,
+    #[serde(default)]
+    pub listeners: Vec<ListenerSettings>,
+    #[serde(default)]
+    pub reloadable: ReloadableSettings,
+}
+
+impl BridgeSettings {
+    pub fn load(path: &str) -> Result<BridgeSettings, BridgeError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot read settings file {path}, {e}"))
+        })?;
+        BridgeSettings::from_json(&content)
+    }
+
+    pub fn from_json(content: &str) -> Result<BridgeSettings, BridgeError> {
+        let settings: BridgeSettings = serde_json::from_str(content)
+            .map_err(|e| BridgeError::InvalidConfig(format!("cannot parse settings, {e}")))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// The bridge ids are used in the urls, they're unique and made of `[A-Za-z0-9_-]`
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        self.reloadable.validate()?;
+        let mut bridge_ids = HashSet::from([DEFAULT_BRIDGE_ID]);
+        for pair in self.bridges.iter() {
+            let bridge_id = pair.bridge_id.as_str();
+            let valid = !bridge_id.is_empty()
+                && bridge_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "bridge id `{bridge_id}` should be made of letters, digits, `_` and `-`"
+                )));
+            }
+            if !bridge_ids.insert(bridge_id) {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "bridge id `{bridge_id}` is used more than once"
+                )));
+            }
+            if let Some(xpub) = pair.deposit_xpub.as_deref() {
+                ExtendedPubKey::from_str(xpub).map_err(|e| {
+                    BridgeError::InvalidConfig(format!(
+                        "deposit xpub of bridge `{bridge_id}` is invalid, {e}"
+                    ))
+                })?;
+            }
+        }
+        let mut binds = HashSet::new();
+        for listener in self.listeners.iter() {
+            let bind = listener.bind.as_str();
+            if listener.unix_path().unwrap_or(bind).is_empty() {
+                return Err(BridgeError::InvalidConfig(
+                    "the bind of a listener is empty".to_owned(),
+                ));
+            }
+            if !binds.insert(bind) {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "listener `{bind}` is given more than once"
+                )));
+            }
+            if listener.unix_path().is_some() && listener.tls.is_some() {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "listener `{bind}` is a unix domain socket, it cannot serve over TLS"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The reloadable settings of the running bridge, a reload reads the settings file again and
+/// swaps the settings in only when the whole file is valid, the readers see either the old
+/// settings or the new ones
+pub struct LiveSettings {
+    path: Option<String>,
+    /// The pairs and the listeners of the file read on start, they're not changed by a reload
+    bridges: Vec<PairSettings>,
+    listeners: Vec<ListenerSettings>,
+    /// The level of `RUST_LOG`, it's restored when `log_level` is unset
+    default_log_level: LevelFilter,
+    current: RwLock<Arc<ReloadableSettings>>,
+}
+
+impl LiveSettings {
+    /// The settings of `path` read on start, `None` if there's no settings file
+    pub fn new(path: Option<String>, settings: BridgeSettings) -> LiveSettings {
+        let live = LiveSettings {
+            path,
+            bridges: settings.bridges,
+            listeners: settings.listeners,
+            default_log_level: log::max_level(),
+            current: RwLock::new(Arc::new(settings.reloadable)),
+        };
+        live.apply_log_level(&live.current());
+        live
+    }
+
+    pub fn current(&self) -> Arc<ReloadableSettings> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Read the settings file again and swap the reloadable settings in, the settings in use
+    /// are kept if the file cannot be read or is invalid
+    pub fn reload(&self) -> Result<Arc<ReloadableSettings>, BridgeError> {
+        let Some(path) = self.path.as_ref() else {
+            return Err(BridgeError::InvalidConfig(
+                "no settings file is given".to_owned(),
+            ));
+        };
+        let settings = BridgeSettings::load(path)?;
+        if settings.bridges != self.bridges {
+            warn!("the bridges of settings file {path} are changed, they're applied on restart");
+        }
+        if settings.listeners != self.listeners {
+            warn!("the listeners of settings file {path} are changed, they're applied on restart");
+        }
+        let reloadable = Arc::new(settings.reloadable);
+        self.apply_log_level(&reloadable);
+        *self.current.write().unwrap() = Arc::clone(&reloadable);
+        info!("settings file {path} is reloaded, {reloadable:?}");
+        Ok(reloadable)
+    }
+
+    fn apply_log_level(&self, settings: &ReloadableSettings) {
+        // validated when the settings are loaded
+        let level = settings.log_level_filter().ok().flatten();
+        log::set_max_level(level.unwrap_or(self.default_log_level));
+    }
+}
+
+/// Reload the settings on every SIGHUP, a failed reload is logged and the settings in use are
+/// kept
+#[cfg(unix)]
+pub async fn reload_settings_on_sighup(settings: Arc<LiveSettings>) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!("cannot install the SIGHUP handler, reason: {e}");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("SIGHUP is received, reloading the settings");
+        if let Err(e) = settings.reload() {
+            warn!(
+                "cannot reload the settings, the ones in use are kept, reason: {}",
+                e.full_message()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_pair_json(bridge_id: &str) -> String {
+        format!(
+            r#"{{"bridge_id": "{bridge_id}", "depc_owner_address": "depc", "solana_owner_address": "owner", "sol_mint_pubkey": "mint", "sol_authority_key": "key"}}"#
+        )
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let content = format!(
+            r#"{{"bridges": [{}, {}]}}"#,
+            make_pair_json("a"),
+            make_pair_json("partner-2")
+        );
+        let settings = BridgeSettings::from_json(&content).unwrap();
+        assert_eq!(settings.bridges.len(), 2);
+        assert_eq!(settings.bridges[1].bridge_id, "partner-2");
+        assert_eq!(settings.bridges[1].sol_mint_pubkey, "mint");
+
+        for bridge_ids in [["a", "a"], ["a", "default"], ["a", "b/c"], ["a", ""]] {
+            let content = format!(
+                r#"{{"bridges": [{}, {}]}}"#,
+                make_pair_json(bridge_ids[0]),
+                make_pair_json(bridge_ids[1])
+            );
+            assert!(matches!(
+                BridgeSettings::from_json(&content),
+                Err(BridgeError::InvalidConfig(_))
+            ));
+        }
+        assert!(BridgeSettings::from_json(r#"{"bridges": [{"bridge_id": "a"}]}"#).is_err());
+
+        // the deposit addresses are derived only when the xpub is given
+        assert_eq!(settings.bridges[0].deposit_xpub, None);
+        let mut pair: serde_json::Value = serde_json::from_str(&make_pair_json("a")).unwrap();
+        pair["deposit_xpub"] = "xpub-invalid".into();
+        let content = serde_json::json!({ "bridges": [pair] }).to_string();
+        assert!(matches!(
+            BridgeSettings::from_json(&content),
+            Err(BridgeError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_reloadable_settings() {
+        let settings = BridgeSettings::from_json(r#"{"bridges": []}"#).unwrap();
+        assert_eq!(settings.reloadable, ReloadableSettings::default());
+        assert_eq!(
+            settings.reloadable.relay_daily_quota,
+            DEFAULT_RELAY_DAILY_QUOTA
+        );
+
+        let content = serde_json::json!({
+            "bridges": [],
+            "reloadable": {
+                "log_level": "debug",
+                "relay_allowlist": [Pubkey::default().to_string()],
+            },
+        })
+        .to_string();
+        let settings = BridgeSettings::from_json(&content).unwrap();
+        assert_eq!(settings.reloadable.log_level.as_deref(), Some("debug"));
+        assert!(settings
+            .reloadable
+            .is_relay_allowed(&Pubkey::default().to_string()));
+        assert!(!settings.reloadable.is_relay_allowed("other"));
+
+        for reloadable in [
+            r#"{"log_level": "loud"}"#,
+            r#"{"relay_allowlist": ["not an address"]}"#,
+        ] {
+            let content = format!(r#"{{"bridges": [], "reloadable": {reloadable}}}"#);
+            assert!(matches!(
+                BridgeSettings::from_json(&content),
+                Err(BridgeError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_listeners() {
+        let settings = BridgeSettings::from_json(r#"{"bridges": []}"#).unwrap();
+        assert!(settings.listeners.is_empty());
+
+        let content = serde_json::json!({
+            "bridges": [],
+            "listeners": [
+                {
+                    "bind": "0.0.0.0:443",
+                    "routes": "public",
+                    "tls": { "cert_path": "cert.pem", "key_path": "key.pem" },
+                },
+                { "bind": "unix:/run/admin.sock", "routes": "admin" },
+                { "bind": "127.0.0.1:3000" },
+            ],
+        })
+        .to_string();
+        let settings = BridgeSettings::from_json(&content).unwrap();
+        let listeners = &settings.listeners;
+        assert_eq!(listeners[0].routes, ListenerRoutes::Public);
+        assert_eq!(listeners[0].tls.as_ref().unwrap().key_path, "key.pem");
+        assert_eq!(listeners[0].unix_path(), None);
+        assert_eq!(listeners[1].unix_path(), Some("/run/admin.sock"));
+        assert!(!listeners[1].routes.serves_public() && listeners[1].routes.serves_admin());
+        assert_eq!(listeners[2], ListenerSettings::new("127.0.0.1:3000"));
+
+        for listeners in [
+            r#"[{"bind": ""}]"#,
+            r#"[{"bind": "unix:"}]"#,
+            r#"[{"bind": "127.0.0.1:3000"}, {"bind": "127.0.0.1:3000", "routes": "admin"}]"#,
+            r#"[{"bind": "unix:/run/admin.sock", "tls": {"cert_path": "c", "key_path": "k"}}]"#,
+            r#"[{"bind": "127.0.0.1:3000", "routes": "internal"}]"#,
+        ] {
+            let content = format!(r#"{{"bridges": [], "listeners": {listeners}}}"#);
+            assert!(matches!(
+                BridgeSettings::from_json(&content),
+                Err(BridgeError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_reload_live_settings() {
+        let path =
+            std::env::temp_dir().join(format!("depc-bridge-settings-{}.json", std::process::id()));
+        let path_str = path.to_str().unwrap().to_owned();
+        let write = |content: &str| std::fs::write(&path, content).unwrap();
+
+        write(&format!(r#"{{"bridges": [{}]}}"#, make_pair_json("a")));
+        let live = LiveSettings::new(
+            Some(path_str.clone()),
+            BridgeSettings::load(&path_str).unwrap(),
+        );
+        assert_eq!(live.current().relay_daily_quota, DEFAULT_RELAY_DAILY_QUOTA);
+
+        write(&format!(
+            r#"{{"bridges": [{}], "reloadable": {{"relay_daily_quota": 5}}}}"#,
+            make_pair_json("a")
+        ));
+        assert_eq!(live.reload().unwrap().relay_daily_quota, 5);
+        assert_eq!(live.current().relay_daily_quota, 5);
+
+        // the invalid file is refused as a whole, the settings in use are kept
+        write(r#"{"bridges": [], "reloadable": {"relay_daily_quota": -1}}"#);
+        assert!(live.reload().is_err());
+        write(r#"{"bridges": [], "reloadable": {"log_level": "loud"}}"#);
+        assert!(live.reload().is_err());
+        assert_eq!(live.current().relay_daily_quota, 5);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(live.reload().is_err());
+        assert!(LiveSettings::new(None, BridgeSettings::default())
+            .reload()
+            .is_err());
+    }
+}
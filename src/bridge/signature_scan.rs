@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use log::{info, warn};
+use solana_sdk::signature::Signature;
+
+use crate::db;
+use crate::BridgeError;
+
+/// The signatures of the last slots are read again by the next scan, the transactions confirmed
+/// in them can still be rolled back before they're finalized
+pub const SIGNATURE_SCAN_OVERLAP_SLOTS: u64 = 32;
+
+/// The result of a scan of the signatures of an account
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureScanReport {
+    /// The signatures processed, the ones of the overlap are counted again by the next scan
+    pub scanned: u64,
+    /// The signature the next scan reads after, `None` if the account has no signature old
+    /// enough yet
+    pub cursor: Option<String>,
+}
+
+/// Scan the signatures of `account` newer than its cursor in table `solana_cursors`, so the
+/// signature list of the account is not read from the beginning every time
+///
+/// `fetch` reads the signatures newer than the given one with their slots, the newest first (see
+/// `SolanaClient::get_signatures_until`), and `process` is called with them from the oldest.
+/// The cursor is moved to the last signature processed at least `SIGNATURE_SCAN_OVERLAP_SLOTS`
+/// slots below the newest one, the signatures above it are processed again by the next scan,
+/// so `process` must be idempotent. The cursor is kept before the signature `process` fails on.
+pub fn scan_signatures<F, P>(
+    conn: &db::Conn,
+    account: &str,
+    now: u64,
+    fetch: F,
+    mut process: P,
+) -> Result<SignatureScanReport, BridgeError>
+where
+    F: FnOnce(Option<Signature>) -> Result<Vec<(Signature, u64)>, BridgeError>,
+    P: FnMut(&Signature, u64) -> Result<(), BridgeError>,
+{
+    let cursor = conn.query_solana_cursor(account)?;
+    let until = match cursor
+        .as_ref()
+        .map(|cursor| Signature::from_str(&cursor.signature))
+    {
+        Some(Ok(signature)) => Some(signature),
+        Some(Err(_)) => {
+            warn!("the cursor of {account} cannot be parsed, scanning from the first signature");
+            None
+        }
+        None => None,
+    };
+    let signatures = fetch(until)?;
+    let mut report = SignatureScanReport {
+        scanned: 0,
+        cursor: cursor.map(|cursor| cursor.signature),
+    };
+    let Some(&(_, newest_slot)) = signatures.first() else {
+        return Ok(report);
+    };
+    let settled_slot = newest_slot.saturating_sub(SIGNATURE_SCAN_OVERLAP_SLOTS);
+    let mut next_cursor = None;
+    let mut result = Ok(());
+    for (signature, slot) in signatures.iter().rev() {
+        if let Err(e) = process(signature, *slot) {
+            result = Err(e);
+            break;
+        }
+        report.scanned += 1;
+        if *slot <= settled_slot {
+            next_cursor = Some((signature, *slot));
+        }
+    }
+    if let Some((signature, slot)) = next_cursor {
+        let signature = signature.to_string();
+        conn.set_solana_cursor(account, &signature, slot, now)?;
+        report.cursor = Some(signature);
+    }
+    result?;
+    info!(
+        "{} signature(s) of {} are scanned, the cursor is {}",
+        report.scanned,
+        account,
+        report.cursor.as_deref().unwrap_or("not set")
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::solana::ErrorKind;
+
+    const TEST_ACCOUNT: &str = "3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L";
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    /// The signatures of `chain` newer than `until`, the newest first, like the node returns
+    fn fetch_from(
+        chain: &[(Signature, u64)],
+        until: Option<Signature>,
+    ) -> Result<Vec<(Signature, u64)>, BridgeError> {
+        Ok(chain
+            .iter()
+            .rev()
+            .take_while(|(signature, _)| Some(*signature) != until)
+            .copied()
+            .collect())
+    }
+
+    #[test]
+    fn test_scan_signatures_after_cursor() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        // oldest first
+        let mut chain: Vec<(Signature, u64)> = [100, 110, 150]
+            .into_iter()
+            .map(|slot| (Signature::new_unique(), slot))
+            .collect();
+        let processed = RefCell::new(vec![]);
+        let process = |signature: &Signature, _slot: u64| -> Result<(), BridgeError> {
+            processed.borrow_mut().push(*signature);
+            Ok(())
+        };
+
+        let report = scan_signatures(
+            &conn,
+            TEST_ACCOUNT,
+            TEST_NOW,
+            |until| fetch_from(&chain, until),
+            process,
+        )
+        .unwrap();
+        // the cursor is kept 32 slots below the newest one
+        assert_eq!(report.scanned, 3);
+        assert_eq!(report.cursor, Some(chain[1].0.to_string()));
+        let cursor = conn.query_solana_cursor(TEST_ACCOUNT).unwrap().unwrap();
+        assert_eq!((cursor.slot, cursor.updated_at), (110, TEST_NOW));
+
+        // only the overlap and the new signatures are read again
+        chain.push((Signature::new_unique(), 200));
+        processed.borrow_mut().clear();
+        let report = scan_signatures(
+            &conn,
+            TEST_ACCOUNT,
+            TEST_NOW + 60,
+            |until| fetch_from(&chain, until),
+            process,
+        )
+        .unwrap();
+        assert_eq!(*processed.borrow(), vec![chain[2].0, chain[3].0]);
+        assert_eq!(report.cursor, Some(chain[2].0.to_string()));
+    }
+
+    #[test]
+    fn test_scan_signatures_keeps_cursor_before_failure() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let chain: Vec<(Signature, u64)> = [100, 110, 200]
+            .into_iter()
+            .map(|slot| (Signature::new_unique(), slot))
+            .collect();
+        let failing = chain[1].0;
+        let res = scan_signatures(
+            &conn,
+            TEST_ACCOUNT,
+            TEST_NOW,
+            |until| fetch_from(&chain, until),
+            |signature, _| {
+                if *signature == failing {
+                    return Err(BridgeError::TokenClient(
+                        "unavailable".to_owned(),
+                        ErrorKind::Transient,
+                    ));
+                }
+                Ok(())
+            },
+        );
+        assert!(res.is_err());
+        let cursor = conn.query_solana_cursor(TEST_ACCOUNT).unwrap().unwrap();
+        assert_eq!(cursor.signature, chain[0].0.to_string());
+
+        // no signature is old enough for the cursor
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let report = scan_signatures(
+            &conn,
+            TEST_ACCOUNT,
+            TEST_NOW,
+            |until| fetch_from(&chain[2..], until),
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!((report.scanned, report.cursor), (1, None));
+        assert!(conn.query_solana_cursor(TEST_ACCOUNT).unwrap().is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::db;
+use crate::BridgeError;
+
+/// The consecutive heights `from..=to` those are pruned or missing on the DePC node, the coins
+/// of them are not indexed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataGap {
+    pub from: u32,
+    pub to: u32,
+    /// The reason of the first height of the gap
+    pub reason: String,
+}
+
+/// The syncing state of the chain index of a bridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeStatus {
+    pub bridge_id: String,
+    pub best_height: Option<u32>,
+    pub checkpoint: Option<db::CheckpointRecord>,
+    pub data_gaps: Vec<DataGap>,
+}
+
+pub fn query_bridge_status(conn: &db::Conn) -> Result<BridgeStatus, BridgeError> {
+    let mut data_gaps: Vec<DataGap> = vec![];
+    for record in conn.query_unavailable_heights()? {
+        match data_gaps.last_mut() {
+            Some(gap) if gap.to + 1 == record.height => gap.to = record.height,
+            _ => data_gaps.push(DataGap {
+                from: record.height,
+                to: record.height,
+                reason: record.reason,
+            }),
+        }
+    }
+    Ok(BridgeStatus {
+        bridge_id: conn.bridge_id().to_owned(),
+        best_height: conn.query_best_height(),
+        checkpoint: conn.query_checkpoint()?,
+        data_gaps,
+    })
+}
+
+impl fmt::Display for BridgeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bridge:      {}", self.bridge_id)?;
+        match self.best_height {
+            Some(height) => writeln!(f, "best height: {height}")?,
+            None => writeln!(f, "best height: no block is synced")?,
+        }
+        match self.checkpoint.as_ref() {
+            Some(checkpoint) => writeln!(
+                f,
+                "checkpoint:  {} ({})",
+                checkpoint.height, checkpoint.hash
+            )?,
+            None => writeln!(f, "checkpoint:  genesis")?,
+        }
+        if self.data_gaps.is_empty() {
+            return write!(f, "data gaps:   none");
+        }
+        write!(f, "data gaps:   {}", self.data_gaps.len())?;
+        for gap in self.data_gaps.iter() {
+            write!(f, "\n  {}..={} {}", gap.from, gap.to, gap.reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    #[test]
+    fn test_data_gaps() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for height in [5, 3, 4, 9] {
+            assert!(conn
+                .mark_height_unavailable(height, &format!("block {height}"), TEST_NOW)
+                .unwrap());
+        }
+        // the first reason is kept
+        assert!(!conn
+            .mark_height_unavailable(3, "transaction a1", TEST_NOW)
+            .unwrap());
+        conn.add_block("hash10", 10, "miner", TEST_NOW).unwrap();
+
+        let status = query_bridge_status(&conn).unwrap();
+        assert_eq!(status.best_height, Some(10));
+        assert_eq!(status.checkpoint, None);
+        assert_eq!(
+            status.data_gaps,
+            vec![
+                DataGap {
+                    from: 3,
+                    to: 5,
+                    reason: "block 3".to_owned()
+                },
+                DataGap {
+                    from: 9,
+                    to: 9,
+                    reason: "block 9".to_owned()
+                },
+            ]
+        );
+        assert_eq!(
+            status.to_string(),
+            "bridge:      default\nbest height: 10\ncheckpoint:  genesis\ndata gaps:   2\n  \
+             3..=5 block 3\n  9..=9 block 9"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::ledger::LedgerEvent;
+use crate::BridgeError;
+
+use super::{ACTION_DEPOSIT, ACTION_WITHDRAW};
+
+/// The windows the bridge is summarized for, the name and the seconds before now
+pub const SUMMARY_WINDOWS: [(&str, u64); 3] = [
+    ("24h", 24 * 3600),
+    ("7d", 7 * 24 * 3600),
+    ("30d", 30 * 24 * 3600),
+];
+
+/// The seconds from being found to being confirmed on the other chain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+impl LatencyPercentiles {
+    /// The nearest-rank percentiles of `latencies` in ascending order, `None` if it's empty
+    pub fn from_sorted(latencies: &[u64]) -> Option<LatencyPercentiles> {
+        if latencies.is_empty() {
+            return None;
+        }
+        let nearest_rank = |percent: usize| {
+            let rank = (latencies.len() * percent).div_ceil(100).max(1);
+            latencies[rank - 1]
+        };
+        Some(LatencyPercentiles {
+            p50: nearest_rank(50),
+            p95: nearest_rank(95),
+            p99: nearest_rank(99),
+        })
+    }
+}
+
+/// The deposits or the withdrawals found in a window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectionSummary {
+    pub count: u64,
+    pub volume: DepcAmount,
+    /// The average seconds from being found to being made on the other chain, `None` if none
+    /// is made yet
+    pub avg_latency: Option<u64>,
+    /// The end-to-end latency of the confirmed ones, `None` if none is confirmed yet
+    pub confirmation_latency: Option<LatencyPercentiles>,
+    /// The ones not processed yet
+    pub pending: u64,
+}
+
+impl From<db::PendingActionTotals> for DirectionSummary {
+    fn from(totals: db::PendingActionTotals) -> Self {
+        DirectionSummary {
+            count: totals.count,
+            volume: totals.amount,
+            avg_latency: totals.avg_latency.map(|latency| latency.round() as u64),
+            confirmation_latency: None,
+            pending: totals.pending,
+        }
+    }
+}
+
+/// The movements of a bridge since a time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BridgeSummary {
+    pub since: u64,
+    pub deposits: DirectionSummary,
+    pub withdrawals: DirectionSummary,
+    /// The network fees of the payouts, the refunds and the sweeps
+    pub fees: DepcAmount,
+    /// The transactions cannot be processed while syncing
+    pub dead_letters: u64,
+    /// The deposits can never be bridged and are returned
+    pub refunds: u64,
+}
+
+pub fn summarize_bridge(conn: &db::Conn, since: u64) -> Result<BridgeSummary, BridgeError> {
+    let mut summary = BridgeSummary {
+        since,
+        fees: conn.query_ledger_event_total(LedgerEvent::Fee.as_str(), since)?,
+        dead_letters: conn.count_dead_letters_since(since)?,
+        refunds: conn.count_refunds_since(since)?,
+        ..Default::default()
+    };
+    for totals in conn.query_pending_action_totals(since)? {
+        let (action, direction) = match totals.action.as_str() {
+            ACTION_DEPOSIT => (ACTION_DEPOSIT, &mut summary.deposits),
+            ACTION_WITHDRAW => (ACTION_WITHDRAW, &mut summary.withdrawals),
+            _ => continue,
+        };
+        *direction = totals.into();
+        direction.confirmation_latency =
+            LatencyPercentiles::from_sorted(&conn.query_confirmation_latencies(action, since)?);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Ledger;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+    const COIN: u64 = 100_000_000;
+
+    fn depc(value64: u64) -> DepcAmount {
+        DepcAmount::from_value64(value64)
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        assert_eq!(LatencyPercentiles::from_sorted(&[]), None);
+        let latencies: Vec<u64> = (1..=200).collect();
+        assert_eq!(
+            LatencyPercentiles::from_sorted(&latencies),
+            Some(LatencyPercentiles {
+                p50: 100,
+                p95: 190,
+                p99: 198,
+            })
+        );
+    }
+
+    #[test]
+    fn test_summarize_bridge() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        assert_eq!(
+            summarize_bridge(&conn, 0).unwrap(),
+            BridgeSummary::default()
+        );
+
+        // a deposit of 2 days ago is minted in 10 minutes, one of today is minted in 20 and
+        // another is still pending, the withdrawal is confirmed 10 minutes after it's paid
+        let actions = [
+            (ACTION_DEPOSIT, 2, TEST_NOW - 2 * 86400, Some(600)),
+            (ACTION_DEPOSIT, 3, TEST_NOW - 3600, Some(1200)),
+            (ACTION_DEPOSIT, 4, TEST_NOW - 60, None),
+            (ACTION_WITHDRAW, 5, TEST_NOW - 1800, Some(300)),
+        ];
+        for (i, (action, coins, timestamp, latency)) in actions.into_iter().enumerate() {
+            let id = conn
+                .add_pending_action(
+                    action,
+                    &format!("{:064x}", i),
+                    "recipient",
+                    depc(coins * COIN),
+                    timestamp,
+                )
+                .unwrap();
+            if let Some(latency) = latency {
+                conn.complete_pending_action(id, Some("txid"), timestamp + latency)
+                    .unwrap();
+                let confirmation = if action == ACTION_WITHDRAW { 600 } else { 0 };
+                conn.confirm_pending_action(id, timestamp + latency + confirmation)
+                    .unwrap();
+            }
+        }
+        let ledger = Ledger::new(conn.clone());
+        ledger
+            .payout("payout", depc(5 * COIN), depc(100_000), TEST_NOW - 1500)
+            .unwrap();
+        ledger
+            .refund("refund", depc(COIN), depc(20_000), TEST_NOW - 3 * 86400)
+            .unwrap();
+        conn.add_refund(
+            "deposit",
+            0,
+            "address",
+            depc(COIN),
+            "invalid recipient",
+            TEST_NOW - 3 * 86400,
+        )
+        .unwrap();
+        conn.add_dead_letter(860130, "txid", Some(1), "invalid", TEST_NOW - 600)
+            .unwrap();
+
+        let summary = summarize_bridge(&conn, TEST_NOW - 86400).unwrap();
+        assert_eq!(
+            summary.deposits,
+            DirectionSummary {
+                count: 2,
+                volume: depc(7 * COIN),
+                avg_latency: Some(1200),
+                confirmation_latency: Some(LatencyPercentiles {
+                    p50: 1200,
+                    p95: 1200,
+                    p99: 1200,
+                }),
+                pending: 1,
+            }
+        );
+        assert_eq!(summary.withdrawals.count, 1);
+        assert_eq!(summary.withdrawals.avg_latency, Some(300));
+        assert_eq!(
+            summary
+                .withdrawals
+                .confirmation_latency
+                .map(|latency| latency.p99),
+            Some(900)
+        );
+        assert_eq!(summary.fees, depc(100_000));
+        assert_eq!(summary.dead_letters, 1);
+        assert_eq!(summary.refunds, 0);
+
+        let summary = summarize_bridge(&conn, TEST_NOW - 7 * 86400).unwrap();
+        assert_eq!(summary.deposits.count, 3);
+        assert_eq!(summary.deposits.avg_latency, Some(900));
+        assert_eq!(
+            summary.deposits.confirmation_latency,
+            Some(LatencyPercentiles {
+                p50: 600,
+                p95: 1200,
+                p99: 1200,
+            })
+        );
+        assert_eq!(summary.fees, depc(120_000));
+        assert_eq!(summary.refunds, 1);
+
+        // the other bridges are summarized on their own
+        let other = conn.scoped("other");
+        assert_eq!(
+            summarize_bridge(&other, 0).unwrap(),
+            BridgeSummary::default()
+        );
+    }
+}
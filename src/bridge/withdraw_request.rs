@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::solana::verify_offchain_signature;
+use crate::BridgeError;
+
+/// The origin of the withdrawals requested by `POST /bridge/withdraw` instead of a DePC
+/// transaction
+pub const WITHDRAW_ORIGIN_OFFCHAIN: &str = "offchain";
+/// The states of table `withdraw_requests`
+pub const WITHDRAW_REQUEST_PENDING: &str = "pending";
+pub const WITHDRAW_REQUEST_ACCEPTED: &str = "accepted";
+pub const WITHDRAW_REQUEST_REJECTED: &str = "rejected";
+
+/// A withdrawal requested off-chain, the tokens `signer` transferred to the owner by the Solana
+/// transaction `signature` are paid out to the DePC address `recipient`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawRequest {
+    pub signature: Signature,
+    pub recipient: String,
+    pub signer: Pubkey,
+}
+
+/// The message the Solana key signs to request the withdrawal, it binds the transaction to the
+/// recipient
+pub fn make_withdraw_request_message(signature: &str, recipient: &str) -> String {
+    format!("depc-bridge withdraw {signature} to {recipient}")
+}
+
+/// Check `message_signature` is made by `signer` for the message of the request, either signed
+/// as it is or as an off-chain message. The tokens are checked to be sent by `signer` when the
+/// request is processed
+pub fn verify_withdraw_request(
+    signature: &str,
+    recipient: &str,
+    signer: &str,
+    message_signature: &str,
+) -> Result<WithdrawRequest, BridgeError> {
+    let parsed_signature = Signature::from_str(signature).map_err(|_| {
+        BridgeError::InvalidWithdrawRequest(format!(
+            "cannot parse signature from string '{signature}'"
+        ))
+    })?;
+    if recipient.is_empty() {
+        return Err(BridgeError::InvalidWithdrawRequest(
+            "'recipient' is empty".to_owned(),
+        ));
+    }
+    let parsed_signer =
+        Pubkey::from_str(signer).map_err(|_| BridgeError::InvalidAddress(signer.to_owned()))?;
+    let parsed_message_signature = Signature::from_str(message_signature).map_err(|_| {
+        BridgeError::InvalidWithdrawRequest(format!(
+            "cannot parse signature from string '{message_signature}'"
+        ))
+    })?;
+    let message = make_withdraw_request_message(signature, recipient);
+    if !verify_offchain_signature(&parsed_signer, &message, &parsed_message_signature) {
+        return Err(BridgeError::InvalidWithdrawRequest(format!(
+            "the message is not signed by {signer}"
+        )));
+    }
+    Ok(WithdrawRequest {
+        signature: parsed_signature,
+        recipient: recipient.to_owned(),
+        signer: parsed_signer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signer::{keypair::keypair_from_seed, Signer};
+
+    use super::*;
+
+    const TEST_RECIPIENT: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+
+    #[test]
+    fn test_verify_withdraw_request() {
+        let keypair = keypair_from_seed(&[7; 32]).unwrap();
+        let signer = keypair.pubkey().to_string();
+        let signature = Signature::new_unique().to_string();
+        let message = make_withdraw_request_message(&signature, TEST_RECIPIENT);
+        let message_signature = keypair.sign_message(message.as_bytes()).to_string();
+
+        let request =
+            verify_withdraw_request(&signature, TEST_RECIPIENT, &signer, &message_signature)
+                .unwrap();
+        assert_eq!(request.signature.to_string(), signature);
+        assert_eq!(request.signer, keypair.pubkey());
+
+        // the message signed for another recipient or by another key
+        assert!(verify_withdraw_request(
+            &signature,
+            "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ",
+            &signer,
+            &message_signature
+        )
+        .is_err());
+        let other = keypair_from_seed(&[8; 32]).unwrap().pubkey().to_string();
+        assert!(
+            verify_withdraw_request(&signature, TEST_RECIPIENT, &other, &message_signature)
+                .is_err()
+        );
+        assert!(
+            verify_withdraw_request("invalid", TEST_RECIPIENT, &signer, &message_signature)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// The percentages (0.0 ~ 1.0) of the RPC calls those are dropped, delayed or corrupted
+///
+/// It's parsed from a string like `drop=0.1,delay=0.2,delay_ms=500,corrupt=0.05`, the absent
+/// keys are zero
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultConfig {
+    pub drop_rate: f64,
+    pub delay_rate: f64,
+    pub delay: Duration,
+    pub corrupt_rate: f64,
+}
+
+impl FromStr for FaultConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = FaultConfig::default();
+        for item in s
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+        {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("invalid fault item `{item}`"))?;
+            let parse_rate = |value: &str| -> Result<f64, String> {
+                match value.parse::<f64>() {
+                    Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+                    _ => Err(format!("invalid rate `{value}` of `{key}`")),
+                }
+            };
+            match key {
+                "drop" => config.drop_rate = parse_rate(value)?,
+                "delay" => config.delay_rate = parse_rate(value)?,
+                "corrupt" => config.corrupt_rate = parse_rate(value)?,
+                "delay_ms" => {
+                    config.delay = Duration::from_millis(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid milliseconds `{value}`"))?,
+                    )
+                }
+                _ => return Err(format!("unknown fault `{key}`")),
+            }
+        }
+        if config.drop_rate + config.delay_rate + config.corrupt_rate > 1.0 {
+            return Err("the sum of the rates should not exceed 1.0".to_owned());
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// The request is never sent, an error is returned instead
+    Drop,
+    /// The request is sent after the delay
+    Delay(Duration),
+    /// The response is replaced by a broken one
+    Corrupt,
+}
+
+/// Decides which fault is injected into each RPC call, all clones share the same random source
+#[derive(Clone)]
+pub struct FaultInjector {
+    config: FaultConfig,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl FaultInjector {
+    pub fn new(config: FaultConfig) -> FaultInjector {
+        FaultInjector {
+            config,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// The same seed injects the same sequence of faults, so a failing run can be replayed
+    pub fn with_seed(config: FaultConfig, seed: u64) -> FaultInjector {
+        FaultInjector {
+            config,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    pub fn next_fault(&self) -> Option<Fault> {
+        let roll: f64 = self.rng.lock().unwrap().gen();
+        let config = &self.config;
+        if roll < config.drop_rate {
+            Some(Fault::Drop)
+        } else if roll < config.drop_rate + config.corrupt_rate {
+            Some(Fault::Corrupt)
+        } else if roll < config.drop_rate + config.corrupt_rate + config.delay_rate {
+            Some(Fault::Delay(config.delay))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fault_config() {
+        let config: FaultConfig = "drop=0.1, delay=0.2,delay_ms=500,corrupt=0.05"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            config,
+            FaultConfig {
+                drop_rate: 0.1,
+                delay_rate: 0.2,
+                delay: Duration::from_millis(500),
+                corrupt_rate: 0.05,
+            }
+        );
+        assert_eq!("".parse::<FaultConfig>().unwrap(), FaultConfig::default());
+        assert!("drop=1.5".parse::<FaultConfig>().is_err());
+        assert!("drop=0.6,corrupt=0.6".parse::<FaultConfig>().is_err());
+        assert!("explode=0.1".parse::<FaultConfig>().is_err());
+    }
+
+    #[test]
+    fn test_next_fault() {
+        let always_drop = FaultInjector::new("drop=1".parse().unwrap());
+        assert_eq!(always_drop.next_fault(), Some(Fault::Drop));
+        let never = FaultInjector::new(FaultConfig::default());
+        assert!((0..100).all(|_| never.next_fault().is_none()));
+
+        let config: FaultConfig = "drop=0.3,delay=0.3,corrupt=0.3".parse().unwrap();
+        let a = FaultInjector::with_seed(config.clone(), 7);
+        let b = FaultInjector::with_seed(config, 7);
+        let faults_a: Vec<_> = (0..100).map(|_| a.next_fault()).collect();
+        let faults_b: Vec<_> = (0..100).map(|_| b.next_fault()).collect();
+        assert_eq!(faults_a, faults_b);
+        assert!(faults_a.contains(&Some(Fault::Drop)));
+        assert!(faults_a.contains(&None));
+    }
+}
@@ -0,0 +1,3 @@
+mod injector;
+
+pub use injector::*;
@@ -0,0 +1,688 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::{Method, RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use solana_sdk::transaction::Transaction;
+
+use super::Error;
+use crate::analysis::ClusterReport;
+use crate::rest::{
+    BalanceResponse, ErrorDetail, ErrorResponse, ReqDepositAddress, ReqDepositIntent,
+    ReqMultisigSignature, ReqParameterChange, ReqRefundClaim, ReqResolveCustodyInflow,
+    ReqUnwatchAddress, ReqWatchAddress, ReqWithdrawRequest, RespAddressCluster, RespBalanceHistory,
+    RespBlockDigests, RespBlockSummaries, RespBlockSummary, RespBridgeEvents, RespBridgeFees,
+    RespBridgeInfo, RespBridgeParameters, RespBridgeSummary, RespBridgeVolume, RespCatchUp,
+    RespCatchUpPlan, RespCustodyInflow, RespCustodyInflows, RespDepositAddress, RespDepositIntent,
+    RespDepositProof, RespDepositRoot, RespExchangeAddressList, RespExchangeAddresses,
+    RespExchangeBalanceByDate, RespHeldTransfer, RespHeldTransfers, RespJobs, RespMiner,
+    RespMiners, RespMultisigPayouts, RespMultisigSignature, RespParameterChange,
+    RespParameterChanges, RespRefund, RespReloadableSettings, RespRichlist, RespSanityReport,
+    RespSolanaTransaction, RespSweepFees, RespTransferFees, RespWatchEvents, RespWatchlist,
+    RespWithdrawRequest, TransactionDetail, UploadTransactionResponse,
+};
+
+const HEADER_IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The client of the REST API of the bridge, a typed function for each endpoint
+///
+/// The requests and the responses are the types of `rest` the server serializes, so they're
+/// always the ones of the server of the same version. The POST requests given an idempotency
+/// key can be retried, the server replays the response of the first one.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    /// The endpoint, under `/bridges/{bridge_id}` when the bridge is given
+    base_url: String,
+    admin_token: Option<String>,
+}
+
+pub struct ClientBuilder {
+    endpoint: String,
+    bridge_id: Option<String>,
+    admin_token: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            endpoint: "http://127.0.0.1:3000".to_owned(),
+            bridge_id: None,
+            admin_token: None,
+            timeout: None,
+        }
+    }
+
+    pub fn set_endpoint(mut self, endpoint: &str) -> ClientBuilder {
+        self.endpoint = endpoint.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// The endpoints of the bridge are called under `/bridges/{bridge_id}`, the ones of the
+    /// default bridge are called at the root unless it's set
+    pub fn set_bridge_id(mut self, bridge_id: &str) -> ClientBuilder {
+        self.bridge_id = Some(bridge_id.to_owned());
+        self
+    }
+
+    /// The token is sent only to the admin endpoints (`/admin/*`)
+    pub fn set_admin_token(mut self, token: &str) -> ClientBuilder {
+        self.admin_token = Some(token.to_owned());
+        self
+    }
+
+    /// The requests are given up after `timeout`, they wait for the server as long as it takes
+    /// by default
+    pub fn set_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let mut http = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        let base_url = match self.bridge_id {
+            Some(bridge_id) => format!("{}/bridges/{}", self.endpoint, bridge_id),
+            None => self.endpoint,
+        };
+        Ok(Client {
+            http: http.build()?,
+            base_url,
+            admin_token: self.admin_token,
+        })
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// The exchange addresses found from the inputs of `txid`
+    pub async fn get_exchange_addresses(
+        &self,
+        txid: &str,
+        depth: Option<u32>,
+        max_addresses: Option<usize>,
+    ) -> Result<RespExchangeAddresses, Error> {
+        let query = [
+            ("depth", depth.map(|depth| depth.to_string())),
+            (
+                "max_addresses",
+                max_addresses.map(|max_addresses| max_addresses.to_string()),
+            ),
+        ];
+        self.get(&format!("/exchange/analyze/{txid}"), &query).await
+    }
+
+    /// A page of the exchange addresses after `cursor`, the next page is after `next_cursor`
+    pub async fn get_exchange_address_list(
+        &self,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<RespExchangeAddressList, Error> {
+        let query = [
+            ("cursor", cursor.map(str::to_owned)),
+            ("limit", limit.map(|limit| limit.to_string())),
+        ];
+        self.get("/exchange/addresses", &query).await
+    }
+
+    /// The balances of the exchange addresses every `days` days by the date, the settings of
+    /// the report on the server are used for the parameters not given
+    pub async fn generate_exchange_balances(
+        &self,
+        days: u64,
+        start_height: Option<u32>,
+        block_interval: Option<u64>,
+        timezone: Option<&str>,
+    ) -> Result<HashMap<String, RespExchangeBalanceByDate>, Error> {
+        let query = [
+            (
+                "start_height",
+                start_height.map(|height| height.to_string()),
+            ),
+            (
+                "block_interval",
+                block_interval.map(|interval| interval.to_string()),
+            ),
+            ("timezone", timezone.map(str::to_owned)),
+        ];
+        self.get(&format!("/exchange/balances/{days}"), &query)
+            .await
+    }
+
+    pub async fn post_rebuild_clusters(
+        &self,
+        idempotency_key: Option<&str>,
+    ) -> Result<ClusterReport, Error> {
+        self.post("/exchange/clusters", None, idempotency_key).await
+    }
+
+    pub async fn get_address_cluster(&self, address: &str) -> Result<RespAddressCluster, Error> {
+        self.get(&format!("/exchange/clusters/{address}"), &[])
+            .await
+    }
+
+    /// The balances of `addresses` at every `step` seconds from `from` to `to`, the fiat values
+    /// are reported in `currency` when it's given
+    pub async fn get_depc_balance_history(
+        &self,
+        addresses: &[&str],
+        from: Option<u64>,
+        to: Option<u64>,
+        step: Option<u64>,
+        currency: Option<&str>,
+    ) -> Result<RespBalanceHistory, Error> {
+        let query = [
+            ("addresses", Some(addresses.join(","))),
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+            ("step", step.map(|step| step.to_string())),
+            ("currency", currency.map(str::to_owned)),
+        ];
+        self.get("/depc/balance-history", &query).await
+    }
+
+    pub async fn get_depc_richlist(&self, limit: Option<u32>) -> Result<RespRichlist, Error> {
+        let query = [("limit", limit.map(|limit| limit.to_string()))];
+        self.get("/depc/richlist", &query).await
+    }
+
+    pub async fn get_depc_block(&self, height_or_hash: &str) -> Result<RespBlockSummary, Error> {
+        self.get(&format!("/depc/block/{height_or_hash}"), &[])
+            .await
+    }
+
+    pub async fn get_depc_blocks(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+    ) -> Result<RespBlockSummaries, Error> {
+        let query = [
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+        ];
+        self.get("/depc/blocks", &query).await
+    }
+
+    /// The miners of the blocks from `from` to `to` (heights)
+    pub async fn get_depc_miners(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<RespMiners, Error> {
+        let query = [
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+            ("limit", limit.map(|limit| limit.to_string())),
+        ];
+        self.get("/depc/miners", &query).await
+    }
+
+    /// The blocks of `miner`, by the day from `from` to `to` (times)
+    pub async fn get_depc_miner(
+        &self,
+        miner: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<RespMiner, Error> {
+        let query = [
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+        ];
+        self.get(&format!("/depc/miners/{miner}"), &query).await
+    }
+
+    pub async fn post_deposit_intent(
+        &self,
+        request: &ReqDepositIntent,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespDepositIntent, Error> {
+        self.post(
+            "/bridge/deposit-intents",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_deposit_intent(&self, id: u64) -> Result<RespDepositIntent, Error> {
+        self.get(&format!("/bridge/deposit-intents/{id}"), &[])
+            .await
+    }
+
+    pub async fn post_deposit_address(
+        &self,
+        request: &ReqDepositAddress,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespDepositAddress, Error> {
+        self.post(
+            "/bridge/deposit-addresses",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_deposit_address(&self, address: &str) -> Result<RespDepositAddress, Error> {
+        self.get(&format!("/bridge/deposit-addresses/{address}"), &[])
+            .await
+    }
+
+    /// The latest deposit root, it's signed when the server has an attestor
+    pub async fn get_deposit_root(&self) -> Result<RespDepositRoot, Error> {
+        self.get("/bridge/deposit-root", &[]).await
+    }
+
+    pub async fn get_deposit_proof(&self, depc_txid: &str) -> Result<RespDepositProof, Error> {
+        self.get(&format!("/bridge/proof/{depc_txid}"), &[]).await
+    }
+
+    /// The events after `since_seq`, the next page is after `last_seq`
+    pub async fn get_bridge_events(
+        &self,
+        since_seq: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<RespBridgeEvents, Error> {
+        let query = [
+            ("since_seq", since_seq.map(|seq| seq.to_string())),
+            ("limit", limit.map(|limit| limit.to_string())),
+        ];
+        self.get("/bridge/events", &query).await
+    }
+
+    /// The status of the bridge, it's signed when the server has an attestor
+    pub async fn get_bridge_info(&self) -> Result<RespBridgeInfo, Error> {
+        self.get("/bridge/info", &[]).await
+    }
+
+    /// The summary of the bridge, it's signed when the server has an attestor
+    pub async fn get_bridge_summary(&self) -> Result<RespBridgeSummary, Error> {
+        self.get("/bridge/summary", &[]).await
+    }
+
+    pub async fn get_bridge_volume(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+        currency: Option<&str>,
+    ) -> Result<RespBridgeVolume, Error> {
+        let query = [
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+            ("currency", currency.map(str::to_owned)),
+        ];
+        self.get("/bridge/stats/volume", &query).await
+    }
+
+    pub async fn get_bridge_fees(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<RespBridgeFees, Error> {
+        let query = [
+            ("from", from.map(|from| from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+        ];
+        self.get("/bridge/fees", &query).await
+    }
+
+    pub async fn get_bridge_catch_up(&self) -> Result<RespCatchUp, Error> {
+        self.get("/bridge/catch-up", &[]).await
+    }
+
+    pub async fn get_bridge_parameters(&self) -> Result<RespBridgeParameters, Error> {
+        self.get("/bridge/parameters", &[]).await
+    }
+
+    pub async fn get_parameter_changes(
+        &self,
+        limit: Option<u32>,
+    ) -> Result<RespParameterChanges, Error> {
+        let query = [("limit", limit.map(|limit| limit.to_string()))];
+        self.get("/bridge/parameter-changes", &query).await
+    }
+
+    pub async fn get_block_digests(
+        &self,
+        from: u32,
+        to: Option<u32>,
+    ) -> Result<RespBlockDigests, Error> {
+        let query = [
+            ("from", Some(from.to_string())),
+            ("to", to.map(|to| to.to_string())),
+        ];
+        self.get("/bridge/block-digests", &query).await
+    }
+
+    pub async fn get_transfer_fees(&self, limit: Option<u32>) -> Result<RespTransferFees, Error> {
+        let query = [("limit", limit.map(|limit| limit.to_string()))];
+        self.get("/bridge/fees/charges", &query).await
+    }
+
+    pub async fn get_held_transfers(&self, limit: Option<u32>) -> Result<RespHeldTransfers, Error> {
+        let query = [("limit", limit.map(|limit| limit.to_string()))];
+        self.get("/bridge/held-transfers", &query).await
+    }
+
+    pub async fn get_unclassified_inflows(
+        &self,
+        limit: Option<u32>,
+    ) -> Result<RespCustodyInflows, Error> {
+        let query = [("limit", limit.map(|limit| limit.to_string()))];
+        self.get("/bridge/unclassified-inflows", &query).await
+    }
+
+    pub async fn post_bridge_withdraw(
+        &self,
+        request: &ReqWithdrawRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespWithdrawRequest, Error> {
+        self.post(
+            "/bridge/withdraw",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_bridge_withdraw(&self, id: u64) -> Result<RespWithdrawRequest, Error> {
+        self.get(&format!("/bridge/withdraw/{id}"), &[]).await
+    }
+
+    pub async fn post_refund_claim(
+        &self,
+        request: &ReqRefundClaim,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespRefund, Error> {
+        self.post(
+            "/bridge/refunds/claims",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_refund(&self, depc_txid: &str, vout: u32) -> Result<RespRefund, Error> {
+        self.get(&format!("/bridge/refunds/{depc_txid}/{vout}"), &[])
+            .await
+    }
+
+    pub async fn get_multisig_payouts(&self) -> Result<RespMultisigPayouts, Error> {
+        self.get("/bridge/multisig/payouts", &[]).await
+    }
+
+    pub async fn post_multisig_signature(
+        &self,
+        payout_id: u64,
+        request: &ReqMultisigSignature,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespMultisigSignature, Error> {
+        self.post(
+            &format!("/bridge/multisig/payouts/{payout_id}/signatures"),
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    /// The balances of `addresses` in the order, the error is in place of the balance cannot
+    /// be read
+    pub async fn get_solana_balance(
+        &self,
+        addresses: &[&str],
+    ) -> Result<Vec<Result<BalanceResponse, ErrorDetail>>, Error> {
+        const PATH: &str = "/solana/balance";
+        let query = [("address", Some(addresses.join(",")))];
+        let balances: Vec<Value> = self.get(PATH, &query).await?;
+        balances
+            .into_iter()
+            .map(|balance| match parse_error(&balance) {
+                Some(error) => Ok(Err(error)),
+                None => serde_json::from_value(balance)
+                    .map(Ok)
+                    .map_err(|e| Error::InvalidResponse(PATH.to_owned(), e.to_string())),
+            })
+            .collect()
+    }
+
+    pub async fn get_solana_history(
+        &self,
+        addresses: &[&str],
+    ) -> Result<Vec<TransactionDetail>, Error> {
+        let query = [("address", Some(addresses.join(",")))];
+        self.get("/solana/history", &query).await
+    }
+
+    pub async fn get_solana_transaction(
+        &self,
+        signature: &str,
+    ) -> Result<RespSolanaTransaction, Error> {
+        self.get(&format!("/solana/tx/{signature}"), &[]).await
+    }
+
+    /// Relay the signed `transaction`, its signature is in `result`
+    pub async fn post_solana_transaction(
+        &self,
+        transaction: &Transaction,
+        idempotency_key: Option<&str>,
+    ) -> Result<UploadTransactionResponse, Error> {
+        let raw_tx =
+            bincode::serialize(transaction).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        self.post(
+            "/solana/post_tx",
+            Some(Value::String(base64::encode(raw_tx))),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_jobs(&self) -> Result<RespJobs, Error> {
+        self.get("/admin/jobs", &[]).await
+    }
+
+    pub async fn get_sanity(&self, stuck_after: Option<u64>) -> Result<RespSanityReport, Error> {
+        let query = [("stuck_after", stuck_after.map(|secs| secs.to_string()))];
+        self.get("/admin/sanity", &query).await
+    }
+
+    pub async fn post_reload_settings(
+        &self,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespReloadableSettings, Error> {
+        self.post("/admin/settings/reload", None, idempotency_key)
+            .await
+    }
+
+    pub async fn get_watchlist(&self) -> Result<RespWatchlist, Error> {
+        self.get("/admin/watchlist", &[]).await
+    }
+
+    /// Watch an address, the watchlist after it is returned
+    pub async fn post_watch_address(
+        &self,
+        request: &ReqWatchAddress,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespWatchlist, Error> {
+        self.post(
+            "/admin/watchlist",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    /// Stop watching an address, the watchlist after it is returned
+    pub async fn post_unwatch_address(
+        &self,
+        request: &ReqUnwatchAddress,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespWatchlist, Error> {
+        self.post(
+            "/admin/watchlist/remove",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn get_watch_events(
+        &self,
+        address: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<RespWatchEvents, Error> {
+        let query = [
+            ("address", address.map(str::to_owned)),
+            ("limit", limit.map(|limit| limit.to_string())),
+        ];
+        self.get("/admin/watchlist/events", &query).await
+    }
+
+    pub async fn post_parameter_change(
+        &self,
+        request: &ReqParameterChange,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespParameterChange, Error> {
+        self.post(
+            "/admin/parameter-changes",
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn post_cancel_parameter_change(
+        &self,
+        id: u64,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespParameterChange, Error> {
+        self.post(
+            &format!("/admin/parameter-changes/{id}/cancel"),
+            None,
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn post_sweep_fees(
+        &self,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespSweepFees, Error> {
+        self.post("/admin/sweep-fees", None, idempotency_key).await
+    }
+
+    pub async fn post_confirm_catch_up(
+        &self,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespCatchUpPlan, Error> {
+        self.post("/admin/catch-up/confirm", None, idempotency_key)
+            .await
+    }
+
+    pub async fn post_resolve_inflow(
+        &self,
+        id: u64,
+        request: &ReqResolveCustodyInflow,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespCustodyInflow, Error> {
+        self.post(
+            &format!("/admin/unclassified-inflows/{id}/resolve"),
+            Some(make_body(request)?),
+            idempotency_key,
+        )
+        .await
+    }
+
+    pub async fn post_release_held_transfer(
+        &self,
+        id: u64,
+        idempotency_key: Option<&str>,
+    ) -> Result<RespHeldTransfer, Error> {
+        self.post(
+            &format!("/admin/held-transfers/{id}/release"),
+            None,
+            idempotency_key,
+        )
+        .await
+    }
+
+    /// The parameters without the value are not sent
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, Option<String>)],
+    ) -> Result<T, Error> {
+        let query: Vec<(&str, &str)> = query
+            .iter()
+            .filter_map(|(name, value)| value.as_deref().map(|value| (*name, value)))
+            .collect();
+        let response = self.request(Method::GET, path).query(&query).send().await?;
+        read_response(path, response).await
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: Option<Value>,
+        idempotency_key: Option<&str>,
+    ) -> Result<T, Error> {
+        let mut request = self.request(Method::POST, path);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        if let Some(key) = idempotency_key {
+            request = request.header(HEADER_IDEMPOTENCY_KEY, key);
+        }
+        read_response(path, request.send().await?).await
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.admin_token {
+            Some(token) if path.starts_with("/admin/") => request.bearer_auth(token),
+            _ => request,
+        }
+    }
+}
+
+fn make_body<B: Serialize>(body: &B) -> Result<Value, Error> {
+    serde_json::to_value(body).map_err(|e| Error::InvalidRequest(e.to_string()))
+}
+
+/// The body is checked for the error before it's parsed, some endpoints reject the requests
+/// with status 200
+async fn read_response<T: DeserializeOwned>(path: &str, response: Response) -> Result<T, Error> {
+    let status = response.status();
+    let body = response.bytes().await?;
+    let value: Value = serde_json::from_slice(&body)
+        .map_err(|e| Error::InvalidResponse(path.to_owned(), format!("status {status}, {e}")))?;
+    if let Some(error) = parse_error(&value) {
+        return Err(Error::Rejected {
+            status: status.as_u16(),
+            code: error.error_code,
+            message: error.message,
+        });
+    }
+    serde_json::from_value(value)
+        .map_err(|e| Error::InvalidResponse(path.to_owned(), e.to_string()))
+}
+
+/// No response has an object `error` other than the errors
+fn parse_error(value: &Value) -> Option<ErrorDetail> {
+    if !value["error"].is_object() {
+        return None;
+    }
+    serde_json::from_value::<ErrorResponse>(value.clone())
+        .ok()
+        .map(|response| response.error)
+}
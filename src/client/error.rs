@@ -0,0 +1,30 @@
+use crate::ErrorCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The server cannot be reached or it doesn't answer in time
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server refuses the request, some endpoints answer the errors with status 200
+    #[error("{code} (status {status}): {message}")]
+    Rejected {
+        status: u16,
+        code: ErrorCode,
+        message: String,
+    },
+    /// The response is not the one of the endpoint, e.g. the server is of another version
+    #[error("invalid response of `{0}`, reason: {1}")]
+    InvalidResponse(String, String),
+    #[error("invalid request, reason: {0}")]
+    InvalidRequest(String),
+}
+
+impl Error {
+    /// The code the server rejects the request with
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::Rejected { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod client;
+mod error;
+
+pub use client::*;
+pub use error::Error;
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The source of the current time, it's injected into the components those make timestamps so
+/// the time-dependent logic can be driven by a controllable clock from the tests
+pub trait Clock: Send + Sync {
+    /// Seconds since the unix epoch
+    fn now(&self) -> u64;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The clock reads the system time
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_now() {
+        let timestamp = SystemClock.now();
+        assert!(timestamp > 0);
+    }
+}
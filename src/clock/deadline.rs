@@ -0,0 +1,126 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    static DEADLINE: Deadline;
+}
+
+/// The time the work of a REST request or a job must be done by, the database queries and the
+/// RPC calls made under it are given the time left instead of their own timeouts, so a request
+/// answered in 5 seconds doesn't leave a chain scan running for minutes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+/// The work is given up since its deadline is passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("the deadline is exceeded")]
+pub struct DeadlineExceeded;
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline(Instant::now() + timeout)
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// The time left, `None` once it's passed
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_none()
+    }
+
+    /// The deadline of the running request or job, `None` outside of them
+    ///
+    /// It's kept by the task, the closures run by `spawn_blocking` or on other threads have to
+    /// be wrapped by `sync_scope` to see it.
+    pub fn current() -> Option<Deadline> {
+        DEADLINE.try_with(|deadline| *deadline).ok()
+    }
+
+    /// Run `f` under the deadline, the earlier deadline set already is kept
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        DEADLINE.scope(self.within_current(), f).await
+    }
+
+    /// Run the blocking `f` under the deadline, the earlier deadline set already is kept
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        DEADLINE.sync_scope(self.within_current(), f)
+    }
+
+    /// The timeout of a call made now, `timeout` is cut to the time left before the current
+    /// deadline
+    pub fn cap_timeout(timeout: Option<Duration>) -> Result<Option<Duration>, DeadlineExceeded> {
+        let Some(deadline) = Deadline::current() else {
+            return Ok(timeout);
+        };
+        let remaining = deadline.remaining().ok_or(DeadlineExceeded)?;
+        Ok(Some(
+            timeout.map_or(remaining, |timeout| timeout.min(remaining)),
+        ))
+    }
+
+    /// The earlier of the deadline and the current one
+    pub fn within_current(self) -> Deadline {
+        Deadline::current().map_or(self, |current| current.min(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deadline_scope() {
+        assert_eq!(Deadline::current(), None);
+        assert_eq!(Deadline::cap_timeout(None), Ok(None));
+
+        let deadline = Deadline::after(Duration::from_secs(5));
+        deadline
+            .scope(async {
+                assert_eq!(Deadline::current(), Some(deadline));
+                let timeout = Deadline::cap_timeout(Some(Duration::from_secs(60)))
+                    .unwrap()
+                    .unwrap();
+                assert!(timeout <= Duration::from_secs(5));
+                assert_eq!(
+                    Deadline::cap_timeout(Some(Duration::from_secs(1))),
+                    Ok(Some(Duration::from_secs(1)))
+                );
+
+                // the nested deadline cannot be later
+                Deadline::after(Duration::from_secs(60))
+                    .sync_scope(|| assert_eq!(Deadline::current(), Some(deadline)));
+                let earlier = Deadline::after(Duration::from_secs(1));
+                earlier.sync_scope(|| assert_eq!(Deadline::current(), Some(earlier)));
+
+                // the blocking closures don't see it unless it's set for them
+                let current = tokio::task::spawn_blocking(Deadline::current)
+                    .await
+                    .unwrap();
+                assert_eq!(current, None);
+                let current =
+                    tokio::task::spawn_blocking(move || deadline.sync_scope(Deadline::current))
+                        .await
+                        .unwrap();
+                assert_eq!(current, Some(deadline));
+            })
+            .await;
+        assert_eq!(Deadline::current(), None);
+
+        let expired = Deadline::after(Duration::ZERO);
+        assert!(expired.is_expired());
+        expired.sync_scope(|| {
+            assert_eq!(
+                Deadline::cap_timeout(Some(Duration::from_secs(1))),
+                Err(DeadlineExceeded)
+            )
+        });
+    }
+}
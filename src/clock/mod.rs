@@ -0,0 +1,7 @@
+mod clock;
+mod deadline;
+mod slots;
+
+pub use clock::*;
+pub use deadline::*;
+pub use slots::*;
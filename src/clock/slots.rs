@@ -0,0 +1,155 @@
+use rusqlite::OptionalExtension;
+
+use crate::db;
+use crate::solana::SlotSample;
+use crate::BridgeError;
+
+/// The nominal duration of a Solana slot, the slots beyond the samples are extrapolated by it
+pub const NOMINAL_SLOT_DURATION_MS: u64 = 400;
+
+/// The correlation between the Solana slots and the timestamps, built from the samples
+/// recorded to table `solana_slots`
+///
+/// A slot between two samples is interpolated linearly, the ones outside the samples are
+/// extrapolated by [`NOMINAL_SLOT_DURATION_MS`] from the nearest sample, so the samples are
+/// recorded periodically to keep the error small
+#[derive(Clone)]
+pub struct SlotTimeline {
+    conn: db::Conn,
+}
+
+impl SlotTimeline {
+    pub fn new(conn: db::Conn) -> SlotTimeline {
+        SlotTimeline { conn }
+    }
+
+    pub fn record(&self, sample: &SlotSample) -> Result<(), BridgeError> {
+        self.conn
+            .add_solana_slot(sample.slot, sample.epoch, sample.block_time)?;
+        Ok(())
+    }
+
+    /// The slot produced at `time`, `None` when no sample is recorded
+    pub fn slot_at(&self, time: u64) -> Result<Option<u64>, BridgeError> {
+        let (before, after) = self.conn.query_solana_slots_around_time(time)?;
+        Ok(match (before, after) {
+            (Some(before), Some(after)) if after.block_time > before.block_time => Some(
+                before.slot
+                    + after.slot.saturating_sub(before.slot) * (time - before.block_time)
+                        / (after.block_time - before.block_time),
+            ),
+            (Some(before), _) => {
+                Some(before.slot + (time - before.block_time) * 1000 / NOMINAL_SLOT_DURATION_MS)
+            }
+            (None, Some(after)) => Some(
+                after
+                    .slot
+                    .saturating_sub((after.block_time - time) * 1000 / NOMINAL_SLOT_DURATION_MS),
+            ),
+            (None, None) => None,
+        })
+    }
+
+    /// The time `slot` is produced, `None` when no sample is recorded
+    pub fn time_of_slot(&self, slot: u64) -> Result<Option<u64>, BridgeError> {
+        let (before, after) = self.conn.query_solana_slots_around_slot(slot)?;
+        Ok(match (before, after) {
+            (Some(before), Some(after)) if after.slot > before.slot => Some(
+                before.block_time
+                    + after.block_time.saturating_sub(before.block_time) * (slot - before.slot)
+                        / (after.slot - before.slot),
+            ),
+            (Some(before), _) => {
+                Some(before.block_time + (slot - before.slot) * NOMINAL_SLOT_DURATION_MS / 1000)
+            }
+            (None, Some(after)) => Some(
+                after
+                    .block_time
+                    .saturating_sub((after.slot - slot) * NOMINAL_SLOT_DURATION_MS / 1000),
+            ),
+            (None, None) => None,
+        })
+    }
+
+    /// The slot produced at the time of the DePC block at `height`, `None` when the block is
+    /// not indexed or no sample is recorded
+    pub fn slot_at_depc_height(&self, height: u32) -> Result<Option<u64>, BridgeError> {
+        match self.conn.query_block_time_by_height(height).optional()? {
+            Some(time) => self.slot_at(time),
+            None => Ok(None),
+        }
+    }
+
+    /// The height of the DePC chain at the time `slot` is produced
+    pub fn depc_height_at_slot(&self, slot: u64) -> Result<Option<u32>, BridgeError> {
+        match self.time_of_slot(slot)? {
+            Some(time) => Ok(self.conn.query_height_by_time(time)?),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    fn sample(slot: u64, block_time: u64) -> SlotSample {
+        SlotSample {
+            slot,
+            epoch: 796,
+            block_time,
+        }
+    }
+
+    #[test]
+    fn test_convert_between_slots_and_times() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let timeline = SlotTimeline::new(conn.clone());
+        assert_eq!(timeline.slot_at(TEST_NOW).unwrap(), None);
+        assert_eq!(timeline.time_of_slot(1000).unwrap(), None);
+
+        // 2 slots per second between the samples
+        timeline.record(&sample(1000, TEST_NOW)).unwrap();
+        timeline.record(&sample(2200, TEST_NOW + 600)).unwrap();
+        assert_eq!(timeline.slot_at(TEST_NOW).unwrap(), Some(1000));
+        assert_eq!(timeline.slot_at(TEST_NOW + 300).unwrap(), Some(1600));
+        assert_eq!(timeline.time_of_slot(1600).unwrap(), Some(TEST_NOW + 300));
+        assert_eq!(timeline.time_of_slot(2200).unwrap(), Some(TEST_NOW + 600));
+
+        // extrapolated by 2.5 slots per second
+        assert_eq!(timeline.slot_at(TEST_NOW + 610).unwrap(), Some(2225));
+        assert_eq!(timeline.slot_at(TEST_NOW - 10).unwrap(), Some(975));
+        assert_eq!(timeline.time_of_slot(2225).unwrap(), Some(TEST_NOW + 610));
+        assert_eq!(timeline.time_of_slot(975).unwrap(), Some(TEST_NOW - 10));
+        assert_eq!(timeline.slot_at(0).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_convert_between_slots_and_depc_heights() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for (height, time) in [
+            (100, TEST_NOW - 200),
+            (101, TEST_NOW),
+            (102, TEST_NOW + 250),
+        ] {
+            conn.add_block(&format!("hash{height}"), height, "miner", time)
+                .unwrap();
+        }
+        let timeline = SlotTimeline::new(conn.clone());
+        assert_eq!(timeline.slot_at_depc_height(101).unwrap(), None);
+        timeline.record(&sample(1000, TEST_NOW)).unwrap();
+        timeline.record(&sample(2200, TEST_NOW + 600)).unwrap();
+
+        assert_eq!(timeline.slot_at_depc_height(101).unwrap(), Some(1000));
+        assert_eq!(timeline.slot_at_depc_height(102).unwrap(), Some(1500));
+        assert_eq!(timeline.slot_at_depc_height(103).unwrap(), None);
+        assert_eq!(timeline.depc_height_at_slot(1499).unwrap(), Some(101));
+        assert_eq!(timeline.depc_height_at_slot(1500).unwrap(), Some(102));
+        assert_eq!(timeline.depc_height_at_slot(100).unwrap(), None);
+    }
+}
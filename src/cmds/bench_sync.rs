@@ -0,0 +1,18 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct BenchSync {
+    /// The number of synthetic blocks to sync
+    #[arg(long, default_value_t = 1000)]
+    pub blocks: u32,
+    /// The number of transactions of each block
+    #[arg(long, default_value_t = 10)]
+    pub txs_per_block: u32,
+    /// The number of outputs of each transaction
+    #[arg(long, default_value_t = 2)]
+    pub outputs_per_tx: u32,
+    /// The path string to the database the blocks are synced to, the database is in memory if
+    /// it's absent
+    #[arg(long)]
+    pub local_db: Option<String>,
+}
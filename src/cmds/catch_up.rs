@@ -0,0 +1,15 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct CatchUp {
+    /// The path string to local database
+    #[arg(long, default_value = "$HOME/depc-bridge.sqlite3")]
+    pub local_db: String,
+    /// The bridge to report, see `--settings` of `run`
+    #[arg(long, default_value = "default")]
+    pub bridge_id: String,
+    /// Confirm the plan awaiting the confirmation, the running bridge executes the backlog
+    /// after it
+    #[arg(long, default_value_t = false)]
+    pub confirm: bool,
+}
@@ -0,0 +1,28 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct InspectScript {
+    /// The script in hex, e.g. the `scriptPubKey` of the OP_RETURN output
+    #[arg(long, conflicts_with = "txid", required_unless_present = "txid")]
+    pub script_hex: Option<String>,
+    /// The DePC transaction the script is fetched from, the output is given by `--vout`
+    #[arg(long, requires = "vout")]
+    pub txid: Option<String>,
+    #[arg(long)]
+    pub vout: Option<u32>,
+    /// The endpoint (http://ip:port) for depc node
+    #[arg(long, default_value = "http://127.0.0.1:18732")]
+    pub depc_rpc_endpoint: String,
+    /// Use cookie for RPC authentication, set it to false to use user/password
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub depc_rpc_use_cookie: bool,
+    /// The path string to file `.cookie`
+    #[arg(long, default_value = "$HOME/.depinc/testnet3/.cookie")]
+    pub depc_rpc_cookie_path: String,
+    /// The username for RPC authentication
+    #[arg(long, default_value = "")]
+    pub depc_rpc_user: String,
+    /// The password for RPC authentication
+    #[arg(long, default_value = "")]
+    pub depc_rpc_passwd: String,
+}
@@ -1,5 +1,17 @@
+#[cfg(feature = "testing")]
+mod bench_sync;
+mod catch_up;
 mod deploy;
+mod inspect_script;
 mod run;
+mod status;
+mod trial_balance;
 
+#[cfg(feature = "testing")]
+pub use bench_sync::*;
+pub use catch_up::*;
 pub use deploy::*;
+pub use inspect_script::*;
 pub use run::*;
+pub use status::*;
+pub use trial_balance::*;
@@ -2,14 +2,19 @@ use clap::Parser;
 
 #[derive(Parser)]
 pub struct Run {
-    /// The address:port the web service will listen to
+    /// `bridge` syncs the chains and bridges the deposits and withdrawals, `index-only` only
+    /// syncs the chain index and serves the read-only endpoints, no key is needed for it
+    #[arg(long, default_value = "bridge")]
+    pub mode: depc_bridge::bridge::RunMode,
+    /// The address:port the web service will listen to, it's not used when the settings file
+    /// gives the listeners
     #[arg(long, default_value = "127.0.0.1:3000")]
     pub bind: String,
     /// The endpoint (http://ip:port) for depc node
     #[arg(long, default_value = "http://127.0.0.1:18732")]
     pub depc_rpc_endpoint: String,
-    /// Use cookie for RPC authentication
-    #[arg(long, default_value_t = true)]
+    /// Use cookie for RPC authentication, set it to false to use user/password
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
     pub depc_rpc_use_cookie: bool,
     /// The path string to file `.cookie`
     #[arg(long, default_value = "$HOME/.depinc/testnet3/.cookie")]
@@ -23,22 +28,279 @@ pub struct Run {
     /// Use proxy for the connection of RPC
     #[arg(long, default_value_t = false)]
     pub depc_rpc_use_proxy: bool,
+    /// The seconds a call to the DePC node is given up after
+    #[arg(long, default_value_t = 60)]
+    pub depc_rpc_timeout: u64,
+    /// The custody address, the bridge starts a new custody era when it's changed
     #[arg(long)]
     pub depc_owner_address: String,
+    /// The seconds the old custody address is still watched for after the custody address of
+    /// a bridge is changed, the payouts are made only from the new address
+    #[arg(long, default_value_t = 604800)]
+    pub custody_overlap: u64,
+    /// The public keys (hex) of the multisig script, in the order of the script, the custody
+    /// address must be the P2SH address of it. The wallet of the node holds one of the keys, the
+    /// payouts collect the signatures of the co-signers through `/bridge/multisig/payouts`
+    #[arg(long, value_delimiter = ',')]
+    pub custody_multisig_keys: Vec<String>,
+    /// The signatures required to spend the coins of the multisig custody
+    #[arg(long, default_value_t = 2)]
+    pub custody_multisig_required: usize,
+    /// Import the custody addresses to the wallet of the DePC node as watch-only when the bridge
+    /// starts, by `address` (`importaddress`) or `descriptor` (`importdescriptors`), their coins
+    /// are cross-checked with the node then. Nothing is imported if it's absent
+    #[arg(long)]
+    pub watch_only_import: Option<depc_bridge::bridge::WatchOnlyImport>,
+    /// Let the node rescan the blocks for the coins of the imported custody addresses, it takes
+    /// a while on a long chain, raise `--depc-rpc-timeout` for it
+    #[arg(long, default_value_t = false)]
+    pub watch_only_rescan: bool,
+    /// The interval (in seconds) to cross-check the coins of the imported custody addresses with
+    /// the wallet of the node
+    #[arg(long, default_value_t = 3600)]
+    pub custody_check_interval: u64,
     #[arg(long)]
     pub solana_owner_address: String,
     /// The endpoint string should be used for establishing connection to solana node
     #[arg(long, default_value = "https://api.devnet.solana.com")]
     pub sol_endpoint: String,
-    /// The authority private key for manipulate spl-token from sonala network
+    /// The authority private key for manipulate spl-token from sonala network, it's required
+    /// unless the mode is `index-only`
     #[arg(long)]
-    pub sol_authority_key: String,
+    pub sol_authority_key: Option<String>,
     /// The mint address of the spl-token
     #[arg(long)]
     pub sol_mint_pubkey: String,
+    /// The seconds a REST request is given, the calls to solana and the database queries it
+    /// makes are cut off and the request is answered with 504 after it, the bridge itself waits
+    /// for the node longer
+    #[arg(long, default_value_t = 10)]
+    pub rest_upstream_timeout: u64,
+    /// The seconds the balances and the analyzed transactions from solana are cached for
+    #[arg(long, default_value_t = 10)]
+    pub sol_cache_ttl: u64,
+    /// The number of the threads fetch the transactions from solana concurrently
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=32))]
+    pub sol_fetch_concurrency: u16,
+    /// The calls can be made to solana per second by the bridge and the REST queries together,
+    /// the calls are not limited if it's absent
+    #[arg(long)]
+    pub sol_rpc_rate: Option<u32>,
+    /// The percent of `--sol-rpc-rate` kept for the calls of the bridge, the REST queries wait
+    /// or are shed when only the reserved calls are left
+    #[arg(long, default_value_t = 25, value_parser = clap::value_parser!(u32).range(0..=100))]
+    pub sol_rpc_reserve: u32,
+    /// The milliseconds a REST query waits for the RPC budget before it's shed
+    #[arg(long, default_value_t = 1000)]
+    pub sol_rpc_query_wait: u64,
+    /// The programs the analyzer keeps the instructions of, `<program id>[=<label>]` separated
+    /// by commas, the instructions of the other unknown programs are skipped
+    #[arg(long, value_delimiter = ',')]
+    pub sol_program_allowlist: Vec<String>,
+    /// Fail the analysis of the transactions call an unknown program instead of skipping it
+    #[arg(long, default_value_t = false)]
+    pub sol_analyzer_strict: bool,
+    /// The number of the workers send the tokens for the deposits
+    #[arg(long, default_value_t = 1)]
+    pub deposit_workers: usize,
+    /// The number of the workers make the DePC transfers for the withdrawals
+    #[arg(long, default_value_t = 1)]
+    pub withdraw_workers: usize,
+    /// The number of the deposits can be queued for the workers
+    #[arg(long, default_value_t = 1)]
+    pub deposit_channel_capacity: usize,
+    /// The number of the withdrawals can be queued for the workers
+    #[arg(long, default_value_t = 1)]
+    pub withdraw_channel_capacity: usize,
+    /// The number of the blocks are synced in one database transaction
+    #[arg(long, default_value_t = 1)]
+    pub db_batch_size: u32,
+    /// The number of the deposits are minted by one Solana transaction
+    #[arg(long, default_value_t = 1)]
+    pub mint_batch_size: usize,
+    /// The seconds to wait before a failed mint is retried, it's doubled by each failure after
+    /// it up to `--mint-retry-max-delay`
+    #[arg(long, default_value_t = 5)]
+    pub mint_retry_delay: u64,
+    #[arg(long, default_value_t = 600)]
+    pub mint_retry_max_delay: u64,
+    /// The deposit is dead-lettered after its mint fails so many times, it's retried until it's
+    /// minted if it's absent
+    #[arg(long)]
+    pub mint_max_attempts: Option<u32>,
+    /// The cold-storage address the excess custody DePC is swept to, no sweeping if it's absent
+    #[arg(long, requires = "sweep_hot_ceiling")]
+    pub sweep_cold_address: Option<String>,
+    /// The ceiling of the custody balance, the DePC exceeds it will be swept to the cold address,
+    /// it's required with `--sweep-cold-address`
+    #[arg(long, requires = "sweep_cold_address")]
+    pub sweep_hot_ceiling: Option<u64>,
+    /// Only make unsigned sweep transactions those will be signed and broadcasted offline
+    #[arg(long, default_value_t = false)]
+    pub sweep_unsigned_only: bool,
+    /// The interval (in seconds) to check the custody balance for sweeping
+    #[arg(long, default_value_t = 600)]
+    pub sweep_interval: u64,
+    /// The payouts signal replaceability (BIP125), so the stuck ones are bumped by replacing
+    /// them instead of by the child transactions spending their change
+    #[arg(long, default_value_t = false)]
+    pub payout_replaceable: bool,
+    /// Bump the fee of a withdrawal payout still unconfirmed so many blocks after it's sent (or
+    /// after its last bump), the stuck payouts are not bumped if it's absent
+    #[arg(long)]
+    pub fee_bump_after_blocks: Option<u32>,
+    /// The fee (in the smallest units of DePC) added to a stuck payout by each bump
+    #[arg(long, default_value_t = 100000)]
+    pub fee_bump_step: u64,
+    /// The most fee (in the smallest units of DePC) paid for a payout with its bumps
+    #[arg(long, default_value_t = 1000000)]
+    pub fee_bump_max_fee: u64,
+    /// The interval (in seconds) to check the payouts are stuck
+    #[arg(long, default_value_t = 60)]
+    pub fee_bump_interval: u64,
+    /// The decimals of the DePC amounts, they're converted to the tokens with the decimals of
+    /// the mint queried from solana
+    #[arg(long, default_value_t = 8)]
+    pub depc_decimals: u8,
+    /// The seconds to wait before an invalid deposit (the recipient cannot be parsed) is
+    /// refunded to its sender, the invalid deposits are not refunded if it's absent
+    #[arg(long)]
+    pub refund_delay: Option<u64>,
+    /// The interval (in seconds) to check the invalid deposits are due for refunding
+    #[arg(long, default_value_t = 60)]
+    pub refund_interval: u64,
+    /// The seconds a deposit intent is valid for after it's registered, the plain payments
+    /// (without OP_RETURN) to the custody address are not matched to the intents if it's absent
+    #[arg(long)]
+    pub deposit_intent_window: Option<u64>,
+    /// The payments to the custody address which make no deposit or withdrawal are recorded as
+    /// the unclassified inflows, the ones of this much DePC (in satoshis) or more are alerted
+    /// as errors and the others as warnings
+    #[arg(long, default_value_t = 0)]
+    pub inflow_alert_amount: u64,
+    /// The flat bridge fee kept from every deposit before it's minted, in satoshis
+    #[arg(long, default_value_t = 0)]
+    pub deposit_fee_flat: u64,
+    /// The bridge fee kept from every deposit in basis points of the amount, it's added to the
+    /// flat fee
+    #[arg(long, default_value_t = 0)]
+    pub deposit_fee_bps: u32,
+    /// The flat bridge fee kept from every withdrawal before it's paid out, in satoshis
+    #[arg(long, default_value_t = 0)]
+    pub withdraw_fee_flat: u64,
+    /// The bridge fee kept from every withdrawal in basis points of the amount
+    #[arg(long, default_value_t = 0)]
+    pub withdraw_fee_bps: u32,
+    /// The least DePC (in satoshis) minted or paid out for a transfer after the fee, the
+    /// smaller ones are not made
+    #[arg(long, default_value_t = 0)]
+    pub transfer_min: u64,
+    /// The most DePC (in satoshis) minted or paid out for a transfer after the fee, the larger
+    /// ones are held until an admin releases them, there's no maximum if it's absent
+    #[arg(long)]
+    pub transfer_max: Option<u64>,
+    /// The most DePC (in satoshis) minted or paid out to a recipient in 24 hours, the
+    /// transfers over it are held until an admin releases them, there's no cap if it's absent
+    #[arg(long)]
+    pub daily_recipient_cap: Option<u64>,
+    /// Catch up when the bridge starts this many blocks or more behind the DePC node, the
+    /// blocks are indexed before the deposits and withdrawals found are acted on, the bridge
+    /// acts while syncing if it's absent
+    #[arg(long)]
+    pub catch_up_blocks: Option<u32>,
+    /// The catch-up backlog of more actions than it waits for `catch-up --confirm`
+    #[arg(long, default_value_t = 0)]
+    pub catch_up_confirm_actions: u64,
+    /// The catch-up backlog of more DePC (in satoshis) than it waits for `catch-up --confirm`
+    #[arg(long, default_value_t = 0)]
+    pub catch_up_confirm_amount: u64,
+    /// The account xpub (or tpub) a deposit address is derived from for each user registered
+    /// by `POST /bridge/deposit-addresses`, the payments to them are credited without OP_RETURN
+    #[arg(long)]
+    pub deposit_xpub: Option<String>,
+    /// The url to fetch the price of DePC from, `{currency}` is replaced with the currency, no
+    /// prices are recorded if it's absent
+    #[arg(long)]
+    pub price_url: Option<String>,
+    /// The JSON pointer to the price in the response of `--price-url`
+    #[arg(long, default_value = "/depinc/{currency}")]
+    pub price_pointer: String,
+    /// The comma separated fiat currencies the prices are recorded in
+    #[arg(long, default_value = "usd")]
+    pub price_currencies: String,
+    /// The interval (in seconds) to record the prices
+    #[arg(long, default_value_t = 300)]
+    pub price_interval: u64,
+    /// The JSON file of the other bridge pairs (custody address, SPL mint and authority) run by
+    /// this process besides the one from the command line, and the settings reloaded on SIGHUP,
+    /// see `bridge::BridgeSettings`
+    #[arg(long)]
+    pub settings: Option<String>,
+    /// The token of the admin endpoints (`/admin/*`), they're not served if it's absent
+    #[arg(long)]
+    pub admin_token: Option<String>,
+    /// The keypair (base58) signing `/bridge/info` and `/bridge/summary`, a key used only for
+    /// them, the responses aren't signed if it's absent
+    #[arg(long)]
+    pub attestation_key: Option<String>,
+    /// The delay (in seconds) before a parameter change made by the admin takes effect
+    #[arg(long, default_value_t = 172800)]
+    pub admin_timelock: u64,
+    /// The Solana address the bridge fees are swept to by `/admin/sweep-fees`, the fees are
+    /// not swept if it's absent
+    #[arg(long)]
+    pub fee_treasury: Option<String>,
+    /// The interval (in seconds) to consolidate the tokens stranded in the auxiliary token
+    /// accounts of the authority back to its token account, they're not swept if it's absent
+    #[arg(long)]
+    pub dust_sweep_interval: Option<u64>,
+    /// The JSON file of the periodic jobs (name, cron expression and enabled flag), see
+    /// `jobs::JobsSettings`, no job is run if it's absent
+    #[arg(long)]
+    pub jobs: Option<String>,
+    /// Publish each new deposit root (job `deposit_root`) on Solana as well, with a memo
+    /// transaction paid by the authority
+    #[arg(long, default_value_t = false)]
+    pub deposit_root_memo: bool,
+    /// The JSON file of the exchange balance reports (block interval, start height and
+    /// timezone), see `rest::ReportSettings`
+    #[arg(long)]
+    pub report_settings: Option<String>,
+    /// The interval (in seconds) to post the activities of the watched addresses to their
+    /// webhooks
+    #[arg(long, default_value_t = 30)]
+    pub watch_interval: u64,
+    /// Use proxy for the webhooks of the watched addresses
+    #[arg(long, default_value_t = false)]
+    pub webhook_use_proxy: bool,
+    /// Sync the chains but only record the mints, payouts and sweeps the bridge would make
+    #[arg(long, default_value_t = false)]
+    pub shadow: bool,
+    /// Inject faults into the DePC RPC calls, e.g. `drop=0.1,delay=0.2,delay_ms=500,corrupt=0.05`
+    #[cfg(feature = "chaos")]
+    #[arg(long)]
+    pub chaos_depc: Option<depc_bridge::chaos::FaultConfig>,
+    /// Inject faults into the Solana RPC calls, the format is the same as `--chaos-depc`
+    #[cfg(feature = "chaos")]
+    #[arg(long)]
+    pub chaos_solana: Option<depc_bridge::chaos::FaultConfig>,
     /// The path string to local database
     #[arg(long, default_value = "$HOME/depc-bridge.sqlite3")]
     pub local_db: String,
+    /// Move the coins spent more than this many blocks below the best height to the archive
+    /// tables `coins_archive_N`, no coin is archived if it's absent
+    #[arg(long)]
+    pub archive_depth: Option<u32>,
+    /// The interval (in seconds) to archive the spent coins
+    #[arg(long, default_value_t = 3600)]
+    pub archive_interval: u64,
+    /// Bootstrap an empty index from the trusted block at this height instead of syncing from
+    /// the genesis, the balances below it are not answered
+    #[arg(long, requires = "start_hash")]
+    pub start_height: Option<u32>,
+    /// The hash of the block at `--start-height`, it must match the DePC node
+    #[arg(long, requires = "start_height")]
+    pub start_hash: Option<String>,
     /// Monitor the chain for the owner address
     #[arg(long, default_value = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ")]
     pub owner_address: String,
@@ -54,4 +316,4 @@ pub struct Run {
     /// The private key to make signature
     #[arg(long)]
     pub eth_private_key: String,
-}
\ No newline at end of file
+}
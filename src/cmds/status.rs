@@ -0,0 +1,11 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct Status {
+    /// The path string to local database
+    #[arg(long, default_value = "$HOME/depc-bridge.sqlite3")]
+    pub local_db: String,
+    /// The bridge to report, see `--settings` of `run`
+    #[arg(long, default_value = "default")]
+    pub bridge_id: String,
+}
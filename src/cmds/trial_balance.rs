@@ -0,0 +1,11 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct TrialBalance {
+    /// The path string to local database
+    #[arg(long, default_value = "$HOME/depc-bridge.sqlite3")]
+    pub local_db: String,
+    /// The bridge the ledger belongs to, see `--settings` of `run`
+    #[arg(long, default_value = "default")]
+    pub bridge_id: String,
+}
@@ -0,0 +1,54 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::{task::yield_now, time::sleep};
+
+use super::Conn;
+
+/// The heights of a partition, the coins spent at heights `N * COINS_ARCHIVE_PARTITION_HEIGHTS`
+/// and above are archived to table `coins_archive_N`
+pub const COINS_ARCHIVE_PARTITION_HEIGHTS: u32 = 100_000;
+/// The heights `Conn::archive_spent_coins` moves at most each call, the writer isn't held for
+/// long by a partition of years of history
+pub const COINS_ARCHIVE_BATCH_HEIGHTS: u32 = 1000;
+
+/// The archive table of `partition`
+pub fn coins_archive_table(partition: u32) -> String {
+    format!("coins_archive_{partition}")
+}
+
+/// Archive the coins spent `depth` blocks below the best height every `interval`, the hot table
+/// `coins` keeps the unspent coins and the recently spent ones
+pub async fn run_coins_archival(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: Conn,
+    depth: u32,
+    interval: Duration,
+) {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        if let Some(best_height) = conn.query_best_height() {
+            let height = best_height.saturating_sub(depth);
+            match conn.archive_spent_coins(height) {
+                Ok(0) => sleep(interval).await,
+                Ok(n) => {
+                    info!("{n} coins spent below height {height} are archived");
+                    // the next batch is moved right away
+                    yield_now().await;
+                }
+                Err(e) => {
+                    warn!("cannot archive the coins spent below height {height}, reason: {e}");
+                    sleep(interval).await;
+                }
+            }
+        } else {
+            sleep(interval).await;
+        }
+    }
+}
@@ -1,30 +1,85 @@
-use std::sync::{Arc, Mutex};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
 
-use rusqlite::{params, Connection, Error};
+use rusqlite::types::{ToSqlOutput, Value};
+use rusqlite::{
+    ffi, params, params_from_iter, Connection, Error, OpenFlags, OptionalExtension, Row, ToSql,
+};
+
+use super::{
+    coins_archive_table, BusyRetry, BusyRetryStats, HexPayload, PayloadColumn, BUSY_TIMEOUT,
+    COINS_ARCHIVE_BATCH_HEIGHTS, COINS_ARCHIVE_PARTITION_HEIGHTS, DEFAULT_BUSY_RETRY_POLICY,
+};
+use crate::amount::{DepcAmount, TokenAmount};
+use crate::clock::Deadline;
+
+/// The deadline of the queries is checked every so many virtual machine instructions
+const DEADLINE_CHECK_INSTRUCTIONS: i32 = 1000;
 
 const SQL_BEGIN_TRANSACTION: &str = "begin transaction";
+const SQL_QUERY_SCHEMA_VERSION: &str = "pragma user_version";
+/// The readers and the writer don't block each other in WAL, it's saved in the database file
+const SQL_ENABLE_WAL: &str = "pragma journal_mode = wal";
+/// The tables of the database for the sanity report, the internal ones of sqlite are left out
+const SQL_QUERY_TABLE_NAMES: &str = "select name from sqlite_master where type = 'table' and name not like 'sqlite_%' order by name";
 
 const SQL_ROLLBACK_TRANSACTION: &str = "rollback transaction";
 
 const SQL_COMMIT_TRANSACTION: &str = "commit transaction";
 
+/// The bridge tables (deposits, withdrawals, pending actions, ...) have column `bridge_id`, the
+/// rows of the existing databases belong to `DEFAULT_BRIDGE_ID`
+const SQL_QUERY_HAS_BRIDGE_ID_COLUMN: &str =
+    "select exists (select 1 from pragma_table_info(?) where name = 'bridge_id')";
+const SQL_QUERY_HAS_COLUMN: &str =
+    "select exists (select 1 from pragma_table_info(?) where name = ?)";
+
 /// Table `blocks`
 const SQL_CREATE_TABLE_BLOCKS: &str =
     "create table if not exists blocks (hash, height, miner, time)";
 const SQL_CREATE_UNIQUE_INDEX_BLOCKS_HASH: &str =
     "create unique index if not exists index__blocks_hash on blocks (hash)";
-const SQL_INSERT_BLOCK: &str = "insert into blocks (hash, height, miner, time) values (?, ?, ?, ?)";
+/// The height<->time lookup, the block time is not strictly increasing so the height at a time
+/// is the highest block made before it
+const SQL_CREATE_INDEX_BLOCKS_TIME_HEIGHT: &str =
+    "create index if not exists index__blocks_time_height on blocks (time, height)";
+/// The blocks of a miner, the mining statistics are grouped by it
+const SQL_CREATE_INDEX_BLOCKS_MINER_HEIGHT: &str =
+    "create index if not exists index__blocks_miner_height on blocks (miner, height)";
+/// A block applied again replaces the one of the same hash
+const SQL_INSERT_BLOCK: &str = "insert into blocks (hash, height, miner, time) values (?, ?, ?, ?) on conflict (hash) do update set height = excluded.height, miner = excluded.miner, time = excluded.time";
+/// The blocks of each miner in the heights, the most blocks first
+const SQL_QUERY_MINER_STATS: &str = "select miner, count(*), min(height), max(height), min(time), max(time) from blocks where height between ? and ? group by miner order by count(*) desc, miner limit ?";
+/// The blocks of a miner in all the heights indexed
+const SQL_QUERY_MINER_STATS_OF_MINER: &str = "select miner, count(*), min(height), max(height), min(time), max(time) from blocks where miner = ? group by miner";
+/// The blocks of a miner by the day (the days since the epoch) of the block time
+const SQL_QUERY_MINER_DAILY_BLOCKS: &str = "select time / 86400, count(*) from blocks where miner = ? and time between ? and ? group by 1 order by 1";
 
 /// Table `transactions`
+/// `height` is the height of block `block_hash`, it's looked up when the transaction is inserted
+/// so the transactions of a height are queried without joining the blocks
 const SQL_CREATE_TABLE_TRANSACTIONS: &str =
-    "create table if not exists transactions (block_hash, txid)";
+    "create table if not exists transactions (block_hash, txid, height integer)";
 const SQL_CREATE_UNIQUE_INDEX_TRANSACTIONS_TXID: &str =
     "create unique index if not exists index__transactions_txid on transactions (txid)";
-const SQL_INSERT_TRANSACTION: &str = "insert into transactions (block_hash, txid) values (?, ?)";
+const SQL_CREATE_INDEX_TRANSACTIONS_BLOCK_HASH: &str =
+    "create index if not exists index__transactions_block_hash on transactions (block_hash)";
+const SQL_CREATE_INDEX_TRANSACTIONS_HEIGHT: &str =
+    "create index if not exists index__transactions_height on transactions (height)";
+/// A transaction indexed already is moved to the block it's applied with again
+const SQL_INSERT_TRANSACTION: &str = "insert into transactions (block_hash, txid, height) values (?1, ?2, (select height from blocks where hash = ?1)) on conflict (txid) do update set block_hash = excluded.block_hash, height = excluded.height";
+const SQL_QUERY_TRANSACTION_HEIGHT: &str = "select height from transactions where txid = ?";
+const SQL_FILL_TRANSACTION_HEIGHTS: &str = "update transactions set height = (select height from blocks where hash = transactions.block_hash) where height is null";
 
 /// Table `coins`
+/// `height` is the height of the block the coin is made in, it's looked up when the coin is
+/// inserted so the balances are queried without joining the blocks
+const COINS_COLUMNS: &str =
+    "txid, n, value, owner, script_hex, is_spent, spent_height, spent_txid, height";
 const SQL_CREATE_TABLE_COINS: &str =
-    "create table if not exists coins (txid, n, value, owner, script_hex, is_spent, spent_height, spent_txid)";
+    "create table if not exists coins (txid, n, value, owner, script_hex, is_spent, spent_height, spent_txid, height integer)";
 const SQL_CREATE_UNIQUE_INDEX_COINS_TXID_N: &str =
     "create unique index if not exists index__coins_txid_n on coins (txid, n)";
 const SQL_CREATE_INDEX_COINS_SPENT_TXID: &str =
@@ -33,137 +88,1715 @@ const SQL_CREATE_INDEX_COINS_OWNER: &str =
     "create index if not exists index__coins_owner on coins (owner)";
 const SQL_CREATE_INDEX_COINS_SPENT_HEIGHT: &str =
     "create index if not exists index__coins_spent_height on coins (spent_height)";
-const SQL_INSERT_COIN: &str =
-    "insert into coins (txid, n, value, owner, script_hex, is_spent) values (?, ?, ?, ?, ?, ?)";
+/// Covers the balance of an address at a height, see `SQL_QUERY_BALANCE_OF_ADDRESS`
+const SQL_CREATE_INDEX_COINS_OWNER_HEIGHT: &str = "create index if not exists index__coins_owner_height on coins (owner, height, spent_height, value)";
+/// Tables `coins_archive_N`, the coins spent at the heights of partition `N` are moved to them
+/// from table `coins`, see `Conn::archive_spent_coins`
+const SQL_QUERY_LOWEST_ARCHIVABLE_SPENT_HEIGHT: &str =
+    "select min(spent_height) from coins where is_spent = true and spent_height < ?";
+const SQL_QUERY_HAS_TABLE: &str =
+    "select exists (select 1 from sqlite_master where type = 'table' and name = ?)";
+const SQL_DELETE_ARCHIVED_COINS: &str =
+    "delete from coins where is_spent = true and spent_height >= ? and spent_height < ?";
+/// View `all_coins`, the coins of table `coins` and the archive tables, the history is queried
+/// from it. `coin_id` is the rowid of the coin in table `coins`
+const SQL_QUERY_COINS_ARCHIVE_TABLES: &str = "select name from sqlite_master where type = 'table' and name like 'coins_archive_%' order by name";
+const SQL_DROP_VIEW_ALL_COINS: &str = "drop view if exists all_coins";
+/// View `all_coin_values`, the columns of `all_coins` the balances are summed from. The
+/// coins are read from the covering indexes only, which isn't done through `all_coins`
+const COIN_VALUES_COLUMNS: &str = "owner, height, spent_height, value";
+const SQL_DROP_VIEW_ALL_COIN_VALUES: &str = "drop view if exists all_coin_values";
+const SQL_QUERY_COIN_SCRIPT: &str = "select script_hex from all_coins where txid = ? and n = ?";
+const SQL_QUERY_COIN_SPENT_TXID: &str =
+    "select spent_txid from all_coins where txid = ? and n = ? and is_spent = true";
+/// The scripts written as hex text before `HexPayload`
+const SQL_QUERY_TEXT_COIN_SCRIPTS: &str = "select rowid, script_hex from coins where rowid > ? and typeof(script_hex) = 'text' order by rowid limit ?";
+const SQL_UPDATE_COIN_SCRIPT: &str = "update coins set script_hex = ? where rowid = ?";
+/// A coin added already is left as it is, it might be spent since
+const SQL_INSERT_COIN: &str = "insert into coins (txid, n, value, owner, script_hex, is_spent, height) values (?1, ?2, ?3, ?4, ?5, ?6, (select height from transactions where txid = ?1)) on conflict (txid, n) do nothing";
 const SQL_MARK_COIN_SPENT: &str =
     "update coins set is_spent = true, spent_txid = ?, spent_height = ? where txid = ? and n = ?";
+/// The coins marked spent without the transaction spending them, they should never be found
+const SQL_QUERY_NUM_SPENT_COINS_WITHOUT_TXID: &str = "select count(*) from all_coins where is_spent = true and (spent_txid is null or spent_txid = '')";
+
+/// Table `address_balances`
+/// The current balances of the addresses, they're updated with the coins so they follow the
+/// synced blocks (and the rollbacks of them)
+const SQL_CREATE_TABLE_ADDRESS_BALANCES: &str = "create table if not exists address_balances (address text primary key not null, balance integer not null)";
+const SQL_CREATE_INDEX_ADDRESS_BALANCES_BALANCE: &str =
+    "create index if not exists index__address_balances_balance on address_balances (balance)";
+const SQL_QUERY_ANY_ADDRESS_BALANCE: &str = "select exists (select 1 from address_balances)";
+const SQL_FILL_ADDRESS_BALANCES: &str = "insert into address_balances (address, balance) select owner, sum(value) from coins where is_spent = false and owner is not null group by owner";
+const SQL_CREDIT_ADDRESS_BALANCE: &str = "insert into address_balances (address, balance) values (?, ?) on conflict (address) do update set balance = balance + excluded.balance";
+const SQL_DEBIT_ADDRESS_BALANCE_BY_COIN: &str = "update address_balances set balance = address_balances.balance - coins.value from coins where coins.txid = ? and coins.n = ? and coins.is_spent = false and address_balances.address = coins.owner";
+const SQL_QUERY_ADDRESS_BALANCE: &str =
+    "select coalesce(sum(balance), 0) from address_balances where address = ?";
+const SQL_QUERY_RICHLIST: &str = "select address, balance from address_balances where balance > 0 order by balance desc, address limit ?";
 
 /// Table `deposit`
 /// the reson I removed `from_address_depc` is because it's a bit more complex of the UTXO model,
 /// A transaction might contains more than one incoming addresses. We might need to create
 /// a slave table contains the addresses which are related to a deposit.
-const SQL_CREATE_TABLE_DEPC_DEPOSIT: &str = "create table if not exists depc_deposit (depc_txid, depc_timestamp, to_address_erc20, amount, erc20_txid, erc20_timestamp)";
-const SQL_CREATE_UNIQUE_INDEX_DEPC_DEPOSIT_DEPC_TXID: &str =
-    "create unique index if not exists index__depc_deposit_depc_txid on depc_deposit (depc_txid)";
-const SQL_INSERT_DEPC_DEPOSIT: &str = "insert into depc_deposit (depc_txid, to_address_erc20, amount, depc_timestamp) values (?, ?, ?, ?)";
-const SQL_UPDATE_DEPC_DEPSOIT: &str =
-    "update depc_deposit set erc20_txid = ?, erc20_timestamp = ? where depc_txid = ?";
+const SQL_CREATE_TABLE_DEPC_DEPOSIT: &str = "create table if not exists depc_deposit (depc_txid, depc_timestamp, to_address_erc20, amount, erc20_txid, erc20_timestamp, bridge_id text not null default 'default')";
+const SQL_DROP_INDEX_DEPC_DEPOSIT_DEPC_TXID: &str =
+    "drop index if exists index__depc_deposit_depc_txid";
+const SQL_CREATE_UNIQUE_INDEX_DEPC_DEPOSIT_BRIDGE_ID_DEPC_TXID: &str = "create unique index if not exists index__depc_deposit_bridge_id_depc_txid on depc_deposit (bridge_id, depc_txid)";
+const SQL_INSERT_DEPC_DEPOSIT: &str = "insert into depc_deposit (depc_txid, to_address_erc20, amount, depc_timestamp, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_UPDATE_DEPC_DEPSOIT: &str = "update depc_deposit set erc20_txid = ?, erc20_timestamp = ? where bridge_id = ? and depc_txid = ?";
+const SQL_UPDATE_DEPC_DEPOSIT_MINT_RECEIPT: &str = "update depc_deposit set erc20_txid = ?, erc20_timestamp = ?, erc20_fee = ?, erc20_slot = ?, erc20_created_ata = ?, erc20_logs = ? where bridge_id = ? and depc_txid = ?";
+const SQL_QUERY_DEPC_DEPOSIT: &str = "select depc_txid, depc_timestamp, to_address_erc20, amount, erc20_txid, erc20_timestamp, erc20_fee, erc20_slot, erc20_created_ata, erc20_logs from depc_deposit where bridge_id = ? and depc_txid = ?";
+const SQL_CREATE_INDEX_DEPC_DEPOSIT_BRIDGE_ID_ERC20_TIMESTAMP: &str = "create index if not exists index__depc_deposit_bridge_id_erc20_timestamp on depc_deposit (bridge_id, erc20_timestamp)";
+/// The order of the leaves of the deposit roots
+const SQL_QUERY_CONFIRMED_DEPC_DEPOSITS: &str = "select depc_txid, depc_timestamp, to_address_erc20, amount, erc20_txid, erc20_timestamp, erc20_fee, erc20_slot, erc20_created_ata, erc20_logs from depc_deposit where bridge_id = ? and erc20_txid is not null and erc20_timestamp < ? order by erc20_timestamp, depc_txid";
 
 /// Table `withdraw`
-const SQL_CREATE_TABLE_DEPC_WITHDRAW: &str = "create table if not exists depc_withdraw (erc20_txid, erc20_timestamp, from_address_erc20, to_address_depc, amount, depc_txid, depc_timestamp)";
-const SQL_CREATE_UNIQUE_INDEX_DEPC_WITHDRAW_ERC20_TXID: &str = "create unique index if not exists index__depc_withdraw_erc20_txid on depc_withdraw (erc20_txid)";
-const SQL_INSERT_DEPC_WITHDRAW: &str = "insert into depc_withdraw (erc20_txid, erc20_timestamp, from_address_erc20, amount) values (?, ?, ?, ?)";
-const SQL_UPDATE_DEPC_WITHDRAW: &str =
-    "update depc_withdraw set depc_txid = ?, depc_timestamp = ?, to_address_depc = ? where erc20_txid = ?";
+const SQL_CREATE_TABLE_DEPC_WITHDRAW: &str = "create table if not exists depc_withdraw (erc20_txid, erc20_timestamp, from_address_erc20, to_address_depc, amount, depc_txid, depc_timestamp, bridge_id text not null default 'default')";
+const SQL_DROP_INDEX_DEPC_WITHDRAW_ERC20_TXID: &str =
+    "drop index if exists index__depc_withdraw_erc20_txid";
+const SQL_CREATE_UNIQUE_INDEX_DEPC_WITHDRAW_BRIDGE_ID_ERC20_TXID: &str = "create unique index if not exists index__depc_withdraw_bridge_id_erc20_txid on depc_withdraw (bridge_id, erc20_txid)";
+const SQL_INSERT_DEPC_WITHDRAW: &str = "insert into depc_withdraw (erc20_txid, erc20_timestamp, from_address_erc20, amount, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_UPDATE_DEPC_WITHDRAW: &str = "update depc_withdraw set depc_txid = ?, depc_timestamp = ?, to_address_depc = ? where bridge_id = ? and erc20_txid = ?";
 const SQL_QUERY_BEST_HEIGHT: &str = "select height from blocks order by height desc limit 1";
+const SQL_QUERY_BLOCK_HASH_BY_HEIGHT: &str = "select hash from blocks where height = ?";
+/// The rollback of the chain index above a height after a reorg, see
+/// `Conn::rollback_blocks_above`. The transactions above the height are removed last, the
+/// statements before them find the orphaned rows by them
+const SQL_QUERY_ORPHANED_DEPOSITS: &str = "select bridge_id, id, source_txid, claimed_at is null and done_at is null from pending_actions where action = 'deposit' and source_txid in (select txid from transactions where height > ?) order by id";
+const SQL_DELETE_PENDING_ACTION: &str = "delete from pending_actions where id = ?";
+const SQL_DELETE_RECORDED_TRANSACTIONS_OF_ACTION: &str =
+    "delete from recorded_transactions where bridge_id = ? and action_id = ?";
+const SQL_DELETE_UNMINTED_DEPC_DEPOSIT: &str =
+    "delete from depc_deposit where bridge_id = ? and depc_txid = ? and erc20_txid is null";
+const SQL_RESET_DEPOSIT_INTENT_MATCH: &str = "update deposit_intents set matched_txid = null, matched_vout = null, matched_at = null where bridge_id = ? and matched_txid = ?";
+const SQL_QUERY_ORPHANED_REFUNDS: &str = "select bridge_id, id, depc_txid from depc_refunds where txid is null and refunded_at is null and depc_txid in (select txid from transactions where height > ?) order by id";
+const SQL_DELETE_DEPC_REFUND: &str = "delete from depc_refunds where id = ?";
+const SQL_DELETE_CUSTODY_IN_LEDGER_ENTRIES: &str =
+    "delete from ledger_entries where bridge_id = ? and event = 'custody_in' and reference = ?";
+const SQL_DELETE_DEPOSIT_FEE_LEDGER_ENTRIES: &str =
+    "delete from ledger_entries where bridge_id = ? and event = 'deposit_fee' and reference = ?";
+const SQL_DELETE_TRANSFER_FEES_OF_ACTION: &str =
+    "delete from transfer_fees where bridge_id = ? and action_id = ?";
+const SQL_DELETE_CUSTODY_IN_EVENTS: &str =
+    "delete from events where bridge_id = ? and event = 'custody_in' and reference = ?";
+const SQL_UNCONFIRM_ORPHANED_PENDING_ACTIONS: &str = "update pending_actions set confirmed_at = null where txid in (select txid from transactions where height > ?)";
+const SQL_CREDIT_ORPHANED_SPENT_COINS: &str = "insert into address_balances (address, balance) select owner, sum(value) from coins where is_spent = true and spent_height > ? and owner is not null group by owner on conflict (address) do update set balance = balance + excluded.balance";
+const SQL_UNSPEND_ORPHANED_SPENT_COINS: &str = "update coins set is_spent = false, spent_txid = null, spent_height = null where is_spent = true and spent_height > ?";
+const SQL_DEBIT_ORPHANED_COINS: &str = "insert into address_balances (address, balance) select owner, -sum(value) from coins where height > ? and owner is not null group by owner on conflict (address) do update set balance = balance + excluded.balance";
+const SQL_DELETE_ORPHANED_COINS: &str = "delete from coins where height > ?";
+const SQL_DELETE_ORPHANED_TRANSACTIONS: &str = "delete from transactions where height > ?";
+const SQL_DELETE_ORPHANED_BLOCKS: &str = "delete from blocks where height > ?";
+const SQL_DELETE_ORPHANED_UNAVAILABLE_HEIGHTS: &str =
+    "delete from unavailable_heights where height > ?";
+const SQL_DELETE_ORPHANED_DEAD_LETTERS: &str = "delete from dead_letters where height > ?";
+const SQL_DELETE_ORPHANED_CUSTODY_INFLOWS: &str = "delete from custody_inflows where height > ?";
+const SQL_DELETE_ORPHANED_BLOCK_DIGESTS: &str = "delete from block_digests where height > ?";
 const SQL_QUERY_ADDRESSES_FROM_TX_INPUTS: &str =
-    "select owner from coins where spent_txid = ? and is_spent = true";
+    "select owner from all_coins where spent_txid = ? and is_spent = true";
 const SQL_QUERY_TXIDS_THOSE_INPUTS_CONTAIN_ADDRESS: &str =
-    "select spent_txid from coins where owner = ? and is_spent = true group by spent_txid";
+    "select spent_txid from all_coins where owner = ? and is_spent = true group by spent_txid";
+const SQL_QUERY_ADDRESS_HAS_COINS: &str = "select exists (select 1 from all_coins where owner = ?)";
 const SQL_QUERY_BALANCE_OF_ADDRESS: &str =
-    "select sum(value) from coins left join transactions on transactions.txid = coins.txid left join blocks on blocks.hash = transactions.block_hash where owner = ? and height <= ? and (spent_height is null or spent_height > ?)";
+    "select sum(value) from all_coin_values where owner = ? and height <= ? and (spent_height is null or spent_height > ?)";
 
+const SQL_QUERY_UNSPENT_COINS_OF_ADDRESS: &str = "select txid, n, value, height from coins where owner = ? and is_spent = false order by txid, n";
+const SQL_QUERY_RECEIVED_BY_ADDRESS: &str =
+    "select coalesce(sum(value), 0) from all_coin_values where owner = ?";
 const SQL_QUERY_BLOCK_TIME_BY_HEIGHT: &str = "select time from blocks where height = ?";
+const SQL_QUERY_HEIGHT_BY_TIME: &str = "select max(height) from blocks where time <= ?";
+/// The summaries of the blocks, the value moved by a block is the total value of its outputs
+const SQL_QUERY_BLOCK_SUMMARY_BY_HEIGHT: &str = "select hash, height, miner, time, (select count(*) from transactions where transactions.height = blocks.height), (select coalesce(sum(all_coins.value), 0) from transactions join all_coins on all_coins.txid = transactions.txid where transactions.height = blocks.height) from blocks where height = ?";
+const SQL_QUERY_BLOCK_SUMMARY_BY_HASH: &str = "select hash, height, miner, time, (select count(*) from transactions where transactions.height = blocks.height), (select coalesce(sum(all_coins.value), 0) from transactions join all_coins on all_coins.txid = transactions.txid where transactions.height = blocks.height) from blocks where hash = ?";
+const SQL_QUERY_BLOCK_SUMMARIES: &str = "select hash, height, miner, time, (select count(*) from transactions where transactions.height = blocks.height), (select coalesce(sum(all_coins.value), 0) from transactions join all_coins on all_coins.txid = transactions.txid where transactions.height = blocks.height) from blocks where height between ? and ? order by height";
 
 /// Table `exchange_addresses`
-const SQL_CREATE_TABLE_EXCHANGE_ADDRESSES: &str = "create table if not exists exchange_addresses (address text primary key not null, analyzed_txid text not null)";
+/// `first_seen` is the time the address is saved by an analysis, it's null for the addresses
+/// saved before the column is added. `rediscoveries` counts the times the saved address is found
+/// again by the analyses
+const SQL_CREATE_TABLE_EXCHANGE_ADDRESSES: &str = "create table if not exists exchange_addresses (address text primary key not null, analyzed_txid text not null, first_seen integer, rediscoveries integer not null default 0)";
 const SQL_CREATE_INDEX_EXCHANGE_ADDRESSES_ANALYZED_TXID: &str = "create index if not exists index__exchange_addresses_analyzed_txid on exchange_addresses (analyzed_txid)";
-const SQL_INSERT_EXCHANGE_ADDRESSE: &str =
-    "insert into exchange_addresses (address, analyzed_txid) values (?, ?)";
+/// The saved address keeps the transaction and the time it's first found by, the rediscoveries
+/// returned are 0 for a new address
+const SQL_UPSERT_EXCHANGE_ADDRESSE: &str = "insert into exchange_addresses (address, analyzed_txid, first_seen) values (?, ?, ?) on conflict (address) do update set rediscoveries = rediscoveries + 1 returning rediscoveries";
 const SQL_QUERY_EXCHANGE_ADDRESSES: &str = "select address from exchange_addresses";
+/// The pages are keyed by the address, the addresses saved meanwhile don't shift them
+const SQL_QUERY_EXCHANGE_ADDRESSES_AFTER: &str = "select address, analyzed_txid, first_seen, rediscoveries from exchange_addresses where address > ? order by address limit ?";
 const SQL_QUERY_NUM_EXCHANGE_ADDRESSES: &str = "select count(*) from exchange_addresses";
 
+/// Table `analysis_frontier`
+/// The addresses visited by the exchange analysis seeded by `seed_txid`, the analysis resumes
+/// from the addresses those are not `expanded` yet
+const SQL_CREATE_TABLE_ANALYSIS_FRONTIER: &str = "create table if not exists analysis_frontier (seed_txid text not null, address text not null, depth integer not null, expanded integer not null default 0, primary key (seed_txid, address))";
+const SQL_INSERT_ANALYSIS_FRONTIER: &str =
+    "insert or ignore into analysis_frontier (seed_txid, address, depth) values (?, ?, ?)";
+const SQL_MARK_ANALYSIS_FRONTIER_EXPANDED: &str =
+    "update analysis_frontier set expanded = 1 where seed_txid = ? and address = ?";
+const SQL_QUERY_ANALYSIS_FRONTIER: &str = "select address, depth, expanded from analysis_frontier where seed_txid = ? order by depth, rowid";
+
+/// Table `address_clusters`
+/// The addresses those are owned by the same entity share the `cluster_id`, the table is
+/// rebuilt as a whole so the ids are only stable between the rebuilds
+const SQL_CREATE_TABLE_ADDRESS_CLUSTERS: &str = "create table if not exists address_clusters (address text primary key not null, cluster_id integer not null)";
+const SQL_CREATE_INDEX_ADDRESS_CLUSTERS_CLUSTER_ID: &str = "create index if not exists index__address_clusters_cluster_id on address_clusters (cluster_id)";
+const SQL_DELETE_ADDRESS_CLUSTERS: &str = "delete from address_clusters";
+const SQL_INSERT_ADDRESS_CLUSTER: &str =
+    "insert into address_clusters (address, cluster_id) values (?, ?)";
+const SQL_QUERY_CLUSTER_ID_OF_ADDRESS: &str =
+    "select cluster_id from address_clusters where address = ?";
+const SQL_QUERY_CLUSTER_MEMBERS: &str =
+    "select address from address_clusters where cluster_id = ? order by address";
+const SQL_QUERY_SPENT_INPUTS: &str =
+    "select spent_txid, owner from all_coins where is_spent = true order by spent_txid, coin_id";
+const SQL_QUERY_OUTPUTS_OF_TWO_OUTPUT_TXS: &str = "select txid, owner, coin_id = (select min(coin_id) from all_coins as first_coins where first_coins.owner = all_coins.owner) from all_coins where txid in (select txid from all_coins group by txid having count(*) = 2) order by txid, n";
+
+/// Table `depc_sweep`
+/// `txid` is null when the sweep is only made as an unsigned transaction for offline signing,
+/// it's set to the transaction spends the `inputs` (the json array of the spent `[txid, vout]`)
+/// after the operator broadcasts it
+const SQL_CREATE_TABLE_DEPC_SWEEP: &str = "create table if not exists depc_sweep (id integer primary key autoincrement, from_address, to_address, amount, txid, raw_tx, timestamp, bridge_id text not null default 'default')";
+const SQL_INSERT_DEPC_SWEEP: &str = "insert into depc_sweep (from_address, to_address, amount, txid, raw_tx, inputs, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_TEXT_DEPC_SWEEP_RAW_TXS: &str = "select id, raw_tx from depc_sweep where id > ? and typeof(raw_tx) = 'text' order by id limit ?";
+const SQL_UPDATE_DEPC_SWEEP_RAW_TX: &str = "update depc_sweep set raw_tx = ? where id = ?";
+const SQL_QUERY_DEPC_SWEEPS: &str = "select id, from_address, to_address, amount, txid, raw_tx, inputs, timestamp from depc_sweep where bridge_id = ? order by id desc limit ?";
+const SQL_QUERY_UNSIGNED_DEPC_SWEEPS: &str = "select id, from_address, to_address, amount, txid, raw_tx, inputs, timestamp from depc_sweep where bridge_id = ? and txid is null order by id";
+const SQL_SETTLE_UNSIGNED_DEPC_SWEEP: &str =
+    "update depc_sweep set txid = ? where bridge_id = ? and id = ? and txid is null";
+
+/// Table `shadow_actions`
+/// The actions (mint, payout, sweep, refund) the bridge would take, only recorded in shadow mode
+const SQL_CREATE_TABLE_SHADOW_ACTIONS: &str = "create table if not exists shadow_actions (id integer primary key autoincrement, action, source_txid, recipient, amount, timestamp, bridge_id text not null default 'default')";
+const SQL_INSERT_SHADOW_ACTION: &str = "insert into shadow_actions (action, source_txid, recipient, amount, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_SHADOW_ACTIONS: &str = "select id, action, source_txid, recipient, amount, timestamp from shadow_actions where bridge_id = ? order by id desc limit ?";
+
+/// Table `dead_letters`
+/// The transactions (or the outputs of them) cannot be processed while syncing, they are skipped
+/// and kept here for the operator
+const SQL_CREATE_TABLE_DEAD_LETTERS: &str = "create table if not exists dead_letters (id integer primary key autoincrement, height, txid, vout, reason, timestamp, bridge_id text not null default 'default')";
+const SQL_INSERT_DEAD_LETTER: &str = "insert into dead_letters (height, txid, vout, reason, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_DEAD_LETTERS: &str = "select id, height, txid, vout, reason, timestamp from dead_letters where bridge_id = ? order by id desc limit ?";
+const SQL_COUNT_DEAD_LETTERS_SINCE: &str =
+    "select count(*) from dead_letters where bridge_id = ? and timestamp >= ?";
+
+/// Table `pending_actions`
+/// The deposits and withdrawals found while syncing, they're saved with the block and the
+/// workers are notified with the row id. `claimed_at` is set while a worker is processing it,
+/// `done_at` and `txid` are set after it's processed (the transaction is submitted), and
+/// `confirmed_at` is set after the transaction is confirmed. The action failed for a transient
+/// reason is not claimed again before `retry_at`, `attempts` counts the failures. `transfer_time`
+/// is the time of the block the transfer is made in
+const SQL_CREATE_TABLE_PENDING_ACTIONS: &str = "create table if not exists pending_actions (id integer primary key autoincrement, action, source_txid, recipient, amount, timestamp, claimed_at, done_at, txid, bridge_id text not null default 'default', confirmed_at integer)";
+const SQL_CREATE_INDEX_PENDING_ACTIONS_DONE_AT: &str =
+    "create index if not exists index__pending_actions_done_at on pending_actions (done_at)";
+const SQL_INSERT_PENDING_ACTION: &str = "insert into pending_actions (action, source_txid, recipient, amount, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?)";
+const SQL_CLAIM_PENDING_ACTION: &str = "update pending_actions set claimed_at = ?1 where bridge_id = ?2 and id = ?3 and claimed_at is null and done_at is null and coalesce(retry_at, 0) <= ?1 and (held_at is null or released_at is not null)";
+const SQL_QUERY_PENDING_ACTION: &str = "select id, action, source_txid, recipient, amount, timestamp, txid, custody_era from pending_actions where bridge_id = ? and id = ?";
+/// The best indexed height is kept as `sent_height`, the payouts left unconfirmed for blocks
+/// after it are bumped, see `query_stuck_payouts`
+const SQL_COMPLETE_PENDING_ACTION: &str = "update pending_actions set done_at = ?, txid = ?, sent_height = (select max(height) from blocks) where bridge_id = ? and id = ?";
+const SQL_RELEASE_PENDING_ACTION: &str = "update pending_actions set claimed_at = null where bridge_id = ? and id = ? and done_at is null";
+const SQL_QUERY_PENDING_ACTION_ATTEMPTS: &str =
+    "select attempts from pending_actions where bridge_id = ? and id = ?";
+const SQL_DEFER_PENDING_ACTION: &str = "update pending_actions set claimed_at = null, attempts = ?, retry_at = ? where bridge_id = ? and id = ? and done_at is null";
+const SQL_RELEASE_CLAIMED_PENDING_ACTIONS: &str = "update pending_actions set claimed_at = null where bridge_id = ? and claimed_at is not null and done_at is null and id not in (select action_id from depc_payouts where bridge_id = ? and status = 'signed')";
+const SQL_QUERY_CLAIMED_PENDING_ACTIONS: &str = "select id, action, source_txid, recipient, amount, timestamp, txid, custody_era from pending_actions where bridge_id = ? and action = ? and claimed_at is not null and done_at is null order by id";
+const SQL_QUERY_PENDING_ACTION_EXISTS_BY_TXID: &str =
+    "select exists (select 1 from pending_actions where bridge_id = ? and txid = ?)";
+const SQL_QUERY_UNCLAIMED_PENDING_ACTION_IDS: &str = "select id from pending_actions where bridge_id = ? and action = ? and claimed_at is null and done_at is null and (held_at is null or released_at is not null) order by id";
+/// An action over the transfer limits is held until it's released, the workers never claim it
+const SQL_HOLD_PENDING_ACTION: &str = "update pending_actions set held_at = ?, held_reason = ? where bridge_id = ? and id = ? and claimed_at is null and done_at is null";
+const SQL_RELEASE_HELD_PENDING_ACTION: &str = "update pending_actions set released_at = ? where bridge_id = ? and id = ? and held_at is not null and released_at is null";
+const SQL_QUERY_HELD_PENDING_ACTIONS: &str = "select id, action, source_txid, recipient, amount, timestamp, held_reason, held_at, released_at from pending_actions where bridge_id = ? and held_at is not null and released_at is null order by id limit ?";
+const SQL_QUERY_HELD_PENDING_ACTION: &str = "select id, action, source_txid, recipient, amount, timestamp, held_reason, held_at, released_at from pending_actions where bridge_id = ? and id = ? and held_at is not null";
+/// The amount of the actions made to a recipient since a time, the held ones are not counted.
+/// The actions are timed by `transfer_time`, the time of the block the transfer is made in
+const SQL_QUERY_RECIPIENT_VOLUME_SINCE: &str = "select coalesce(sum(amount), 0) from pending_actions where bridge_id = ? and action = ? and recipient = ? and coalesce(transfer_time, timestamp) >= ? and (held_at is null or released_at is not null)";
+const SQL_SET_PENDING_ACTION_TRANSFER_TIME: &str =
+    "update pending_actions set transfer_time = ? where bridge_id = ? and id = ?";
+const SQL_QUERY_PENDING_ACTIONS_BETWEEN: &str = "select id, action, source_txid, recipient, amount, timestamp, txid, custody_era from pending_actions where bridge_id = ? and timestamp between ? and ? order by id";
+/// The actions found since a time by kind, the latency is from being found to being made on the
+/// other chain, the ones skipped in shadow mode have no txid
+const SQL_CONFIRM_PENDING_ACTION: &str = "update pending_actions set confirmed_at = ? where bridge_id = ? and id = ? and txid is not null and confirmed_at is null";
+const SQL_CONFIRM_PENDING_ACTIONS_IN_BLOCK: &str = "update pending_actions set confirmed_at = ?1 where bridge_id = ?2 and confirmed_at is null and txid in (select txid from transactions where block_hash = ?3)";
+/// The seconds from being found to being confirmed of the actions found since a time
+const SQL_QUERY_CONFIRMATION_LATENCIES: &str = "select max(confirmed_at - timestamp, 0) from pending_actions where bridge_id = ? and action = ? and timestamp >= ? and confirmed_at is not null order by 1";
+const SQL_QUERY_PENDING_ACTION_TOTALS: &str = "select action, count(*), coalesce(sum(amount), 0), avg(case when txid is not null then done_at - timestamp end), count(*) - count(done_at) from pending_actions where bridge_id = ? and timestamp >= ? group by action order by action";
+/// The actions not processed yet by kind
+const SQL_QUERY_PENDING_ACTION_BACKLOG: &str = "select action, count(*), coalesce(sum(amount), 0) from pending_actions where bridge_id = ? and done_at is null group by action order by action";
+/// The actions of a kind found before a time and still in an intermediate state by the state,
+/// the ones skipped in shadow mode are done without a txid and never confirmed
+const SQL_QUERY_STUCK_PENDING_ACTIONS: &str = "select case when done_at is not null then 'unconfirmed' when claimed_at is not null then 'claimed' when held_at is not null and released_at is null then 'held' else 'queued' end as state, count(*), min(timestamp) from pending_actions where bridge_id = ? and action = ? and timestamp < ? and (done_at is null or (txid is not null and confirmed_at is null)) group by state order by state";
+
+/// Table `depc_refunds`
+/// The deposits can never be bridged (the recipient cannot be parsed), the DePC is returned to
+/// an input address of the deposit transaction. `txid` and `refunded_at` are set after the
+/// refund is made, `txid` is null for the refunds recorded in shadow mode
+///
+/// The refund is paid to `claim_address` instead when the sender claims it by a message signed
+/// by the key of `refund_address`, `claim_status` is submitted, verified or rejected
+const SQL_CREATE_TABLE_DEPC_REFUNDS: &str = "create table if not exists depc_refunds (id integer primary key autoincrement, depc_txid, vout, refund_address, amount, reason, timestamp, txid, refunded_at, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_DEPC_REFUNDS_DEPC_TXID_VOUT: &str = "create unique index if not exists index__depc_refunds_depc_txid_vout on depc_refunds (depc_txid, vout)";
+const SQL_INSERT_DEPC_REFUND: &str = "insert into depc_refunds (depc_txid, vout, refund_address, amount, reason, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_DUE_DEPC_REFUNDS: &str = "select id, depc_txid, vout, refund_address, amount, reason, timestamp, txid, refunded_at, claim_address, claim_signature, claim_status from depc_refunds where bridge_id = ? and refunded_at is null and timestamp <= ? order by id";
+const SQL_QUERY_DEPC_REFUNDS: &str = "select id, depc_txid, vout, refund_address, amount, reason, timestamp, txid, refunded_at, claim_address, claim_signature, claim_status from depc_refunds where bridge_id = ? order by id desc limit ?";
+const SQL_QUERY_DEPC_REFUND_OF_OUTPUT: &str = "select id, depc_txid, vout, refund_address, amount, reason, timestamp, txid, refunded_at, claim_address, claim_signature, claim_status from depc_refunds where bridge_id = ? and depc_txid = ? and vout = ?";
+const SQL_COUNT_REFUNDS_SINCE: &str =
+    "select count(*) from depc_refunds where bridge_id = ? and timestamp >= ?";
+const SQL_COMPLETE_DEPC_REFUND: &str =
+    "update depc_refunds set txid = ?, refunded_at = ? where bridge_id = ? and id = ?";
+/// A claim replaces the one rejected only, the one submitted or verified is kept
+const SQL_CLAIM_DEPC_REFUND: &str = "update depc_refunds set claim_address = ?, claim_signature = ?, claim_status = 'submitted' where bridge_id = ? and id = ? and refunded_at is null and (claim_status is null or claim_status = 'rejected')";
+const SQL_QUERY_SUBMITTED_DEPC_REFUND_CLAIMS: &str = "select id, depc_txid, vout, refund_address, amount, reason, timestamp, txid, refunded_at, claim_address, claim_signature, claim_status from depc_refunds where bridge_id = ? and refunded_at is null and claim_status = 'submitted' order by id";
+const SQL_SET_DEPC_REFUND_CLAIM_STATUS: &str = "update depc_refunds set claim_status = ? where bridge_id = ? and id = ? and claim_status = 'submitted'";
+
+/// Table `prices`
+/// The prices of 1 DePC in the fiat currencies, recorded by the price oracle as they're fetched
+const SQL_CREATE_TABLE_PRICES: &str = "create table if not exists prices (currency text not null, timestamp integer not null, price real not null, source text, primary key (currency, timestamp))";
+const SQL_INSERT_PRICE: &str =
+    "insert or replace into prices (currency, timestamp, price, source) values (?, ?, ?, ?)";
+const SQL_QUERY_PRICE_AT: &str = "select price from prices where currency = ? and timestamp <= ? order by timestamp desc limit 1";
+
+/// Table `deposit_intents`
+/// The deposits registered before paying without OP_RETURN, a plain payment from `sender` to
+/// the custody address is matched to the intent while syncing, and the output is recorded
+const SQL_CREATE_TABLE_DEPOSIT_INTENTS: &str = "create table if not exists deposit_intents (id integer primary key autoincrement, sender, recipient, created_at, matched_txid, matched_vout, matched_at, bridge_id text not null default 'default')";
+const SQL_CREATE_INDEX_DEPOSIT_INTENTS_SENDER: &str =
+    "create index if not exists index__deposit_intents_sender on deposit_intents (sender)";
+const SQL_INSERT_DEPOSIT_INTENT: &str =
+    "insert into deposit_intents (sender, recipient, created_at, bridge_id) values (?, ?, ?, ?)";
+const SQL_QUERY_DEPOSIT_INTENT: &str = "select id, sender, recipient, created_at, matched_txid, matched_vout, matched_at from deposit_intents where bridge_id = ? and id = ?";
+const SQL_QUERY_OPEN_DEPOSIT_INTENT: &str = "select id, sender, recipient, created_at, matched_txid, matched_vout, matched_at from deposit_intents where bridge_id = ? and sender = ? and matched_txid is null and created_at >= ? and created_at <= ? order by id limit 1";
+const SQL_MATCH_DEPOSIT_INTENT: &str = "update deposit_intents set matched_txid = ?, matched_vout = ?, matched_at = ? where bridge_id = ? and id = ? and matched_txid is null";
+/// Table `deposit_wallets`
+/// The account xpub of each bridge the deposit addresses are derived from, an xpub is used by
+/// one bridge only
+const SQL_CREATE_TABLE_DEPOSIT_WALLETS: &str = "create table if not exists deposit_wallets (bridge_id text primary key, xpub text not null unique)";
+const SQL_SAVE_DEPOSIT_XPUB: &str = "insert into deposit_wallets (bridge_id, xpub) values (?, ?) on conflict (bridge_id) do update set xpub = excluded.xpub";
+const SQL_QUERY_DEPOSIT_XPUB: &str = "select xpub from deposit_wallets where bridge_id = ?";
+/// Table `deposit_addresses`
+/// The addresses derived for the users, `derivation_index` is the index of the receiving
+/// address of the xpub, any payment to an address is credited to its recipient while syncing
+const SQL_CREATE_TABLE_DEPOSIT_ADDRESSES: &str = "create table if not exists deposit_addresses (address text not null, derivation_index integer not null, recipient text not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_DEPOSIT_ADDRESSES_ADDRESS: &str = "create unique index if not exists index__deposit_addresses_address on deposit_addresses (address)";
+const SQL_CREATE_UNIQUE_INDEX_DEPOSIT_ADDRESSES_BRIDGE_ID_DERIVATION_INDEX: &str = "create unique index if not exists index__deposit_addresses_bridge_id_derivation_index on deposit_addresses (bridge_id, derivation_index)";
+/// The addresses given out before the paths are recorded are the receiving addresses
+const SQL_FILL_DEPOSIT_ADDRESS_DERIVATION_PATHS: &str = "update deposit_addresses set derivation_path = '0/' || derivation_index where derivation_path is null";
+const SQL_INSERT_DEPOSIT_ADDRESS: &str = "insert into deposit_addresses (address, derivation_index, derivation_path, recipient, created_at, bridge_id) values (?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_DEPOSIT_ADDRESS: &str = "select address, derivation_index, derivation_path, recipient, created_at from deposit_addresses where bridge_id = ? and address = ?";
+const SQL_QUERY_DEPOSIT_ADDRESSES_BALANCE: &str = "select coalesce(sum(address_balances.balance), 0) from deposit_addresses join address_balances on address_balances.address = deposit_addresses.address where deposit_addresses.bridge_id = ?";
+const SQL_QUERY_NEXT_DEPOSIT_ADDRESS_INDEX: &str =
+    "select coalesce(max(derivation_index) + 1, 0) from deposit_addresses where bridge_id = ?";
+/// Table `deposit_roots`
+/// The Merkle roots of the confirmed deposits published by the bridge, a root covers the
+/// deposits confirmed before `covered_until`, `memo_signature` is the Solana transaction the
+/// root is published by
+const SQL_CREATE_TABLE_DEPOSIT_ROOTS: &str = "create table if not exists deposit_roots (id integer primary key autoincrement, root text not null, leaf_count integer not null, covered_until integer not null, created_at integer not null, memo_signature text, bridge_id text not null default 'default')";
+const SQL_INSERT_DEPOSIT_ROOT: &str = "insert into deposit_roots (root, leaf_count, covered_until, created_at, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_QUERY_LATEST_DEPOSIT_ROOT: &str = "select id, root, leaf_count, covered_until, created_at, memo_signature from deposit_roots where bridge_id = ? order by id desc limit 1";
+const SQL_UPDATE_DEPOSIT_ROOT_MEMO_SIGNATURE: &str =
+    "update deposit_roots set memo_signature = ? where bridge_id = ? and id = ?";
+/// Table `parameter_changes`
+/// The changes of the bridge parameters queued by the admin, a change takes effect at
+/// `activates_at` unless it's cancelled before that
+const SQL_CREATE_TABLE_PARAMETER_CHANGES: &str = "create table if not exists parameter_changes (id integer primary key autoincrement, name text not null, value text not null, created_at integer not null, activates_at integer not null, cancelled_at integer, bridge_id text not null default 'default')";
+const SQL_INSERT_PARAMETER_CHANGE: &str = "insert into parameter_changes (name, value, created_at, activates_at, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_QUERY_PARAMETER_CHANGE: &str = "select id, name, value, created_at, activates_at, cancelled_at from parameter_changes where bridge_id = ? and id = ?";
+const SQL_QUERY_PARAMETER_CHANGES: &str = "select id, name, value, created_at, activates_at, cancelled_at from parameter_changes where bridge_id = ? order by id desc limit ?";
+const SQL_CANCEL_PARAMETER_CHANGE: &str = "update parameter_changes set cancelled_at = ? where bridge_id = ? and id = ? and cancelled_at is null and activates_at > ?";
+const SQL_QUERY_EFFECTIVE_PARAMETER_VALUE: &str = "select value from parameter_changes where bridge_id = ? and name = ? and cancelled_at is null and activates_at <= ? order by activates_at desc, id desc limit 1";
+/// Table `jobs`
+/// The periodic jobs of the process and their last and next runs
+const SQL_CREATE_TABLE_JOBS: &str = "create table if not exists jobs (name text primary key, schedule text not null, enabled integer not null, running integer not null default 0, last_started_at integer, last_finished_at integer, last_error text, next_run_at integer)";
+const SQL_SAVE_JOB: &str = "insert into jobs (name, schedule, enabled, next_run_at) values (?, ?, ?, ?) on conflict (name) do update set schedule = excluded.schedule, enabled = excluded.enabled, next_run_at = excluded.next_run_at, running = 0";
+const SQL_QUERY_JOB: &str = "select name, schedule, enabled, running, last_started_at, last_finished_at, last_error, next_run_at from jobs where name = ?";
+const SQL_QUERY_JOBS: &str = "select name, schedule, enabled, running, last_started_at, last_finished_at, last_error, next_run_at from jobs order by name";
+const SQL_START_JOB: &str =
+    "update jobs set running = 1, last_started_at = ?, next_run_at = ? where name = ?";
+const SQL_SET_JOB_NEXT_RUN: &str = "update jobs set next_run_at = ? where name = ?";
+const SQL_FINISH_JOB: &str =
+    "update jobs set running = 0, last_finished_at = ?, last_error = ? where name = ?";
+
+/// Table `withdraw_signatures`
+/// The Solana transactions used by the withdrawals, a transaction is never used twice
+const SQL_CREATE_TABLE_WITHDRAW_SIGNATURES: &str = "create table if not exists withdraw_signatures (signature text not null, depc_txid text not null, timestamp integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_WITHDRAW_SIGNATURES_BRIDGE_ID_SIGNATURE: &str = "create unique index if not exists index__withdraw_signatures_bridge_id_signature on withdraw_signatures (bridge_id, signature)";
+const SQL_INSERT_WITHDRAW_SIGNATURE: &str = "insert or ignore into withdraw_signatures (signature, depc_txid, timestamp, bridge_id) values (?, ?, ?, ?)";
+const SQL_QUERY_WITHDRAW_SIGNATURE: &str =
+    "select depc_txid from withdraw_signatures where bridge_id = ? and signature = ?";
+
+/// Table `ata_owners`
+/// The wallets own the token accounts, it's shared by the bridges
+const SQL_CREATE_TABLE_ATA_OWNERS: &str =
+    "create table if not exists ata_owners (account text primary key, owner text not null)";
+const SQL_INSERT_ATA_OWNER: &str =
+    "insert or replace into ata_owners (account, owner) values (?, ?)";
+const SQL_QUERY_ATA_OWNER: &str = "select owner from ata_owners where account = ?";
+
+/// Table `outgoing_transactions`
+/// The signed Solana transactions saved before they're submitted, the recipients are a json
+/// array of [address, amount]. The settled ones are kept after they're acknowledged
+const SQL_CREATE_TABLE_OUTGOING_TRANSACTIONS: &str = "create table if not exists outgoing_transactions (bridge_id text not null, signature text not null, raw_tx blob not null, last_valid_block_height integer not null, recipients text not null, status text not null, created_at integer not null, settled_at integer, acknowledged_at integer, primary key (bridge_id, signature))";
+const SQL_INSERT_OUTGOING_TRANSACTION: &str = "insert into outgoing_transactions (bridge_id, signature, raw_tx, last_valid_block_height, recipients, status, created_at) values (?, ?, ?, ?, ?, 'pending', ?)";
+const SQL_SET_OUTGOING_TRANSACTION_STATUS: &str = "update outgoing_transactions set status = ?, settled_at = ? where bridge_id = ? and signature = ?";
+const SQL_QUERY_UNACKNOWLEDGED_OUTGOING_TRANSACTIONS: &str = "select signature, raw_tx, last_valid_block_height, recipients, status, created_at, settled_at from outgoing_transactions where bridge_id = ? and acknowledged_at is null order by created_at, rowid";
+const SQL_ACKNOWLEDGE_OUTGOING_TRANSACTION: &str = "update outgoing_transactions set acknowledged_at = ? where bridge_id = ? and signature = ? and acknowledged_at is null";
+
+/// Table `solana_slots`
+/// The samples of the Solana slots and their block times, the slots between the samples are
+/// interpolated
+const SQL_CREATE_TABLE_SOLANA_SLOTS: &str = "create table if not exists solana_slots (slot integer primary key, epoch integer not null, block_time integer not null)";
+const SQL_CREATE_INDEX_SOLANA_SLOTS_BLOCK_TIME: &str =
+    "create index if not exists index__solana_slots_block_time on solana_slots (block_time)";
+const SQL_INSERT_SOLANA_SLOT: &str =
+    "insert or replace into solana_slots (slot, epoch, block_time) values (?, ?, ?)";
+const SQL_QUERY_SOLANA_SLOT_BEFORE_TIME: &str = "select slot, epoch, block_time from solana_slots where block_time <= ? order by block_time desc, slot desc limit 1";
+const SQL_QUERY_SOLANA_SLOT_AFTER_TIME: &str = "select slot, epoch, block_time from solana_slots where block_time >= ? order by block_time, slot limit 1";
+const SQL_QUERY_SOLANA_SLOT_BEFORE: &str =
+    "select slot, epoch, block_time from solana_slots where slot <= ? order by slot desc limit 1";
+const SQL_QUERY_SOLANA_SLOT_AFTER: &str =
+    "select slot, epoch, block_time from solana_slots where slot >= ? order by slot limit 1";
+
+/// Table `solana_cursors`
+/// The signature of a Solana account the scans read after, see `bridge::scan_signatures`
+const SQL_CREATE_TABLE_SOLANA_CURSORS: &str = "create table if not exists solana_cursors (account text not null, signature text not null, slot integer not null, updated_at integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_SOLANA_CURSORS_BRIDGE_ID_ACCOUNT: &str = "create unique index if not exists index__solana_cursors_bridge_id_account on solana_cursors (bridge_id, account)";
+const SQL_SET_SOLANA_CURSOR: &str = "insert into solana_cursors (account, signature, slot, updated_at, bridge_id) values (?, ?, ?, ?, ?) on conflict (bridge_id, account) do update set signature = excluded.signature, slot = excluded.slot, updated_at = excluded.updated_at";
+const SQL_QUERY_SOLANA_CURSOR: &str = "select account, signature, slot, updated_at from solana_cursors where bridge_id = ? and account = ?";
+
+/// Table `ledger_entries`
+/// The double-entry postings of the bridge movements, every entry debits one account and
+/// credits another by the same amount, see `ledger::Ledger`
+const SQL_CREATE_TABLE_LEDGER_ENTRIES: &str = "create table if not exists ledger_entries (id integer primary key autoincrement, event text not null, debit text not null, credit text not null, amount integer not null, reference text not null, timestamp integer not null, bridge_id text not null default 'default')";
+const SQL_INSERT_LEDGER_ENTRY: &str = "insert into ledger_entries (event, debit, credit, amount, reference, timestamp, bridge_id) values (?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_LEDGER_ENTRIES: &str = "select id, event, debit, credit, amount, reference, timestamp from ledger_entries where bridge_id = ? order by id desc limit ?";
+const SQL_QUERY_LEDGER_EVENT_TOTAL: &str = "select coalesce(sum(amount), 0) from ledger_entries where bridge_id = ? and event = ? and timestamp >= ?";
+const SQL_QUERY_LEDGER_ACCOUNT_TOTALS: &str = "select account, sum(debit), sum(credit) from (select debit as account, amount as debit, 0 as credit from ledger_entries where bridge_id = ?1 union all select credit, 0, amount from ledger_entries where bridge_id = ?1) group by account order by account";
+const SQL_QUERY_LEDGER_DAILY_TOTALS: &str = "select timestamp / 86400, event, sum(amount) from ledger_entries where bridge_id = ? and credit = ? and timestamp >= ? and timestamp <= ? group by timestamp / 86400, event order by timestamp / 86400, event";
+
+/// Table `events`
+/// The bridge movements in the order they happen, the consumers resume from the last sequence
+/// number they have seen, see `ledger::Ledger`
+const SQL_CREATE_TABLE_EVENTS: &str = "create table if not exists events (seq integer primary key autoincrement, event text not null, amount integer not null, reference text not null, timestamp integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_INDEX_EVENTS_BRIDGE_ID_SEQ: &str =
+    "create index if not exists index__events_bridge_id_seq on events (bridge_id, seq)";
+const SQL_INSERT_EVENT: &str =
+    "insert into events (event, amount, reference, timestamp, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_QUERY_EVENTS_SINCE: &str = "select seq, event, amount, reference, timestamp from events where bridge_id = ? and seq > ? order by seq limit ?";
+
+/// Table `fee_sweeps`
+/// The bridge fees transferred to the treasury, see `bridge::sweep_fees`. A sweep is 'pending'
+/// from before it's sent until it's 'sent' (the signature is empty until then) or 'failed'
+const SQL_CREATE_TABLE_FEE_SWEEPS: &str = "create table if not exists fee_sweeps (id integer primary key autoincrement, treasury text not null, amount integer not null, signature text not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_INSERT_PENDING_FEE_SWEEP: &str = "insert into fee_sweeps (treasury, amount, signature, created_at, bridge_id, status) values (?, ?, '', ?, ?, 'pending')";
+const SQL_COMPLETE_FEE_SWEEP: &str = "update fee_sweeps set signature = ?, status = 'sent' where bridge_id = ? and id = ? and status = 'pending'";
+const SQL_FAIL_FEE_SWEEP: &str =
+    "update fee_sweeps set status = 'failed' where bridge_id = ? and id = ? and status = 'pending'";
+const SQL_QUERY_FEE_SWEEPS: &str = "select id, treasury, amount, signature, created_at from fee_sweeps where bridge_id = ? and status = 'sent' order by id desc limit ?";
+const SQL_QUERY_PENDING_FEE_SWEEPS: &str = "select id, treasury, amount, signature, created_at from fee_sweeps where bridge_id = ? and status = 'pending' order by id";
+const SQL_QUERY_FEE_SWEEP_EXISTS_BY_SIGNATURE: &str = "select exists(select 1 from fee_sweeps where bridge_id = ? and signature = ? and status = 'sent')";
+
+/// Table `dust_sweeps`
+/// The tokens stranded in the auxiliary accounts of the authority and consolidated back to its
+/// token account, a row for each account, see `bridge::sweep_dust`
+const SQL_CREATE_TABLE_DUST_SWEEPS: &str = "create table if not exists dust_sweeps (id integer primary key autoincrement, account text not null, amount integer not null, signature text not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_INSERT_DUST_SWEEP: &str = "insert into dust_sweeps (account, amount, signature, created_at, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_QUERY_DUST_SWEEPS: &str = "select id, account, amount, signature, created_at from dust_sweeps where bridge_id = ? order by id desc limit ?";
+
+/// Table `multisig_payouts`
+/// The withdrawals from the multisig custody collecting the signatures of the co-signers, the
+/// raw transaction has the signatures merged so far and `prevtxs` is the json array of the spent
+/// coins the signers need, see `bridge::start_multisig_payout`
+const SQL_CREATE_TABLE_MULTISIG_PAYOUTS: &str = "create table if not exists multisig_payouts (id integer primary key autoincrement, action_id integer not null, raw_tx text not null, prevtxs text not null, status text not null, txid text, created_at integer not null, sent_at integer, bridge_id text not null default 'default')";
+const SQL_INSERT_MULTISIG_PAYOUT: &str = "insert into multisig_payouts (action_id, raw_tx, prevtxs, status, created_at, bridge_id) values (?, ?, ?, 'collecting', ?, ?)";
+const SQL_QUERY_MULTISIG_PAYOUT: &str = "select id, action_id, raw_tx, prevtxs, status, txid, created_at, sent_at from multisig_payouts where bridge_id = ? and id = ?";
+const SQL_QUERY_MULTISIG_PAYOUT_BY_ACTION: &str = "select id, action_id, raw_tx, prevtxs, status, txid, created_at, sent_at from multisig_payouts where bridge_id = ? and action_id = ? order by id desc limit 1";
+const SQL_QUERY_COLLECTING_MULTISIG_PAYOUTS: &str = "select id, action_id, raw_tx, prevtxs, status, txid, created_at, sent_at from multisig_payouts where bridge_id = ? and status = 'collecting' order by id";
+const SQL_UPDATE_MULTISIG_PAYOUT_RAW_TX: &str = "update multisig_payouts set raw_tx = ? where bridge_id = ? and id = ? and status = 'collecting'";
+const SQL_COMPLETE_MULTISIG_PAYOUT: &str = "update multisig_payouts set status = 'sent', txid = ?, sent_at = ? where bridge_id = ? and id = ? and status = 'collecting'";
+
+/// Table `depc_payouts`
+/// The signed transactions of the withdrawals saved before they're sent, the signed ones are
+/// in doubt until the node knows them, their withdrawals are left claimed
+const SQL_CREATE_TABLE_DEPC_PAYOUTS: &str = "create table if not exists depc_payouts (id integer primary key autoincrement, bridge_id text not null, action_id integer not null, txid text not null, raw_tx text not null, status text not null, created_at integer not null, sent_at integer)";
+const SQL_INSERT_DEPC_PAYOUT: &str = "insert into depc_payouts (bridge_id, action_id, txid, raw_tx, status, created_at) values (?, ?, ?, ?, 'signed', ?)";
+const SQL_QUERY_SIGNED_DEPC_PAYOUTS: &str = "select id, action_id, txid, raw_tx, status, created_at, sent_at from depc_payouts where bridge_id = ? and status = 'signed' order by id";
+const SQL_COMPLETE_DEPC_PAYOUT: &str = "update depc_payouts set status = 'sent', sent_at = ? where bridge_id = ? and id = ? and status = 'signed'";
+
+/// Table `multisig_signatures`
+/// The copies of the payouts signed by the co-signers, they're submitted, then merged into the
+/// payout or rejected by the node with the reason
+const SQL_CREATE_TABLE_MULTISIG_SIGNATURES: &str = "create table if not exists multisig_signatures (id integer primary key autoincrement, payout_id integer not null, raw_tx text not null, status text not null, error text, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_INSERT_MULTISIG_SIGNATURE: &str = "insert into multisig_signatures (payout_id, raw_tx, status, created_at, bridge_id) values (?, ?, 'submitted', ?, ?)";
+const SQL_QUERY_SUBMITTED_MULTISIG_SIGNATURES: &str = "select id, payout_id, raw_tx, status, error, created_at from multisig_signatures where bridge_id = ? and payout_id = ? and status = 'submitted' order by id";
+const SQL_SET_MULTISIG_SIGNATURE_STATUS: &str =
+    "update multisig_signatures set status = ?, error = ? where bridge_id = ? and id = ?";
+
+/// Table `payout_bumps`
+/// The replacement chains of the stuck payouts, `rbf` replaces `bumped_txid` by `txid`, and
+/// `cpfp` spends the change of `bumped_txid` by child `txid`. `fee` is the fee added by the
+/// bump, see `bridge::bump_stuck_payouts`. The signed bump is saved `pending` before it's sent,
+/// it's in doubt until the node knows it, and the payout takes it when it's `sent`
+const SQL_CREATE_TABLE_PAYOUT_BUMPS: &str = "create table if not exists payout_bumps (id integer primary key autoincrement, action_id integer not null, kind text not null, bumped_txid text not null, txid text not null, fee integer not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_INSERT_PAYOUT_BUMP: &str = "insert into payout_bumps (action_id, kind, bumped_txid, txid, raw_tx, fee, status, created_at, bridge_id) values (?, ?, ?, ?, ?, ?, 'pending', ?, ?)";
+const SQL_QUERY_PAYOUT_BUMPS: &str = "select id, action_id, kind, bumped_txid, txid, raw_tx, fee, status, created_at from payout_bumps where bridge_id = ? and action_id = ? and status = 'sent' order by id";
+const SQL_QUERY_PENDING_PAYOUT_BUMPS: &str = "select id, action_id, kind, bumped_txid, txid, raw_tx, fee, status, created_at from payout_bumps where bridge_id = ? and status = 'pending' order by id";
+const SQL_COMPLETE_PAYOUT_BUMP: &str =
+    "update payout_bumps set status = 'sent' where bridge_id = ? and id = ? and status = 'pending'";
+const SQL_QUERY_STUCK_PAYOUTS: &str = "select id, action, source_txid, recipient, amount, timestamp, txid, custody_era from pending_actions where bridge_id = ?1 and action = 'withdraw' and txid is not null and confirmed_at is null and sent_height <= ?2 and id not in (select action_id from payout_bumps where bridge_id = ?1 and status = 'pending') order by id";
+const SQL_REPLACE_PAYOUT_TXID: &str = "update pending_actions set txid = ?, sent_height = ? where bridge_id = ? and id = ? and confirmed_at is null";
+const SQL_UPDATE_PAYOUT_SENT_HEIGHT: &str = "update pending_actions set sent_height = ? where bridge_id = ? and id = ? and confirmed_at is null";
+/// A replaced payout might be confirmed instead of its replacement, the action takes the txid
+/// of the confirmed one back
+const SQL_RESTORE_REPLACED_PAYOUTS_IN_BLOCK: &str = "update pending_actions set txid = (select b.bumped_txid from payout_bumps b where b.bridge_id = pending_actions.bridge_id and b.action_id = pending_actions.id and b.kind = 'rbf' and b.status = 'sent' and b.bumped_txid in (select txid from transactions where block_hash = ?2) limit 1) where bridge_id = ?1 and confirmed_at is null and id in (select action_id from payout_bumps where bridge_id = ?1 and kind = 'rbf' and status = 'sent' and bumped_txid in (select txid from transactions where block_hash = ?2))";
+
+/// Table `recorded_transactions`
+/// The outputs the pending actions are made for, an output recorded is never processed again
+/// when its block is scanned again, e.g. after a restart. The rows of the deposits removed by
+/// a reorg are removed with them
+const SQL_CREATE_TABLE_RECORDED_TRANSACTIONS: &str = "create table if not exists recorded_transactions (id integer primary key autoincrement, txid text not null, vout integer not null, action text not null, action_id integer not null, recorded_at integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_RECORDED_TRANSACTIONS_BRIDGE_ID_TXID_VOUT: &str = "create unique index if not exists unique_index__recorded_transactions_bridge_id_txid_vout on recorded_transactions (bridge_id, txid, vout)";
+const SQL_INSERT_RECORDED_TRANSACTION: &str = "insert into recorded_transactions (txid, vout, action, action_id, recorded_at, bridge_id) values (?, ?, ?, ?, ?, ?) on conflict (bridge_id, txid, vout) do nothing";
+const SQL_QUERY_RECORDED_TRANSACTION: &str = "select id, txid, vout, action, action_id, recorded_at from recorded_transactions where bridge_id = ? and txid = ? and vout = ?";
+
+/// Table `transfer_fees`
+/// The bridge fees charged on the deposits and the withdrawals, `amount` is the one received
+/// and the pending action is made for the rest. The fees are posted to the ledger as well, the
+/// rows of the deposits removed by a reorg are removed with them
+const SQL_CREATE_TABLE_TRANSFER_FEES: &str = "create table if not exists transfer_fees (id integer primary key autoincrement, action text not null, source_txid text not null, action_id integer not null, amount integer not null, fee integer not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_TRANSFER_FEES_BRIDGE_ID_ACTION_ID: &str = "create unique index if not exists unique_index__transfer_fees_bridge_id_action_id on transfer_fees (bridge_id, action_id)";
+const SQL_INSERT_TRANSFER_FEE: &str = "insert into transfer_fees (action, source_txid, action_id, amount, fee, created_at, bridge_id) values (?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_TRANSFER_FEES: &str = "select id, action, source_txid, action_id, amount, fee, created_at from transfer_fees where bridge_id = ? order by id desc limit ?";
+
+/// Table `custody_inflows`
+/// The payments to the custody addresses which make no bridge action, e.g. without OP_RETURN
+/// or under the deposit threshold, they're left `unclassified` until an operator resolves
+/// them. The rows above the height of a reorg are removed
+const SQL_CREATE_TABLE_CUSTODY_INFLOWS: &str = "create table if not exists custody_inflows (id integer primary key autoincrement, txid text not null, vout integer not null, address text not null, amount integer not null, reason text not null, height integer not null, status text not null default 'unclassified', resolution text, found_at integer not null, resolved_at integer, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_CUSTODY_INFLOWS_BRIDGE_ID_TXID_VOUT: &str = "create unique index if not exists unique_index__custody_inflows_bridge_id_txid_vout on custody_inflows (bridge_id, txid, vout)";
+const SQL_INSERT_CUSTODY_INFLOW: &str = "insert into custody_inflows (txid, vout, address, amount, reason, height, found_at, bridge_id) values (?, ?, ?, ?, ?, ?, ?, ?) on conflict (bridge_id, txid, vout) do nothing";
+const SQL_QUERY_UNCLASSIFIED_CUSTODY_INFLOWS: &str = "select id, txid, vout, address, amount, reason, height, status, resolution, found_at, resolved_at from custody_inflows where bridge_id = ? and status = 'unclassified' order by id desc limit ?";
+const SQL_QUERY_CUSTODY_INFLOW: &str = "select id, txid, vout, address, amount, reason, height, status, resolution, found_at, resolved_at from custody_inflows where bridge_id = ? and id = ?";
+const SQL_RESOLVE_CUSTODY_INFLOW: &str = "update custody_inflows set status = 'resolved', resolution = ?, resolved_at = ? where bridge_id = ? and id = ? and status = 'unclassified'";
+
+/// Table `block_digests`
+/// The digest of the bridge events of every synced block (see `bridge::digest_block_events`),
+/// a block synced again replaces its digest. The rows above the height of a reorg are removed
+const SQL_CREATE_TABLE_BLOCK_DIGESTS: &str = "create table if not exists block_digests (id integer primary key autoincrement, height integer not null, block_hash text not null, event_count integer not null, digest text not null, created_at integer not null, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_BLOCK_DIGESTS_BRIDGE_ID_HEIGHT: &str = "create unique index if not exists unique_index__block_digests_bridge_id_height on block_digests (bridge_id, height)";
+const SQL_INSERT_BLOCK_DIGEST: &str = "insert into block_digests (height, block_hash, event_count, digest, created_at, bridge_id) values (?, ?, ?, ?, ?, ?) on conflict (bridge_id, height) do update set block_hash = excluded.block_hash, event_count = excluded.event_count, digest = excluded.digest, created_at = excluded.created_at";
+const SQL_QUERY_BLOCK_DIGESTS_BETWEEN: &str = "select height, block_hash, event_count, digest, created_at from block_digests where bridge_id = ? and height between ? and ? order by height";
+
+/// Table `catch_up_plans`
+/// The catch-ups after the downtime of the bridge, the blocks `from_height..=to_height` are
+/// indexed before the backlog of the pending actions is executed, see `bridge::start_catch_up`.
+/// The totals are set when the backlog is planned.
+const SQL_CREATE_TABLE_CATCH_UP_PLANS: &str = "create table if not exists catch_up_plans (id integer primary key autoincrement, from_height integer not null, to_height integer not null, status text not null, deposits integer not null default 0, deposit_amount integer not null default 0, withdrawals integer not null default 0, withdraw_amount integer not null default 0, started_at integer not null, planned_at integer, confirmed_at integer, bridge_id text not null default 'default')";
+const SQL_INSERT_CATCH_UP_PLAN: &str = "insert into catch_up_plans (from_height, to_height, status, started_at, bridge_id) values (?, ?, ?, ?, ?)";
+const SQL_QUERY_LATEST_CATCH_UP_PLAN: &str = "select id, from_height, to_height, status, deposits, deposit_amount, withdrawals, withdraw_amount, started_at, planned_at, confirmed_at from catch_up_plans where bridge_id = ? order by id desc limit 1";
+const SQL_EXTEND_CATCH_UP_PLAN: &str = "update catch_up_plans set to_height = ?1 where bridge_id = ?2 and id = ?3 and planned_at is null and to_height < ?1";
+const SQL_PLAN_CATCH_UP: &str = "update catch_up_plans set status = ?, deposits = ?, deposit_amount = ?, withdrawals = ?, withdraw_amount = ?, planned_at = ? where bridge_id = ? and id = ? and planned_at is null";
+const SQL_CONFIRM_CATCH_UP_PLAN: &str = "update catch_up_plans set status = ?, confirmed_at = ? where bridge_id = ? and id = ? and status = ? and confirmed_at is null";
+
+/// Table `watchlist`
+/// The DePC and Solana addresses watched by the operators, it's shared by the bridges
+const SQL_CREATE_TABLE_WATCHLIST: &str = "create table if not exists watchlist (chain text not null, address text not null, label text not null, webhook text, created_at integer not null, primary key (chain, address))";
+const SQL_SAVE_WATCHED_ADDRESS: &str = "insert into watchlist (chain, address, label, webhook, created_at) values (?, ?, ?, ?, ?) on conflict (chain, address) do update set label = excluded.label, webhook = excluded.webhook";
+const SQL_DELETE_WATCHED_ADDRESS: &str = "delete from watchlist where chain = ? and address = ?";
+const SQL_QUERY_WATCHLIST: &str =
+    "select chain, address, label, webhook, created_at from watchlist order by chain, address";
+const SQL_QUERY_WATCHED_ADDRESSES: &str = "select address from watchlist where chain = ?";
+
+/// Table `watch_events`
+/// The activities of the watched addresses, an event is delivered to the webhook of the address
+/// once
+const SQL_CREATE_TABLE_WATCH_EVENTS: &str = "create table if not exists watch_events (id integer primary key autoincrement, chain text not null, address text not null, txid text not null, asset text not null, direction text not null, net_change text not null, timestamp integer not null, delivered_at integer, attempts integer not null default 0, last_error_code text)";
+const SQL_CREATE_UNIQUE_INDEX_WATCH_EVENTS_CHAIN_ADDRESS_TXID_ASSET: &str = "create unique index if not exists index__watch_events_chain_address_txid_asset on watch_events (chain, address, txid, asset)";
+const SQL_INSERT_WATCH_EVENT: &str = "insert or ignore into watch_events (chain, address, txid, asset, direction, net_change, timestamp) values (?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_WATCH_EVENTS: &str = "select id, chain, address, txid, asset, direction, net_change, timestamp, delivered_at, last_error_code from watch_events where ?1 is null or address = ?1 order by id desc limit ?2";
+const SQL_QUERY_UNDELIVERED_WATCH_EVENTS: &str = "select e.id, e.chain, e.address, e.txid, e.asset, e.direction, e.net_change, e.timestamp, e.delivered_at, e.last_error_code, w.webhook from watch_events e join watchlist w on w.chain = e.chain and w.address = e.address where e.delivered_at is null and w.webhook is not null and e.attempts < ? order by e.id limit ?";
+const SQL_FINISH_WATCH_EVENT_DELIVERY: &str =
+    "update watch_events set delivered_at = ?, last_error_code = ?, attempts = attempts + 1 where id = ?";
+const SQL_QUERY_SPENT_VALUES_BY_OWNER: &str =
+    "select owner, sum(value) from all_coins where spent_txid = ? and owner is not null group by owner";
+
+/// Table `checkpoint`
+/// The trusted block the chain index is bootstrapped from, the blocks and coins below it are not
+/// indexed, see `bridge::bootstrap_from_checkpoint`
+const SQL_CREATE_TABLE_CHECKPOINT: &str = "create table if not exists checkpoint (id integer primary key check (id = 1), height integer not null, hash text not null, time integer not null, created_at integer not null)";
+const SQL_INSERT_CHECKPOINT: &str =
+    "insert into checkpoint (id, height, hash, time, created_at) values (1, ?, ?, ?, ?)";
+const SQL_QUERY_CHECKPOINT: &str = "select height, hash, time, created_at from checkpoint";
+
+/// Table `unavailable_heights`
+/// The heights the blocks or the transactions of are pruned or missing on the DePC node, the
+/// syncing skips them and the coins of them are not indexed
+const SQL_CREATE_TABLE_UNAVAILABLE_HEIGHTS: &str = "create table if not exists unavailable_heights (height integer primary key, reason text not null, recorded_at integer not null)";
+const SQL_INSERT_UNAVAILABLE_HEIGHT: &str =
+    "insert or ignore into unavailable_heights (height, reason, recorded_at) values (?, ?, ?)";
+const SQL_QUERY_UNAVAILABLE_HEIGHTS: &str =
+    "select height, reason, recorded_at from unavailable_heights order by height";
+
+/// Table `relay_counts`
+/// The transactions relayed by `POST /solana/post_tx` for a fee payer each day (the days since
+/// the epoch), a fee payer is limited to a quota of them a day
+const SQL_CREATE_TABLE_RELAY_COUNTS: &str = "create table if not exists relay_counts (fee_payer text not null, day integer not null, count integer not null, bridge_id text not null default 'default', primary key (bridge_id, fee_payer, day))";
+const SQL_ADD_RELAY: &str = "insert into relay_counts (fee_payer, day, count, bridge_id) values (?1, ?2, 1, ?4) on conflict (bridge_id, fee_payer, day) do update set count = count + 1 where count < ?3";
+
+/// Table `idempotency_keys`
+/// The responses of the POST requests with header `Idempotency-Key`, they are replayed for the
+/// retries of the requests, `status` is null while the first request is being served
+const SQL_CREATE_TABLE_IDEMPOTENCY_KEYS: &str = "create table if not exists idempotency_keys (idempotency_key text not null, request_hash text not null, status integer, body blob, created_at integer not null, bridge_id text not null default 'default', primary key (bridge_id, idempotency_key))";
+const SQL_CLAIM_IDEMPOTENCY_KEY: &str = "insert into idempotency_keys (idempotency_key, request_hash, created_at, bridge_id) values (?1, ?2, ?3, ?5) on conflict (bridge_id, idempotency_key) do update set request_hash = excluded.request_hash, status = null, body = null, created_at = excluded.created_at where idempotency_keys.created_at < ?4";
+const SQL_QUERY_IDEMPOTENCY_KEY: &str = "select idempotency_key, request_hash, status, body, created_at from idempotency_keys where bridge_id = ? and idempotency_key = ?";
+const SQL_SAVE_IDEMPOTENT_RESPONSE: &str =
+    "update idempotency_keys set status = ?, body = ? where bridge_id = ? and idempotency_key = ?";
+const SQL_DELETE_IDEMPOTENCY_KEY: &str =
+    "delete from idempotency_keys where bridge_id = ? and idempotency_key = ?";
+
+/// Table `custody_eras`
+/// The custody addresses of a bridge in the order they're rotated, the address of an era is
+/// watched until `watched_until` after the next era starts, it's null for the current era
+const SQL_CREATE_TABLE_CUSTODY_ERAS: &str = "create table if not exists custody_eras (bridge_id text not null, era integer not null, address text not null, started_at integer not null, watched_until integer, primary key (bridge_id, era))";
+const SQL_QUERY_CUSTODY_ERAS: &str = "select era, address, started_at, watched_until from custody_eras where bridge_id = ? order by era";
+const SQL_RETIRE_CUSTODY_ERA: &str =
+    "update custody_eras set watched_until = ? where bridge_id = ? and watched_until is null";
+const SQL_INSERT_CUSTODY_ERA: &str = "insert into custody_eras (bridge_id, era, address, started_at) select ?1, coalesce(max(era), 0) + 1, ?2, ?3 from custody_eras where bridge_id = ?1";
+const SQL_SET_PENDING_ACTION_CUSTODY_ERA: &str =
+    "update pending_actions set custody_era = ? where bridge_id = ? and id = ?";
+const SQL_SET_REFUND_CUSTODY_ERA: &str =
+    "update depc_refunds set custody_era = ? where bridge_id = ? and depc_txid = ? and vout = ?";
+
+/// Table `withdraw_requests`
+/// The withdrawals requested off-chain, the signer of the request binds the tokens it
+/// transferred to the owner with the DePC recipient, see `bridge::verify_withdraw_request`
+const SQL_CREATE_TABLE_WITHDRAW_REQUESTS: &str = "create table if not exists withdraw_requests (id integer primary key autoincrement, signature text not null, recipient text not null, signer text not null, origin text not null, status text not null, reason text, pending_action_id integer, created_at integer not null, processed_at integer, bridge_id text not null default 'default')";
+const SQL_CREATE_UNIQUE_INDEX_WITHDRAW_REQUESTS_BRIDGE_ID_SIGNATURE: &str = "create unique index if not exists index__withdraw_requests_bridge_id_signature on withdraw_requests (bridge_id, signature)";
+const SQL_INSERT_WITHDRAW_REQUEST: &str = "insert or ignore into withdraw_requests (signature, recipient, signer, origin, status, created_at, bridge_id) values (?, ?, ?, ?, ?, ?, ?)";
+const SQL_QUERY_WITHDRAW_REQUEST: &str = "select id, signature, recipient, signer, origin, status, reason, pending_action_id, created_at, processed_at from withdraw_requests where bridge_id = ? and id = ?";
+const SQL_QUERY_WITHDRAW_REQUESTS_BY_STATUS: &str = "select id, signature, recipient, signer, origin, status, reason, pending_action_id, created_at, processed_at from withdraw_requests where bridge_id = ? and status = ? order by id";
+const SQL_COMPLETE_WITHDRAW_REQUEST: &str = "update withdraw_requests set status = ?, reason = ?, pending_action_id = ?, processed_at = ? where bridge_id = ? and id = ? and processed_at is null";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositRecord {
+    pub depc_txid: String,
+    pub depc_timestamp: u64,
+    pub to_address: String,
+    pub amount: DepcAmount,
+    pub erc20_txid: Option<String>,
+    pub erc20_timestamp: Option<u64>,
+    /// `None` when the deposit is confirmed without the receipt of the Solana transaction
+    pub mint_receipt: Option<MintReceiptRecord>,
+}
+
+/// The cost and the confirmation of the Solana transaction minting a batch of deposits, every
+/// deposit of the batch holds the same receipt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MintReceiptRecord {
+    /// The fee of the whole transaction in lamports, `None` when the transaction is fetched
+    /// without its status
+    pub fee: Option<u64>,
+    pub slot: u64,
+    /// Whether the transaction creates the token account of any recipient
+    pub created_ata: bool,
+    /// The logs of the programs the transaction runs, `None` as the fee is
+    pub raw_logs: Option<Vec<String>>,
+}
+
+/// An address found by the analysis of the exchange addresses and the transaction the analysis
+/// of is started from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeAddressRecord {
+    pub address: String,
+    pub analyzed_txid: String,
+    pub first_seen: Option<u64>,
+    /// The times the address is found again after it's saved
+    pub rediscoveries: u64,
+}
+
+/// The blocks mined by a miner, the first and the last of them by the height
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinerStatsRecord {
+    pub miner: String,
+    pub blocks: u32,
+    pub first_height: u32,
+    pub last_height: u32,
+    /// The earliest and the latest block time, the block time is not strictly increasing
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnspentCoinRecord {
+    pub txid: String,
+    pub n: u32,
+    pub value: DepcAmount,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSummaryRecord {
+    pub hash: String,
+    pub height: u32,
+    pub miner: String,
+    pub time: u64,
+    pub tx_count: u32,
+    /// The total value of the outputs of the block
+    pub total_value: DepcAmount,
+}
+
+pub struct SweepRecord {
+    pub id: u64,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: DepcAmount,
+    pub txid: Option<String>,
+    pub raw_tx: String,
+    /// Null for the sweeps made before the inputs are recorded
+    pub inputs: Option<String>,
+    pub timestamp: u64,
+}
+
+pub struct ShadowActionRecord {
+    pub id: u64,
+    pub action: String,
+    pub source_txid: String,
+    pub recipient: String,
+    pub amount: DepcAmount,
+    pub timestamp: u64,
+}
+
+pub struct AnalysisFrontierRecord {
+    pub address: String,
+    /// The number of the hops from the inputs of the seed transaction
+    pub depth: u32,
+    pub expanded: bool,
+}
+
+pub struct PendingActionRecord {
+    pub id: u64,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub source_txid: String,
+    pub recipient: String,
+    pub amount: DepcAmount,
+    pub timestamp: u64,
+    pub txid: Option<String>,
+    /// The custody era of the address the action is received by, see `CustodyEraRecord`
+    pub custody_era: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundRecord {
+    pub id: u64,
+    pub depc_txid: String,
+    pub vout: u32,
+    pub refund_address: String,
+    /// The value of the deposit, the fee of the refund is paid from it
+    pub amount: DepcAmount,
+    pub reason: String,
+    pub timestamp: u64,
+    pub txid: Option<String>,
+    pub refunded_at: Option<u64>,
+    /// The address the sender claims the refund to, see `bridge::make_refund_claim_message`
+    pub claim_address: Option<String>,
+    pub claim_signature: Option<String>,
+    /// submitted, verified or rejected
+    pub claim_status: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositIntentRecord {
+    pub id: u64,
+    /// The DePC address pays the deposit
+    pub sender: String,
+    pub recipient: String,
+    pub created_at: u64,
+    /// The output matched to the intent
+    pub matched_txid: Option<String>,
+    pub matched_vout: Option<u32>,
+    pub matched_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositRootRecord {
+    pub id: u64,
+    /// The hex of the Merkle root, see `bridge::MerkleTree`
+    pub root: String,
+    pub leaf_count: u64,
+    /// The root covers the deposits minted before it
+    pub covered_until: u64,
+    pub created_at: u64,
+    pub memo_signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositAddressRecord {
+    pub address: String,
+    /// The index of the receiving address of the deposit xpub
+    pub derivation_index: u32,
+    /// The path of the address below the deposit xpub, `chain/index`, the key of the address is
+    /// derived by it from the private key of the xpub to sweep the coins
+    pub derivation_path: String,
+    /// The solana address the payments to the address are credited to
+    pub recipient: String,
+    pub created_at: u64,
+}
+
+/// A custody address of a bridge, the eras are numbered from 1 in the order the addresses are
+/// rotated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustodyEraRecord {
+    pub era: u32,
+    pub address: String,
+    pub started_at: u64,
+    /// The outputs to the address are credited until it after the next era starts, `None` for
+    /// the current era
+    pub watched_until: Option<u64>,
+}
+
+/// A signed Solana transaction of a bridge, see `solana::OutgoingTxStore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingTransactionRecord {
+    pub signature: String,
+    pub raw_tx: Vec<u8>,
+    pub last_valid_block_height: u64,
+    pub recipients: String,
+    /// pending, confirmed, failed or expired
+    pub status: String,
+    pub created_at: u64,
+    pub settled_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawRequestRecord {
+    pub id: u64,
+    /// The Solana transaction transfers the tokens to the owner
+    pub signature: String,
+    pub recipient: String,
+    /// The Solana key signs the request, it must be the sender of the tokens
+    pub signer: String,
+    pub origin: String,
+    pub status: String,
+    /// Why the request is rejected
+    pub reason: Option<String>,
+    /// The payout of the accepted request
+    pub pending_action_id: Option<u64>,
+    pub created_at: u64,
+    pub processed_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterChangeRecord {
+    pub id: u64,
+    pub name: String,
+    pub value: String,
+    pub created_at: u64,
+    pub activates_at: u64,
+    pub cancelled_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolanaSlotRecord {
+    pub slot: u64,
+    pub epoch: u64,
+    pub block_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolanaCursorRecord {
+    pub account: String,
+    pub signature: String,
+    /// The slot of the transaction of `signature`
+    pub slot: u64,
+    pub updated_at: u64,
+}
+
+pub struct JobRecord {
+    pub name: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub last_started_at: Option<u64>,
+    pub last_finished_at: Option<u64>,
+    /// The error of the last run, `None` if it succeeds
+    pub last_error: Option<String>,
+    /// `None` if the job is disabled
+    pub next_run_at: Option<u64>,
+}
+
+pub struct EventRecord {
+    /// The sequence number, it increases with each event of all the bridges
+    pub seq: u64,
+    pub event: String,
+    pub amount: DepcAmount,
+    /// The transaction the movement is made by
+    pub reference: String,
+    pub timestamp: u64,
+}
+
+pub struct LedgerEntryRecord {
+    pub id: u64,
+    pub event: String,
+    pub debit: String,
+    pub credit: String,
+    pub amount: DepcAmount,
+    /// The transaction the movement is made by
+    pub reference: String,
+    pub timestamp: u64,
+}
+
+/// The totals of an account of the ledger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerAccountTotals {
+    pub account: String,
+    pub debit: DepcAmount,
+    pub credit: DepcAmount,
+}
+
+/// The total of the entries of an event on a day (UTC)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerDailyTotal {
+    /// The days since the epoch
+    pub day: u64,
+    pub event: String,
+    pub amount: DepcAmount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeSweepRecord {
+    pub id: u64,
+    pub treasury: String,
+    pub amount: DepcAmount,
+    /// The Solana transaction the fees are transferred by
+    pub signature: String,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DustSweepRecord {
+    pub id: u64,
+    /// The auxiliary token account the tokens are consolidated from
+    pub account: String,
+    pub amount: DepcAmount,
+    /// The Solana transaction consolidates all the accounts of the sweep
+    pub signature: String,
+    pub created_at: u64,
+}
+
+/// A withdrawal paid from the custody address, see table `depc_payouts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepcPayoutRecord {
+    pub id: u64,
+    /// The pending action of the withdrawal
+    pub action_id: u64,
+    pub txid: String,
+    /// The hex of the signed transaction
+    pub raw_tx: String,
+    /// signed or sent
+    pub status: String,
+    pub created_at: u64,
+    pub sent_at: Option<u64>,
+}
+
+/// A withdrawal from the multisig custody, see table `multisig_payouts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigPayoutRecord {
+    pub id: u64,
+    /// The pending action of the withdrawal
+    pub action_id: u64,
+    /// The hex of the transaction with the signatures merged so far
+    pub raw_tx: String,
+    /// The json array of the spent coins, see `depc::PrevTx`
+    pub prevtxs: String,
+    /// collecting or sent
+    pub status: String,
+    pub txid: Option<String>,
+    pub created_at: u64,
+    pub sent_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigSignatureRecord {
+    pub id: u64,
+    pub payout_id: u64,
+    pub raw_tx: String,
+    /// submitted, merged or rejected
+    pub status: String,
+    /// The reason the node rejects it
+    pub error: Option<String>,
+    pub created_at: u64,
+}
+
+/// A bump of a stuck payout, see table `payout_bumps`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutBumpRecord {
+    pub id: u64,
+    /// The pending action of the withdrawal
+    pub action_id: u64,
+    /// rbf or cpfp
+    pub kind: String,
+    /// The transaction replaced by `txid`, or the parent of `txid`
+    pub bumped_txid: String,
+    pub txid: String,
+    /// The hex of the signed bump
+    pub raw_tx: String,
+    /// The fee added by the bump
+    pub fee: DepcAmount,
+    /// pending or sent
+    pub status: String,
+    pub created_at: u64,
+}
+
+/// An output a pending action is made for, see table `recorded_transactions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedTransactionRecord {
+    pub id: u64,
+    pub txid: String,
+    pub vout: u32,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub action_id: u64,
+    pub recorded_at: u64,
+}
+
+/// The bridge fee charged on a transfer, see table `transfer_fees`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferFeeRecord {
+    pub id: u64,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub source_txid: String,
+    pub action_id: u64,
+    /// The amount received, the fee included
+    pub amount: DepcAmount,
+    pub fee: DepcAmount,
+    pub created_at: u64,
+}
+
+/// A payment to a custody address without a bridge action, see table `custody_inflows`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustodyInflowRecord {
+    pub id: u64,
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub amount: DepcAmount,
+    /// Why it's not a deposit, e.g. `no_recipient`
+    pub reason: String,
+    pub height: u32,
+    /// `unclassified` or `resolved`
+    pub status: String,
+    /// The note of the operator resolves it
+    pub resolution: Option<String>,
+    pub found_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+/// The digest of the bridge events of a block, see table `block_digests`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDigestRecord {
+    pub height: u32,
+    pub block_hash: String,
+    pub event_count: u32,
+    /// The hex of the SHA-256
+    pub digest: String,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatchUpPlanRecord {
+    pub id: u64,
+    pub from_height: u32,
+    pub to_height: u32,
+    pub status: String,
+    pub deposits: u64,
+    pub deposit_amount: DepcAmount,
+    pub withdrawals: u64,
+    pub withdraw_amount: DepcAmount,
+    pub started_at: u64,
+    pub planned_at: Option<u64>,
+    pub confirmed_at: Option<u64>,
+}
+
+/// The totals of the actions of a kind found since a time
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingActionTotals {
+    pub action: String,
+    pub count: u64,
+    pub amount: DepcAmount,
+    /// The average seconds from being found to being made, `None` if none is made
+    pub avg_latency: Option<f64>,
+    /// The actions not processed yet
+    pub pending: u64,
+}
+
+/// The actions of a kind not processed yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingActionBacklog {
+    pub action: String,
+    pub count: u64,
+    pub amount: DepcAmount,
+}
+
+/// An action held by the transfer limits, see `bridge::TransferLimits`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeldPendingActionRecord {
+    pub id: u64,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub source_txid: String,
+    pub recipient: String,
+    pub amount: DepcAmount,
+    pub timestamp: u64,
+    pub held_reason: String,
+    pub held_at: u64,
+    pub released_at: Option<u64>,
+}
+
+/// The actions of a kind those stay in an intermediate state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StuckPendingActions {
+    /// `queued` (not claimed yet), `held` (over the transfer limits, not released yet),
+    /// `claimed` (not made yet) or `unconfirmed` (made but the transaction is not confirmed)
+    pub state: String,
+    pub count: u64,
+    /// The time the oldest of them is found
+    pub oldest: u64,
+}
+
+pub struct WatchedAddressRecord {
+    /// `depc` or `solana`
+    pub chain: String,
+    pub address: String,
+    pub label: String,
+    /// The url the events of the address are posted to
+    pub webhook: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEventRecord {
+    pub id: u64,
+    pub chain: String,
+    pub address: String,
+    pub txid: String,
+    /// `depc`, `sol` or `token`
+    pub asset: String,
+    /// `in`, `out` or `self`
+    pub direction: String,
+    /// The signed net change of the address in the base units of the asset
+    pub net_change: String,
+    pub timestamp: u64,
+    pub delivered_at: Option<u64>,
+    /// The code of the error the last delivery fails with (`ErrorCode`), `None` once delivered
+    pub last_error_code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointRecord {
+    pub height: u32,
+    pub hash: String,
+    /// The time of the checkpoint block
+    pub time: u64,
+    pub created_at: u64,
+}
+
+/// The chain index rolled back by `Conn::rollback_blocks_above`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollbackRecord {
+    pub blocks: u64,
+    pub transactions: u64,
+    pub coins: u64,
+    /// The deposits (bridge id, DePC txid) not picked by the workers, they're removed and made
+    /// again when the transactions are found in the new blocks
+    pub removed_deposits: Vec<(String, String)>,
+    /// The deposits (bridge id, DePC txid) being minted or minted already, they're kept
+    pub kept_deposits: Vec<(String, String)>,
+    /// The refunds not made yet, they're removed as the deposits
+    pub removed_refunds: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnavailableHeightRecord {
+    pub height: u32,
+    pub reason: String,
+    pub recorded_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKeyRecord {
+    pub key: String,
+    pub request_hash: String,
+    /// `None` while the first request is being served
+    pub status: Option<u16>,
+    pub body: Option<Vec<u8>>,
+    pub created_at: u64,
+}
+
+pub struct DeadLetterRecord {
+    pub id: u64,
+    pub height: u32,
+    pub txid: String,
+    /// `None` when the whole transaction is skipped
+    pub vout: Option<u32>,
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+/// The bridge the rows are read and written for when the connection is not scoped
+pub const DEFAULT_BRIDGE_ID: &str = "default";
+/// The version of the schema made by `Conn::init`, it's saved as `pragma user_version` and
+/// bumped when `init` changes the existing tables
+pub const SCHEMA_VERSION: u32 = 25;
+
+/// The read-only connections opened to a database file, the queries run on them and don't wait
+/// for the writes
+const READER_POOL_SIZE: usize = 4;
+
+/// A mutation run by the writer thread on the write connection
+type WriteCommand = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// A command to the writer thread
+enum WriterCommand {
+    Write(WriteCommand),
+    /// Tell the first sender the writer is paused and wait until the second is dropped, the
+    /// transaction of another thread runs meanwhile, see `Conn::transaction`
+    Pause(mpsc::SyncSender<()>, mpsc::Receiver<()>),
+}
+
+thread_local! {
+    /// The pool the transaction of the thread is open on, see `Conn::transaction`
+    static TRANSACTION_POOL: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The connections to a database
+///
+/// All the mutations are sent to the writer thread through a channel and run one by one on the
+/// write connection, so the syncers, the workers and the REST jobs never contend for it. The
+/// queries run on the pooled read-only connections. A transaction runs on the thread opens it,
+/// see `Conn::transaction`.
+struct Pool {
+    write_conn: Arc<Mutex<Connection>>,
+    writer: mpsc::Sender<WriterCommand>,
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+    /// Held by the open transaction, the queries of the other threads wait for it without the
+    /// readers
+    transaction_gate: Mutex<()>,
+    busy_retry: Arc<BusyRetry>,
+}
+
+/// The connection to the local database, the chain index (blocks, coins, ...) is shared by all
+/// the bridges, the rows of the bridge tables are scoped by `bridge_id`
 #[derive(Clone)]
 pub struct Conn {
-    conn: Arc<Mutex<Connection>>,
+    pool: Arc<Pool>,
+    bridge_id: String,
 }
 
 impl Conn {
+    /// A database file is switched to WAL, so the queries never wait for the writes of the
+    /// syncers and the writes wait only for the checkpoints
     pub fn open_or_create(db_path: &str) -> Result<Conn, Error> {
         let conn = Connection::open(db_path)?;
-        Ok(Conn {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let mut readers = vec![];
+        // the readers of an in-memory or a temporary database would open other databases
+        if !db_path.is_empty() && db_path != ":memory:" {
+            conn.query_row(SQL_ENABLE_WAL, [], |row| row.get::<_, String>(0))?;
+            for _ in 0..READER_POOL_SIZE {
+                let reader = Connection::open_with_flags(
+                    db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | OpenFlags::SQLITE_OPEN_URI
+                        | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                reader.busy_timeout(BUSY_TIMEOUT)?;
+                readers.push(reader);
+            }
+        }
+        Ok(Conn::new(conn, readers))
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "testing"))]
     pub fn open_in_mem() -> Result<Conn, Error> {
         let conn = Connection::open_in_memory()?;
-        Ok(Conn {
-            conn: Arc::new(Mutex::new(conn)),
+        Ok(Conn::new(conn, vec![]))
+    }
+
+    fn new(conn: Connection, readers: Vec<Connection>) -> Conn {
+        let write_conn = Arc::new(Mutex::new(conn));
+        let (writer, commands) = mpsc::channel::<WriterCommand>();
+        let conn = Arc::clone(&write_conn);
+        // the thread exits when the last clone of the connection is dropped
+        thread::spawn(move || {
+            for command in commands {
+                match command {
+                    WriterCommand::Write(command) => command(&mut conn.lock().unwrap()),
+                    WriterCommand::Pause(paused, resume) => {
+                        let _ = paused.send(());
+                        let _ = resume.recv();
+                    }
+                }
+            }
+        });
+        Conn {
+            pool: Arc::new(Pool {
+                write_conn,
+                writer,
+                readers: readers.into_iter().map(Mutex::new).collect(),
+                next_reader: AtomicUsize::new(0),
+                transaction_gate: Mutex::new(()),
+                busy_retry: Arc::new(BusyRetry::new(DEFAULT_BUSY_RETRY_POLICY)),
+            }),
+            bridge_id: DEFAULT_BRIDGE_ID.to_owned(),
+        }
+    }
+
+    /// The same connection (and the same database transaction) scoped to another bridge
+    pub fn scoped(&self, bridge_id: &str) -> Conn {
+        Conn {
+            pool: Arc::clone(&self.pool),
+            bridge_id: bridge_id.to_owned(),
+        }
+    }
+
+    /// Run the queries of `f` on a connection, they're run again when the database is locked,
+    /// see `BusyRetry`. They're interrupted once the current deadline passes, see `Deadline`
+    fn read<T>(&self, f: impl Fn(&Connection) -> Result<T, Error>) -> Result<T, Error> {
+        let deadline = Deadline::current();
+        // without the readers the queries run on the write connection, they wait for the
+        // transaction of another thread so they never see its uncommitted rows
+        let _gate = (self.pool.readers.is_empty() && !self.in_transaction())
+            .then(|| self.pool.transaction_gate.lock().unwrap());
+        self.pool.busy_retry.run(|| match deadline {
+            Some(deadline) => read_until(&self.reader(), deadline, &f),
+            None => f(&self.reader()),
+        })
+    }
+
+    /// The counters of the statements retried since the database is locked
+    pub fn busy_retry_stats(&self) -> BusyRetryStats {
+        self.pool.busy_retry.stats()
+    }
+
+    /// A connection to run the queries, an idle reader is preferred. The queries of the
+    /// transaction run on the write connection to see the uncommitted rows
+    fn reader(&self) -> MutexGuard<'_, Connection> {
+        let pool = &self.pool;
+        if pool.readers.is_empty() || self.in_transaction() {
+            return pool.write_conn.lock().unwrap();
+        }
+        let first = pool.next_reader.fetch_add(1, Ordering::Relaxed);
+        for i in 0..pool.readers.len() {
+            if let Ok(c) = pool.readers[(first + i) % pool.readers.len()].try_lock() {
+                return c;
+            }
+        }
+        pool.readers[first % pool.readers.len()].lock().unwrap()
+    }
+
+    /// Run `f` on the writer thread and wait for the result, the mutations of the transaction
+    /// run on the thread of it instead
+    fn write<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        if self.in_transaction() {
+            return f(&mut self.pool.write_conn.lock().unwrap());
+        }
+        let (reply, result) = mpsc::sync_channel(1);
+        let command: WriteCommand = Box::new(move |c| {
+            let _ = reply.send(f(c));
+        });
+        self.pool
+            .writer
+            .send(WriterCommand::Write(command))
+            .map_err(|_| writer_stopped())?;
+        result.recv().unwrap_or_else(|_| Err(writer_stopped()))
+    }
+
+    /// Whether the thread runs a transaction on the database, see `transaction`
+    fn in_transaction(&self) -> bool {
+        TRANSACTION_POOL.with(|pool| pool.get() == Arc::as_ptr(&self.pool) as usize)
+    }
+
+    /// Run `f` in a database transaction, nothing of it is kept when it fails
+    ///
+    /// The transaction runs on the calling thread, the queries and the mutations `f` makes by
+    /// any clone of the connection see the uncommitted rows. The writer thread is paused until
+    /// it's closed, so the mutations of the others never join it, and their queries never see
+    /// its rows. `f` must not wait for the others, e.g. by an async task, it's run in a
+    /// transaction opened by the thread already.
+    pub fn transaction<T, E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<Error>,
+    {
+        if self.in_transaction() {
+            return f();
+        }
+        let (paused, pausing) = mpsc::sync_channel(0);
+        let (resume, resuming) = mpsc::channel();
+        self.pool
+            .writer
+            .send(WriterCommand::Pause(paused, resuming))
+            .map_err(|_| writer_stopped())?;
+        pausing.recv().map_err(|_| writer_stopped())?;
+        let scope = TransactionScope {
+            conn: self,
+            _gate: self.pool.transaction_gate.lock().unwrap(),
+            _resume: resume,
+        };
+        TRANSACTION_POOL.with(|pool| pool.set(Arc::as_ptr(&self.pool) as usize));
+        self.execute(SQL_BEGIN_TRANSACTION, params![])?;
+        let res = f();
+        if res.is_ok() {
+            self.execute(SQL_COMMIT_TRANSACTION, params![])?;
+        }
+        // the failed one is rolled back
+        drop(scope);
+        res
+    }
+
+    /// Run the statement on the writer thread, the number of the changed rows is returned
+    fn execute(&self, sql: &'static str, params: &[&dyn ToSql]) -> Result<usize, Error> {
+        let params = to_values(params)?;
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| busy_retry.run(|| c.execute(sql, params_from_iter(params.iter()))))
+    }
+
+    /// Run the insert on the writer thread, the rowid of the inserted row is returned
+    fn insert(&self, sql: &'static str, params: &[&dyn ToSql]) -> Result<u64, Error> {
+        let params = to_values(params)?;
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| c.execute(sql, params_from_iter(params.iter())))?;
+            Ok(c.last_insert_rowid() as u64)
         })
     }
 
+    /// Run the statements in order on the writer thread, no other mutation runs between them
+    fn execute_all(&self, statements: &[(&'static str, &[&dyn ToSql])]) -> Result<(), Error> {
+        let statements = statements
+            .iter()
+            .map(|(sql, params)| Ok((*sql, to_values(params)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            // each statement is retried alone, the ones before it are not run again
+            for (sql, params) in statements {
+                busy_retry.run(|| c.execute(sql, params_from_iter(params.iter())))?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn bridge_id(&self) -> &str {
+        &self.bridge_id
+    }
+
     pub fn init(&self) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_CREATE_TABLE_BLOCKS, [])?;
-        c.execute(SQL_CREATE_UNIQUE_INDEX_BLOCKS_HASH, [])?;
+        self.write(|c| {
+            c.execute(SQL_CREATE_TABLE_BLOCKS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_BLOCKS_HASH, [])?;
+            c.execute(SQL_CREATE_INDEX_BLOCKS_TIME_HEIGHT, [])?;
+            c.execute(SQL_CREATE_INDEX_BLOCKS_MINER_HEIGHT, [])?;
 
-        c.execute(SQL_CREATE_TABLE_TRANSACTIONS, [])?;
-        c.execute(SQL_CREATE_UNIQUE_INDEX_TRANSACTIONS_TXID, [])?;
+            c.execute(SQL_CREATE_TABLE_TRANSACTIONS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_TRANSACTIONS_TXID, [])?;
+            c.execute(SQL_CREATE_INDEX_TRANSACTIONS_BLOCK_HASH, [])?;
+            add_column(c, "transactions", "height", "integer")?;
+            c.execute(SQL_CREATE_INDEX_TRANSACTIONS_HEIGHT, [])?;
 
-        c.execute(SQL_CREATE_TABLE_COINS, [])?;
-        c.execute(SQL_CREATE_UNIQUE_INDEX_COINS_TXID_N, [])?;
-        c.execute(SQL_CREATE_INDEX_COINS_SPENT_TXID, [])?;
-        c.execute(SQL_CREATE_INDEX_COINS_OWNER, [])?;
-        c.execute(SQL_CREATE_INDEX_COINS_SPENT_HEIGHT, [])?;
+            c.execute(SQL_CREATE_TABLE_COINS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_COINS_TXID_N, [])?;
+            c.execute(SQL_CREATE_INDEX_COINS_SPENT_TXID, [])?;
+            c.execute(SQL_CREATE_INDEX_COINS_OWNER, [])?;
+            c.execute(SQL_CREATE_INDEX_COINS_SPENT_HEIGHT, [])?;
+            add_column(c, "coins", "height", "integer")?;
+            c.execute(SQL_CREATE_INDEX_COINS_OWNER_HEIGHT, [])?;
+            for table in query_coins_archive_tables(c)? {
+                add_column(c, &table, "height", "integer")?;
+                c.execute(&format_archive_owner_height_index(&table), [])?;
+            }
+            create_all_coins_view(c)?;
 
-        c.execute(SQL_CREATE_TABLE_DEPC_DEPOSIT, [])?;
-        c.execute(SQL_CREATE_UNIQUE_INDEX_DEPC_DEPOSIT_DEPC_TXID, [])?;
+            c.execute(SQL_CREATE_TABLE_ADDRESS_BALANCES, [])?;
+            c.execute(SQL_CREATE_INDEX_ADDRESS_BALANCES_BALANCE, [])?;
+            // the database is made before the balances are maintained, fill them from the coins
+            if !c.query_row(SQL_QUERY_ANY_ADDRESS_BALANCE, [], |row| {
+                row.get::<_, bool>(0)
+            })? {
+                c.execute(SQL_FILL_ADDRESS_BALANCES, [])?;
+            }
 
-        c.execute(SQL_CREATE_TABLE_DEPC_WITHDRAW, [])?;
-        c.execute(SQL_CREATE_UNIQUE_INDEX_DEPC_WITHDRAW_ERC20_TXID, [])?;
+            c.execute(SQL_CREATE_TABLE_DEPC_DEPOSIT, [])?;
+            add_bridge_id_column(c, "depc_deposit")?;
+            add_column(c, "depc_deposit", "erc20_fee", "integer")?;
+            add_column(c, "depc_deposit", "erc20_slot", "integer")?;
+            add_column(c, "depc_deposit", "erc20_created_ata", "integer")?;
+            add_column(c, "depc_deposit", "erc20_logs", "text")?;
+            c.execute(SQL_DROP_INDEX_DEPC_DEPOSIT_DEPC_TXID, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_DEPC_DEPOSIT_BRIDGE_ID_DEPC_TXID, [])?;
+            c.execute(SQL_CREATE_INDEX_DEPC_DEPOSIT_BRIDGE_ID_ERC20_TIMESTAMP, [])?;
 
-        c.execute(SQL_CREATE_TABLE_EXCHANGE_ADDRESSES, [])?;
-        c.execute(SQL_CREATE_INDEX_EXCHANGE_ADDRESSES_ANALYZED_TXID, [])?;
+            c.execute(SQL_CREATE_TABLE_DEPC_WITHDRAW, [])?;
+            add_bridge_id_column(c, "depc_withdraw")?;
+            c.execute(SQL_DROP_INDEX_DEPC_WITHDRAW_ERC20_TXID, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_DEPC_WITHDRAW_BRIDGE_ID_ERC20_TXID,
+                [],
+            )?;
 
-        Ok(())
+            c.execute(SQL_CREATE_TABLE_EXCHANGE_ADDRESSES, [])?;
+            add_column(c, "exchange_addresses", "first_seen", "integer")?;
+            add_column(
+                c,
+                "exchange_addresses",
+                "rediscoveries",
+                "integer not null default 0",
+            )?;
+            c.execute(SQL_CREATE_INDEX_EXCHANGE_ADDRESSES_ANALYZED_TXID, [])?;
+
+            c.execute(SQL_CREATE_TABLE_ANALYSIS_FRONTIER, [])?;
+
+            c.execute(SQL_CREATE_TABLE_ADDRESS_CLUSTERS, [])?;
+            c.execute(SQL_CREATE_INDEX_ADDRESS_CLUSTERS_CLUSTER_ID, [])?;
+
+            c.execute(SQL_CREATE_TABLE_DEPC_SWEEP, [])?;
+            add_bridge_id_column(c, "depc_sweep")?;
+            add_column(c, "depc_sweep", "inputs", "text")?;
+
+            c.execute(SQL_CREATE_TABLE_SHADOW_ACTIONS, [])?;
+            add_bridge_id_column(c, "shadow_actions")?;
+
+            c.execute(SQL_CREATE_TABLE_DEAD_LETTERS, [])?;
+            add_bridge_id_column(c, "dead_letters")?;
+
+            c.execute(SQL_CREATE_TABLE_PENDING_ACTIONS, [])?;
+            add_bridge_id_column(c, "pending_actions")?;
+            add_column(c, "pending_actions", "confirmed_at", "integer")?;
+            add_column(c, "pending_actions", "custody_era", "integer")?;
+            add_column(
+                c,
+                "pending_actions",
+                "attempts",
+                "integer not null default 0",
+            )?;
+            add_column(c, "pending_actions", "retry_at", "integer")?;
+            add_column(c, "pending_actions", "sent_height", "integer")?;
+            add_column(c, "pending_actions", "held_at", "integer")?;
+            add_column(c, "pending_actions", "held_reason", "text")?;
+            add_column(c, "pending_actions", "released_at", "integer")?;
+            add_column(c, "pending_actions", "transfer_time", "integer")?;
+            c.execute(SQL_CREATE_INDEX_PENDING_ACTIONS_DONE_AT, [])?;
+
+            c.execute(SQL_CREATE_TABLE_DEPC_REFUNDS, [])?;
+            add_bridge_id_column(c, "depc_refunds")?;
+            add_column(c, "depc_refunds", "custody_era", "integer")?;
+            add_column(c, "depc_refunds", "claim_address", "text")?;
+            add_column(c, "depc_refunds", "claim_signature", "text")?;
+            add_column(c, "depc_refunds", "claim_status", "text")?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_DEPC_REFUNDS_DEPC_TXID_VOUT, [])?;
+
+            c.execute(SQL_CREATE_TABLE_PRICES, [])?;
+
+            c.execute(SQL_CREATE_TABLE_DEPOSIT_INTENTS, [])?;
+            add_bridge_id_column(c, "deposit_intents")?;
+
+            c.execute(SQL_CREATE_TABLE_PARAMETER_CHANGES, [])?;
+            c.execute(SQL_CREATE_INDEX_DEPOSIT_INTENTS_SENDER, [])?;
+
+            c.execute(SQL_CREATE_TABLE_DEPOSIT_WALLETS, [])?;
+            c.execute(SQL_CREATE_TABLE_DEPOSIT_ADDRESSES, [])?;
+            add_column(c, "deposit_addresses", "derivation_path", "text")?;
+            c.execute(SQL_FILL_DEPOSIT_ADDRESS_DERIVATION_PATHS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_DEPOSIT_ADDRESSES_ADDRESS, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_DEPOSIT_ADDRESSES_BRIDGE_ID_DERIVATION_INDEX,
+                [],
+            )?;
+            c.execute(SQL_CREATE_TABLE_DEPOSIT_ROOTS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_JOBS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_WITHDRAW_SIGNATURES, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_WITHDRAW_SIGNATURES_BRIDGE_ID_SIGNATURE,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_WITHDRAW_REQUESTS, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_WITHDRAW_REQUESTS_BRIDGE_ID_SIGNATURE,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_ATA_OWNERS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_SOLANA_SLOTS, [])?;
+            c.execute(SQL_CREATE_INDEX_SOLANA_SLOTS_BLOCK_TIME, [])?;
+
+            c.execute(SQL_CREATE_TABLE_SOLANA_CURSORS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_SOLANA_CURSORS_BRIDGE_ID_ACCOUNT, [])?;
+
+            c.execute(SQL_CREATE_TABLE_LEDGER_ENTRIES, [])?;
+
+            c.execute(SQL_CREATE_TABLE_EVENTS, [])?;
+            c.execute(SQL_CREATE_INDEX_EVENTS_BRIDGE_ID_SEQ, [])?;
+
+            c.execute(SQL_CREATE_TABLE_FEE_SWEEPS, [])?;
+            add_column(c, "fee_sweeps", "status", "text not null default 'sent'")?;
+
+            c.execute(SQL_CREATE_TABLE_DUST_SWEEPS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_MULTISIG_PAYOUTS, [])?;
+            c.execute(SQL_CREATE_TABLE_MULTISIG_SIGNATURES, [])?;
+
+            c.execute(SQL_CREATE_TABLE_PAYOUT_BUMPS, [])?;
+            add_column(c, "payout_bumps", "raw_tx", "text not null default ''")?;
+            add_column(c, "payout_bumps", "status", "text not null default 'sent'")?;
+
+            c.execute(SQL_CREATE_TABLE_RECORDED_TRANSACTIONS, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_RECORDED_TRANSACTIONS_BRIDGE_ID_TXID_VOUT,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_TRANSFER_FEES, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_TRANSFER_FEES_BRIDGE_ID_ACTION_ID,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_CUSTODY_INFLOWS, [])?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_CUSTODY_INFLOWS_BRIDGE_ID_TXID_VOUT,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_BLOCK_DIGESTS, [])?;
+            c.execute(SQL_CREATE_UNIQUE_INDEX_BLOCK_DIGESTS_BRIDGE_ID_HEIGHT, [])?;
+
+            c.execute(SQL_CREATE_TABLE_CATCH_UP_PLANS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_WATCHLIST, [])?;
+            c.execute(SQL_CREATE_TABLE_WATCH_EVENTS, [])?;
+            add_column(c, "watch_events", "last_error_code", "text")?;
+            c.execute(
+                SQL_CREATE_UNIQUE_INDEX_WATCH_EVENTS_CHAIN_ADDRESS_TXID_ASSET,
+                [],
+            )?;
+
+            c.execute(SQL_CREATE_TABLE_CHECKPOINT, [])?;
+
+            c.execute(SQL_CREATE_TABLE_UNAVAILABLE_HEIGHTS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_RELAY_COUNTS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_IDEMPOTENCY_KEYS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_CUSTODY_ERAS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_OUTGOING_TRANSACTIONS, [])?;
+
+            c.execute(SQL_CREATE_TABLE_DEPC_PAYOUTS, [])?;
+
+            // the database made by a newer build is left as it is, see `query_schema_version`
+            let version: u32 = c.query_row(SQL_QUERY_SCHEMA_VERSION, [], |row| row.get(0))?;
+            // the transactions and the coins are inserted before column `height` is added, the
+            // heights of the coins are looked up from the transactions
+            if version < 6 {
+                c.execute(SQL_FILL_TRANSACTION_HEIGHTS, [])?;
+            }
+            if version < 5 {
+                c.execute(&format_fill_coin_heights("coins"), [])?;
+                for table in query_coins_archive_tables(c)? {
+                    c.execute(&format_fill_coin_heights(&table), [])?;
+                }
+            }
+            if version < SCHEMA_VERSION {
+                c.execute_batch(&format!("pragma user_version = {SCHEMA_VERSION}"))?;
+            }
+
+            Ok(())
+        })
     }
 
-    pub fn begin_transaction(&self) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_BEGIN_TRANSACTION, [])?;
-        Ok(())
+    /// The schema version of the database, it's newer than `SCHEMA_VERSION` when the database
+    /// is made by a newer build
+    pub fn query_schema_version(&self) -> Result<u32, Error> {
+        self.read(|c| c.query_row(SQL_QUERY_SCHEMA_VERSION, [], |row| row.get(0)))
     }
 
-    pub fn rollback_transaction(&self) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_ROLLBACK_TRANSACTION, [])?;
-        Ok(())
+    /// The tables of the database and the rows of them, in the order of the names
+    pub fn query_table_row_counts(&self) -> Result<Vec<(String, u64)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_TABLE_NAMES)?;
+            let tables = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut counts = Vec::with_capacity(tables.len());
+            for table in tables {
+                let sql = format!("select count(*) from \"{table}\"");
+                let count = c.query_row(&sql, [], |row| row.get(0))?;
+                counts.push((table, count));
+            }
+            Ok(counts)
+        })
     }
 
-    pub fn commit_transaction(&self) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_COMMIT_TRANSACTION, [])?;
-        Ok(())
+    pub fn query_num_spent_coins_without_txid(&self) -> Result<u64, Error> {
+        self.read(|c| c.query_row(SQL_QUERY_NUM_SPENT_COINS_WITHOUT_TXID, [], |row| row.get(0)))
     }
 
     pub fn add_block(&self, hash: &str, height: u32, miner: &str, time: u64) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_INSERT_BLOCK, params![hash, height, miner, time])?;
+        self.execute(SQL_INSERT_BLOCK, params![hash, height, miner, time])?;
         Ok(())
     }
 
     pub fn add_transaction(&self, block_hash: &str, txid: &str) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_INSERT_TRANSACTION, params![block_hash, txid])?;
+        self.execute(SQL_INSERT_TRANSACTION, params![block_hash, txid])?;
         Ok(())
     }
 
+    /// The height of the block the transaction is indexed in, `None` if it's not indexed
+    pub fn query_transaction_height(&self, txid: &str) -> Result<Option<u32>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_TRANSACTION_HEIGHT, params![txid], |row| {
+                row.get::<_, Option<u32>>(0)
+            })
+            .optional()
+            .map(Option::flatten)
+        })
+    }
+
+    /// The owner is credited only when the coin is new, so a block applied again (e.g. after
+    /// the sync of it failed halfway) never counts its coins twice
     pub fn add_coin(
         &self,
         txid: &str,
         n: u32,
-        value: u64,
+        value: DepcAmount,
         owner: &str,
         script_hex: &str,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
-            SQL_INSERT_COIN,
-            params![txid, n, value, owner, script_hex, false],
-        )?;
-        Ok(())
+        let txid = txid.to_owned();
+        let owner = owner.to_owned();
+        let script_hex = HexPayload(script_hex.to_owned());
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                let inserted = sp.execute(
+                    SQL_INSERT_COIN,
+                    params![txid, n, value, owner, script_hex, false],
+                )?;
+                if inserted > 0 {
+                    sp.execute(SQL_CREDIT_ADDRESS_BALANCE, params![owner, value])?;
+                }
+                sp.commit()
+            })
+        })
     }
 
     pub fn mark_coin_to_spent(
@@ -173,25 +1806,135 @@ impl Conn {
         spent_txid: &str,
         spent_height: u32,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
-            SQL_MARK_COIN_SPENT,
-            params![spent_txid, spent_height, txid, n],
-        )?;
-        Ok(())
+        // the coin is still unspent here, a coin marked twice is only debited once
+        self.execute_all(&[
+            (SQL_DEBIT_ADDRESS_BALANCE_BY_COIN, params![txid, n]),
+            (
+                SQL_MARK_COIN_SPENT,
+                params![spent_txid, spent_height, txid, n],
+            ),
+        ])
+    }
+
+    /// The transaction spends the coin in the index, `None` if it's unspent or unknown
+    pub fn query_coin_spent_txid(&self, txid: &str, n: u32) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            let spent_txid: Option<Option<String>> = c
+                .query_row(SQL_QUERY_COIN_SPENT_TXID, params![txid, n], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+            Ok(spent_txid.flatten())
+        })
+    }
+
+    /// The script of the coin in hex
+    pub fn query_coin_script(&self, txid: &str, n: u32) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_COIN_SCRIPT, params![txid, n], |row| {
+                row.get::<_, HexPayload>(0)
+            })
+            .optional()
+            .map(|script| script.map(|script| script.0))
+        })
+    }
+
+    /// Move the coins spent below `height` to the archive tables, a batch of up to
+    /// `COINS_ARCHIVE_BATCH_HEIGHTS` heights of one partition from the lowest spent height is
+    /// moved each call. The number of the moved coins is returned, 0 if no coin is left
+    pub fn archive_spent_coins(&self, height: u32) -> Result<usize, Error> {
+        self.write(move |c| {
+            let sp = c.savepoint()?;
+            let Some(from) = sp.query_row(
+                SQL_QUERY_LOWEST_ARCHIVABLE_SPENT_HEIGHT,
+                params![height],
+                |row| row.get::<_, Option<u32>>(0),
+            )?
+            else {
+                return Ok(0);
+            };
+            let partition = from / COINS_ARCHIVE_PARTITION_HEIGHTS;
+            let to = height
+                .min(from.saturating_add(COINS_ARCHIVE_BATCH_HEIGHTS))
+                .min((partition + 1).saturating_mul(COINS_ARCHIVE_PARTITION_HEIGHTS));
+            let table = coins_archive_table(partition);
+            let exists = sp.query_row(SQL_QUERY_HAS_TABLE, params![table], |row| {
+                row.get::<_, bool>(0)
+            })?;
+            if !exists {
+                sp.execute_batch(&format!(
+                    "create table {table} (coin_id integer primary key, {COINS_COLUMNS});
+                     create index index__{table}_txid_n on {table} (txid, n);
+                     create index index__{table}_spent_txid on {table} (spent_txid);
+                     create index index__{table}_owner on {table} (owner);"
+                ))?;
+                sp.execute(&format_archive_owner_height_index(&table), [])?;
+                create_all_coins_view(&sp)?;
+            }
+            let n = sp.execute(
+                &format!(
+                    "insert into {table} (coin_id, {COINS_COLUMNS}) select rowid, {COINS_COLUMNS} from coins where is_spent = true and spent_height >= ? and spent_height < ?"
+                ),
+                params![from, to],
+            )?;
+            sp.execute(SQL_DELETE_ARCHIVED_COINS, params![from, to])?;
+            sp.commit()?;
+            Ok(n)
+        })
+    }
+
+    /// Rewrite up to `limit` payloads of `column` those are written as hex text, from the row
+    /// after `after`. The last row and the number of the rows are returned, `None` if no row
+    /// is left
+    pub fn compress_payloads(
+        &self,
+        column: PayloadColumn,
+        after: u64,
+        limit: u32,
+    ) -> Result<Option<(u64, usize)>, Error> {
+        let (sql_query, sql_update) = match column {
+            PayloadColumn::CoinScripts => (SQL_QUERY_TEXT_COIN_SCRIPTS, SQL_UPDATE_COIN_SCRIPT),
+            PayloadColumn::SweepRawTxs => (
+                SQL_QUERY_TEXT_DEPC_SWEEP_RAW_TXS,
+                SQL_UPDATE_DEPC_SWEEP_RAW_TX,
+            ),
+        };
+        self.write(move |c| {
+            let sp = c.savepoint()?;
+            let rows = {
+                let mut stmt = sp.prepare(sql_query)?;
+                let iter = stmt.query_map(params![after, limit], |row| {
+                    Ok((row.get::<_, u64>(0)?, row.get::<_, HexPayload>(1)?))
+                })?;
+                iter.collect::<Result<Vec<_>, Error>>()?
+            };
+            let Some(&(last, _)) = rows.last() else {
+                return Ok(None);
+            };
+            for (rowid, payload) in rows.iter() {
+                sp.execute(sql_update, params![payload, rowid])?;
+            }
+            sp.commit()?;
+            Ok(Some((last, rows.len())))
+        })
     }
 
     pub fn save_deposit(
         &self,
         depc_txid: &str,
         to_address_erc20: &str,
-        amount: u64,
+        amount: DepcAmount,
         depc_timestamp: u64,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
+        self.execute(
             SQL_INSERT_DEPC_DEPOSIT,
-            params![depc_txid, to_address_erc20, amount, depc_timestamp],
+            params![
+                depc_txid,
+                to_address_erc20,
+                amount,
+                depc_timestamp,
+                self.bridge_id
+            ],
         )?;
         Ok(())
     }
@@ -202,182 +1945,4703 @@ impl Conn {
         erc20_timestamp: u64,
         depc_txid: &str,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
+        self.execute(
             SQL_UPDATE_DEPC_DEPSOIT,
-            params![erc20_txid, erc20_timestamp, depc_txid],
+            params![erc20_txid, erc20_timestamp, self.bridge_id, depc_txid],
         )?;
         Ok(())
     }
 
-    pub fn make_withdraw(
+    /// Confirm the deposits minted by the same Solana transaction in one savepoint with the
+    /// receipt of the transaction if it's known, none of them is confirmed if any update fails
+    pub fn confirm_deposits_batch(
         &self,
+        depc_txids: &[&str],
         erc20_txid: &str,
         erc20_timestamp: u64,
-        from_address_erc20: &str,
-        amount: u64,
+        receipt: Option<MintReceiptRecord>,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
-            SQL_INSERT_DEPC_WITHDRAW,
-            params![erc20_txid, erc20_timestamp, from_address_erc20, amount],
-        )?;
-        Ok(())
+        let depc_txids: Vec<String> = depc_txids.iter().map(|txid| txid.to_string()).collect();
+        let erc20_txid = erc20_txid.to_owned();
+        // the logs are kept as a json array
+        let raw_logs = receipt
+            .as_ref()
+            .and_then(|receipt| receipt.raw_logs.as_ref())
+            .map(|raw_logs| serde_json::Value::from(raw_logs.clone()).to_string());
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            // the whole savepoint is retried, it's rolled back when it fails
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                {
+                    let mut stmt = sp.prepare_cached(SQL_UPDATE_DEPC_DEPOSIT_MINT_RECEIPT)?;
+                    for depc_txid in depc_txids.iter() {
+                        stmt.execute(params![
+                            erc20_txid,
+                            erc20_timestamp,
+                            receipt.as_ref().and_then(|receipt| receipt.fee),
+                            receipt.as_ref().map(|receipt| receipt.slot),
+                            receipt.as_ref().map(|receipt| receipt.created_ata),
+                            raw_logs,
+                            bridge_id,
+                            depc_txid
+                        ])?;
+                    }
+                }
+                sp.commit()
+            })
+        })
     }
 
-    pub fn confirm_withdraw(
-        &self,
-        depc_txid: &str,
+    pub fn query_deposit(&self, depc_txid: &str) -> Result<Option<DepositRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_DEPC_DEPOSIT,
+                params![self.bridge_id, depc_txid],
+                make_deposit,
+            )
+            .optional()
+        })
+    }
+
+    /// The deposits minted before `until` in the order of the minting
+    pub fn query_confirmed_deposits(&self, until: u64) -> Result<Vec<DepositRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_CONFIRMED_DEPC_DEPOSITS)?;
+            let iter = stmt.query_map(params![self.bridge_id, until], make_deposit)?;
+            iter.collect()
+        })
+    }
+
+    pub fn make_withdraw(
+        &self,
+        erc20_txid: &str,
+        erc20_timestamp: u64,
+        from_address_erc20: &str,
+        amount: TokenAmount,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_DEPC_WITHDRAW,
+            params![
+                erc20_txid,
+                erc20_timestamp,
+                from_address_erc20,
+                amount,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn confirm_withdraw(
+        &self,
+        depc_txid: &str,
         depc_timestamp: u64,
         depc_address: &str,
         erc20_txid: &str,
     ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(
+        self.execute(
             SQL_UPDATE_DEPC_WITHDRAW,
-            params![depc_txid, depc_timestamp, depc_address, erc20_txid],
+            params![
+                depc_txid,
+                depc_timestamp,
+                depc_address,
+                self.bridge_id,
+                erc20_txid
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_best_height(&self) -> Option<u32> {
+        self.read(|c| c.query_row(SQL_QUERY_BEST_HEIGHT, [], |row| row.get(0)))
+            .ok()
+    }
+
+    pub fn query_block_hash_by_height(&self, height: u32) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_BLOCK_HASH_BY_HEIGHT, params![height], |row| {
+                row.get(0)
+            })
+            .optional()
+        })
+    }
+
+    /// Unwind the chain index above `height` in one savepoint when the blocks are replaced by
+    /// a reorg, the blocks are synced again from the height
+    ///
+    /// The coins spent above the height are unspent, and the blocks, the transactions and the
+    /// coins above it are removed with the balances of the owners. The deposits and the refunds
+    /// of the removed transactions are removed with their ledger entries and events of all the
+    /// bridges unless the workers have picked them, and the payouts confirmed by the removed
+    /// blocks wait for the confirmation again
+    pub fn rollback_blocks_above(&self, height: u32) -> Result<RollbackRecord, Error> {
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                let mut record = RollbackRecord::default();
+                let deposits = sp
+                    .prepare(SQL_QUERY_ORPHANED_DEPOSITS)?
+                    .query_map(params![height], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                    })?
+                    .collect::<Result<Vec<(String, u64, String, bool)>, _>>()?;
+                for (bridge_id, id, txid, unpicked) in deposits {
+                    if !unpicked {
+                        record.kept_deposits.push((bridge_id, txid));
+                        continue;
+                    }
+                    sp.execute(SQL_DELETE_PENDING_ACTION, params![id])?;
+                    sp.execute(
+                        SQL_DELETE_RECORDED_TRANSACTIONS_OF_ACTION,
+                        params![bridge_id, id],
+                    )?;
+                    sp.execute(SQL_DELETE_UNMINTED_DEPC_DEPOSIT, params![bridge_id, txid])?;
+                    sp.execute(SQL_RESET_DEPOSIT_INTENT_MATCH, params![bridge_id, txid])?;
+                    sp.execute(
+                        SQL_DELETE_CUSTODY_IN_LEDGER_ENTRIES,
+                        params![bridge_id, txid],
+                    )?;
+                    sp.execute(
+                        SQL_DELETE_DEPOSIT_FEE_LEDGER_ENTRIES,
+                        params![bridge_id, txid],
+                    )?;
+                    sp.execute(SQL_DELETE_TRANSFER_FEES_OF_ACTION, params![bridge_id, id])?;
+                    sp.execute(SQL_DELETE_CUSTODY_IN_EVENTS, params![bridge_id, txid])?;
+                    record.removed_deposits.push((bridge_id, txid));
+                }
+                let refunds = sp
+                    .prepare(SQL_QUERY_ORPHANED_REFUNDS)?
+                    .query_map(params![height], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })?
+                    .collect::<Result<Vec<(String, u64, String)>, _>>()?;
+                for (bridge_id, id, txid) in refunds {
+                    sp.execute(SQL_DELETE_DEPC_REFUND, params![id])?;
+                    sp.execute(
+                        SQL_DELETE_CUSTODY_IN_LEDGER_ENTRIES,
+                        params![bridge_id, txid],
+                    )?;
+                    sp.execute(SQL_DELETE_CUSTODY_IN_EVENTS, params![bridge_id, txid])?;
+                    record.removed_refunds += 1;
+                }
+                sp.execute(SQL_UNCONFIRM_ORPHANED_PENDING_ACTIONS, params![height])?;
+                // the spent coins are credited back before the coins above the height are
+                // debited, the coins both made and spent above it are counted by both
+                sp.execute(SQL_CREDIT_ORPHANED_SPENT_COINS, params![height])?;
+                sp.execute(SQL_UNSPEND_ORPHANED_SPENT_COINS, params![height])?;
+                sp.execute(SQL_DEBIT_ORPHANED_COINS, params![height])?;
+                record.coins = sp.execute(SQL_DELETE_ORPHANED_COINS, params![height])? as u64;
+                record.transactions =
+                    sp.execute(SQL_DELETE_ORPHANED_TRANSACTIONS, params![height])? as u64;
+                record.blocks = sp.execute(SQL_DELETE_ORPHANED_BLOCKS, params![height])? as u64;
+                sp.execute(SQL_DELETE_ORPHANED_UNAVAILABLE_HEIGHTS, params![height])?;
+                sp.execute(SQL_DELETE_ORPHANED_DEAD_LETTERS, params![height])?;
+                sp.execute(SQL_DELETE_ORPHANED_CUSTODY_INFLOWS, params![height])?;
+                sp.execute(SQL_DELETE_ORPHANED_BLOCK_DIGESTS, params![height])?;
+                sp.commit()?;
+                Ok(record)
+            })
+        })
+    }
+
+    pub fn query_block_time_by_height(&self, height: u32) -> Result<u64, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_BLOCK_TIME_BY_HEIGHT, params![height], |row| {
+                row.get(0)
+            })
+        })
+    }
+
+    pub fn query_block_summary_by_height(
+        &self,
+        height: u32,
+    ) -> Result<Option<BlockSummaryRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_BLOCK_SUMMARY_BY_HEIGHT,
+                params![height],
+                make_block_summary,
+            )
+            .optional()
+        })
+    }
+
+    pub fn query_block_summary_by_hash(
+        &self,
+        hash: &str,
+    ) -> Result<Option<BlockSummaryRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_BLOCK_SUMMARY_BY_HASH,
+                params![hash],
+                make_block_summary,
+            )
+            .optional()
+        })
+    }
+
+    /// The summaries of the blocks `from..=to` ordered by the height
+    pub fn query_block_summaries(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<BlockSummaryRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_BLOCK_SUMMARIES)?;
+            let iter = stmt.query_map(params![from, to], make_block_summary)?;
+            iter.collect()
+        })
+    }
+
+    /// The miners of the blocks `from..=to`, the ones of the most blocks first
+    pub fn query_miner_stats(
+        &self,
+        from: u32,
+        to: u32,
+        limit: u32,
+    ) -> Result<Vec<MinerStatsRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_MINER_STATS)?;
+            let iter = stmt.query_map(params![from, to, limit], make_miner_stats)?;
+            iter.collect()
+        })
+    }
+
+    /// `None` if the miner made no block indexed
+    pub fn query_miner_stats_of_miner(
+        &self,
+        miner: &str,
+    ) -> Result<Option<MinerStatsRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_MINER_STATS_OF_MINER,
+                params![miner],
+                make_miner_stats,
+            )
+            .optional()
+        })
+    }
+
+    /// The blocks of the miner made from `from` to `to` (inclusive) by the day, returns
+    /// (days since the epoch, blocks) of the days it made any block
+    pub fn query_miner_daily_blocks(
+        &self,
+        miner: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, u32)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_MINER_DAILY_BLOCKS)?;
+            let iter = stmt.query_map(params![miner, from, to], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The height of the chain at `time`, `None` when no block is made before it
+    pub fn query_height_by_time(&self, time: u64) -> Result<Option<u32>, Error> {
+        self.read(|c| c.query_row(SQL_QUERY_HEIGHT_BY_TIME, params![time], |row| row.get(0)))
+    }
+
+    /// Whether any coin is paid to the address, the spent ones included
+    pub fn query_address_has_coins(&self, address: &str) -> Result<bool, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_ADDRESS_HAS_COINS, params![address], |row| {
+                row.get(0)
+            })
+        })
+    }
+
+    pub fn query_balance(&self, address: &str, height: u32) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            Ok(c.query_row(
+                SQL_QUERY_BALANCE_OF_ADDRESS,
+                params![address, height, height],
+                |row| row.get(0),
+            )?)
+        })
+    }
+
+    /// The current balance of the address, it follows the synced blocks
+    pub fn query_address_balance(&self, address: &str) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_ADDRESS_BALANCE, params![address], |row| {
+                row.get(0)
+            })
+        })
+    }
+
+    /// The coins of the address not spent yet, ordered by the txid and the index
+    pub fn query_unspent_coins(&self, address: &str) -> Result<Vec<UnspentCoinRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNSPENT_COINS_OF_ADDRESS)?;
+            let iter = stmt.query_map(params![address], |row| {
+                Ok(UnspentCoinRecord {
+                    txid: row.get(0)?,
+                    n: row.get(1)?,
+                    value: row.get(2)?,
+                    height: row.get(3)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The total of the coins ever paid to the address, the spent and the archived ones included
+    pub fn query_received_by_address(&self, address: &str) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_RECEIVED_BY_ADDRESS, params![address], |row| {
+                row.get(0)
+            })
+        })
+    }
+
+    /// The addresses with the largest current balances, ordered by the balance
+    pub fn query_richlist(&self, limit: u32) -> Result<Vec<(String, DepcAmount)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_RICHLIST)?;
+            let iter = stmt.query_map(params![limit], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_inputs(&self, txid: &str) -> Result<Vec<String>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_ADDRESSES_FROM_TX_INPUTS)?;
+            let iter = stmt.query_map(params![txid], |row| {
+                let address: String = row.get(0)?;
+                Ok(address)
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_txids_those_inputs_contain_address(
+        &self,
+        address: &str,
+    ) -> Result<Vec<String>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_TXIDS_THOSE_INPUTS_CONTAIN_ADDRESS)?;
+            let iter = stmt.query_map(params![address], |row| Ok(row.get(0).unwrap()))?;
+            iter.collect()
+        })
+    }
+
+    /// Save the exchange address found in transaction `txid`, true if the address is new. The
+    /// address saved already is counted as rediscovered
+    pub fn add_analyzed_exchange_address_from_tx(
+        &self,
+        address: &str,
+        txid: &str,
+        first_seen: u64,
+    ) -> Result<bool, Error> {
+        let (address, txid) = (address.to_owned(), txid.to_owned());
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        let rediscoveries = self.write(move |c| {
+            busy_retry.run(|| {
+                c.query_row(
+                    SQL_UPSERT_EXCHANGE_ADDRESSE,
+                    params![address, txid, first_seen],
+                    |row| row.get::<_, u64>(0),
+                )
+            })
+        })?;
+        Ok(rediscoveries == 0)
+    }
+
+    /// All the exchange addresses, see `query_exchange_addresses_after` for the pages of them
+    pub fn query_analyzed_exchange_addresses(&self) -> Result<Vec<String>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_EXCHANGE_ADDRESSES)?;
+            let iter = stmt.query_map([], |row| {
+                let address: String = row.get(0)?;
+                Ok(address)
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_num_exchange_addresses(&self) -> Result<u64, Error> {
+        self.read(|c| Ok(c.query_row(SQL_QUERY_NUM_EXCHANGE_ADDRESSES, [], |row| row.get(0))?))
+    }
+
+    /// At most `limit` exchange addresses after `after` in the order of the addresses, the
+    /// first ones if it's empty
+    pub fn query_exchange_addresses_after(
+        &self,
+        after: &str,
+        limit: u32,
+    ) -> Result<Vec<ExchangeAddressRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_EXCHANGE_ADDRESSES_AFTER)?;
+            let iter = stmt.query_map(params![after, limit], make_exchange_address)?;
+            iter.collect()
+        })
+    }
+
+    /// Returns `false` when the address is visited by the analysis already
+    pub fn add_analysis_frontier(
+        &self,
+        seed_txid: &str,
+        address: &str,
+        depth: u32,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_INSERT_ANALYSIS_FRONTIER,
+            params![seed_txid, address, depth],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn mark_analysis_frontier_expanded(
+        &self,
+        seed_txid: &str,
+        address: &str,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_MARK_ANALYSIS_FRONTIER_EXPANDED,
+            params![seed_txid, address],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_analysis_frontier(
+        &self,
+        seed_txid: &str,
+    ) -> Result<Vec<AnalysisFrontierRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_ANALYSIS_FRONTIER)?;
+            let iter = stmt.query_map(params![seed_txid], |row| {
+                Ok(AnalysisFrontierRecord {
+                    address: row.get(0)?,
+                    depth: row.get(1)?,
+                    expanded: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The input addresses of the spending transactions as (spent txid, owner), ordered by the
+    /// spent txid
+    pub fn query_spent_inputs(&self) -> Result<Vec<(String, String)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SPENT_INPUTS)?;
+            let iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            iter.collect()
+        })
+    }
+
+    /// The outputs of the transactions those have exactly 2 outputs as (txid, owner, first
+    /// seen), `first seen` is true when the output is the first coin ever paid to the owner
+    pub fn query_outputs_of_two_output_txs(&self) -> Result<Vec<(String, String, bool)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_OUTPUTS_OF_TWO_OUTPUT_TXS)?;
+            let iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            iter.collect()
+        })
+    }
+
+    /// Replace all the clusters with `clusters` (address, cluster id) in one savepoint
+    pub fn replace_address_clusters(&self, clusters: &[(String, u64)]) -> Result<(), Error> {
+        let clusters = clusters.to_vec();
+        self.write(move |c| {
+            let sp = c.savepoint()?;
+            sp.execute(SQL_DELETE_ADDRESS_CLUSTERS, [])?;
+            {
+                let mut stmt = sp.prepare(SQL_INSERT_ADDRESS_CLUSTER)?;
+                for (address, cluster_id) in clusters {
+                    stmt.execute(params![address, cluster_id])?;
+                }
+            }
+            sp.commit()
+        })
+    }
+
+    pub fn query_cluster_id_of_address(&self, address: &str) -> Result<Option<u64>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_CLUSTER_ID_OF_ADDRESS, params![address], |row| {
+                row.get(0)
+            })
+            .optional()
+        })
+    }
+
+    pub fn query_cluster_members(&self, cluster_id: u64) -> Result<Vec<String>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_CLUSTER_MEMBERS)?;
+            let iter = stmt.query_map(params![cluster_id], |row| row.get(0))?;
+            iter.collect()
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_sweep(
+        &self,
+        from_address: &str,
+        to_address: &str,
+        amount: DepcAmount,
+        txid: Option<&str>,
+        raw_tx: &str,
+        inputs: &str,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_DEPC_SWEEP,
+            params![
+                from_address,
+                to_address,
+                amount,
+                txid,
+                HexPayload(raw_tx.to_owned()),
+                inputs,
+                timestamp,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_sweeps(&self, limit: u32) -> Result<Vec<SweepRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_DEPC_SWEEPS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_sweep)?;
+            iter.collect()
+        })
+    }
+
+    /// The unsigned sweeps the operator has not broadcast yet, the oldest first
+    pub fn query_unsigned_sweeps(&self) -> Result<Vec<SweepRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNSIGNED_DEPC_SWEEPS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_sweep)?;
+            iter.collect()
+        })
+    }
+
+    /// Set the transaction spends the inputs of the unsigned sweep `id`, returns false if it's
+    /// settled already
+    pub fn settle_unsigned_sweep(&self, id: u64, txid: &str) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_SETTLE_UNSIGNED_DEPC_SWEEP,
+            params![txid, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn add_shadow_action(
+        &self,
+        action: &str,
+        source_txid: &str,
+        recipient: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_SHADOW_ACTION,
+            params![
+                action,
+                source_txid,
+                recipient,
+                amount,
+                timestamp,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_shadow_actions(&self, limit: u32) -> Result<Vec<ShadowActionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SHADOW_ACTIONS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], |row| {
+                Ok(ShadowActionRecord {
+                    id: row.get(0)?,
+                    action: row.get(1)?,
+                    source_txid: row.get(2)?,
+                    recipient: row.get(3)?,
+                    amount: row.get(4)?,
+                    timestamp: row.get(5)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn add_dead_letter(
+        &self,
+        height: u32,
+        txid: &str,
+        vout: Option<u32>,
+        reason: &str,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_DEAD_LETTER,
+            params![height, txid, vout, reason, timestamp, self.bridge_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_dead_letters(&self, limit: u32) -> Result<Vec<DeadLetterRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_DEAD_LETTERS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], |row| {
+                Ok(DeadLetterRecord {
+                    id: row.get(0)?,
+                    height: row.get(1)?,
+                    txid: row.get(2)?,
+                    vout: row.get(3)?,
+                    reason: row.get(4)?,
+                    timestamp: row.get(5)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn count_dead_letters_since(&self, since: u64) -> Result<u64, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_COUNT_DEAD_LETTERS_SINCE,
+                params![self.bridge_id, since],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Returns the id of the new row
+    pub fn add_pending_action(
+        &self,
+        action: &str,
+        source_txid: &str,
+        recipient: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_PENDING_ACTION,
+            params![
+                action,
+                source_txid,
+                recipient,
+                amount,
+                timestamp,
+                self.bridge_id
+            ],
+        )
+    }
+
+    /// Claim the action for a worker, returns `None` when it's claimed by another worker or
+    /// it's done already
+    pub fn claim_pending_action(
+        &self,
+        id: u64,
+        timestamp: u64,
+    ) -> Result<Option<PendingActionRecord>, Error> {
+        let bridge_id = self.bridge_id.clone();
+        // claimed and read by one command, the action cannot be changed between them
+        self.write(move |c| {
+            if c.execute(SQL_CLAIM_PENDING_ACTION, params![timestamp, bridge_id, id])? == 0 {
+                return Ok(None);
+            }
+            c.query_row(
+                SQL_QUERY_PENDING_ACTION,
+                params![bridge_id, id],
+                make_pending_action,
+            )
+            .optional()
+        })
+    }
+
+    pub fn query_pending_action(&self, id: u64) -> Result<Option<PendingActionRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_PENDING_ACTION,
+                params![self.bridge_id, id],
+                make_pending_action,
+            )
+            .optional()
+        })
+    }
+
+    pub fn complete_pending_action(
+        &self,
+        id: u64,
+        txid: Option<&str>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_COMPLETE_PENDING_ACTION,
+            params![timestamp, txid, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// The transaction of the action is confirmed at `timestamp`
+    pub fn confirm_pending_action(&self, id: u64, timestamp: u64) -> Result<(), Error> {
+        self.execute(
+            SQL_CONFIRM_PENDING_ACTION,
+            params![timestamp, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// The actions the transactions of are in block `block_hash` are confirmed at `timestamp`,
+    /// returns the number of them
+    pub fn confirm_pending_actions_in_block(
+        &self,
+        block_hash: &str,
+        timestamp: u64,
+    ) -> Result<usize, Error> {
+        let block_hash = block_hash.to_owned();
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                sp.execute(
+                    SQL_RESTORE_REPLACED_PAYOUTS_IN_BLOCK,
+                    params![bridge_id, block_hash],
+                )?;
+                let confirmed = sp.execute(
+                    SQL_CONFIRM_PENDING_ACTIONS_IN_BLOCK,
+                    params![timestamp, bridge_id, block_hash],
+                )?;
+                sp.commit()?;
+                Ok(confirmed)
+            })
+        })
+    }
+
+    /// The seconds from being found to being confirmed of the actions found at `since` or
+    /// later, in ascending order
+    pub fn query_confirmation_latencies(
+        &self,
+        action: &str,
+        since: u64,
+    ) -> Result<Vec<u64>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_CONFIRMATION_LATENCIES)?;
+            let iter = stmt.query_map(params![self.bridge_id, action, since], |row| row.get(0))?;
+            iter.collect()
+        })
+    }
+
+    /// Give the claimed action back, so it's picked again by the workers
+    pub fn release_pending_action(&self, id: u64) -> Result<(), Error> {
+        self.execute(SQL_RELEASE_PENDING_ACTION, params![self.bridge_id, id])?;
+        Ok(())
+    }
+
+    /// The times the action failed for the transient reasons, see `defer_pending_action`
+    pub fn query_pending_action_attempts(&self, id: u64) -> Result<u32, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_PENDING_ACTION_ATTEMPTS,
+                params![self.bridge_id, id],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Give back the action failed for a transient reason `attempts` times, it's not claimed
+    /// again before `retry_at`
+    pub fn defer_pending_action(&self, id: u64, attempts: u32, retry_at: u64) -> Result<(), Error> {
+        self.execute(
+            SQL_DEFER_PENDING_ACTION,
+            params![attempts, retry_at, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Give back all the actions are claimed but not done, returns the number of them. The
+    /// withdrawals of the signed payouts are kept, see `add_depc_payout`
+    pub fn release_claimed_pending_actions(&self) -> Result<usize, Error> {
+        self.execute(
+            SQL_RELEASE_CLAIMED_PENDING_ACTIONS,
+            params![self.bridge_id, self.bridge_id],
+        )
+    }
+
+    /// The actions of a kind claimed by the workers but not done, e.g. the ones left by the
+    /// last run before they're released
+    pub fn query_claimed_pending_actions(
+        &self,
+        action: &str,
+    ) -> Result<Vec<PendingActionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_CLAIMED_PENDING_ACTIONS)?;
+            let iter = stmt.query_map(params![self.bridge_id, action], make_pending_action)?;
+            iter.collect()
+        })
+    }
+
+    /// Whether any action is made by the transaction `txid`
+    pub fn query_pending_action_exists_by_txid(&self, txid: &str) -> Result<bool, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_PENDING_ACTION_EXISTS_BY_TXID,
+                params![self.bridge_id, txid],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    pub fn query_unclaimed_pending_action_ids(&self, action: &str) -> Result<Vec<u64>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNCLAIMED_PENDING_ACTION_IDS)?;
+            let iter = stmt.query_map(params![self.bridge_id, action], |row| row.get(0))?;
+            iter.collect()
+        })
+    }
+
+    /// The deposits and withdrawals found in `from..=to`, whether they're done or not
+    pub fn query_pending_actions_between(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<PendingActionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PENDING_ACTIONS_BETWEEN)?;
+            let iter = stmt.query_map(params![self.bridge_id, from, to], make_pending_action)?;
+            iter.collect()
+        })
+    }
+
+    /// The totals of the actions found at `since` or later by kind
+    pub fn query_pending_action_totals(
+        &self,
+        since: u64,
+    ) -> Result<Vec<PendingActionTotals>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PENDING_ACTION_TOTALS)?;
+            let iter = stmt.query_map(params![self.bridge_id, since], |row| {
+                Ok(PendingActionTotals {
+                    action: row.get(0)?,
+                    count: row.get(1)?,
+                    amount: row.get(2)?,
+                    avg_latency: row.get(3)?,
+                    pending: row.get(4)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_pending_action_backlog(&self) -> Result<Vec<PendingActionBacklog>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PENDING_ACTION_BACKLOG)?;
+            let iter = stmt.query_map(params![self.bridge_id], |row| {
+                Ok(PendingActionBacklog {
+                    action: row.get(0)?,
+                    count: row.get(1)?,
+                    amount: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The actions of kind `action` found before `before` those are not confirmed yet
+    pub fn query_stuck_pending_actions(
+        &self,
+        action: &str,
+        before: u64,
+    ) -> Result<Vec<StuckPendingActions>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_STUCK_PENDING_ACTIONS)?;
+            let iter = stmt.query_map(params![self.bridge_id, action, before], |row| {
+                Ok(StuckPendingActions {
+                    state: row.get(0)?,
+                    count: row.get(1)?,
+                    oldest: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Hold the unclaimed action `id` with `reason`, the workers never claim it until it's
+    /// released
+    pub fn hold_pending_action(&self, id: u64, reason: &str, held_at: u64) -> Result<(), Error> {
+        self.execute(
+            SQL_HOLD_PENDING_ACTION,
+            params![held_at, reason, self.bridge_id, id],
         )?;
         Ok(())
     }
 
-    pub fn query_best_height(&self) -> Option<u32> {
-        let c = self.conn.lock().unwrap();
-        if let Ok(height) = c.query_row(SQL_QUERY_BEST_HEIGHT, [], |row| -> Result<u32, Error> {
-            let height = row.get(0).unwrap();
-            Ok(height)
-        }) {
-            Some(height)
-        } else {
+    /// Returns whether the action is held and released now
+    pub fn release_held_pending_action(&self, id: u64, released_at: u64) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_RELEASE_HELD_PENDING_ACTION,
+            params![released_at, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// The actions held and not released yet, the oldest first
+    pub fn query_held_pending_actions(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<HeldPendingActionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_HELD_PENDING_ACTIONS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_held_pending_action)?;
+            iter.collect()
+        })
+    }
+
+    /// The action ever held, it's returned after it's released as well
+    pub fn query_held_pending_action(
+        &self,
+        id: u64,
+    ) -> Result<Option<HeldPendingActionRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_HELD_PENDING_ACTION,
+                params![self.bridge_id, id],
+                make_held_pending_action,
+            )
+            .optional()
+        })
+    }
+
+    /// The amount of the `action`s made to `recipient` since `since` by the time of their
+    /// transfers (the time they're found without it), the held ones are not counted until
+    /// they're released
+    pub fn query_recipient_volume_since(
+        &self,
+        action: &str,
+        recipient: &str,
+        since: u64,
+    ) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_RECIPIENT_VOLUME_SINCE,
+                params![self.bridge_id, action, recipient, since],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    pub fn add_price(
+        &self,
+        currency: &str,
+        timestamp: u64,
+        price: f64,
+        source: &str,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_PRICE,
+            params![currency, timestamp, price, source],
+        )?;
+        Ok(())
+    }
+
+    /// The last price recorded at or before `timestamp`
+    pub fn query_price_at(&self, currency: &str, timestamp: u64) -> Result<Option<f64>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_PRICE_AT, params![currency, timestamp], |row| {
+                row.get(0)
+            })
+            .optional()
+        })
+    }
+
+    pub fn add_refund(
+        &self,
+        depc_txid: &str,
+        vout: u32,
+        refund_address: &str,
+        amount: DepcAmount,
+        reason: &str,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_DEPC_REFUND,
+            params![
+                depc_txid,
+                vout,
+                refund_address,
+                amount,
+                reason,
+                timestamp,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The refunds are not made yet and are recorded no later than `timestamp`
+    pub fn query_due_refunds(&self, timestamp: u64) -> Result<Vec<RefundRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_DUE_DEPC_REFUNDS)?;
+            let iter = stmt.query_map(params![self.bridge_id, timestamp], make_refund)?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_refunds(&self, limit: u32) -> Result<Vec<RefundRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_DEPC_REFUNDS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_refund)?;
+            iter.collect()
+        })
+    }
+
+    /// The refund of the invalid deposit `depc_txid:vout`
+    pub fn query_refund_of_output(
+        &self,
+        depc_txid: &str,
+        vout: u32,
+    ) -> Result<Option<RefundRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_DEPC_REFUND_OF_OUTPUT,
+                params![self.bridge_id, depc_txid, vout],
+                make_refund,
+            )
+            .optional()
+        })
+    }
+
+    pub fn count_refunds_since(&self, since: u64) -> Result<u64, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_COUNT_REFUNDS_SINCE,
+                params![self.bridge_id, since],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    pub fn complete_refund(
+        &self,
+        id: u64,
+        txid: Option<&str>,
+        refunded_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_COMPLETE_DEPC_REFUND,
+            params![txid, refunded_at, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Claim refund `id` to `address`, it's false when the refund is made already or another
+    /// claim of it is submitted or verified
+    pub fn claim_refund(&self, id: u64, address: &str, signature: &str) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_CLAIM_DEPC_REFUND,
+            params![address, signature, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// The claims of the refunds not made yet those are not verified yet
+    pub fn query_submitted_refund_claims(&self) -> Result<Vec<RefundRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SUBMITTED_DEPC_REFUND_CLAIMS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_refund)?;
+            iter.collect()
+        })
+    }
+
+    /// Set the submitted claim of refund `id` verified or rejected
+    pub fn set_refund_claim_status(&self, id: u64, status: &str) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_DEPC_REFUND_CLAIM_STATUS,
+            params![status, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the id of the new row
+    pub fn add_deposit_intent(
+        &self,
+        sender: &str,
+        recipient: &str,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_DEPOSIT_INTENT,
+            params![sender, recipient, created_at, self.bridge_id],
+        )
+    }
+
+    pub fn query_deposit_intent(&self, id: u64) -> Result<Option<DepositIntentRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_DEPOSIT_INTENT,
+                params![self.bridge_id, id],
+                make_deposit_intent,
+            )
+            .optional()
+        })
+    }
+
+    /// The oldest intent of `sender` which is not matched yet and is created in
+    /// `created_from..=created_to`
+    pub fn query_open_deposit_intent(
+        &self,
+        sender: &str,
+        created_from: u64,
+        created_to: u64,
+    ) -> Result<Option<DepositIntentRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_OPEN_DEPOSIT_INTENT,
+                params![self.bridge_id, sender, created_from, created_to],
+                make_deposit_intent,
+            )
+            .optional()
+        })
+    }
+
+    /// Returns `false` when the intent is matched already
+    pub fn match_deposit_intent(
+        &self,
+        id: u64,
+        txid: &str,
+        vout: u32,
+        timestamp: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_MATCH_DEPOSIT_INTENT,
+            params![txid, vout, timestamp, self.bridge_id, id],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// The xpub the deposit addresses of the bridge are derived from, it fails when the xpub is
+    /// used by another bridge
+    pub fn save_deposit_xpub(&self, xpub: &str) -> Result<(), Error> {
+        self.execute(SQL_SAVE_DEPOSIT_XPUB, params![self.bridge_id, xpub])?;
+        Ok(())
+    }
+
+    pub fn query_deposit_xpub(&self) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_DEPOSIT_XPUB, params![self.bridge_id], |row| {
+                row.get(0)
+            })
+            .optional()
+        })
+    }
+
+    /// It fails when the address or the index is taken already
+    pub fn add_deposit_address(
+        &self,
+        address: &str,
+        derivation_index: u32,
+        derivation_path: &str,
+        recipient: &str,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_DEPOSIT_ADDRESS,
+            params![
+                address,
+                derivation_index,
+                derivation_path,
+                recipient,
+                created_at,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn query_deposit_address(
+        &self,
+        address: &str,
+    ) -> Result<Option<DepositAddressRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_DEPOSIT_ADDRESS,
+                params![self.bridge_id, address],
+                make_deposit_address,
+            )
+            .optional()
+        })
+    }
+
+    /// The current balance of all the deposit addresses of the bridge
+    pub fn query_deposit_addresses_balance(&self) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_DEPOSIT_ADDRESSES_BALANCE,
+                params![self.bridge_id],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// The index after the last one given out, 0 if none is
+    pub fn query_next_deposit_address_index(&self) -> Result<u32, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_NEXT_DEPOSIT_ADDRESS_INDEX,
+                params![self.bridge_id],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Returns the id of the new row
+    pub fn add_deposit_root(
+        &self,
+        root: &str,
+        leaf_count: u64,
+        covered_until: u64,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_DEPOSIT_ROOT,
+            params![root, leaf_count, covered_until, created_at, self.bridge_id],
+        )
+    }
+
+    pub fn query_latest_deposit_root(&self) -> Result<Option<DepositRootRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_LATEST_DEPOSIT_ROOT,
+                params![self.bridge_id],
+                |row| {
+                    Ok(DepositRootRecord {
+                        id: row.get(0)?,
+                        root: row.get(1)?,
+                        leaf_count: row.get(2)?,
+                        covered_until: row.get(3)?,
+                        created_at: row.get(4)?,
+                        memo_signature: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+        })
+    }
+
+    pub fn set_deposit_root_memo_signature(&self, id: u64, signature: &str) -> Result<(), Error> {
+        self.execute(
+            SQL_UPDATE_DEPOSIT_ROOT_MEMO_SIGNATURE,
+            params![signature, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the id of the new row
+    pub fn add_parameter_change(
+        &self,
+        name: &str,
+        value: &str,
+        created_at: u64,
+        activates_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_PARAMETER_CHANGE,
+            params![name, value, created_at, activates_at, self.bridge_id],
+        )
+    }
+
+    pub fn query_parameter_change(&self, id: u64) -> Result<Option<ParameterChangeRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_PARAMETER_CHANGE,
+                params![self.bridge_id, id],
+                make_parameter_change,
+            )
+            .optional()
+        })
+    }
+
+    pub fn query_parameter_changes(&self, limit: u32) -> Result<Vec<ParameterChangeRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PARAMETER_CHANGES)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_parameter_change)?;
+            iter.collect()
+        })
+    }
+
+    /// Returns `false` when the change is cancelled already or it's in effect at `timestamp`
+    pub fn cancel_parameter_change(&self, id: u64, timestamp: u64) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_CANCEL_PARAMETER_CHANGE,
+            params![timestamp, self.bridge_id, id, timestamp],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// The value of the last change activated at or before `timestamp` and not cancelled
+    pub fn query_effective_parameter_value(
+        &self,
+        name: &str,
+        timestamp: u64,
+    ) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_EFFECTIVE_PARAMETER_VALUE,
+                params![self.bridge_id, name, timestamp],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+    }
+
+    /// Insert or update the job, the last run is kept
+    pub fn save_job(
+        &self,
+        name: &str,
+        schedule: &str,
+        enabled: bool,
+        next_run_at: Option<u64>,
+    ) -> Result<(), Error> {
+        self.execute(SQL_SAVE_JOB, params![name, schedule, enabled, next_run_at])?;
+        Ok(())
+    }
+
+    pub fn query_job(&self, name: &str) -> Result<Option<JobRecord>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_JOB, params![name], make_job)
+                .optional()
+        })
+    }
+
+    pub fn query_jobs(&self) -> Result<Vec<JobRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_JOBS)?;
+            let iter = stmt.query_map([], make_job)?;
+            iter.collect()
+        })
+    }
+
+    pub fn start_job(
+        &self,
+        name: &str,
+        started_at: u64,
+        next_run_at: Option<u64>,
+    ) -> Result<(), Error> {
+        self.execute(SQL_START_JOB, params![started_at, next_run_at, name])?;
+        Ok(())
+    }
+
+    pub fn set_job_next_run(&self, name: &str, next_run_at: Option<u64>) -> Result<(), Error> {
+        self.execute(SQL_SET_JOB_NEXT_RUN, params![next_run_at, name])?;
+        Ok(())
+    }
+
+    pub fn finish_job(
+        &self,
+        name: &str,
+        finished_at: u64,
+        error: Option<&str>,
+    ) -> Result<(), Error> {
+        self.execute(SQL_FINISH_JOB, params![finished_at, error, name])?;
+        Ok(())
+    }
+
+    /// Record the Solana transaction is used by the withdrawal `depc_txid`, the first use is
+    /// kept
+    pub fn add_withdraw_signature(
+        &self,
+        signature: &str,
+        depc_txid: &str,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_WITHDRAW_SIGNATURE,
+            params![signature, depc_txid, timestamp, self.bridge_id],
+        )?;
+        Ok(())
+    }
+
+    /// The withdrawal the Solana transaction is used by
+    pub fn query_withdraw_signature(&self, signature: &str) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_WITHDRAW_SIGNATURE,
+                params![self.bridge_id, signature],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+    }
+
+    /// Returns the id of the new row, `None` when the Solana transaction is requested already
+    pub fn add_withdraw_request(
+        &self,
+        signature: &str,
+        recipient: &str,
+        signer: &str,
+        origin: &str,
+        status: &str,
+        created_at: u64,
+    ) -> Result<Option<u64>, Error> {
+        let params = to_values(params![
+            signature,
+            recipient,
+            signer,
+            origin,
+            status,
+            created_at,
+            self.bridge_id
+        ])?;
+        self.write(move |c| {
+            let n = c.execute(SQL_INSERT_WITHDRAW_REQUEST, params_from_iter(params))?;
+            Ok((n > 0).then(|| c.last_insert_rowid() as u64))
+        })
+    }
+
+    pub fn query_withdraw_request(&self, id: u64) -> Result<Option<WithdrawRequestRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_WITHDRAW_REQUEST,
+                params![self.bridge_id, id],
+                make_withdraw_request,
+            )
+            .optional()
+        })
+    }
+
+    /// The requests of `status` in the order they're made
+    pub fn query_withdraw_requests_by_status(
+        &self,
+        status: &str,
+    ) -> Result<Vec<WithdrawRequestRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_WITHDRAW_REQUESTS_BY_STATUS)?;
+            let iter = stmt.query_map(params![self.bridge_id, status], make_withdraw_request)?;
+            iter.collect()
+        })
+    }
+
+    /// Returns `false` when the request is processed already
+    pub fn complete_withdraw_request(
+        &self,
+        id: u64,
+        status: &str,
+        reason: Option<&str>,
+        pending_action_id: Option<u64>,
+        processed_at: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_COMPLETE_WITHDRAW_REQUEST,
+            params![
+                status,
+                reason,
+                pending_action_id,
+                processed_at,
+                self.bridge_id,
+                id
+            ],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn query_custody_eras(&self) -> Result<Vec<CustodyEraRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_CUSTODY_ERAS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_custody_era)?;
+            iter.collect()
+        })
+    }
+
+    /// Start the next custody era with `address`, the address of the current era is watched
+    /// until `watched_until`. Both are made in one savepoint
+    pub fn start_custody_era(
+        &self,
+        address: &str,
+        watched_until: u64,
+        started_at: u64,
+    ) -> Result<(), Error> {
+        let address = address.to_owned();
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                sp.execute(SQL_RETIRE_CUSTODY_ERA, params![watched_until, bridge_id])?;
+                sp.execute(
+                    SQL_INSERT_CUSTODY_ERA,
+                    params![bridge_id, address, started_at],
+                )?;
+                sp.commit()
+            })
+        })
+    }
+
+    pub fn set_pending_action_custody_era(&self, id: u64, custody_era: u32) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_PENDING_ACTION_CUSTODY_ERA,
+            params![custody_era, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// The time of the block the transfer of the action is made in, see
+    /// `query_recipient_volume_since`
+    pub fn set_pending_action_transfer_time(
+        &self,
+        id: u64,
+        transfer_time: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_PENDING_ACTION_TRANSFER_TIME,
+            params![transfer_time, self.bridge_id, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_refund_custody_era(
+        &self,
+        depc_txid: &str,
+        vout: u32,
+        custody_era: u32,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_REFUND_CUSTODY_ERA,
+            params![custody_era, self.bridge_id, depc_txid, vout],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_outgoing_transaction(
+        &self,
+        signature: &str,
+        raw_tx: &[u8],
+        last_valid_block_height: u64,
+        recipients: &str,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_OUTGOING_TRANSACTION,
+            params![
+                self.bridge_id,
+                signature,
+                raw_tx,
+                last_valid_block_height,
+                recipients,
+                created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_outgoing_transaction_status(
+        &self,
+        signature: &str,
+        status: &str,
+        settled_at: Option<u64>,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_OUTGOING_TRANSACTION_STATUS,
+            params![status, settled_at, self.bridge_id, signature],
+        )?;
+        Ok(())
+    }
+
+    /// The transactions not acknowledged yet in the order they're saved
+    pub fn query_unacknowledged_outgoing_transactions(
+        &self,
+    ) -> Result<Vec<OutgoingTransactionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNACKNOWLEDGED_OUTGOING_TRANSACTIONS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_outgoing_transaction)?;
+            iter.collect()
+        })
+    }
+
+    pub fn acknowledge_outgoing_transaction(
+        &self,
+        signature: &str,
+        acknowledged_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_ACKNOWLEDGE_OUTGOING_TRANSACTION,
+            params![acknowledged_at, self.bridge_id, signature],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_ata_owner(&self, account: &str, owner: &str) -> Result<(), Error> {
+        self.execute(SQL_INSERT_ATA_OWNER, params![account, owner])?;
+        Ok(())
+    }
+
+    pub fn query_ata_owner(&self, account: &str) -> Result<Option<String>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_ATA_OWNER, params![account], |row| row.get(0))
+                .optional()
+        })
+    }
+
+    pub fn add_solana_slot(&self, slot: u64, epoch: u64, block_time: u64) -> Result<(), Error> {
+        self.execute(SQL_INSERT_SOLANA_SLOT, params![slot, epoch, block_time])?;
+        Ok(())
+    }
+
+    /// The last sample at or before `time` and the first one at or after it
+    pub fn query_solana_slots_around_time(
+        &self,
+        time: u64,
+    ) -> Result<(Option<SolanaSlotRecord>, Option<SolanaSlotRecord>), Error> {
+        self.read(|c| {
+            let before = c
+                .query_row(
+                    SQL_QUERY_SOLANA_SLOT_BEFORE_TIME,
+                    params![time],
+                    make_solana_slot,
+                )
+                .optional()?;
+            let after = c
+                .query_row(
+                    SQL_QUERY_SOLANA_SLOT_AFTER_TIME,
+                    params![time],
+                    make_solana_slot,
+                )
+                .optional()?;
+            Ok((before, after))
+        })
+    }
+
+    /// The last sample at or before `slot` and the first one at or after it
+    pub fn query_solana_slots_around_slot(
+        &self,
+        slot: u64,
+    ) -> Result<(Option<SolanaSlotRecord>, Option<SolanaSlotRecord>), Error> {
+        self.read(|c| {
+            let before = c
+                .query_row(
+                    SQL_QUERY_SOLANA_SLOT_BEFORE,
+                    params![slot],
+                    make_solana_slot,
+                )
+                .optional()?;
+            let after = c
+                .query_row(SQL_QUERY_SOLANA_SLOT_AFTER, params![slot], make_solana_slot)
+                .optional()?;
+            Ok((before, after))
+        })
+    }
+
+    /// Move the cursor of `account` to `signature`
+    pub fn set_solana_cursor(
+        &self,
+        account: &str,
+        signature: &str,
+        slot: u64,
+        now: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SET_SOLANA_CURSOR,
+            params![account, signature, slot, now, self.bridge_id],
+        )?;
+        Ok(())
+    }
+
+    /// `None` if `account` is never scanned
+    pub fn query_solana_cursor(&self, account: &str) -> Result<Option<SolanaCursorRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_SOLANA_CURSOR,
+                params![self.bridge_id, account],
+                make_solana_cursor,
+            )
+            .optional()
+        })
+    }
+
+    pub fn add_ledger_entry(
+        &self,
+        event: &str,
+        debit: &str,
+        credit: &str,
+        amount: DepcAmount,
+        reference: &str,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_LEDGER_ENTRY,
+            params![
+                event,
+                debit,
+                credit,
+                amount,
+                reference,
+                timestamp,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the sequence number of the event
+    pub fn add_event(
+        &self,
+        event: &str,
+        amount: DepcAmount,
+        reference: &str,
+        timestamp: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_EVENT,
+            params![event, amount, reference, timestamp, self.bridge_id],
+        )
+    }
+
+    /// The events after sequence number `since_seq` in order
+    pub fn query_events(&self, since_seq: u64, limit: u32) -> Result<Vec<EventRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_EVENTS_SINCE)?;
+            let iter = stmt.query_map(params![self.bridge_id, since_seq, limit], |row| {
+                Ok(EventRecord {
+                    seq: row.get(0)?,
+                    event: row.get(1)?,
+                    amount: row.get(2)?,
+                    reference: row.get(3)?,
+                    timestamp: row.get(4)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_ledger_entries(&self, limit: u32) -> Result<Vec<LedgerEntryRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_LEDGER_ENTRIES)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], |row| {
+                Ok(LedgerEntryRecord {
+                    id: row.get(0)?,
+                    event: row.get(1)?,
+                    debit: row.get(2)?,
+                    credit: row.get(3)?,
+                    amount: row.get(4)?,
+                    reference: row.get(5)?,
+                    timestamp: row.get(6)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The total of the entries of `event` posted at `since` or later
+    pub fn query_ledger_event_total(&self, event: &str, since: u64) -> Result<DepcAmount, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_LEDGER_EVENT_TOTAL,
+                params![self.bridge_id, event, since],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// The total debits and credits of each account, ordered by the account
+    pub fn query_ledger_account_totals(&self) -> Result<Vec<LedgerAccountTotals>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_LEDGER_ACCOUNT_TOTALS)?;
+            let iter = stmt.query_map(params![self.bridge_id], |row| {
+                Ok(LedgerAccountTotals {
+                    account: row.get(0)?,
+                    debit: row.get(1)?,
+                    credit: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// The daily totals of each event of the entries credit `account`, posted from `from` to
+    /// `to` (inclusive), ordered by the day and the event
+    pub fn query_ledger_daily_totals(
+        &self,
+        account: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<LedgerDailyTotal>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_LEDGER_DAILY_TOTALS)?;
+            let iter = stmt.query_map(params![self.bridge_id, account, from, to], |row| {
+                Ok(LedgerDailyTotal {
+                    day: row.get(0)?,
+                    event: row.get(1)?,
+                    amount: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Record a sweep before it's sent, returns the id of the sweep
+    pub fn add_pending_fee_sweep(
+        &self,
+        treasury: &str,
+        amount: DepcAmount,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_PENDING_FEE_SWEEP,
+            params![treasury, amount, created_at, self.bridge_id],
+        )
+    }
+
+    /// The pending sweep is sent by Solana transaction `signature`
+    pub fn complete_fee_sweep(&self, id: u64, signature: &str) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_COMPLETE_FEE_SWEEP,
+            params![signature, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// The pending sweep is never sent, its fees are swept again
+    pub fn fail_fee_sweep(&self, id: u64) -> Result<bool, Error> {
+        let updated = self.execute(SQL_FAIL_FEE_SWEEP, params![self.bridge_id, id])?;
+        Ok(updated > 0)
+    }
+
+    /// The latest sweeps of the bridge fees sent first
+    pub fn query_fee_sweeps(&self, limit: u32) -> Result<Vec<FeeSweepRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_FEE_SWEEPS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_fee_sweep)?;
+            iter.collect()
+        })
+    }
+
+    /// The sweeps not known to be sent or not, in the order they're recorded
+    pub fn query_pending_fee_sweeps(&self) -> Result<Vec<FeeSweepRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PENDING_FEE_SWEEPS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_fee_sweep)?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_fee_sweep_exists_by_signature(&self, signature: &str) -> Result<bool, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_FEE_SWEEP_EXISTS_BY_SIGNATURE,
+                params![self.bridge_id, signature],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Record the accounts consolidated by Solana transaction `signature`, all of them or none.
+    /// Returns the ids of the sweeps in the order of the accounts
+    pub fn add_dust_sweeps(
+        &self,
+        accounts: &[(String, DepcAmount)],
+        signature: &str,
+        created_at: u64,
+    ) -> Result<Vec<u64>, Error> {
+        let accounts = accounts.to_vec();
+        let signature = signature.to_owned();
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                let mut ids = Vec::with_capacity(accounts.len());
+                for (account, amount) in accounts.iter() {
+                    sp.execute(
+                        SQL_INSERT_DUST_SWEEP,
+                        params![account, amount, signature, created_at, bridge_id],
+                    )?;
+                    ids.push(sp.last_insert_rowid() as u64);
+                }
+                sp.commit()?;
+                Ok(ids)
+            })
+        })
+    }
+
+    /// The latest sweeps of the stranded tokens first
+    pub fn query_dust_sweeps(&self, limit: u32) -> Result<Vec<DustSweepRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_DUST_SWEEPS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], |row| {
+                Ok(DustSweepRecord {
+                    id: row.get(0)?,
+                    account: row.get(1)?,
+                    amount: row.get(2)?,
+                    signature: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Returns the id of the payout
+    /// Save the signed payout of the withdrawal before it's sent, returns the id of it
+    pub fn add_depc_payout(
+        &self,
+        action_id: u64,
+        txid: &str,
+        raw_tx: &str,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_DEPC_PAYOUT,
+            params![self.bridge_id, action_id, txid, raw_tx, created_at],
+        )
+    }
+
+    /// The payouts signed but not known to be sent, in the order they're saved
+    pub fn query_signed_depc_payouts(&self) -> Result<Vec<DepcPayoutRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SIGNED_DEPC_PAYOUTS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_depc_payout)?;
+            iter.collect()
+        })
+    }
+
+    pub fn complete_depc_payout(&self, id: u64, sent_at: u64) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_COMPLETE_DEPC_PAYOUT,
+            params![sent_at, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn add_multisig_payout(
+        &self,
+        action_id: u64,
+        raw_tx: &str,
+        prevtxs: &str,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_MULTISIG_PAYOUT,
+            params![action_id, raw_tx, prevtxs, created_at, self.bridge_id],
+        )
+    }
+
+    pub fn query_multisig_payout(&self, id: u64) -> Result<Option<MultisigPayoutRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_MULTISIG_PAYOUT,
+                params![self.bridge_id, id],
+                make_multisig_payout,
+            )
+            .optional()
+        })
+    }
+
+    /// The latest payout of pending action `action_id`
+    pub fn query_multisig_payout_by_action(
+        &self,
+        action_id: u64,
+    ) -> Result<Option<MultisigPayoutRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_MULTISIG_PAYOUT_BY_ACTION,
+                params![self.bridge_id, action_id],
+                make_multisig_payout,
+            )
+            .optional()
+        })
+    }
+
+    /// The payouts collecting the signatures in the order they're made
+    pub fn query_collecting_multisig_payouts(&self) -> Result<Vec<MultisigPayoutRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_COLLECTING_MULTISIG_PAYOUTS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_multisig_payout)?;
+            iter.collect()
+        })
+    }
+
+    /// Returns whether the payout is still collecting the signatures
+    pub fn complete_multisig_payout(
+        &self,
+        id: u64,
+        txid: &str,
+        sent_at: u64,
+    ) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_COMPLETE_MULTISIG_PAYOUT,
+            params![txid, sent_at, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Returns the id of the signed copy
+    pub fn add_multisig_signature(
+        &self,
+        payout_id: u64,
+        raw_tx: &str,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_MULTISIG_SIGNATURE,
+            params![payout_id, raw_tx, created_at, self.bridge_id],
+        )
+    }
+
+    /// The signed copies of the payout not merged or rejected yet, in the order they're
+    /// submitted
+    pub fn query_submitted_multisig_signatures(
+        &self,
+        payout_id: u64,
+    ) -> Result<Vec<MultisigSignatureRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SUBMITTED_MULTISIG_SIGNATURES)?;
+            let iter = stmt.query_map(params![self.bridge_id, payout_id], |row| {
+                Ok(MultisigSignatureRecord {
+                    id: row.get(0)?,
+                    payout_id: row.get(1)?,
+                    raw_tx: row.get(2)?,
+                    status: row.get(3)?,
+                    error: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Save the transaction of the payout with the signatures merged into it, the signed copies
+    /// are marked merged, or rejected with the reason, in the same transaction
+    pub fn merge_multisig_signatures(
+        &self,
+        payout_id: u64,
+        raw_tx: &str,
+        results: &[(u64, Option<String>)],
+    ) -> Result<(), Error> {
+        let raw_tx = raw_tx.to_owned();
+        let results = results.to_vec();
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                sp.execute(
+                    SQL_UPDATE_MULTISIG_PAYOUT_RAW_TX,
+                    params![raw_tx, bridge_id, payout_id],
+                )?;
+                for (id, error) in results.iter() {
+                    let status = if error.is_some() {
+                        "rejected"
+                    } else {
+                        "merged"
+                    };
+                    sp.execute(
+                        SQL_SET_MULTISIG_SIGNATURE_STATUS,
+                        params![status, error, bridge_id, id],
+                    )?;
+                }
+                sp.commit()
+            })
+        })
+    }
+
+    /// The withdrawals paid out by the transactions still unconfirmed, they're sent at
+    /// `max_sent_height` or before
+    pub fn query_stuck_payouts(
+        &self,
+        max_sent_height: u32,
+    ) -> Result<Vec<PendingActionRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_STUCK_PAYOUTS)?;
+            let iter = stmt.query_map(
+                params![self.bridge_id, max_sent_height],
+                make_pending_action,
+            )?;
+            iter.collect()
+        })
+    }
+
+    /// Save the signed bump of the payout of withdrawal `action_id` before it's sent, the
+    /// payout takes it by `complete_payout_bump`
+    ///
+    /// Returns the id of the bump
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_payout_bump(
+        &self,
+        action_id: u64,
+        kind: &str,
+        bumped_txid: &str,
+        txid: &str,
+        raw_tx: &str,
+        fee: DepcAmount,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_PAYOUT_BUMP,
+            params![
+                action_id,
+                kind,
+                bumped_txid,
+                txid,
+                raw_tx,
+                fee,
+                created_at,
+                self.bridge_id
+            ],
+        )
+    }
+
+    /// The bump is sent at best height `height`, the withdrawal takes the txid of the
+    /// replacement if it's `rbf`. The payout is bumped again only after blocks from `height`
+    ///
+    /// Returns false if the bump is not pending
+    pub fn complete_payout_bump(
+        &self,
+        bump: &PayoutBumpRecord,
+        height: u32,
+    ) -> Result<bool, Error> {
+        let (kind, txid) = (bump.kind.clone(), bump.txid.clone());
+        let (id, action_id) = (bump.id, bump.action_id);
+        let bridge_id = self.bridge_id.clone();
+        let busy_retry = Arc::clone(&self.pool.busy_retry);
+        self.write(move |c| {
+            busy_retry.run(|| {
+                let sp = c.savepoint()?;
+                if sp.execute(SQL_COMPLETE_PAYOUT_BUMP, params![bridge_id, id])? == 0 {
+                    return Ok(false);
+                }
+                if kind == "rbf" {
+                    sp.execute(
+                        SQL_REPLACE_PAYOUT_TXID,
+                        params![txid, height, bridge_id, action_id],
+                    )?;
+                } else {
+                    sp.execute(
+                        SQL_UPDATE_PAYOUT_SENT_HEIGHT,
+                        params![height, bridge_id, action_id],
+                    )?;
+                }
+                sp.commit()?;
+                Ok(true)
+            })
+        })
+    }
+
+    /// The bumps signed but not known to be sent, in the order they're saved
+    pub fn query_pending_payout_bumps(&self) -> Result<Vec<PayoutBumpRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PENDING_PAYOUT_BUMPS)?;
+            let iter = stmt.query_map(params![self.bridge_id], make_payout_bump)?;
+            iter.collect()
+        })
+    }
+
+    /// The bumps of the payout of withdrawal `action_id` in the order they're made
+    pub fn query_payout_bumps(&self, action_id: u64) -> Result<Vec<PayoutBumpRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_PAYOUT_BUMPS)?;
+            let iter = stmt.query_map(params![self.bridge_id, action_id], make_payout_bump)?;
+            iter.collect()
+        })
+    }
+
+    /// Record output `txid:vout` is processed into pending action `action_id`, returns whether
+    /// it's not recorded before
+    pub fn add_recorded_transaction(
+        &self,
+        txid: &str,
+        vout: u32,
+        action: &str,
+        action_id: u64,
+        recorded_at: u64,
+    ) -> Result<bool, Error> {
+        let inserted = self.execute(
+            SQL_INSERT_RECORDED_TRANSACTION,
+            params![txid, vout, action, action_id, recorded_at, self.bridge_id],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn query_recorded_transaction(
+        &self,
+        txid: &str,
+        vout: u32,
+    ) -> Result<Option<RecordedTransactionRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_RECORDED_TRANSACTION,
+                params![self.bridge_id, txid, vout],
+                |row| {
+                    Ok(RecordedTransactionRecord {
+                        id: row.get(0)?,
+                        txid: row.get(1)?,
+                        vout: row.get(2)?,
+                        action: row.get(3)?,
+                        action_id: row.get(4)?,
+                        recorded_at: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+        })
+    }
+
+    /// Record `fee` is charged on `amount` of the transfer `source_txid`, pending action
+    /// `action_id` is made for the rest
+    pub fn add_transfer_fee(
+        &self,
+        action: &str,
+        source_txid: &str,
+        action_id: u64,
+        amount: DepcAmount,
+        fee: DepcAmount,
+        created_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_TRANSFER_FEE,
+            params![
+                action,
+                source_txid,
+                action_id,
+                amount,
+                fee,
+                created_at,
+                self.bridge_id
+            ],
+        )
+    }
+
+    /// The latest fees charged
+    pub fn query_transfer_fees(&self, limit: u32) -> Result<Vec<TransferFeeRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_TRANSFER_FEES)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], |row| {
+                Ok(TransferFeeRecord {
+                    id: row.get(0)?,
+                    action: row.get(1)?,
+                    source_txid: row.get(2)?,
+                    action_id: row.get(3)?,
+                    amount: row.get(4)?,
+                    fee: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Record the payment `txid:vout` to custody address `address` makes no bridge action,
+    /// returns whether it's not recorded before
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_custody_inflow(
+        &self,
+        txid: &str,
+        vout: u32,
+        address: &str,
+        amount: DepcAmount,
+        reason: &str,
+        height: u32,
+        found_at: u64,
+    ) -> Result<bool, Error> {
+        let inserted = self.execute(
+            SQL_INSERT_CUSTODY_INFLOW,
+            params![
+                txid,
+                vout,
+                address,
+                amount,
+                reason,
+                height,
+                found_at,
+                self.bridge_id
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// The latest custody inflows those are not resolved yet
+    pub fn query_unclassified_custody_inflows(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<CustodyInflowRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNCLASSIFIED_CUSTODY_INFLOWS)?;
+            let iter = stmt.query_map(params![self.bridge_id, limit], make_custody_inflow)?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_custody_inflow(&self, id: u64) -> Result<Option<CustodyInflowRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_CUSTODY_INFLOW,
+                params![self.bridge_id, id],
+                make_custody_inflow,
+            )
+            .optional()
+        })
+    }
+
+    /// Resolve the unclassified inflow `id` with the note of the operator, returns whether it
+    /// was unclassified
+    pub fn resolve_custody_inflow(
+        &self,
+        id: u64,
+        resolution: &str,
+        resolved_at: u64,
+    ) -> Result<bool, Error> {
+        let updated = self.execute(
+            SQL_RESOLVE_CUSTODY_INFLOW,
+            params![resolution, resolved_at, self.bridge_id, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Record the digest of the `event_count` bridge events of block `block_hash`, the one of
+    /// the height is replaced
+    pub fn add_block_digest(
+        &self,
+        height: u32,
+        block_hash: &str,
+        event_count: u32,
+        digest: &str,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_BLOCK_DIGEST,
+            params![
+                height,
+                block_hash,
+                event_count,
+                digest,
+                created_at,
+                self.bridge_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The digests of the blocks `from..=to` by the height, the heights not synced are missing
+    pub fn query_block_digests(&self, from: u32, to: u32) -> Result<Vec<BlockDigestRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_BLOCK_DIGESTS_BETWEEN)?;
+            let iter = stmt.query_map(params![self.bridge_id, from, to], |row| {
+                Ok(BlockDigestRecord {
+                    height: row.get(0)?,
+                    block_hash: row.get(1)?,
+                    event_count: row.get(2)?,
+                    digest: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Returns the id of the plan
+    pub fn add_catch_up_plan(
+        &self,
+        from_height: u32,
+        to_height: u32,
+        status: &str,
+        started_at: u64,
+    ) -> Result<u64, Error> {
+        self.insert(
+            SQL_INSERT_CATCH_UP_PLAN,
+            params![from_height, to_height, status, started_at, self.bridge_id],
+        )
+    }
+
+    pub fn query_latest_catch_up_plan(&self) -> Result<Option<CatchUpPlanRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_LATEST_CATCH_UP_PLAN,
+                params![self.bridge_id],
+                make_catch_up_plan,
+            )
+            .optional()
+        })
+    }
+
+    /// Index up to `to_height` before the plan is made, the plan is never shortened
+    pub fn extend_catch_up_plan(&self, id: u64, to_height: u32) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_EXTEND_CATCH_UP_PLAN,
+            params![to_height, self.bridge_id, id],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Returns `false` when the plan is made already
+    #[allow(clippy::too_many_arguments)]
+    pub fn plan_catch_up(
+        &self,
+        id: u64,
+        status: &str,
+        deposits: u64,
+        deposit_amount: DepcAmount,
+        withdrawals: u64,
+        withdraw_amount: DepcAmount,
+        planned_at: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_PLAN_CATCH_UP,
+            params![
+                status,
+                deposits,
+                deposit_amount,
+                withdrawals,
+                withdraw_amount,
+                planned_at,
+                self.bridge_id,
+                id
+            ],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Move the plan of `from_status` to `status`, returns `false` when it's not of
+    /// `from_status` or confirmed already
+    pub fn confirm_catch_up_plan(
+        &self,
+        id: u64,
+        from_status: &str,
+        status: &str,
+        confirmed_at: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_CONFIRM_CATCH_UP_PLAN,
+            params![status, confirmed_at, self.bridge_id, id, from_status],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn save_watched_address(
+        &self,
+        chain: &str,
+        address: &str,
+        label: &str,
+        webhook: Option<&str>,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SAVE_WATCHED_ADDRESS,
+            params![chain, address, label, webhook, created_at],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether the address is watched
+    pub fn delete_watched_address(&self, chain: &str, address: &str) -> Result<bool, Error> {
+        Ok(self.execute(SQL_DELETE_WATCHED_ADDRESS, params![chain, address])? > 0)
+    }
+
+    pub fn query_watchlist(&self) -> Result<Vec<WatchedAddressRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_WATCHLIST)?;
+            let iter = stmt.query_map([], |row| {
+                Ok(WatchedAddressRecord {
+                    chain: row.get(0)?,
+                    address: row.get(1)?,
+                    label: row.get(2)?,
+                    webhook: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    pub fn query_watched_addresses(&self, chain: &str) -> Result<Vec<String>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_WATCHED_ADDRESSES)?;
+            let iter = stmt.query_map(params![chain], |row| row.get(0))?;
+            iter.collect()
+        })
+    }
+
+    /// Returns whether the event is new, the event of the same transaction and asset is
+    /// recorded once
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_watch_event(
+        &self,
+        chain: &str,
+        address: &str,
+        txid: &str,
+        asset: &str,
+        direction: &str,
+        net_change: &str,
+        timestamp: u64,
+    ) -> Result<bool, Error> {
+        Ok(self.execute(
+            SQL_INSERT_WATCH_EVENT,
+            params![chain, address, txid, asset, direction, net_change, timestamp],
+        )? > 0)
+    }
+
+    /// The latest events, of `address` only if it's given
+    pub fn query_watch_events(
+        &self,
+        address: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<WatchEventRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_WATCH_EVENTS)?;
+            let iter = stmt.query_map(params![address, limit], make_watch_event)?;
+            iter.collect()
+        })
+    }
+
+    /// The events to deliver (with the webhooks), the ones failed `max_attempts` times are
+    /// given up
+    pub fn query_undelivered_watch_events(
+        &self,
+        max_attempts: u32,
+        limit: u32,
+    ) -> Result<Vec<(WatchEventRecord, String)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNDELIVERED_WATCH_EVENTS)?;
+            let iter = stmt.query_map(params![max_attempts, limit], |row| {
+                Ok((make_watch_event(row)?, row.get(10)?))
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Count an attempt to deliver the event, it's delivered if `delivered_at` is given,
+    /// otherwise `error_code` is the code of the error it fails with
+    pub fn finish_watch_event_delivery(
+        &self,
+        id: u64,
+        delivered_at: Option<u64>,
+        error_code: Option<&str>,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_FINISH_WATCH_EVENT_DELIVERY,
+            params![delivered_at, error_code, id],
+        )?;
+        Ok(())
+    }
+
+    /// The values spent by transaction `txid` of each owner
+    pub fn query_spent_values_by_owner(
+        &self,
+        txid: &str,
+    ) -> Result<Vec<(String, DepcAmount)>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_SPENT_VALUES_BY_OWNER)?;
+            let iter = stmt.query_map(params![txid], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            iter.collect()
+        })
+    }
+
+    pub fn save_checkpoint(
+        &self,
+        height: u32,
+        hash: &str,
+        time: u64,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_INSERT_CHECKPOINT,
+            params![height, hash, time, created_at],
+        )?;
+        Ok(())
+    }
+
+    /// The checkpoint the index is bootstrapped from, `None` when it's synced from the genesis
+    pub fn query_checkpoint(&self) -> Result<Option<CheckpointRecord>, Error> {
+        self.read(|c| {
+            c.query_row(SQL_QUERY_CHECKPOINT, [], |row| {
+                Ok(CheckpointRecord {
+                    height: row.get(0)?,
+                    hash: row.get(1)?,
+                    time: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            })
+            .optional()
+        })
+    }
+
+    /// Returns whether the height is not recorded before, the first reason is kept
+    pub fn mark_height_unavailable(
+        &self,
+        height: u32,
+        reason: &str,
+        recorded_at: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_INSERT_UNAVAILABLE_HEIGHT,
+            params![height, reason, recorded_at],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn query_unavailable_heights(&self) -> Result<Vec<UnavailableHeightRecord>, Error> {
+        self.read(|c| {
+            let mut stmt = c.prepare(SQL_QUERY_UNAVAILABLE_HEIGHTS)?;
+            let iter = stmt.query_map([], |row| {
+                Ok(UnavailableHeightRecord {
+                    height: row.get(0)?,
+                    reason: row.get(1)?,
+                    recorded_at: row.get(2)?,
+                })
+            })?;
+            iter.collect()
+        })
+    }
+
+    /// Count a transaction relayed for `fee_payer` on `day`, false if `quota` transactions are
+    /// relayed for it on that day already
+    pub fn add_relay(&self, fee_payer: &str, day: u64, quota: u32) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_ADD_RELAY,
+            params![fee_payer, day, quota, self.bridge_id],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Claim `key` for a request, false if it's claimed by a request at `expired_before` or
+    /// later, the older claims are taken over
+    pub fn claim_idempotency_key(
+        &self,
+        key: &str,
+        request_hash: &str,
+        now: u64,
+        expired_before: u64,
+    ) -> Result<bool, Error> {
+        let n = self.execute(
+            SQL_CLAIM_IDEMPOTENCY_KEY,
+            params![key, request_hash, now, expired_before, self.bridge_id],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn query_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyKeyRecord>, Error> {
+        self.read(|c| {
+            c.query_row(
+                SQL_QUERY_IDEMPOTENCY_KEY,
+                params![self.bridge_id, key],
+                |row| {
+                    Ok(IdempotencyKeyRecord {
+                        key: row.get(0)?,
+                        request_hash: row.get(1)?,
+                        status: row.get(2)?,
+                        body: row.get(3)?,
+                        created_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+        })
+    }
+
+    /// Save the response of the request `key` is claimed by, it's replayed for the retries
+    pub fn save_idempotent_response(
+        &self,
+        key: &str,
+        status: u16,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        self.execute(
+            SQL_SAVE_IDEMPOTENT_RESPONSE,
+            params![status, body, self.bridge_id, key],
+        )?;
+        Ok(())
+    }
+
+    /// Release `key`, the request failed and may be retried
+    pub fn delete_idempotency_key(&self, key: &str) -> Result<(), Error> {
+        self.execute(SQL_DELETE_IDEMPOTENCY_KEY, params![self.bridge_id, key])?;
+        Ok(())
+    }
+}
+
+fn make_watch_event(row: &Row) -> Result<WatchEventRecord, Error> {
+    Ok(WatchEventRecord {
+        id: row.get(0)?,
+        chain: row.get(1)?,
+        address: row.get(2)?,
+        txid: row.get(3)?,
+        asset: row.get(4)?,
+        direction: row.get(5)?,
+        net_change: row.get(6)?,
+        timestamp: row.get(7)?,
+        delivered_at: row.get(8)?,
+        last_error_code: row.get(9)?,
+    })
+}
+
+fn make_solana_slot(row: &Row) -> Result<SolanaSlotRecord, Error> {
+    Ok(SolanaSlotRecord {
+        slot: row.get(0)?,
+        epoch: row.get(1)?,
+        block_time: row.get(2)?,
+    })
+}
+
+fn make_solana_cursor(row: &Row) -> Result<SolanaCursorRecord, Error> {
+    Ok(SolanaCursorRecord {
+        account: row.get(0)?,
+        signature: row.get(1)?,
+        slot: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+fn make_job(row: &Row) -> Result<JobRecord, Error> {
+    Ok(JobRecord {
+        name: row.get(0)?,
+        schedule: row.get(1)?,
+        enabled: row.get(2)?,
+        running: row.get(3)?,
+        last_started_at: row.get(4)?,
+        last_finished_at: row.get(5)?,
+        last_error: row.get(6)?,
+        next_run_at: row.get(7)?,
+    })
+}
+
+fn make_exchange_address(row: &Row) -> Result<ExchangeAddressRecord, Error> {
+    Ok(ExchangeAddressRecord {
+        address: row.get(0)?,
+        analyzed_txid: row.get(1)?,
+        first_seen: row.get(2)?,
+        rediscoveries: row.get(3)?,
+    })
+}
+
+fn make_parameter_change(row: &Row) -> Result<ParameterChangeRecord, Error> {
+    Ok(ParameterChangeRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        value: row.get(2)?,
+        created_at: row.get(3)?,
+        activates_at: row.get(4)?,
+        cancelled_at: row.get(5)?,
+    })
+}
+
+fn query_coins_archive_tables(c: &Connection) -> Result<Vec<String>, Error> {
+    let mut stmt = c.prepare(SQL_QUERY_COINS_ARCHIVE_TABLES)?;
+    let iter = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    iter.collect()
+}
+
+/// The archive tables are covered for the balances as table `coins`
+fn format_archive_owner_height_index(table: &str) -> String {
+    format!(
+        "create index if not exists index__{table}_owner_height on {table} (owner, height, \
+         spent_height, value)"
+    )
+}
+
+fn format_fill_coin_heights(table: &str) -> String {
+    format!(
+        "update {table} set height = (select height from transactions where txid = {table}.txid) \
+         where height is null"
+    )
+}
+
+/// Add column `bridge_id` to the bridge table of an existing database
+/// Make views `all_coins` and `all_coin_values` of table `coins` and all the archive tables
+fn create_all_coins_view(c: &Connection) -> Result<(), Error> {
+    let tables = query_coins_archive_tables(c)?;
+    let mut sql =
+        format!("create view all_coins as select rowid as coin_id, {COINS_COLUMNS} from coins");
+    let mut values_sql =
+        format!("create view all_coin_values as select {COIN_VALUES_COLUMNS} from coins");
+    for table in tables {
+        sql += &format!(" union all select coin_id, {COINS_COLUMNS} from {table}");
+        values_sql += &format!(" union all select {COIN_VALUES_COLUMNS} from {table}");
+    }
+    c.execute(SQL_DROP_VIEW_ALL_COINS, [])?;
+    c.execute(&sql, [])?;
+    c.execute(SQL_DROP_VIEW_ALL_COIN_VALUES, [])?;
+    c.execute(&values_sql, [])?;
+    Ok(())
+}
+
+/// The parameters are copied to be sent to the writer thread
+fn to_values(params: &[&dyn ToSql]) -> Result<Vec<Value>, Error> {
+    params
+        .iter()
+        .map(|param| match param.to_sql()? {
+            ToSqlOutput::Borrowed(value) => Ok(value.into()),
+            ToSqlOutput::Owned(value) => Ok(value),
+            _ => Err(Error::ToSqlConversionFailure(
+                "the parameter cannot be sent to the writer".into(),
+            )),
+        })
+        .collect()
+}
+
+/// The transaction open on the thread, see `Conn::transaction`. It's rolled back unless it's
+/// committed, then the writer thread resumes
+struct TransactionScope<'a> {
+    conn: &'a Conn,
+    _gate: MutexGuard<'a, ()>,
+    _resume: mpsc::Sender<()>,
+}
+
+impl Drop for TransactionScope<'_> {
+    fn drop(&mut self) {
+        let c = self
+            .conn
+            .pool
+            .write_conn
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if !c.is_autocommit() {
+            // the rows are discarded anyway when it fails, the connection is closed
+            let _ = c.execute_batch(SQL_ROLLBACK_TRANSACTION);
+        }
+        TRANSACTION_POOL.with(|pool| pool.set(0));
+    }
+}
+
+/// The error of the mutations sent after the writer thread panicked
+fn writer_stopped() -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_ABORT),
+        Some("the database writer is stopped".to_owned()),
+    )
+}
+
+/// The error of the queries interrupted since the deadline passes
+fn deadline_exceeded() -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_INTERRUPT),
+        Some("the deadline of the query is exceeded".to_owned()),
+    )
+}
+
+/// Run the queries of `f` on `c` until `deadline`, the one running then is interrupted
+fn read_until<T>(
+    c: &Connection,
+    deadline: Deadline,
+    f: impl Fn(&Connection) -> Result<T, Error>,
+) -> Result<T, Error> {
+    if deadline.is_expired() {
+        return Err(deadline_exceeded());
+    }
+    let at = deadline.instant();
+    c.progress_handler(
+        DEADLINE_CHECK_INSTRUCTIONS,
+        Some(move || std::time::Instant::now() >= at),
+    );
+    let result = f(c);
+    c.progress_handler(DEADLINE_CHECK_INSTRUCTIONS, None::<fn() -> bool>);
+    match result {
+        Err(Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::OperationInterrupted => {
+            Err(deadline_exceeded())
+        }
+        result => result,
+    }
+}
+
+fn add_bridge_id_column(c: &Connection, table: &str) -> Result<(), Error> {
+    if !c.query_row(SQL_QUERY_HAS_BRIDGE_ID_COLUMN, params![table], |row| {
+        row.get::<_, bool>(0)
+    })? {
+        c.execute(
+            &format!(
+                "alter table {table} add column bridge_id text not null default '{DEFAULT_BRIDGE_ID}'"
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add `column` to `table` made by an older build
+fn add_column(c: &Connection, table: &str, column: &str, definition: &str) -> Result<(), Error> {
+    if !c.query_row(SQL_QUERY_HAS_COLUMN, params![table, column], |row| {
+        row.get::<_, bool>(0)
+    })? {
+        c.execute(
+            &format!("alter table {table} add column {column} {definition}"),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn make_pending_action(row: &Row) -> Result<PendingActionRecord, Error> {
+    Ok(PendingActionRecord {
+        id: row.get(0)?,
+        action: row.get(1)?,
+        source_txid: row.get(2)?,
+        recipient: row.get(3)?,
+        amount: row.get(4)?,
+        timestamp: row.get(5)?,
+        txid: row.get(6)?,
+        custody_era: row.get(7)?,
+    })
+}
+
+fn make_refund(row: &Row) -> Result<RefundRecord, Error> {
+    Ok(RefundRecord {
+        id: row.get(0)?,
+        depc_txid: row.get(1)?,
+        vout: row.get(2)?,
+        refund_address: row.get(3)?,
+        amount: row.get(4)?,
+        reason: row.get(5)?,
+        timestamp: row.get(6)?,
+        txid: row.get(7)?,
+        refunded_at: row.get(8)?,
+        claim_address: row.get(9)?,
+        claim_signature: row.get(10)?,
+        claim_status: row.get(11)?,
+    })
+}
+
+fn make_sweep(row: &Row) -> Result<SweepRecord, Error> {
+    Ok(SweepRecord {
+        id: row.get(0)?,
+        from_address: row.get(1)?,
+        to_address: row.get(2)?,
+        amount: row.get(3)?,
+        txid: row.get(4)?,
+        raw_tx: row.get::<_, HexPayload>(5)?.0,
+        inputs: row.get(6)?,
+        timestamp: row.get(7)?,
+    })
+}
+
+fn make_held_pending_action(row: &Row) -> Result<HeldPendingActionRecord, Error> {
+    Ok(HeldPendingActionRecord {
+        id: row.get(0)?,
+        action: row.get(1)?,
+        source_txid: row.get(2)?,
+        recipient: row.get(3)?,
+        amount: row.get(4)?,
+        timestamp: row.get(5)?,
+        held_reason: row.get(6)?,
+        held_at: row.get(7)?,
+        released_at: row.get(8)?,
+    })
+}
+
+fn make_custody_inflow(row: &Row) -> Result<CustodyInflowRecord, Error> {
+    Ok(CustodyInflowRecord {
+        id: row.get(0)?,
+        txid: row.get(1)?,
+        vout: row.get(2)?,
+        address: row.get(3)?,
+        amount: row.get(4)?,
+        reason: row.get(5)?,
+        height: row.get(6)?,
+        status: row.get(7)?,
+        resolution: row.get(8)?,
+        found_at: row.get(9)?,
+        resolved_at: row.get(10)?,
+    })
+}
+
+fn make_deposit_intent(row: &Row) -> Result<DepositIntentRecord, Error> {
+    Ok(DepositIntentRecord {
+        id: row.get(0)?,
+        sender: row.get(1)?,
+        recipient: row.get(2)?,
+        created_at: row.get(3)?,
+        matched_txid: row.get(4)?,
+        matched_vout: row.get(5)?,
+        matched_at: row.get(6)?,
+    })
+}
+
+fn make_deposit(row: &Row) -> Result<DepositRecord, Error> {
+    Ok(DepositRecord {
+        depc_txid: row.get(0)?,
+        depc_timestamp: row.get(1)?,
+        to_address: row.get(2)?,
+        amount: row.get(3)?,
+        erc20_txid: row.get(4)?,
+        erc20_timestamp: row.get(5)?,
+        mint_receipt: match row.get::<_, Option<u64>>(7)? {
+            Some(slot) => Some(MintReceiptRecord {
+                fee: row.get(6)?,
+                slot,
+                created_ata: row.get::<_, Option<bool>>(8)?.unwrap_or_default(),
+                // the json array of the logs, see `confirm_deposits_batch`
+                raw_logs: row
+                    .get::<_, Option<String>>(9)?
+                    .and_then(|raw_logs| serde_json::from_str(&raw_logs).ok()),
+            }),
+            None => None,
+        },
+    })
+}
+
+fn make_deposit_address(row: &Row) -> Result<DepositAddressRecord, Error> {
+    Ok(DepositAddressRecord {
+        address: row.get(0)?,
+        derivation_index: row.get(1)?,
+        derivation_path: row.get(2)?,
+        recipient: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+fn make_withdraw_request(row: &Row) -> Result<WithdrawRequestRecord, Error> {
+    Ok(WithdrawRequestRecord {
+        id: row.get(0)?,
+        signature: row.get(1)?,
+        recipient: row.get(2)?,
+        signer: row.get(3)?,
+        origin: row.get(4)?,
+        status: row.get(5)?,
+        reason: row.get(6)?,
+        pending_action_id: row.get(7)?,
+        created_at: row.get(8)?,
+        processed_at: row.get(9)?,
+    })
+}
+
+fn make_custody_era(row: &Row) -> Result<CustodyEraRecord, Error> {
+    Ok(CustodyEraRecord {
+        era: row.get(0)?,
+        address: row.get(1)?,
+        started_at: row.get(2)?,
+        watched_until: row.get(3)?,
+    })
+}
+
+fn make_outgoing_transaction(row: &Row) -> Result<OutgoingTransactionRecord, Error> {
+    Ok(OutgoingTransactionRecord {
+        signature: row.get(0)?,
+        raw_tx: row.get(1)?,
+        last_valid_block_height: row.get(2)?,
+        recipients: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+        settled_at: row.get(6)?,
+    })
+}
+
+fn make_fee_sweep(row: &Row) -> Result<FeeSweepRecord, Error> {
+    Ok(FeeSweepRecord {
+        id: row.get(0)?,
+        treasury: row.get(1)?,
+        amount: row.get(2)?,
+        signature: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+fn make_depc_payout(row: &Row) -> Result<DepcPayoutRecord, Error> {
+    Ok(DepcPayoutRecord {
+        id: row.get(0)?,
+        action_id: row.get(1)?,
+        txid: row.get(2)?,
+        raw_tx: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+        sent_at: row.get(6)?,
+    })
+}
+
+fn make_payout_bump(row: &Row) -> Result<PayoutBumpRecord, Error> {
+    Ok(PayoutBumpRecord {
+        id: row.get(0)?,
+        action_id: row.get(1)?,
+        kind: row.get(2)?,
+        bumped_txid: row.get(3)?,
+        txid: row.get(4)?,
+        raw_tx: row.get(5)?,
+        fee: row.get(6)?,
+        status: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn make_multisig_payout(row: &Row) -> Result<MultisigPayoutRecord, Error> {
+    Ok(MultisigPayoutRecord {
+        id: row.get(0)?,
+        action_id: row.get(1)?,
+        raw_tx: row.get(2)?,
+        prevtxs: row.get(3)?,
+        status: row.get(4)?,
+        txid: row.get(5)?,
+        created_at: row.get(6)?,
+        sent_at: row.get(7)?,
+    })
+}
+
+fn make_catch_up_plan(row: &Row) -> Result<CatchUpPlanRecord, Error> {
+    Ok(CatchUpPlanRecord {
+        id: row.get(0)?,
+        from_height: row.get(1)?,
+        to_height: row.get(2)?,
+        status: row.get(3)?,
+        deposits: row.get(4)?,
+        deposit_amount: row.get(5)?,
+        withdrawals: row.get(6)?,
+        withdraw_amount: row.get(7)?,
+        started_at: row.get(8)?,
+        planned_at: row.get(9)?,
+        confirmed_at: row.get(10)?,
+    })
+}
+
+fn make_miner_stats(row: &Row) -> Result<MinerStatsRecord, Error> {
+    Ok(MinerStatsRecord {
+        miner: row.get(0)?,
+        blocks: row.get(1)?,
+        first_height: row.get(2)?,
+        last_height: row.get(3)?,
+        first_seen: row.get(4)?,
+        last_seen: row.get(5)?,
+    })
+}
+
+fn make_block_summary(row: &Row) -> Result<BlockSummaryRecord, Error> {
+    Ok(BlockSummaryRecord {
+        hash: row.get(0)?,
+        height: row.get(1)?,
+        miner: row.get(2)?,
+        time: row.get(3)?,
+        tx_count: row.get(4)?,
+        total_value: row.get(5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_or_create() {
+        assert!(Conn::open_or_create(&shellexpand::env("$HOME/hello.sqlite3").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_writer_and_readers() {
+        let path =
+            std::env::temp_dir().join(format!("depc-bridge-writer-{}.sqlite3", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Conn::open_or_create(path.to_str().unwrap()).unwrap();
+        conn.init().unwrap();
+        assert_eq!(conn.pool.readers.len(), READER_POOL_SIZE);
+
+        // the blocks added by the clones are written one by one and read by the readers
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let conn = conn.clone();
+                thread::spawn(move || {
+                    for height in i * 25..(i + 1) * 25 {
+                        conn.add_block(&format!("hash{height}"), height, "miner", 1000)
+                            .unwrap();
+                        assert!(conn
+                            .query_block_summary_by_height(height)
+                            .unwrap()
+                            .is_some());
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(conn.query_best_height(), Some(99));
+        assert!(conn.reader().execute("delete from blocks", []).is_err());
+
+        // the uncommitted blocks are seen only by the transaction, the block added by another
+        // thread meanwhile is written after it's rolled back
+        let mut writer = None;
+        let res = conn.transaction(|| {
+            conn.add_block("hash100", 100, "miner", 1000)?;
+            assert_eq!(conn.query_best_height(), Some(100));
+            let other = conn.clone();
+            assert_eq!(
+                thread::spawn(move || other.query_best_height())
+                    .join()
+                    .unwrap(),
+                Some(99)
+            );
+            let other = conn.clone();
+            writer = Some(thread::spawn(move || {
+                other.add_block("hash101", 101, "miner", 1000).unwrap()
+            }));
+            thread::sleep(std::time::Duration::from_millis(50));
+            Err::<(), Error>(Error::QueryReturnedNoRows)
+        });
+        assert!(matches!(res, Err(Error::QueryReturnedNoRows)));
+        writer.unwrap().join().unwrap();
+        assert_eq!(conn.query_best_height(), Some(101));
+        assert!(conn.query_block_hash_by_height(100).unwrap().is_none());
+        // a transaction opened by the thread already is joined
+        conn.transaction(|| {
+            conn.add_block("hash100", 100, "miner", 1000)?;
+            conn.transaction(|| conn.add_block("hash102", 102, "miner", 1000))
+        })
+        .unwrap();
+        assert_eq!(conn.query_best_height(), Some(102));
+
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_until_deadline() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        conn.add_block("hash1", 1, "miner", 1000).unwrap();
+        let deadline = Deadline::after(std::time::Duration::from_secs(60));
+        assert_eq!(deadline.sync_scope(|| conn.query_best_height()), Some(1));
+
+        // a scan running past the deadline is interrupted
+        let deadline = Deadline::after(std::time::Duration::from_millis(50));
+        let started = std::time::Instant::now();
+        let err = read_until(&conn.reader(), deadline, |c| {
+            c.query_row(
+                "with recursive n(i) as \
+                 (select 1 union all select i + 1 from n where i < 1000000000) \
+                 select count(*) from n",
+                [],
+                |row| row.get::<_, u64>(0),
+            )
+        })
+        .unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            err.sqlite_error_code(),
+            Some(rusqlite::ErrorCode::OperationInterrupted)
+        );
+        assert_eq!(err.to_string(), "the deadline of the query is exceeded");
+
+        // the queries aren't run after it, and the connection isn't interrupted without it
+        assert_eq!(deadline.sync_scope(|| conn.query_best_height()), None);
+        assert_eq!(conn.query_best_height(), Some(1));
+    }
+
+    #[test]
+    fn test_wal_and_busy_retry() {
+        let path =
+            std::env::temp_dir().join(format!("depc-bridge-busy-{}.sqlite3", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Conn::open_or_create(path.to_str().unwrap()).unwrap();
+        conn.init().unwrap();
+        let journal_mode: String = conn
+            .read(|c| c.query_row("pragma journal_mode", [], |row| row.get(0)))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        // another process holds the write lock longer than the busy timeout
+        let other = Connection::open(&path).unwrap();
+        other.execute_batch("begin immediate").unwrap();
+        let holder = thread::spawn(move || {
+            thread::sleep(BUSY_TIMEOUT + std::time::Duration::from_millis(500));
+            other.execute_batch("commit").unwrap();
+        });
+        // the queries don't wait for it, the write is retried until it's released
+        assert_eq!(conn.query_best_height(), None);
+        conn.add_block("hash0", 0, "miner", 1000).unwrap();
+        holder.join().unwrap();
+        assert_eq!(conn.query_best_height(), Some(0));
+        let stats = conn.busy_retry_stats();
+        assert!(stats.retries > 0);
+        assert_eq!(stats.exhausted, 0);
+
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_in_memory_init() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+    }
+
+    #[test]
+    fn test_add_block() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_block("hash value", 1000, "address", 1938483848)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_add_transaction() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_transaction("hash value", "txid").unwrap();
+        conn.add_block("hash1", 1, "miner", 1000).unwrap();
+        conn.add_transaction("hash1", "txid1").unwrap();
+        let heights = conn
+            .read(|c| {
+                let mut stmt = c.prepare("select height from transactions order by rowid")?;
+                let iter = stmt.query_map([], |row| row.get::<_, Option<u32>>(0))?;
+                iter.collect::<Result<Vec<_>, _>>()
+            })
+            .unwrap();
+        // the block of the first transaction is unknown
+        assert_eq!(heights, vec![None, Some(1)]);
+    }
+
+    #[test]
+    fn test_block_applied_again() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let value = DepcAmount::from_value64(5000);
+        for _ in 0..2 {
+            conn.add_block("hash1", 1, "miner", 1000).unwrap();
+            conn.add_transaction("hash1", "txid1").unwrap();
+            conn.add_coin("txid1", 0, value, "owner", "").unwrap();
+        }
+        let count = |table: &str| {
+            conn.query_table_row_counts()
+                .unwrap()
+                .into_iter()
+                .find(|(name, _)| name == table)
+                .map(|(_, n)| n)
+        };
+        assert_eq!(count("blocks"), Some(1));
+        assert_eq!(count("transactions"), Some(1));
+        assert_eq!(count("coins"), Some(1));
+        assert_eq!(conn.query_address_balance("owner").unwrap(), value);
+
+        // the spent coin is not made unspent again, and the transaction follows the block
+        conn.add_block("hash2", 2, "miner", 1100).unwrap();
+        conn.add_transaction("hash2", "txid2").unwrap();
+        conn.mark_coin_to_spent("txid1", 0, "txid2", 2).unwrap();
+        conn.add_coin("txid1", 0, value, "owner", "").unwrap();
+        assert_eq!(
+            conn.query_address_balance("owner").unwrap(),
+            DepcAmount::default()
+        );
+        conn.add_block("hash3", 3, "miner", 1200).unwrap();
+        conn.add_transaction("hash3", "txid2").unwrap();
+        assert_eq!(conn.query_transaction_height("txid2").unwrap(), Some(3));
+        assert_eq!(count("transactions"), Some(2));
+    }
+
+    #[test]
+    fn test_rollback_blocks_above() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let amount = |value64| DepcAmount::from_value64(value64);
+        // txid2 spends txid1:0 and txid3 spends txid2:0
+        for (height, txid, spent, owner, value64) in [
+            (1, "txid1", None, "a", 5000),
+            (2, "txid2", Some("txid1"), "b", 4000),
+            (3, "txid3", Some("txid2"), "c", 3000),
+        ] {
+            let hash = format!("hash{height}");
+            conn.add_block(&hash, height, "miner", 1000 + height as u64)
+                .unwrap();
+            conn.add_transaction(&hash, txid).unwrap();
+            if let Some(spent) = spent {
+                conn.mark_coin_to_spent(spent, 0, txid, height).unwrap();
+            }
+            conn.add_coin(txid, 0, amount(value64), owner, "").unwrap();
+        }
+        for txid in ["txid2", "txid3"] {
+            conn.save_deposit(txid, "recipient", amount(100), 1000)
+                .unwrap();
+            conn.add_ledger_entry(
+                "custody_in",
+                "custody",
+                "deposits_due",
+                amount(100),
+                txid,
+                1000,
+            )
+            .unwrap();
+            conn.add_ledger_entry(
+                "deposit_fee",
+                "deposits_due",
+                "bridge_fees",
+                amount(10),
+                txid,
+                1000,
+            )
+            .unwrap();
+            conn.add_event("custody_in", amount(100), txid, 1000)
+                .unwrap();
+        }
+        let removed = conn
+            .add_pending_action("deposit", "txid2", "recipient", amount(100), 1000)
+            .unwrap();
+        assert!(conn
+            .add_recorded_transaction("txid2", 0, "deposit", removed, 1000)
+            .unwrap());
+        // recorded once
+        assert!(!conn
+            .add_recorded_transaction("txid2", 0, "deposit", removed, 1001)
+            .unwrap());
+        let picked = conn
+            .add_pending_action("deposit", "txid3", "recipient", amount(100), 1000)
+            .unwrap();
+        conn.claim_pending_action(picked, 1100).unwrap().unwrap();
+        conn.add_recorded_transaction("txid3", 0, "deposit", picked, 1000)
+            .unwrap();
+        for (txid, id) in [("txid2", removed), ("txid3", picked)] {
+            conn.add_transfer_fee("deposit", txid, id, amount(100), amount(10), 1000)
+                .unwrap();
+        }
+
+        let record = conn.rollback_blocks_above(1).unwrap();
+        assert_eq!(
+            (record.blocks, record.transactions, record.coins),
+            (2, 2, 2)
+        );
+        assert_eq!(
+            record.removed_deposits,
+            vec![("default".to_owned(), "txid2".to_owned())]
+        );
+        assert_eq!(
+            record.kept_deposits,
+            vec![("default".to_owned(), "txid3".to_owned())]
+        );
+        assert_eq!(conn.query_best_height(), Some(1));
+        assert_eq!(conn.query_block_hash_by_height(2).unwrap(), None);
+        assert_eq!(conn.query_transaction_height("txid2").unwrap(), None);
+        // the coin spent by the removed blocks is unspent again
+        assert_eq!(conn.query_address_balance("a").unwrap(), amount(5000));
+        assert_eq!(conn.query_address_balance("b").unwrap(), amount(0));
+        assert_eq!(conn.query_address_balance("c").unwrap(), amount(0));
+        assert_eq!(conn.query_balance("a", 3).unwrap(), amount(5000));
+        assert_eq!(conn.query_num_spent_coins_without_txid().unwrap(), 0);
+        // the picked deposit is kept with its ledger entry
+        assert!(conn.query_deposit("txid2").unwrap().is_none());
+        assert!(conn.query_deposit("txid3").unwrap().is_some());
+        // the removed deposit is processed again when it's found in the new blocks
+        assert!(conn
+            .query_recorded_transaction("txid2", 0)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            conn.query_recorded_transaction("txid3", 0)
+                .unwrap()
+                .map(|recorded| recorded.action_id),
+            Some(picked)
+        );
+        let references: Vec<String> = conn
+            .query_ledger_entries(10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.reference)
+            .collect();
+        assert_eq!(references, vec!["txid3".to_owned(), "txid3".to_owned()]);
+        assert_eq!(conn.query_events(0, 10).unwrap().len(), 1);
+        let fees = conn.query_transfer_fees(10).unwrap();
+        assert_eq!(fees.len(), 1);
+        assert_eq!((fees[0].action_id, fees[0].fee), (picked, amount(10)));
+
+        // the blocks of the new chain are synced from the height
+        conn.add_block("hash2b", 2, "miner", 1102).unwrap();
+        conn.add_transaction("hash2b", "txid2").unwrap();
+        conn.mark_coin_to_spent("txid1", 0, "txid2", 2).unwrap();
+        conn.add_coin("txid2", 0, amount(4000), "b", "").unwrap();
+        assert_eq!(conn.query_address_balance("a").unwrap(), amount(0));
+        assert_eq!(conn.query_address_balance("b").unwrap(), amount(4000));
+        conn.save_deposit("txid2", "recipient", amount(100), 1000)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_custody_inflows() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let amount = DepcAmount::from_value64(5000);
+        for (height, txid) in [(1, "txid1"), (2, "txid2")] {
+            conn.add_block(&format!("hash{height}"), height, "miner", 1000)
+                .unwrap();
+            assert!(conn
+                .add_custody_inflow(txid, 0, "custody", amount, "no_recipient", height, 1000)
+                .unwrap());
+        }
+        // found again when the block is synced again
+        assert!(!conn
+            .add_custody_inflow("txid1", 0, "custody", amount, "no_recipient", 1, 1001)
+            .unwrap());
+        let inflows = conn.query_unclassified_custody_inflows(10).unwrap();
+        assert_eq!(inflows.len(), 2);
+        assert_eq!(inflows[0].txid, "txid2");
+        assert_eq!(inflows[1].found_at, 1000);
+
+        let id = inflows[1].id;
+        assert!(conn
+            .resolve_custody_inflow(id, "returned to the sender", 1100)
+            .unwrap());
+        // resolved once
+        assert!(!conn.resolve_custody_inflow(id, "again", 1200).unwrap());
+        assert!(!conn.resolve_custody_inflow(100, "unknown", 1200).unwrap());
+        let resolved = conn.query_custody_inflow(id).unwrap().unwrap();
+        assert_eq!(resolved.status, "resolved");
+        assert_eq!(
+            resolved.resolution.as_deref(),
+            Some("returned to the sender")
+        );
+        assert_eq!(resolved.resolved_at, Some(1100));
+        // other bridges don't see it
+        assert!(conn
+            .scoped("other")
+            .query_custody_inflow(id)
+            .unwrap()
+            .is_none());
+
+        // the inflow of the orphaned block is removed
+        conn.rollback_blocks_above(1).unwrap();
+        assert!(conn
+            .query_unclassified_custody_inflows(10)
+            .unwrap()
+            .is_empty());
+        assert!(conn.query_custody_inflow(id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_block_digests() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for height in 1..=3 {
+            let hash = format!("hash{height}");
+            conn.add_block(&hash, height, "miner", 1000).unwrap();
+            conn.add_block_digest(height, &hash, 0, &format!("digest{height}"), 1000)
+                .unwrap();
+        }
+        // synced again
+        conn.add_block_digest(2, "hash2", 1, "digest2b", 1100)
+            .unwrap();
+        let digests = conn.query_block_digests(2, 10).unwrap();
+        assert_eq!(digests.len(), 2);
+        assert_eq!(
+            digests[0],
+            BlockDigestRecord {
+                height: 2,
+                block_hash: "hash2".to_owned(),
+                event_count: 1,
+                digest: "digest2b".to_owned(),
+                created_at: 1100,
+            }
+        );
+        assert_eq!(digests[1].height, 3);
+        assert!(conn
+            .scoped("other")
+            .query_block_digests(0, 10)
+            .unwrap()
+            .is_empty());
+
+        // the digests of the orphaned blocks are removed
+        conn.rollback_blocks_above(1).unwrap();
+        let digests = conn.query_block_digests(0, 10).unwrap();
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].digest, "digest1");
+    }
+
+    #[test]
+    fn test_init_fills_heights_of_existing_rows() {
+        let conn = Conn::open_in_mem().unwrap();
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            c.execute_batch(
+                "create table blocks (hash, height, miner, time);
+                 create table transactions (block_hash, txid);
+                 create table coins (txid, n, value, owner, script_hex, is_spent, spent_height, spent_txid);
+                 insert into blocks values ('hash1', 1, 'miner', 1000), ('hash2', 2, 'miner', 1200);
+                 insert into transactions values ('hash1', 'txid1'), ('hash2', 'txid2');
+                 insert into coins (txid, n, value, owner, is_spent) values ('txid1', 0, 1000, 'a', false), ('txid2', 0, 500, 'a', false);",
+            )
+            .unwrap();
+        }
+        conn.init().unwrap();
+        assert_eq!(conn.query_schema_version().unwrap(), SCHEMA_VERSION);
+        let heights = conn
+            .read(|c| {
+                c.query_row(
+                    "select (select group_concat(height) from transactions), (select group_concat(height) from coins)",
+                    [],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+            })
+            .unwrap();
+        assert_eq!(heights, ("1,2".to_owned(), "1,2".to_owned()));
+        assert_eq!(
+            conn.query_balance("a", 1).unwrap(),
+            DepcAmount::from_value64(1000)
+        );
+        let summary = conn.query_block_summary_by_height(2).unwrap().unwrap();
+        assert_eq!((summary.tx_count, summary.total_value.value64()), (1, 500));
+    }
+
+    #[test]
+    fn test_add_coin_and_mark() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_coin(
+            "txid",
+            0,
+            DepcAmount::from_value64(1000),
+            "helloaddress",
+            "39204848b93948",
+        )
+        .unwrap();
+        conn.mark_coin_to_spent("txid", 0, "spent_txid", 10203)
+            .unwrap();
+        assert_eq!(
+            conn.query_coin_script("txid", 0).unwrap().as_deref(),
+            Some("39204848b93948")
+        );
+        assert_eq!(conn.query_coin_script("txid", 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_compress_payloads() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        // the rows written as hex text before
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            for n in 0..3 {
+                c.execute(
+                    "insert into coins (txid, n, value, owner, script_hex, is_spent) values ('txid', ?, 1000, 'a', '76a914', 0)",
+                    params![n],
+                )
+                .unwrap();
+            }
+            c.execute("insert into depc_sweep (from_address, to_address, amount, raw_tx, timestamp) values ('hot', 'cold', 1000, '0200beef', 1000)", []).unwrap();
+        }
+        conn.add_coin("txid", 3, DepcAmount::from_value64(1000), "a", "76a914")
+            .unwrap();
+
+        assert_eq!(
+            conn.compress_payloads(PayloadColumn::CoinScripts, 0, 2)
+                .unwrap(),
+            Some((2, 2))
+        );
+        assert_eq!(
+            conn.compress_payloads(PayloadColumn::CoinScripts, 2, 2)
+                .unwrap(),
+            Some((3, 1))
+        );
+        assert_eq!(
+            conn.compress_payloads(PayloadColumn::CoinScripts, 3, 2)
+                .unwrap(),
             None
+        );
+        assert_eq!(
+            conn.compress_payloads(PayloadColumn::SweepRawTxs, 0, 2)
+                .unwrap(),
+            Some((1, 1))
+        );
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            let texts: u32 = c
+                .query_row(
+                    "select count(*) from coins where typeof(script_hex) = 'text'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(texts, 0);
+        }
+        for n in 0..4 {
+            assert_eq!(
+                conn.query_coin_script("txid", n).unwrap().as_deref(),
+                Some("76a914")
+            );
         }
+        assert_eq!(conn.query_sweeps(10).unwrap()[0].raw_tx, "0200beef");
     }
 
-    pub fn query_block_time_by_height(&self, height: u32) -> u64 {
-        let c = self.conn.lock().unwrap();
-        c.query_row(SQL_QUERY_BLOCK_TIME_BY_HEIGHT, params![height], |row| {
-            row.get(0)
-        })
-        .unwrap()
+    #[test]
+    fn test_make_deposit() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.save_deposit(
+            "depc_txid",
+            "to_erc20_address",
+            DepcAmount::from_value64(10000000),
+            394838121,
+        )
+        .unwrap();
+
+        conn.confirm_deposit("erc20_txid", 193847845, "depc_txid")
+            .unwrap();
+
+        let deposit = conn.query_deposit("depc_txid").unwrap().unwrap();
+        assert_eq!(deposit.amount, DepcAmount::from_value64(10000000));
+        assert_eq!(deposit.erc20_txid.as_deref(), Some("erc20_txid"));
+        assert!(conn.query_deposit("unknown_txid").unwrap().is_none());
     }
 
-    pub fn query_balance(&self, address: &str, height: u32) -> Result<u64, Error> {
-        let c = self.conn.lock().unwrap();
-        Ok(c.query_row(
-            SQL_QUERY_BALANCE_OF_ADDRESS,
-            params![address, height, height],
-            |row| row.get(0),
-        )?)
+    #[test]
+    fn test_confirm_deposits_batch() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for depc_txid in ["a", "b", "c"] {
+            conn.save_deposit(depc_txid, "recipient", DepcAmount::from_value64(1), 100)
+                .unwrap();
+        }
+        let receipt = MintReceiptRecord {
+            fee: Some(5000),
+            slot: 42,
+            created_ata: true,
+            raw_logs: Some(vec![
+                "Program log: Instruction: Transfer".to_owned(),
+                "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success".to_owned(),
+            ]),
+        };
+        conn.confirm_deposits_batch(&["a", "b"], "sig", 200, Some(receipt.clone()))
+            .unwrap();
+        for depc_txid in ["a", "b"] {
+            let deposit = conn.query_deposit(depc_txid).unwrap().unwrap();
+            assert_eq!(deposit.erc20_txid.as_deref(), Some("sig"));
+            assert_eq!(deposit.erc20_timestamp, Some(200));
+            assert_eq!(deposit.mint_receipt.as_ref(), Some(&receipt));
+        }
+        let deposit = conn.query_deposit("c").unwrap().unwrap();
+        assert_eq!((deposit.erc20_txid, deposit.mint_receipt), (None, None));
+        conn.confirm_deposits_batch(&[], "sig2", 300, None).unwrap();
+        assert_eq!(conn.query_confirmed_deposits(301).unwrap().len(), 2);
     }
 
-    pub fn query_inputs(&self, txid: &str) -> Result<Vec<String>, Error> {
-        let c = self.conn.lock().unwrap();
-        let mut stmt = c.prepare(SQL_QUERY_ADDRESSES_FROM_TX_INPUTS)?;
-        let iter = stmt.query_map(params![txid], |row| {
-            let address: String = row.get(0)?;
-            Ok(address)
-        })?;
-        iter.collect()
+    #[test]
+    fn test_deposit_roots() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        for (depc_txid, confirmed_at) in [("b", Some(200)), ("a", Some(200)), ("c", None)] {
+            conn.save_deposit(depc_txid, "recipient", DepcAmount::from_value64(1), 100)
+                .unwrap();
+            if let Some(confirmed_at) = confirmed_at {
+                conn.confirm_deposit("sig", confirmed_at, depc_txid)
+                    .unwrap();
+            }
+        }
+        conn.save_deposit("d", "recipient", DepcAmount::from_value64(1), 100)
+            .unwrap();
+        conn.confirm_deposit("sig", 300, "d").unwrap();
+        let txids = |until| {
+            conn.query_confirmed_deposits(until)
+                .unwrap()
+                .into_iter()
+                .map(|deposit| deposit.depc_txid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(txids(300), vec!["a", "b"]);
+        assert_eq!(txids(301), vec!["a", "b", "d"]);
+
+        assert!(conn.query_latest_deposit_root().unwrap().is_none());
+        conn.add_deposit_root("root1", 2, 300, 300).unwrap();
+        let id = conn.add_deposit_root("root2", 3, 301, 301).unwrap();
+        conn.set_deposit_root_memo_signature(id, "memo").unwrap();
+        let root = conn.query_latest_deposit_root().unwrap().unwrap();
+        assert_eq!(root.root, "root2");
+        assert_eq!(root.leaf_count, 3);
+        assert_eq!(root.covered_until, 301);
+        assert_eq!(root.memo_signature.as_deref(), Some("memo"));
+        assert!(conn
+            .scoped("other")
+            .query_latest_deposit_root()
+            .unwrap()
+            .is_none());
     }
 
-    pub fn query_txids_those_inputs_contain_address(
-        &self,
-        address: &str,
-    ) -> Result<Vec<String>, Error> {
-        let c = self.conn.lock().unwrap();
-        let mut stmt = c.prepare(SQL_QUERY_TXIDS_THOSE_INPUTS_CONTAIN_ADDRESS)?;
-        let iter = stmt.query_map(params![address], |row| Ok(row.get(0).unwrap()))?;
-        iter.collect()
+    #[test]
+    fn test_make_withdraw() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.make_withdraw(
+            "erc20_txid",
+            193847845,
+            "from_address",
+            TokenAmount::new(1000000),
+        )
+        .unwrap();
+        conn.confirm_withdraw("depc_txid", 193848478, "erc20_txid", "depc_address")
+            .unwrap();
     }
 
-    pub fn add_analyzed_exchange_address_from_tx(
-        &self,
-        address: &str,
-        txid: &str,
-    ) -> Result<(), Error> {
-        let c = self.conn.lock().unwrap();
-        c.execute(SQL_INSERT_EXCHANGE_ADDRESSE, params![address, txid])?;
-        Ok(())
+    #[test]
+    fn test_add_and_query_sweeps() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_sweep(
+            "hot_address",
+            "cold_address",
+            DepcAmount::from_value64(1000),
+            None,
+            "0200beef",
+            r#"[["coin_txid",0]]"#,
+            193847845,
+        )
+        .unwrap();
+        conn.add_sweep(
+            "hot_address",
+            "cold_address",
+            DepcAmount::from_value64(2000),
+            Some("sweep_txid"),
+            "0200cafe",
+            "[]",
+            193847900,
+        )
+        .unwrap();
+
+        let sweeps = conn.query_sweeps(10).unwrap();
+        assert_eq!(sweeps.len(), 2);
+        assert_eq!(sweeps[0].amount, DepcAmount::from_value64(2000));
+        assert_eq!(sweeps[0].txid.as_deref(), Some("sweep_txid"));
+        assert_eq!(sweeps[1].txid, None);
+        assert_eq!(sweeps[1].inputs.as_deref(), Some(r#"[["coin_txid",0]]"#));
+
+        // the unsigned sweep is settled once by the transaction the operator broadcasts
+        let unsigned = conn.query_unsigned_sweeps().unwrap();
+        assert_eq!(unsigned.len(), 1);
+        assert_eq!(unsigned[0].id, sweeps[1].id);
+        assert!(conn
+            .settle_unsigned_sweep(unsigned[0].id, "signed_txid")
+            .unwrap());
+        assert!(!conn
+            .settle_unsigned_sweep(unsigned[0].id, "other_txid")
+            .unwrap());
+        assert!(conn.query_unsigned_sweeps().unwrap().is_empty());
+
+        conn.add_coin(
+            "coin_txid",
+            0,
+            DepcAmount::from_value64(3000),
+            "hot_address",
+            "a914",
+        )
+        .unwrap();
+        assert_eq!(conn.query_coin_spent_txid("coin_txid", 0).unwrap(), None);
+        conn.mark_coin_to_spent("coin_txid", 0, "signed_txid", 100)
+            .unwrap();
+        assert_eq!(
+            conn.query_coin_spent_txid("coin_txid", 0).unwrap(),
+            Some("signed_txid".to_owned())
+        );
     }
 
-    pub fn query_analyzed_exchange_addresses(&self) -> Result<Vec<String>, Error> {
-        let c = self.conn.lock().unwrap();
-        let mut stmt = c.prepare(SQL_QUERY_EXCHANGE_ADDRESSES)?;
-        let iter = stmt.query_map([], |row| {
-            let address: String = row.get(0)?;
-            Ok(address)
-        })?;
-        iter.collect()
+    #[test]
+    fn test_add_and_query_shadow_actions() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_shadow_action(
+            "mint",
+            "depc_txid",
+            "solana_address",
+            DepcAmount::from_value64(1000),
+            193847845,
+        )
+        .unwrap();
+        conn.add_shadow_action(
+            "payout",
+            "withdraw_txid",
+            "depc_address",
+            DepcAmount::from_value64(2000),
+            193847900,
+        )
+        .unwrap();
+
+        let actions = conn.query_shadow_actions(1).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action, "payout");
+        assert_eq!(actions[0].amount, DepcAmount::from_value64(2000));
+        assert_eq!(conn.query_shadow_actions(10).unwrap().len(), 2);
     }
 
-    pub fn query_num_exchange_addresses(&self) -> Result<u64, Error> {
-        let c = self.conn.lock().unwrap();
-        Ok(c.query_row(SQL_QUERY_NUM_EXCHANGE_ADDRESSES, [], |row| row.get(0))?)
+    #[test]
+    fn test_add_and_query_dead_letters() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_dead_letter(10, "txid1", None, "invalid response", 193847845)
+            .unwrap();
+        conn.add_dead_letter(12, "txid2", Some(1), "invalid address", 193847900)
+            .unwrap();
+
+        let letters = conn.query_dead_letters(10).unwrap();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].txid, "txid2");
+        assert_eq!(letters[0].vout, Some(1));
+        assert_eq!(letters[1].height, 10);
+        assert_eq!(letters[1].vout, None);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_claim_and_complete_pending_actions() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        let id1 = conn
+            .add_pending_action(
+                "deposit",
+                "depc_txid1",
+                "solana_address",
+                DepcAmount::from_value64(1000),
+                193847845,
+            )
+            .unwrap();
+        let id2 = conn
+            .add_pending_action(
+                "deposit",
+                "depc_txid2",
+                "solana_address",
+                DepcAmount::from_value64(2000),
+                193847845,
+            )
+            .unwrap();
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids("deposit").unwrap(),
+            vec![id1, id2]
+        );
+        assert!(conn
+            .query_unclaimed_pending_action_ids("withdraw")
+            .unwrap()
+            .is_empty());
+
+        let action = conn.claim_pending_action(id1, 193847900).unwrap().unwrap();
+        assert_eq!(action.source_txid, "depc_txid1");
+        assert_eq!(action.amount, DepcAmount::from_value64(1000));
+        // the action cannot be claimed twice
+        assert!(conn.claim_pending_action(id1, 193847900).unwrap().is_none());
+        conn.complete_pending_action(id1, Some("erc20_txid"), 193847950)
+            .unwrap();
+
+        conn.claim_pending_action(id2, 193847900).unwrap().unwrap();
+        conn.release_pending_action(id2).unwrap();
+        conn.claim_pending_action(id2, 193847900).unwrap().unwrap();
+        // the claimed actions are left by a crashed run
+        assert_eq!(conn.release_claimed_pending_actions().unwrap(), 1);
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids("deposit").unwrap(),
+            vec![id2]
+        );
+
+        let actions = conn
+            .query_pending_actions_between(193847800, 193847845)
+            .unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].txid.as_deref(), Some("erc20_txid"));
+        assert!(conn
+            .query_pending_actions_between(193847846, 193847900)
+            .unwrap()
+            .is_empty());
+    }
 
     #[test]
-    fn test_open_or_create() {
-        assert!(Conn::open_or_create(&shellexpand::env("$HOME/hello.sqlite3").unwrap()).is_ok());
+    fn test_depc_payouts() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let mut ids = vec![];
+        for txid in ["depc_txid1", "depc_txid2"] {
+            let id = conn
+                .add_pending_action(
+                    "withdraw",
+                    txid,
+                    "depc_address",
+                    DepcAmount::from_value64(1000),
+                    193847845,
+                )
+                .unwrap();
+            conn.claim_pending_action(id, 193847900).unwrap().unwrap();
+            ids.push(id);
+        }
+        let payout_id = conn
+            .add_depc_payout(ids[0], "payout_txid", "raw_tx", 193847900)
+            .unwrap();
+        let payouts = conn.query_signed_depc_payouts().unwrap();
+        assert_eq!(payouts.len(), 1);
+        assert_eq!(payouts[0].action_id, ids[0]);
+        assert_eq!(payouts[0].txid, "payout_txid");
+        assert_eq!(payouts[0].raw_tx, "raw_tx");
+        // the withdrawal of the signed payout is in doubt, it's left claimed
+        assert_eq!(conn.release_claimed_pending_actions().unwrap(), 1);
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids("withdraw").unwrap(),
+            vec![ids[1]]
+        );
+
+        assert!(conn.complete_depc_payout(payout_id, 193847950).unwrap());
+        assert!(!conn.complete_depc_payout(payout_id, 193847950).unwrap());
+        assert!(conn.query_signed_depc_payouts().unwrap().is_empty());
+        assert_eq!(conn.release_claimed_pending_actions().unwrap(), 1);
     }
 
     #[test]
-    fn test_open_in_memory_init() {
+    fn test_confirm_pending_actions() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
+
+        let mut ids = vec![];
+        for (action, timestamp) in [("deposit", 1000), ("withdraw", 1100), ("withdraw", 1200)] {
+            let id = conn
+                .add_pending_action(
+                    action,
+                    "source_txid",
+                    "recipient",
+                    DepcAmount::from_value64(1000),
+                    timestamp,
+                )
+                .unwrap();
+            ids.push(id);
+        }
+        conn.complete_pending_action(ids[0], Some("signature"), 1030)
+            .unwrap();
+        conn.confirm_pending_action(ids[0], 1030).unwrap();
+        conn.complete_pending_action(ids[1], Some("payout_txid"), 1110)
+            .unwrap();
+        // the action without a transaction is never confirmed
+        conn.confirm_pending_action(ids[2], 1300).unwrap();
+
+        // the payout is confirmed by the block it's synced in
+        conn.add_block("block_1", 1, "miner", 1400).unwrap();
+        conn.add_transaction("block_1", "payout_txid").unwrap();
+        assert_eq!(
+            conn.confirm_pending_actions_in_block("block_1", 1400)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            conn.confirm_pending_actions_in_block("block_1", 1500)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            conn.query_confirmation_latencies("deposit", 0).unwrap(),
+            vec![30]
+        );
+        assert_eq!(
+            conn.query_confirmation_latencies("withdraw", 0).unwrap(),
+            vec![300]
+        );
+        assert!(conn
+            .query_confirmation_latencies("withdraw", 1101)
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_add_block() {
+    fn test_add_and_complete_refunds() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
 
-        conn.add_block("hash value", 1000, "address", 1938483848)
+        for (txid, timestamp) in [("depc_txid1", 1000), ("depc_txid2", 2000)] {
+            conn.add_refund(
+                txid,
+                0,
+                "depc_sender",
+                DepcAmount::from_value64(5000000),
+                "invalid address: bad",
+                timestamp,
+            )
+            .unwrap();
+        }
+        // the output is refunded once
+        assert!(conn
+            .add_refund(
+                "depc_txid1",
+                0,
+                "depc_sender",
+                DepcAmount::from_value64(5000000),
+                "",
+                3000
+            )
+            .is_err());
+        let due = conn.query_due_refunds(1500).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].depc_txid, "depc_txid1");
+        assert_eq!(due[0].refund_address, "depc_sender");
+        conn.complete_refund(due[0].id, Some("refund_txid"), 1600)
             .unwrap();
+        assert!(conn.query_due_refunds(1500).unwrap().is_empty());
+
+        let refunds = conn.query_refunds(10).unwrap();
+        assert_eq!(refunds.len(), 2);
+        assert_eq!(refunds[0].txid, None);
+        assert_eq!(refunds[1].txid.as_deref(), Some("refund_txid"));
+        assert_eq!(refunds[1].refunded_at, Some(1600));
     }
 
     #[test]
-    fn test_add_transaction() {
+    fn test_claim_refunds() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
+        conn.add_refund(
+            "depc_txid1",
+            0,
+            "depc_sender",
+            DepcAmount::from_value64(5000000),
+            "invalid address: bad",
+            1000,
+        )
+        .unwrap();
+        let id = conn.query_refunds(10).unwrap()[0].id;
 
-        conn.add_transaction("hash value", "txid").unwrap();
+        assert!(conn.claim_refund(id, "depc_other", "sig1").unwrap());
+        // the submitted claim is kept until it's verified or rejected
+        assert!(!conn.claim_refund(id, "depc_another", "sig2").unwrap());
+        let claims = conn.query_submitted_refund_claims().unwrap();
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].claim_address.as_deref(), Some("depc_other"));
+        assert_eq!(claims[0].claim_signature.as_deref(), Some("sig1"));
+
+        // the rejected claim is replaced
+        conn.set_refund_claim_status(id, "rejected").unwrap();
+        assert!(conn.query_submitted_refund_claims().unwrap().is_empty());
+        assert!(conn.claim_refund(id, "depc_another", "sig2").unwrap());
+        conn.set_refund_claim_status(id, "verified").unwrap();
+        assert!(!conn.claim_refund(id, "depc_other", "sig3").unwrap());
+        let refund = conn
+            .query_refund_of_output("depc_txid1", 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(refund.claim_address.as_deref(), Some("depc_another"));
+        assert_eq!(refund.claim_status.as_deref(), Some("verified"));
+
+        // the refund made is not claimed
+        conn.add_refund(
+            "depc_txid2",
+            0,
+            "depc_sender",
+            DepcAmount::from_value64(5000000),
+            "invalid address: bad",
+            1000,
+        )
+        .unwrap();
+        let id = conn.query_refunds(10).unwrap()[0].id;
+        conn.complete_refund(id, Some("refund_txid"), 1600).unwrap();
+        assert!(!conn.claim_refund(id, "depc_other", "sig1").unwrap());
     }
 
     #[test]
-    fn test_add_coin_and_mark() {
+    fn test_match_deposit_intents() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
 
-        conn.add_coin("txid", 0, 1000, "helloaddress", "39204848b93948")
+        let id1 = conn
+            .add_deposit_intent("depc_sender", "solana_address1", 1000)
             .unwrap();
-        conn.mark_coin_to_spent("txid", 0, "spent_txid", 10203)
+        let id2 = conn
+            .add_deposit_intent("depc_sender", "solana_address2", 1100)
+            .unwrap();
+        assert!(conn
+            .query_open_deposit_intent("another_sender", 0, 2000)
+            .unwrap()
+            .is_none());
+        // the oldest one in the window is matched first
+        let intent = conn
+            .query_open_deposit_intent("depc_sender", 0, 2000)
+            .unwrap()
             .unwrap();
+        assert_eq!(intent.id, id1);
+        assert!(conn.match_deposit_intent(id1, "txid", 1, 2000).unwrap());
+        assert!(!conn.match_deposit_intent(id1, "txid2", 0, 2000).unwrap());
+        let intent = conn.query_deposit_intent(id1).unwrap().unwrap();
+        assert_eq!(intent.recipient, "solana_address1");
+        assert_eq!(intent.matched_txid.as_deref(), Some("txid"));
+        assert_eq!(intent.matched_vout, Some(1));
+        assert_eq!(intent.matched_at, Some(2000));
+
+        assert_eq!(
+            conn.query_open_deposit_intent("depc_sender", 0, 2000)
+                .unwrap()
+                .unwrap()
+                .id,
+            id2
+        );
+        // created out of the window
+        assert!(conn
+            .query_open_deposit_intent("depc_sender", 1101, 2000)
+            .unwrap()
+            .is_none());
+        assert!(conn.query_deposit_intent(100).unwrap().is_none());
     }
 
     #[test]
-    fn test_make_deposit() {
+    fn test_deposit_addresses() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
+        assert_eq!(conn.query_deposit_xpub().unwrap(), None);
+        conn.save_deposit_xpub("xpub1").unwrap();
+        conn.save_deposit_xpub("xpub2").unwrap();
+        assert_eq!(conn.query_deposit_xpub().unwrap().as_deref(), Some("xpub2"));
+        // an xpub is used by one bridge only
+        let other = conn.scoped("other");
+        assert!(other.save_deposit_xpub("xpub2").is_err());
 
-        conn.save_deposit("depc_txid", "to_erc20_address", 10000000, 394838121)
+        assert_eq!(conn.query_next_deposit_address_index().unwrap(), 0);
+        conn.add_deposit_address("address0", 0, "0/0", "recipient0", 1000)
             .unwrap();
+        conn.add_deposit_address("address1", 1, "0/1", "recipient1", 1100)
+            .unwrap();
+        assert_eq!(conn.query_next_deposit_address_index().unwrap(), 2);
+        // the index or the address is taken
+        assert!(conn
+            .add_deposit_address("address2", 1, "0/1", "recipient2", 1200)
+            .is_err());
+        assert!(other
+            .add_deposit_address("address1", 0, "0/0", "recipient2", 1200)
+            .is_err());
+        assert_eq!(
+            conn.query_deposit_address("address1").unwrap(),
+            Some(DepositAddressRecord {
+                address: "address1".to_owned(),
+                derivation_index: 1,
+                derivation_path: "0/1".to_owned(),
+                recipient: "recipient1".to_owned(),
+                created_at: 1100,
+            })
+        );
+        assert!(other.query_deposit_address("address1").unwrap().is_none());
+        assert_eq!(other.query_next_deposit_address_index().unwrap(), 0);
 
-        conn.confirm_deposit("erc20_txid", 193847845, "depc_txid")
+        // the address is used once it's paid
+        assert!(!conn.query_address_has_coins("address0").unwrap());
+        conn.add_coin("txid", 0, DepcAmount::from_value64(1), "address0", "")
+            .unwrap();
+        assert!(conn.query_address_has_coins("address0").unwrap());
+        conn.add_coin("txid", 1, DepcAmount::from_value64(2), "address1", "")
+            .unwrap();
+        conn.add_coin("txid", 2, DepcAmount::from_value64(4), "address2", "")
             .unwrap();
+        assert_eq!(
+            conn.query_deposit_addresses_balance().unwrap(),
+            DepcAmount::from_value64(3)
+        );
+        assert_eq!(
+            conn.query_address_balance("address2").unwrap(),
+            DepcAmount::from_value64(4)
+        );
+        assert_eq!(
+            other.query_deposit_addresses_balance().unwrap(),
+            DepcAmount::ZERO
+        );
     }
 
     #[test]
-    fn test_make_withdraw() {
+    fn test_withdraw_requests() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let id = conn
+            .add_withdraw_request(
+                "sig1",
+                "depc_recipient",
+                "signer",
+                "offchain",
+                "pending",
+                1000,
+            )
+            .unwrap()
+            .unwrap();
+        // the same transaction is requested once
+        assert_eq!(
+            conn.add_withdraw_request("sig1", "another", "signer", "offchain", "pending", 1100)
+                .unwrap(),
+            None
+        );
+        let pending = conn.query_withdraw_requests_by_status("pending").unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].recipient, "depc_recipient");
+        assert_eq!(pending[0].processed_at, None);
+
+        assert!(conn
+            .complete_withdraw_request(id, "accepted", None, Some(7), 1200)
+            .unwrap());
+        assert!(!conn
+            .complete_withdraw_request(id, "rejected", Some("late"), None, 1300)
+            .unwrap());
+        let request = conn.query_withdraw_request(id).unwrap().unwrap();
+        assert_eq!(request.status, "accepted");
+        assert_eq!(request.reason, None);
+        assert_eq!(request.pending_action_id, Some(7));
+        assert_eq!(request.processed_at, Some(1200));
+        assert!(conn
+            .query_withdraw_requests_by_status("pending")
+            .unwrap()
+            .is_empty());
+        assert!(conn.query_withdraw_request(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bridge_tables_scoped_by_bridge_id() {
         let conn = Conn::open_in_mem().unwrap();
         conn.init().unwrap();
+        let other = conn.scoped("other");
+        assert_eq!(conn.bridge_id(), DEFAULT_BRIDGE_ID);
+        assert_eq!(other.bridge_id(), "other");
 
-        conn.make_withdraw("erc20_txid", 193847845, "from_address", 1000000)
+        // the same output can be a deposit of both bridges
+        let amount = DepcAmount::from_value64(1000);
+        conn.save_deposit("txid", "solana_address", amount, 1000)
             .unwrap();
-        conn.confirm_withdraw("depc_txid", 193848478, "erc20_txid", "depc_address")
+        other
+            .save_deposit("txid", "solana_address", amount, 1000)
+            .unwrap();
+        assert!(conn
+            .save_deposit("txid", "solana_address", amount, 1000)
+            .is_err());
+        other.confirm_deposit("sig", 2000, "txid").unwrap();
+        assert!(conn
+            .query_deposit("txid")
+            .unwrap()
+            .unwrap()
+            .erc20_txid
+            .is_none());
+
+        let id = other
+            .add_pending_action("deposit", "txid", "solana_address", amount, 1000)
+            .unwrap();
+        assert!(conn.claim_pending_action(id, 1100).unwrap().is_none());
+        assert!(conn
+            .query_unclaimed_pending_action_ids("deposit")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            other.query_unclaimed_pending_action_ids("deposit").unwrap(),
+            vec![id]
+        );
+        assert!(other.claim_pending_action(id, 1100).unwrap().is_some());
+        assert_eq!(conn.release_claimed_pending_actions().unwrap(), 0);
+
+        let id = other
+            .add_deposit_intent("sender", "recipient", 1000)
+            .unwrap();
+        assert!(conn.query_deposit_intent(id).unwrap().is_none());
+        assert!(conn
+            .query_open_deposit_intent("sender", 0, 2000)
+            .unwrap()
+            .is_none());
+        assert!(other.query_deposit_intent(id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_init_adds_bridge_id_to_existing_tables() {
+        let conn = Conn::open_in_mem().unwrap();
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            c.execute("create table pending_actions (id integer primary key autoincrement, action, source_txid, recipient, amount, timestamp, claimed_at, done_at, txid)", []).unwrap();
+            c.execute("insert into pending_actions (action, source_txid, recipient, amount, timestamp) values ('deposit', 'txid', 'solana_address', 1000, 1000)", []).unwrap();
+        }
+        conn.init().unwrap();
+        // the rows made before belong to the default bridge
+        assert_eq!(
+            conn.query_unclaimed_pending_action_ids("deposit").unwrap(),
+            vec![1]
+        );
+        assert!(conn
+            .scoped("other")
+            .query_unclaimed_pending_action_ids("deposit")
+            .unwrap()
+            .is_empty());
+        // and it's initialized again without error
+        conn.init().unwrap();
+        assert_eq!(conn.query_schema_version().unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_query_height_by_time() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_block("hash0", 0, "miner", 1000).unwrap();
+        conn.add_block("hash1", 1, "miner", 1200).unwrap();
+        // the time of a block might be earlier than the last one
+        conn.add_block("hash2", 2, "miner", 1150).unwrap();
+
+        assert_eq!(conn.query_height_by_time(999).unwrap(), None);
+        assert_eq!(conn.query_height_by_time(1000).unwrap(), Some(0));
+        assert_eq!(conn.query_height_by_time(1199).unwrap(), Some(2));
+        assert_eq!(conn.query_height_by_time(5000).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_address_balances_follow_coins() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_coin("txid1", 0, DepcAmount::from_value64(1000), "a", "")
+            .unwrap();
+        conn.add_coin("txid1", 1, DepcAmount::from_value64(3000), "b", "")
+            .unwrap();
+        conn.add_coin("txid2", 0, DepcAmount::from_value64(500), "a", "")
+            .unwrap();
+        conn.mark_coin_to_spent("txid1", 1, "txid3", 10).unwrap();
+        conn.mark_coin_to_spent("txid1", 1, "txid3", 10).unwrap();
+        conn.add_coin("txid3", 0, DepcAmount::from_value64(2000), "c", "")
+            .unwrap();
+
+        let richlist = conn.query_richlist(10).unwrap();
+        assert_eq!(
+            richlist,
+            vec![
+                ("c".to_owned(), DepcAmount::from_value64(2000)),
+                ("a".to_owned(), DepcAmount::from_value64(1500)),
+            ]
+        );
+        assert_eq!(conn.query_richlist(1).unwrap().len(), 1);
+
+        // the balances are filled from the coins when the table is made
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            c.execute("drop table address_balances", []).unwrap();
+        }
+        conn.init().unwrap();
+        assert_eq!(conn.query_richlist(10).unwrap(), richlist);
+    }
+
+    #[test]
+    fn test_archive_spent_coins() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_coin("txid1", 0, DepcAmount::from_value64(1000), "a", "51")
+            .unwrap();
+        conn.add_coin("txid1", 1, DepcAmount::from_value64(3000), "b", "52")
+            .unwrap();
+        conn.add_coin("txid1", 2, DepcAmount::from_value64(500), "c", "53")
+            .unwrap();
+        conn.mark_coin_to_spent("txid1", 0, "txid2", 10).unwrap();
+        conn.mark_coin_to_spent("txid1", 1, "txid3", 150_000)
+            .unwrap();
+        let spent_inputs = conn.query_spent_inputs().unwrap();
+
+        // a batch is moved each call, the coins of two partitions
+        assert_eq!(conn.archive_spent_coins(200_000).unwrap(), 1);
+        assert_eq!(conn.archive_spent_coins(200_000).unwrap(), 1);
+        assert_eq!(conn.archive_spent_coins(200_000).unwrap(), 0);
+        {
+            let c = conn.pool.write_conn.lock().unwrap();
+            let hot: u32 = c
+                .query_row("select count(*) from coins", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(hot, 1);
+            for table in [coins_archive_table(0), coins_archive_table(1)] {
+                assert!(c
+                    .query_row(SQL_QUERY_HAS_TABLE, params![table], |row| {
+                        row.get::<_, bool>(0)
+                    })
+                    .unwrap());
+            }
+        }
+
+        // the history is still queried
+        assert_eq!(conn.query_inputs("txid2").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(conn.query_inputs("txid3").unwrap(), vec!["b".to_owned()]);
+        assert_eq!(conn.query_spent_inputs().unwrap(), spent_inputs);
+        assert_eq!(
+            conn.query_coin_script("txid1", 1).unwrap().as_deref(),
+            Some("52")
+        );
+        assert_eq!(
+            conn.query_richlist(10).unwrap(),
+            vec![("c".to_owned(), DepcAmount::from_value64(500))]
+        );
+        // and the view is made again with the archive tables
+        conn.init().unwrap();
+        assert_eq!(conn.query_spent_inputs().unwrap(), spent_inputs);
+    }
+
+    #[test]
+    fn test_balance_query_uses_covering_index() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_block("hash1", 1, "miner", 1000).unwrap();
+        conn.add_transaction("hash1", "txid1").unwrap();
+        conn.add_coin("txid1", 0, DepcAmount::from_value64(1000), "a", "")
+            .unwrap();
+        conn.add_block("hash2", 2, "miner", 1200).unwrap();
+        conn.add_transaction("hash2", "txid2").unwrap();
+        conn.add_coin("txid2", 0, DepcAmount::from_value64(500), "a", "")
+            .unwrap();
+        conn.mark_coin_to_spent("txid1", 0, "txid2", 2).unwrap();
+
+        // the height is stored with the coin when it's added
+        assert_eq!(
+            conn.query_balance("a", 1).unwrap(),
+            DepcAmount::from_value64(1000)
+        );
+        assert_eq!(
+            conn.query_balance("a", 2).unwrap(),
+            DepcAmount::from_value64(500)
+        );
+
+        // the height is kept when the coins are archived
+        assert_eq!(conn.archive_spent_coins(200_000).unwrap(), 1);
+        assert_eq!(
+            conn.query_balance("a", 1).unwrap(),
+            DepcAmount::from_value64(1000)
+        );
+
+        let details = conn
+            .read(|c| {
+                let mut stmt = c.prepare(&format!(
+                    "explain query plan {SQL_QUERY_BALANCE_OF_ADDRESS}"
+                ))?;
+                let iter = stmt.query_map(params!["a", 1, 1], |row| row.get::<_, String>(3))?;
+                Ok(iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .unwrap();
+        // every table of the view is searched by its covering index, neither a scan nor a join
+        let searches = details
+            .iter()
+            .filter(|detail| !detail.contains("all_coin_values") && detail.contains("coins"))
+            .collect::<Vec<_>>();
+        assert_eq!(searches.len(), 2, "{details:?}");
+        assert!(searches[0].contains("USING COVERING INDEX index__coins_owner_height"));
+        assert!(searches[1].contains("USING COVERING INDEX index__coins_archive_0_owner_height"));
+        assert!(details.iter().all(|detail| !detail.contains("blocks")));
+    }
+
+    #[test]
+    fn test_query_block_summaries() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        conn.add_block("hash0", 0, "miner0", 1000).unwrap();
+        conn.add_block("hash1", 1, "miner1", 1180).unwrap();
+        conn.add_transaction("hash1", "txid1").unwrap();
+        conn.add_transaction("hash1", "txid2").unwrap();
+        conn.add_coin("txid1", 0, DepcAmount::from_value64(1000), "a", "")
+            .unwrap();
+        conn.add_coin("txid2", 0, DepcAmount::from_value64(300), "b", "")
             .unwrap();
+        conn.add_coin("txid2", 1, DepcAmount::from_value64(200), "c", "")
+            .unwrap();
+
+        let block = conn.query_block_summary_by_hash("hash1").unwrap().unwrap();
+        assert_eq!(block.height, 1);
+        assert_eq!(block.miner, "miner1");
+        assert_eq!(block.tx_count, 2);
+        assert_eq!(block.total_value, DepcAmount::from_value64(1500));
+        let block = conn.query_block_summary_by_height(0).unwrap().unwrap();
+        assert_eq!(block.tx_count, 0);
+        assert_eq!(block.total_value, DepcAmount::ZERO);
+        assert!(conn.query_block_summary_by_height(2).unwrap().is_none());
+
+        let blocks = conn.query_block_summaries(0, 5).unwrap();
+        assert_eq!(
+            blocks.iter().map(|block| block.height).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_query_miner_stats() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let blocks = [
+            (0, "miner0", 86400 - 60),
+            (1, "miner1", 86400),
+            (2, "miner0", 86400 + 600),
+            (3, "miner0", 2 * 86400 + 60),
+            // the block time is earlier than the last block
+            (4, "miner1", 86400 + 300),
+        ];
+        for (height, miner, time) in blocks {
+            conn.add_block(&format!("hash{height}"), height, miner, time)
+                .unwrap();
+        }
+
+        let stats = conn.query_miner_stats(0, 4, 10).unwrap();
+        assert_eq!(
+            stats,
+            vec![
+                MinerStatsRecord {
+                    miner: "miner0".to_owned(),
+                    blocks: 3,
+                    first_height: 0,
+                    last_height: 3,
+                    first_seen: 86400 - 60,
+                    last_seen: 2 * 86400 + 60,
+                },
+                MinerStatsRecord {
+                    miner: "miner1".to_owned(),
+                    blocks: 2,
+                    first_height: 1,
+                    last_height: 4,
+                    first_seen: 86400,
+                    last_seen: 86400 + 300,
+                },
+            ]
+        );
+        // only the heights and the miners within the limit
+        let stats = conn.query_miner_stats(2, 4, 1).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].miner.as_str(), stats[0].blocks), ("miner0", 2));
+
+        let miner = conn.query_miner_stats_of_miner("miner1").unwrap().unwrap();
+        assert_eq!(
+            (miner.blocks, miner.first_height, miner.last_height),
+            (2, 1, 4)
+        );
+        assert_eq!(conn.query_miner_stats_of_miner("nobody").unwrap(), None);
+        assert_eq!(
+            conn.query_miner_daily_blocks("miner0", 0, 3 * 86400)
+                .unwrap(),
+            vec![(0, 1), (1, 1), (2, 1)]
+        );
+        assert_eq!(
+            conn.query_miner_daily_blocks("miner1", 86400, 86400 + 300)
+                .unwrap(),
+            vec![(1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_relay_quota() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        assert!(conn.add_relay("payer", 100, 2).unwrap());
+        assert!(conn.add_relay("payer", 100, 2).unwrap());
+        assert!(!conn.add_relay("payer", 100, 2).unwrap());
+        // the quota is counted for each payer, each day and each bridge
+        assert!(conn.add_relay("other", 100, 2).unwrap());
+        assert!(conn.add_relay("payer", 101, 2).unwrap());
+        assert!(conn.scoped("partner").add_relay("payer", 100, 2).unwrap());
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let conn = Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+
+        assert!(conn.claim_idempotency_key("key", "hash", 100, 0).unwrap());
+        let record = conn.query_idempotency_key("key").unwrap().unwrap();
+        assert_eq!(record.request_hash, "hash");
+        assert_eq!(record.status, None);
+        assert!(!conn.claim_idempotency_key("key", "hash", 101, 0).unwrap());
+
+        conn.save_idempotent_response("key", 200, b"{}").unwrap();
+        let record = conn.query_idempotency_key("key").unwrap().unwrap();
+        assert_eq!(record.status, Some(200));
+        assert_eq!(record.body.as_deref(), Some(&b"{}"[..]));
+
+        // the keys are scoped to the bridge
+        assert!(conn
+            .scoped("partner")
+            .query_idempotency_key("key")
+            .unwrap()
+            .is_none());
+        assert!(conn
+            .scoped("partner")
+            .claim_idempotency_key("key", "other", 101, 0)
+            .unwrap());
+
+        // an expired key is claimed again
+        assert!(conn
+            .claim_idempotency_key("key", "other", 200, 150)
+            .unwrap());
+        let record = conn.query_idempotency_key("key").unwrap().unwrap();
+        assert_eq!(record.request_hash, "other");
+        assert_eq!(record.status, None);
+        assert_eq!(record.body, None);
+        assert_eq!(record.created_at, 200);
+
+        conn.delete_idempotency_key("key").unwrap();
+        assert!(conn.query_idempotency_key("key").unwrap().is_none());
+        assert!(conn.claim_idempotency_key("key", "hash", 201, 150).unwrap());
     }
-}
\ No newline at end of file
+}
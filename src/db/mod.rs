@@ -1,3 +1,9 @@
+mod archive;
 mod conn;
+mod payload;
+mod retry;
 
+pub use archive::*;
 pub use conn::*;
+pub use payload::*;
+pub use retry::*;
@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{info, warn};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Value, ValueRef};
+use tokio::time::sleep;
+
+use super::Conn;
+
+/// The first byte of a stored payload tells how the rest is encoded
+const PAYLOAD_RAW: u8 = 0;
+const PAYLOAD_ZSTD: u8 = 1;
+
+/// The payloads longer than it are compressed when feature `zstd` is enabled, the standard
+/// scripts (25 bytes) are stored raw since they don't get any smaller
+pub const PAYLOAD_COMPRESSION_THRESHOLD: usize = 128;
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// The rows a batch of `run_payload_compression` rewrites
+const PAYLOAD_COMPRESSION_BATCH: u32 = 1000;
+
+/// A hex payload of a row (the script of a coin, the raw transaction of a sweep)
+///
+/// It's stored as the bytes instead of the hex, half the size, the large ones are compressed.
+/// The rows written as hex text before are read as they are, and a payload which isn't
+/// lowercase hex is stored as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexPayload(pub String);
+
+impl HexPayload {
+    fn encode(&self) -> Option<Vec<u8>> {
+        if self.0.bytes().any(|b| b.is_ascii_uppercase()) {
+            return None;
+        }
+        let bytes = hex::decode(&self.0).ok()?;
+        if bytes.len() > PAYLOAD_COMPRESSION_THRESHOLD {
+            if let Some(compressed) = compress(&bytes) {
+                let mut blob = vec![PAYLOAD_ZSTD];
+                blob.extend_from_slice(&compressed);
+                return Some(blob);
+            }
+        }
+        let mut blob = vec![PAYLOAD_RAW];
+        blob.extend_from_slice(&bytes);
+        Some(blob)
+    }
+
+    fn decode(blob: &[u8]) -> FromSqlResult<HexPayload> {
+        match blob.split_first() {
+            Some((&PAYLOAD_RAW, bytes)) => Ok(HexPayload(hex::encode(bytes))),
+            #[cfg(feature = "zstd")]
+            Some((&PAYLOAD_ZSTD, compressed)) => zstd::decode_all(compressed)
+                .map(|bytes| HexPayload(hex::encode(bytes)))
+                .map_err(|e| FromSqlError::Other(Box::new(e))),
+            #[cfg(not(feature = "zstd"))]
+            Some((&PAYLOAD_ZSTD, _)) => Err(FromSqlError::Other(
+                "the payload is compressed by zstd, feature `zstd` is required".into(),
+            )),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for HexPayload {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self.encode() {
+            Some(blob) => Ok(ToSqlOutput::Owned(Value::Blob(blob))),
+            None => self.0.to_sql(),
+        }
+    }
+}
+
+impl FromSql for HexPayload {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(_) => String::column_result(value).map(HexPayload),
+            ValueRef::Blob(blob) => HexPayload::decode(blob),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// The payload compressed by zstd, `None` if it doesn't get smaller
+#[cfg(feature = "zstd")]
+fn compress(bytes: &[u8]) -> Option<Vec<u8>> {
+    zstd::bulk::compress(bytes, ZSTD_LEVEL)
+        .ok()
+        .filter(|compressed| compressed.len() < bytes.len())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress(_bytes: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+/// The columns of the hex payloads, the rows written as hex text are rewritten by
+/// `Conn::compress_payloads`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadColumn {
+    CoinScripts,
+    SweepRawTxs,
+}
+
+impl PayloadColumn {
+    pub const ALL: [PayloadColumn; 2] = [PayloadColumn::CoinScripts, PayloadColumn::SweepRawTxs];
+}
+
+/// Rewrite the payloads written as hex text in batches, the other tasks keep using the database
+/// between the batches
+pub async fn run_payload_compression(exit_sig: Arc<Mutex<bool>>, conn: Conn, interval: Duration) {
+    for column in PayloadColumn::ALL {
+        let mut after = 0;
+        let mut rows = 0;
+        loop {
+            {
+                let exit = exit_sig.lock().unwrap();
+                if *exit {
+                    return;
+                }
+            }
+            match conn.compress_payloads(column, after, PAYLOAD_COMPRESSION_BATCH) {
+                Ok(Some((last, n))) => {
+                    after = last;
+                    rows += n;
+                }
+                Ok(None) => break,
+                Err(e) => warn!("cannot rewrite the payloads of {column:?}, reason: {e}"),
+            }
+            sleep(interval).await;
+        }
+        if rows > 0 {
+            info!("{rows} payloads of {column:?} are rewritten");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_payload_encoding() {
+        let script = HexPayload("76a914b1e2c3d4e5f60718293a4b5c6d7e8f9001122388ac".to_owned());
+        let blob = script.encode().unwrap();
+        assert_eq!(blob[0], PAYLOAD_RAW);
+        assert_eq!(blob.len(), 1 + script.0.len() / 2);
+        assert_eq!(HexPayload::decode(&blob).unwrap(), script);
+
+        // the large payloads are compressed when they get smaller
+        let raw_tx = HexPayload("0200".repeat(PAYLOAD_COMPRESSION_THRESHOLD));
+        let blob = raw_tx.encode().unwrap();
+        if cfg!(feature = "zstd") {
+            assert_eq!(blob[0], PAYLOAD_ZSTD);
+            assert!(blob.len() < raw_tx.0.len() / 4);
+        }
+        assert_eq!(HexPayload::decode(&blob).unwrap(), raw_tx);
+
+        assert_eq!(HexPayload(String::new()).encode(), Some(vec![PAYLOAD_RAW]));
+        assert!(HexPayload("0200BEEF".to_owned()).encode().is_none());
+        assert!(HexPayload("not hex".to_owned()).encode().is_none());
+        assert!(HexPayload::decode(&[]).is_err());
+        assert!(HexPayload::decode(&[9, 1]).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+use rusqlite::{Error, ErrorCode};
+
+/// How long SQLite itself waits for a lock before a statement fails with `SQLITE_BUSY`, it's set
+/// as `pragma busy_timeout` on every connection
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub const DEFAULT_BUSY_RETRY_POLICY: BusyRetryPolicy = BusyRetryPolicy {
+    max_retries: 5,
+    base_delay: Duration::from_millis(50),
+    max_delay: Duration::from_secs(1),
+};
+
+/// How the statements failed with `SQLITE_BUSY` or `SQLITE_LOCKED` after `BUSY_TIMEOUT` are
+/// retried, the delay doubles from `base_delay` up to `max_delay`, and a random half of it is
+/// skipped so the connections waiting for the same lock don't retry in lockstep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusyRetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for BusyRetryPolicy {
+    fn default() -> Self {
+        DEFAULT_BUSY_RETRY_POLICY
+    }
+}
+
+impl BusyRetryPolicy {
+    /// The delay before retry `attempt` (from 0), `jitter` is in `[0, 1]`
+    pub fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        backoff / 2 + backoff.mul_f64(jitter.clamp(0.0, 1.0)) / 2
+    }
+}
+
+/// Whether the statement failed since the database is locked by another connection
+pub fn is_busy(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::SqliteFailure(e, _)
+            if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// The counters of the busy retries of a database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BusyRetryStats {
+    /// The statements run again after they failed with `SQLITE_BUSY`
+    pub retries: u64,
+    /// The statements still failed after `max_retries`
+    pub exhausted: u64,
+}
+
+/// The policy with the counters, shared by the connections to a database
+#[derive(Debug, Default)]
+pub(crate) struct BusyRetry {
+    policy: BusyRetryPolicy,
+    retries: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+impl BusyRetry {
+    pub fn new(policy: BusyRetryPolicy) -> Self {
+        BusyRetry {
+            policy,
+            retries: AtomicU64::new(0),
+            exhausted: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `f` until it doesn't fail with `SQLITE_BUSY` or the retries are used up, `f` must be
+    /// one statement (or one savepoint) so a retry never repeats the rows written already
+    pub fn run<T>(&self, mut f: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if is_busy(&e) => {
+                    if attempt >= self.policy.max_retries {
+                        self.exhausted.fetch_add(1, Ordering::Relaxed);
+                        warn!("the database is still locked after {attempt} retries, {e}");
+                        return Err(e);
+                    }
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(self.policy.delay(attempt, jitter()));
+                    attempt += 1;
+                }
+                r => return r,
+            }
+        }
+    }
+
+    pub fn stats(&self) -> BusyRetryStats {
+        BusyRetryStats {
+            retries: self.retries.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A random number in `[0, 1]`, the keys of a new `RandomState` are random
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::ffi;
+
+    use super::*;
+
+    fn busy() -> Error {
+        Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_BUSY), None)
+    }
+
+    #[test]
+    fn test_busy_retry_policy() {
+        let policy = DEFAULT_BUSY_RETRY_POLICY;
+        assert_eq!(policy.delay(0, 0.0), Duration::from_millis(25));
+        assert_eq!(policy.delay(0, 1.0), Duration::from_millis(50));
+        assert_eq!(policy.delay(2, 1.0), Duration::from_millis(200));
+        // capped
+        assert_eq!(policy.delay(10, 1.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX, 0.5), Duration::from_millis(750));
+        for _ in 0..100 {
+            let delay = policy.delay(1, jitter());
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+
+        assert!(is_busy(&busy()));
+        assert!(is_busy(&Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_LOCKED),
+            None
+        )));
+        assert!(!is_busy(&Error::QueryReturnedNoRows));
+    }
+
+    #[test]
+    fn test_busy_retry() {
+        let retry = BusyRetry::new(BusyRetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        });
+        // the lock is released at the third attempt
+        let mut attempts = 0;
+        let r = retry.run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(busy())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(r.unwrap(), 3);
+        assert_eq!(
+            retry.stats(),
+            BusyRetryStats {
+                retries: 2,
+                exhausted: 0
+            }
+        );
+
+        let mut attempts = 0;
+        let r: Result<(), _> = retry.run(|| {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(is_busy(&r.unwrap_err()));
+        assert_eq!(attempts, 4);
+        assert_eq!(
+            retry.stats(),
+            BusyRetryStats {
+                retries: 5,
+                exhausted: 1
+            }
+        );
+
+        // the other errors are returned at once
+        let mut attempts = 0;
+        let r: Result<(), _> = retry.run(|| {
+            attempts += 1;
+            Err(Error::QueryReturnedNoRows)
+        });
+        assert!(r.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -1,14 +1,31 @@
+use std::collections::HashSet;
 use std::fs;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use log::error;
+use serde_json::{json, Value};
 
-use super::{Block, Error, Transaction, Address, Amount, TxID};
+use super::{
+    format_amount, parse_amount, Address, Amount, Block, BlockchainInfo, DescriptorInfo, Error,
+    ImportDescriptorResult, NetworkInfo, PrevTx, SignedRawTransaction, Transaction, TxID, Unspent,
+    ValidatedAddress,
+};
 
 use crate::rpc;
 
+/// The fee is paid for each transaction made by the bridge
+pub const DEFAULT_TX_FEE: Amount = 100_000;
+
 #[derive(Clone)]
 pub struct Client {
     config: rpc::Config,
+    /// The transfers signal replaceability, see `ClientBuilder::set_replaceable`
+    replaceable: bool,
+    /// Held from the coins are selected until the transaction is broadcast, see `lock_spending`
+    spend_lock: Arc<Mutex<()>>,
+    /// The coins spent by the transactions not broadcast yet, see `reserve_coins`
+    reserved_coins: Arc<Mutex<HashSet<(TxID, u32)>>>,
 }
 
 impl Client {
@@ -17,7 +34,10 @@ impl Client {
             .set_method("getblockcount")
             .build();
         match rpc::Client::new(self.config.clone()).send(&rpc_json) {
-            Ok(resp) => Ok(resp.result.as_u64().unwrap() as u32),
+            Ok(resp) => match resp.result.as_u64() {
+                Some(height) => Ok(height as u32),
+                None => Err(Error::InvalidResponse("getblockcount".to_owned())),
+            },
             Err(e) => {
                 error!("cannot execute `getheight`, reason: {e}");
                 Err(Error::RpcError)
@@ -31,10 +51,13 @@ impl Client {
             .add_param_i64("height", height as i64)
             .build();
         match rpc::Client::new(self.config.clone()).send(&rpc_json) {
-            Ok(resp) => Ok(resp.result.as_str().unwrap().to_owned()),
+            Ok(resp) => match resp.result.as_str() {
+                Some(block_hash) => Ok(block_hash.to_owned()),
+                None => Err(Error::InvalidResponse("getblockhash".to_owned())),
+            },
             Err(e) => {
                 error!("cannot execute `getblockhash`, reason: {e}");
-                Ok("".to_owned())
+                Err(Error::RpcError)
             }
         }
     }
@@ -45,10 +68,13 @@ impl Client {
             .add_param_string("blockhash", block_hash)
             .build();
         match rpc::Client::new(self.config.clone()).send(&rpc_json) {
-            Ok(resp) => Ok(serde_json::from_value(resp.result).unwrap()),
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `getblock`, reason: {e}");
+                Error::InvalidResponse("getblock".to_owned())
+            }),
             Err(e) => {
                 error!("cannot execute `getblock`, reason: {e}");
-                Err(Error::RpcError)
+                Err(make_rpc_error(e))
             }
         }
     }
@@ -60,18 +86,497 @@ impl Client {
             .add_param_bool("verbose", true)
             .build();
         match rpc::Client::new(self.config.clone()).send(&rpc_json) {
-            Ok(resp) => Ok(serde_json::from_value(resp.result).unwrap()),
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `getrawtransaction`, reason: {e}");
+                Error::InvalidResponse("getrawtransaction".to_owned())
+            }),
             Err(e) => {
                 error!("cannot execute `getrawtransaction`, reason: {e}");
+                Err(make_rpc_error(e))
+            }
+        }
+    }
+
+    pub fn validate_address(&self, address: &str) -> Result<ValidatedAddress, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("validateaddress")
+            .add_param_string("address", address)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `validateaddress`, reason: {e}");
+                Error::InvalidResponse("validateaddress".to_owned())
+            }),
+            Err(e) => {
+                error!("cannot execute `validateaddress`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    pub fn get_network_info(&self) -> Result<NetworkInfo, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("getnetworkinfo")
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `getnetworkinfo`, reason: {e}");
+                Error::InvalidResponse("getnetworkinfo".to_owned())
+            }),
+            Err(e) => {
+                error!("cannot execute `getnetworkinfo`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    pub fn get_blockchain_info(&self) -> Result<BlockchainInfo, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("getblockchaininfo")
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `getblockchaininfo`, reason: {e}");
+                Error::InvalidResponse("getblockchaininfo".to_owned())
+            }),
+            Err(e) => {
+                error!("cannot execute `getblockchaininfo`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    pub fn list_unspent(&self, address: &Address) -> Result<Vec<Unspent>, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("listunspent")
+            .add_param_i64("minconf", 1)
+            .add_param_i64("maxconf", 9999999)
+            .add_param_value("addresses", json!([address]))
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `listunspent`, reason: {e}");
+                Error::RpcError
+            }),
+            Err(e) => {
+                error!("cannot execute `listunspent`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    /// The total received by `address` in the blocks, the address must be in the wallet of
+    /// the node, e.g. imported as watch-only
+    pub fn get_received_by_address(&self, address: &Address) -> Result<Amount, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("getreceivedbyaddress")
+            .add_param_string("address", address)
+            .add_param_i64("minconf", 1)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result.as_f64() {
+                Some(amount) => Ok(parse_amount(amount)),
+                None => Err(Error::InvalidResponse("getreceivedbyaddress".to_owned())),
+            },
+            Err(e) => {
+                error!("cannot execute `getreceivedbyaddress`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    /// Import `address` to the legacy wallet of the node as watch-only, the blocks are
+    /// rescanned for its coins when `rescan` is set, which takes a while
+    pub fn import_address(&self, address: &str, label: &str, rescan: bool) -> Result<(), Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("importaddress")
+            .add_param_string("address", address)
+            .add_param_string("label", label)
+            .add_param_bool("rescan", rescan)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("cannot execute `importaddress`, reason: {e}");
+                match e.downcast_ref::<rpc::ServerError>() {
+                    Some(server_error) => Err(Error::CannotImport(
+                        address.to_owned(),
+                        server_error.message.clone(),
+                    )),
+                    None => Err(Error::RpcError),
+                }
+            }
+        }
+    }
+
+    /// The descriptor with its checksum, `importdescriptors` takes it only with the checksum
+    pub fn get_descriptor_info(&self, descriptor: &str) -> Result<DescriptorInfo, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("getdescriptorinfo")
+            .add_param_string("descriptor", descriptor)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result).map_err(|e| {
+                error!("cannot parse result of `getdescriptorinfo`, reason: {e}");
+                Error::InvalidResponse("getdescriptorinfo".to_owned())
+            }),
+            Err(e) => {
+                error!("cannot execute `getdescriptorinfo`, reason: {e}");
+                match e.downcast_ref::<rpc::ServerError>() {
+                    Some(server_error) => Err(Error::CannotImport(
+                        descriptor.to_owned(),
+                        server_error.message.clone(),
+                    )),
+                    None => Err(Error::RpcError),
+                }
+            }
+        }
+    }
+
+    /// Import `descriptor` to the descriptor wallet of the node as watch-only, the blocks are
+    /// rescanned for its coins from the genesis when `rescan` is set, or from now otherwise
+    pub fn import_descriptor(
+        &self,
+        descriptor: &str,
+        label: &str,
+        rescan: bool,
+    ) -> Result<(), Error> {
+        let descriptor = self.get_descriptor_info(descriptor)?.descriptor;
+        let timestamp = if rescan { json!(0) } else { json!("now") };
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("importdescriptors")
+            .add_param_value(
+                "requests",
+                json!([{"desc": descriptor, "timestamp": timestamp, "label": label}]),
+            )
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => {
+                let results: Vec<ImportDescriptorResult> = serde_json::from_value(resp.result)
+                    .map_err(|e| {
+                        error!("cannot parse result of `importdescriptors`, reason: {e}");
+                        Error::InvalidResponse("importdescriptors".to_owned())
+                    })?;
+                match results.into_iter().next() {
+                    Some(result) if result.success => Ok(()),
+                    Some(result) => Err(Error::CannotImport(
+                        descriptor,
+                        result.error.map(|error| error.message).unwrap_or_default(),
+                    )),
+                    None => Err(Error::InvalidResponse("importdescriptors".to_owned())),
+                }
+            }
+            Err(e) => {
+                error!("cannot execute `importdescriptors`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    /// The transfers made by the clones of the client select the coins one by one, hold the
+    /// guard from `make_transfer` until the transaction is broadcast so the next one never
+    /// picks the same coins
+    pub fn lock_spending(&self) -> MutexGuard<'_, ()> {
+        self.spend_lock.lock().unwrap()
+    }
+
+    /// Keep the coins out of the transfers made by the clones of the client, e.g. the ones
+    /// spent by an unsigned transaction the operator broadcasts later
+    pub fn reserve_coins(&self, coins: &[(TxID, u32)]) {
+        let mut reserved = self.reserved_coins.lock().unwrap();
+        reserved.extend(coins.iter().cloned());
+    }
+
+    /// Return the coins to the transfers, see `reserve_coins`
+    pub fn unreserve_coins(&self, coins: &[(TxID, u32)]) {
+        let mut reserved = self.reserved_coins.lock().unwrap();
+        for coin in coins.iter() {
+            reserved.remove(coin);
+        }
+    }
+
+    /// Build an unsigned transaction which transfers `amount` from `from_address` to
+    /// `to_address`, the change (after the fee is deducted) is returned to `from_address`
+    ///
+    /// Returns the hex string of the raw transaction, it can be signed offline
+    pub fn make_transfer(
+        &self,
+        from_address: &Address,
+        to_address: &Address,
+        amount: Amount,
+    ) -> Result<String, Error> {
+        self.make_transfer_spending(from_address, to_address, amount)
+            .map(|(hex, _)| hex)
+    }
+
+    /// The same as `make_transfer`, the coins spent by the transaction are returned with it
+    pub fn make_transfer_spending(
+        &self,
+        from_address: &Address,
+        to_address: &Address,
+        amount: Amount,
+    ) -> Result<(String, Vec<Unspent>), Error> {
+        let mut unspents = self.list_unspent(from_address)?;
+        {
+            let reserved = self.reserved_coins.lock().unwrap();
+            unspents.retain(|unspent| !reserved.contains(&(unspent.txid.clone(), unspent.vout)));
+        }
+        // pick coins until the amount and the fee are covered
+        let required = amount + DEFAULT_TX_FEE;
+        let mut spent = vec![];
+        let mut total = 0;
+        for unspent in unspents.into_iter() {
+            if total >= required {
+                break;
+            }
+            total += unspent.value64();
+            spent.push(unspent);
+        }
+        if total < required {
+            return Err(Error::InsufficientFunds);
+        }
+        let inputs: Vec<(TxID, u32)> = spent
+            .iter()
+            .map(|unspent| (unspent.txid.clone(), unspent.vout))
+            .collect();
+        let mut outputs = vec![(to_address.clone(), amount)];
+        let change = total - required;
+        if change > 0 {
+            outputs.push((from_address.clone(), change));
+        }
+        let hex = self.create_raw_transaction(&inputs, &outputs, self.replaceable)?;
+        Ok((hex, spent))
+    }
+
+    /// Build an unsigned transaction spends the coins `inputs` (txid, vout) to the `outputs`
+    /// (address, amount), the fee is what the outputs leave of the inputs
+    ///
+    /// A `replaceable` transaction signals BIP125, it can be replaced by one pays more fee
+    pub fn create_raw_transaction(
+        &self,
+        inputs: &[(TxID, u32)],
+        outputs: &[(Address, Amount)],
+        replaceable: bool,
+    ) -> Result<String, Error> {
+        let inputs: Vec<Value> = inputs
+            .iter()
+            .map(|(txid, vout)| json!({"txid": txid, "vout": vout}))
+            .collect();
+        let mut outputs_json = serde_json::Map::new();
+        for (address, amount) in outputs.iter() {
+            outputs_json.insert(address.clone(), json!(format_amount(*amount)));
+        }
+
+        let mut builder = rpc::RequestBuilder::new()
+            .set_method("createrawtransaction")
+            .add_param_value("inputs", Value::Array(inputs))
+            .add_param_value("outputs", Value::Object(outputs_json));
+        // the request of a final transaction is left as it was
+        if replaceable {
+            builder = builder.add_param_bool("replaceable", true);
+        }
+        let rpc_json = builder.build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result.as_str() {
+                Some(hex) => Ok(hex.to_owned()),
+                None => Err(Error::RpcError),
+            },
+            Err(e) => {
+                error!("cannot execute `createrawtransaction`, reason: {e}");
                 Err(Error::RpcError)
             }
         }
     }
 
-    pub fn transfer(&self, from_address: &Address, to_address: &Address, amount: Amount) -> Result<TxID, Error> {
-        // TODO matthew: start a new transaction to transfer the DePC to target address with
-        // correct amount
-        todo!("complete this method")
+    pub fn sign_raw_transaction(&self, hex: &str) -> Result<String, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("signrawtransactionwithwallet")
+            .add_param_string("hexstring", hex)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => {
+                let signed: SignedRawTransaction =
+                    serde_json::from_value(resp.result).map_err(|_| Error::RpcError)?;
+                if !signed.complete {
+                    return Err(Error::CannotSignTransaction);
+                }
+                Ok(signed.hex)
+            }
+            Err(e) => {
+                error!("cannot execute `signrawtransactionwithwallet`, reason: {e}");
+                Err(Error::CannotSignTransaction)
+            }
+        }
+    }
+
+    /// Sign the inputs of the transaction the wallet has the keys of, the coins of the P2SH
+    /// addresses are described by `prevtxs`. The transaction is partially signed unless
+    /// `complete` is set in the result, the other signers sign it next
+    pub fn sign_raw_transaction_with_prevtxs(
+        &self,
+        hex: &str,
+        prevtxs: &[PrevTx],
+    ) -> Result<SignedRawTransaction, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("signrawtransactionwithwallet")
+            .add_param_string("hexstring", hex)
+            .add_param_value("prevtxs", json!(prevtxs))
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => serde_json::from_value(resp.result)
+                .map_err(|_| Error::InvalidResponse("signrawtransactionwithwallet".to_owned())),
+            Err(e) => {
+                error!("cannot execute `signrawtransactionwithwallet`, reason: {e}");
+                Err(Error::CannotSignTransaction)
+            }
+        }
+    }
+
+    /// Merge the signatures of the copies of the same transaction signed by the different
+    /// signers, the node refuses the copies of the other transactions
+    pub fn combine_raw_transaction(&self, hexs: &[String]) -> Result<String, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("combinerawtransaction")
+            .add_param_value("txs", json!(hexs))
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result.as_str() {
+                Some(hex) => Ok(hex.to_owned()),
+                None => Err(Error::InvalidResponse("combinerawtransaction".to_owned())),
+            },
+            Err(e) => {
+                error!("cannot execute `combinerawtransaction`, reason: {e}");
+                match e.downcast_ref::<rpc::ServerError>() {
+                    Some(server_error) => Err(Error::CannotCombineTransactions(
+                        server_error.message.clone(),
+                    )),
+                    None => Err(Error::RpcError),
+                }
+            }
+        }
+    }
+
+    pub fn send_raw_transaction(&self, hex: &str) -> Result<TxID, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("sendrawtransaction")
+            .add_param_string("hexstring", hex)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result.as_str() {
+                Some(txid) => Ok(txid.to_owned()),
+                None => Err(Error::RpcError),
+            },
+            Err(e) => {
+                error!("cannot execute `sendrawtransaction`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    /// The txid of the raw transaction, it's known before the transaction is sent
+    pub fn decode_raw_transaction_txid(&self, hex: &str) -> Result<TxID, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("decoderawtransaction")
+            .add_param_string("hexstring", hex)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result["txid"].as_str() {
+                Some(txid) => Ok(txid.to_owned()),
+                None => Err(Error::InvalidResponse("decoderawtransaction".to_owned())),
+            },
+            Err(e) => {
+                error!("cannot execute `decoderawtransaction`, reason: {e}");
+                Err(make_rpc_error(e))
+            }
+        }
+    }
+
+    /// Sign `message` by the key of `address` in the wallet of the node, the signature is the
+    /// base64 string `verify_message` takes
+    pub fn sign_message(&self, address: &Address, message: &str) -> Result<String, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("signmessage")
+            .add_param_string("address", address)
+            .add_param_string("message", message)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => match resp.result.as_str() {
+                Some(signature) => Ok(signature.to_owned()),
+                None => Err(Error::InvalidResponse("signmessage".to_owned())),
+            },
+            Err(e) => {
+                error!("cannot execute `signmessage`, reason: {e}");
+                match e.downcast_ref::<rpc::ServerError>() {
+                    Some(_) => Err(Error::CannotSignMessage(address.clone())),
+                    None => Err(Error::RpcError),
+                }
+            }
+        }
+    }
+
+    /// Check `signature` is made by the key of `address` for `message`, the users prove they
+    /// control the address without moving the coins. A malformed address or signature the node
+    /// refuses is not verified either
+    pub fn verify_message(
+        &self,
+        address: &Address,
+        signature: &str,
+        message: &str,
+    ) -> Result<bool, Error> {
+        let rpc_json = rpc::RequestBuilder::new()
+            .set_method("verifymessage")
+            .add_param_string("address", address)
+            .add_param_string("signature", signature)
+            .add_param_string("message", message)
+            .build();
+        match rpc::Client::new(self.config.clone()).send(&rpc_json) {
+            Ok(resp) => resp
+                .result
+                .as_bool()
+                .ok_or_else(|| Error::InvalidResponse("verifymessage".to_owned())),
+            Err(e) if e.downcast_ref::<rpc::ServerError>().is_some() => Ok(false),
+            Err(e) => {
+                error!("cannot execute `verifymessage`, reason: {e}");
+                Err(Error::RpcError)
+            }
+        }
+    }
+
+    pub fn transfer(
+        &self,
+        from_address: &Address,
+        to_address: &Address,
+        amount: Amount,
+    ) -> Result<TxID, Error> {
+        let _spending = self.lock_spending();
+        let hex = self.make_transfer(from_address, to_address, amount)?;
+        let signed_hex = self.sign_raw_transaction(&hex)?;
+        self.send_raw_transaction(&signed_hex)
+    }
+
+    /// The same as `make_transfer`, the transaction is signed by the wallet and returned with
+    /// its txid, so it can be saved before it's sent. Hold the guard from `lock_spending` until
+    /// it's broadcast
+    pub fn make_signed_transfer(
+        &self,
+        from_address: &Address,
+        to_address: &Address,
+        amount: Amount,
+    ) -> Result<(TxID, String), Error> {
+        let hex = self.make_transfer(from_address, to_address, amount)?;
+        let signed_hex = self.sign_raw_transaction(&hex)?;
+        let txid = self.decode_raw_transaction_txid(&signed_hex)?;
+        Ok((txid, signed_hex))
+    }
+}
+
+/// The data pruned or missing on the node is told apart from the failures of the RPC service
+fn make_rpc_error(e: anyhow::Error) -> Error {
+    match e.downcast_ref::<rpc::ServerError>() {
+        Some(server_error) if server_error.is_data_unavailable() => {
+            Error::DataUnavailable(server_error.message.clone())
+        }
+        _ => Error::RpcError,
     }
 }
 
@@ -79,6 +584,10 @@ pub struct ClientBuilder {
     endpoint: String,
     use_proxy: bool,
     auth: Option<String>,
+    timeout: Option<Duration>,
+    replaceable: bool,
+    #[cfg(feature = "chaos")]
+    fault_injector: Option<crate::chaos::FaultInjector>,
 }
 
 impl ClientBuilder {
@@ -87,6 +596,10 @@ impl ClientBuilder {
             endpoint: "http://127.0.0.1:18732".to_owned(),
             use_proxy: false,
             auth: None,
+            timeout: None,
+            replaceable: false,
+            #[cfg(feature = "chaos")]
+            fault_injector: None,
         }
     }
 
@@ -105,6 +618,20 @@ impl ClientBuilder {
         self
     }
 
+    /// The calls are given up after `timeout`, they wait for the node as long as it takes by
+    /// default
+    pub fn set_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The transfers made by `make_transfer` signal replaceability (BIP125), so the stuck
+    /// payouts can be bumped by replacing them, see `bridge::bump_stuck_payouts`
+    pub fn set_replaceable(mut self, replaceable: bool) -> ClientBuilder {
+        self.replaceable = replaceable;
+        self
+    }
+
     pub fn set_auth_from_cookie(self, cookie_path: &str) -> ClientBuilder {
         let auth_str = fs::read_to_string(cookie_path).unwrap();
         self.set_auth(&auth_str)
@@ -120,13 +647,29 @@ impl ClientBuilder {
         self.set_auth_from_cookie(&cookie_path)
     }
 
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(
+        mut self,
+        fault_injector: crate::chaos::FaultInjector,
+    ) -> ClientBuilder {
+        self.fault_injector = Some(fault_injector);
+        self
+    }
+
     pub fn build(self) -> Client {
         Client {
             config: rpc::Config {
                 endpoint: self.endpoint,
                 use_proxy: self.use_proxy,
                 auth: self.auth,
+                timeout: self.timeout,
+                request_ids: Arc::default(),
+                #[cfg(feature = "chaos")]
+                fault_injector: self.fault_injector,
             },
+            replaceable: self.replaceable,
+            spend_lock: Arc::default(),
+            reserved_coins: Arc::default(),
         }
     }
 }
@@ -221,4 +764,4 @@ mod tests {
         }
         assert!(false);
     }
-}
\ No newline at end of file
+}
@@ -7,7 +7,28 @@ pub enum Error {
     InvalidScript,
     NotOPReturn,
     InvalidStringFromScript,
+    /// The recipient isn't encoded back to the same string, it's truncated or poisoned
+    NonCanonicalRecipient(String),
     NotErc20Address,
+    InsufficientFunds,
+    CannotSignTransaction,
+    /// The wallet of the node doesn't have the key of the address
+    CannotSignMessage(String),
+    InvalidResponse(String),
+    /// The block is pruned or the transaction is missing on the node
+    DataUnavailable(String),
+    /// The extended key cannot be parsed or the child cannot be derived from it
+    InvalidExtendedKey(String),
+    InvalidDerivationPath(String),
+    /// The wallet of the node refuses to import the address or the descriptor
+    CannotImport(String, String),
+    /// The multisig script of the custody is not valid
+    InvalidMultisig(String),
+    InvalidAddress(String, String),
+    /// The node refuses to merge the signed copies of a transaction
+    CannotCombineTransactions(String),
+    /// The copy of a multisig payout submitted by a co-signer is not signed by its key
+    InvalidSignedCopy(String),
 }
 
 impl fmt::Display for Error {
@@ -18,7 +39,46 @@ impl fmt::Display for Error {
             Error::InvalidScript => write!(f, "the script is invalid"),
             Error::NotOPReturn => write!(f, "the script is not started with OP_RETURN"),
             Error::InvalidStringFromScript => write!(f, "the stored string from script is invalid"),
+            Error::NonCanonicalRecipient(recipient) => {
+                write!(
+                    f,
+                    "the recipient `{}` is not a canonical address",
+                    recipient
+                )
+            }
             Error::NotErc20Address => write!(f, "cannot decode erc20 address from stored string"),
+            Error::InsufficientFunds => write!(f, "not enough funds to make the transaction"),
+            Error::CannotSignTransaction => write!(f, "cannot sign the transaction by wallet"),
+            Error::CannotSignMessage(address) => {
+                write!(f, "cannot sign the message by the key of {}", address)
+            }
+            Error::InvalidResponse(method) => {
+                write!(f, "the response of `{}` cannot be parsed", method)
+            }
+            Error::DataUnavailable(reason) => {
+                write!(f, "the data is pruned or missing on the node, {}", reason)
+            }
+            Error::InvalidExtendedKey(reason) => {
+                write!(f, "the extended key is invalid, {}", reason)
+            }
+            Error::InvalidDerivationPath(path) => {
+                write!(f, "the derivation path `{}` is invalid", path)
+            }
+            Error::CannotImport(subject, reason) => {
+                write!(f, "cannot import `{}` as watch-only, {}", subject, reason)
+            }
+            Error::InvalidMultisig(reason) => {
+                write!(f, "the multisig script is invalid, {}", reason)
+            }
+            Error::InvalidAddress(address, reason) => {
+                write!(f, "the address `{}` is invalid, {}", address, reason)
+            }
+            Error::CannotCombineTransactions(reason) => {
+                write!(f, "cannot combine the signed transactions, {}", reason)
+            }
+            Error::InvalidSignedCopy(reason) => {
+                write!(f, "the signed copy is invalid, {}", reason)
+            }
         }
     }
 }
@@ -1,9 +1,13 @@
 mod client;
 mod error;
+mod multisig;
 mod script;
 mod types;
+mod wallet;
 
 pub use client::*;
 pub use error::Error;
+pub use multisig::*;
 pub use script::*;
 pub use types::*;
+pub use wallet::*;
@@ -0,0 +1,341 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::script::{Builder, Instruction};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{ecdsa, NetworkKind, PublicKey, ScriptBuf, Transaction};
+
+use super::{Address, Error};
+
+/// The most keys of a multisig script redeemed by P2SH, the redeem script must not be longer
+/// than 520 bytes
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+/// The raw multisig script `m <pubkey>... n OP_CHECKMULTISIG` of a custody address, the
+/// address is the P2SH of it
+///
+/// The coins of the address are spent only when `required` of the keys sign, the wallet of the
+/// node holds one of them and the co-signers the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigScript {
+    required: usize,
+    pubkeys: Vec<PublicKey>,
+}
+
+impl MultisigScript {
+    /// Arguments:
+    /// * required - The signatures required to spend, `m` of the script
+    /// * pubkeys - The hex of the public keys, in the order of the script
+    pub fn new(required: usize, pubkeys: &[String]) -> Result<Self, Error> {
+        if pubkeys.is_empty() || pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(Error::InvalidMultisig(format!(
+                "{} keys, it's 1 to {MAX_MULTISIG_KEYS}",
+                pubkeys.len()
+            )));
+        }
+        if required == 0 || required > pubkeys.len() {
+            return Err(Error::InvalidMultisig(format!(
+                "{required} of {} keys are required",
+                pubkeys.len()
+            )));
+        }
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pubkey| {
+                PublicKey::from_str(pubkey)
+                    .map_err(|e| Error::InvalidMultisig(format!("key `{pubkey}`, {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pubkeys.iter().collect::<BTreeSet<_>>().len() != pubkeys.len() {
+            return Err(Error::InvalidMultisig("a key is repeated".to_owned()));
+        }
+        Ok(MultisigScript { required, pubkeys })
+    }
+
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    pub fn total(&self) -> usize {
+        self.pubkeys.len()
+    }
+
+    fn redeem_script(&self) -> ScriptBuf {
+        let mut builder = Builder::new().push_int(self.required as i64);
+        for pubkey in self.pubkeys.iter() {
+            builder = builder.push_key(pubkey);
+        }
+        builder
+            .push_int(self.pubkeys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    /// The hex of the redeem script, the signers need it with the spent coins
+    pub fn redeem_script_hex(&self) -> String {
+        hex::encode(self.redeem_script().as_bytes())
+    }
+
+    /// The hex of the P2SH script the coins of the custody address are locked by
+    pub fn script_pubkey_hex(&self) -> String {
+        let script = ScriptBuf::new_p2sh(&self.redeem_script().script_hash());
+        hex::encode(script.as_bytes())
+    }
+
+    /// The P2SH address of the script on `network`
+    pub fn address(&self, network: NetworkKind) -> Result<Address, Error> {
+        bitcoin::Address::p2sh(&self.redeem_script(), network)
+            .map(|address| address.to_string())
+            .map_err(|e| Error::InvalidMultisig(e.to_string()))
+    }
+
+    /// Whether `address` is the P2SH address of the script, on any network
+    pub fn is_address(&self, address: &str) -> bool {
+        address_script_pubkey_hex(address).is_ok_and(|hex| hex == self.script_pubkey_hex())
+    }
+
+    /// Check `signed_tx` is a copy of `raw_tx` signed by `pubkey`, the key must be one of the
+    /// script and each input of the copy must carry a valid signature of it
+    ///
+    /// The scripts of the inputs are not compared, only the rest of the transaction is.
+    pub fn verify_signed_copy(
+        &self,
+        raw_tx: &str,
+        signed_tx: &str,
+        pubkey: &str,
+    ) -> Result<(), Error> {
+        let pubkey = PublicKey::from_str(pubkey)
+            .map_err(|e| Error::InvalidSignedCopy(format!("key `{pubkey}`, {e}")))?;
+        if !self.pubkeys.contains(&pubkey) {
+            return Err(Error::InvalidSignedCopy(format!(
+                "key `{pubkey}` is not a key of the {self}"
+            )));
+        }
+        let tx = decode_transaction(raw_tx)?;
+        let signed = decode_transaction(signed_tx)?;
+        if signed.compute_ntxid() != tx.compute_ntxid() {
+            return Err(Error::InvalidSignedCopy(
+                "it's not a copy of the payout".to_owned(),
+            ));
+        }
+        let redeem_script = self.redeem_script();
+        let secp = Secp256k1::verification_only();
+        let cache = SighashCache::new(&signed);
+        for (index, input) in signed.input.iter().enumerate() {
+            let mut pushes = vec![];
+            for instruction in input.script_sig.instructions() {
+                match instruction {
+                    Ok(Instruction::PushBytes(bytes)) => pushes.push(bytes.as_bytes()),
+                    Ok(Instruction::Op(_)) => {}
+                    Err(e) => return Err(Error::InvalidSignedCopy(format!("input {index}, {e}"))),
+                }
+            }
+            // `OP_0 <signature>... <redeem script>`
+            if pushes.pop() != Some(redeem_script.as_bytes()) {
+                return Err(Error::InvalidSignedCopy(format!(
+                    "input {index} doesn't spend the {self}"
+                )));
+            }
+            let signed_by_key = pushes.into_iter().any(|bytes| {
+                let Ok(signature) = ecdsa::Signature::from_slice(bytes) else {
+                    return false;
+                };
+                cache
+                    .legacy_signature_hash(index, &redeem_script, signature.sighash_type.to_u32())
+                    .is_ok_and(|sighash| {
+                        let message = Message::from_digest(sighash.to_byte_array());
+                        secp.verify_ecdsa(&message, &signature.signature, &pubkey.inner)
+                            .is_ok()
+                    })
+            });
+            if !signed_by_key {
+                return Err(Error::InvalidSignedCopy(format!(
+                    "input {index} is not signed by key `{pubkey}`"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_transaction(raw_tx: &str) -> Result<Transaction, Error> {
+    let bytes = hex::decode(raw_tx).map_err(|_| Error::InvalidHex)?;
+    bitcoin::consensus::deserialize(&bytes).map_err(|e| Error::InvalidSignedCopy(e.to_string()))
+}
+
+impl fmt::Display for MultisigScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-of-{} multisig", self.required, self.pubkeys.len())
+    }
+}
+
+/// The hex of the script the coins paid to `address` are locked by
+pub fn address_script_pubkey_hex(address: &str) -> Result<String, Error> {
+    let address = bitcoin::Address::from_str(address)
+        .map_err(|e| Error::InvalidAddress(address.to_owned(), e.to_string()))?
+        .assume_checked();
+    Ok(hex::encode(address.script_pubkey().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::script::PushBytesBuf;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{absolute, transaction, Amount, EcdsaSighashType, TxIn, TxOut};
+
+    use super::*;
+
+    /// The compressed keys of `G`, `2G` and `3G`
+    const TEST_PUBKEYS: [&str; 3] = [
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    ];
+
+    fn test_pubkeys() -> Vec<String> {
+        TEST_PUBKEYS
+            .iter()
+            .map(|pubkey| pubkey.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_multisig_script() {
+        let multisig = MultisigScript::new(2, &test_pubkeys()).unwrap();
+        assert_eq!(multisig.to_string(), "2-of-3 multisig");
+        assert_eq!(
+            multisig.redeem_script_hex(),
+            format!(
+                "52{}53ae",
+                TEST_PUBKEYS.map(|pubkey| format!("21{pubkey}")).concat()
+            )
+        );
+        assert_eq!(
+            multisig.script_pubkey_hex(),
+            "a91415fc0754e73eb85d1cbce08786fadb7320ecb8dc87"
+        );
+        assert_eq!(
+            multisig.address(NetworkKind::Test).unwrap(),
+            "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS"
+        );
+        assert_eq!(
+            multisig.address(NetworkKind::Main).unwrap(),
+            "33hG2q39jRi2NqicRJB4ggY1J8EJm97Szz"
+        );
+        assert!(multisig.is_address("2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS"));
+        assert!(!multisig.is_address("2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ"));
+        assert!(!multisig.is_address("not an address"));
+        assert_eq!(
+            address_script_pubkey_hex("2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS").unwrap(),
+            multisig.script_pubkey_hex()
+        );
+
+        // the order of the keys makes another script
+        let mut reversed = test_pubkeys();
+        reversed.reverse();
+        assert!(!MultisigScript::new(2, &reversed)
+            .unwrap()
+            .is_address("2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS"));
+    }
+
+    /// The payout spending two coins of the script, the inputs are not signed
+    fn make_test_payout(multisig: &MultisigScript) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(100000),
+                script_pubkey: ScriptBuf::new_p2sh(&multisig.redeem_script().script_hash()),
+            }],
+        }
+    }
+
+    /// Sign each input of `tx` by the secret keys `keys`, the secret key `n` is of the key `nG`
+    fn sign_test_payout(multisig: &MultisigScript, tx: &Transaction, keys: &[u8]) -> String {
+        let secp = Secp256k1::new();
+        let redeem_script = multisig.redeem_script();
+        let mut signed = tx.clone();
+        for index in 0..tx.input.len() {
+            let sighash = SighashCache::new(tx)
+                .legacy_signature_hash(index, &redeem_script, EcdsaSighashType::All.to_u32())
+                .unwrap();
+            let message = Message::from_digest(sighash.to_byte_array());
+            let mut builder = Builder::new().push_int(0);
+            for key in keys {
+                let mut secret = [0; 32];
+                secret[31] = *key;
+                let signature = ecdsa::Signature {
+                    signature: secp.sign_ecdsa(&message, &SecretKey::from_slice(&secret).unwrap()),
+                    sighash_type: EcdsaSighashType::All,
+                };
+                builder = builder.push_slice(PushBytesBuf::try_from(signature.to_vec()).unwrap());
+            }
+            let redeem_script = PushBytesBuf::try_from(redeem_script.to_bytes()).unwrap();
+            signed.input[index].script_sig = builder.push_slice(redeem_script).into_script();
+        }
+        bitcoin::consensus::encode::serialize_hex(&signed)
+    }
+
+    #[test]
+    fn test_verify_signed_copy() {
+        let multisig = MultisigScript::new(2, &test_pubkeys()).unwrap();
+        let payout = make_test_payout(&multisig);
+        let raw_tx = bitcoin::consensus::encode::serialize_hex(&payout);
+        let signed = sign_test_payout(&multisig, &payout, &[1, 2]);
+        multisig
+            .verify_signed_copy(&raw_tx, &signed, TEST_PUBKEYS[0])
+            .unwrap();
+        multisig
+            .verify_signed_copy(&raw_tx, &signed, TEST_PUBKEYS[1])
+            .unwrap();
+
+        // the copy is not signed by the third key
+        assert!(matches!(
+            multisig.verify_signed_copy(&raw_tx, &signed, TEST_PUBKEYS[2]),
+            Err(Error::InvalidSignedCopy(_))
+        ));
+        // the key of `4G` is not a key of the script
+        let mut secret = [0; 32];
+        secret[31] = 4;
+        let other = PublicKey::new(
+            SecretKey::from_slice(&secret)
+                .unwrap()
+                .public_key(&Secp256k1::new()),
+        );
+        assert!(matches!(
+            multisig.verify_signed_copy(&raw_tx, &signed, &other.to_string()),
+            Err(Error::InvalidSignedCopy(_))
+        ));
+        // the unsigned payout carries no signature
+        assert!(multisig
+            .verify_signed_copy(&raw_tx, &raw_tx, TEST_PUBKEYS[0])
+            .is_err());
+        // the outputs of the copy are changed
+        let mut changed = payout.clone();
+        changed.output[0].value = Amount::from_sat(1);
+        let signed = sign_test_payout(&multisig, &changed, &[1]);
+        assert!(matches!(
+            multisig.verify_signed_copy(&raw_tx, &signed, TEST_PUBKEYS[0]),
+            Err(Error::InvalidSignedCopy(_))
+        ));
+        assert!(matches!(
+            multisig.verify_signed_copy(&raw_tx, "not hex", TEST_PUBKEYS[0]),
+            Err(Error::InvalidHex)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_multisig_script() {
+        let pubkeys = test_pubkeys();
+        assert!(MultisigScript::new(0, &pubkeys).is_err());
+        assert!(MultisigScript::new(4, &pubkeys).is_err());
+        assert!(MultisigScript::new(1, &[]).is_err());
+        assert!(MultisigScript::new(2, &[pubkeys[0].clone(), "02ab".to_owned()]).is_err());
+        assert!(MultisigScript::new(2, &[pubkeys[0].clone(), pubkeys[0].clone()]).is_err());
+    }
+}
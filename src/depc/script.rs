@@ -1,29 +1,98 @@
+use std::str::FromStr;
+
+use bitcoin::base58;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
 use super::{Address, Error};
 use crate::bridge::DepcScriptData;
 
+/// The most Solana transactions a withdrawal can be made of
+pub const MAX_WITHDRAW_SIGNATURES: usize = 8;
+/// The length of a DePC address without the checksum, the version byte and the hash160
+const DEPC_ADDRESS_PAYLOAD_LEN: usize = 21;
+
+/// Extract the bridge data from an OP_RETURN script, the deposit only contains the solana
+/// recipient, the withdrawal contains the DePC recipient and the signature of the transaction
+/// made on solana chain
 pub fn extract_string_from_script_hex(hex_str: &str) -> Result<DepcScriptData<Address>, Error> {
-    //TODO:2. As shown in Figures 2 and 3, implement extract_string_from_script_hex to return in the format of the struct DepcScriptData. The deposit direction only includes the recipient (which is the Solana receiving address specified by the user), while the withdraw direction includes both the recipient and the signature (which is a special request transaction initiated by the user on the DePINC chain with an amount of 0, including the signature of the new transaction on the Solana chain and the target address for withdrawal on the DePINC chain)."
-    let data = match hex::decode(hex_str) {
-        Ok(r) => r,
-        Err(_) => {
-            return Err(Error::InvalidHex);
+    decode_script_hex(hex_str).map_err(|e| e.error)
+}
+
+/// Why a script cannot be decoded and where, `offset` is the byte of the script the decoding
+/// stops at (the hex has 2 digits a byte)
+#[derive(Debug)]
+pub struct ScriptDecodeError {
+    pub error: Error,
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl ScriptDecodeError {
+    fn new(error: Error, offset: usize, reason: impl Into<String>) -> ScriptDecodeError {
+        ScriptDecodeError {
+            error,
+            offset,
+            reason: reason.into(),
         }
-    };
+    }
+}
+
+impl std::fmt::Display for ScriptDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}, {}", self.error, self.offset, self.reason)
+    }
+}
+
+/// The same as `extract_string_from_script_hex`, but the error tells the byte the decoding
+/// stops at, e.g. to find out why a deposit isn't detected
+pub fn decode_script_hex(hex_str: &str) -> Result<DepcScriptData<Address>, ScriptDecodeError> {
+    let data = hex::decode(hex_str).map_err(|e| {
+        let offset = match e {
+            hex::FromHexError::InvalidHexCharacter { index, .. } => index / 2,
+            _ => hex_str.len() / 2,
+        };
+        ScriptDecodeError::new(Error::InvalidHex, offset, e.to_string())
+    })?;
 
     // check the first byte is OP_RETURN
-    const DEFAULT_OPCODE: u8 = 0;
-    let opcode = data.get(0).unwrap_or(&DEFAULT_OPCODE);
-    if *opcode != OP_RETURN {
-        return Err(Error::NotOPReturn);
+    match data.first() {
+        Some(&OP_RETURN) => {}
+        Some(opcode) => {
+            return Err(ScriptDecodeError::new(
+                Error::NotOPReturn,
+                0,
+                format!("the first opcode is {opcode:#04x}"),
+            ));
+        }
+        None => return Err(ScriptDecodeError::new(Error::NotOPReturn, 0, "it's empty")),
     }
 
-    // now decode and check the size of the content
-    let size = u32::from_le_bytes(data[2..=5].try_into().unwrap()) as usize;
-    if size - 1 != data.len() - 6 {
-        return Err(Error::InvalidScript);
+    // now decode and check the size of the content, all the slicing is checked because the
+    // script comes from the chain and can be anything
+    let size = match data.get(2..6).and_then(|bytes| bytes.try_into().ok()) {
+        Some(bytes) => u32::from_le_bytes(bytes) as usize,
+        None => {
+            return Err(ScriptDecodeError::new(
+                Error::InvalidScript,
+                data.len(),
+                "it ends before the 4 bytes of the size at byte 2",
+            ));
+        }
+    };
+    if size.checked_sub(1) != Some(data.len() - 6) {
+        return Err(ScriptDecodeError::new(
+            Error::InvalidScript,
+            2,
+            format!(
+                "the size is {size}, but it should be {} for the {} bytes after it",
+                data.len() - 5,
+                data.len() - 6
+            ),
+        ));
     }
 
-    Ok(decode_script_after_op_return(&data[6..])?)
+    decode_script_after_op_return(&data[6..], 6)
 }
 
 const OP_RETURN: u8 = 0x6au8;
@@ -31,55 +100,356 @@ const OP_PUSHDATA1: u8 = 0x4cu8;
 const OP_PUSHDATA2: u8 = 0x4du8;
 const OP_PUSHDATA4: u8 = 0x4eu8;
 
-fn decode_script_after_op_return(script: &[u8]) -> Result<DepcScriptData<Address>, Error> {
-    let opcode = *match script.get(0) {
-        Some(c) => c,
-        None => {
-            return Err(Error::InvalidScript);
+/// `base` is the offset of `script` in the whole script
+fn decode_script_after_op_return(
+    script: &[u8],
+    base: usize,
+) -> Result<DepcScriptData<Address>, ScriptDecodeError> {
+    let invalid = |offset: usize, reason: String| {
+        ScriptDecodeError::new(Error::InvalidScript, base + offset, reason)
+    };
+    let Some(&opcode) = script.first() else {
+        return Err(invalid(0, "nothing is pushed after the size".to_owned()));
+    };
+    let (size, start_index) = match opcode {
+        n if n < OP_PUSHDATA1 => (n as usize, 1),
+        OP_PUSHDATA1 => (read_size(script, 1, base)?, 2),
+        OP_PUSHDATA2 => (read_size(script, 2, base)?, 3),
+        OP_PUSHDATA4 => (read_size(script, 4, base)?, 5),
+        _ => {
+            return Err(invalid(0, format!("opcode {opcode:#04x} is not a push")));
         }
     };
-    let mut size = 1usize;
-    let mut start_index = 1usize;
-    if opcode < OP_PUSHDATA1 {
-        size = opcode as usize;
-        start_index = 1;
-    } else if opcode == OP_PUSHDATA1 {
-        size = match script.get(1) {
-            Some(n) => *n as usize,
-            None => {
-                return Err(Error::InvalidScript);
+    // ensure the length of slice equals to the number of size which is calculated from above
+    let slice = &script[start_index..];
+    if slice.len() != size {
+        return Err(invalid(
+            start_index,
+            format!("{size} bytes are pushed, but {} bytes follow", slice.len()),
+        ));
+    }
+    let content = std::str::from_utf8(slice).map_err(|e| {
+        ScriptDecodeError::new(
+            Error::InvalidStringFromScript,
+            base + start_index + e.valid_up_to(),
+            "the pushed bytes are not utf-8",
+        )
+    })?;
+    decode_script_content(content).map_err(|(offset, error, reason)| {
+        ScriptDecodeError::new(error, base + start_index + offset, reason)
+    })
+}
+
+/// Read the little-endian size of `OP_PUSHDATA1/2/4` which follows the opcode
+fn read_size(script: &[u8], num_bytes: usize, base: usize) -> Result<usize, ScriptDecodeError> {
+    match script.get(1..1 + num_bytes) {
+        Some(bytes) => Ok(bytes
+            .iter()
+            .rev()
+            .fold(0usize, |size, byte| (size << 8) | *byte as usize)),
+        None => Err(ScriptDecodeError::new(
+            Error::InvalidScript,
+            base + script.len(),
+            format!("it ends before the {num_bytes} bytes of the pushed size"),
+        )),
+    }
+}
+
+/// The content is `<recipient>` for a deposit, and `<recipient>:<signature>[,<signature>...]`
+/// for a withdrawal, the tokens of a withdrawal can be transferred by up to
+/// `MAX_WITHDRAW_SIGNATURES` different transactions
+///
+/// The error comes with the offset of the part in the content and the reason.
+fn decode_script_content(content: &str) -> Result<DepcScriptData<Address>, (usize, Error, String)> {
+    if let Some((recipient, signatures)) = content.split_once(':') {
+        let mut parsed: Vec<Signature> = vec![];
+        let mut offset = recipient.len() + 1;
+        for raw in signatures.split(',') {
+            let invalid = |reason: String| (offset, Error::InvalidStringFromScript, reason);
+            let signature = Signature::from_str(raw)
+                .map_err(|_| invalid(format!("`{raw}` is not a signature")))?;
+            if parsed.contains(&signature) {
+                return Err(invalid(format!("signature {signature} is duplicated")));
+            }
+            if parsed.len() == MAX_WITHDRAW_SIGNATURES {
+                return Err(invalid(format!(
+                    "more than {MAX_WITHDRAW_SIGNATURES} signatures"
+                )));
             }
+            offset += raw.len() + 1;
+            parsed.push(signature);
+        }
+        check_depc_recipient(recipient).map_err(|e| {
+            let reason = "the recipient of a withdrawal is a DePC address".to_owned();
+            (0, e, reason)
+        })?;
+        Ok(DepcScriptData {
+            recipient: recipient.to_owned(),
+            signatures: parsed,
+        })
+    } else {
+        check_solana_recipient(content).map_err(|e| {
+            let reason = "the recipient of a deposit is a Solana address".to_owned();
+            (0, e, reason)
+        })?;
+        Ok(DepcScriptData {
+            recipient: content.to_owned(),
+            signatures: vec![],
+        })
+    }
+}
+
+/// The recipients are decoded and encoded again, a recipient which isn't exactly the same
+/// string (a truncated address, a look-alike character) is rejected rather than paid to
+/// whatever it's decoded to
+fn check_solana_recipient(recipient: &str) -> Result<(), Error> {
+    match Pubkey::from_str(recipient) {
+        Ok(pubkey) if pubkey.to_string() == recipient => Ok(()),
+        _ => Err(Error::NonCanonicalRecipient(recipient.to_owned())),
+    }
+}
+
+fn check_depc_recipient(recipient: &str) -> Result<(), Error> {
+    match base58::decode_check(recipient) {
+        Ok(payload)
+            if payload.len() == DEPC_ADDRESS_PAYLOAD_LEN
+                && base58::encode_check(&payload) == recipient =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::NonCanonicalRecipient(recipient.to_owned())),
+    }
+}
+
+/// Make the script hex which can be decoded by `extract_string_from_script_hex`
+#[cfg(any(test, feature = "testing"))]
+pub fn make_script_hex(content: &str) -> String {
+    let mut pushed = vec![];
+    let len = content.len();
+    if len < OP_PUSHDATA1 as usize {
+        pushed.push(len as u8);
+    } else if len <= u8::MAX as usize {
+        pushed.push(OP_PUSHDATA1);
+        pushed.push(len as u8);
+    } else {
+        pushed.push(OP_PUSHDATA2);
+        pushed.extend_from_slice(&(len as u16).to_le_bytes());
+    }
+    pushed.extend_from_slice(content.as_bytes());
+
+    let mut data = vec![OP_RETURN, 0x04];
+    data.extend_from_slice(&(pushed.len() as u32 + 1).to_le_bytes());
+    data.extend_from_slice(&pushed);
+    hex::encode(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+
+    const TEST_SOLANA_ADDRESS: &str = "7My8xLpS8Nuao32SZ3PsiU9jERNuoWDBtQDrtTKb3guY";
+    const TEST_DEPC_ADDRESS: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_SIGNATURE: &str =
+        "25A1pSwLHvagx8FD3oyAGot1Kfp9keqFhdfGgDZq4s9xjkPc4h5R3P6ikf5ookcsKuZEJDcFShsa3JdgVXYbmgRx";
+
+    #[test]
+    fn test_extract_deposit_script() {
+        let script_hex = make_script_hex(TEST_SOLANA_ADDRESS);
+        let script_data = extract_string_from_script_hex(&script_hex).unwrap();
+        assert_eq!(script_data.recipient, TEST_SOLANA_ADDRESS);
+        assert!(script_data.signatures.is_empty());
+    }
+
+    #[test]
+    fn test_extract_withdraw_script() {
+        let script_hex = make_script_hex(&format!("{TEST_DEPC_ADDRESS}:{TEST_SIGNATURE}"));
+        let script_data = extract_string_from_script_hex(&script_hex).unwrap();
+        assert_eq!(script_data.recipient, TEST_DEPC_ADDRESS);
+        assert_eq!(
+            script_data.signatures,
+            vec![Signature::from_str(TEST_SIGNATURE).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_extract_withdraw_script_of_multiple_signatures() {
+        let signatures: Vec<Signature> = (0..MAX_WITHDRAW_SIGNATURES + 1)
+            .map(|_| Signature::new_unique())
+            .collect();
+        let content = |signatures: &[Signature]| {
+            let signatures: Vec<String> = signatures.iter().map(|s| s.to_string()).collect();
+            make_script_hex(&format!("{TEST_DEPC_ADDRESS}:{}", signatures.join(",")))
         };
-        start_index = 2;
-    } else if opcode == OP_PUSHDATA2 {
-        if script.len() < 3 {
-            return Err(Error::InvalidScript);
+        let script_data =
+            extract_string_from_script_hex(&content(&signatures[..MAX_WITHDRAW_SIGNATURES]))
+                .unwrap();
+        assert_eq!(script_data.recipient, TEST_DEPC_ADDRESS);
+        assert_eq!(
+            script_data.signatures,
+            signatures[..MAX_WITHDRAW_SIGNATURES]
+        );
+
+        // too many or duplicated signatures
+        for signatures in [
+            signatures.clone(),
+            vec![signatures[0], signatures[1], signatures[0]],
+        ] {
+            assert!(matches!(
+                extract_string_from_script_hex(&content(&signatures)),
+                Err(Error::InvalidStringFromScript)
+            ));
         }
-        let slice = &script[1..=2];
-        size = u16::from_le_bytes(slice.try_into().unwrap()) as usize;
-        start_index = 3;
-    } else if opcode == OP_PUSHDATA4 {
-        if script.len() < 5 {
-            return Err(Error::InvalidScript);
+    }
+
+    #[test]
+    fn test_extract_withdraw_script_invalid_signature() {
+        let script_hex = make_script_hex(&format!("{TEST_DEPC_ADDRESS}:not-a-signature"));
+        assert!(matches!(
+            extract_string_from_script_hex(&script_hex),
+            Err(Error::InvalidStringFromScript)
+        ));
+    }
+
+    #[test]
+    fn test_extract_non_canonical_recipient() {
+        let last = TEST_SOLANA_ADDRESS.len() - 1;
+        let depc_last = TEST_DEPC_ADDRESS.len() - 1;
+        for content in [
+            // truncated
+            TEST_SOLANA_ADDRESS[..last].to_owned(),
+            format!("{}:{TEST_SIGNATURE}", &TEST_DEPC_ADDRESS[..depc_last]),
+            // a look-alike character or the spaces around
+            TEST_SOLANA_ADDRESS.replacen('M', "\u{41c}", 1),
+            format!(" {TEST_SOLANA_ADDRESS}"),
+            format!("{TEST_DEPC_ADDRESS} :{TEST_SIGNATURE}"),
+            // the checksum doesn't match
+            format!("{}2:{TEST_SIGNATURE}", &TEST_DEPC_ADDRESS[..depc_last]),
+            // a Solana address as the recipient of a withdrawal
+            format!("{TEST_SOLANA_ADDRESS}:{TEST_SIGNATURE}"),
+        ] {
+            assert!(
+                matches!(
+                    extract_string_from_script_hex(&make_script_hex(&content)),
+                    Err(Error::NonCanonicalRecipient(recipient)) if content.starts_with(&recipient)
+                ),
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_truncated_header() {
+        assert!(matches!(
+            extract_string_from_script_hex("6a04"),
+            Err(Error::InvalidScript)
+        ));
+        // size is zero
+        assert!(matches!(
+            extract_string_from_script_hex("6a0400000000"),
+            Err(Error::InvalidScript)
+        ));
+    }
+
+    #[test]
+    fn test_extract_truncated_pushdata() {
+        // OP_PUSHDATA2 without the size bytes
+        assert!(matches!(
+            extract_string_from_script_hex("6a04020000004d"),
+            Err(Error::InvalidScript)
+        ));
+        // OP_PUSHDATA1 claims 5 bytes but there are 2
+        assert!(matches!(
+            extract_string_from_script_hex("6a04050000004c056162"),
+            Err(Error::InvalidScript)
+        ));
+    }
+
+    #[test]
+    fn test_decode_error_offsets() {
+        for (script_hex, offset) in [
+            ("6a0x", 1),
+            ("76a914", 0),
+            ("6a04", 2),
+            // nothing is pushed, or the size doesn't match
+            ("6a0401000000", 6),
+            ("6a0405000000", 2),
+            // OP_PUSHDATA2 without the size bytes
+            ("6a04020000004d", 7),
+            // OP_PUSHDATA1 claims 5 bytes but there are 2
+            ("6a04050000004c056162", 8),
+        ] {
+            let e = decode_script_hex(script_hex).unwrap_err();
+            assert_eq!(e.offset, offset, "{script_hex}: {e}");
+        }
+
+        // the second signature is bad
+        let content = format!("{TEST_DEPC_ADDRESS}:{TEST_SIGNATURE},bad");
+        let e = decode_script_hex(&make_script_hex(&content)).unwrap_err();
+        assert!(matches!(e.error, Error::InvalidStringFromScript));
+        // the envelope, OP_PUSHDATA1 and the size of the push
+        assert_eq!(e.offset, 8 + content.find("bad").unwrap());
+        assert!(e.to_string().contains("`bad` is not a signature"), "{e}");
+
+        let e = decode_script_hex(&make_script_hex(&TEST_SOLANA_ADDRESS[1..])).unwrap_err();
+        assert!(matches!(e.error, Error::NonCanonicalRecipient(_)));
+        assert_eq!(e.offset, 7);
+    }
+
+    #[test]
+    fn test_extract_not_op_return() {
+        assert!(matches!(
+            extract_string_from_script_hex("76a914"),
+            Err(Error::NotOPReturn)
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_extract_never_panics_on_any_string(s in ".*") {
+            let _ = extract_string_from_script_hex(&s);
+        }
+
+        #[test]
+        fn prop_extract_never_panics_on_any_bytes(data in vec(any::<u8>(), 0..512)) {
+            let _ = extract_string_from_script_hex(&hex::encode(data));
+        }
+
+        #[test]
+        fn prop_extract_never_panics_on_any_pushed_data(body in vec(any::<u8>(), 0..512)) {
+            // a valid envelope, so the decoder after OP_RETURN sees arbitrary bytes
+            let mut data = vec![OP_RETURN, 0x04];
+            data.extend_from_slice(&(body.len() as u32 + 1).to_le_bytes());
+            data.extend_from_slice(&body);
+            let _ = extract_string_from_script_hex(&hex::encode(data));
+        }
+
+        #[test]
+        fn prop_deposit_round_trip(bytes in any::<[u8; 32]>()) {
+            let recipient = Pubkey::new_from_array(bytes).to_string();
+            let script_data = extract_string_from_script_hex(&make_script_hex(&recipient)).unwrap();
+            prop_assert_eq!(script_data.recipient, recipient);
+            prop_assert!(script_data.signatures.is_empty());
+        }
+
+        #[test]
+        fn prop_non_canonical_deposit_is_rejected(recipient in "[1-9A-HJ-NP-Za-km-z]{1,300}") {
+            let canonical = Pubkey::from_str(&recipient)
+                .is_ok_and(|pubkey| pubkey.to_string() == recipient);
+            let extracted = extract_string_from_script_hex(&make_script_hex(&recipient));
+            prop_assert_eq!(extracted.is_ok(), canonical);
+        }
+
+        #[test]
+        fn prop_truncated_script_is_rejected(
+            recipient in "[1-9A-HJ-NP-Za-km-z]{1,300}",
+            num_bytes in 1usize..32,
+        ) {
+            let script_hex = make_script_hex(&recipient);
+            let num_bytes = num_bytes.min(script_hex.len() / 2);
+            let truncated = &script_hex[..script_hex.len() - 2 * num_bytes];
+            prop_assert!(extract_string_from_script_hex(truncated).is_err());
         }
-        let slice = &script[1..=4];
-        size = u32::from_le_bytes(slice.try_into().unwrap()) as usize;
-        start_index = 5;
     }
-    // ensure the length of slice equals to the number of size which is calculated from above
-    let slice = &script[start_index..];
-    assert_eq!(slice.len(), size);
-    // Ok(match std::str::from_utf8(&slice) {
-    //     Ok(s) => s,
-    //     Err(_) => {
-    //         return Err(Error::InvalidStringFromScript);
-    //     }
-    // })
-    let script: DepcScriptData<Address>;
-
-    script = DepcScriptData {
-        recipient: "".parse().unwrap(),
-        signature: "".parse().unwrap(),
-    };
-    Ok(script)
 }
@@ -1,9 +1,26 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub type Address = String;
 pub type Amount = u64;
 pub type TxID = String;
 
+/// The number of the smallest units in 1 DePC
+pub const COIN: Amount = 100_000_000;
+
+/// The most sequence number of an input signals the transaction replaceable (BIP125)
+pub const MAX_BIP125_RBF_SEQUENCE: u32 = 0xfffffffd;
+
+/// Convert an amount into the decimal string representation accepted by the DePC RPC, e.g.
+/// `1.50000000`, using a string avoids the precision issues of floating numbers
+pub fn format_amount(amount: Amount) -> String {
+    format!("{}.{:08}", amount / COIN, amount % COIN)
+}
+
+/// Convert a floating amount returned by RPC (in DePC) into the smallest units
+pub fn parse_amount(value: f64) -> Amount {
+    (value * COIN as f64).round() as Amount
+}
+
 #[derive(Deserialize)]
 pub struct Block {
     pub hash: String,
@@ -11,6 +28,9 @@ pub struct Block {
     pub miner: String,
     pub time: u64,
     pub tx: Vec<String>,
+    /// The hash of the block before it, the genesis block has none
+    #[serde(rename = "previousblockhash")]
+    pub prev_hash: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -24,12 +44,19 @@ pub struct In {
     pub coinbase: Option<String>,
     pub txid: Option<String>,
     pub vout: Option<u32>,
+    pub sequence: Option<u32>,
 }
 
 impl In {
     pub fn is_coinbase(&self) -> bool {
         self.coinbase.is_some()
     }
+
+    /// Whether the input signals the transaction replaceable (BIP125)
+    pub fn signals_rbf(&self) -> bool {
+        self.sequence
+            .is_some_and(|sequence| sequence <= MAX_BIP125_RBF_SEQUENCE)
+    }
 }
 
 #[derive(Deserialize)]
@@ -59,3 +86,124 @@ pub struct Transaction {
     pub vin: Vec<In>,
     pub vout: Vec<Out>,
 }
+
+impl Transaction {
+    /// A transaction signals replaceability by any of its inputs, it can be replaced by one
+    /// pays more fee before it's confirmed
+    pub fn signals_rbf(&self) -> bool {
+        self.vin.iter().any(|txin| txin.signals_rbf())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Unspent {
+    pub txid: String,
+    pub vout: u32,
+    pub address: Option<String>,
+    pub amount: f64,
+    pub confirmations: u32,
+}
+
+impl Unspent {
+    pub fn value64(&self) -> Amount {
+        parse_amount(self.amount)
+    }
+}
+
+/// The result of `getdescriptorinfo`, the descriptor comes with its checksum
+#[derive(Deserialize)]
+pub struct DescriptorInfo {
+    pub descriptor: String,
+}
+
+/// A result of `importdescriptors`, one for each imported descriptor
+#[derive(Deserialize)]
+pub struct ImportDescriptorResult {
+    pub success: bool,
+    pub error: Option<ImportDescriptorError>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportDescriptorError {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct SignedRawTransaction {
+    pub hex: String,
+    pub complete: bool,
+}
+
+/// A coin spent by a raw transaction, the wallet signs the coins of the P2SH addresses it
+/// doesn't watch with it, see `Client::sign_raw_transaction_with_prevtxs`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrevTx {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: String,
+    #[serde(rename = "redeemScript")]
+    pub redeem_script: String,
+    /// The amount in the decimal string, see `format_amount`
+    pub amount: String,
+}
+
+/// The result of `validateaddress`, an address of the other network is invalid
+#[derive(Deserialize)]
+pub struct ValidatedAddress {
+    #[serde(rename = "isvalid")]
+    pub is_valid: bool,
+    pub address: Option<String>,
+    /// Whether the wallet of the node has the private key of the address
+    #[serde(rename = "ismine")]
+    pub is_mine: Option<bool>,
+    #[serde(rename = "iswatchonly")]
+    pub is_watch_only: Option<bool>,
+}
+
+/// The result of `getnetworkinfo`
+#[derive(Deserialize)]
+pub struct NetworkInfo {
+    pub version: u64,
+    pub subversion: String,
+    #[serde(rename = "protocolversion")]
+    pub protocol_version: u64,
+    /// The number of the connected peers
+    pub connections: u32,
+}
+
+/// The result of `getblockchaininfo`
+#[derive(Deserialize)]
+pub struct BlockchainInfo {
+    /// `main`, `test` or `regtest`
+    pub chain: String,
+    pub blocks: u32,
+    pub headers: u32,
+    #[serde(rename = "bestblockhash")]
+    pub best_block_hash: String,
+    /// The node is still catching up with the network, the blocks are not final
+    #[serde(rename = "initialblockdownload")]
+    pub initial_block_download: bool,
+    #[serde(rename = "verificationprogress")]
+    pub verification_progress: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0), "0.00000000");
+        assert_eq!(format_amount(1), "0.00000001");
+        assert_eq!(format_amount(150_000_000), "1.50000000");
+        assert_eq!(format_amount(83_000_000 * COIN), "83000000.00000000");
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount(0.00000001), 1);
+        assert_eq!(parse_amount(1.5), 150_000_000);
+        assert_eq!(parse_amount(25.0), 25 * COIN);
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::bip32::{ChildNumber, Xpriv, Xpub};
+use bitcoin::secp256k1::Secp256k1;
+
+use super::{Address, Error};
+
+/// The chain of the receiving addresses of an account
+pub const EXTERNAL_CHAIN: u32 = 0;
+/// The chain of the change addresses of an account
+pub const INTERNAL_CHAIN: u32 = 1;
+/// The unused addresses in a row a scan stops after, as the wallets of BIP44 do
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// The purpose of the BIP44 paths, `m/44'/coin'/account'/chain/index`
+const BIP44_PURPOSE: u32 = 44;
+
+/// The path of an address below the account key, `chain/index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerivationPath {
+    pub chain: u32,
+    pub index: u32,
+}
+
+impl DerivationPath {
+    pub fn new(chain: u32, index: u32) -> Self {
+        DerivationPath { chain, index }
+    }
+
+    /// The path of the receiving address `index`
+    pub fn external(index: u32) -> Self {
+        DerivationPath::new(EXTERNAL_CHAIN, index)
+    }
+
+    fn to_children(self) -> Result<[ChildNumber; 2], Error> {
+        Ok([normal_child(self.chain)?, normal_child(self.index)?])
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidDerivationPath(s.to_owned());
+        let (chain, index) = s.split_once('/').ok_or_else(invalid)?;
+        Ok(DerivationPath {
+            chain: chain.parse().map_err(|_| invalid())?,
+            index: index.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.chain, self.index)
+    }
+}
+
+/// The extended public key of an account of a DePC HD wallet (`m/44'/coin'/account'`), the
+/// receiving addresses are derived from it without the private keys
+///
+/// The addresses are P2PKH of the network of the key, `xpub` for the mainnet and `tpub` for
+/// the testnet and the regtest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPubKey(Xpub);
+
+impl ExtendedPubKey {
+    /// The receiving address `index` of the account, `.../0/index`
+    pub fn derive_address(&self, index: u32) -> Result<Address, Error> {
+        self.derive_address_at(DerivationPath::external(index))
+    }
+
+    /// The address of `path` below the account
+    pub fn derive_address_at(&self, path: DerivationPath) -> Result<Address, Error> {
+        let xpub = self
+            .0
+            .derive_pub(&Secp256k1::verification_only(), &path.to_children()?)
+            .map_err(|e| Error::InvalidExtendedKey(e.to_string()))?;
+        Ok(bitcoin::Address::p2pkh(xpub.to_pub().pubkey_hash(), xpub.network).to_string())
+    }
+
+    /// Scan the addresses of `chain` from index 0 until `gap_limit` addresses in a row are not
+    /// used, the used ones are returned with their paths
+    ///
+    /// A wallet restored from the keys finds the coins the same way, so the addresses after a
+    /// longer gap are not found by it.
+    pub fn scan_addresses<E: From<Error>>(
+        &self,
+        chain: u32,
+        gap_limit: u32,
+        mut is_used: impl FnMut(&Address) -> Result<bool, E>,
+    ) -> Result<Vec<(DerivationPath, Address)>, E> {
+        let mut used = vec![];
+        let mut gap = 0;
+        let mut index = 0;
+        while gap < gap_limit {
+            let path = DerivationPath::new(chain, index);
+            let address = self.derive_address_at(path)?;
+            if is_used(&address)? {
+                used.push((path, address));
+                gap = 0;
+            } else {
+                gap += 1;
+            }
+            index += 1;
+        }
+        Ok(used)
+    }
+}
+
+fn normal_child(index: u32) -> Result<ChildNumber, Error> {
+    ChildNumber::from_normal_idx(index).map_err(|e| Error::InvalidExtendedKey(e.to_string()))
+}
+
+impl FromStr for ExtendedPubKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Xpub::from_str(s)
+            .map(ExtendedPubKey)
+            .map_err(|e| Error::InvalidExtendedKey(e.to_string()))
+    }
+}
+
+impl fmt::Display for ExtendedPubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The extended private key of a DePC HD wallet, `xprv` for the mainnet and `tprv` for the
+/// testnet and the regtest
+///
+/// It's never kept by the bridge, the coins of the deposit addresses are swept with it offline.
+/// It's not displayed so it's not logged by mistake.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedPrivKey(Xpriv);
+
+impl ExtendedPrivKey {
+    /// The key of BIP44 account `account` of a master key, `m/44'/coin'/account'`, the coin
+    /// type is 0 for the mainnet and 1 for the others
+    pub fn derive_account(&self, account: u32) -> Result<ExtendedPrivKey, Error> {
+        if self.0.depth != 0 {
+            return Err(Error::InvalidExtendedKey(format!(
+                "the key of depth {} is not a master key",
+                self.0.depth
+            )));
+        }
+        let coin_type = if self.0.network.is_mainnet() { 0 } else { 1 };
+        let path = [
+            hardened_child(BIP44_PURPOSE)?,
+            hardened_child(coin_type)?,
+            hardened_child(account)?,
+        ];
+        self.derive(&path).map(ExtendedPrivKey)
+    }
+
+    /// The extended public key the deposit addresses are derived from
+    pub fn to_extended_pub_key(&self) -> ExtendedPubKey {
+        ExtendedPubKey(Xpub::from_priv(&Secp256k1::signing_only(), &self.0))
+    }
+
+    /// The private key of the address of `path` below the account in WIF
+    pub fn derive_private_key(&self, path: DerivationPath) -> Result<String, Error> {
+        Ok(self.derive(&path.to_children()?)?.to_priv().to_wif())
+    }
+
+    fn derive(&self, path: &[ChildNumber]) -> Result<Xpriv, Error> {
+        self.0
+            .derive_priv(&Secp256k1::signing_only(), &path)
+            .map_err(|e| Error::InvalidExtendedKey(e.to_string()))
+    }
+}
+
+fn hardened_child(index: u32) -> Result<ChildNumber, Error> {
+    ChildNumber::from_hardened_idx(index).map_err(|e| Error::InvalidExtendedKey(e.to_string()))
+}
+
+impl FromStr for ExtendedPrivKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the key is not put into the error
+        Xpriv::from_str(s)
+            .map(ExtendedPrivKey)
+            .map_err(|_| Error::InvalidExtendedKey("cannot parse the private key".to_owned()))
+    }
+}
+
+impl fmt::Debug for ExtendedPrivKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExtendedPrivKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The account `m/44'/0'/0'` of mnemonic `abandon abandon ... about`
+    const TEST_XPUB: &str =
+        "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+    /// The same key of the testnet
+    const TEST_TPUB: &str =
+        "tpubDCBWBScQPGv4Xk3JSbhw6wYYpayMjb2eAYyArpbSqQTbLDpphHGAetB6VQgVeftLML8vDSUEWcC2xDi3qJJ3YCDChJDvqVzpgoYSuT52MhJ";
+    /// The master keys of the mnemonic
+    const TEST_XPRV: &str =
+        "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu";
+    const TEST_TPRV: &str =
+        "tprv8ZgxMBicQKsPe5YMU9gHen4Ez3ApihUfykaqUorj9t6FDqy3nP6eoXiAo2ssvpAjoLroQxHqr3R5nE3a5dU3DHTjTgJDd7zrbniJr6nrCzd";
+
+    #[test]
+    fn test_derive_addresses() {
+        let xpub = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
+        assert_eq!(xpub.to_string(), TEST_XPUB);
+        assert_eq!(
+            xpub.derive_address(0).unwrap(),
+            "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+        );
+        assert_eq!(
+            xpub.derive_address(1).unwrap(),
+            "1Ak8PffB2meyfYnbXZR9EGfLfFZVpzJvQP"
+        );
+        let tpub = ExtendedPubKey::from_str(TEST_TPUB).unwrap();
+        assert_eq!(
+            tpub.derive_address(0).unwrap(),
+            "n1M8ZVQtL7QoFvGMg24D6b2ojWvFXCGpoS"
+        );
+
+        // the hardened children cannot be derived from a public key
+        assert!(matches!(
+            xpub.derive_address(1 << 31),
+            Err(Error::InvalidExtendedKey(_))
+        ));
+        assert!(ExtendedPubKey::from_str("xpub").is_err());
+        assert!(ExtendedPubKey::from_str(&TEST_XPUB.replace('6', "7")).is_err());
+    }
+
+    #[test]
+    fn test_derivation_paths() {
+        let path = DerivationPath::from_str("1/5").unwrap();
+        assert_eq!(path, DerivationPath::new(INTERNAL_CHAIN, 5));
+        assert_eq!(path.to_string(), "1/5");
+        assert_eq!(DerivationPath::external(3).to_string(), "0/3");
+        for path in ["", "1", "1/", "1/a", "1/2/3", "-1/2"] {
+            assert!(DerivationPath::from_str(path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_derive_private_keys() {
+        let xprv = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+        let account = xprv.derive_account(0).unwrap();
+        assert_eq!(account.to_extended_pub_key().to_string(), TEST_XPUB);
+        assert_eq!(
+            account
+                .derive_private_key(DerivationPath::external(0))
+                .unwrap(),
+            "L4p2b9VAf8k5aUahF1JCJUzZkgNEAqLfq8DDdQiyAprQAKSbu8hf"
+        );
+        let change = DerivationPath::new(INTERNAL_CHAIN, 0);
+        assert_eq!(
+            account
+                .to_extended_pub_key()
+                .derive_address_at(change)
+                .unwrap(),
+            "1J3J6EvPrv8q6AC3VCjWV45Uf3nssNMRtH"
+        );
+        // the coin type of the testnet is 1
+        let tprv = ExtendedPrivKey::from_str(TEST_TPRV).unwrap();
+        let account = tprv.derive_account(0).unwrap();
+        assert_eq!(
+            account.to_extended_pub_key().derive_address(0).unwrap(),
+            "mkpZhYtJu2r87Js3pDiWJDmPte2NRZ8bJV"
+        );
+        assert_eq!(
+            account
+                .derive_private_key(DerivationPath::external(0))
+                .unwrap(),
+            "cV6NTLu255SZ5iCNkVHezNGDH5qv6CanJpgBPqYgJU13NNKJhRs1"
+        );
+
+        // only the accounts of a master key are derived
+        assert!(account.derive_account(0).is_err());
+        // the key is not shown
+        assert_eq!(format!("{xprv:?}"), "ExtendedPrivKey(..)");
+        let e = ExtendedPrivKey::from_str(&TEST_XPRV[..100]).unwrap_err();
+        assert!(!e.to_string().contains(&TEST_XPRV[..100]));
+    }
+
+    #[test]
+    fn test_scan_addresses() {
+        let xpub = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
+        let used = [
+            xpub.derive_address(1).unwrap(),
+            xpub.derive_address(4).unwrap(),
+        ];
+        let mut scanned = 0;
+        let found = xpub
+            .scan_addresses::<Error>(EXTERNAL_CHAIN, 3, |address| {
+                scanned += 1;
+                Ok(used.contains(address))
+            })
+            .unwrap();
+        assert_eq!(
+            found,
+            vec![
+                (DerivationPath::external(1), used[0].clone()),
+                (DerivationPath::external(4), used[1].clone()),
+            ]
+        );
+        // the scan stops after index 7
+        assert_eq!(scanned, 8);
+
+        // an address after a longer gap is not found
+        let found = xpub
+            .scan_addresses::<Error>(EXTERNAL_CHAIN, 2, |address| Ok(used.contains(address)))
+            .unwrap();
+        assert_eq!(found.len(), 1);
+    }
+}
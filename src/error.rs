@@ -0,0 +1,438 @@
+use crate::amount::DepcAmount;
+use crate::{depc, solana};
+
+/// The error of the bridge, the errors from the modules are converted into it so they can be
+/// propagated with `?` and the original error is kept as the source
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    #[error("DePC error")]
+    Depc(#[from] depc::Error),
+    #[error("solana error")]
+    Solana(#[from] solana::Error),
+    #[error("cannot analyze solana transaction")]
+    Analyzer(#[from] solana::AnalyzerError),
+    #[error("database error")]
+    Database(#[from] rusqlite::Error),
+    /// The call of the token client fails, the kind tells whether it's worth retrying
+    #[error("token client error: {0}")]
+    TokenClient(String, solana::ErrorKind),
+    #[error("invalid address `{0}`")]
+    InvalidAddress(String),
+    #[error("block {0} cannot be found")]
+    BlockNotFound(u32),
+    #[error("the channel `{0}` is closed")]
+    ChannelClosed(&'static str),
+    #[error("the amount overflows or cannot be converted exactly")]
+    AmountOverflow,
+    #[error("invalid config, {0}")]
+    InvalidConfig(String),
+    #[error("price feed error: {0}")]
+    PriceFeed(String),
+    #[error("the recipient `{0}` is {1}, the tokens sent to it would be lost")]
+    ReservedRecipient(String, &'static str),
+    #[error("solana transaction {0} is used by withdrawal {1} already")]
+    SignatureReused(String, String),
+    #[error("webhook error: {0}")]
+    Webhook(String),
+    #[error("invalid withdraw request, {0}")]
+    InvalidWithdrawRequest(String),
+    #[error("block {0} doesn't follow the indexed block before it, the chain is reorganized")]
+    ChainReorganized(u32),
+    #[error("the indexed blocks differ from the chain deeper than {0} blocks")]
+    ReorgTooDeep(u32),
+    #[error("the bridge fee takes all of the amount {0}")]
+    FeeExceedsAmount(DepcAmount),
+    #[error("the transfer {0} is under the minimum {1}")]
+    BelowTransferMinimum(DepcAmount, DepcAmount),
+    #[error("the amount {0} is under the unit {1} the mint can hold")]
+    BelowConversionUnit(DepcAmount, DepcAmount),
+}
+
+/// The machine-readable code of an error, it's stable across the releases and the languages so
+/// the integrators branch on it instead of parsing the message
+///
+/// The same codes are in the REST error bodies (`error.error_code`), the exit messages of the
+/// command line and the failed deliveries of the webhooks. A code is never renamed or reused,
+/// new ones might be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// The DePC node is unreachable or busy
+    DepcRpcUnavailable,
+    /// The block is pruned or the transaction is missing on the DePC node
+    DepcDataUnavailable,
+    /// The wallet of the DePC node cannot pay for the transaction
+    DepcInsufficientFunds,
+    /// The other errors of the DePC node, e.g. a response cannot be parsed
+    DepcRpcFailed,
+    /// The Solana node is unreachable, busy or refuses the call
+    SolanaRpcUnavailable,
+    /// The Solana node is behind or unhealthy
+    SolanaNodeUnhealthy,
+    /// The Solana transaction is invalid or not related to the bridge
+    SolanaTransactionInvalid,
+    /// The node or the database doesn't answer a REST request within the budget of it
+    UpstreamTimeout,
+    /// The associated token account of the recipient is missing and cannot be created
+    RecipientAtaMissing,
+    TokenClientFailed,
+    PriceFeedUnavailable,
+    WebhookFailed,
+    /// The database is locked by another process
+    DatabaseBusy,
+    DatabaseFailed,
+    /// A parameter of the request is missing, malformed or out of range
+    InvalidParameter,
+    InvalidAddress,
+    InvalidConfig,
+    /// The recipient is an address the tokens sent to would be lost
+    RecipientReserved,
+    AmountOverflow,
+    NotFound,
+    BlockNotFound,
+    /// The data before the checkpoint the index is bootstrapped from is not indexed
+    DataNotIndexed,
+    /// The optional service the request needs (e.g. the price feed) is not configured
+    NotConfigured,
+    WithdrawRequestInvalid,
+    /// The Solana transaction is requested to be withdrawn already
+    WithdrawRequestDuplicated,
+    /// The Solana transaction is used by another withdrawal already
+    WithdrawSignatureReused,
+    /// The transaction `/solana/post_tx` relays is malformed
+    RelayTransactionInvalid,
+    RelayTransactionNotSigned,
+    RelayFeePayerIsAuthority,
+    RelayQuotaExceeded,
+    RelayFailed,
+    Unauthorized,
+    IdempotencyKeyInvalid,
+    /// The `Idempotency-Key` is used by another request
+    IdempotencyKeyReused,
+    /// The request of the `Idempotency-Key` is being served or failed
+    IdempotencyKeyConflict,
+    RequestTooLarge,
+    PreflightFailed,
+    LedgerUnbalanced,
+    /// The errors not covered by the other codes
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 39] = [
+        ErrorCode::DepcRpcUnavailable,
+        ErrorCode::DepcDataUnavailable,
+        ErrorCode::DepcInsufficientFunds,
+        ErrorCode::DepcRpcFailed,
+        ErrorCode::SolanaRpcUnavailable,
+        ErrorCode::SolanaNodeUnhealthy,
+        ErrorCode::SolanaTransactionInvalid,
+        ErrorCode::UpstreamTimeout,
+        ErrorCode::RecipientAtaMissing,
+        ErrorCode::TokenClientFailed,
+        ErrorCode::PriceFeedUnavailable,
+        ErrorCode::WebhookFailed,
+        ErrorCode::DatabaseBusy,
+        ErrorCode::DatabaseFailed,
+        ErrorCode::InvalidParameter,
+        ErrorCode::InvalidAddress,
+        ErrorCode::InvalidConfig,
+        ErrorCode::RecipientReserved,
+        ErrorCode::AmountOverflow,
+        ErrorCode::NotFound,
+        ErrorCode::BlockNotFound,
+        ErrorCode::DataNotIndexed,
+        ErrorCode::NotConfigured,
+        ErrorCode::WithdrawRequestInvalid,
+        ErrorCode::WithdrawRequestDuplicated,
+        ErrorCode::WithdrawSignatureReused,
+        ErrorCode::RelayTransactionInvalid,
+        ErrorCode::RelayTransactionNotSigned,
+        ErrorCode::RelayFeePayerIsAuthority,
+        ErrorCode::RelayQuotaExceeded,
+        ErrorCode::RelayFailed,
+        ErrorCode::Unauthorized,
+        ErrorCode::IdempotencyKeyInvalid,
+        ErrorCode::IdempotencyKeyReused,
+        ErrorCode::IdempotencyKeyConflict,
+        ErrorCode::RequestTooLarge,
+        ErrorCode::PreflightFailed,
+        ErrorCode::LedgerUnbalanced,
+        ErrorCode::Internal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::DepcRpcUnavailable => "DEPC_RPC_UNAVAILABLE",
+            ErrorCode::DepcDataUnavailable => "DEPC_DATA_UNAVAILABLE",
+            ErrorCode::DepcInsufficientFunds => "DEPC_INSUFFICIENT_FUNDS",
+            ErrorCode::DepcRpcFailed => "DEPC_RPC_FAILED",
+            ErrorCode::SolanaRpcUnavailable => "SOLANA_RPC_UNAVAILABLE",
+            ErrorCode::SolanaNodeUnhealthy => "SOLANA_NODE_UNHEALTHY",
+            ErrorCode::SolanaTransactionInvalid => "SOLANA_TRANSACTION_INVALID",
+            ErrorCode::UpstreamTimeout => "UPSTREAM_TIMEOUT",
+            ErrorCode::RecipientAtaMissing => "RECIPIENT_ATA_MISSING",
+            ErrorCode::TokenClientFailed => "TOKEN_CLIENT_FAILED",
+            ErrorCode::PriceFeedUnavailable => "PRICE_FEED_UNAVAILABLE",
+            ErrorCode::WebhookFailed => "WEBHOOK_FAILED",
+            ErrorCode::DatabaseBusy => "DATABASE_BUSY",
+            ErrorCode::DatabaseFailed => "DATABASE_FAILED",
+            ErrorCode::InvalidParameter => "INVALID_PARAMETER",
+            ErrorCode::InvalidAddress => "INVALID_ADDRESS",
+            ErrorCode::InvalidConfig => "INVALID_CONFIG",
+            ErrorCode::RecipientReserved => "RECIPIENT_RESERVED",
+            ErrorCode::AmountOverflow => "AMOUNT_OVERFLOW",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::BlockNotFound => "BLOCK_NOT_FOUND",
+            ErrorCode::DataNotIndexed => "DATA_NOT_INDEXED",
+            ErrorCode::NotConfigured => "NOT_CONFIGURED",
+            ErrorCode::WithdrawRequestInvalid => "WITHDRAW_REQUEST_INVALID",
+            ErrorCode::WithdrawRequestDuplicated => "WITHDRAW_REQUEST_DUPLICATED",
+            ErrorCode::WithdrawSignatureReused => "WITHDRAW_SIGNATURE_REUSED",
+            ErrorCode::RelayTransactionInvalid => "RELAY_TRANSACTION_INVALID",
+            ErrorCode::RelayTransactionNotSigned => "RELAY_TRANSACTION_NOT_SIGNED",
+            ErrorCode::RelayFeePayerIsAuthority => "RELAY_FEE_PAYER_IS_AUTHORITY",
+            ErrorCode::RelayQuotaExceeded => "RELAY_QUOTA_EXCEEDED",
+            ErrorCode::RelayFailed => "RELAY_FAILED",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::IdempotencyKeyInvalid => "IDEMPOTENCY_KEY_INVALID",
+            ErrorCode::IdempotencyKeyReused => "IDEMPOTENCY_KEY_REUSED",
+            ErrorCode::IdempotencyKeyConflict => "IDEMPOTENCY_KEY_CONFLICT",
+            ErrorCode::RequestTooLarge => "REQUEST_TOO_LARGE",
+            ErrorCode::PreflightFailed => "PREFLIGHT_FAILED",
+            ErrorCode::LedgerUnbalanced => "LEDGER_UNBALANCED",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for ErrorCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// The codes added by a newer server are read as `Internal`
+impl<'de> serde::Deserialize<'de> for ErrorCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Ok(ErrorCode::ALL
+            .into_iter()
+            .find(|known| known.as_str() == code)
+            .unwrap_or(ErrorCode::Internal))
+    }
+}
+
+impl BridgeError {
+    /// The error of a call of the token client `C`, classified by it
+    pub fn token_client<C: solana::TokenClient>(e: C::Error) -> BridgeError {
+        let kind = C::error_kind(&e);
+        BridgeError::TokenClient(e.to_string(), kind)
+    }
+
+    /// The deposit is too small to be bridged, the bridge fee takes all of it, nothing is left
+    /// to mint or it's under the transfer minimum
+    pub fn is_below_minimum(&self) -> bool {
+        matches!(
+            self,
+            BridgeError::FeeExceedsAmount(_)
+                | BridgeError::BelowConversionUnit(..)
+                | BridgeError::BelowTransferMinimum(..)
+        )
+    }
+
+    /// The code of the error, see `ErrorCode`
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            BridgeError::Depc(e) => match e {
+                depc::Error::RpcError => ErrorCode::DepcRpcUnavailable,
+                depc::Error::DataUnavailable(_) => ErrorCode::DepcDataUnavailable,
+                depc::Error::InsufficientFunds => ErrorCode::DepcInsufficientFunds,
+                depc::Error::InvalidExtendedKey(_)
+                | depc::Error::InvalidDerivationPath(_)
+                | depc::Error::InvalidMultisig(_) => ErrorCode::InvalidConfig,
+                _ => ErrorCode::DepcRpcFailed,
+            },
+            BridgeError::Solana(e) => match e {
+                solana::Error::NodeUnhealthy(_) => ErrorCode::SolanaNodeUnhealthy,
+                solana::Error::CannotCreateAssociatedAccount(_) => ErrorCode::RecipientAtaMissing,
+                solana::Error::InvalidTransaction(_)
+                | solana::Error::CannotParseTransactionInfo(_)
+                | solana::Error::NotARelatedTransactionOfAuthority(_)
+                | solana::Error::MoreThanOneRelatedInstructionsFoundFrom1Transaction(_) => {
+                    ErrorCode::SolanaTransactionInvalid
+                }
+                solana::Error::InvalidMintAddress(_)
+                | solana::Error::CannotParsePubkeyFromString(_) => ErrorCode::InvalidAddress,
+                _ => ErrorCode::SolanaRpcUnavailable,
+            },
+            BridgeError::Analyzer(_) => ErrorCode::SolanaTransactionInvalid,
+            BridgeError::Database(rusqlite::Error::SqliteFailure(e, _))
+                if matches!(
+                    e.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) =>
+            {
+                ErrorCode::DatabaseBusy
+            }
+            BridgeError::Database(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::OperationInterrupted =>
+            {
+                ErrorCode::UpstreamTimeout
+            }
+            BridgeError::Database(_) => ErrorCode::DatabaseFailed,
+            BridgeError::TokenClient(..) => ErrorCode::TokenClientFailed,
+            BridgeError::InvalidAddress(_) => ErrorCode::InvalidAddress,
+            BridgeError::BlockNotFound(_) => ErrorCode::BlockNotFound,
+            BridgeError::ChannelClosed(_) => ErrorCode::Internal,
+            BridgeError::AmountOverflow => ErrorCode::AmountOverflow,
+            BridgeError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            BridgeError::PriceFeed(_) => ErrorCode::PriceFeedUnavailable,
+            BridgeError::ReservedRecipient(..) => ErrorCode::RecipientReserved,
+            BridgeError::SignatureReused(..) => ErrorCode::WithdrawSignatureReused,
+            BridgeError::Webhook(_) => ErrorCode::WebhookFailed,
+            BridgeError::InvalidWithdrawRequest(_) => ErrorCode::WithdrawRequestInvalid,
+            BridgeError::ChainReorganized(_) | BridgeError::ReorgTooDeep(_) => ErrorCode::Internal,
+            BridgeError::FeeExceedsAmount(_) => ErrorCode::InvalidParameter,
+            BridgeError::BelowTransferMinimum(..) => ErrorCode::InvalidParameter,
+            BridgeError::BelowConversionUnit(..) => ErrorCode::InvalidParameter,
+        }
+    }
+
+    /// The message of the error and all its sources, e.g.
+    /// `DePC error: RPC service error`
+    pub fn full_message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            message += &format!(": {}", e);
+            source = e.source();
+        }
+        message
+    }
+
+    /// The transient errors (the services are unreachable or busy) might go away when the same
+    /// call is made again, the others won't
+    pub fn is_transient(&self) -> bool {
+        match self {
+            BridgeError::Depc(depc::Error::RpcError) => true,
+            BridgeError::Database(rusqlite::Error::SqliteFailure(e, _)) => matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            ),
+            BridgeError::TokenClient(_, kind) => matches!(
+                kind,
+                solana::ErrorKind::Transient | solana::ErrorKind::Unknown
+            ),
+            BridgeError::Solana(_) | BridgeError::PriceFeed(_) | BridgeError::Webhook(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_height() -> Result<u32, BridgeError> {
+        let height: Result<u32, depc::Error> = Err(depc::Error::RpcError);
+        Ok(height?)
+    }
+
+    #[test]
+    fn test_error_source_chaining() {
+        let err = query_height().unwrap_err();
+        assert!(matches!(err, BridgeError::Depc(depc::Error::RpcError)));
+        assert_eq!(err.full_message(), "DePC error: RPC service error");
+
+        let err = BridgeError::from(rusqlite::Error::QueryReturnedNoRows);
+        assert_eq!(err.full_message(), "database error: Query returned no rows");
+        assert_eq!(
+            BridgeError::InvalidAddress("abc".to_owned()).full_message(),
+            "invalid address `abc`"
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let codes: std::collections::HashSet<&str> =
+            ErrorCode::ALL.iter().map(ErrorCode::as_str).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        assert!(codes
+            .iter()
+            .all(|code| code.bytes().all(|b| b.is_ascii_uppercase() || b == b'_')));
+
+        assert_eq!(
+            BridgeError::from(depc::Error::RpcError).code(),
+            ErrorCode::DepcRpcUnavailable
+        );
+        assert_eq!(
+            BridgeError::from(solana::Error::CannotCreateAssociatedAccount(
+                "owner".to_owned()
+            ))
+            .code(),
+            ErrorCode::RecipientAtaMissing
+        );
+        assert_eq!(
+            BridgeError::SignatureReused("signature".to_owned(), "txid".to_owned())
+                .code()
+                .to_string(),
+            "WITHDRAW_SIGNATURE_REUSED"
+        );
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert_eq!(BridgeError::from(busy).code(), ErrorCode::DatabaseBusy);
+        let interrupted = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+            None,
+        );
+        assert_eq!(
+            BridgeError::from(interrupted).code(),
+            ErrorCode::UpstreamTimeout
+        );
+        assert_eq!(
+            serde_json::to_value(ErrorCode::InvalidParameter).unwrap(),
+            "INVALID_PARAMETER"
+        );
+        for code in ErrorCode::ALL {
+            let value = serde_json::to_value(code).unwrap();
+            assert_eq!(serde_json::from_value::<ErrorCode>(value).unwrap(), code);
+        }
+        assert_eq!(
+            serde_json::from_value::<ErrorCode>(serde_json::json!("NEW_CODE")).unwrap(),
+            ErrorCode::Internal
+        );
+    }
+
+    #[test]
+    fn test_error_is_transient() {
+        assert!(BridgeError::from(depc::Error::RpcError).is_transient());
+        assert!(
+            !BridgeError::from(depc::Error::InvalidResponse("getblock".to_owned())).is_transient()
+        );
+        assert!(!BridgeError::InvalidAddress("abc".to_owned()).is_transient());
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(BridgeError::from(busy).is_transient());
+        assert!(!BridgeError::from(rusqlite::Error::QueryReturnedNoRows).is_transient());
+        for (kind, transient) in [
+            (solana::ErrorKind::Transient, true),
+            (solana::ErrorKind::Unknown, true),
+            (solana::ErrorKind::Permanent, false),
+            (solana::ErrorKind::InDoubt, false),
+        ] {
+            let e = BridgeError::TokenClient("node is busy".to_owned(), kind);
+            assert_eq!(e.is_transient(), transient, "{kind:?}");
+        }
+    }
+}
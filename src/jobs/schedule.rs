@@ -0,0 +1,218 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike};
+
+use crate::BridgeError;
+
+/// A schedule matches at least once in 4 years unless it never matches, e.g. `0 0 30 2 *`
+const MAX_SEARCH_DAYS: u64 = 4 * 366;
+
+/// The cron expression of a job in UTC, 5 fields of minute (0-59), hour (0-23), day of month
+/// (1-31), month (1-12) and day of week (0-7, both 0 and 7 are Sunday). A field is `*`, a
+/// value, a range `a-b` or a list of them separated by `,`, each one may have a step `/n`.
+/// `@hourly`, `@daily` and `@weekly` are the shortcuts.
+///
+/// The day matches if either the day of month or the day of week matches when both of them are
+/// restricted, like cron.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    /// The first time the schedule matches after `timestamp`, it's always a whole minute
+    pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+        const MINUTES_PER_DAY: u64 = 24 * 60;
+        let mut minute = timestamp / 60 + 1;
+        let last = minute + MAX_SEARCH_DAYS * MINUTES_PER_DAY;
+        while minute < last {
+            let day = minute / MINUTES_PER_DAY;
+            let date = DateTime::from_timestamp((day * 24 * 3600) as i64, 0)?;
+            if !self.matches_day(
+                date.day(),
+                date.month(),
+                date.weekday().num_days_from_sunday(),
+            ) {
+                minute = (day + 1) * MINUTES_PER_DAY;
+                continue;
+            }
+            if self.hours & (1 << (minute / 60 % 24)) == 0 {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if self.minutes & (1 << (minute % 60)) == 0 {
+                minute += 1;
+                continue;
+            }
+            return Some(minute * 60);
+        }
+        None
+    }
+
+    fn matches_day(&self, day_of_month: u32, month: u32, day_of_week: u32) -> bool {
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        let matches_day_of_month = self.days_of_month & (1 << day_of_month) != 0;
+        let matches_day_of_week = self.days_of_week & (1 << day_of_week) != 0;
+        if self.any_day_of_month || self.any_day_of_week {
+            matches_day_of_month && matches_day_of_week
+        } else {
+            matches_day_of_month || matches_day_of_week
+        }
+    }
+}
+
+/// Parse a field to the bit set of the values, returns whether it's `*` as well
+fn parse_field(field: &str, min: u32, max: u32) -> Result<(u64, bool), String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step '{step}'")),
+            },
+            None => (part, 1),
+        };
+        let parse_value = |value: &str| match value.parse::<u32>() {
+            Ok(value) if (min..=max).contains(&value) => Ok(value),
+            _ => Err(format!("'{value}' is not in {min}-{max}")),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some((first, last)) = range.split_once('-') {
+            (parse_value(first)?, parse_value(last)?)
+        } else if part.contains('/') {
+            // `a/n` is from `a` to the max
+            (parse_value(range)?, max)
+        } else {
+            let value = parse_value(range)?;
+            (value, value)
+        };
+        if first > last {
+            return Err(format!("range '{range}' is empty"));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok((bits, field == "*"))
+}
+
+impl FromStr for Schedule {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            expression => expression,
+        };
+        let invalid = |reason: String| {
+            BridgeError::InvalidConfig(format!("invalid schedule `{s}`, {reason}"))
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(invalid(format!("{} fields instead of 5", fields.len())));
+        };
+        let (minutes, _) = parse_field(minutes, 0, 59).map_err(invalid)?;
+        let (hours, _) = parse_field(hours, 0, 23).map_err(invalid)?;
+        let (days_of_month, any_day_of_month) =
+            parse_field(days_of_month, 1, 31).map_err(invalid)?;
+        let (months, _) = parse_field(months, 1, 12).map_err(invalid)?;
+        let (mut days_of_week, any_day_of_week) =
+            parse_field(days_of_week, 0, 7).map_err(invalid)?;
+        // Sunday is 0 and 7
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Schedule {
+            expression: s.trim().to_owned(),
+            minutes,
+            hours,
+            days_of_month,
+            months,
+            days_of_week,
+            any_day_of_month,
+            any_day_of_week,
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-11-07 17:30:00 UTC, a Thursday
+    const TEST_NOW: u64 = 1731000600;
+
+    fn next(expression: &str, timestamp: u64) -> Option<u64> {
+        Schedule::from_str(expression)
+            .unwrap()
+            .next_after(timestamp)
+    }
+
+    #[test]
+    fn test_next_after() {
+        assert_eq!(next("* * * * *", TEST_NOW), Some(TEST_NOW + 60));
+        assert_eq!(next("* * * * *", TEST_NOW + 59), Some(TEST_NOW + 60));
+        assert_eq!(next("*/20 * * * *", TEST_NOW), Some(TEST_NOW + 10 * 60));
+        assert_eq!(next("@hourly", TEST_NOW), Some(TEST_NOW + 30 * 60));
+        assert_eq!(
+            next("15 3 * * *", TEST_NOW),
+            Some(TEST_NOW + (9 * 60 + 45) * 60)
+        );
+        assert_eq!(next("0 0 1 1 *", TEST_NOW), Some(1735689600));
+        // the next Sunday, 7 is Sunday as well
+        assert_eq!(next("0 0 * * 0", TEST_NOW), Some(1731196800));
+        assert_eq!(next("0 0 * * 7", TEST_NOW), Some(1731196800));
+        // either the 9th or the Sunday
+        assert_eq!(next("0 0 9 * 0", TEST_NOW), Some(1731110400));
+        assert_eq!(next("30 17 7 11 1-5", TEST_NOW), Some(TEST_NOW + 24 * 3600));
+        assert_eq!(next("0 0 30 2 *", TEST_NOW), None);
+        assert_eq!(
+            Schedule::from_str(" @daily ").unwrap().to_string(),
+            "@daily"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_schedule() {
+        for expression in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "@yearly",
+        ] {
+            assert!(
+                matches!(
+                    Schedule::from_str(expression),
+                    Err(BridgeError::InvalidConfig(_))
+                ),
+                "{expression}"
+            );
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+use super::Schedule;
+use crate::clock::{Deadline, SharedClock};
+use crate::db;
+use crate::BridgeError;
+
+/// The interval to check the due jobs
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The job rebuilds the address clusters, see `analysis::build_address_clusters`
+pub const JOB_ADDRESS_CLUSTERS: &str = "address_clusters";
+/// The job records the current Solana slot, see `clock::SlotTimeline`
+pub const JOB_SOLANA_SLOTS: &str = "solana_slots";
+/// The job scans the transfers to the token account of the owner after the last scan, see
+/// `bridge::scan_signatures`
+pub const JOB_SOLANA_SIGNATURES: &str = "solana_signatures";
+/// The job publishes the Merkle root of the minted deposits, see `bridge::publish_deposit_root`
+pub const JOB_DEPOSIT_ROOT: &str = "deposit_root";
+
+/// The seconds a run of a job is given by default, see `JobSettings::timeout`
+pub const DEFAULT_JOB_TIMEOUT: u64 = 3600;
+
+/// The work of a job, it's run on a blocking thread
+pub type JobTask = Arc<dyn Fn() -> Result<(), BridgeError> + Send + Sync>;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_JOB_TIMEOUT
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct JobSettings {
+    pub name: String,
+    /// The cron expression, see [`Schedule`]
+    pub schedule: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// The seconds a run is given, the database queries and the RPC calls it makes after that
+    /// fail, see `clock::Deadline`
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+/// The jobs file, the jobs are run by the schedules in UTC
+///
+/// ```json
+/// {
+///   "jobs": [
+///     { "name": "address_clusters", "schedule": "0 */6 * * *", "enabled": true, "timeout": 600 }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct JobsSettings {
+    pub jobs: Vec<JobSettings>,
+}
+
+impl JobsSettings {
+    pub fn load(path: &str) -> Result<JobsSettings, BridgeError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot read jobs file {path}, {e}"))
+        })?;
+        JobsSettings::from_json(&content)
+    }
+
+    pub fn from_json(content: &str) -> Result<JobsSettings, BridgeError> {
+        serde_json::from_str(content)
+            .map_err(|e| BridgeError::InvalidConfig(format!("cannot parse jobs, {e}")))
+    }
+}
+
+struct Job {
+    name: String,
+    schedule: Schedule,
+    enabled: bool,
+    timeout: Duration,
+    task: JobTask,
+    /// A job is never run again before the last run finishes
+    running: Arc<AtomicBool>,
+}
+
+/// Run the periodic jobs by their schedules, the last and next runs are saved to table `jobs`
+/// so the schedules survive the restarts, and a run missed while the process was down is made
+/// right after the start
+pub struct Scheduler {
+    conn: db::Conn,
+    clock: SharedClock,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(conn: db::Conn, clock: SharedClock) -> Scheduler {
+        Scheduler {
+            conn,
+            clock,
+            jobs: vec![],
+        }
+    }
+
+    pub fn add_job(mut self, settings: &JobSettings, task: JobTask) -> Result<Self, BridgeError> {
+        if self.jobs.iter().any(|job| job.name == settings.name) {
+            return Err(BridgeError::InvalidConfig(format!(
+                "job `{}` is added more than once",
+                settings.name
+            )));
+        }
+        self.jobs.push(Job {
+            name: settings.name.clone(),
+            schedule: Schedule::from_str(&settings.schedule)?,
+            enabled: settings.enabled,
+            timeout: Duration::from_secs(settings.timeout),
+            task,
+            running: Arc::new(AtomicBool::new(false)),
+        });
+        Ok(self)
+    }
+
+    /// Save the jobs, the saved next run is kept unless the schedule is changed, and the jobs
+    /// those are no longer added are disabled
+    pub fn init(&self) -> Result<(), BridgeError> {
+        let now = self.clock.now();
+        let names: HashSet<&str> = self.jobs.iter().map(|job| job.name.as_str()).collect();
+        for record in self.conn.query_jobs()? {
+            if !names.contains(record.name.as_str()) && record.enabled {
+                info!("job {} is not configured, it's disabled", record.name);
+                self.conn
+                    .save_job(&record.name, &record.schedule, false, None)?;
+            }
+        }
+        for job in self.jobs.iter() {
+            let schedule = job.schedule.to_string();
+            let next_run_at = if !job.enabled {
+                None
+            } else {
+                match self.conn.query_job(&job.name)? {
+                    Some(record) if record.schedule == schedule && record.next_run_at.is_some() => {
+                        record.next_run_at
+                    }
+                    _ => job.schedule.next_after(now),
+                }
+            };
+            self.conn
+                .save_job(&job.name, &schedule, job.enabled, next_run_at)?;
+        }
+        Ok(())
+    }
+
+    /// Start the enabled jobs those are due, a due run of a job is skipped if the last run
+    /// hasn't finished
+    pub fn run_due_jobs(&self) -> Result<Vec<JoinHandle<()>>, BridgeError> {
+        let now = self.clock.now();
+        let mut handles = vec![];
+        for job in self.jobs.iter().filter(|job| job.enabled) {
+            let Some(record) = self.conn.query_job(&job.name)? else {
+                continue;
+            };
+            match record.next_run_at {
+                Some(next_run_at) if next_run_at <= now => {}
+                _ => continue,
+            }
+            let next_run_at = job.schedule.next_after(now);
+            if job.running.swap(true, Ordering::SeqCst) {
+                warn!("job {} is still running, the due run is skipped", job.name);
+                self.conn.set_job_next_run(&job.name, next_run_at)?;
+                continue;
+            }
+            if let Err(e) = self.conn.start_job(&job.name, now, next_run_at) {
+                job.running.store(false, Ordering::SeqCst);
+                return Err(e.into());
+            }
+            info!("job {} is started", job.name);
+            let (name, task, running) = (
+                job.name.clone(),
+                Arc::clone(&job.task),
+                Arc::clone(&job.running),
+            );
+            let (conn, clock) = (self.conn.clone(), Arc::clone(&self.clock));
+            let deadline = Deadline::after(job.timeout);
+            handles.push(tokio::task::spawn_blocking(move || {
+                let result = deadline.sync_scope(|| task());
+                let error = match result {
+                    Ok(()) => {
+                        info!("job {name} is finished");
+                        None
+                    }
+                    Err(e) => {
+                        error!("job {name} fails, reason: {}", e.full_message());
+                        Some(e.full_message())
+                    }
+                };
+                if let Err(e) = conn.finish_job(&name, clock.now(), error.as_deref()) {
+                    error!("cannot save the run of job {name}, reason: {e}");
+                }
+                running.store(false, Ordering::SeqCst);
+            }));
+        }
+        Ok(handles)
+    }
+}
+
+pub async fn run_scheduler(
+    exit_sig: Arc<Mutex<bool>>,
+    scheduler: Scheduler,
+) -> Result<(), BridgeError> {
+    scheduler.init()?;
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        if let Err(e) = scheduler.run_due_jobs() {
+            warn!("cannot run the due jobs, reason: {}", e.full_message());
+        }
+        sleep(TICK_INTERVAL).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::testing::MockClock;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    fn make_settings(name: &str, schedule: &str) -> JobSettings {
+        JobSettings {
+            name: name.to_owned(),
+            schedule: schedule.to_owned(),
+            enabled: true,
+            timeout: DEFAULT_JOB_TIMEOUT,
+        }
+    }
+
+    async fn run_due_jobs(scheduler: &Scheduler) -> usize {
+        let handles = scheduler.run_due_jobs().unwrap();
+        let n = handles.len();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        n
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_by_schedule() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = MockClock::new(TEST_NOW);
+        let runs = Arc::new(AtomicU32::new(0));
+        let task: JobTask = {
+            let runs = Arc::clone(&runs);
+            Arc::new(move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        };
+        let failing: JobTask = Arc::new(|| Err(BridgeError::ChannelClosed("test")));
+        let make_scheduler = || {
+            Scheduler::new(conn.clone(), Arc::new(clock.clone()))
+                .add_job(&make_settings("counter", "@hourly"), Arc::clone(&task))
+                .unwrap()
+                .add_job(
+                    &make_settings("failing", "*/10 * * * *"),
+                    Arc::clone(&failing),
+                )
+                .unwrap()
+        };
+        let scheduler = make_scheduler();
+        assert!(scheduler
+            .add_job(&make_settings("counter", "@daily"), Arc::clone(&task))
+            .is_err());
+        let scheduler = make_scheduler();
+        scheduler.init().unwrap();
+        assert_eq!(run_due_jobs(&scheduler).await, 0);
+
+        clock.set(TEST_NOW + 1800);
+        assert_eq!(run_due_jobs(&scheduler).await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let record = conn.query_job("counter").unwrap().unwrap();
+        assert_eq!(record.last_started_at, Some(TEST_NOW + 1800));
+        assert_eq!(record.last_finished_at, Some(TEST_NOW + 1800));
+        assert_eq!(record.last_error, None);
+        assert_eq!(record.next_run_at, Some(TEST_NOW + 1800 + 3600));
+        let record = conn.query_job("failing").unwrap().unwrap();
+        assert!(record.last_error.is_some());
+        assert_eq!(record.next_run_at, Some(TEST_NOW + 1800 + 600));
+
+        // the run missed while the process is down is made after the restart
+        clock.set(TEST_NOW + 3 * 3600);
+        let scheduler = make_scheduler();
+        scheduler.init().unwrap();
+        assert_eq!(run_due_jobs(&scheduler).await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // the disabled job and the job no longer added are not run
+        let mut settings = make_settings("counter", "@hourly");
+        settings.enabled = false;
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(clock.clone()))
+            .add_job(&settings, Arc::clone(&task))
+            .unwrap();
+        scheduler.init().unwrap();
+        clock.advance(24 * 3600);
+        assert_eq!(run_due_jobs(&scheduler).await, 0);
+        let records = conn.query_jobs().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|record| !record.enabled));
+    }
+
+    #[tokio::test]
+    async fn test_job_never_overlaps() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = MockClock::new(TEST_NOW);
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Mutex::new(rx);
+        let task: JobTask = Arc::new(move || {
+            rx.lock().unwrap().recv().unwrap();
+            Ok(())
+        });
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(clock.clone()))
+            .add_job(&make_settings("slow", "* * * * *"), task)
+            .unwrap();
+        scheduler.init().unwrap();
+
+        clock.advance(60);
+        let handles = scheduler.run_due_jobs().unwrap();
+        assert_eq!(handles.len(), 1);
+        assert!(conn.query_job("slow").unwrap().unwrap().running);
+        // the run is still in progress
+        clock.advance(60);
+        assert!(scheduler.run_due_jobs().unwrap().is_empty());
+        assert_eq!(
+            conn.query_job("slow").unwrap().unwrap().next_run_at,
+            Some(TEST_NOW + 180)
+        );
+
+        tx.send(()).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(!conn.query_job("slow").unwrap().unwrap().running);
+        clock.advance(60);
+        let handles = scheduler.run_due_jobs().unwrap();
+        assert_eq!(handles.len(), 1);
+        tx.send(()).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_runs_under_deadline() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = MockClock::new(TEST_NOW);
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let task: JobTask = Arc::new(move || {
+            let remaining = Deadline::current().and_then(|deadline| deadline.remaining());
+            tx.lock().unwrap().send(remaining).unwrap();
+            Ok(())
+        });
+        let mut settings = make_settings("scan", "* * * * *");
+        settings.timeout = 30;
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(clock.clone()))
+            .add_job(&settings, task)
+            .unwrap();
+        scheduler.init().unwrap();
+
+        clock.advance(60);
+        assert_eq!(run_due_jobs(&scheduler).await, 1);
+        let remaining = rx.recv().unwrap().unwrap();
+        assert!(remaining <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_parse_jobs_settings() {
+        let settings = JobsSettings::from_json(
+            r#"{"jobs": [{"name": "address_clusters", "schedule": "0 */6 * * *"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.jobs,
+            vec![make_settings(JOB_ADDRESS_CLUSTERS, "0 */6 * * *")]
+        );
+        let settings = JobsSettings::from_json(
+            r#"{"jobs": [{"name": "deposit_root", "schedule": "@hourly", "timeout": 60}]}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.jobs[0].timeout, 60);
+        assert!(JobsSettings::from_json(r#"{"jobs": [{"name": "a"}]}"#).is_err());
+    }
+}
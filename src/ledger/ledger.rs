@@ -0,0 +1,568 @@
+use std::fmt;
+
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::BridgeError;
+
+/// The DePC held by the custody address of the bridge
+pub const ACCOUNT_CUSTODY: &str = "custody";
+/// The DePC swept from the custody to the cold address
+pub const ACCOUNT_COLD_STORAGE: &str = "cold_storage";
+/// The deposits received but neither minted nor refunded yet
+pub const ACCOUNT_DEPOSITS_DUE: &str = "deposits_due";
+/// The wrapped tokens minted for the deposits and not returned yet
+pub const ACCOUNT_WRAPPED_SUPPLY: &str = "wrapped_supply";
+/// The wrapped tokens returned for the withdrawals but not paid out yet
+pub const ACCOUNT_WITHDRAWALS_DUE: &str = "withdrawals_due";
+/// The fees of the DePC transactions paid by the bridge
+pub const ACCOUNT_NETWORK_FEES: &str = "network_fees";
+/// The bridge fees kept from the deposits and the withdrawals, in the wrapped tokens held by
+/// the authority, and not swept to the treasury yet
+pub const ACCOUNT_BRIDGE_FEES: &str = "bridge_fees";
+/// The bridge fees swept to the treasury
+pub const ACCOUNT_FEE_TREASURY: &str = "fee_treasury";
+
+/// The bridge movement a ledger entry is posted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerEvent {
+    /// A deposit (valid or not) is received by the custody
+    CustodyIn,
+    /// The wrapped tokens are minted for a deposit
+    WrappedMinted,
+    /// The wrapped tokens are returned for a withdrawal
+    WrappedReturned,
+    /// The DePC is paid out for a withdrawal
+    Payout,
+    /// An invalid deposit is returned to its sender
+    Refund,
+    /// The fee of a payout, a refund or a sweep
+    Fee,
+    /// The excess of the custody is swept to the cold address
+    Sweep,
+    /// The bridge fee kept from a deposit
+    DepositFee,
+    /// The bridge fee kept from a withdrawal
+    WithdrawFee,
+    /// The bridge fees are swept to the treasury
+    FeeSweep,
+    /// The tokens stranded in the auxiliary accounts are consolidated to the authority
+    DustSweep,
+    /// The fee added to a stuck payout by a replacement or a child transaction
+    FeeBump,
+    /// The lamports the authority pays for the Solana transaction minting a batch of deposits
+    MintFee,
+}
+
+impl LedgerEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerEvent::CustodyIn => "custody_in",
+            LedgerEvent::WrappedMinted => "wrapped_minted",
+            LedgerEvent::WrappedReturned => "wrapped_returned",
+            LedgerEvent::Payout => "payout",
+            LedgerEvent::Refund => "refund",
+            LedgerEvent::Fee => "fee",
+            LedgerEvent::Sweep => "sweep",
+            LedgerEvent::DepositFee => "deposit_fee",
+            LedgerEvent::WithdrawFee => "withdraw_fee",
+            LedgerEvent::FeeSweep => "fee_sweep",
+            LedgerEvent::DustSweep => "dust_sweep",
+            LedgerEvent::FeeBump => "fee_bump",
+            LedgerEvent::MintFee => "mint_fee",
+        }
+    }
+}
+
+impl fmt::Display for LedgerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The double-entry ledger of a bridge, it's posted to table `ledger_entries` as the movements
+/// happen, so the bridge is reconciled from the ledger instead of the operational tables
+///
+/// The wrapped tokens have the decimals of DePC, so all the accounts are in DePC. The entries
+/// posted while syncing are in the database transaction of the block, the ones of the workers
+/// are posted right after the movements are made. Each movement is recorded to table `events`
+/// with the entries as well, it's served by `/bridge/events`.
+#[derive(Clone)]
+pub struct Ledger {
+    conn: db::Conn,
+}
+
+impl Ledger {
+    pub fn new(conn: db::Conn) -> Ledger {
+        Ledger { conn }
+    }
+
+    fn post(
+        &self,
+        event: LedgerEvent,
+        debit: &str,
+        credit: &str,
+        amount: DepcAmount,
+        reference: &str,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        self.conn
+            .add_ledger_entry(event.as_str(), debit, credit, amount, reference, timestamp)?;
+        Ok(())
+    }
+
+    /// Record the movement to the event feed of the bridge, the fees are posted only
+    fn record(
+        &self,
+        event: LedgerEvent,
+        reference: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.conn
+            .add_event(event.as_str(), amount, reference, timestamp)?;
+        Ok(())
+    }
+
+    /// The deposit of DePC transaction `txid` is received, it's either minted or refunded later
+    pub fn custody_in(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::CustodyIn,
+            ACCOUNT_CUSTODY,
+            ACCOUNT_DEPOSITS_DUE,
+            amount,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::CustodyIn, txid, amount, timestamp)
+    }
+
+    /// The wrapped tokens of a deposit are minted by Solana transaction `signature`
+    pub fn wrapped_minted(
+        &self,
+        signature: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::WrappedMinted,
+            ACCOUNT_DEPOSITS_DUE,
+            ACCOUNT_WRAPPED_SUPPLY,
+            amount,
+            signature,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::WrappedMinted, signature, amount, timestamp)
+    }
+
+    /// The wrapped tokens are returned for the withdrawal of DePC transaction `txid`
+    pub fn wrapped_returned(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::WrappedReturned,
+            ACCOUNT_WRAPPED_SUPPLY,
+            ACCOUNT_WITHDRAWALS_DUE,
+            amount,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::WrappedReturned, txid, amount, timestamp)
+    }
+
+    /// A withdrawal is paid out by `txid`, the fee is paid by the bridge
+    pub fn payout(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        fee: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::Payout,
+            ACCOUNT_WITHDRAWALS_DUE,
+            ACCOUNT_CUSTODY,
+            amount,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::Payout, txid, amount, timestamp)?;
+        self.post(
+            LedgerEvent::Fee,
+            ACCOUNT_NETWORK_FEES,
+            ACCOUNT_CUSTODY,
+            fee,
+            txid,
+            timestamp,
+        )
+    }
+
+    /// An invalid deposit is refunded by `txid`, the fee is paid from the deposit
+    pub fn refund(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        fee: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::Refund,
+            ACCOUNT_DEPOSITS_DUE,
+            ACCOUNT_CUSTODY,
+            amount,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::Refund, txid, amount, timestamp)?;
+        self.post(
+            LedgerEvent::Fee,
+            ACCOUNT_DEPOSITS_DUE,
+            ACCOUNT_CUSTODY,
+            fee,
+            txid,
+            timestamp,
+        )
+    }
+
+    /// The excess of the custody is swept to the cold address by `txid`
+    pub fn sweep(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        fee: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::Sweep,
+            ACCOUNT_COLD_STORAGE,
+            ACCOUNT_CUSTODY,
+            amount,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::Sweep, txid, amount, timestamp)?;
+        self.post(
+            LedgerEvent::Fee,
+            ACCOUNT_NETWORK_FEES,
+            ACCOUNT_CUSTODY,
+            fee,
+            txid,
+            timestamp,
+        )
+    }
+
+    /// The bridge fee of the deposit of DePC transaction `txid` is kept from the wrapped tokens
+    pub fn deposit_fee(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::DepositFee,
+            ACCOUNT_DEPOSITS_DUE,
+            ACCOUNT_BRIDGE_FEES,
+            amount,
+            txid,
+            timestamp,
+        )
+    }
+
+    /// The bridge fee of the withdrawal of DePC transaction `txid` is kept from the payout
+    pub fn withdraw_fee(
+        &self,
+        txid: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::WithdrawFee,
+            ACCOUNT_WITHDRAWALS_DUE,
+            ACCOUNT_BRIDGE_FEES,
+            amount,
+            txid,
+            timestamp,
+        )
+    }
+
+    /// The bridge fees are swept to the treasury by Solana transaction `signature`
+    pub fn fee_sweep(
+        &self,
+        signature: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::FeeSweep,
+            ACCOUNT_BRIDGE_FEES,
+            ACCOUNT_FEE_TREASURY,
+            amount,
+            signature,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::FeeSweep, signature, amount, timestamp)
+    }
+
+    /// The tokens stranded in the auxiliary accounts are consolidated to the token account of
+    /// the authority by Solana transaction `signature`. Both are held by the authority, so it's
+    /// recorded to the event feed only, no entry is posted
+    pub fn dust_sweep(
+        &self,
+        signature: &str,
+        amount: DepcAmount,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.record(LedgerEvent::DustSweep, signature, amount, timestamp)
+    }
+
+    /// A stuck payout is bumped by `txid` with the extra `fee`, the payout itself is posted
+    /// once by `payout` and never again
+    pub fn fee_bump(&self, txid: &str, fee: DepcAmount, timestamp: u64) -> Result<(), BridgeError> {
+        self.post(
+            LedgerEvent::FeeBump,
+            ACCOUNT_NETWORK_FEES,
+            ACCOUNT_CUSTODY,
+            fee,
+            txid,
+            timestamp,
+        )?;
+        self.record(LedgerEvent::FeeBump, txid, fee, timestamp)
+    }
+
+    /// Solana transaction `signature` minting the deposits costs the authority `lamports`. It's
+    /// recorded to the event feed only, the accounts are in DePC and the lamports are not
+    pub fn mint_fee(
+        &self,
+        signature: &str,
+        lamports: u64,
+        timestamp: u64,
+    ) -> Result<(), BridgeError> {
+        self.record(
+            LedgerEvent::MintFee,
+            signature,
+            DepcAmount::from_value64(lamports),
+            timestamp,
+        )
+    }
+
+    /// The bridge fees kept and not swept to the treasury yet
+    pub fn unswept_fees(&self) -> Result<DepcAmount, BridgeError> {
+        let balance = -self.trial_balance()?.balance_of(ACCOUNT_BRIDGE_FEES);
+        Ok(DepcAmount::from_value64(balance.max(0) as u64))
+    }
+
+    pub fn trial_balance(&self) -> Result<TrialBalance, BridgeError> {
+        let mut trial_balance = TrialBalance::default();
+        for totals in self.conn.query_ledger_account_totals()? {
+            trial_balance.total_debit = trial_balance
+                .total_debit
+                .checked_add(totals.debit)
+                .ok_or(BridgeError::AmountOverflow)?;
+            trial_balance.total_credit = trial_balance
+                .total_credit
+                .checked_add(totals.credit)
+                .ok_or(BridgeError::AmountOverflow)?;
+            trial_balance.accounts.push(totals);
+        }
+        Ok(trial_balance)
+    }
+}
+
+/// The totals of the accounts of the ledger, the debits equal the credits unless the ledger
+/// is broken
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrialBalance {
+    pub accounts: Vec<db::LedgerAccountTotals>,
+    pub total_debit: DepcAmount,
+    pub total_credit: DepcAmount,
+}
+
+impl TrialBalance {
+    pub fn is_balanced(&self) -> bool {
+        self.total_debit == self.total_credit
+    }
+
+    /// The debit balance of `account`, it's negative for a credit balance
+    pub fn balance_of(&self, account: &str) -> i128 {
+        self.accounts
+            .iter()
+            .find(|totals| totals.account == account)
+            .map_or(0, |totals| {
+                totals.debit.value64() as i128 - totals.credit.value64() as i128
+            })
+    }
+}
+
+impl fmt::Display for TrialBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:>20} {:>20}", "account", "debit", "credit")?;
+        for totals in self.accounts.iter() {
+            writeln!(
+                f,
+                "{:<16} {:>20} {:>20}",
+                totals.account,
+                totals.debit.to_string(),
+                totals.credit.to_string()
+            )?;
+        }
+        write!(
+            f,
+            "{:<16} {:>20} {:>20}",
+            "total",
+            self.total_debit.to_string(),
+            self.total_credit.to_string()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+    const COIN: u64 = 100_000_000;
+
+    fn depc(value64: u64) -> DepcAmount {
+        DepcAmount::from_value64(value64)
+    }
+
+    #[test]
+    fn test_trial_balance() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let ledger = Ledger::new(conn.clone());
+        assert_eq!(ledger.trial_balance().unwrap(), TrialBalance::default());
+
+        let fee = depc(100_000);
+        ledger
+            .custody_in("deposit", depc(10 * COIN), TEST_NOW)
+            .unwrap();
+        ledger
+            .wrapped_minted("mint", depc(10 * COIN), TEST_NOW)
+            .unwrap();
+        ledger.custody_in("invalid", depc(COIN), TEST_NOW).unwrap();
+        ledger
+            .refund("refund", depc(COIN - 100_000), fee, TEST_NOW)
+            .unwrap();
+        ledger
+            .wrapped_returned("withdraw", depc(4 * COIN), TEST_NOW)
+            .unwrap();
+        ledger
+            .payout("payout", depc(4 * COIN), fee, TEST_NOW)
+            .unwrap();
+        ledger.sweep("sweep", depc(COIN), fee, TEST_NOW).unwrap();
+
+        let trial_balance = ledger.trial_balance().unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(trial_balance.total_debit, depc(31 * COIN + 200_000));
+        let accounts: Vec<_> = trial_balance
+            .accounts
+            .iter()
+            .map(|totals| totals.account.as_str())
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                ACCOUNT_COLD_STORAGE,
+                ACCOUNT_CUSTODY,
+                ACCOUNT_DEPOSITS_DUE,
+                ACCOUNT_NETWORK_FEES,
+                ACCOUNT_WITHDRAWALS_DUE,
+                ACCOUNT_WRAPPED_SUPPLY,
+            ]
+        );
+        // the custody keeps the backing of the wrapped tokens in circulation
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_CUSTODY),
+            (5 * COIN - 200_000) as i128
+        );
+        assert_eq!(
+            trial_balance.balance_of(ACCOUNT_WRAPPED_SUPPLY),
+            -((6 * COIN) as i128)
+        );
+        assert_eq!(trial_balance.balance_of(ACCOUNT_DEPOSITS_DUE), 0);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_WITHDRAWALS_DUE), 0);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_COLD_STORAGE), COIN as i128);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_NETWORK_FEES), 200_000);
+
+        // the other bridges have their own ledgers
+        let other = Ledger::new(conn.scoped("other"));
+        assert_eq!(other.trial_balance().unwrap(), TrialBalance::default());
+        assert_eq!(conn.query_ledger_entries(100).unwrap().len(), 10);
+
+        // the fees are not the events
+        let events = conn.query_events(0, 100).unwrap();
+        let names: Vec<_> = events.iter().map(|event| event.event.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "custody_in",
+                "wrapped_minted",
+                "custody_in",
+                "refund",
+                "wrapped_returned",
+                "payout",
+                "sweep",
+            ]
+        );
+        assert_eq!(events[5].reference, "payout");
+        assert_eq!(events[5].amount, depc(4 * COIN));
+        assert!(events.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+        let since = conn.query_events(events[4].seq, 100).unwrap();
+        assert_eq!(since.len(), 2);
+        assert_eq!(since[0].seq, events[5].seq);
+        assert!(other.conn.query_events(0, 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_bridge_fees() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let ledger = Ledger::new(conn.clone());
+        assert!(ledger.unswept_fees().unwrap().is_zero());
+
+        ledger
+            .custody_in("deposit", depc(10 * COIN), TEST_NOW)
+            .unwrap();
+        ledger
+            .wrapped_minted("mint", depc(10 * COIN - 1000), TEST_NOW)
+            .unwrap();
+        ledger.deposit_fee("deposit", depc(1000), TEST_NOW).unwrap();
+        ledger
+            .wrapped_returned("withdraw", depc(4 * COIN), TEST_NOW)
+            .unwrap();
+        ledger
+            .withdraw_fee("withdraw", depc(500), TEST_NOW)
+            .unwrap();
+        ledger
+            .payout("payout", depc(4 * COIN - 500), depc(100_000), TEST_NOW)
+            .unwrap();
+        assert_eq!(ledger.unswept_fees().unwrap(), depc(1500));
+
+        ledger.fee_sweep("fee-sweep", depc(1200), TEST_NOW).unwrap();
+        assert_eq!(ledger.unswept_fees().unwrap(), depc(300));
+        let trial_balance = ledger.trial_balance().unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(trial_balance.balance_of(ACCOUNT_DEPOSITS_DUE), 0);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_WITHDRAWALS_DUE), 0);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_BRIDGE_FEES), -300);
+        assert_eq!(trial_balance.balance_of(ACCOUNT_FEE_TREASURY), -1200);
+
+        // the bridge fees are not the events, the sweep is
+        let events = conn.query_events(0, 100).unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[4].event, "fee_sweep");
+        assert_eq!(events[4].reference, "fee-sweep");
+    }
+}
@@ -0,0 +1,3 @@
+mod ledger;
+
+pub use ledger::*;
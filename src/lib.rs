@@ -0,0 +1,63 @@
+//! # DePINC Bridge
+//!
+//! The bridge between DePC on the DePINC chain and the spl-token on Solana, the binary
+//! `depc-bridge` is a thin command line wrapper of this library.
+//!
+//! The other services (explorers, exchange backends) can embed the indexer and the clients:
+//!
+//! * [`amount`] - The amounts of DePC and the Solana tokens with checked arithmetic
+//! * [`analysis`] - The analysis of the exchange addresses from the indexed coins
+//! * [`depc`] - The JSON-RPC client of the DePINC node and the decoder of the bridge scripts
+//! * [`solana`] - The spl-token client and the transaction analyzer of Solana
+//! * [`db`] - The local SQLite database the blocks, coins and bridge records are indexed to
+//! * [`bridge`] - The syncing loop and the deposit/withdrawal processing
+//! * [`ledger`] - The double-entry ledger of the bridge movements
+//! * [`price`] - The fiat prices of DePC from the pluggable price feeds
+//! * [`jobs`] - The scheduler of the periodic jobs
+//! * [`service`] - The business rules the web service and the command line share
+//! * [`rest`] - The web service and the types of its requests and responses
+//! * `client` - The typed client of the web service, behind the feature `client`
+//! * [`watch`] - The watched addresses and the notifications of their activities
+//!
+//! ```no_run
+//! use depc_bridge::{db, depc};
+//!
+//! let client = depc::ClientBuilder::new()
+//!     .set_endpoint("http://127.0.0.1:18732")
+//!     .set_auth("user:passwd")
+//!     .build();
+//! let conn = db::Conn::open_or_create("depc-bridge.sqlite3").unwrap();
+//! conn.init().unwrap();
+//! println!("chain height {}", client.get_height().unwrap());
+//! ```
+//!
+//! The public API follows semantic versioning, the breaking changes are listed in
+//! `CHANGELOG.md`. The modules behind the features `testing` and `chaos` are for tests only
+//! and not covered by the versioning.
+
+pub mod amount;
+pub mod analysis;
+pub mod bridge;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod clock;
+pub mod db;
+pub mod depc;
+pub mod jobs;
+pub mod ledger;
+pub mod price;
+pub mod rest;
+pub mod service;
+pub mod solana;
+pub mod watch;
+
+mod error;
+
+mod rpc;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use error::{BridgeError, ErrorCode};
@@ -1,41 +1,107 @@
-mod depc;
-mod solana;
-
-mod bridge;
-
-mod db;
-mod rpc;
-
 mod args;
 mod cmds;
 
-mod rest;
-
 use std::{
+    process::ExitCode,
     str::FromStr,
     sync::{Arc, Mutex},
 };
 
-use anyhow::Result;
-use bridge::Bridge;
+use anyhow::{bail, Result};
 use clap::Parser;
-use log::{debug, info};
-use rest::run_service;
+use depc_bridge::{
+    amount::DepcAmount,
+    analysis::build_address_clusters,
+    bridge::{
+        bootstrap_from_checkpoint, import_custody_watch_only, make_deposit_root_memo,
+        publish_deposit_root, run_custody_cross_check, run_dust_sweep, run_preflight,
+        run_sanity_check, scan_signatures, set_deposit_xpub, Bridge, BridgeSettings, CatchUpConfig,
+        FeeBumpConfig, FeeConfig, FeeSchedule, ListenerRoutes, ListenerSettings, LiveSettings,
+        MintRetryConfig, PipelineConfig, RefundConfig, RunMode, SweepConfig, TransferLimits,
+        DEFAULT_STUCK_DEPOSIT_AGE,
+    },
+    clock::{system_clock, SlotTimeline},
+    db, depc,
+    jobs::{
+        run_scheduler, JobTask, JobsSettings, Scheduler, JOB_ADDRESS_CLUSTERS, JOB_DEPOSIT_ROOT,
+        JOB_SOLANA_SIGNATURES, JOB_SOLANA_SLOTS,
+    },
+    price::{run_price_recording, HttpPriceProvider, PriceOracle},
+    rest::{run_service, AdminConfig, Attestor, ReportSettings},
+    service::{BridgeService, ServiceError},
+    solana::{
+        AnalyzerPolicy, DbOutgoingStore, RpcBudget, RpcBudgetConfig, RpcPriority, SolanaClient,
+    },
+    watch::{run_watch_notifications, HttpWatchNotifier},
+    BridgeError, ErrorCode,
+};
+use log::{debug, error, info, warn};
 
 use args::{Args, Commands};
-use solana::SolanaClient;
 use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair};
+use spl_associated_token_account::get_associated_token_address;
+
+/// The error a command fails with besides the ones of the library
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+struct CommandError {
+    code: ErrorCode,
+    message: String,
+}
+
+/// The code of the error the command exits with, the scripts branch on it instead of the message
+fn error_code(e: &anyhow::Error) -> ErrorCode {
+    for cause in e.chain() {
+        if let Some(e) = cause.downcast_ref::<CommandError>() {
+            return e.code;
+        }
+        if let Some(e) = cause.downcast_ref::<ServiceError>() {
+            return e.code();
+        }
+        if let Some(e) = cause.downcast_ref::<BridgeError>() {
+            return e.code();
+        }
+        if cause.is::<rusqlite::Error>() {
+            return ErrorCode::DatabaseFailed;
+        }
+    }
+    ErrorCode::Internal
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     env_logger::init();
     debug!("debug mode");
 
-    let args = Args::parse();
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error[{}]: {:#}", error_code(&e), e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
+async fn run(args: Args) -> Result<()> {
     match args.command {
         Commands::Run(args) => {
-            let client = if args.depc_rpc_use_cookie {
+            let pipeline_config = PipelineConfig {
+                deposit_workers: args.deposit_workers,
+                withdraw_workers: args.withdraw_workers,
+                deposit_channel_capacity: args.deposit_channel_capacity,
+                withdraw_channel_capacity: args.withdraw_channel_capacity,
+                db_batch_size: args.db_batch_size,
+                mint_batch_size: args.mint_batch_size,
+            };
+            pipeline_config.validate()?;
+            let mint_retry_config = MintRetryConfig {
+                base_delay: std::time::Duration::from_secs(args.mint_retry_delay),
+                max_delay: std::time::Duration::from_secs(args.mint_retry_max_delay),
+                max_attempts: args.mint_max_attempts,
+            };
+            mint_retry_config.validate()?;
+
+            let builder = if args.depc_rpc_use_cookie {
                 let cookie_path = shellexpand::env(&args.depc_rpc_cookie_path).unwrap();
                 info!(
                     "prepare client with cookie file {} to {}",
@@ -44,8 +110,8 @@ async fn main() -> Result<()> {
                 depc::ClientBuilder::new()
                     .set_auth_from_cookie(&cookie_path)
                     .set_use_proxy(args.depc_rpc_use_proxy)
+                    .set_timeout(std::time::Duration::from_secs(args.depc_rpc_timeout))
                     .set_endpoint(&args.depc_rpc_endpoint)
-                    .build()
             } else {
                 info!(
                     "prepare client with user/passwd to {}",
@@ -55,38 +121,512 @@ async fn main() -> Result<()> {
                 depc::ClientBuilder::new()
                     .set_auth(&auth_str)
                     .set_use_proxy(args.depc_rpc_use_proxy)
+                    .set_timeout(std::time::Duration::from_secs(args.depc_rpc_timeout))
                     .set_endpoint(&args.depc_rpc_endpoint)
-                    .build()
             };
+            #[cfg(feature = "chaos")]
+            let builder = match args.chaos_depc.clone() {
+                Some(fault_config) => {
+                    log::warn!("injecting faults into DePC RPC calls, {:?}", fault_config);
+                    builder.set_fault_injector(depc_bridge::chaos::FaultInjector::new(fault_config))
+                }
+                None => builder,
+            };
+            let client = builder.set_replaceable(args.payout_replaceable).build();
 
             let db_path = shellexpand::env(&args.local_db).unwrap();
             let conn = db::Conn::open_or_create(&db_path).unwrap();
             conn.init()?;
             info!("connected to local database, path {}", db_path);
+            let report = run_sanity_check(&conn, system_clock().now(), DEFAULT_STUCK_DEPOSIT_AGE)?;
+            if report.is_sane() {
+                info!("the sanity check of the database passes\n{report}");
+            } else {
+                warn!("the sanity check of the database finds problems\n{report}");
+            }
+
+            if let (Some(height), Some(hash)) = (args.start_height, args.start_hash.as_ref()) {
+                let now = system_clock().now();
+                bootstrap_from_checkpoint(&conn, &client, height, hash, now)?;
+            }
 
             let exit_sig = Arc::new(Mutex::new(false));
 
+            let mut analyzer_policy = AnalyzerPolicy {
+                strict: args.sol_analyzer_strict,
+                ..Default::default()
+            };
+            for entry in args.sol_program_allowlist.iter() {
+                analyzer_policy
+                    .allow(entry)
+                    .map_err(|e| BridgeError::InvalidConfig(e.to_string()))?;
+            }
+
+            let rpc_budget = args.sol_rpc_rate.map(|rate| {
+                let config = RpcBudgetConfig::new(
+                    rate,
+                    args.sol_rpc_reserve,
+                    std::time::Duration::from_millis(args.sol_rpc_query_wait),
+                );
+                info!(
+                    "the calls to solana are limited to {} per second, {} of them are kept for \
+                     the bridge",
+                    config.calls_per_sec, config.reserve
+                );
+                Arc::new(RpcBudget::new(config))
+            });
+            // the clients of the bridge and the ones of the REST queries share the budget, the
+            // RPC client is made again so the fault injection is not kept
+            let with_budget =
+                |client: SolanaClient, priority: RpcPriority| match rpc_budget.as_ref() {
+                    Some(budget) => client.set_rpc_budget(Arc::clone(budget), priority),
+                    None => client,
+                };
+
+            let index_only = args.mode == RunMode::IndexOnly;
+            // the clients of the index-only mode never sign, a throwaway key stands for the
+            // authority so no key of the custody is loaded
+            let authority_key = |key: Option<&str>| -> Result<Keypair> {
+                match key {
+                    _ if index_only => Ok(Keypair::new()),
+                    Some(key) => Ok(Keypair::from_base58_string(key)),
+                    None => bail!(BridgeError::InvalidConfig(
+                        "--sol-authority-key is required unless --mode is index-only".to_owned()
+                    )),
+                }
+            };
+            if index_only {
+                info!("index-only mode, nothing is bridged and the keys are not loaded");
+            }
+
             // create bridge here
             let sol_mint_pubkey = Pubkey::from_str(&args.sol_mint_pubkey).unwrap();
-            let sol_authority_key = Keypair::from_base58_string(&args.sol_authority_key);
+            let sol_authority_key = authority_key(args.sol_authority_key.as_deref())?;
+            #[cfg(feature = "chaos")]
+            let contract_client = match args.chaos_solana.clone() {
+                Some(fault_config) => {
+                    log::warn!("injecting faults into Solana RPC calls, {:?}", fault_config);
+                    SolanaClient::new_with_rpc_client(
+                        depc_bridge::solana::make_fault_rpc_client(
+                            &args.sol_endpoint,
+                            depc_bridge::chaos::FaultInjector::new(fault_config),
+                            CommitmentConfig::confirmed(),
+                        ),
+                        sol_mint_pubkey,
+                        sol_authority_key,
+                    )
+                }
+                None => SolanaClient::new(
+                    &args.sol_endpoint,
+                    sol_mint_pubkey,
+                    sol_authority_key,
+                    CommitmentConfig::confirmed(),
+                ),
+            };
+            #[cfg(not(feature = "chaos"))]
             let contract_client = SolanaClient::new(
                 &args.sol_endpoint,
                 sol_mint_pubkey,
                 sol_authority_key,
                 CommitmentConfig::confirmed(),
             );
-            let bridge = Bridge::<SolanaClient>::new(
+            let contract_client = with_budget(contract_client, RpcPriority::Critical)
+                .set_cache_ttl(std::time::Duration::from_secs(args.sol_cache_ttl))
+                .set_fetch_concurrency(args.sol_fetch_concurrency as usize)
+                .set_owner_store(Arc::new(conn.clone()))
+                .set_outgoing_store(Arc::new(DbOutgoingStore::new(conn.clone(), system_clock())))
+                .set_analyzer_policy(analyzer_policy.clone());
+            let mut custody_addresses = vec![args.depc_owner_address.clone()];
+            let mut bridge = Bridge::<SolanaClient>::new(
                 conn.clone(),
-                client,
+                client.clone(),
                 args.depc_owner_address,
-                args.solana_owner_address,
+                args.solana_owner_address.clone(),
                 contract_client.clone(),
             );
-            let bridge_handler = bridge.run();
+            // the calls abandoned by the REST requests end with them
+            let rest_timeout = std::time::Duration::from_secs(args.rest_upstream_timeout);
+            let mut other_bridges = vec![];
+            let settings_path = args
+                .settings
+                .as_ref()
+                .map(|path| shellexpand::env(path).unwrap().into_owned());
+            let settings = match settings_path.as_ref() {
+                Some(path) => BridgeSettings::load(path)?,
+                None => BridgeSettings::default(),
+            };
+            let mut deposit_xpubs = vec![];
+            if let Some(xpub) = args.deposit_xpub.as_ref() {
+                deposit_xpubs.push((conn.clone(), xpub.clone()));
+            }
+            for pair in settings.bridges.iter().cloned() {
+                info!(
+                    "bridge {} for custody address {} and mint {}",
+                    pair.bridge_id, pair.depc_owner_address, pair.sol_mint_pubkey
+                );
+                if let Some(xpub) = pair.deposit_xpub {
+                    deposit_xpubs.push((conn.scoped(&pair.bridge_id), xpub));
+                }
+                let pair_client = SolanaClient::new(
+                    &args.sol_endpoint,
+                    Pubkey::from_str(&pair.sol_mint_pubkey)?,
+                    authority_key(Some(&pair.sol_authority_key))?,
+                    CommitmentConfig::confirmed(),
+                );
+                let pair_client = with_budget(pair_client, RpcPriority::Critical)
+                    .set_cache_ttl(std::time::Duration::from_secs(args.sol_cache_ttl))
+                    .set_fetch_concurrency(args.sol_fetch_concurrency as usize)
+                    .set_owner_store(Arc::new(conn.clone()))
+                    .set_outgoing_store(Arc::new(DbOutgoingStore::new(
+                        conn.scoped(&pair.bridge_id),
+                        system_clock(),
+                    )))
+                    .set_analyzer_policy(analyzer_policy.clone());
+                custody_addresses.push(pair.depc_owner_address.clone());
+                bridge = bridge.add_pair(
+                    &pair.bridge_id,
+                    pair.depc_owner_address,
+                    pair.solana_owner_address,
+                    pair_client.clone(),
+                );
+                let rest_client = pair_client.set_rpc_timeout(rest_timeout);
+                other_bridges.push((pair.bridge_id, with_budget(rest_client, RpcPriority::Query)));
+            }
+            for (conn, xpub) in deposit_xpubs.iter() {
+                let xpub = depc::ExtendedPubKey::from_str(xpub).map_err(BridgeError::from)?;
+                set_deposit_xpub(conn, &xpub)?;
+                info!(
+                    "deposit addresses of bridge {} are derived from {xpub}",
+                    conn.bridge_id()
+                );
+            }
+            bridge = bridge.set_deposit_addresses(!deposit_xpubs.is_empty());
+            // the listeners of the settings file replace `--bind`
+            let mut listeners = if settings.listeners.is_empty() {
+                vec![ListenerSettings::new(&args.bind)]
+            } else {
+                settings.listeners.clone()
+            };
+            if index_only {
+                for listener in listeners.iter_mut() {
+                    listener.routes = ListenerRoutes::ReadOnly;
+                }
+            }
+            let live_settings = Arc::new(LiveSettings::new(settings_path, settings));
+            #[cfg(unix)]
+            tokio::spawn(depc_bridge::bridge::reload_settings_on_sighup(Arc::clone(
+                &live_settings,
+            )));
+            // clap requires the ceiling with the cold address
+            if let (Some(cold_address), Some(hot_ceiling)) =
+                (args.sweep_cold_address, args.sweep_hot_ceiling)
+            {
+                info!("sweeping custody DePC exceeds {hot_ceiling} to cold address {cold_address}");
+                bridge = bridge.set_sweep_config(SweepConfig {
+                    cold_address,
+                    hot_ceiling: DepcAmount::from_value64(hot_ceiling),
+                    unsigned_only: args.sweep_unsigned_only,
+                    interval: std::time::Duration::from_secs(args.sweep_interval),
+                });
+            }
+            if let Some(delay) = args.refund_delay {
+                info!("refunding the invalid deposits after {delay}s");
+                bridge = bridge.set_refund_config(RefundConfig {
+                    delay: std::time::Duration::from_secs(delay),
+                    interval: std::time::Duration::from_secs(args.refund_interval),
+                });
+            }
+            if let Some(after_blocks) = args.fee_bump_after_blocks {
+                info!("bumping the fees of the payouts unconfirmed after {after_blocks} blocks");
+                bridge = bridge.set_fee_bump_config(FeeBumpConfig {
+                    after_blocks,
+                    fee_step: DepcAmount::from_value64(args.fee_bump_step),
+                    max_fee: DepcAmount::from_value64(args.fee_bump_max_fee),
+                    interval: std::time::Duration::from_secs(args.fee_bump_interval),
+                });
+            }
+            if let Some(window) = args.deposit_intent_window {
+                info!("matching the plain payments to the deposit intents within {window}s");
+                bridge = bridge.set_deposit_intent_window(std::time::Duration::from_secs(window));
+            }
+            bridge =
+                bridge.set_inflow_alert_amount(DepcAmount::from_value64(args.inflow_alert_amount));
+            bridge = bridge.set_fee_config(FeeConfig {
+                deposit: FeeSchedule {
+                    flat: DepcAmount::from_value64(args.deposit_fee_flat),
+                    rate_bps: args.deposit_fee_bps,
+                },
+                withdraw: FeeSchedule {
+                    flat: DepcAmount::from_value64(args.withdraw_fee_flat),
+                    rate_bps: args.withdraw_fee_bps,
+                },
+            });
+            bridge = bridge.set_transfer_limits(TransferLimits {
+                min: DepcAmount::from_value64(args.transfer_min),
+                max: args.transfer_max.map(DepcAmount::from_value64),
+                daily_cap: args.daily_recipient_cap.map(DepcAmount::from_value64),
+            });
+            bridge =
+                bridge.set_custody_overlap(std::time::Duration::from_secs(args.custody_overlap));
+            bridge = bridge.set_depc_decimals(args.depc_decimals);
+            let custody_multisig = if args.custody_multisig_keys.is_empty() {
+                None
+            } else {
+                Some(depc::MultisigScript::new(
+                    args.custody_multisig_required,
+                    &args.custody_multisig_keys,
+                )?)
+            };
+            if let Some(multisig) = custody_multisig.clone() {
+                info!("the custody is the P2SH address of the {multisig} script");
+                bridge = bridge.set_custody_multisig(multisig);
+            }
+            if let Some(min_blocks) = args.catch_up_blocks {
+                info!("catching up when the bridge starts {min_blocks} blocks behind or more");
+                bridge = bridge.set_catch_up_config(CatchUpConfig {
+                    min_blocks,
+                    confirm_actions: args.catch_up_confirm_actions,
+                    confirm_amount: DepcAmount::from_value64(args.catch_up_confirm_amount),
+                });
+            }
+            if args.shadow {
+                info!("shadow mode, the actions are recorded to table `shadow_actions` only");
+            }
+            // the authorities of the index-only mode are not the ones of the mints
+            let mut solana_clients = vec![];
+            if !index_only {
+                solana_clients.push((db::DEFAULT_BRIDGE_ID.to_owned(), contract_client.clone()));
+                solana_clients.extend(other_bridges.iter().cloned());
+            }
+            let failures = run_preflight(&conn, &client, &solana_clients);
+            if !failures.is_empty() {
+                for failure in failures.iter() {
+                    error!("preflight check fails, {failure}");
+                }
+                bail!(CommandError {
+                    code: ErrorCode::PreflightFailed,
+                    message: format!("{} preflight checks fail", failures.len()),
+                });
+            }
+            if let Some(import) = args.watch_only_import {
+                for address in custody_addresses.iter() {
+                    import_custody_watch_only(&client, address, import, args.watch_only_rescan)?;
+                    info!("custody address {address} is imported to the DePC node as watch-only");
+                }
+                tokio::spawn(run_custody_cross_check(
+                    Arc::clone(&exit_sig),
+                    conn.clone(),
+                    client.clone(),
+                    custody_addresses,
+                    std::time::Duration::from_secs(args.custody_check_interval),
+                    system_clock(),
+                ));
+            }
+            if let Some(interval) = args.dust_sweep_interval.filter(|_| !index_only) {
+                info!("sweeping the stranded tokens every {interval}s");
+                tokio::spawn(run_dust_sweep(
+                    Arc::clone(&exit_sig),
+                    conn.clone(),
+                    contract_client.clone(),
+                    std::time::Duration::from_secs(interval),
+                    system_clock(),
+                ));
+            }
+            let bridge_handler = tokio::spawn(
+                bridge
+                    .set_pipeline_config(pipeline_config)
+                    .set_mint_retry_config(mint_retry_config)
+                    .set_shadow(args.shadow)
+                    .set_mode(args.mode)
+                    .set_exit_sig(Arc::clone(&exit_sig))
+                    .run(),
+            );
+
+            let price_oracle = args.price_url.map(|price_url| {
+                let provider = HttpPriceProvider::new(&price_url, &args.price_pointer);
+                Arc::new(PriceOracle::new(conn.clone(), Box::new(provider)))
+            });
+            if let Some(price_oracle) = price_oracle.as_ref() {
+                let currencies = args
+                    .price_currencies
+                    .split(',')
+                    .map(|currency| currency.trim().to_lowercase())
+                    .filter(|currency| !currency.is_empty())
+                    .collect::<Vec<_>>();
+                info!("recording the prices of DePC in {}", currencies.join(","));
+                tokio::spawn(run_price_recording(
+                    Arc::clone(&exit_sig),
+                    Arc::clone(price_oracle),
+                    currencies,
+                    std::time::Duration::from_secs(args.price_interval),
+                ));
+            }
+
+            if let Some(jobs_path) = args.jobs.as_ref() {
+                let jobs_path = shellexpand::env(jobs_path).unwrap();
+                let mut scheduler = Scheduler::new(conn.clone(), system_clock());
+                for job in JobsSettings::load(&jobs_path)?.jobs {
+                    let task: JobTask = match job.name.as_str() {
+                        JOB_ADDRESS_CLUSTERS => {
+                            let conn = conn.clone();
+                            Arc::new(move || {
+                                let report = build_address_clusters(&conn)?;
+                                info!(
+                                    "{} addresses are grouped into {} clusters",
+                                    report.addresses, report.clusters
+                                );
+                                Ok(())
+                            })
+                        }
+                        JOB_SOLANA_SLOTS => {
+                            let timeline = SlotTimeline::new(conn.clone());
+                            let contract_client = contract_client.clone();
+                            Arc::new(move || {
+                                let sample = contract_client.get_slot_sample()?;
+                                debug!("solana slot {} at {}", sample.slot, sample.block_time);
+                                timeline.record(&sample)
+                            })
+                        }
+                        JOB_SOLANA_SIGNATURES => {
+                            let conn = conn.clone();
+                            let contract_client = contract_client.clone();
+                            let clock = system_clock();
+                            // the withdrawals transfer the tokens to the token account of the owner
+                            let account = get_associated_token_address(
+                                &Pubkey::from_str(&args.solana_owner_address)?,
+                                &contract_client.mint_pubkey(),
+                            );
+                            Arc::new(move || {
+                                scan_signatures(
+                                    &conn,
+                                    &account.to_string(),
+                                    clock.now(),
+                                    |until| {
+                                        Ok(contract_client.get_signatures_until(&account, until)?)
+                                    },
+                                    |signature, slot| {
+                                        let signature = signature.to_string();
+                                        if conn.query_withdraw_signature(&signature)?.is_none() {
+                                            debug!(
+                                                "transfer {signature} at slot {slot} is not \
+                                                 used by a withdrawal yet"
+                                            );
+                                        }
+                                        Ok(())
+                                    },
+                                )?;
+                                Ok(())
+                            })
+                        }
+                        JOB_DEPOSIT_ROOT => {
+                            let conn = conn.clone();
+                            let contract_client = contract_client.clone();
+                            let clock = system_clock();
+                            let memo = args.deposit_root_memo && !index_only;
+                            Arc::new(move || {
+                                let Some(root) = publish_deposit_root(&conn, clock.now())? else {
+                                    return Ok(());
+                                };
+                                info!(
+                                    "deposit root {} of {} deposits is published",
+                                    root.root, root.leaf_count
+                                );
+                                if memo {
+                                    let signature = contract_client.send_memo(
+                                        &make_deposit_root_memo(conn.bridge_id(), &root),
+                                    )?;
+                                    conn.set_deposit_root_memo_signature(
+                                        root.id,
+                                        &signature.to_string(),
+                                    )?;
+                                }
+                                Ok(())
+                            })
+                        }
+                        name => bail!(BridgeError::InvalidConfig(format!("unknown job `{name}`"))),
+                    };
+                    info!("job {} runs at `{}`", job.name, job.schedule);
+                    scheduler = scheduler.add_job(&job, task)?;
+                }
+                tokio::spawn(run_scheduler(Arc::clone(&exit_sig), scheduler));
+            }
+
+            // the payloads written as hex text by the older builds
+            tokio::spawn(db::run_payload_compression(
+                Arc::clone(&exit_sig),
+                conn.clone(),
+                std::time::Duration::from_secs(1),
+            ));
+
+            if let Some(depth) = args.archive_depth {
+                info!("archiving the coins spent {depth} blocks below the best height");
+                tokio::spawn(db::run_coins_archival(
+                    Arc::clone(&exit_sig),
+                    conn.clone(),
+                    depth,
+                    std::time::Duration::from_secs(args.archive_interval),
+                ));
+            }
+
+            tokio::spawn(run_watch_notifications(
+                Arc::clone(&exit_sig),
+                conn.clone(),
+                Arc::new(HttpWatchNotifier::new().set_use_proxy(args.webhook_use_proxy)),
+                std::time::Duration::from_secs(args.watch_interval),
+                system_clock(),
+            ));
 
             // running webservice
-            run_service(&args.bind, conn, contract_client.clone(), exit_sig).await;
-            bridge_handler.await.unwrap();
+            let fee_treasury = args
+                .fee_treasury
+                .as_deref()
+                .map(Pubkey::from_str)
+                .transpose()?;
+            if index_only && args.admin_token.is_some() {
+                warn!("the admin endpoints are not served in index-only mode");
+            }
+            let admin = args
+                .admin_token
+                .filter(|_| !index_only)
+                .map(|token| AdminConfig {
+                    token,
+                    timelock: std::time::Duration::from_secs(args.admin_timelock),
+                    fee_treasury,
+                    depc_decimals: args.depc_decimals,
+                });
+            let attestor = args.attestation_key.as_ref().map(|key| {
+                let attestor = Attestor::new(Keypair::from_base58_string(key));
+                info!(
+                    "the status and the summary are signed by {}",
+                    attestor.pubkey()
+                );
+                Arc::new(attestor)
+            });
+            let report = match args.report_settings.as_ref() {
+                Some(path) => ReportSettings::load(&shellexpand::env(path).unwrap())?,
+                None => ReportSettings::default(),
+            };
+            run_service(
+                &listeners,
+                conn,
+                with_budget(
+                    contract_client.clone().set_rpc_timeout(rest_timeout),
+                    RpcPriority::Query,
+                ),
+                other_bridges,
+                price_oracle,
+                admin,
+                custody_multisig,
+                report,
+                rest_timeout,
+                attestor,
+                live_settings,
+                exit_sig,
+            )
+            .await?;
+            if let Err(e) = bridge_handler.await.unwrap() {
+                error!("bridge exits with error: {}", e.full_message());
+            }
 
             info!("exit.");
             Ok(())
@@ -94,5 +634,119 @@ async fn main() -> Result<()> {
         Commands::Deploy(_) => {
             todo!("complete this command")
         }
+        Commands::TrialBalance(args) => {
+            let conn = db::Conn::open_or_create(&shellexpand::env(&args.local_db)?)?;
+            conn.init()?;
+            let service = BridgeService::new(conn.scoped(&args.bridge_id), system_clock());
+            let trial_balance = service.trial_balance()?;
+            println!("{trial_balance}");
+            if !trial_balance.is_balanced() {
+                bail!(CommandError {
+                    code: ErrorCode::LedgerUnbalanced,
+                    message: format!(
+                        "the ledger is not balanced, debit {} credit {}",
+                        trial_balance.total_debit, trial_balance.total_credit
+                    ),
+                });
+            }
+            Ok(())
+        }
+        Commands::Status(args) => {
+            let conn = db::Conn::open_or_create(&shellexpand::env(&args.local_db)?)?;
+            conn.init()?;
+            let service = BridgeService::new(conn.scoped(&args.bridge_id), system_clock());
+            println!("{}", service.status()?);
+            Ok(())
+        }
+        Commands::CatchUp(args) => {
+            let conn = db::Conn::open_or_create(&shellexpand::env(&args.local_db)?)?;
+            conn.init()?;
+            let service = BridgeService::new(conn.scoped(&args.bridge_id), system_clock());
+            if args.confirm {
+                service.confirm_catch_up()?;
+            }
+            print!("{}", service.catch_up_report()?);
+            Ok(())
+        }
+        Commands::InspectScript(args) => {
+            let script_hex = match (args.script_hex, args.txid) {
+                (Some(script_hex), _) => script_hex,
+                (None, txid) => {
+                    let txid = txid.unwrap_or_default();
+                    let vout = args.vout.unwrap_or_default();
+                    let builder = depc::ClientBuilder::new().set_endpoint(&args.depc_rpc_endpoint);
+                    let builder = if args.depc_rpc_use_cookie {
+                        builder.set_auth_from_cookie(&shellexpand::env(&args.depc_rpc_cookie_path)?)
+                    } else {
+                        builder
+                            .set_auth(&format!("{}:{}", args.depc_rpc_user, args.depc_rpc_passwd))
+                    };
+                    let transaction = builder
+                        .build()
+                        .get_transaction(&txid)
+                        .map_err(BridgeError::from)?;
+                    match transaction.vout.into_iter().find(|out| out.n == vout) {
+                        Some(out) => out.script_pubkey.hex,
+                        None => bail!(CommandError {
+                            code: ErrorCode::InvalidParameter,
+                            message: format!("transaction {txid} has no output {vout}"),
+                        }),
+                    }
+                }
+            };
+            println!("script: {script_hex}");
+            match depc::decode_script_hex(&script_hex) {
+                Ok(data) if data.signatures.is_empty() => {
+                    println!("action: deposit");
+                    println!("recipient: {}", data.recipient);
+                }
+                Ok(data) => {
+                    println!("action: withdraw");
+                    println!("recipient: {}", data.recipient);
+                    for signature in data.signatures {
+                        println!("signature: {signature}");
+                    }
+                }
+                Err(e) => {
+                    // under the first hex digit of the byte the decoding stops at
+                    println!("{}^", " ".repeat("script: ".len() + 2 * e.offset));
+                    bail!(CommandError {
+                        code: ErrorCode::InvalidParameter,
+                        message: format!("the script cannot be decoded, {e}"),
+                    });
+                }
+            }
+            Ok(())
+        }
+        #[cfg(feature = "testing")]
+        Commands::BenchSync(args) => {
+            let chain = depc_bridge::testing::SyntheticChain {
+                num_blocks: args.blocks,
+                txs_per_block: args.txs_per_block,
+                outputs_per_tx: args.outputs_per_tx,
+            };
+            let node = depc_bridge::testing::FakeDepcNode::start();
+            depc_bridge::testing::push_synthetic_blocks(&node, chain);
+            let conn = match args.local_db {
+                Some(local_db) => db::Conn::open_or_create(&shellexpand::env(&local_db)?)?,
+                None => db::Conn::open_in_mem()?,
+            };
+            conn.init()?;
+            info!(
+                "syncing {} synthetic block(s), {} tx(s) per block, {} output(s) per tx",
+                args.blocks, args.txs_per_block, args.outputs_per_tx
+            );
+            let report = depc_bridge::testing::run_sync_bench(&node, conn, chain).await;
+            println!(
+                "synced {} block(s), {} tx(s), {} output(s) in {:.3}s, {:.1} blocks/s, {:.1} txs/s",
+                report.blocks,
+                report.transactions,
+                report.outputs,
+                report.elapsed.as_secs_f64(),
+                report.blocks_per_sec(),
+                report.transactions_per_sec()
+            );
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,5 @@
+mod oracle;
+mod provider;
+
+pub use oracle::*;
+pub use provider::*;
@@ -0,0 +1,145 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::time::sleep;
+
+use super::PriceProvider;
+use crate::amount::DepcAmount;
+use crate::clock::{system_clock, SharedClock};
+use crate::db;
+use crate::solana::TtlCache;
+use crate::BridgeError;
+
+pub const DEFAULT_PRICE_CACHE_TTL: Duration = Duration::from_secs(60);
+pub const DEFAULT_PRICE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The prices of DePC in the fiat currencies, the current prices are fetched from the provider
+/// and recorded to table `prices`, so the value of an event is reported with the price at the
+/// time it happened
+pub struct PriceOracle {
+    conn: db::Conn,
+    provider: Box<dyn PriceProvider>,
+    cache: TtlCache<String, f64>,
+    clock: SharedClock,
+}
+
+impl PriceOracle {
+    pub fn new(conn: db::Conn, provider: Box<dyn PriceProvider>) -> PriceOracle {
+        PriceOracle {
+            conn,
+            provider,
+            cache: TtlCache::new(DEFAULT_PRICE_CACHE_TTL),
+            clock: system_clock(),
+        }
+    }
+
+    pub fn set_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = TtlCache::new(ttl);
+        self
+    }
+
+    pub fn set_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Fetch the current price (cached for the TTL) and record it with the current time
+    pub fn record_price(&self, currency: &str) -> Result<f64, BridgeError> {
+        let currency = currency.to_lowercase();
+        let price = self
+            .cache
+            .get_or_try_insert(currency.clone(), || self.provider.current_price(&currency))?;
+        self.conn
+            .add_price(&currency, self.clock.now(), price, self.provider.name())?;
+        Ok(price)
+    }
+
+    /// The last price recorded at or before `timestamp`
+    pub fn price_at(&self, currency: &str, timestamp: u64) -> Result<Option<f64>, BridgeError> {
+        Ok(self
+            .conn
+            .query_price_at(&currency.to_lowercase(), timestamp)?)
+    }
+
+    /// The value of `amount` at `timestamp`, `None` when no price is recorded before it
+    pub fn fiat_value(
+        &self,
+        amount: DepcAmount,
+        currency: &str,
+        timestamp: u64,
+    ) -> Result<Option<f64>, BridgeError> {
+        const COIN: f64 = 100000000.0;
+        let price = self.price_at(currency, timestamp)?;
+        Ok(price.map(|price| amount.value64() as f64 / COIN * price))
+    }
+}
+
+/// Record the prices of `currencies` every `interval` until the exit signal is set
+pub async fn run_price_recording(
+    exit_sig: Arc<Mutex<bool>>,
+    oracle: Arc<PriceOracle>,
+    currencies: Vec<String>,
+    interval: Duration,
+) -> Result<(), BridgeError> {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        for currency in currencies.iter() {
+            match oracle.record_price(currency) {
+                Ok(price) => debug!("price of DePC is {price} {currency}"),
+                Err(e) => warn!(
+                    "cannot record price in {currency}, reason: {}",
+                    e.full_message()
+                ),
+            }
+        }
+        sleep(interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::price::FixedPriceProvider;
+    use crate::testing::MockClock;
+
+    #[test]
+    fn test_record_and_query_prices() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = MockClock::new(1000);
+        let provider = FixedPriceProvider::new(HashMap::from([("usd".to_owned(), 0.5)]));
+        let oracle =
+            PriceOracle::new(conn.clone(), Box::new(provider)).set_clock(Arc::new(clock.clone()));
+
+        assert_eq!(oracle.price_at("usd", 2000).unwrap(), None);
+        assert_eq!(oracle.record_price("USD").unwrap(), 0.5);
+        assert!(oracle.record_price("eur").is_err());
+        clock.advance(600);
+        conn.add_price("usd", clock.now(), 0.25, "manual").unwrap();
+
+        assert_eq!(oracle.price_at("usd", 999).unwrap(), None);
+        assert_eq!(oracle.price_at("usd", 1599).unwrap(), Some(0.5));
+        assert_eq!(oracle.price_at("usd", 5000).unwrap(), Some(0.25));
+        assert_eq!(
+            oracle
+                .fiat_value(DepcAmount::from_value64(3 * 100000000), "usd", 1000)
+                .unwrap(),
+            Some(1.5)
+        );
+        assert_eq!(
+            oracle
+                .fiat_value(DepcAmount::from_value64(100000000), "eur", 1000)
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use ureq::AgentBuilder;
+
+use crate::BridgeError;
+
+/// The source of the current price of DePC, the historical prices are recorded by
+/// `PriceOracle` as they're fetched
+pub trait PriceProvider: Send + Sync {
+    /// The name of the provider, it's recorded with the prices
+    fn name(&self) -> &str;
+
+    /// The price of 1 DePC in `currency` (lowercase, e.g. `usd`)
+    fn current_price(&self, currency: &str) -> Result<f64, BridgeError>;
+}
+
+/// The prices never change, for the pegged currencies and the tests
+pub struct FixedPriceProvider {
+    prices: HashMap<String, f64>,
+}
+
+impl FixedPriceProvider {
+    pub fn new(prices: HashMap<String, f64>) -> FixedPriceProvider {
+        FixedPriceProvider { prices }
+    }
+}
+
+impl PriceProvider for FixedPriceProvider {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    fn current_price(&self, currency: &str) -> Result<f64, BridgeError> {
+        self.prices
+            .get(currency)
+            .copied()
+            .ok_or_else(|| BridgeError::PriceFeed(format!("no price of {currency}")))
+    }
+}
+
+/// Query the price from a JSON API, `{currency}` in the url and the JSON pointer is replaced by
+/// the currency, e.g. `https://api.coingecko.com/api/v3/simple/price?ids=depinc&vs_currencies={currency}`
+/// with pointer `/depinc/{currency}`
+pub struct HttpPriceProvider {
+    url: String,
+    pointer: String,
+    use_proxy: bool,
+}
+
+impl HttpPriceProvider {
+    pub fn new(url: &str, pointer: &str) -> HttpPriceProvider {
+        HttpPriceProvider {
+            url: url.to_owned(),
+            pointer: pointer.to_owned(),
+            use_proxy: false,
+        }
+    }
+
+    pub fn set_use_proxy(mut self, use_proxy: bool) -> Self {
+        self.use_proxy = use_proxy;
+        self
+    }
+}
+
+impl PriceProvider for HttpPriceProvider {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn current_price(&self, currency: &str) -> Result<f64, BridgeError> {
+        let url = self.url.replace("{currency}", currency);
+        let agent = AgentBuilder::new()
+            .try_proxy_from_env(self.use_proxy)
+            .build();
+        let body = agent
+            .get(&url)
+            .call()
+            .map_err(|e| e.to_string())
+            .and_then(|resp| resp.into_string().map_err(|e| e.to_string()))
+            .map_err(|e| BridgeError::PriceFeed(format!("cannot query {url}, reason: {e}")))?;
+        let value: Value = serde_json::from_str(&body)
+            .map_err(|e| BridgeError::PriceFeed(format!("invalid response of {url}, {e}")))?;
+        extract_price(&value, &self.pointer.replace("{currency}", currency))
+    }
+}
+
+/// The price might be a number or a string of the number
+fn extract_price(value: &Value, pointer: &str) -> Result<f64, BridgeError> {
+    let price = match value.pointer(pointer) {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    };
+    price
+        .filter(|price| price.is_finite() && *price >= 0.0)
+        .ok_or_else(|| BridgeError::PriceFeed(format!("no valid price at {pointer}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_price() {
+        let value = json!({"depinc": {"usd": 0.0123, "eur": "0.011", "btc": -1}});
+        assert_eq!(extract_price(&value, "/depinc/usd").unwrap(), 0.0123);
+        assert_eq!(extract_price(&value, "/depinc/eur").unwrap(), 0.011);
+        assert!(extract_price(&value, "/depinc/btc").is_err());
+        assert!(extract_price(&value, "/depinc/jpy").is_err());
+
+        let provider = FixedPriceProvider::new(HashMap::from([("usd".to_owned(), 0.5)]));
+        assert_eq!(provider.current_price("usd").unwrap(), 0.5);
+        assert!(provider.current_price("eur").is_err());
+    }
+}
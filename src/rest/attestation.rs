@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+
+/// The field of the signed responses the attestation is put in
+pub const ATTESTATION_FIELD: &str = "attestation";
+
+/// Signs the statements of the bridge (`/bridge/info` and `/bridge/summary`) with a dedicated
+/// Ed25519 key, so the responses archived by third parties can be verified later
+///
+/// The signed message is the canonical JSON (the keys sorted, no whitespace) of
+/// `{"payload": <response without the attestation>, "signed_at": <unix seconds>}`, the
+/// signature is put in the response with the public key and the time, see `verify_attestation`.
+pub struct Attestor {
+    keypair: Keypair,
+}
+
+/// The signature of a response, check it with `verify_attestation`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub pubkey: String,
+    pub signed_at: u64,
+    pub signature: String,
+}
+
+impl Attestor {
+    pub fn new(keypair: Keypair) -> Self {
+        Attestor { keypair }
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Put the attestation of `payload` at `signed_at` into it, `payload` is an object
+    pub fn attest(&self, mut payload: Value, signed_at: u64) -> Value {
+        let message = make_attestation_message(&payload, signed_at);
+        let attestation = Attestation {
+            pubkey: self.keypair.pubkey().to_string(),
+            signed_at,
+            signature: self.keypair.sign_message(message.as_bytes()).to_string(),
+        };
+        if let Some(object) = payload.as_object_mut() {
+            object.insert(
+                ATTESTATION_FIELD.to_owned(),
+                serde_json::to_value(attestation).unwrap(),
+            );
+        }
+        payload
+    }
+}
+
+/// The message signed for `payload` at `signed_at`
+pub fn make_attestation_message(payload: &Value, signed_at: u64) -> String {
+    let mut message = String::new();
+    write_canonical_json(
+        &json!({"payload": payload, "signed_at": signed_at}),
+        &mut message,
+    );
+    message
+}
+
+/// Check the attestation of a signed response is made by `pubkey`
+pub fn verify_attestation(response: &Value, pubkey: &Pubkey) -> bool {
+    let mut payload = response.clone();
+    let Some(attestation) = payload
+        .as_object_mut()
+        .and_then(|object| object.remove(ATTESTATION_FIELD))
+    else {
+        return false;
+    };
+    let (Some(signed_at), Some(signature)) = (
+        attestation["signed_at"].as_u64(),
+        attestation["signature"]
+            .as_str()
+            .and_then(|signature| signature.parse::<Signature>().ok()),
+    ) else {
+        return false;
+    };
+    attestation["pubkey"].as_str() == Some(pubkey.to_string().as_str())
+        && signature.verify(
+            pubkey.as_ref(),
+            make_attestation_message(&payload, signed_at).as_bytes(),
+        )
+}
+
+/// The keys of the objects are sorted whatever order the map keeps
+fn write_canonical_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(object) => {
+            let mut keys: Vec<_> = object.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical_json(&object[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(value, out);
+            }
+            out.push(']');
+        }
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signer::keypair::keypair_from_seed;
+
+    use super::*;
+
+    #[test]
+    fn test_attestation() {
+        let payload = json!({"windows": [{"b": 1, "a": "x y"}], "generated_at": 1731000600});
+        assert_eq!(
+            make_attestation_message(&payload, 1731000700),
+            r#"{"payload":{"generated_at":1731000600,"windows":[{"a":"x y","b":1}]},"signed_at":1731000700}"#
+        );
+
+        let attestor = Attestor::new(keypair_from_seed(&[5; 32]).unwrap());
+        let response = attestor.attest(payload.clone(), 1731000700);
+        assert_eq!(response["generated_at"], payload["generated_at"]);
+        assert_eq!(response["attestation"]["signed_at"], 1731000700);
+        assert!(verify_attestation(&response, &attestor.pubkey()));
+
+        // the statement or the time is changed
+        let mut changed = response.clone();
+        changed["generated_at"] = json!(1731000601);
+        assert!(!verify_attestation(&changed, &attestor.pubkey()));
+        let mut changed = response.clone();
+        changed["attestation"]["signed_at"] = json!(1731000701);
+        assert!(!verify_attestation(&changed, &attestor.pubkey()));
+        // or it's signed by another key
+        let other = keypair_from_seed(&[6; 32]).unwrap();
+        assert!(!verify_attestation(&response, &other.pubkey()));
+        assert!(!verify_attestation(&payload, &attestor.pubkey()));
+    }
+}
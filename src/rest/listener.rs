@@ -0,0 +1,233 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use log::{debug, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+
+use crate::bridge::{ListenerSettings, TlsSettings};
+use crate::BridgeError;
+
+/// The stream of an accepted connection, plain or over TLS
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+/// A bound listener of the REST service
+pub enum BoundListener {
+    Tcp(TcpListener, Option<TlsAcceptor>),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+enum Accepted {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl BoundListener {
+    /// Bind the listener of `settings`, the socket file left by the last run is removed for a
+    /// Unix domain socket
+    pub async fn bind(settings: &ListenerSettings) -> Result<BoundListener, BridgeError> {
+        let bind = settings.bind.as_str();
+        if let Some(path) = settings.unix_path() {
+            return bind_unix(path);
+        }
+        let listener = TcpListener::bind(bind).await.map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot listen on {bind}, reason: {e}"))
+        })?;
+        let acceptor = settings.tls.as_ref().map(load_tls_acceptor).transpose()?;
+        Ok(BoundListener::Tcp(listener, acceptor))
+    }
+
+    async fn accept(&self) -> std::io::Result<Accepted> {
+        match self {
+            BoundListener::Tcp(listener, _) => listener
+                .accept()
+                .await
+                .map(|(stream, _)| Accepted::Tcp(stream)),
+            #[cfg(unix)]
+            BoundListener::Unix(listener) => listener
+                .accept()
+                .await
+                .map(|(stream, _)| Accepted::Unix(stream)),
+        }
+    }
+
+    fn tls_acceptor(&self) -> Option<TlsAcceptor> {
+        match self {
+            BoundListener::Tcp(_, acceptor) => acceptor.clone(),
+            #[cfg(unix)]
+            BoundListener::Unix(_) => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str) -> Result<BoundListener, BridgeError> {
+    match std::fs::remove_file(path) {
+        Ok(()) => info!("the socket file {path} of the last run is removed"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("cannot remove socket file {path}, reason: {e}"),
+    }
+    let listener = tokio::net::UnixListener::bind(path).map_err(|e| {
+        BridgeError::InvalidConfig(format!("cannot listen on unix:{path}, reason: {e}"))
+    })?;
+    Ok(BoundListener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &str) -> Result<BoundListener, BridgeError> {
+    Err(BridgeError::InvalidConfig(format!(
+        "cannot listen on unix:{path}, the unix domain sockets are not supported here"
+    )))
+}
+
+fn load_tls_acceptor(tls: &TlsSettings) -> Result<TlsAcceptor, BridgeError> {
+    let open = |path: &str| {
+        File::open(path).map(BufReader::new).map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot read TLS file {path}, reason: {e}"))
+        })
+    };
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert_path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            BridgeError::InvalidConfig(format!(
+                "cannot parse certificates {}, reason: {e}",
+                tls.cert_path
+            ))
+        })?;
+    let key = rustls_pemfile::private_key(&mut open(&tls.key_path)?)
+        .ok()
+        .flatten()
+        .ok_or_else(|| {
+            BridgeError::InvalidConfig(format!("no private key is found in {}", tls.key_path))
+        })?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| BridgeError::InvalidConfig(format!("invalid TLS certificate, {e}")))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serve `app` on the connections of `listener` until `shutdown` turns true, then wait for the
+/// requests in flight. The TLS handshakes are made on the tasks of the connections so a slow
+/// client doesn't hold the others
+pub async fn serve_listener(
+    listener: BoundListener,
+    app: Router,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let tls_acceptor = listener.tls_acceptor();
+    // every connection holds a receiver, they're all dropped when the connections are closed
+    let (close_tx, close_rx) = watch::channel(());
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.changed() => break,
+        };
+        let accepted = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // e.g. out of the file descriptors, the listener is kept
+                warn!("cannot accept a connection, reason: {e}");
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let app = app.clone();
+        let tls_acceptor = tls_acceptor.clone();
+        let shutdown = shutdown.clone();
+        let close_rx = close_rx.clone();
+        tokio::spawn(async move {
+            let stream: Box<dyn Connection> = match (accepted, tls_acceptor) {
+                (Accepted::Tcp(stream), Some(acceptor)) => match acceptor.accept(stream).await {
+                    Ok(stream) => Box::new(stream),
+                    Err(e) => {
+                        debug!("TLS handshake fails, reason: {e}");
+                        return;
+                    }
+                },
+                (Accepted::Tcp(stream), None) => Box::new(stream),
+                #[cfg(unix)]
+                (Accepted::Unix(stream), _) => Box::new(stream),
+            };
+            serve_connection(stream, app, shutdown).await;
+            drop(close_rx);
+        });
+    }
+    drop(close_rx);
+    close_tx.closed().await;
+}
+
+async fn serve_connection(
+    stream: Box<dyn Connection>,
+    app: Router,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let builder = Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection(TokioIo::new(stream), TowerToHyperService::new(app));
+    tokio::pin!(connection);
+    let result = tokio::select! {
+        result = connection.as_mut() => result,
+        _ = shutdown.changed() => {
+            connection.as_mut().graceful_shutdown();
+            connection.await
+        }
+    };
+    if let Err(e) = result {
+        debug!("the connection is closed, reason: {e}");
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    async fn get_root(path: &str) -> String {
+        let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        resp
+    }
+
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        let path = std::env::temp_dir().join(format!("depc-bridge-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        let settings = ListenerSettings::new(&format!("unix:{path}"));
+        let app = Router::new().route("/", get(|| async { "hello world" }));
+
+        // the socket file left by the last run is replaced
+        std::fs::write(&path, "").unwrap();
+        let listener = BoundListener::bind(&settings).await.unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let server = tokio::spawn(serve_listener(listener, app, shutdown_rx));
+
+        let resp = get_root(&path).await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with("hello world"));
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+        assert!(tokio::net::UnixStream::connect(&path).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,3 +1,14 @@
+mod attestation;
+mod listener;
+mod report;
 mod service;
+mod types;
 
-pub use service::*;
\ No newline at end of file
+#[cfg(test)]
+mod schema;
+
+pub use attestation::*;
+pub use listener::*;
+pub use report::*;
+pub use service::*;
+pub use types::*;
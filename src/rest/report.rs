@@ -0,0 +1,225 @@
+use chrono::{DateTime, FixedOffset};
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+
+use crate::db;
+use crate::BridgeError;
+
+/// The nominal interval of the DePC blocks, 3 minutes
+pub const DEFAULT_BLOCK_INTERVAL: u64 = 180;
+/// The first height of the exchange balance reports
+pub const DEFAULT_REPORT_START_HEIGHT: u32 = 860130;
+
+fn default_block_interval() -> u64 {
+    DEFAULT_BLOCK_INTERVAL
+}
+
+fn default_start_height() -> u32 {
+    DEFAULT_REPORT_START_HEIGHT
+}
+
+fn default_timezone() -> String {
+    "UTC".to_owned()
+}
+
+/// The settings of the exchange balance reports (`/exchange/balances/{days}`), each one can be
+/// overridden by the query parameter of the same name
+///
+/// ```json
+/// { "block_interval": 180, "start_height": 860130, "timezone": "+08:00" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ReportSettings {
+    /// The nominal interval of the blocks in seconds, the reports step by it only where the
+    /// block times are not indexed
+    #[serde(default = "default_block_interval")]
+    pub block_interval: u64,
+    #[serde(default = "default_start_height")]
+    pub start_height: u32,
+    /// The offset the dates are reported in, `UTC` or `±HH:MM`
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+impl Default for ReportSettings {
+    fn default() -> Self {
+        ReportSettings {
+            block_interval: default_block_interval(),
+            start_height: default_start_height(),
+            timezone: default_timezone(),
+        }
+    }
+}
+
+impl ReportSettings {
+    pub fn load(path: &str) -> Result<ReportSettings, BridgeError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot read report settings file {path}, {e}"))
+        })?;
+        ReportSettings::from_json(&content)
+    }
+
+    pub fn from_json(content: &str) -> Result<ReportSettings, BridgeError> {
+        let settings: ReportSettings = serde_json::from_str(content).map_err(|e| {
+            BridgeError::InvalidConfig(format!("cannot parse report settings, {e}"))
+        })?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), BridgeError> {
+        if self.block_interval == 0 {
+            return Err(BridgeError::InvalidConfig(
+                "block_interval should be positive".to_owned(),
+            ));
+        }
+        parse_timezone(&self.timezone)?;
+        Ok(())
+    }
+
+    pub fn offset(&self) -> FixedOffset {
+        parse_timezone(&self.timezone).unwrap_or(FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+/// Parse `UTC`, `Z` or the offset `±HH:MM`, the `+` decoded from a query string as a space is
+/// taken as `+`
+pub fn parse_timezone(timezone: &str) -> Result<FixedOffset, BridgeError> {
+    let invalid = || {
+        BridgeError::InvalidConfig(format!(
+            "invalid timezone `{timezone}`, it should be `UTC` or `±HH:MM`"
+        ))
+    };
+    let trimmed = timezone.trim();
+    if trimmed.eq_ignore_ascii_case("utc") || trimmed == "Z" {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    let (sign, offset) = match trimmed.strip_prefix('-') {
+        Some(offset) => (-1, offset),
+        None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    // the sign is required unless it's a decoded `+`
+    if sign == 1 && !timezone.starts_with(['+', ' ']) {
+        return Err(invalid());
+    }
+    let (hours, minutes) = offset.split_once(':').ok_or_else(invalid)?;
+    let is_two_digits = |value: &str| value.len() == 2 && value.bytes().all(|b| b.is_ascii_digit());
+    if !is_two_digits(hours) || !is_two_digits(minutes) {
+        return Err(invalid());
+    }
+    match (hours.parse::<i32>(), minutes.parse::<i32>()) {
+        (Ok(hours), Ok(minutes)) if minutes < 60 => {
+            FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// The heights and the block times to report every `period` seconds from `start_height` to the
+/// best height
+///
+/// The height of a date is found by the indexed block times, so the reports follow the actual
+/// intervals of the blocks. Where no block time is indexed, the height steps by the nominal
+/// `block_interval`.
+pub fn report_heights(
+    conn: &db::Conn,
+    start_height: u32,
+    period: u64,
+    block_interval: u64,
+) -> Result<Vec<(u32, u64)>, BridgeError> {
+    let chain_height = conn.query_best_height().unwrap_or_default();
+    let tip_time = conn
+        .query_block_time_by_height(chain_height)
+        .optional()?
+        .unwrap_or_default();
+    let nominal_step = (period / block_interval.max(1)).clamp(1, u32::MAX as u64) as u32;
+    let mut height = start_height;
+    let mut time = conn.query_block_time_by_height(height)?;
+    let mut points = vec![];
+    loop {
+        points.push((height, time));
+        let target = time.saturating_add(period);
+        let next_height = match conn.query_height_by_time(target)? {
+            Some(next_height) if next_height > height && target <= tip_time => next_height,
+            _ => height.saturating_add(nominal_step),
+        };
+        if next_height > chain_height || next_height == height {
+            break;
+        }
+        height = next_height;
+        time = conn
+            .query_block_time_by_height(height)
+            .optional()?
+            .unwrap_or(target);
+    }
+    Ok(points)
+}
+
+/// Format the block time in `offset`
+pub fn format_report_date(time: u64, offset: &FixedOffset) -> String {
+    DateTime::from_timestamp(time as i64, 0)
+        .unwrap_or_default()
+        .with_timezone(offset)
+        .to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_report_settings() {
+        let settings = ReportSettings::from_json(r#"{"timezone": "+08:00"}"#).unwrap();
+        assert_eq!(settings.block_interval, DEFAULT_BLOCK_INTERVAL);
+        assert_eq!(settings.start_height, DEFAULT_REPORT_START_HEIGHT);
+        assert_eq!(settings.offset(), FixedOffset::east_opt(8 * 3600).unwrap());
+        assert_eq!(
+            parse_timezone("-05:30").unwrap(),
+            FixedOffset::west_opt(5 * 3600 + 1800).unwrap()
+        );
+        assert_eq!(
+            parse_timezone(" 08:00").unwrap(),
+            FixedOffset::east_opt(8 * 3600).unwrap()
+        );
+        for timezone in ["08:00", "+8:00", "+08:60", "+24:00", "Asia/Tokyo"] {
+            assert!(parse_timezone(timezone).is_err(), "{timezone}");
+        }
+        assert!(ReportSettings::from_json(r#"{"block_interval": 0}"#).is_err());
+        assert_eq!(
+            format_report_date(1731000000, &settings.offset()),
+            "2024-11-08T01:20:00+08:00"
+        );
+    }
+
+    #[test]
+    fn test_report_heights_by_block_times() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        const START: u64 = 1731000000;
+        // the blocks are made every 10 minutes for the first hour, then every 30 minutes, and
+        // the blocks 20-29 are not indexed
+        for height in 0..20u32 {
+            let time = if height <= 6 {
+                START + height as u64 * 600
+            } else {
+                START + 3600 + (height as u64 - 6) * 1800
+            };
+            conn.add_block(&format!("hash{height}"), height, "miner", time)
+                .unwrap();
+        }
+        conn.add_block("hash30", 30, "miner", START + 14 * 3600)
+            .unwrap();
+
+        let heights: Vec<u32> = report_heights(&conn, 0, 3600, 600)
+            .unwrap()
+            .into_iter()
+            .map(|(height, _)| height)
+            .collect();
+        assert_eq!(heights, vec![0, 6, 8, 10, 12, 14, 16, 18, 19, 25]);
+        // no block is made in the hour after block 19, the time of block 25 is the nominal one
+        let points = report_heights(&conn, 0, 3600, 600).unwrap();
+        assert_eq!(points[9], (25, START + 27000 + 3600));
+
+        assert!(report_heights(&conn, 100, 3600, 600).is_err());
+    }
+}
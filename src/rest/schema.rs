@@ -0,0 +1,178 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+/// The OpenAPI document describes the REST endpoints, the responses are validated against it
+/// from the tests
+pub struct OpenApi {
+    doc: Value,
+}
+
+impl OpenApi {
+    pub fn load() -> OpenApi {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("docs/openapi.json");
+        let content = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("cannot read {}, reason: {}", path.display(), e));
+        OpenApi {
+            doc: serde_json::from_str(&content).unwrap(),
+        }
+    }
+
+    /// All the (path, method) pairs those are described by the document
+    pub fn operations(&self) -> Vec<(String, String)> {
+        let mut operations = vec![];
+        if let Some(paths) = self.doc["paths"].as_object() {
+            for (path, item) in paths {
+                for method in item.as_object().into_iter().flat_map(|item| item.keys()) {
+                    operations.push((path.clone(), method.clone()));
+                }
+            }
+        }
+        operations.sort();
+        operations
+    }
+
+    /// Validate the json response of the operation, `path` is the template from the document,
+    /// e.g. `/exchange/analyze/{txid}`
+    pub fn validate_response(&self, path: &str, method: &str, value: &Value) -> Result<(), String> {
+        let schema = &self.doc["paths"][path][method]["responses"]["200"]["content"]
+            ["application/json"]["schema"];
+        if schema.is_null() {
+            return Err(format!("no json response is described for {method} {path}"));
+        }
+        self.validate(schema, value, "$")
+    }
+
+    fn resolve<'a>(&'a self, schema: &'a Value) -> Result<&'a Value, String> {
+        match schema["$ref"].as_str() {
+            Some(reference) => {
+                let name = reference
+                    .strip_prefix("#/components/schemas/")
+                    .ok_or_else(|| format!("unsupported reference {reference}"))?;
+                let schema = &self.doc["components"]["schemas"][name];
+                if schema.is_null() {
+                    return Err(format!("unknown schema {name}"));
+                }
+                Ok(schema)
+            }
+            None => Ok(schema),
+        }
+    }
+
+    fn validate(&self, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        let schema = self.resolve(schema)?;
+
+        if let Some(candidates) = schema["oneOf"].as_array() {
+            let matched = candidates
+                .iter()
+                .filter(|candidate| self.validate(candidate, value, at).is_ok())
+                .count();
+            if matched != 1 {
+                return Err(format!("{at} matches {matched} of the `oneOf` schemas"));
+            }
+            return Ok(());
+        }
+
+        if value.is_null() && schema["nullable"].as_bool() == Some(true) {
+            return Ok(());
+        }
+
+        if let Some(expected) = schema["type"].as_str() {
+            let matched = match expected {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "boolean" => value.is_boolean(),
+                _ => return Err(format!("unsupported type {expected}")),
+            };
+            if !matched {
+                return Err(format!("{at} is expected to be {expected}, got {value}"));
+            }
+        }
+
+        if let Some(variants) = schema["enum"].as_array() {
+            if !variants.contains(value) {
+                return Err(format!("{at} is not one of {}", schema["enum"]));
+            }
+        }
+
+        if let Some(object) = value.as_object() {
+            if let Some(required) = schema["required"].as_array() {
+                for name in required.iter().filter_map(|name| name.as_str()) {
+                    if !object.contains_key(name) {
+                        return Err(format!("{at}.{name} is required"));
+                    }
+                }
+            }
+            let properties = schema["properties"].as_object();
+            for (name, field) in object {
+                let at = format!("{at}.{name}");
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => self.validate(property, field, &at)?,
+                    None => match &schema["additionalProperties"] {
+                        Value::Bool(false) => return Err(format!("{at} is not expected")),
+                        Value::Object(_) => {
+                            self.validate(&schema["additionalProperties"], field, &at)?
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (i, item) in array.iter().enumerate() {
+                self.validate(items, item, &format!("{at}[{i}]"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_error_response() {
+        let openapi = OpenApi::load();
+        let error = json!({"error": {"code": 0, "message": "invalid transaction data"}});
+        assert!(openapi
+            .validate_response("/solana/post_tx", "post", &error)
+            .is_ok());
+
+        // the amount is a number instead of a string
+        let balance = json!([{"address": "address", "balance": 1000}]);
+        assert!(openapi
+            .validate_response("/solana/balance", "get", &balance)
+            .is_err());
+        // the field is renamed
+        let balance = json!([{"address": "address", "amount": "1000"}]);
+        assert!(openapi
+            .validate_response("/solana/balance", "get", &balance)
+            .is_err());
+
+        // the intent is not matched yet
+        let mut intent = json!({
+            "id": 1,
+            "sender": "sender",
+            "recipient": "recipient",
+            "created_at": 1731000600,
+            "matched_txid": null,
+            "matched_vout": null,
+            "matched_at": null,
+        });
+        assert!(openapi
+            .validate_response("/bridge/deposit-intents/{id}", "get", &intent)
+            .is_ok());
+        intent["id"] = Value::Null;
+        assert!(openapi
+            .validate_response("/bridge/deposit-intents/{id}", "get", &intent)
+            .is_err());
+    }
+}
@@ -1,43 +1,145 @@
 use axum::{
-    extract::{Path, Query, State},
+    body::{to_bytes, Body},
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use chrono::DateTime;
 use log::{error, info, warn};
 use num_format::{Locale, ToFormattedString};
-use serde::Serialize;
 use serde_json::Value;
 use std::str::FromStr;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::signal;
 
 use serde_json::json;
-use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use solana_sdk::{hash::hashv, pubkey::Pubkey, transaction::Transaction};
 
 use crate::{
-    db,
-    solana::{AnalyzedInstruction, InstructionDetail, SolanaClient},
+    amount::{AmountConverter, AmountFormat, DepcAmount, TokenAmount},
+    analysis::{expand_exchange_addresses, ExpansionLimits},
+    bridge::{
+        make_deposit_leaf_data, parameter_value, query_deposit_proof, register_deposit_address,
+        run_sanity_check, summarize_bridge, sweep_fees, BridgeParameter, ListenerRoutes,
+        ListenerSettings, LiveSettings, ACTION_DEPOSIT, ACTION_WITHDRAW, DEFAULT_STUCK_DEPOSIT_AGE,
+        DEFAULT_TIMELOCK, SUMMARY_WINDOWS,
+    },
+    clock::{system_clock, Deadline, SharedClock},
+    db, depc,
+    price::PriceOracle,
+    rest::{
+        format_report_date, report_heights, serve_listener, types::*, Attestor, BoundListener,
+        ReportSettings,
+    },
+    service::{
+        BridgeService, DepcService, ServiceError, SolanaService, MAX_PARAMETER_CHANGES_LIMIT,
+    },
+    solana::{
+        AnalyzedInstruction, AnalyzedTransaction, Direction, InstructionDetail, SolanaClient,
+        TokenClient, TtlCache,
+    },
+    watch::{WatchChain, Watchlist},
+    BridgeError, ErrorCode,
 };
 
+/// The default range and step of `/depc/balance-history` in seconds
+const DEFAULT_BALANCE_HISTORY_RANGE: u64 = 7 * 24 * 3600;
+const DEFAULT_BALANCE_HISTORY_STEP: u64 = 24 * 3600;
+const MAX_BALANCE_HISTORY_POINTS: u64 = 1000;
+const DEFAULT_FIAT_CURRENCY: &str = "usd";
+const DEFAULT_WATCH_EVENTS_LIMIT: u32 = 100;
+const MAX_WATCH_EVENTS_LIMIT: u32 = 1000;
+const DEFAULT_BRIDGE_EVENTS_LIMIT: u32 = 100;
+const MAX_BRIDGE_EVENTS_LIMIT: u32 = 1000;
+const DEFAULT_CUSTODY_INFLOWS_LIMIT: u32 = 100;
+const MAX_CUSTODY_INFLOWS_LIMIT: u32 = 1000;
+const DEFAULT_TRANSFER_FEES_LIMIT: u32 = 100;
+const MAX_TRANSFER_FEES_LIMIT: u32 = 1000;
+const MAX_BLOCK_DIGESTS_RANGE: u32 = 1000;
+const DEFAULT_HELD_TRANSFERS_LIMIT: u32 = 100;
+const MAX_HELD_TRANSFERS_LIMIT: u32 = 1000;
+/// `/bridge/summary` is computed at most once in it
+const SUMMARY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The responses of the requests with header `Idempotency-Key` are replayed for the retries
+/// within it
+const IDEMPOTENCY_KEY_TTL: u64 = 24 * 3600;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+/// The bodies of the requests with header `Idempotency-Key` are buffered to be hashed
+const MAX_IDEMPOTENT_REQUEST_BODY: usize = 1024 * 1024;
+const HEADER_IDEMPOTENCY_KEY: &str = "idempotency-key";
+const HEADER_IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+/// The cursor of the next page of the CSV export of `/exchange/addresses`
+const HEADER_NEXT_CURSOR: &str = "next-cursor";
+
+/// The admin endpoints (`/admin/*`) are served only with the config, the requests must carry
+/// the token as `Authorization: Bearer <token>`
+#[derive(Clone)]
+pub struct AdminConfig {
+    pub token: String,
+    /// The parameter changes made by the admin take effect after it
+    pub timelock: Duration,
+    /// The bridge fees are swept to it by `/admin/sweep-fees`, they're not swept if it's absent
+    pub fee_treasury: Option<Pubkey>,
+    /// The decimals of DePC the swept fees are converted to the token with
+    pub depc_decimals: u8,
+}
+
+struct IdempotencyState {
+    conn: db::Conn,
+    clock: SharedClock,
+}
+
 #[derive(Clone)]
 struct ServerData {
     conn: db::Conn,
     solana_client: SolanaClient,
     exit: Arc<Mutex<bool>>,
+    clock: SharedClock,
+    price_oracle: Option<Arc<PriceOracle>>,
+    timelock: Duration,
+    report: ReportSettings,
+    /// The summaries in each format of the amounts
+    summary_cache: Arc<TtlCache<AmountFormat, Value>>,
+    settings: Arc<LiveSettings>,
+    /// The time the calls to the Solana node of a request are given
+    upstream_timeout: Duration,
+    /// Signs the status and the summary when it's given
+    attestor: Option<Arc<Attestor>>,
+    fee_treasury: Option<Pubkey>,
+    depc_decimals: u8,
+    /// Only one sweep of the bridge fees is made at a time
+    fee_sweep_lock: Arc<tokio::sync::Mutex<()>>,
+    /// The copies of the multisig payouts submitted by the co-signers are checked against it
+    custody_multisig: Option<depc::MultisigScript>,
+    bridge: BridgeService,
+    depc: DepcService,
+    solana: SolanaService,
+}
+
+impl ServerData {
+    fn attest(&self, payload: Value) -> Value {
+        match &self.attestor {
+            Some(attestor) => attestor.attest(payload, self.clock.now()),
+            None => payload,
+        }
+    }
 }
 
 trait FormatMoney {
     fn format_money(&self) -> String;
 }
 
-impl FormatMoney for u64 {
+impl FormatMoney for DepcAmount {
     fn format_money(&self) -> String {
         const COIN: u64 = 100000000;
-        (self / COIN).to_formatted_string(&Locale::en)
+        (self.value64() / COIN).to_formatted_string(&Locale::en)
     }
 }
 
@@ -46,146 +148,169 @@ async fn get_root() -> &'static str {
     "hello world"
 }
 
-#[derive(Serialize)]
-struct RespExchangeBalanceByDate {
-    balance: u64,
-    balance_human: String,
-    addresses: HashMap<String, String>,
-}
-
-#[derive(Serialize)]
-struct RespExchangeAddresses {
-    total: u64,
-    saved: u64,
-}
-
-#[derive(Serialize)]
-struct BalanceResponse {
-    address: String,
-    balance: u64,
-}
-
-#[derive(Serialize)]
-struct UploadTransactionResponse {
-    result: String,
-}
-
-#[derive(Serialize)]
-struct TransactionDetail {
-    signature: String,
-    source: String,
-    destination: String,
-    amount: u64,
-    fee: u64,
-    timestamp: i64,
-    r#type: String,
-}
-
 #[axum::debug_handler]
 async fn get_exchange_addresses(
     Path(txid): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
     State(state): State<Arc<ServerData>>,
-) -> Json<Value> {
-    let mut final_addresses = vec![];
-    let addresses = state.conn.query_inputs(&txid).unwrap();
-    final_addresses.extend(addresses.clone());
+) -> Result<Json<Value>, BridgeError> {
+    let default_limits = ExpansionLimits::default();
+    let limits = ExpansionLimits {
+        max_depth: params
+            .get("depth")
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(default_limits.max_depth),
+        max_addresses: params
+            .get("max_addresses")
+            .and_then(|max_addresses| max_addresses.parse().ok())
+            .unwrap_or(default_limits.max_addresses),
+    };
     info!(
-        "queried total {} address(es) from txid {}",
-        final_addresses.len(),
-        txid
+        "analyzing exchange addresses from txid {}, depth {}, max {} address(es)",
+        txid, limits.max_depth, limits.max_addresses
     );
-    let mut final_txids = Vec::new();
-    for address in addresses.iter() {
-        tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
-        {
-            let exit = state.exit.lock().unwrap();
-            if *exit {
-                break;
-            }
-        }
-        info!("querying txids which are related to address {}", address);
-        let txids = state
-            .conn
-            .query_txids_those_inputs_contain_address(address)
-            .unwrap();
-        info!(
-            "queried total {} txid(s) which are related to address {}",
-            txids.len(),
-            address
-        );
-        final_txids.extend(txids);
-    }
-    final_txids.sort();
-    final_txids.dedup();
+    let report =
+        expand_exchange_addresses(&state.conn, &txid, limits, state.clock.now(), &state.exit)
+            .await?;
     info!(
-        "analyzing total {} txids to get exchange addresses",
-        final_txids.len()
+        "result is ready, {} address(es) visited, complete {}",
+        report.visited, report.complete
     );
-    let mut total_saved = 0u64;
-    for txid in final_txids.iter() {
-        tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
-        {
-            let exit = state.exit.lock().unwrap();
-            if *exit {
-                break;
-            }
+
+    Ok(Json(
+        serde_json::to_value(RespExchangeAddresses {
+            saved: report.saved,
+            total: state.conn.query_num_exchange_addresses()?,
+            visited: report.visited,
+            complete: report.complete,
+        })
+        .unwrap(),
+    ))
+}
+
+/// A page of the exchange addresses after `cursor`, `format=csv` exports the page as CSV with
+/// the cursor of the next page in header `Next-Cursor`
+#[axum::debug_handler]
+async fn get_exchange_address_list(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Response, BridgeError> {
+    let csv = match params.get("format").map(String::as_str) {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(format) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'format' should be json or csv, not '{}'", format),
+            ))
+            .into_response())
         }
-        let sub_addresses = state.conn.query_inputs(txid).unwrap();
-        info!(
-            "appending total {} address(es) into database",
-            sub_addresses.len()
-        );
-        for address in sub_addresses {
-            let res = state
-                .conn
-                .add_analyzed_exchange_address_from_tx(&address, txid);
-            if res.is_err() {
-                error!(
-                    "append related address {} from tx {} is failed, reason: {:?}",
-                    address,
-                    txid,
-                    res.err()
-                );
-            } else {
-                info!("saved {} from tx {} into database", address, txid);
-                total_saved += 1;
-            }
+    };
+    // a limit cannot be parsed is out of the range as well
+    let limit = params
+        .get("limit")
+        .map(|limit| limit.parse::<u32>().unwrap_or_default());
+    let cursor = params.get("cursor").map(String::as_str);
+    let page = match state.depc.exchange_addresses(cursor, limit) {
+        Ok(page) => page,
+        Err(e) => return reject(e).map(IntoResponse::into_response),
+    };
+    if csv {
+        // the addresses are base58 and the txids are hex, no field is quoted
+        let mut body = "address,analyzed_txid,first_seen\n".to_owned();
+        for record in page.addresses {
+            let first_seen = record.first_seen.map(|time| time.to_string());
+            body += &format!(
+                "{},{},{}\n",
+                record.address,
+                record.analyzed_txid,
+                first_seen.unwrap_or_default()
+            );
+        }
+        let mut resp = ([(header::CONTENT_TYPE, "text/csv")], body).into_response();
+        if let Some(value) = page
+            .next_cursor
+            .and_then(|cursor| HeaderValue::from_str(&cursor).ok())
+        {
+            resp.headers_mut()
+                .insert(HeaderName::from_static(HEADER_NEXT_CURSOR), value);
         }
+        return Ok(resp);
     }
-    info!("result is ready.");
-
-    Json(
-        serde_json::to_value(RespExchangeAddresses {
-            saved: total_saved,
-            total: state.conn.query_num_exchange_addresses().unwrap(),
+    Ok(Json(
+        serde_json::to_value(RespExchangeAddressList {
+            addresses: page
+                .addresses
+                .into_iter()
+                .map(RespExchangeAddress::from)
+                .collect(),
+            next_cursor: page.next_cursor,
+            total: state.conn.query_num_exchange_addresses()?,
         })
         .unwrap(),
     )
+    .into_response())
 }
 
+/// The balances of the exchange addresses every `days` days from the start height, the start
+/// height, the block interval and the timezone of `ReportSettings` can be overridden by the
+/// query parameters
 #[axum::debug_handler]
 async fn generate_exchange_balances(
     Path(days): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
     State(state): State<Arc<ServerData>>,
-) -> Json<Value> {
-    let days = days.parse().unwrap_or(7);
+) -> Result<Json<Value>, BridgeError> {
+    let days: u64 = days.parse().unwrap_or(7);
+    let mut settings = state.report.clone();
+    match (
+        parse_u64_param(&params, "start_height"),
+        parse_u64_param(&params, "block_interval"),
+    ) {
+        (Ok(start_height), Ok(block_interval)) => {
+            if let Some(start_height) = start_height {
+                settings.start_height = start_height.try_into().unwrap_or(u32::MAX);
+            }
+            if let Some(block_interval) = block_interval {
+                settings.block_interval = block_interval;
+            }
+        }
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    }
+    if let Some(timezone) = params.get("timezone") {
+        settings.timezone = timezone.clone();
+    }
+    if let Err(e) = settings.validate() {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            e.to_string(),
+        )));
+    }
+    if let Some(checkpoint) = state.conn.query_checkpoint()? {
+        if settings.start_height < checkpoint.height {
+            return Ok(Json(make_below_checkpoint_error_json(&checkpoint)));
+        }
+    }
+    let offset = settings.offset();
     // query balances with different period
-    const HEIGHTS_DAY: u32 = 60 / 3 * 24;
-    const MIN_HEIGHT: u32 = 860130u32;
-    let heights_period: u32 = HEIGHTS_DAY * days;
+    let heights = report_heights(
+        &state.conn,
+        settings.start_height,
+        days.saturating_mul(24 * 3600),
+        settings.block_interval,
+    )?;
     let mut resp = HashMap::new();
-    let chain_height = state.conn.query_best_height().unwrap_or_default();
-    let mut curr_height = MIN_HEIGHT;
-    'outer: loop {
-        let block_timestamp = state.conn.query_block_time_by_height(curr_height);
-        let now = DateTime::from_timestamp(block_timestamp as i64, 0).unwrap();
-        info!("checking balance for date {}...", now.to_rfc3339());
+    'outer: for (curr_height, block_timestamp) in heights {
+        let date = format_report_date(block_timestamp, &offset);
+        info!("checking balance for date {}...", date);
         let mut balance_by_date = RespExchangeBalanceByDate {
-            balance: 0,
-            balance_human: 0u64.format_money(),
+            balance: DepcAmount::ZERO,
+            balance_human: DepcAmount::ZERO.format_money(),
             addresses: HashMap::new(),
         };
-        let final_addresses = state.conn.query_analyzed_exchange_addresses().unwrap();
+        let final_addresses = state.conn.query_analyzed_exchange_addresses()?;
         info!("total {} exchange address(es) found", final_addresses.len());
         for address in final_addresses.iter() {
             tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
@@ -199,8 +324,11 @@ async fn generate_exchange_balances(
                 .conn
                 .query_balance(address, curr_height)
                 .unwrap_or_default();
-            if curr_balance > 0 {
-                balance_by_date.balance += curr_balance;
+            if !curr_balance.is_zero() {
+                balance_by_date.balance = balance_by_date
+                    .balance
+                    .checked_add(curr_balance)
+                    .ok_or(BridgeError::AmountOverflow)?;
                 balance_by_date
                     .addresses
                     .insert(address.clone(), curr_balance.format_money());
@@ -210,242 +338,4015 @@ async fn generate_exchange_balances(
         info!("checked, balance = {}", balance_by_date.balance_human);
 
         // save to resp
-        resp.insert(now.to_rfc3339(), balance_by_date);
-        // next
-        curr_height += heights_period;
-        if curr_height > chain_height {
-            break;
-        }
+        resp.insert(date, balance_by_date);
     }
     info!("done.");
 
-    Json(serde_json::to_value(resp).unwrap())
+    Ok(Json(serde_json::to_value(resp).unwrap()))
 }
 
+/// The balances of the comma separated `addresses` at every `step` seconds from `from` to `to`,
+/// the balance at a time is the one after the last block made before it
 #[axum::debug_handler]
-async fn get_solana_balance(
+async fn get_depc_balance_history(
     Query(params): Query<HashMap<String, String>>,
     State(state): State<Arc<ServerData>>,
-) -> Json<Value> {
-    let res = params.get("address");
-    if res.is_none() {
-        // no 'address' can be found from parameter list, return errors
-        return Json(make_error_json(
-            0,
-            "no 'address' can be found from parameter list".to_owned(),
-        ));
+) -> Result<Json<Value>, BridgeError> {
+    let Some(addresses) = params.get("addresses") else {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            "no 'addresses' can be found from parameter list".to_owned(),
+        )));
+    };
+    let addresses: Vec<&str> = addresses
+        .split(',')
+        .filter(|address| !address.is_empty())
+        .collect();
+    let currency = params
+        .get("currency")
+        .map(|currency| currency.to_lowercase());
+    if currency.is_some() && state.price_oracle.is_none() {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotConfigured,
+            "no price feed is configured".to_owned(),
+        )));
+    }
+    let parse_param = |name: &str| parse_u64_param(&params, name);
+    let (from, to, step) = match (parse_param("from"), parse_param("to"), parse_param("step")) {
+        (Ok(from), Ok(to), Ok(step)) => (from, to, step.unwrap_or(DEFAULT_BALANCE_HISTORY_STEP)),
+        (Err(message), _, _) | (_, Err(message), _) | (_, _, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)));
+        }
+    };
+    let to = match (to, state.conn.query_best_height()) {
+        (Some(to), _) => to,
+        (None, Some(height)) => state.conn.query_block_time_by_height(height)?,
+        (None, None) => {
+            return Ok(Json(
+                serde_json::to_value(RespBalanceHistory { points: vec![] }).unwrap(),
+            ))
+        }
+    };
+    let from = from.unwrap_or(to.saturating_sub(DEFAULT_BALANCE_HISTORY_RANGE));
+    if step == 0 || from > to || (to - from) / step >= MAX_BALANCE_HISTORY_POINTS {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            format!(
+                "invalid range from {} to {} with step {}, at most {} points can be returned",
+                from, to, step, MAX_BALANCE_HISTORY_POINTS
+            ),
+        )));
+    }
+    if let Some(checkpoint) = state.conn.query_checkpoint()? {
+        if from < checkpoint.time {
+            return Ok(Json(make_below_checkpoint_error_json(&checkpoint)));
+        }
     }
-    let mut balances = vec![];
 
-    let iter = res.unwrap().split(",");
-    for address in iter {
-        let res = Pubkey::from_str(address);
-        if res.is_err() {
-            return Json(make_error_json(
-                0,
-                format!("cannot parse address from string '{}'", address),
-            ));
+    let mut points = vec![];
+    for timestamp in (from..=to).step_by(step as usize) {
+        {
+            let exit = state.exit.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        let Some(height) = state.conn.query_height_by_time(timestamp)? else {
+            // no block is made yet
+            continue;
+        };
+        let mut point = BalancePoint {
+            timestamp,
+            height,
+            balance: DepcAmount::ZERO,
+            balances: BTreeMap::new(),
+            fiat_value: None,
+        };
+        for address in addresses.iter() {
+            let balance = state
+                .conn
+                .query_balance(address, height)
+                .unwrap_or_default();
+            point.balance = point
+                .balance
+                .checked_add(balance)
+                .ok_or(BridgeError::AmountOverflow)?;
+            point.balances.insert(address.to_string(), balance);
         }
-        let pubkey = res.unwrap();
-        if let Ok(balance) = state.solana_client.get_balance(&pubkey) {
-            let resp = BalanceResponse {
-                address: address.to_owned(),
-                balance,
-            };
-            let value = serde_json::to_value(resp).unwrap();
-            balances.push(value);
-        } else {
-            let value =
-                make_error_json(0, format!("cannot get balance for address: '{}'", address));
-            balances.push(value);
+        if let (Some(currency), Some(oracle)) = (&currency, &state.price_oracle) {
+            point.fiat_value = oracle.fiat_value(point.balance, currency, timestamp)?;
         }
+        points.push(point);
     }
-    Json(json!(balances))
+    Ok(Json(
+        serde_json::to_value(RespBalanceHistory { points }).unwrap(),
+    ))
 }
 
 #[axum::debug_handler]
-async fn get_solana_history(
+async fn get_depc_richlist(
     Query(params): Query<HashMap<String, String>>,
     State(state): State<Arc<ServerData>>,
-) -> Json<Value> {
-    let res = params.get("address");
-    if res.is_none() {
-        // no 'address' can be found from parameter list, return errors
-        return Json(make_error_json(
-            0,
-            "no 'address' can be found from parameter list".to_owned(),
-        ));
-    }
-    let mut parsed_transactions = vec![];
-    let iter = res.unwrap().split(",");
-    for address in iter {
-        let res = Pubkey::from_str(address);
-        if res.is_err() {
-            // invalid address, need to return err
-            return Json(make_error_json(
-                0,
-                format!("cannot parse address from string '{}'", address),
-            ));
-        }
-        let pubkey = res.unwrap();
-        let res = state
-            .solana_client
-            .get_transactions_related_to_address(&pubkey);
-        if let Err(e) = res {
-            return Json(make_error_json(
-                0,
-                format!(
-                    "cannot parse or get transactions related to address {}, reason: {}",
-                    address, e
-                ),
-            ));
-        }
-        let analyzed_transactions = res.unwrap();
-        for analyzed_transaction in analyzed_transactions.iter() {
-            for ix in analyzed_transaction.instructions.iter() {
-                let transaction_detail = match ix {
-                    AnalyzedInstruction::SplToken(ix_detail) => make_transaction_detail(
-                        ix_detail,
-                        &analyzed_transaction.signature,
-                        analyzed_transaction.fee,
-                        analyzed_transaction.timestamp,
-                        "token".to_owned(),
-                    ),
-                    AnalyzedInstruction::Solana(ix_detail) => make_transaction_detail(
-                        ix_detail,
-                        &analyzed_transaction.signature,
-                        analyzed_transaction.fee,
-                        analyzed_transaction.timestamp,
-                        "sol".to_owned(),
-                    ),
-                };
-                parsed_transactions.push(transaction_detail);
-            }
-        }
-    }
-    Json(json!(parsed_transactions))
+) -> Result<Json<Value>, BridgeError> {
+    // a limit cannot be parsed is out of the range as well
+    let limit = params
+        .get("limit")
+        .map(|limit| limit.parse::<u32>().unwrap_or_default());
+    let holders = match state.depc.richlist(limit) {
+        Ok(holders) => holders,
+        Err(e) => return reject(e),
+    };
+    let holders = holders
+        .into_iter()
+        .zip(1..)
+        .map(|((address, balance), rank)| RichlistHolder {
+            rank,
+            address,
+            balance,
+        })
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespRichlist { holders }).unwrap(),
+    ))
 }
 
+/// The block summary by the height or the hash, the hash is 64 hex digits so it's never taken
+/// as a height
 #[axum::debug_handler]
-async fn post_solana_transaction(
+async fn get_depc_block(
+    Path(height_or_hash): Path<String>,
     State(state): State<Arc<ServerData>>,
-    Json(base64_data): Json<String>,
-) -> Json<Value> {
-    let res = base64::decode(&base64_data);
-    if res.is_err() {
-        return Json(make_error_json(0, "cannot decode base64 data".to_owned()));
-    }
-    let bytes = res.unwrap();
-    let res = bincode::deserialize(&bytes);
-    if res.is_err() {
-        // cannot deserialize the binary code into transaction
-        return Json(make_error_json(0, "invalid transaction data".to_owned()));
-    }
-    let transaction = res.unwrap();
-    if let Ok(signature) = state.solana_client.upload_transaction(&transaction) {
-        Json(json!(UploadTransactionResponse {
-            result: signature.to_string(),
-        }))
-    } else {
-        Json(make_error_json(
-            0,
-            "failed to upload transaction".to_owned(),
-        ))
+) -> Result<Json<Value>, BridgeError> {
+    match state.depc.block(&height_or_hash) {
+        Ok(block) => Ok(Json(
+            serde_json::to_value(RespBlockSummary::from(block)).unwrap(),
+        )),
+        Err(e) => reject(e),
     }
 }
 
-async fn shutdown_signal(exit: Arc<Mutex<bool>>) {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("failed to install Ctrl+C handler");
-        warn!("ctrl-c is received, sending exit signal");
-
-        {
-            let mut e = exit.lock().unwrap();
-            *e = true;
+#[axum::debug_handler]
+async fn get_depc_blocks(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let parse_param = |name: &str| -> Result<Option<u32>, String> {
+        params
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("cannot parse '{}' from string '{}'", name, value))
+            })
+            .transpose()
+    };
+    let (from, to) = match (parse_param("from"), parse_param("to")) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
         }
     };
-
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("failed to install signal handler")
-            .recv()
-            .await;
+    let blocks = match state.depc.blocks(from, to) {
+        Ok(blocks) => blocks,
+        Err(e) => return reject(e),
     };
-
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        _ = ctrl_c => {},
-        _ = terminate => {},
-    }
+    let blocks = blocks.into_iter().map(RespBlockSummary::from).collect();
+    Ok(Json(
+        serde_json::to_value(RespBlockSummaries { blocks }).unwrap(),
+    ))
 }
 
-pub async fn run_service(
-    bind: &str,
-    conn: db::Conn,
-    solana_client: SolanaClient,
-    exit_sig: Arc<Mutex<bool>>,
-) {
-    info!("listening on {}", bind);
-    let app = Router::new()
-        .route("/", get(get_root))
-        .route("/exchange/analyze/:txid", get(get_exchange_addresses))
-        .route("/exchange/balances/:days", get(generate_exchange_balances))
-        .route("/solana/balance", get(get_solana_balance))
-        .route("/solana/history", get(get_solana_history))
-        .route("/solana/post_tx", post(post_solana_transaction))
-        .with_state(Arc::new(ServerData {
-            conn,
-            solana_client,
-            exit: Arc::clone(&exit_sig),
-        }));
-    let listener = tokio::net::TcpListener::bind(bind).await.unwrap();
-
-    info!("web server is running...");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(exit_sig))
-        .await
-        .unwrap();
+/// The miners of the blocks from `from` to `to` (heights), the ones of the most blocks first
+#[axum::debug_handler]
+async fn get_depc_miners(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let parse_param = |name: &str| -> Result<Option<u32>, String> {
+        params
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("cannot parse '{}' from string '{}'", name, value))
+            })
+            .transpose()
+    };
+    let (from, to, limit) = match (parse_param("from"), parse_param("to"), parse_param("limit")) {
+        (Ok(from), Ok(to), Ok(limit)) => (from, to, limit),
+        (Err(message), _, _) | (_, Err(message), _) | (_, _, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    };
+    let report = match state.depc.miners(from, to, limit) {
+        Ok(report) => report,
+        Err(e) => return reject(e),
+    };
+    Ok(Json(
+        serde_json::to_value(RespMiners {
+            from: report.from,
+            to: report.to,
+            miners: report
+                .miners
+                .into_iter()
+                .map(RespMinerStats::from)
+                .collect(),
+        })
+        .unwrap(),
+    ))
+}
 
-    info!("web server exits.");
+/// The blocks of a miner, and the ones made from `from` to `to` (times) by the day (UTC)
+#[axum::debug_handler]
+async fn get_depc_miner(
+    Path(miner): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let (from, to) = match (
+        parse_u64_param(&params, "from"),
+        parse_u64_param(&params, "to"),
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    };
+    let report = match state.depc.miner(&miner, from, to) {
+        Ok(report) => report,
+        Err(e) => return reject(e),
+    };
+    let days = report
+        .days
+        .into_iter()
+        .map(|(day, blocks)| RespMinerDailyBlocks {
+            date: chrono::DateTime::from_timestamp((day * 24 * 3600) as i64, 0)
+                .unwrap_or_default()
+                .format("%Y-%m-%d")
+                .to_string(),
+            blocks,
+        })
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespMiner {
+            stats: RespMinerStats::from(report.stats),
+            from: report.from,
+            to: report.to,
+            days,
+        })
+        .unwrap(),
+    ))
 }
 
-fn make_transaction_detail(
-    ix_detail: &InstructionDetail,
-    signature: &Signature,
-    fee: u64,
-    timestamp: i64,
-    r#type: String,
-) -> TransactionDetail {
-    TransactionDetail {
-        signature: signature.to_string(),
-        source: ix_detail.source.to_string(),
-        destination: ix_detail.destination.to_string(),
-        amount: ix_detail.amount,
-        fee,
-        timestamp,
-        r#type,
+#[axum::debug_handler]
+async fn post_rebuild_clusters(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.depc.rebuild_clusters() {
+        Ok(report) => Ok(Json(serde_json::to_value(report).unwrap())),
+        Err(e) => reject(e),
     }
 }
 
-#[derive(Serialize)]
-struct ErrorDetail {
-    code: u32,
-    message: String,
+#[axum::debug_handler]
+async fn get_address_cluster(
+    Path(address): Path<String>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let cluster = match state.depc.address_cluster(&address) {
+        Ok(cluster) => cluster,
+        Err(e) => return reject(e),
+    };
+    Ok(Json(
+        serde_json::to_value(RespAddressCluster {
+            address,
+            cluster_id: cluster.cluster_id,
+            members: cluster.members,
+        })
+        .unwrap(),
+    ))
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: ErrorDetail,
+/// The volume of the deposits and withdrawals found in `from..=to`, each one is valued with the
+/// price recorded before it's found
+#[axum::debug_handler]
+async fn get_bridge_volume(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let Some(oracle) = state.price_oracle.as_ref() else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotConfigured,
+            "no price feed is configured".to_owned(),
+        )));
+    };
+    let currency = params
+        .get("currency")
+        .map(|currency| currency.to_lowercase())
+        .unwrap_or_else(|| DEFAULT_FIAT_CURRENCY.to_owned());
+    let (from, to) = match (
+        parse_u64_param(&params, "from"),
+        parse_u64_param(&params, "to"),
+    ) {
+        (Ok(from), Ok(to)) => (from.unwrap_or(0), to.unwrap_or_else(|| state.clock.now())),
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    };
+    if from > to {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            format!("invalid range from {} to {}", from, to),
+        )));
+    }
+
+    let mut deposits = VolumeStats::default();
+    let mut withdrawals = VolumeStats::default();
+    for action in state.conn.query_pending_actions_between(from, to)? {
+        let stats = match action.action.as_str() {
+            ACTION_DEPOSIT => &mut deposits,
+            ACTION_WITHDRAW => &mut withdrawals,
+            _ => continue,
+        };
+        stats.count += 1;
+        stats.amount = stats
+            .amount
+            .checked_add(action.amount)
+            .ok_or(BridgeError::AmountOverflow)?;
+        match oracle.fiat_value(action.amount, &currency, action.timestamp)? {
+            Some(value) => stats.fiat_value += value,
+            None => stats.unpriced += 1,
+        }
+    }
+    Ok(Json(
+        serde_json::to_value(RespBridgeVolume {
+            currency,
+            from,
+            to,
+            deposits,
+            withdrawals,
+        })
+        .unwrap(),
+    ))
 }
 
-fn make_error_json(code: u32, message: String) -> Value {
-    serde_json::to_value(ErrorResponse {
-        error: ErrorDetail { code, message },
-    })
-    .unwrap()
+/// The bridge fees kept in the range by the day (UTC) and the direction
+#[axum::debug_handler]
+async fn get_bridge_fees(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let (from, to) = match (
+        parse_u64_param(&params, "from"),
+        parse_u64_param(&params, "to"),
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    };
+    let summary = match state.bridge.fees(from, to) {
+        Ok(summary) => summary,
+        Err(e) => return reject(e),
+    };
+    let days = summary
+        .days
+        .into_iter()
+        .map(|daily| RespDailyFees {
+            date: chrono::DateTime::from_timestamp((daily.day * 24 * 3600) as i64, 0)
+                .unwrap_or_default()
+                .format("%Y-%m-%d")
+                .to_string(),
+            deposits: daily.deposits,
+            withdrawals: daily.withdrawals,
+        })
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespBridgeFees {
+            from: summary.from,
+            to: summary.to,
+            deposits: summary.deposits,
+            withdrawals: summary.withdrawals,
+            days,
+            unswept: summary.unswept,
+        })
+        .unwrap(),
+    ))
+}
+
+/// Transfer the bridge fees not swept yet to the treasury, `sweep` is null if there's nothing
+/// to sweep
+#[axum::debug_handler]
+async fn post_sweep_fees(State(state): State<Arc<ServerData>>) -> Result<Json<Value>, BridgeError> {
+    let Some(treasury) = state.fee_treasury else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotConfigured,
+            "no fee treasury is configured".to_owned(),
+        )));
+    };
+    let _sweeping = state.fee_sweep_lock.lock().await;
+    let (conn, solana_client, depc_decimals, now) = (
+        state.conn.clone(),
+        state.solana_client.clone(),
+        state.depc_decimals,
+        state.clock.now(),
+    );
+    let sweep = match tokio::task::spawn_blocking(move || {
+        // the decimals of the mint are queried like the bridge does when it's run
+        let mint_decimals = solana_client
+            .mint_decimals()
+            .map_err(BridgeError::token_client::<SolanaClient>)?;
+        let converter = AmountConverter::new(depc_decimals, mint_decimals).ok_or_else(|| {
+            BridgeError::InvalidConfig(format!(
+                "cannot convert {depc_decimals} decimals of DePC to {mint_decimals} decimals of \
+                 the mint"
+            ))
+        })?;
+        sweep_fees(&conn, &solana_client, &converter, &treasury, now)
+    })
+    .await
+    .unwrap()
+    {
+        Ok(sweep) => sweep,
+        Err(e @ BridgeError::TokenClient(..)) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::TokenClientFailed,
+                format!("cannot sweep the bridge fees to {treasury}, reason: {e}"),
+            )));
+        }
+        Err(e) => return Err(e),
+    };
+    let sweep = sweep.map(|sweep| RespFeeSweep {
+        id: sweep.id,
+        treasury: sweep.treasury,
+        amount: sweep.amount,
+        signature: sweep.signature,
+        created_at: sweep.created_at,
+    });
+    Ok(Json(serde_json::to_value(RespSweepFees { sweep }).unwrap()))
+}
+
+/// The payouts from the multisig custody collecting the signatures of the co-signers
+#[axum::debug_handler]
+async fn get_multisig_payouts(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let mut payouts = vec![];
+    for payout in state.conn.query_collecting_multisig_payouts()? {
+        let prevtxs = serde_json::from_str(&payout.prevtxs).map_err(|e| {
+            depc::Error::InvalidMultisig(format!("prevtxs of payout {}, {e}", payout.id))
+        })?;
+        payouts.push(RespMultisigPayout {
+            id: payout.id,
+            action_id: payout.action_id,
+            raw_tx: payout.raw_tx,
+            prevtxs,
+            created_at: payout.created_at,
+        });
+    }
+    Ok(Json(
+        serde_json::to_value(RespMultisigPayouts { payouts }).unwrap(),
+    ))
+}
+
+/// Submit a copy of the payout signed by a co-signer, it's merged into the payout by the
+/// withdraw worker. The payout is broadcast once it has the signatures required
+///
+/// It's not behind the admin token, the copy is authenticated by the signatures of the key of
+/// the co-signer instead
+#[axum::debug_handler]
+async fn post_multisig_signature(
+    Path(payout_id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqMultisigSignature>,
+) -> Result<Json<Value>, BridgeError> {
+    let payout = state
+        .conn
+        .query_multisig_payout(payout_id)?
+        .filter(|payout| payout.txid.is_none());
+    let Some(payout) = payout else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("multisig payout {payout_id} is not found or sent already"),
+        )));
+    };
+    if req.raw_tx.is_empty() || hex::decode(&req.raw_tx).is_err() {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            "'raw_tx' should be the hex of the signed transaction".to_owned(),
+        )));
+    }
+    let Some(multisig) = state.custody_multisig.as_ref() else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotConfigured,
+            "the custody is not a multisig script".to_owned(),
+        )));
+    };
+    // the copy is taken only when it's signed by a key of the script
+    if let Err(e) = multisig.verify_signed_copy(&payout.raw_tx, &req.raw_tx, &req.pubkey) {
+        warn!("a copy of multisig payout {payout_id} is refused, {e}");
+        return Ok(Json(make_error_json(
+            ErrorCode::Unauthorized,
+            e.to_string(),
+        )));
+    }
+    let id = state
+        .conn
+        .add_multisig_signature(payout_id, &req.raw_tx, state.clock.now())?;
+    info!(
+        "a copy of multisig payout {payout_id} signed by {} is submitted, id {id}",
+        req.pubkey
+    );
+    Ok(Json(
+        serde_json::to_value(RespMultisigSignature { id, payout_id }).unwrap(),
+    ))
+}
+
+/// The bridge fees charged on the deposits and the withdrawals, the latest first
+#[axum::debug_handler]
+async fn get_transfer_fees(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let limit = match params.get("limit").map(|limit| limit.parse::<u32>()) {
+        None => DEFAULT_TRANSFER_FEES_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_TRANSFER_FEES_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_TRANSFER_FEES_LIMIT),
+            )));
+        }
+    };
+    let fees = state
+        .conn
+        .query_transfer_fees(limit)?
+        .into_iter()
+        .map(RespTransferFee::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespTransferFees { fees }).unwrap(),
+    ))
+}
+
+/// The digests of the bridge events of the blocks `from..=to` (heights), `to` is the last of
+/// `MAX_BLOCK_DIGESTS_RANGE` blocks by default
+#[axum::debug_handler]
+async fn get_block_digests(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let parse_param = |name: &str| -> Result<Option<u32>, String> {
+        params
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("cannot parse '{}' from string '{}'", name, value))
+            })
+            .transpose()
+    };
+    let (from, to) = match (parse_param("from"), parse_param("to")) {
+        (Ok(Some(from)), Ok(to)) => (
+            from,
+            to.unwrap_or(from.saturating_add(MAX_BLOCK_DIGESTS_RANGE - 1)),
+        ),
+        (Ok(None), Ok(_)) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                "'from' is required".to_owned(),
+            )))
+        }
+        (Err(message), _) | (_, Err(message)) => {
+            return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message)))
+        }
+    };
+    if to < from || to - from >= MAX_BLOCK_DIGESTS_RANGE {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            format!("invalid range from {from} to {to}, at most {MAX_BLOCK_DIGESTS_RANGE} blocks"),
+        )));
+    }
+    let digests = state
+        .conn
+        .query_block_digests(from, to)?
+        .into_iter()
+        .map(RespBlockDigest::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespBlockDigests { from, to, digests }).unwrap(),
+    ))
+}
+
+/// The payments to the custody addresses which make no bridge action and are not resolved yet,
+/// the latest first
+#[axum::debug_handler]
+async fn get_unclassified_inflows(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let limit = match params.get("limit").map(|limit| limit.parse::<u32>()) {
+        None => DEFAULT_CUSTODY_INFLOWS_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_CUSTODY_INFLOWS_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_CUSTODY_INFLOWS_LIMIT),
+            )));
+        }
+    };
+    let inflows = state
+        .conn
+        .query_unclassified_custody_inflows(limit)?
+        .into_iter()
+        .map(RespCustodyInflow::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespCustodyInflows { inflows }).unwrap(),
+    ))
+}
+
+/// The deposits and the withdrawals held by the transfer limits and not released yet, the
+/// oldest first
+#[axum::debug_handler]
+async fn get_held_transfers(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let limit = match params.get("limit").map(|limit| limit.parse::<u32>()) {
+        None => DEFAULT_HELD_TRANSFERS_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_HELD_TRANSFERS_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_HELD_TRANSFERS_LIMIT),
+            )));
+        }
+    };
+    let transfers = state
+        .conn
+        .query_held_pending_actions(limit)?
+        .into_iter()
+        .map(RespHeldTransfer::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespHeldTransfers { transfers }).unwrap(),
+    ))
+}
+
+/// Release a held transfer, the workers make it as usual then
+#[axum::debug_handler]
+async fn post_release_held_transfer(
+    Path(id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    if !state
+        .conn
+        .release_held_pending_action(id, state.clock.now())?
+    {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("held transfer {id} is not found or released already"),
+        )));
+    }
+    let Some(transfer) = state.conn.query_held_pending_action(id)? else {
+        return Err(BridgeError::Database(rusqlite::Error::QueryReturnedNoRows));
+    };
+    info!(
+        "held {} {id} of {} to {} is released",
+        transfer.action, transfer.amount, transfer.recipient
+    );
+    Ok(Json(
+        serde_json::to_value(RespHeldTransfer::from(transfer)).unwrap(),
+    ))
+}
+
+/// Resolve an unclassified inflow after the operator handles the funds by hand
+#[axum::debug_handler]
+async fn post_resolve_inflow(
+    Path(id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqResolveCustodyInflow>,
+) -> Result<Json<Value>, BridgeError> {
+    let resolution = req.resolution.trim();
+    if resolution.is_empty() {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            "'resolution' should tell what's done with the funds".to_owned(),
+        )));
+    }
+    if !state
+        .conn
+        .resolve_custody_inflow(id, resolution, state.clock.now())?
+    {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("unclassified inflow {id} is not found or resolved already"),
+        )));
+    }
+    let Some(inflow) = state.conn.query_custody_inflow(id)? else {
+        return Err(BridgeError::Database(rusqlite::Error::QueryReturnedNoRows));
+    };
+    info!("unclassified inflow {id} is resolved, {resolution}");
+    Ok(Json(
+        serde_json::to_value(RespCustodyInflow::from(inflow)).unwrap(),
+    ))
+}
+
+/// The latest catch-up plan of the bridge, the totals of the one being indexed are the ones
+/// found so far
+#[axum::debug_handler]
+async fn get_bridge_catch_up(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let report = match state.bridge.catch_up_report() {
+        Ok(report) => report,
+        Err(e) => return reject(e),
+    };
+    Ok(Json(
+        serde_json::to_value(RespCatchUp {
+            bridge_id: report.bridge_id,
+            best_height: report.best_height,
+            plan: report.plan.map(RespCatchUpPlan::from),
+        })
+        .unwrap(),
+    ))
+}
+
+/// Confirm the catch-up plan awaiting the confirmation, the backlog is executed after it
+#[axum::debug_handler]
+async fn post_confirm_catch_up(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.confirm_catch_up() {
+        Ok(plan) => Ok(Json(
+            serde_json::to_value(RespCatchUpPlan::from(plan)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// The syncing state of the bridge and the data gaps of the index
+#[axum::debug_handler]
+async fn get_bridge_info(State(state): State<Arc<ServerData>>) -> Result<Json<Value>, BridgeError> {
+    let status = match state.bridge.status() {
+        Ok(status) => status,
+        Err(e) => return reject(e),
+    };
+    let data_gaps = status
+        .data_gaps
+        .into_iter()
+        .map(|gap| RespDataGap {
+            from: gap.from,
+            to: gap.to,
+            reason: gap.reason,
+        })
+        .collect();
+    Ok(Json(
+        state.attest(
+            serde_json::to_value(RespBridgeInfo {
+                bridge_id: status.bridge_id,
+                best_height: status.best_height,
+                checkpoint_height: status.checkpoint.map(|checkpoint| checkpoint.height),
+                data_gaps,
+                attestation: None,
+            })
+            .unwrap(),
+        ),
+    ))
+}
+
+/// The parameters in effect and the changes queued for them
+#[axum::debug_handler]
+async fn get_bridge_parameters(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let now = state.clock.now();
+    let mut parameters = BTreeMap::new();
+    for parameter in BridgeParameter::ALL {
+        parameters.insert(
+            parameter.name().to_owned(),
+            parameter_value(&state.conn, parameter, now)?,
+        );
+    }
+    let mut pending: Vec<_> = state
+        .conn
+        .query_parameter_changes(MAX_PARAMETER_CHANGES_LIMIT)?
+        .into_iter()
+        .map(|record| RespParameterChange::new(record, now))
+        .filter(|change| change.status == "pending")
+        .collect();
+    pending.reverse();
+    Ok(Json(
+        serde_json::to_value(RespBridgeParameters {
+            parameters,
+            pending,
+        })
+        .unwrap(),
+    ))
+}
+
+/// The deposits, the withdrawals, the fees and the failures of the last day, week and month,
+/// it's cached for a minute since the windows scan the tables
+#[axum::debug_handler]
+async fn get_bridge_summary(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let summary = state
+        .summary_cache
+        .get_or_try_insert(AmountFormat::current(), || {
+            let now = state.clock.now();
+            let mut windows = vec![];
+            for (window, seconds) in SUMMARY_WINDOWS {
+                let summary = summarize_bridge(&state.conn, now.saturating_sub(seconds))?;
+                windows.push(RespWindowSummary {
+                    window: window.to_owned(),
+                    since: summary.since,
+                    deposits: summary.deposits.into(),
+                    withdrawals: summary.withdrawals.into(),
+                    fees: summary.fees,
+                    failures: RespFailureCounts {
+                        dead_letters: summary.dead_letters,
+                        refunds: summary.refunds,
+                    },
+                });
+            }
+            Ok::<_, BridgeError>(
+                serde_json::to_value(RespBridgeSummary {
+                    generated_at: now,
+                    windows,
+                    attestation: None,
+                })
+                .unwrap(),
+            )
+        })?;
+    // the cached summary is signed at each request
+    Ok(Json(state.attest(summary)))
+}
+
+/// The events after `since_seq` in order, a consumer keeps `last_seq` and resumes from it so
+/// every event is seen once after the downtime
+#[axum::debug_handler]
+async fn get_bridge_events(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let since_seq = match parse_u64_param(&params, "since_seq") {
+        Ok(since_seq) => since_seq.unwrap_or(0),
+        Err(message) => return Ok(Json(make_error_json(ErrorCode::InvalidParameter, message))),
+    };
+    let limit = match params.get("limit").map(|limit| limit.parse::<u32>()) {
+        None => DEFAULT_BRIDGE_EVENTS_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_BRIDGE_EVENTS_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_BRIDGE_EVENTS_LIMIT),
+            )));
+        }
+    };
+    let events: Vec<_> = state
+        .conn
+        .query_events(since_seq, limit)?
+        .into_iter()
+        .map(RespBridgeEvent::from)
+        .collect();
+    let last_seq = events.last().map_or(since_seq, |event| event.seq);
+    Ok(Json(
+        serde_json::to_value(RespBridgeEvents { events, last_seq }).unwrap(),
+    ))
+}
+
+/// The parameter changes, the latest first
+#[axum::debug_handler]
+async fn get_parameter_changes(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    // a limit cannot be parsed is out of the range as well
+    let limit = params
+        .get("limit")
+        .map(|limit| limit.parse::<u32>().unwrap_or_default());
+    let changes = match state.bridge.parameter_changes(limit) {
+        Ok(changes) => changes,
+        Err(e) => return reject(e),
+    };
+    let now = state.clock.now();
+    let changes = changes
+        .into_iter()
+        .map(|record| RespParameterChange::new(record, now))
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespParameterChanges { changes }).unwrap(),
+    ))
+}
+
+/// Queue a parameter change, it takes effect after the timelock
+#[axum::debug_handler]
+async fn post_parameter_change(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqParameterChange>,
+) -> Result<Json<Value>, BridgeError> {
+    match state
+        .bridge
+        .queue_parameter_change(&req.name, &req.value, state.timelock)
+    {
+        Ok(change) => Ok(Json(
+            serde_json::to_value(RespParameterChange::new(change, state.clock.now())).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// Cancel a parameter change before it takes effect
+#[axum::debug_handler]
+async fn post_cancel_parameter_change(
+    Path(id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.cancel_parameter_change(id) {
+        Ok(change) => Ok(Json(
+            serde_json::to_value(RespParameterChange::new(change, state.clock.now())).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// The periodic jobs and their last and next runs
+#[axum::debug_handler]
+async fn get_jobs(State(state): State<Arc<ServerData>>) -> Result<Json<Value>, BridgeError> {
+    let jobs = state
+        .conn
+        .query_jobs()?
+        .into_iter()
+        .map(RespJob::from)
+        .collect();
+    Ok(Json(serde_json::to_value(RespJobs { jobs }).unwrap()))
+}
+
+/// The sanity report of the database, the one logged on startup checked again now
+#[axum::debug_handler]
+async fn get_sanity(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let stuck_after = match params.get("stuck_after").map(|secs| secs.parse::<u64>()) {
+        None => DEFAULT_STUCK_DEPOSIT_AGE,
+        Some(Ok(secs)) => secs,
+        Some(Err(_)) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                "'stuck_after' should be the seconds".to_owned(),
+            )));
+        }
+    };
+    let report = run_sanity_check(&state.conn, state.clock.now(), stuck_after)?;
+    Ok(Json(
+        serde_json::to_value(RespSanityReport::from(report)).unwrap(),
+    ))
+}
+
+/// Read the settings file again and apply the reloadable settings, the ones in use are kept if
+/// the file is invalid
+#[axum::debug_handler]
+async fn post_reload_settings(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.settings.reload() {
+        Ok(settings) => {
+            info!("the settings are reloaded by the admin");
+            Ok(Json(
+                serde_json::to_value(RespReloadableSettings::from(settings.as_ref())).unwrap(),
+            ))
+        }
+        Err(e @ BridgeError::InvalidConfig(_)) => {
+            Ok(Json(make_error_json(e.code(), e.to_string())))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The watched addresses
+#[axum::debug_handler]
+async fn get_watchlist(State(state): State<Arc<ServerData>>) -> Result<Json<Value>, BridgeError> {
+    let addresses = Watchlist::new(state.conn.clone())
+        .list()?
+        .into_iter()
+        .map(RespWatchedAddress::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespWatchlist { addresses }).unwrap(),
+    ))
+}
+
+/// Watch an address, or update the label and the webhook of the watched one
+#[axum::debug_handler]
+async fn post_watch_address(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqWatchAddress>,
+) -> Result<Json<Value>, BridgeError> {
+    let watchlist = Watchlist::new(state.conn.clone());
+    match WatchChain::from_str(&req.chain).and_then(|chain| {
+        watchlist.watch(
+            chain,
+            &req.address,
+            &req.label,
+            req.webhook.as_deref(),
+            state.clock.now(),
+        )
+    }) {
+        Ok(()) => {}
+        Err(e @ BridgeError::InvalidConfig(_)) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                e.to_string(),
+            )));
+        }
+        Err(e @ BridgeError::InvalidAddress(_)) => {
+            return Ok(Json(make_error_json(e.code(), e.to_string())));
+        }
+        Err(e) => return Err(e),
+    }
+    info!("{} address {} is watched", req.chain, req.address);
+    get_watchlist(State(state)).await
+}
+
+/// Stop watching an address, its recorded events are kept
+#[axum::debug_handler]
+async fn post_unwatch_address(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqUnwatchAddress>,
+) -> Result<Json<Value>, BridgeError> {
+    let chain = match WatchChain::from_str(&req.chain) {
+        Ok(chain) => chain,
+        Err(e) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                e.to_string(),
+            )))
+        }
+    };
+    if !Watchlist::new(state.conn.clone()).unwatch(chain, &req.address)? {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("{} address {} is not watched", req.chain, req.address),
+        )));
+    }
+    info!(
+        "{} address {} is not watched any more",
+        req.chain, req.address
+    );
+    get_watchlist(State(state)).await
+}
+
+/// The recent activities of the watched addresses, the latest first
+#[axum::debug_handler]
+async fn get_watch_events(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let limit = match params.get("limit").map(|limit| limit.parse::<u32>()) {
+        None => DEFAULT_WATCH_EVENTS_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_WATCH_EVENTS_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_WATCH_EVENTS_LIMIT),
+            )));
+        }
+    };
+    let events = state
+        .conn
+        .query_watch_events(params.get("address").map(String::as_str), limit)?
+        .into_iter()
+        .map(RespWatchEvent::from)
+        .collect();
+    Ok(Json(
+        serde_json::to_value(RespWatchEvents { events }).unwrap(),
+    ))
+}
+
+/// Reject the requests without the admin token
+async fn require_admin_token(
+    State(token): State<Arc<String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| tokens_equal(value.as_bytes(), token.as_bytes()));
+    if !authorized {
+        warn!("unauthorized request to {}", request.uri());
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(make_error_json(
+                ErrorCode::Unauthorized,
+                "the admin token is missing or invalid".to_owned(),
+            )),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// `/admin/*`, of the default bridge or under `/bridges/{bridge_id}`
+fn is_admin_path(path: &str) -> bool {
+    let path = match path.strip_prefix("/bridges/") {
+        Some(rest) => rest.find('/').map_or("", |index| &rest[index..]),
+        None => path,
+    };
+    path == "/admin" || path.starts_with("/admin/")
+}
+
+/// Answer the endpoints the listener doesn't serve as not found, so the admin endpoints cannot
+/// be told from the public interface
+async fn restrict_routes(
+    State(routes): State<ListenerRoutes>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let served = if is_admin_path(request.uri().path()) {
+        routes.serves_admin()
+    } else {
+        routes.serves_public()
+            && (routes.serves_writes() || matches!(*request.method(), Method::GET | Method::HEAD))
+    };
+    if !served {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(make_error_json(
+                ErrorCode::NotFound,
+                format!("{} is not served here", request.uri().path()),
+            )),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Replay the response of the POST request with the same `Idempotency-Key` header, a request
+/// is served once for the retries of a flaky client
+///
+/// The key is released when the request fails with a server error so it can be retried, and it
+/// cannot be used for another request (path and body) until it expires.
+async fn idempotent_requests(
+    State(state): State<Arc<IdempotencyState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(HEADER_IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if (1..=MAX_IDEMPOTENCY_KEY_LEN).contains(&key.len()) => key.to_owned(),
+        _ => {
+            return make_error_response(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::IdempotencyKeyInvalid,
+                format!(
+                    "'Idempotency-Key' should be 1 to {} visible ASCII characters",
+                    MAX_IDEMPOTENCY_KEY_LEN
+                ),
+            );
+        }
+    };
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_IDEMPOTENT_REQUEST_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            return make_error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorCode::RequestTooLarge,
+                format!("cannot read the request body, reason: {}", e),
+            );
+        }
+    };
+    // the query is hashed too, the format of the amounts is chosen by it
+    let path_and_query = parts.uri.path_and_query().map_or("", |p| p.as_str());
+    let request_hash = hashv(&[path_and_query.as_bytes(), &body]).to_string();
+
+    let now = state.clock.now();
+    let expired_before = now.saturating_sub(IDEMPOTENCY_KEY_TTL);
+    match state
+        .conn
+        .claim_idempotency_key(&key, &request_hash, now, expired_before)
+    {
+        Ok(true) => {}
+        Ok(false) => return replay_idempotent_response(&state.conn, &key, &request_hash),
+        Err(e) => return BridgeError::from(e).into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+    if status.is_server_error() {
+        if let Err(e) = state.conn.delete_idempotency_key(&key) {
+            warn!("cannot release idempotency key {}, reason: {}", key, e);
+        }
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(
+                "cannot read the response for idempotency key {}, reason: {}",
+                key, e
+            );
+            if let Err(e) = state.conn.delete_idempotency_key(&key) {
+                warn!("cannot release idempotency key {}, reason: {}", key, e);
+            }
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if let Err(e) = state
+        .conn
+        .save_idempotent_response(&key, status.as_u16(), &body)
+    {
+        warn!(
+            "cannot save the response for idempotency key {}, reason: {}",
+            key, e
+        );
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// The saved response of the request `key` is claimed by
+fn replay_idempotent_response(conn: &db::Conn, key: &str, request_hash: &str) -> Response {
+    let record = match conn.query_idempotency_key(key) {
+        Ok(Some(record)) => record,
+        // released by the failed request just now
+        Ok(None) => {
+            return make_error_response(
+                StatusCode::CONFLICT,
+                ErrorCode::IdempotencyKeyConflict,
+                "the request of 'Idempotency-Key' failed, retry it".to_owned(),
+            );
+        }
+        Err(e) => return BridgeError::from(e).into_response(),
+    };
+    if record.request_hash != request_hash {
+        return make_error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::IdempotencyKeyReused,
+            "'Idempotency-Key' is used by another request".to_owned(),
+        );
+    }
+    let (Some(status), Some(body)) = (record.status, record.body) else {
+        return make_error_response(
+            StatusCode::CONFLICT,
+            ErrorCode::IdempotencyKeyConflict,
+            "the request of 'Idempotency-Key' is being served".to_owned(),
+        );
+    };
+    (
+        StatusCode::from_u16(status).unwrap_or(StatusCode::OK),
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (
+                HeaderName::from_static(HEADER_IDEMPOTENT_REPLAYED),
+                HeaderValue::from_static("true"),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Serialize the amounts of the response as integers for `?numeric=1`, the legacy clients use
+/// it while they're migrated to the amounts as strings
+async fn numeric_amounts(request: Request, next: Next) -> Response {
+    let numeric = request.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair == "numeric=1" || pair == "numeric=true")
+    });
+    let format = if numeric {
+        AmountFormat::Number
+    } else {
+        AmountFormat::String
+    };
+    format.scope(next.run(request)).await
+}
+
+/// Run the request under the deadline of `upstream_timeout`, the database queries and the RPC
+/// calls it makes are cut off there
+async fn request_deadline(
+    State(upstream_timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    Deadline::after(upstream_timeout)
+        .scope(next.run(request))
+        .await
+}
+
+/// Compare the tokens in constant time for the tokens of the same length
+fn tokens_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Register a deposit without OP_RETURN, the next plain payment from `sender` to the custody
+/// address is bridged to `recipient`
+#[axum::debug_handler]
+async fn post_deposit_intent(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqDepositIntent>,
+) -> Result<Json<Value>, BridgeError> {
+    match state
+        .bridge
+        .register_deposit_intent(&req.sender, &req.recipient)
+    {
+        Ok(intent) => Ok(Json(
+            serde_json::to_value(RespDepositIntent::from(intent)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_deposit_intent(
+    Path(id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.deposit_intent(id) {
+        Ok(intent) => Ok(Json(
+            serde_json::to_value(RespDepositIntent::from(intent)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// Give out a DePC address of the bridge to `recipient`, any payment to it is bridged to the
+/// recipient
+#[axum::debug_handler]
+async fn post_deposit_address(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqDepositAddress>,
+) -> Result<Json<Value>, BridgeError> {
+    let Ok(recipient) = Pubkey::from_str(&req.recipient) else {
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidAddress,
+            format!("cannot parse address from string '{}'", req.recipient),
+        )));
+    };
+    if let Some((_, description)) = state
+        .solana_client
+        .reserved_addresses()
+        .into_iter()
+        .find(|(address, _)| *address == recipient)
+    {
+        return Ok(Json(make_error_json(
+            ErrorCode::RecipientReserved,
+            BridgeError::ReservedRecipient(req.recipient, description).to_string(),
+        )));
+    }
+    let Some(record) = register_deposit_address(&state.conn, &req.recipient, state.clock.now())?
+    else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotConfigured,
+            "no deposit xpub is configured".to_owned(),
+        )));
+    };
+    Ok(Json(
+        serde_json::to_value(RespDepositAddress::from(record)).unwrap(),
+    ))
+}
+
+#[axum::debug_handler]
+async fn get_deposit_address(
+    Path(address): Path<String>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let Some(record) = state.conn.query_deposit_address(&address)? else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("deposit address {} is not found", address),
+        )));
+    };
+    Ok(Json(
+        serde_json::to_value(RespDepositAddress::from(record)).unwrap(),
+    ))
+}
+
+/// The latest Merkle root of the minted deposits
+#[axum::debug_handler]
+async fn get_deposit_root(
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    let Some(record) = state.conn.query_latest_deposit_root()? else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            "no deposit root is published yet".to_owned(),
+        )));
+    };
+    Ok(Json(state.attest(
+        serde_json::to_value(RespDepositRoot::from(record)).unwrap(),
+    )))
+}
+
+/// The inclusion proof of a minted deposit in the latest deposit root
+#[axum::debug_handler]
+async fn get_deposit_proof(
+    Path(depc_txid): Path<String>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    if state.conn.query_deposit(&depc_txid)?.is_none() {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!("deposit {} is not found", depc_txid),
+        )));
+    }
+    let Some(proof) = query_deposit_proof(&state.conn, &depc_txid)? else {
+        return Ok(Json(make_error_json(
+            ErrorCode::NotFound,
+            format!(
+                "deposit {} is not covered by a published deposit root yet",
+                depc_txid
+            ),
+        )));
+    };
+    let deposit = proof.deposit;
+    Ok(Json(
+        serde_json::to_value(RespDepositProof {
+            leaf_data: make_deposit_leaf_data(
+                &deposit.depc_txid,
+                &deposit.to_address,
+                deposit.amount,
+            ),
+            depc_txid: deposit.depc_txid,
+            recipient: deposit.to_address,
+            amount: deposit.amount,
+            leaf: hex::encode(proof.leaf),
+            index: proof.index,
+            siblings: proof
+                .steps
+                .iter()
+                .map(|step| RespProofStep {
+                    hash: hex::encode(step.sibling),
+                    side: step.side.name().to_owned(),
+                })
+                .collect(),
+            root: proof.root.into(),
+        })
+        .unwrap(),
+    ))
+}
+
+#[axum::debug_handler]
+async fn post_bridge_withdraw(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqWithdrawRequest>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.register_withdraw_request(
+        &req.signature,
+        &req.recipient,
+        &req.signer,
+        &req.message_signature,
+    ) {
+        Ok(request) => Ok(Json(
+            serde_json::to_value(RespWithdrawRequest::from(request)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_bridge_withdraw(
+    Path(id): Path<u64>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.withdraw_request(id) {
+        Ok(request) => Ok(Json(
+            serde_json::to_value(RespWithdrawRequest::from(request)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// Claim the refund of an invalid deposit to another address, the refund worker verifies the
+/// signature of the refund address before the refund is made
+#[axum::debug_handler]
+async fn post_refund_claim(
+    State(state): State<Arc<ServerData>>,
+    Json(req): Json<ReqRefundClaim>,
+) -> Result<Json<Value>, BridgeError> {
+    match state
+        .bridge
+        .claim_refund(&req.depc_txid, req.vout, &req.address, &req.signature)
+    {
+        Ok(refund) => Ok(Json(
+            serde_json::to_value(RespRefund::from(refund)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_refund(
+    Path((depc_txid, vout)): Path<(String, u32)>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, BridgeError> {
+    match state.bridge.refund(&depc_txid, vout) {
+        Ok(refund) => Ok(Json(
+            serde_json::to_value(RespRefund::from(refund)).unwrap(),
+        )),
+        Err(e) => reject(e),
+    }
+}
+
+/// The Solana node doesn't answer a REST request within the budget of it
+struct UpstreamTimeout(Duration);
+
+impl IntoResponse for UpstreamTimeout {
+    fn into_response(self) -> Response {
+        make_error_response(
+            StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::UpstreamTimeout,
+            format!("the solana node doesn't answer in {:?}", self.0),
+        )
+    }
+}
+
+/// The time the upstream calls of a REST request are given, they share it
+struct UpstreamBudget {
+    timeout: Duration,
+    deadline: Deadline,
+}
+
+impl UpstreamBudget {
+    /// The budget ends with the deadline of the request if it's earlier
+    fn start(timeout: Duration) -> UpstreamBudget {
+        UpstreamBudget {
+            timeout,
+            deadline: Deadline::after(timeout).within_current(),
+        }
+    }
+
+    /// Make the blocking call `f` off the workers under the deadline, the request is answered
+    /// when the budget runs out and the abandoned call is cut off by the deadline too
+    async fn call<T, F>(&self, f: F) -> Result<T, UpstreamTimeout>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let deadline = self.deadline;
+        let call = tokio::task::spawn_blocking(move || deadline.sync_scope(f));
+        let until = tokio::time::Instant::from_std(deadline.instant());
+        match tokio::time::timeout_at(until, call).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => {
+                warn!("the solana node doesn't answer in {:?}", self.timeout);
+                Err(UpstreamTimeout(self.timeout))
+            }
+        }
+    }
+}
+
+#[axum::debug_handler]
+async fn get_solana_balance(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, UpstreamTimeout> {
+    let res = params.get("address");
+    if res.is_none() {
+        // no 'address' can be found from parameter list, return errors
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            "no 'address' can be found from parameter list".to_owned(),
+        )));
+    }
+    let budget = UpstreamBudget::start(state.upstream_timeout);
+    let mut balances = vec![];
+
+    let iter = res.unwrap().split(",");
+    for address in iter {
+        let pubkey = match SolanaService::parse_address(address) {
+            Ok(pubkey) => pubkey,
+            Err(e) => return Ok(Json(make_error_json(e.code(), e.to_string()))),
+        };
+        let service = state.solana.clone();
+        match budget.call(move || service.balance(&pubkey)).await? {
+            Ok(balance) => {
+                let resp = BalanceResponse {
+                    address: address.to_owned(),
+                    balance,
+                };
+                balances.push(serde_json::to_value(resp).unwrap());
+            }
+            Err(e) => balances.push(make_error_json(e.code(), e.to_string())),
+        }
+    }
+    Ok(Json(json!(balances)))
+}
+
+#[axum::debug_handler]
+async fn get_solana_history(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, UpstreamTimeout> {
+    let res = params.get("address");
+    if res.is_none() {
+        // no 'address' can be found from parameter list, return errors
+        return Ok(Json(make_error_json(
+            ErrorCode::InvalidParameter,
+            "no 'address' can be found from parameter list".to_owned(),
+        )));
+    }
+    let budget = UpstreamBudget::start(state.upstream_timeout);
+    let mut parsed_transactions = vec![];
+    let iter = res.unwrap().split(",");
+    for address in iter {
+        let pubkey = match SolanaService::parse_address(address) {
+            Ok(pubkey) => pubkey,
+            Err(e) => return Ok(Json(make_error_json(e.code(), e.to_string()))),
+        };
+        let solana_client = state.solana_client.clone();
+        let res = budget
+            .call(move || solana_client.get_transactions_related_to_address(&pubkey))
+            .await?;
+        if let Err(e) = res {
+            return Ok(Json(make_error_json(
+                ErrorCode::SolanaRpcUnavailable,
+                format!(
+                    "cannot parse or get transactions related to address {}, reason: {}",
+                    address, e
+                ),
+            )));
+        }
+        let analyzed_transactions = res.unwrap();
+        let watchlist = Watchlist::new(state.conn.clone());
+        for analyzed_transaction in analyzed_transactions.iter() {
+            if let Err(e) = watchlist.record_solana_transaction(&pubkey, analyzed_transaction) {
+                warn!(
+                    "cannot record the activity of watched address {address}, reason: {}",
+                    e.full_message()
+                );
+            }
+            let effect = analyzed_transaction.effect_on(&pubkey);
+            for ix in analyzed_transaction.instructions.iter() {
+                let transaction_detail = match ix {
+                    AnalyzedInstruction::SplToken(ix_detail) => make_transaction_detail(
+                        ix_detail,
+                        analyzed_transaction,
+                        "token".to_owned(),
+                        address,
+                        effect.map(|effect| (effect.direction, effect.token_change)),
+                    ),
+                    AnalyzedInstruction::Solana(ix_detail) => make_transaction_detail(
+                        ix_detail,
+                        analyzed_transaction,
+                        "sol".to_owned(),
+                        address,
+                        effect.map(|effect| (effect.direction, effect.sol_change)),
+                    ),
+                    // the instructions of the other programs move nothing the analyzer counts
+                    AnalyzedInstruction::Other(_) => continue,
+                };
+                parsed_transactions.push(transaction_detail);
+            }
+        }
+    }
+    Ok(Json(json!(parsed_transactions)))
+}
+
+/// The analysis of any Solana transaction by the parsing of the bridge
+#[axum::debug_handler]
+async fn get_solana_transaction(
+    Path(signature): Path<String>,
+    State(state): State<Arc<ServerData>>,
+) -> Result<Json<Value>, UpstreamTimeout> {
+    let parsed_signature = match SolanaService::parse_signature(&signature) {
+        Ok(signature) => signature,
+        Err(e) => return Ok(Json(make_error_json(e.code(), e.to_string()))),
+    };
+    let budget = UpstreamBudget::start(state.upstream_timeout);
+    let service = state.solana.clone();
+    match budget
+        .call(move || service.transaction(&parsed_signature))
+        .await?
+    {
+        Ok(transaction) => Ok(Json(
+            serde_json::to_value(RespSolanaTransaction::from(&transaction)).unwrap(),
+        )),
+        Err(e) => Ok(Json(make_error_json(e.code(), e.to_string()))),
+    }
+}
+
+#[axum::debug_handler]
+async fn post_solana_transaction(
+    State(state): State<Arc<ServerData>>,
+    Json(base64_data): Json<String>,
+) -> Result<Json<Value>, UpstreamTimeout> {
+    let res = base64::decode(&base64_data);
+    if res.is_err() {
+        return Ok(Json(make_error_json(
+            ErrorCode::RelayTransactionInvalid,
+            "cannot decode base64 data".to_owned(),
+        )));
+    }
+    let bytes = res.unwrap();
+    let res = bincode::deserialize(&bytes);
+    if res.is_err() {
+        // cannot deserialize the binary code into transaction
+        return Ok(Json(make_error_json(
+            ErrorCode::RelayTransactionInvalid,
+            "invalid transaction data".to_owned(),
+        )));
+    }
+    let transaction: Transaction = res.unwrap();
+    if !is_fully_signed(&transaction) {
+        return Ok(Json(make_error_json(
+            ErrorCode::RelayTransactionNotSigned,
+            "the transaction is not fully signed".to_owned(),
+        )));
+    }
+    // the message of a signed transaction has the signers first, the fee payer is the first one
+    let fee_payer = transaction.message.account_keys[0];
+    if fee_payer == state.solana_client.authority_pubkey() {
+        return Ok(Json(make_error_json(
+            ErrorCode::RelayFeePayerIsAuthority,
+            "the fee payer of the transaction is the bridge authority".to_owned(),
+        )));
+    }
+    let settings = state.settings.current();
+    let day = state.clock.now() / (24 * 3600);
+    let relayed = if settings.is_relay_allowed(&fee_payer.to_string()) {
+        Ok(true)
+    } else {
+        state
+            .conn
+            .add_relay(&fee_payer.to_string(), day, settings.relay_daily_quota)
+    };
+    match relayed {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(Json(make_error_json(
+                ErrorCode::RelayQuotaExceeded,
+                format!(
+                    "{fee_payer} has relayed {} transactions today",
+                    settings.relay_daily_quota
+                ),
+            )));
+        }
+        Err(e) => {
+            error!("cannot count the transaction relayed for {fee_payer}, reason: {e}");
+            return Ok(Json(make_error_json(
+                ErrorCode::RelayFailed,
+                "failed to upload transaction".to_owned(),
+            )));
+        }
+    }
+    let solana_client = state.solana_client.clone();
+    let budget = UpstreamBudget::start(state.upstream_timeout);
+    if let Ok(signature) = budget
+        .call(move || solana_client.upload_transaction(&transaction))
+        .await?
+    {
+        Ok(Json(json!(UploadTransactionResponse {
+            result: signature.to_string(),
+        })))
+    } else {
+        Ok(Json(make_error_json(
+            ErrorCode::RelayFailed,
+            "failed to upload transaction".to_owned(),
+        )))
+    }
+}
+
+/// All the signatures required by the message are made, and they're valid
+fn is_fully_signed(transaction: &Transaction) -> bool {
+    let required = usize::from(transaction.message.header.num_required_signatures);
+    required > 0
+        && transaction.signatures.len() == required
+        && transaction.message.account_keys.len() >= required
+        && transaction.verify().is_ok()
+}
+
+async fn shutdown_signal(exit: Arc<Mutex<bool>>) {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+        warn!("ctrl-c is received, sending exit signal");
+
+        {
+            let mut e = exit.lock().unwrap();
+            *e = true;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn make_router(
+    conn: db::Conn,
+    solana_client: SolanaClient,
+    exit: Arc<Mutex<bool>>,
+    clock: SharedClock,
+    price_oracle: Option<Arc<PriceOracle>>,
+    admin: Option<AdminConfig>,
+    custody_multisig: Option<depc::MultisigScript>,
+    report: ReportSettings,
+    upstream_timeout: Duration,
+    attestor: Option<Arc<Attestor>>,
+    settings: Arc<LiveSettings>,
+) -> Router {
+    let idempotency = Arc::new(IdempotencyState {
+        conn: conn.clone(),
+        clock: Arc::clone(&clock),
+    });
+    let mut router = Router::new()
+        .route("/", get(get_root))
+        .route("/exchange/analyze/:txid", get(get_exchange_addresses))
+        .route("/exchange/addresses", get(get_exchange_address_list))
+        .route("/exchange/balances/:days", get(generate_exchange_balances))
+        .route("/exchange/clusters", post(post_rebuild_clusters))
+        .route("/depc/balance-history", get(get_depc_balance_history))
+        .route("/depc/richlist", get(get_depc_richlist))
+        .route("/depc/block/:height_or_hash", get(get_depc_block))
+        .route("/depc/blocks", get(get_depc_blocks))
+        .route("/depc/miners", get(get_depc_miners))
+        .route("/depc/miners/:miner", get(get_depc_miner))
+        .route("/exchange/clusters/:address", get(get_address_cluster))
+        .route("/bridge/deposit-intents", post(post_deposit_intent))
+        .route("/bridge/deposit-intents/:id", get(get_deposit_intent))
+        .route("/bridge/deposit-addresses", post(post_deposit_address))
+        .route(
+            "/bridge/deposit-addresses/:address",
+            get(get_deposit_address),
+        )
+        .route("/bridge/deposit-root", get(get_deposit_root))
+        .route("/bridge/proof/:depc_txid", get(get_deposit_proof))
+        .route("/bridge/events", get(get_bridge_events))
+        .route("/bridge/info", get(get_bridge_info))
+        .route("/bridge/summary", get(get_bridge_summary))
+        .route("/bridge/stats/volume", get(get_bridge_volume))
+        .route("/bridge/fees", get(get_bridge_fees))
+        .route("/bridge/fees/charges", get(get_transfer_fees))
+        .route("/bridge/block-digests", get(get_block_digests))
+        .route("/bridge/held-transfers", get(get_held_transfers))
+        .route("/bridge/catch-up", get(get_bridge_catch_up))
+        .route(
+            "/bridge/unclassified-inflows",
+            get(get_unclassified_inflows),
+        )
+        .route("/bridge/parameters", get(get_bridge_parameters))
+        .route("/bridge/parameter-changes", get(get_parameter_changes))
+        .route("/bridge/withdraw", post(post_bridge_withdraw))
+        .route("/bridge/withdraw/:id", get(get_bridge_withdraw))
+        .route("/bridge/refunds/claims", post(post_refund_claim))
+        .route("/bridge/refunds/:depc_txid/:vout", get(get_refund))
+        .route("/bridge/multisig/payouts", get(get_multisig_payouts))
+        .route(
+            "/bridge/multisig/payouts/:id/signatures",
+            post(post_multisig_signature),
+        )
+        .route("/solana/balance", get(get_solana_balance))
+        .route("/solana/history", get(get_solana_history))
+        .route("/solana/tx/:signature", get(get_solana_transaction))
+        .route("/solana/post_tx", post(post_solana_transaction))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&idempotency),
+            idempotent_requests,
+        ));
+    let timelock = admin
+        .as_ref()
+        .map_or(DEFAULT_TIMELOCK, |admin| admin.timelock);
+    let fee_treasury = admin.as_ref().and_then(|admin| admin.fee_treasury);
+    let depc_decimals = admin
+        .as_ref()
+        .map_or(DepcAmount::DECIMALS, |admin| admin.depc_decimals);
+    if let Some(admin) = admin {
+        router = router.merge(
+            Router::new()
+                .route("/admin/jobs", get(get_jobs))
+                .route("/admin/sanity", get(get_sanity))
+                .route("/admin/settings/reload", post(post_reload_settings))
+                .route(
+                    "/admin/watchlist",
+                    get(get_watchlist).post(post_watch_address),
+                )
+                .route("/admin/watchlist/remove", post(post_unwatch_address))
+                .route("/admin/watchlist/events", get(get_watch_events))
+                .route("/admin/parameter-changes", post(post_parameter_change))
+                .route(
+                    "/admin/parameter-changes/:id/cancel",
+                    post(post_cancel_parameter_change),
+                )
+                .route("/admin/sweep-fees", post(post_sweep_fees))
+                .route("/admin/catch-up/confirm", post(post_confirm_catch_up))
+                .route(
+                    "/admin/unclassified-inflows/:id/resolve",
+                    post(post_resolve_inflow),
+                )
+                .route(
+                    "/admin/held-transfers/:id/release",
+                    post(post_release_held_transfer),
+                )
+                // the token is checked before the key is claimed
+                .route_layer(middleware::from_fn_with_state(
+                    idempotency,
+                    idempotent_requests,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    Arc::new(admin.token),
+                    require_admin_token,
+                )),
+        );
+    }
+    let bridge = BridgeService::new(conn.clone(), Arc::clone(&clock));
+    let depc = DepcService::new(conn.clone(), Arc::clone(&clock));
+    let solana = SolanaService::new(solana_client.clone());
+    router
+        .layer(middleware::from_fn(numeric_amounts))
+        .layer(middleware::from_fn_with_state(
+            upstream_timeout,
+            request_deadline,
+        ))
+        .with_state(Arc::new(ServerData {
+            conn,
+            solana_client,
+            exit,
+            clock,
+            price_oracle,
+            timelock,
+            report,
+            summary_cache: Arc::new(TtlCache::new(SUMMARY_CACHE_TTL)),
+            settings,
+            upstream_timeout,
+            attestor,
+            fee_treasury,
+            depc_decimals,
+            fee_sweep_lock: Arc::new(tokio::sync::Mutex::new(())),
+            custody_multisig,
+            bridge,
+            depc,
+            solana,
+        }))
+}
+
+/// Serve the endpoints of the bridge of `conn` at the root and under `/bridges/{bridge_id}`,
+/// and the endpoints of each one of `other_bridges` (bridge id and the client of its mint)
+/// under `/bridges/{bridge_id}`, the chain endpoints (`/depc/*` and `/exchange/*`) are the
+/// same for all the bridges
+#[allow(clippy::too_many_arguments)]
+fn make_bridges_router(
+    conn: db::Conn,
+    solana_client: SolanaClient,
+    other_bridges: Vec<(String, SolanaClient)>,
+    exit: Arc<Mutex<bool>>,
+    clock: SharedClock,
+    price_oracle: Option<Arc<PriceOracle>>,
+    admin: Option<AdminConfig>,
+    custody_multisig: Option<depc::MultisigScript>,
+    report: ReportSettings,
+    upstream_timeout: Duration,
+    attestor: Option<Arc<Attestor>>,
+    settings: Arc<LiveSettings>,
+) -> Router {
+    let default_router = make_router(
+        conn.clone(),
+        solana_client,
+        Arc::clone(&exit),
+        Arc::clone(&clock),
+        price_oracle.clone(),
+        admin.clone(),
+        custody_multisig,
+        report.clone(),
+        upstream_timeout,
+        attestor.clone(),
+        Arc::clone(&settings),
+    );
+    let mut router = default_router
+        .clone()
+        .nest(&format!("/bridges/{}", conn.bridge_id()), default_router);
+    for (bridge_id, solana_client) in other_bridges {
+        let bridge_router = make_router(
+            conn.scoped(&bridge_id),
+            solana_client,
+            Arc::clone(&exit),
+            Arc::clone(&clock),
+            price_oracle.clone(),
+            admin.clone(),
+            // the custody of the other bridges is not a multisig script
+            None,
+            report.clone(),
+            upstream_timeout,
+            attestor.clone(),
+            Arc::clone(&settings),
+        );
+        router = router.nest(&format!("/bridges/{bridge_id}"), bridge_router);
+    }
+    router
+}
+
+/// The fiat values are reported only when `price_oracle` is given, and the admin endpoints are
+/// served only when `admin` is given, the status and the summary are signed only when
+/// `attestor` is given, the co-signers submit the signed copies of the multisig payouts only
+/// when `custody_multisig` is given, `settings` are shared by all the bridges
+///
+/// The listeners share the state of the endpoints, each one serves the endpoints of its
+/// `routes`. All of them are bound before any serves, so a listener cannot be bound fails the
+/// start
+#[allow(clippy::too_many_arguments)]
+pub async fn run_service(
+    listeners: &[ListenerSettings],
+    conn: db::Conn,
+    solana_client: SolanaClient,
+    other_bridges: Vec<(String, SolanaClient)>,
+    price_oracle: Option<Arc<PriceOracle>>,
+    admin: Option<AdminConfig>,
+    custody_multisig: Option<depc::MultisigScript>,
+    report: ReportSettings,
+    upstream_timeout: Duration,
+    attestor: Option<Arc<Attestor>>,
+    settings: Arc<LiveSettings>,
+    exit_sig: Arc<Mutex<bool>>,
+) -> Result<(), BridgeError> {
+    let has_admin = admin.is_some();
+    let app = make_bridges_router(
+        conn,
+        solana_client,
+        other_bridges,
+        Arc::clone(&exit_sig),
+        system_clock(),
+        price_oracle,
+        admin,
+        custody_multisig,
+        report,
+        upstream_timeout,
+        attestor,
+        settings,
+    );
+    let mut bound = vec![];
+    for listener in listeners.iter() {
+        if listener.routes == ListenerRoutes::Admin && !has_admin {
+            warn!(
+                "listener {} serves the admin endpoints only, but no admin token is configured",
+                listener.bind
+            );
+        }
+        let app = app.clone().layer(middleware::from_fn_with_state(
+            listener.routes,
+            restrict_routes,
+        ));
+        bound.push((BoundListener::bind(listener).await?, app));
+        info!(
+            "listening on {}, routes {:?}{}",
+            listener.bind,
+            listener.routes,
+            if listener.tls.is_some() {
+                " over TLS"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal(exit_sig).await;
+        let _ = shutdown_tx.send(true);
+    });
+    info!("web server is running...");
+    futures::future::join_all(
+        bound
+            .into_iter()
+            .map(|(listener, app)| serve_listener(listener, app, shutdown_rx.clone())),
+    )
+    .await;
+
+    info!("web server exits.");
+    Ok(())
+}
+
+fn make_transaction_detail(
+    ix_detail: &InstructionDetail,
+    transaction: &AnalyzedTransaction,
+    r#type: String,
+    address: &str,
+    effect: Option<(Direction, i128)>,
+) -> TransactionDetail {
+    TransactionDetail {
+        signature: transaction.signature.to_string(),
+        source: ix_detail.source.to_string(),
+        destination: ix_detail.destination.to_string(),
+        source_owner: ix_detail.source_owner.map(|owner| owner.to_string()),
+        destination_owner: ix_detail.destination_owner.map(|owner| owner.to_string()),
+        amount: TokenAmount::new(ix_detail.amount),
+        fee: TokenAmount::new(transaction.fee),
+        timestamp: transaction.timestamp,
+        r#type,
+        address: address.to_owned(),
+        direction: effect.map(|(direction, _)| direction.to_string()),
+        net_change: effect.map_or(0, |(_, change)| change).to_string(),
+    }
+}
+
+impl IntoResponse for BridgeError {
+    fn into_response(self) -> Response {
+        error!("cannot handle the request, reason: {}", self.full_message());
+        // the queries interrupted by the deadline of the request
+        let status = match self.code() {
+            ErrorCode::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(make_error_json(self.code(), self.full_message())),
+        )
+            .into_response()
+    }
+}
+
+fn parse_u64_param(params: &HashMap<String, String>, name: &str) -> Result<Option<u64>, String> {
+    params
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("cannot parse '{}' from string '{}'", name, value))
+        })
+        .transpose()
+}
+
+/// The index bootstrapped from a checkpoint has no coin below it, the balances there are
+/// refused instead of being reported wrong
+fn make_below_checkpoint_error_json(checkpoint: &db::CheckpointRecord) -> Value {
+    make_error_json(
+        ErrorCode::DataNotIndexed,
+        format!(
+            "the balances before checkpoint block {} (time {}) are not indexed",
+            checkpoint.height, checkpoint.time
+        ),
+    )
+}
+
+fn make_error_response(status: StatusCode, code: ErrorCode, message: String) -> Response {
+    (status, Json(make_error_json(code, message))).into_response()
+}
+
+fn make_error_json(code: ErrorCode, message: String) -> Value {
+    serde_json::to_value(ErrorResponse {
+        error: ErrorDetail {
+            code: legacy_error_code(code),
+            error_code: code,
+            message,
+        },
+    })
+    .unwrap()
+}
+
+/// The requests the services reject are answered as the client errors, the failures of the
+/// bridge as 500
+fn reject(e: ServiceError) -> Result<Json<Value>, BridgeError> {
+    match e {
+        ServiceError::Rejected { code, message } => Ok(Json(make_error_json(code, message))),
+        ServiceError::Bridge(e) => Err(e),
+    }
+}
+
+/// The numeric codes of the errors made before `ErrorCode`, the transactions `/solana/post_tx`
+/// refuses to relay have their own, the others are 0
+fn legacy_error_code(code: ErrorCode) -> u32 {
+    match code {
+        ErrorCode::RelayTransactionNotSigned => 1001,
+        ErrorCode::RelayFeePayerIsAuthority => 1002,
+        ErrorCode::RelayQuotaExceeded => 1003,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+    };
+    use solana_sdk::{
+        commitment_config::CommitmentConfig,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        signature::{Keypair, Signature},
+        signer::{keypair::keypair_from_seed, Signer},
+        system_program,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::bridge::{
+        make_withdraw_request_message, publish_deposit_root, set_deposit_xpub, BridgeSettings,
+        CATCH_UP_AWAITING_CONFIRMATION, CATCH_UP_INDEXING, DEFAULT_RELAY_DAILY_QUOTA,
+    };
+    use crate::ledger::Ledger;
+    use crate::price::FixedPriceProvider;
+    use crate::rest::schema::OpenApi;
+    use crate::rest::verify_attestation;
+    use crate::solana::{default_fixture_dir, make_fixture_rpc_client, DEFAULT_LOCAL_ENDPOINT};
+    use crate::testing::MockClock;
+
+    /// Set this environment variable to rewrite the golden files from the current responses
+    const UPDATE_GOLDEN_FILES_ENV: &str = "UPDATE_GOLDEN_FILES";
+
+    const TEST_EXCHANGE_ADDRESS_1: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_EXCHANGE_ADDRESS_2: &str = "323nLaJTdaNjHMrtyVc7kXsDm448FJTmi9";
+    const TEST_USER_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+    const TEST_SOLANA_BALANCE_ADDRESS: &str = "Afa4Jc8cGhyQc6v64sVw7qpUMiHDrTSc2umPwEdvAZ9M";
+    const TEST_SOLANA_HISTORY_ADDRESS: &str = "3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L";
+    /// The token transfer from it in the fixtures
+    const TEST_SOLANA_TX_SIGNATURE: &str =
+        "25A1pSwLHvagx8FD3oyAGot1Kfp9keqFhdfGgDZq4s9xjkPc4h5R3P6ikf5ookcsKuZEJDcFShsa3JdgVXYbmgRx";
+    const TEST_WITHDRAW_SIGNATURE: &str =
+        "5CJsD1Gu5B3mipr6FNftqyhh9zs42Tg4GDoFu8Stx7cQjyuUwxHqKQwMQD9UmHhiWfp2dGBvMy1LPbV318LfLBLc";
+    /// The testnet account `m/44'/0'/0'` of mnemonic `abandon abandon ... about`
+    const TEST_DEPOSIT_XPUB: &str =
+        "tpubDCBWBScQPGv4Xk3JSbhw6wYYpayMjb2eAYyArpbSqQTbLDpphHGAetB6VQgVeftLML8vDSUEWcC2xDi3qJJ3YCDChJDvqVzpgoYSuT52MhJ";
+    /// The first deposit address derived from it
+    const TEST_DEPOSIT_ADDRESS: &str = "n1M8ZVQtL7QoFvGMg24D6b2ojWvFXCGpoS";
+    const TEST_CUSTODY_ADDRESS: &str = "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS";
+    const TEST_NOW: u64 = 1731000600;
+    const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+    const TEST_TIMELOCK: Duration = Duration::from_secs(3600);
+    const TEST_UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+    const TEST_AUTHORITY_SEED: [u8; 32] = [9; 32];
+    const TEST_QUOTA_PAYER_SEED: [u8; 32] = [11; 32];
+    const TEST_ATTESTATION_SEED: [u8; 32] = [12; 32];
+
+    fn golden_dir() -> PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("samples/rest")
+    }
+
+    fn assert_golden(name: &str, value: &Value) {
+        let path = golden_dir().join(format!("{}.json", name));
+        if std::env::var_os(UPDATE_GOLDEN_FILES_ENV).is_some() {
+            std::fs::create_dir_all(golden_dir()).unwrap();
+            std::fs::write(&path, serde_json::to_string_pretty(value).unwrap() + "\n").unwrap();
+            return;
+        }
+        let content = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "cannot read golden file {}, set {} to write it, reason: {}",
+                path.display(),
+                UPDATE_GOLDEN_FILES_ENV,
+                e
+            )
+        });
+        let expected: Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(*value, expected, "response differs from {}", path.display());
+    }
+
+    /// Blocks 860129 and 860130, the transaction `b1` spends the coins of both exchange
+    /// addresses and returns the change to the first one
+    fn make_test_conn() -> db::Conn {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let (txid_a1, txid_b1) = (format!("{:064x}", 0xa1), format!("{:064x}", 0xb1));
+        // the index is bootstrapped from block 860129
+        conn.add_block("block_860129", 860129, "miner", 1730999820)
+            .unwrap();
+        conn.save_checkpoint(860129, "block_860129", 1730999820, TEST_NOW - 7200)
+            .unwrap();
+        // the next block is pruned by the node
+        conn.mark_height_unavailable(860131, "block block_860131: pruned", TEST_NOW - 60)
+            .unwrap();
+        conn.add_transaction("block_860129", &txid_a1).unwrap();
+        conn.add_coin(
+            &txid_a1,
+            0,
+            DepcAmount::from_value64(30 * 100000000),
+            TEST_EXCHANGE_ADDRESS_1,
+            "",
+        )
+        .unwrap();
+        conn.add_coin(
+            &txid_a1,
+            1,
+            DepcAmount::from_value64(20 * 100000000),
+            TEST_EXCHANGE_ADDRESS_2,
+            "",
+        )
+        .unwrap();
+        conn.add_block("block_860130", 860130, "miner", 1731000000)
+            .unwrap();
+        conn.add_transaction("block_860130", &txid_b1).unwrap();
+        conn.mark_coin_to_spent(&txid_a1, 0, &txid_b1, 860130)
+            .unwrap();
+        conn.mark_coin_to_spent(&txid_a1, 1, &txid_b1, 860130)
+            .unwrap();
+        conn.add_coin(
+            &txid_b1,
+            0,
+            DepcAmount::from_value64(40 * 100000000),
+            TEST_USER_ADDRESS,
+            "",
+        )
+        .unwrap();
+        conn.add_coin(
+            &txid_b1,
+            1,
+            DepcAmount::from_value64(9 * 100000000),
+            TEST_EXCHANGE_ADDRESS_1,
+            "",
+        )
+        .unwrap();
+        // the bridge actions, the first deposit is found before any price is recorded
+        let actions = [
+            (ACTION_DEPOSIT, 0xc1, 2, 1730999000),
+            (ACTION_DEPOSIT, 0xc2, 30, 1730999820),
+            (ACTION_WITHDRAW, 0xc3, 5, 1731000000),
+        ];
+        for (action, txid, coins, timestamp) in actions {
+            conn.add_pending_action(
+                action,
+                &format!("{:064x}", txid),
+                TEST_SOLANA_BALANCE_ADDRESS,
+                DepcAmount::from_value64(coins * 100000000),
+                timestamp,
+            )
+            .unwrap();
+        }
+        // an output of b1 cannot be processed 8 days ago
+        conn.add_dead_letter(
+            860130,
+            &txid_b1,
+            Some(1),
+            "unknown recipient",
+            TEST_NOW - 8 * 86400,
+        )
+        .unwrap();
+        // the first deposit is minted and the withdrawal is returned
+        let events = [
+            ("custody_in", 0xc1, 2, 1730999000),
+            ("wrapped_minted", 0xd1, 2, 1730999100),
+            ("custody_in", 0xc2, 30, 1730999820),
+            ("wrapped_returned", 0xc3, 5, 1731000000),
+        ];
+        for (event, reference, coins, timestamp) in events {
+            conn.add_event(
+                event,
+                DepcAmount::from_value64(coins * 100000000),
+                &format!("{:064x}", reference),
+                timestamp,
+            )
+            .unwrap();
+        }
+        set_deposit_xpub(&conn, &TEST_DEPOSIT_XPUB.parse().unwrap()).unwrap();
+        // c0 is minted before the index is bootstrapped, the root covers it and c1, c2 is
+        // minted after the root
+        let deposits = [
+            (0xc0, 1, 1730990000, 0xd0, 1730990100),
+            (0xc1, 2, 1730999000, 0xd1, 1730999100),
+            (0xc2, 30, 1730999820, 0xd2, TEST_NOW - 60),
+        ];
+        for (txid, coins, timestamp, signature, minted_at) in deposits {
+            let txid = format!("{:064x}", txid);
+            conn.save_deposit(
+                &txid,
+                TEST_SOLANA_BALANCE_ADDRESS,
+                DepcAmount::from_value64(coins * 100000000),
+                timestamp,
+            )
+            .unwrap();
+            conn.confirm_deposit(&format!("{:064x}", signature), minted_at, &txid)
+                .unwrap();
+        }
+        publish_deposit_root(&conn, TEST_NOW - 600).unwrap();
+        conn.add_price("usd", 1730999820, 0.5, "test").unwrap();
+        conn.add_price("usd", 1730999950, 0.25, "test").unwrap();
+        // an activated change and a pending one
+        conn.add_parameter_change("deposit_threshold", "2000", TEST_NOW - 7300, TEST_NOW - 100)
+            .unwrap();
+        conn.add_parameter_change(
+            "withdraw_threshold",
+            "3000",
+            TEST_NOW - 600,
+            TEST_NOW + 3000,
+        )
+        .unwrap();
+        // the bridge fees of c0 two days ago, c1 and c3, none is swept yet
+        let ledger = Ledger::new(conn.clone());
+        ledger
+            .deposit_fee(
+                &format!("{:064x}", 0xc0),
+                DepcAmount::from_value64(100000),
+                1730850000,
+            )
+            .unwrap();
+        ledger
+            .deposit_fee(
+                &format!("{:064x}", 0xc1),
+                DepcAmount::from_value64(200000),
+                1730999000,
+            )
+            .unwrap();
+        ledger
+            .withdraw_fee(
+                &format!("{:064x}", 0xc3),
+                DepcAmount::from_value64(50000),
+                1731000000,
+            )
+            .unwrap();
+        // the bridge was down, the backlog of the catch-up awaits the confirmation
+        let plan_id = conn
+            .add_catch_up_plan(860130, 860131, CATCH_UP_INDEXING, TEST_NOW - 3600)
+            .unwrap();
+        conn.plan_catch_up(
+            plan_id,
+            CATCH_UP_AWAITING_CONFIRMATION,
+            1,
+            DepcAmount::from_value64(30 * 100000000),
+            1,
+            DepcAmount::from_value64(5 * 100000000),
+            TEST_NOW - 3000,
+        )
+        .unwrap();
+        // a job runs every 6 hours, the last run fails
+        conn.save_job("address_clusters", "0 */6 * * *", true, Some(1731002400))
+            .unwrap();
+        conn.start_job("address_clusters", 1730980800, Some(1731002400))
+            .unwrap();
+        conn.finish_job("address_clusters", 1730980810, Some("database error"))
+            .unwrap();
+        // an exchange address is watched and received the coins of b1
+        conn.save_watched_address(
+            "depc",
+            TEST_EXCHANGE_ADDRESS_1,
+            "exchange",
+            Some("https://example.com/hook"),
+            TEST_NOW - 3600,
+        )
+        .unwrap();
+        conn.save_watched_address(
+            "solana",
+            TEST_SOLANA_HISTORY_ADDRESS,
+            "history",
+            None,
+            TEST_NOW - 3600,
+        )
+        .unwrap();
+        conn.add_watch_event(
+            "depc",
+            TEST_EXCHANGE_ADDRESS_1,
+            &txid_b1,
+            "depc",
+            "in",
+            "900000000",
+            1731000000,
+        )
+        .unwrap();
+        // a payment to the custody address without OP_RETURN and a dust one
+        for (txid, coins, reason) in [
+            (0xe1, 100000000, "no_recipient"),
+            (0xe2, 500, "below_threshold"),
+        ] {
+            conn.add_custody_inflow(
+                &format!("{:064x}", txid),
+                0,
+                TEST_CUSTODY_ADDRESS,
+                DepcAmount::from_value64(coins),
+                reason,
+                860130,
+                TEST_NOW - 600,
+            )
+            .unwrap();
+        }
+        // the withdrawal is over the maximum transfer
+        conn.hold_pending_action(
+            3,
+            "500000000 is over the maximum transfer 100000000",
+            TEST_NOW - 600,
+        )
+        .unwrap();
+        // the digests of the blocks, none of their outputs is to the custody address
+        let digests = [
+            (
+                860129,
+                "3a460f5c431af77e09677052c02680fb3520edecd819f3a8f6b9b2ad131b1d10",
+            ),
+            (
+                860130,
+                "fbe649b0ed1e36069f454b9edbf24d2f17322ef46552021a2e05b442288459e0",
+            ),
+        ];
+        for (height, digest) in digests {
+            conn.add_block_digest(
+                height,
+                &format!("block_{height}"),
+                0,
+                digest,
+                TEST_NOW - 600,
+            )
+            .unwrap();
+        }
+        // the bridge fee is kept from the second deposit, 30 DePC are minted
+        conn.add_transfer_fee(
+            ACTION_DEPOSIT,
+            &format!("{:064x}", 0xc2),
+            2,
+            DepcAmount::from_value64(3000300000),
+            DepcAmount::from_value64(300000),
+            1730999820,
+        )
+        .unwrap();
+        // the quota of the payer is used up today
+        let quota_payer = keypair_from_seed(&TEST_QUOTA_PAYER_SEED).unwrap().pubkey();
+        for _ in 0..DEFAULT_RELAY_DAILY_QUOTA {
+            conn.add_relay(
+                &quota_payer.to_string(),
+                TEST_NOW / (24 * 3600),
+                DEFAULT_RELAY_DAILY_QUOTA,
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    /// A transaction paid by `payer`, it's signed by the payer only if `signed`
+    fn make_test_transaction(payer: &Keypair, signed: bool) -> Value {
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::default(),
+            &[1],
+            vec![AccountMeta::new(payer.pubkey(), true)],
+        );
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        if signed {
+            transaction.sign(&[payer], Hash::default());
+        }
+        json!(base64::encode(bincode::serialize(&transaction).unwrap()))
+    }
+
+    fn make_test_settings() -> Arc<LiveSettings> {
+        Arc::new(LiveSettings::new(None, BridgeSettings::default()))
+    }
+
+    fn make_test_admin() -> Option<AdminConfig> {
+        Some(AdminConfig {
+            token: TEST_ADMIN_TOKEN.to_owned(),
+            timelock: TEST_TIMELOCK,
+            fee_treasury: None,
+            depc_decimals: DepcAmount::DECIMALS,
+        })
+    }
+
+    fn make_test_solana_client() -> SolanaClient {
+        let rpc_client = make_fixture_rpc_client(
+            DEFAULT_LOCAL_ENDPOINT,
+            &default_fixture_dir(),
+            CommitmentConfig::confirmed(),
+        );
+        SolanaClient::new_with_rpc_client(
+            rpc_client,
+            Pubkey::new_unique(),
+            keypair_from_seed(&TEST_AUTHORITY_SEED).unwrap(),
+        )
+    }
+
+    fn make_test_router() -> Router {
+        let solana_client = make_test_solana_client();
+        let conn = make_test_conn();
+        // the prices are recorded by the test conn, the provider is never asked
+        let price_oracle = PriceOracle::new(
+            conn.clone(),
+            Box::new(FixedPriceProvider::new(HashMap::new())),
+        );
+        make_router(
+            conn,
+            solana_client,
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            Some(Arc::new(price_oracle)),
+            make_test_admin(),
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            None,
+            make_test_settings(),
+        )
+    }
+
+    async fn call(router: &Router, method: &str, uri: &str, body: Option<Value>) -> Vec<u8> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+            .body(match body {
+                Some(body) => Body::from(body.to_string()),
+                None => Body::empty(),
+            })
+            .unwrap();
+        let resp = router.clone().oneshot(request).await.unwrap();
+        assert!(
+            resp.status().is_success(),
+            "{} {} returns {}",
+            method,
+            uri,
+            resp.status()
+        );
+        to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    // the blocking rpc client of solana needs the multi-thread runtime
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_golden_files() {
+        let openapi = OpenApi::load();
+        let router = make_test_router();
+
+        let root = call(&router, "GET", "/", None).await;
+        assert_eq!(root, b"hello world");
+        let mut covered = BTreeSet::from([("/".to_owned(), "get".to_owned())]);
+
+        // the analyzing saves the exchange addresses those are used by the balances
+        let txid_b1 = format!("{:064x}", 0xb1);
+        let withdraw_signer = keypair_from_seed(&[7; 32]).unwrap();
+        let withdraw_message_signature = withdraw_signer
+            .sign_message(
+                make_withdraw_request_message(TEST_WITHDRAW_SIGNATURE, TEST_USER_ADDRESS)
+                    .as_bytes(),
+            )
+            .to_string();
+        let cases = [
+            // the rows of the test conn are counted before any request writes
+            (
+                "admin_sanity",
+                "/admin/sanity",
+                "GET",
+                "/admin/sanity?stuck_after=600".to_owned(),
+                None,
+            ),
+            (
+                "exchange_analyze",
+                "/exchange/analyze/{txid}",
+                "GET",
+                format!("/exchange/analyze/{}", txid_b1),
+                None,
+            ),
+            (
+                "exchange_addresses",
+                "/exchange/addresses",
+                "GET",
+                "/exchange/addresses?limit=1".to_owned(),
+                None,
+            ),
+            (
+                "exchange_addresses_next_page",
+                "/exchange/addresses",
+                "GET",
+                format!("/exchange/addresses?cursor={}", TEST_EXCHANGE_ADDRESS_1),
+                None,
+            ),
+            (
+                "exchange_addresses_invalid_format",
+                "/exchange/addresses",
+                "GET",
+                "/exchange/addresses?format=xml".to_owned(),
+                None,
+            ),
+            (
+                "exchange_balances",
+                "/exchange/balances/{days}",
+                "GET",
+                "/exchange/balances/7".to_owned(),
+                None,
+            ),
+            (
+                "exchange_balances_timezone",
+                "/exchange/balances/{days}",
+                "GET",
+                "/exchange/balances/1?start_height=860130&block_interval=60&timezone=%2B08:00"
+                    .to_owned(),
+                None,
+            ),
+            (
+                "exchange_balances_below_checkpoint",
+                "/exchange/balances/{days}",
+                "GET",
+                "/exchange/balances/1?start_height=860000".to_owned(),
+                None,
+            ),
+            (
+                "exchange_clusters_rebuild",
+                "/exchange/clusters",
+                "POST",
+                "/exchange/clusters".to_owned(),
+                None,
+            ),
+            (
+                "exchange_cluster",
+                "/exchange/clusters/{address}",
+                "GET",
+                format!("/exchange/clusters/{}", TEST_EXCHANGE_ADDRESS_2),
+                None,
+            ),
+            (
+                "exchange_cluster_unknown_address",
+                "/exchange/clusters/{address}",
+                "GET",
+                format!("/exchange/clusters/{}", TEST_USER_ADDRESS),
+                None,
+            ),
+            (
+                "depc_balance_history",
+                "/depc/balance-history",
+                "GET",
+                format!(
+                    "/depc/balance-history?addresses={},{}&from=1730999820&to=1731000000&step=180",
+                    TEST_EXCHANGE_ADDRESS_1, TEST_EXCHANGE_ADDRESS_2
+                ),
+                None,
+            ),
+            (
+                "depc_balance_history_usd",
+                "/depc/balance-history",
+                "GET",
+                format!(
+                    "/depc/balance-history?addresses={},{}&from=1730999820&to=1731000000&step=180&currency=USD",
+                    TEST_EXCHANGE_ADDRESS_1, TEST_EXCHANGE_ADDRESS_2
+                ),
+                None,
+            ),
+            (
+                "depc_balance_history_below_checkpoint",
+                "/depc/balance-history",
+                "GET",
+                format!(
+                    "/depc/balance-history?addresses={}&from=1730999000&to=1731000000",
+                    TEST_EXCHANGE_ADDRESS_1
+                ),
+                None,
+            ),
+            (
+                "depc_balance_history_no_addresses",
+                "/depc/balance-history",
+                "GET",
+                "/depc/balance-history".to_owned(),
+                None,
+            ),
+            (
+                "depc_richlist",
+                "/depc/richlist",
+                "GET",
+                "/depc/richlist?limit=2".to_owned(),
+                None,
+            ),
+            (
+                "depc_richlist_invalid_limit",
+                "/depc/richlist",
+                "GET",
+                "/depc/richlist?limit=0".to_owned(),
+                None,
+            ),
+            (
+                "depc_block",
+                "/depc/block/{height_or_hash}",
+                "GET",
+                "/depc/block/860130".to_owned(),
+                None,
+            ),
+            (
+                "depc_block_by_hash",
+                "/depc/block/{height_or_hash}",
+                "GET",
+                "/depc/block/block_860129".to_owned(),
+                None,
+            ),
+            (
+                "depc_block_not_found",
+                "/depc/block/{height_or_hash}",
+                "GET",
+                "/depc/block/1".to_owned(),
+                None,
+            ),
+            (
+                "depc_blocks",
+                "/depc/blocks",
+                "GET",
+                "/depc/blocks?from=860129".to_owned(),
+                None,
+            ),
+            (
+                "depc_blocks_invalid_range",
+                "/depc/blocks",
+                "GET",
+                "/depc/blocks?from=860000".to_owned(),
+                None,
+            ),
+            (
+                "depc_miners",
+                "/depc/miners",
+                "GET",
+                "/depc/miners".to_owned(),
+                None,
+            ),
+            (
+                "depc_miners_invalid_limit",
+                "/depc/miners",
+                "GET",
+                "/depc/miners?limit=0".to_owned(),
+                None,
+            ),
+            (
+                "depc_miner",
+                "/depc/miners/{miner}",
+                "GET",
+                "/depc/miners/miner".to_owned(),
+                None,
+            ),
+            (
+                "depc_miner_not_found",
+                "/depc/miners/{miner}",
+                "GET",
+                "/depc/miners/nobody".to_owned(),
+                None,
+            ),
+            (
+                "bridge_deposit_intent_create",
+                "/bridge/deposit-intents",
+                "POST",
+                "/bridge/deposit-intents".to_owned(),
+                Some(json!({
+                    "sender": TEST_USER_ADDRESS,
+                    "recipient": TEST_SOLANA_BALANCE_ADDRESS,
+                })),
+            ),
+            (
+                "bridge_deposit_intent_invalid_recipient",
+                "/bridge/deposit-intents",
+                "POST",
+                "/bridge/deposit-intents".to_owned(),
+                Some(json!({"sender": TEST_USER_ADDRESS, "recipient": "invalid"})),
+            ),
+            (
+                "bridge_deposit_intent",
+                "/bridge/deposit-intents/{id}",
+                "GET",
+                "/bridge/deposit-intents/1".to_owned(),
+                None,
+            ),
+            (
+                "bridge_deposit_intent_not_found",
+                "/bridge/deposit-intents/{id}",
+                "GET",
+                "/bridge/deposit-intents/2".to_owned(),
+                None,
+            ),
+            (
+                "bridge_deposit_address_create",
+                "/bridge/deposit-addresses",
+                "POST",
+                "/bridge/deposit-addresses".to_owned(),
+                Some(json!({"recipient": TEST_SOLANA_BALANCE_ADDRESS})),
+            ),
+            (
+                "bridge_deposit_address_reserved_recipient",
+                "/bridge/deposit-addresses",
+                "POST",
+                "/bridge/deposit-addresses".to_owned(),
+                Some(json!({"recipient": system_program::id().to_string()})),
+            ),
+            (
+                "bridge_deposit_address",
+                "/bridge/deposit-addresses/{address}",
+                "GET",
+                format!("/bridge/deposit-addresses/{}", TEST_DEPOSIT_ADDRESS),
+                None,
+            ),
+            (
+                "bridge_deposit_address_not_found",
+                "/bridge/deposit-addresses/{address}",
+                "GET",
+                format!("/bridge/deposit-addresses/{}", TEST_USER_ADDRESS),
+                None,
+            ),
+            (
+                "bridge_deposit_root",
+                "/bridge/deposit-root",
+                "GET",
+                "/bridge/deposit-root".to_owned(),
+                None,
+            ),
+            (
+                "bridge_deposit_proof",
+                "/bridge/proof/{depc_txid}",
+                "GET",
+                format!("/bridge/proof/{:064x}", 0xc1),
+                None,
+            ),
+            (
+                "bridge_deposit_proof_not_covered",
+                "/bridge/proof/{depc_txid}",
+                "GET",
+                format!("/bridge/proof/{:064x}", 0xc2),
+                None,
+            ),
+            (
+                "bridge_deposit_proof_not_found",
+                "/bridge/proof/{depc_txid}",
+                "GET",
+                format!("/bridge/proof/{:064x}", 0xc3),
+                None,
+            ),
+            (
+                "bridge_withdraw_create",
+                "/bridge/withdraw",
+                "POST",
+                "/bridge/withdraw".to_owned(),
+                Some(json!({
+                    "signature": TEST_WITHDRAW_SIGNATURE,
+                    "recipient": TEST_USER_ADDRESS,
+                    "signer": withdraw_signer.pubkey().to_string(),
+                    "message_signature": withdraw_message_signature,
+                })),
+            ),
+            (
+                // the message is signed for another recipient
+                "bridge_withdraw_invalid_message_signature",
+                "/bridge/withdraw",
+                "POST",
+                "/bridge/withdraw".to_owned(),
+                Some(json!({
+                    "signature": TEST_WITHDRAW_SIGNATURE,
+                    "recipient": TEST_EXCHANGE_ADDRESS_1,
+                    "signer": withdraw_signer.pubkey().to_string(),
+                    "message_signature": withdraw_message_signature,
+                })),
+            ),
+            (
+                "bridge_withdraw",
+                "/bridge/withdraw/{id}",
+                "GET",
+                "/bridge/withdraw/1".to_owned(),
+                None,
+            ),
+            (
+                "bridge_withdraw_not_found",
+                "/bridge/withdraw/{id}",
+                "GET",
+                "/bridge/withdraw/2".to_owned(),
+                None,
+            ),
+            (
+                "bridge_summary",
+                "/bridge/summary",
+                "GET",
+                "/bridge/summary".to_owned(),
+                None,
+            ),
+            (
+                "bridge_events",
+                "/bridge/events",
+                "GET",
+                "/bridge/events?since_seq=1&limit=2".to_owned(),
+                None,
+            ),
+            (
+                "bridge_events_numeric",
+                "/bridge/events",
+                "GET",
+                "/bridge/events?since_seq=1&limit=2&numeric=1".to_owned(),
+                None,
+            ),
+            (
+                "bridge_events_invalid_since_seq",
+                "/bridge/events",
+                "GET",
+                "/bridge/events?since_seq=-1".to_owned(),
+                None,
+            ),
+            (
+                "bridge_info",
+                "/bridge/info",
+                "GET",
+                "/bridge/info".to_owned(),
+                None,
+            ),
+            (
+                "bridge_stats_volume",
+                "/bridge/stats/volume",
+                "GET",
+                "/bridge/stats/volume?currency=usd".to_owned(),
+                None,
+            ),
+            (
+                "bridge_stats_volume_unpriced",
+                "/bridge/stats/volume",
+                "GET",
+                "/bridge/stats/volume?currency=eur&from=1730999820".to_owned(),
+                None,
+            ),
+            (
+                "bridge_stats_volume_invalid_range",
+                "/bridge/stats/volume",
+                "GET",
+                "/bridge/stats/volume?from=1731000000&to=1730999820".to_owned(),
+                None,
+            ),
+            (
+                "bridge_fees",
+                "/bridge/fees",
+                "GET",
+                "/bridge/fees".to_owned(),
+                None,
+            ),
+            (
+                "bridge_fees_today",
+                "/bridge/fees",
+                "GET",
+                "/bridge/fees?from=1730937600&to=1731023999".to_owned(),
+                None,
+            ),
+            (
+                "bridge_fees_invalid_range",
+                "/bridge/fees",
+                "GET",
+                "/bridge/fees?from=1731000000&to=1730999820".to_owned(),
+                None,
+            ),
+            (
+                "bridge_catch_up",
+                "/bridge/catch-up",
+                "GET",
+                "/bridge/catch-up".to_owned(),
+                None,
+            ),
+            (
+                "bridge_parameters",
+                "/bridge/parameters",
+                "GET",
+                "/bridge/parameters".to_owned(),
+                None,
+            ),
+            (
+                "bridge_block_digests",
+                "/bridge/block-digests",
+                "GET",
+                "/bridge/block-digests?from=860129&to=860135".to_owned(),
+                None,
+            ),
+            (
+                "bridge_block_digests_invalid_range",
+                "/bridge/block-digests",
+                "GET",
+                "/bridge/block-digests?from=860130&to=860129".to_owned(),
+                None,
+            ),
+            (
+                "bridge_held_transfers",
+                "/bridge/held-transfers",
+                "GET",
+                "/bridge/held-transfers?limit=10".to_owned(),
+                None,
+            ),
+            (
+                "bridge_fees_charges",
+                "/bridge/fees/charges",
+                "GET",
+                "/bridge/fees/charges?limit=10".to_owned(),
+                None,
+            ),
+            (
+                "bridge_unclassified_inflows",
+                "/bridge/unclassified-inflows",
+                "GET",
+                "/bridge/unclassified-inflows?limit=10".to_owned(),
+                None,
+            ),
+            (
+                "bridge_multisig_payouts",
+                "/bridge/multisig/payouts",
+                "GET",
+                "/bridge/multisig/payouts".to_owned(),
+                None,
+            ),
+            (
+                "bridge_multisig_signature_not_found",
+                "/bridge/multisig/payouts/{id}/signatures",
+                "POST",
+                "/bridge/multisig/payouts/1/signatures".to_owned(),
+                Some(json!({"raw_tx": "0200", "pubkey": "02ab"})),
+            ),
+            (
+                "bridge_refund_claim_not_found",
+                "/bridge/refunds/claims",
+                "POST",
+                "/bridge/refunds/claims".to_owned(),
+                Some(json!({
+                    "depc_txid": format!("{:064x}", 0xd3),
+                    "vout": 0,
+                    "address": TEST_USER_ADDRESS,
+                    "signature": "signature",
+                })),
+            ),
+            (
+                "bridge_refund_not_found",
+                "/bridge/refunds/{depc_txid}/{vout}",
+                "GET",
+                format!("/bridge/refunds/{:064x}/0", 0xd3),
+                None,
+            ),
+            (
+                "bridge_parameter_changes",
+                "/bridge/parameter-changes",
+                "GET",
+                "/bridge/parameter-changes?limit=10".to_owned(),
+                None,
+            ),
+            (
+                "bridge_parameter_changes_invalid_limit",
+                "/bridge/parameter-changes",
+                "GET",
+                "/bridge/parameter-changes?limit=0".to_owned(),
+                None,
+            ),
+            (
+                "admin_jobs",
+                "/admin/jobs",
+                "GET",
+                "/admin/jobs".to_owned(),
+                None,
+            ),
+            (
+                "admin_parameter_change_create",
+                "/admin/parameter-changes",
+                "POST",
+                "/admin/parameter-changes".to_owned(),
+                Some(json!({"name": "withdraw_threshold", "value": "5000"})),
+            ),
+            (
+                "admin_parameter_change_unknown",
+                "/admin/parameter-changes",
+                "POST",
+                "/admin/parameter-changes".to_owned(),
+                Some(json!({"name": "fee", "value": "5000"})),
+            ),
+            (
+                "admin_parameter_change_cancel",
+                "/admin/parameter-changes/{id}/cancel",
+                "POST",
+                "/admin/parameter-changes/2/cancel".to_owned(),
+                None,
+            ),
+            (
+                "admin_parameter_change_cancel_activated",
+                "/admin/parameter-changes/{id}/cancel",
+                "POST",
+                "/admin/parameter-changes/1/cancel".to_owned(),
+                None,
+            ),
+            (
+                "admin_watchlist",
+                "/admin/watchlist",
+                "GET",
+                "/admin/watchlist".to_owned(),
+                None,
+            ),
+            (
+                "admin_watchlist_add",
+                "/admin/watchlist",
+                "POST",
+                "/admin/watchlist".to_owned(),
+                Some(json!({
+                    "chain": "depc",
+                    "address": TEST_USER_ADDRESS,
+                    "label": "user",
+                    "webhook": "https://example.com/user",
+                })),
+            ),
+            (
+                "admin_watchlist_add_unknown_chain",
+                "/admin/watchlist",
+                "POST",
+                "/admin/watchlist".to_owned(),
+                Some(json!({"chain": "bitcoin", "address": TEST_USER_ADDRESS})),
+            ),
+            (
+                "admin_watchlist_remove",
+                "/admin/watchlist/remove",
+                "POST",
+                "/admin/watchlist/remove".to_owned(),
+                Some(json!({"chain": "depc", "address": TEST_USER_ADDRESS})),
+            ),
+            (
+                "admin_settings_reload_no_file",
+                "/admin/settings/reload",
+                "POST",
+                "/admin/settings/reload".to_owned(),
+                None,
+            ),
+            (
+                "admin_sweep_fees_not_configured",
+                "/admin/sweep-fees",
+                "POST",
+                "/admin/sweep-fees".to_owned(),
+                None,
+            ),
+            (
+                "admin_unclassified_inflow_resolve",
+                "/admin/unclassified-inflows/{id}/resolve",
+                "POST",
+                "/admin/unclassified-inflows/1/resolve".to_owned(),
+                Some(json!({"resolution": "returned to the sender"})),
+            ),
+            (
+                "admin_unclassified_inflow_resolve_not_found",
+                "/admin/unclassified-inflows/{id}/resolve",
+                "POST",
+                "/admin/unclassified-inflows/1/resolve".to_owned(),
+                Some(json!({"resolution": "returned to the sender"})),
+            ),
+            (
+                "admin_held_transfer_release",
+                "/admin/held-transfers/{id}/release",
+                "POST",
+                "/admin/held-transfers/3/release".to_owned(),
+                None,
+            ),
+            (
+                "admin_held_transfer_release_not_found",
+                "/admin/held-transfers/{id}/release",
+                "POST",
+                "/admin/held-transfers/3/release".to_owned(),
+                None,
+            ),
+            (
+                "admin_catch_up_confirm",
+                "/admin/catch-up/confirm",
+                "POST",
+                "/admin/catch-up/confirm".to_owned(),
+                None,
+            ),
+            (
+                "admin_catch_up_confirm_not_found",
+                "/admin/catch-up/confirm",
+                "POST",
+                "/admin/catch-up/confirm".to_owned(),
+                None,
+            ),
+            (
+                "admin_watchlist_events",
+                "/admin/watchlist/events",
+                "GET",
+                format!("/admin/watchlist/events?address={}", TEST_EXCHANGE_ADDRESS_1),
+                None,
+            ),
+            (
+                "admin_watchlist_events_invalid_limit",
+                "/admin/watchlist/events",
+                "GET",
+                "/admin/watchlist/events?limit=0".to_owned(),
+                None,
+            ),
+            (
+                "solana_balance",
+                "/solana/balance",
+                "GET",
+                format!("/solana/balance?address={}", TEST_SOLANA_BALANCE_ADDRESS),
+                None,
+            ),
+            (
+                "solana_balance_invalid_address",
+                "/solana/balance",
+                "GET",
+                "/solana/balance?address=invalid".to_owned(),
+                None,
+            ),
+            (
+                "solana_balance_no_address",
+                "/solana/balance",
+                "GET",
+                "/solana/balance".to_owned(),
+                None,
+            ),
+            (
+                "solana_history",
+                "/solana/history",
+                "GET",
+                format!("/solana/history?address={}", TEST_SOLANA_HISTORY_ADDRESS),
+                None,
+            ),
+            (
+                "solana_history_no_address",
+                "/solana/history",
+                "GET",
+                "/solana/history".to_owned(),
+                None,
+            ),
+            (
+                "solana_tx",
+                "/solana/tx/{signature}",
+                "GET",
+                format!("/solana/tx/{}", TEST_SOLANA_TX_SIGNATURE),
+                None,
+            ),
+            (
+                "solana_tx_invalid_signature",
+                "/solana/tx/{signature}",
+                "GET",
+                "/solana/tx/invalid".to_owned(),
+                None,
+            ),
+            (
+                "solana_tx_not_found",
+                "/solana/tx/{signature}",
+                "GET",
+                format!("/solana/tx/{}", Signature::default()),
+                None,
+            ),
+            (
+                "solana_post_tx_invalid_base64",
+                "/solana/post_tx",
+                "POST",
+                "/solana/post_tx".to_owned(),
+                Some(json!("not base64!")),
+            ),
+            (
+                "solana_post_tx_invalid_transaction",
+                "/solana/post_tx",
+                "POST",
+                "/solana/post_tx".to_owned(),
+                Some(json!(base64::encode([1u8, 2, 3]))),
+            ),
+            (
+                "solana_post_tx_not_signed",
+                "/solana/post_tx",
+                "POST",
+                "/solana/post_tx".to_owned(),
+                Some(make_test_transaction(
+                    &keypair_from_seed(&[10; 32]).unwrap(),
+                    false,
+                )),
+            ),
+            (
+                "solana_post_tx_authority_fee_payer",
+                "/solana/post_tx",
+                "POST",
+                "/solana/post_tx".to_owned(),
+                Some(make_test_transaction(
+                    &keypair_from_seed(&TEST_AUTHORITY_SEED).unwrap(),
+                    true,
+                )),
+            ),
+            (
+                "solana_post_tx_quota_exceeded",
+                "/solana/post_tx",
+                "POST",
+                "/solana/post_tx".to_owned(),
+                Some(make_test_transaction(
+                    &keypair_from_seed(&TEST_QUOTA_PAYER_SEED).unwrap(),
+                    true,
+                )),
+            ),
+        ];
+        for (name, path, method, uri, body) in cases {
+            let resp = call(&router, method, &uri, body).await;
+            let value: Value = serde_json::from_slice(&resp).unwrap();
+            let method = method.to_lowercase();
+            if let Err(e) = openapi.validate_response(path, &method, &value) {
+                panic!("response of {} doesn't match the schema, {}", name, e);
+            }
+            assert_golden(name, &value);
+            covered.insert((path.to_owned(), method));
+        }
+
+        // every endpoint from the document should be checked
+        assert_eq!(
+            covered.into_iter().collect::<Vec<_>>(),
+            openapi.operations()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_scoped_by_bridge_id() {
+        let router = make_bridges_router(
+            make_test_conn(),
+            make_test_solana_client(),
+            vec![("partner".to_owned(), make_test_solana_client())],
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            None,
+            make_test_settings(),
+        );
+
+        let body = json!({"sender": TEST_USER_ADDRESS, "recipient": TEST_SOLANA_BALANCE_ADDRESS});
+        let resp = call(
+            &router,
+            "POST",
+            "/bridges/partner/bridge/deposit-intents",
+            Some(body),
+        )
+        .await;
+        let intent: Value = serde_json::from_slice(&resp).unwrap();
+        let uri = format!("/bridge/deposit-intents/{}", intent["id"]);
+        let resp = call(&router, "GET", &format!("/bridges/partner{uri}"), None).await;
+        assert_eq!(serde_json::from_slice::<Value>(&resp).unwrap(), intent);
+        // the intent is not found by the other bridges
+        for prefix in ["", "/bridges/default"] {
+            let resp = call(&router, "GET", &format!("{prefix}{uri}"), None).await;
+            let value: Value = serde_json::from_slice(&resp).unwrap();
+            assert!(value["error"]["message"]
+                .as_str()
+                .unwrap()
+                .ends_with("is not found"));
+        }
+        // only the default bridge derives deposit addresses
+        let body = json!({"recipient": TEST_SOLANA_BALANCE_ADDRESS});
+        for (prefix, error_code) in [("", None), ("/bridges/partner", Some("NOT_CONFIGURED"))] {
+            let uri = format!("{prefix}/bridge/deposit-addresses");
+            let resp = call(&router, "POST", &uri, Some(body.clone())).await;
+            let value: Value = serde_json::from_slice(&resp).unwrap();
+            assert_eq!(value["error"]["error_code"].as_str(), error_code);
+        }
+        // the deposit roots are built of the deposits of each bridge
+        let body = call(&router, "GET", "/bridges/partner/bridge/deposit-root", None).await;
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["error"]["error_code"], "NOT_FOUND");
+
+        // the chain endpoints are the same for all the bridges
+        let richlist = call(&router, "GET", "/depc/richlist", None).await;
+        let partner_richlist = call(&router, "GET", "/bridges/partner/depc/richlist", None).await;
+        assert_eq!(richlist, partner_richlist);
+
+        let request = Request::builder()
+            .uri("/bridges/unknown/depc/richlist")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_listener_routes() {
+        assert!(is_admin_path("/admin/jobs"));
+        assert!(is_admin_path("/bridges/partner/admin/jobs"));
+        assert!(!is_admin_path("/bridge/info"));
+        assert!(!is_admin_path("/bridges/admin/bridge/info"));
+        assert!(!is_admin_path("/administrator"));
+
+        let router = make_test_router();
+        let status = |routes: ListenerRoutes, uri: &str| {
+            let router = router
+                .clone()
+                .layer(middleware::from_fn_with_state(routes, restrict_routes));
+            let request = Request::builder()
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .body(Body::empty())
+                .unwrap();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+        for (routes, public, admin) in [
+            (ListenerRoutes::All, StatusCode::OK, StatusCode::OK),
+            (
+                ListenerRoutes::Public,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+            ),
+            (ListenerRoutes::Admin, StatusCode::NOT_FOUND, StatusCode::OK),
+            (
+                ListenerRoutes::ReadOnly,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            assert_eq!(status(routes, "/bridge/parameters").await, public);
+            assert_eq!(status(routes, "/admin/jobs").await, admin);
+        }
+
+        // nothing is posted to the read-only listener
+        let request = Request::builder()
+            .method("POST")
+            .uri("/bridge/deposit-intents")
+            .header("content-type", "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        let resp = router
+            .clone()
+            .layer(middleware::from_fn_with_state(
+                ListenerRoutes::ReadOnly,
+                restrict_routes,
+            ))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_admin_token_required() {
+        let router = make_test_router();
+        let body = json!({"name": "deposit_threshold", "value": "1"});
+        for authorization in [None, Some("Bearer wrong-token"), Some(TEST_ADMIN_TOKEN)] {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/admin/parameter-changes")
+                .header("content-type", "application/json");
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let resp = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+        // the change is not queued
+        let resp = call(&router, "GET", "/bridge/parameters", None).await;
+        let value: Value = serde_json::from_slice(&resp).unwrap();
+        assert_eq!(value["pending"].as_array().unwrap().len(), 1);
+
+        // the admin endpoints are not served without the token
+        let request = Request::builder()
+            .method("POST")
+            .uri("/admin/parameter-changes/2/cancel")
+            .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+            .body(Body::empty())
+            .unwrap();
+        let router = make_bridges_router(
+            make_test_conn(),
+            make_test_solana_client(),
+            vec![],
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            None,
+            make_test_settings(),
+        );
+        let resp = router.oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_multisig_signature() {
+        /// The compressed keys of `G` and `2G`
+        const TEST_COSIGNER_PUBKEYS: [&str; 2] = [
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        ];
+        /// A payout of one input without outputs, it's not signed
+        const TEST_PAYOUT_RAW_TX: &str = concat!(
+            "0200000001",
+            "0000000000000000000000000000000000000000000000000000000000000000ffffffff",
+            "00ffffffff",
+            "0000000000",
+        );
+
+        let conn = make_test_conn();
+        let payout_id = conn
+            .add_multisig_payout(1, TEST_PAYOUT_RAW_TX, "[]", TEST_NOW)
+            .unwrap();
+        let multisig =
+            depc::MultisigScript::new(2, &TEST_COSIGNER_PUBKEYS.map(str::to_owned)).unwrap();
+        let uri = format!("/bridge/multisig/payouts/{payout_id}/signatures");
+        let body = json!({"raw_tx": TEST_PAYOUT_RAW_TX, "pubkey": TEST_COSIGNER_PUBKEYS[1]});
+        for (custody_multisig, error_code) in
+            [(None, "NOT_CONFIGURED"), (Some(multisig), "UNAUTHORIZED")]
+        {
+            // the admin endpoints are not served, the copy is checked by the key instead
+            let router = make_router(
+                conn.clone(),
+                make_test_solana_client(),
+                Arc::new(Mutex::new(false)),
+                Arc::new(MockClock::new(TEST_NOW)),
+                None,
+                None,
+                custody_multisig,
+                ReportSettings::default(),
+                TEST_UPSTREAM_TIMEOUT,
+                None,
+                make_test_settings(),
+            );
+            let resp = call(&router, "POST", &uri, Some(body.clone())).await;
+            let value: Value = serde_json::from_slice(&resp).unwrap();
+            assert_eq!(value["error"]["error_code"], error_code);
+        }
+        // the copy without the signature of the key is not taken
+        assert!(conn
+            .query_submitted_multisig_signatures(payout_id)
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn call_idempotent(router: &Router, key: &str, body: &Value) -> Response {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/bridge/deposit-intents")
+            .header("content-type", "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_idempotency_key() {
+        let router = make_test_router();
+        let body = json!({
+            "sender": TEST_USER_ADDRESS,
+            "recipient": TEST_SOLANA_BALANCE_ADDRESS,
+        });
+
+        let resp = call_idempotent(&router, "intent-1", &body).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("idempotent-replayed").is_none());
+        let first = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+
+        // the retry is replayed, no intent is registered again
+        let resp = call_idempotent(&router, "intent-1", &body).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["idempotent-replayed"], "true");
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let replayed = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(replayed, first);
+
+        // another key registers another intent
+        let resp = call_idempotent(&router, "intent-2", &body).await;
+        let second = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let first: Value = serde_json::from_slice(&first).unwrap();
+        let second: Value = serde_json::from_slice(&second).unwrap();
+        assert_eq!(second["id"], first["id"].as_u64().unwrap() + 1);
+
+        // the key cannot be used for another request
+        let other_body = json!({
+            "sender": TEST_EXCHANGE_ADDRESS_1,
+            "recipient": TEST_SOLANA_BALANCE_ADDRESS,
+        });
+        let resp = call_idempotent(&router, "intent-1", &other_body).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let resp = call_idempotent(&router, &"k".repeat(MAX_IDEMPOTENCY_KEY_LEN + 1), &body).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_exchange_addresses_csv() {
+        let router = make_test_router();
+        call(
+            &router,
+            "GET",
+            &format!("/exchange/analyze/{:064x}", 0xb1),
+            None,
+        )
+        .await;
+
+        let request = Request::builder()
+            .uri("/exchange/addresses?format=csv&limit=1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "text/csv");
+        assert_eq!(resp.headers()["next-cursor"], TEST_EXCHANGE_ADDRESS_1);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            format!(
+                "address,analyzed_txid,first_seen\n{},{:064x},{}\n",
+                TEST_EXCHANGE_ADDRESS_1, 0xb1, TEST_NOW
+            )
+        );
+
+        // the last page has no next one
+        let request = Request::builder()
+            .uri(format!(
+                "/exchange/addresses?format=csv&cursor={}",
+                TEST_EXCHANGE_ADDRESS_1
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(request).await.unwrap();
+        assert!(resp.headers().get("next-cursor").is_none());
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap().lines().count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_internal_error() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let router = make_router(
+            conn,
+            make_test_solana_client(),
+            Arc::new(Mutex::new(false)),
+            system_clock(),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            None,
+            make_test_settings(),
+        );
+
+        // no block is synced, the block time cannot be found
+        let request = Request::builder()
+            .uri("/exchange/balances/7")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            value["error"]["message"],
+            "database error: Query returned no rows"
+        );
+        assert_eq!(value["error"]["error_code"], "DATABASE_FAILED");
+    }
+
+    #[tokio::test]
+    async fn test_rest_upstream_timeout() {
+        // the node accepts the connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let solana_client = SolanaClient::new(
+            &format!("http://{}", listener.local_addr().unwrap()),
+            Pubkey::new_unique(),
+            Keypair::new(),
+            CommitmentConfig::confirmed(),
+        )
+        .set_rpc_timeout(Duration::from_secs(1));
+        let router = make_router(
+            make_test_conn(),
+            solana_client,
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            Duration::from_millis(100),
+            None,
+            make_test_settings(),
+        );
+
+        let request = Request::builder()
+            .uri(format!("/solana/balance?address={}", Pubkey::new_unique()))
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["error"]["error_code"], "UPSTREAM_TIMEOUT");
+        assert_eq!(
+            value["error"]["message"],
+            "the solana node doesn't answer in 100ms"
+        );
+
+        // the parameters are checked without calling the node
+        let request = Request::builder()
+            .uri("/solana/balance?address=invalid")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_rest_client() {
+        use crate::client::{self, ClientBuilder};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let router = make_test_router();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let client = ClientBuilder::new()
+            .set_endpoint(&endpoint)
+            .build()
+            .unwrap();
+
+        let info = client.get_bridge_info().await.unwrap();
+        assert_eq!(info.checkpoint_height, Some(860129));
+        assert!(info.attestation.is_none());
+        let events = client.get_bridge_events(Some(1), Some(2)).await.unwrap();
+        assert_eq!(events.events.len(), 2);
+        assert_eq!(events.last_seq, 3);
+
+        let request = ReqDepositIntent {
+            sender: TEST_USER_ADDRESS.to_owned(),
+            recipient: TEST_SOLANA_BALANCE_ADDRESS.to_owned(),
+        };
+        let intent = client
+            .post_deposit_intent(&request, Some("intent-1"))
+            .await
+            .unwrap();
+        // the retry is replayed
+        let retried = client
+            .post_deposit_intent(&request, Some("intent-1"))
+            .await
+            .unwrap();
+        assert_eq!(retried.id, intent.id);
+        let found = client.get_deposit_intent(intent.id).await.unwrap();
+        assert_eq!(found.recipient, TEST_SOLANA_BALANCE_ADDRESS);
+        // the rejections answered with status 200 are errors as well
+        match client.get_deposit_intent(intent.id + 100).await {
+            Err(client::Error::Rejected { status, code, .. }) => {
+                assert_eq!((status, code), (200, ErrorCode::NotFound));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // the token is sent to the admin endpoints only when it's given
+        assert_eq!(
+            client.get_jobs().await.unwrap_err().code(),
+            Some(ErrorCode::Unauthorized)
+        );
+        let admin = ClientBuilder::new()
+            .set_endpoint(&endpoint)
+            .set_admin_token(TEST_ADMIN_TOKEN)
+            .build()
+            .unwrap();
+        assert!(admin.get_jobs().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rest_request_deadline() {
+        // the deadline of the request passes before the first query
+        let router = make_router(
+            make_test_conn(),
+            make_test_solana_client(),
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            Duration::ZERO,
+            None,
+            make_test_settings(),
+        );
+
+        let request = Request::builder()
+            .uri("/exchange/balances/7")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["error"]["error_code"], "UPSTREAM_TIMEOUT");
+        assert!(value["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("database error: the deadline of the query is exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_rest_attested_responses() {
+        let attestor = Attestor::new(keypair_from_seed(&TEST_ATTESTATION_SEED).unwrap());
+        let pubkey = attestor.pubkey();
+        let openapi = OpenApi::load();
+        let router = make_router(
+            make_test_conn(),
+            make_test_solana_client(),
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            None,
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            Some(Arc::new(attestor)),
+            make_test_settings(),
+        );
+        for uri in ["/bridge/info", "/bridge/summary"] {
+            let body = call(&router, "GET", uri, None).await;
+            let value: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(value["attestation"]["pubkey"], pubkey.to_string());
+            assert_eq!(value["attestation"]["signed_at"], TEST_NOW);
+            assert!(verify_attestation(&value, &pubkey), "{uri}");
+            openapi.validate_response(uri, "get", &value).unwrap();
+
+            // the signature doesn't hold for a changed statement
+            let mut changed = value.clone();
+            changed["attestation"]["signed_at"] = json!(TEST_NOW + 1);
+            assert!(!verify_attestation(&changed, &pubkey), "{uri}");
+        }
+
+        // the responses aren't signed without the attestor
+        let body = call(&make_test_router(), "GET", "/bridge/info", None).await;
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert!(value.get("attestation").is_none());
+    }
+
+    async fn post_relay(router: &Router, payer: &Keypair) -> Value {
+        let body = call(
+            router,
+            "POST",
+            "/solana/post_tx",
+            Some(make_test_transaction(payer, true)),
+        )
+        .await;
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rest_reload_settings() {
+        let path = std::env::temp_dir().join(format!(
+            "depc-bridge-rest-settings-{}.json",
+            std::process::id()
+        ));
+        let path_str = path.to_str().unwrap().to_owned();
+        std::fs::write(&path, r#"{"bridges": []}"#).unwrap();
+        let settings = Arc::new(LiveSettings::new(
+            Some(path_str.clone()),
+            BridgeSettings::load(&path_str).unwrap(),
+        ));
+        let router = make_router(
+            make_test_conn(),
+            make_test_solana_client(),
+            Arc::new(Mutex::new(false)),
+            Arc::new(MockClock::new(TEST_NOW)),
+            None,
+            make_test_admin(),
+            None,
+            ReportSettings::default(),
+            TEST_UPSTREAM_TIMEOUT,
+            None,
+            settings,
+        );
+        let quota_payer = keypair_from_seed(&TEST_QUOTA_PAYER_SEED).unwrap();
+        assert_eq!(
+            post_relay(&router, &quota_payer).await["error"]["error_code"],
+            "RELAY_QUOTA_EXCEEDED"
+        );
+
+        // the payer is allowed without the quota once the settings are reloaded
+        std::fs::write(
+            &path,
+            json!({
+                "bridges": [],
+                "reloadable": {
+                    "relay_daily_quota": 5,
+                    "relay_allowlist": [quota_payer.pubkey().to_string()],
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let body = call(&router, "POST", "/admin/settings/reload", None).await;
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["relay_daily_quota"], 5);
+        assert_eq!(
+            value["relay_allowlist"][0],
+            quota_payer.pubkey().to_string()
+        );
+        assert_ne!(
+            post_relay(&router, &quota_payer).await["error"]["error_code"],
+            "RELAY_QUOTA_EXCEEDED"
+        );
+
+        // the invalid file is refused, the settings in use are kept
+        std::fs::write(
+            &path,
+            r#"{"bridges": [], "reloadable": {"log_level": "loud"}}"#,
+        )
+        .unwrap();
+        let body = call(&router, "POST", "/admin/settings/reload", None).await;
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["error"]["error_code"], "INVALID_CONFIG");
+        assert_ne!(
+            post_relay(&router, &quota_payer).await["error"]["error_code"],
+            "RELAY_QUOTA_EXCEEDED"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,1203 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amount::{DepcAmount, TokenAmount},
+    bridge::{DirectionSummary, ReloadableSettings, SanityReport},
+    db, depc,
+    rest::Attestation,
+    solana::{AnalyzedInstruction, AnalyzedTransaction},
+    ErrorCode,
+};
+
+// The bodies of the requests and the responses of the endpoints, the server and the client
+// (feature `client`) share them so they're always in sync
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespExchangeBalanceByDate {
+    pub balance: DepcAmount,
+    pub balance_human: String,
+    pub addresses: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespExchangeAddresses {
+    pub total: u64,
+    pub saved: u64,
+    pub visited: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespExchangeAddress {
+    pub address: String,
+    pub analyzed_txid: String,
+    pub first_seen: Option<u64>,
+    pub rediscoveries: u64,
+}
+
+impl From<db::ExchangeAddressRecord> for RespExchangeAddress {
+    fn from(record: db::ExchangeAddressRecord) -> Self {
+        RespExchangeAddress {
+            address: record.address,
+            analyzed_txid: record.analyzed_txid,
+            first_seen: record.first_seen,
+            rediscoveries: record.rediscoveries,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespExchangeAddressList {
+    pub addresses: Vec<RespExchangeAddress>,
+    pub next_cursor: Option<String>,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalancePoint {
+    pub timestamp: u64,
+    pub height: u32,
+    /// The total balance of the addresses
+    pub balance: DepcAmount,
+    pub balances: BTreeMap<String, DepcAmount>,
+    /// The value of `balance` in the requested currency
+    pub fiat_value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBalanceHistory {
+    pub points: Vec<BalancePoint>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VolumeStats {
+    pub count: u64,
+    pub amount: DepcAmount,
+    pub fiat_value: f64,
+    /// The number of the actions no price is recorded before, they're not in `fiat_value`
+    pub unpriced: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeVolume {
+    pub currency: String,
+    pub from: u64,
+    pub to: u64,
+    pub deposits: VolumeStats,
+    pub withdrawals: VolumeStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDailyFees {
+    /// `YYYY-MM-DD` in UTC
+    pub date: String,
+    pub deposits: DepcAmount,
+    pub withdrawals: DepcAmount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeFees {
+    pub from: u64,
+    pub to: u64,
+    pub deposits: DepcAmount,
+    pub withdrawals: DepcAmount,
+    pub days: Vec<RespDailyFees>,
+    /// All the fees not swept to the treasury yet
+    pub unswept: DepcAmount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespFeeSweep {
+    pub id: u64,
+    pub treasury: String,
+    pub amount: DepcAmount,
+    pub signature: String,
+    pub created_at: u64,
+}
+
+/// `sweep` is null when there's nothing to sweep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespSweepFees {
+    pub sweep: Option<RespFeeSweep>,
+}
+
+/// A payout from the multisig custody collecting the signatures, the co-signers sign `raw_tx`
+/// with `prevtxs` by `signrawtransactionwithkey` and submit the signed copies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMultisigPayout {
+    pub id: u64,
+    pub action_id: u64,
+    pub raw_tx: String,
+    pub prevtxs: Vec<depc::PrevTx>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMultisigPayouts {
+    pub payouts: Vec<RespMultisigPayout>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqMultisigSignature {
+    /// The hex of the transaction signed by the co-signer
+    pub raw_tx: String,
+    /// The hex of the key of the co-signer, each input of `raw_tx` must carry its signature
+    pub pubkey: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMultisigSignature {
+    pub id: u64,
+    pub payout_id: u64,
+}
+
+/// A payment to the custody address without a bridge action, `reason` is one of
+/// `no_recipient`, `below_threshold`, `below_minimum`, `duplicate_output` and `unrecognized`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespCustodyInflow {
+    pub id: u64,
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub amount: DepcAmount,
+    pub reason: String,
+    pub height: u32,
+    /// `unclassified` or `resolved`
+    pub status: String,
+    pub resolution: Option<String>,
+    pub found_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+impl From<db::CustodyInflowRecord> for RespCustodyInflow {
+    fn from(record: db::CustodyInflowRecord) -> Self {
+        RespCustodyInflow {
+            id: record.id,
+            txid: record.txid,
+            vout: record.vout,
+            address: record.address,
+            amount: record.amount,
+            reason: record.reason,
+            height: record.height,
+            status: record.status,
+            resolution: record.resolution,
+            found_at: record.found_at,
+            resolved_at: record.resolved_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespCustodyInflows {
+    pub inflows: Vec<RespCustodyInflow>,
+}
+
+/// The bridge fee charged on a transfer, `net` is what's minted or paid out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespTransferFee {
+    pub id: u64,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub source_txid: String,
+    pub action_id: u64,
+    pub amount: DepcAmount,
+    pub fee: DepcAmount,
+    pub net: DepcAmount,
+    pub created_at: u64,
+}
+
+impl From<db::TransferFeeRecord> for RespTransferFee {
+    fn from(record: db::TransferFeeRecord) -> Self {
+        RespTransferFee {
+            id: record.id,
+            action: record.action,
+            source_txid: record.source_txid,
+            action_id: record.action_id,
+            amount: record.amount,
+            fee: record.fee,
+            net: record.amount.checked_sub(record.fee).unwrap_or_default(),
+            created_at: record.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespTransferFees {
+    pub fees: Vec<RespTransferFee>,
+}
+
+/// The digest of the bridge events of a synced block, see `bridge::digest_block_events`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBlockDigest {
+    pub height: u32,
+    pub block_hash: String,
+    pub event_count: u32,
+    pub digest: String,
+    pub created_at: u64,
+}
+
+impl From<db::BlockDigestRecord> for RespBlockDigest {
+    fn from(record: db::BlockDigestRecord) -> Self {
+        RespBlockDigest {
+            height: record.height,
+            block_hash: record.block_hash,
+            event_count: record.event_count,
+            digest: record.digest,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// The heights in the range without a digest are not synced, e.g. the pruned blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBlockDigests {
+    pub from: u32,
+    pub to: u32,
+    pub digests: Vec<RespBlockDigest>,
+}
+
+/// A deposit or a withdrawal held by the transfer limits, it's made after an admin releases it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespHeldTransfer {
+    pub id: u64,
+    /// `deposit` or `withdraw`
+    pub action: String,
+    pub source_txid: String,
+    pub recipient: String,
+    pub amount: DepcAmount,
+    pub timestamp: u64,
+    pub reason: String,
+    pub held_at: u64,
+    pub released_at: Option<u64>,
+}
+
+impl From<db::HeldPendingActionRecord> for RespHeldTransfer {
+    fn from(record: db::HeldPendingActionRecord) -> Self {
+        RespHeldTransfer {
+            id: record.id,
+            action: record.action,
+            source_txid: record.source_txid,
+            recipient: record.recipient,
+            amount: record.amount,
+            timestamp: record.timestamp,
+            reason: record.held_reason,
+            held_at: record.held_at,
+            released_at: record.released_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespHeldTransfers {
+    pub transfers: Vec<RespHeldTransfer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqResolveCustodyInflow {
+    /// What's done with the funds, e.g. returned to the sender or credited by hand
+    pub resolution: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespCatchUpPlan {
+    pub id: u64,
+    pub from_height: u32,
+    pub to_height: u32,
+    pub status: String,
+    pub deposits: u64,
+    pub deposit_amount: DepcAmount,
+    pub withdrawals: u64,
+    pub withdraw_amount: DepcAmount,
+    pub started_at: u64,
+    pub planned_at: Option<u64>,
+    pub confirmed_at: Option<u64>,
+}
+
+impl From<db::CatchUpPlanRecord> for RespCatchUpPlan {
+    fn from(record: db::CatchUpPlanRecord) -> Self {
+        RespCatchUpPlan {
+            id: record.id,
+            from_height: record.from_height,
+            to_height: record.to_height,
+            status: record.status,
+            deposits: record.deposits,
+            deposit_amount: record.deposit_amount,
+            withdrawals: record.withdrawals,
+            withdraw_amount: record.withdraw_amount,
+            started_at: record.started_at,
+            planned_at: record.planned_at,
+            confirmed_at: record.confirmed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespCatchUp {
+    pub bridge_id: String,
+    pub best_height: Option<u32>,
+    pub plan: Option<RespCatchUpPlan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqParameterChange {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespParameterChange {
+    pub id: u64,
+    pub name: String,
+    pub value: String,
+    pub created_at: u64,
+    pub activates_at: u64,
+    pub cancelled_at: Option<u64>,
+    /// `pending`, `activated` or `cancelled`
+    pub status: String,
+}
+
+impl RespParameterChange {
+    pub fn new(record: db::ParameterChangeRecord, now: u64) -> RespParameterChange {
+        let status = if record.cancelled_at.is_some() {
+            "cancelled"
+        } else if record.activates_at > now {
+            "pending"
+        } else {
+            "activated"
+        };
+        RespParameterChange {
+            id: record.id,
+            name: record.name,
+            value: record.value,
+            created_at: record.created_at,
+            activates_at: record.activates_at,
+            cancelled_at: record.cancelled_at,
+            status: status.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeParameters {
+    /// The values in effect
+    pub parameters: BTreeMap<String, DepcAmount>,
+    /// The changes those will take effect unless they're cancelled
+    pub pending: Vec<RespParameterChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespParameterChanges {
+    pub changes: Vec<RespParameterChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespLatencyPercentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDirectionSummary {
+    pub count: u64,
+    pub volume: DepcAmount,
+    /// The average seconds from being found to being made on the other chain
+    pub avg_latency: Option<u64>,
+    /// The seconds from being found to being confirmed on the other chain
+    pub confirmation_latency: Option<RespLatencyPercentiles>,
+    pub pending: u64,
+}
+
+impl From<DirectionSummary> for RespDirectionSummary {
+    fn from(summary: DirectionSummary) -> Self {
+        RespDirectionSummary {
+            count: summary.count,
+            volume: summary.volume,
+            avg_latency: summary.avg_latency,
+            confirmation_latency: summary.confirmation_latency.map(|latency| {
+                RespLatencyPercentiles {
+                    p50: latency.p50,
+                    p95: latency.p95,
+                    p99: latency.p99,
+                }
+            }),
+            pending: summary.pending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespFailureCounts {
+    pub dead_letters: u64,
+    pub refunds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWindowSummary {
+    pub window: String,
+    pub since: u64,
+    pub deposits: RespDirectionSummary,
+    pub withdrawals: RespDirectionSummary,
+    pub fees: DepcAmount,
+    pub failures: RespFailureCounts,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeSummary {
+    pub generated_at: u64,
+    pub windows: Vec<RespWindowSummary>,
+    /// Put by the server when the attestor is configured, see `Attestor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeEvent {
+    pub seq: u64,
+    pub event: String,
+    pub amount: DepcAmount,
+    pub reference: String,
+    pub timestamp: u64,
+}
+
+impl From<db::EventRecord> for RespBridgeEvent {
+    fn from(record: db::EventRecord) -> Self {
+        RespBridgeEvent {
+            seq: record.seq,
+            event: record.event,
+            amount: record.amount,
+            reference: record.reference,
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeEvents {
+    pub events: Vec<RespBridgeEvent>,
+    /// The sequence number the next page starts after, it's `since_seq` when no event is newer
+    pub last_seq: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespJob {
+    pub name: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub last_started_at: Option<u64>,
+    pub last_finished_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub next_run_at: Option<u64>,
+}
+
+impl From<db::JobRecord> for RespJob {
+    fn from(record: db::JobRecord) -> Self {
+        RespJob {
+            name: record.name,
+            schedule: record.schedule,
+            enabled: record.enabled,
+            running: record.running,
+            last_started_at: record.last_started_at,
+            last_finished_at: record.last_finished_at,
+            last_error: record.last_error,
+            next_run_at: record.next_run_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespJobs {
+    pub jobs: Vec<RespJob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespTableRows {
+    pub name: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespStuckDeposits {
+    pub state: String,
+    pub count: u64,
+    pub oldest: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespSanityReport {
+    pub bridge_id: String,
+    pub checked_at: u64,
+    pub stuck_before: u64,
+    pub sane: bool,
+    pub tables: Vec<RespTableRows>,
+    pub stuck_deposits: Vec<RespStuckDeposits>,
+    pub spent_coins_without_txid: u64,
+}
+
+impl From<SanityReport> for RespSanityReport {
+    fn from(report: SanityReport) -> Self {
+        RespSanityReport {
+            sane: report.is_sane(),
+            bridge_id: report.bridge_id,
+            checked_at: report.checked_at,
+            stuck_before: report.stuck_before,
+            tables: report
+                .tables
+                .into_iter()
+                .map(|(name, rows)| RespTableRows { name, rows })
+                .collect(),
+            stuck_deposits: report
+                .stuck_deposits
+                .into_iter()
+                .map(|stuck| RespStuckDeposits {
+                    state: stuck.state,
+                    count: stuck.count,
+                    oldest: stuck.oldest,
+                })
+                .collect(),
+            spent_coins_without_txid: report.spent_coins_without_txid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespReloadableSettings {
+    pub log_level: Option<String>,
+    pub relay_daily_quota: u32,
+    pub relay_allowlist: Vec<String>,
+}
+
+impl From<&ReloadableSettings> for RespReloadableSettings {
+    fn from(settings: &ReloadableSettings) -> Self {
+        RespReloadableSettings {
+            log_level: settings.log_level.clone(),
+            relay_daily_quota: settings.relay_daily_quota,
+            relay_allowlist: settings.relay_allowlist.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDataGap {
+    pub from: u32,
+    pub to: u32,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBridgeInfo {
+    pub bridge_id: String,
+    pub best_height: Option<u32>,
+    /// The height the index is bootstrapped from, `None` when it's synced from the genesis
+    pub checkpoint_height: Option<u32>,
+    /// The heights pruned or missing on the DePC node, the coins of them are not indexed
+    pub data_gaps: Vec<RespDataGap>,
+    /// Put by the server when the attestor is configured, see `Attestor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqWatchAddress {
+    /// `depc` or `solana`
+    pub chain: String,
+    pub address: String,
+    #[serde(default)]
+    pub label: String,
+    /// The url the events of the address are posted to
+    pub webhook: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqUnwatchAddress {
+    pub chain: String,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWatchedAddress {
+    pub chain: String,
+    pub address: String,
+    pub label: String,
+    pub webhook: Option<String>,
+    pub created_at: u64,
+}
+
+impl From<db::WatchedAddressRecord> for RespWatchedAddress {
+    fn from(record: db::WatchedAddressRecord) -> Self {
+        RespWatchedAddress {
+            chain: record.chain,
+            address: record.address,
+            label: record.label,
+            webhook: record.webhook,
+            created_at: record.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWatchlist {
+    pub addresses: Vec<RespWatchedAddress>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWatchEvent {
+    pub id: u64,
+    pub chain: String,
+    pub address: String,
+    pub txid: String,
+    pub asset: String,
+    pub direction: String,
+    pub net_change: String,
+    pub timestamp: u64,
+    pub delivered_at: Option<u64>,
+    pub last_error_code: Option<String>,
+}
+
+impl From<db::WatchEventRecord> for RespWatchEvent {
+    fn from(record: db::WatchEventRecord) -> Self {
+        RespWatchEvent {
+            id: record.id,
+            chain: record.chain,
+            address: record.address,
+            txid: record.txid,
+            asset: record.asset,
+            direction: record.direction,
+            net_change: record.net_change,
+            timestamp: record.timestamp,
+            delivered_at: record.delivered_at,
+            last_error_code: record.last_error_code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWatchEvents {
+    pub events: Vec<RespWatchEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBlockSummary {
+    pub hash: String,
+    pub height: u32,
+    pub miner: String,
+    pub time: u64,
+    pub tx_count: u32,
+    pub total_value: DepcAmount,
+}
+
+impl From<db::BlockSummaryRecord> for RespBlockSummary {
+    fn from(record: db::BlockSummaryRecord) -> Self {
+        RespBlockSummary {
+            hash: record.hash,
+            height: record.height,
+            miner: record.miner,
+            time: record.time,
+            tx_count: record.tx_count,
+            total_value: record.total_value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespBlockSummaries {
+    pub blocks: Vec<RespBlockSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMinerStats {
+    pub miner: String,
+    pub blocks: u32,
+    pub first_height: u32,
+    pub last_height: u32,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+impl From<db::MinerStatsRecord> for RespMinerStats {
+    fn from(record: db::MinerStatsRecord) -> Self {
+        RespMinerStats {
+            miner: record.miner,
+            blocks: record.blocks,
+            first_height: record.first_height,
+            last_height: record.last_height,
+            first_seen: record.first_seen,
+            last_seen: record.last_seen,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMiners {
+    pub from: u32,
+    pub to: u32,
+    pub miners: Vec<RespMinerStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMinerDailyBlocks {
+    /// `YYYY-MM-DD` in UTC
+    pub date: String,
+    pub blocks: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespMiner {
+    #[serde(flatten)]
+    pub stats: RespMinerStats,
+    pub from: u64,
+    pub to: u64,
+    pub days: Vec<RespMinerDailyBlocks>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RichlistHolder {
+    pub rank: u32,
+    pub address: String,
+    pub balance: DepcAmount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespRichlist {
+    pub holders: Vec<RichlistHolder>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespAddressCluster {
+    pub address: String,
+    pub cluster_id: u64,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqDepositIntent {
+    /// The DePC address the payment will be made from
+    pub sender: String,
+    pub recipient: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDepositIntent {
+    pub id: u64,
+    pub sender: String,
+    pub recipient: String,
+    pub created_at: u64,
+    pub matched_txid: Option<String>,
+    pub matched_vout: Option<u32>,
+    pub matched_at: Option<u64>,
+}
+
+impl From<db::DepositIntentRecord> for RespDepositIntent {
+    fn from(record: db::DepositIntentRecord) -> Self {
+        RespDepositIntent {
+            id: record.id,
+            sender: record.sender,
+            recipient: record.recipient,
+            created_at: record.created_at,
+            matched_txid: record.matched_txid,
+            matched_vout: record.matched_vout,
+            matched_at: record.matched_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqDepositAddress {
+    pub recipient: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDepositAddress {
+    pub address: String,
+    pub derivation_index: u32,
+    pub derivation_path: String,
+    pub recipient: String,
+    pub created_at: u64,
+}
+
+impl From<db::DepositAddressRecord> for RespDepositAddress {
+    fn from(record: db::DepositAddressRecord) -> Self {
+        RespDepositAddress {
+            address: record.address,
+            derivation_index: record.derivation_index,
+            derivation_path: record.derivation_path,
+            recipient: record.recipient,
+            created_at: record.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDepositRoot {
+    /// The hex of the Merkle root of the deposits minted before `covered_until`
+    pub root: String,
+    pub leaf_count: u64,
+    pub covered_until: u64,
+    pub created_at: u64,
+    /// The Solana transaction the root is published by with a memo
+    pub memo_signature: Option<String>,
+    /// Put by the server when the attestor is configured, see `Attestor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+impl From<db::DepositRootRecord> for RespDepositRoot {
+    fn from(record: db::DepositRootRecord) -> Self {
+        RespDepositRoot {
+            root: record.root,
+            leaf_count: record.leaf_count,
+            covered_until: record.covered_until,
+            created_at: record.created_at,
+            memo_signature: record.memo_signature,
+            attestation: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespProofStep {
+    pub hash: String,
+    /// The side of the sibling, `left` or `right`
+    pub side: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespDepositProof {
+    pub depc_txid: String,
+    pub recipient: String,
+    pub amount: DepcAmount,
+    /// The data the leaf is the hash of, see `bridge::make_deposit_leaf_data`
+    pub leaf_data: String,
+    pub leaf: String,
+    pub index: usize,
+    /// From the leaf up to the root
+    pub siblings: Vec<RespProofStep>,
+    pub root: RespDepositRoot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqWithdrawRequest {
+    /// The Solana transaction transfers the tokens to the owner
+    pub signature: String,
+    /// The DePC address the withdrawal is paid to
+    pub recipient: String,
+    /// The Solana key sends the tokens and signs the request
+    pub signer: String,
+    /// The signature of `signer` over the message of `bridge::make_withdraw_request_message`
+    pub message_signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespWithdrawRequest {
+    pub id: u64,
+    pub signature: String,
+    pub recipient: String,
+    pub signer: String,
+    pub origin: String,
+    pub status: String,
+    pub reason: Option<String>,
+    pub pending_action_id: Option<u64>,
+    pub created_at: u64,
+    pub processed_at: Option<u64>,
+}
+
+impl From<db::WithdrawRequestRecord> for RespWithdrawRequest {
+    fn from(record: db::WithdrawRequestRecord) -> Self {
+        RespWithdrawRequest {
+            id: record.id,
+            signature: record.signature,
+            recipient: record.recipient,
+            signer: record.signer,
+            origin: record.origin,
+            status: record.status,
+            reason: record.reason,
+            pending_action_id: record.pending_action_id,
+            created_at: record.created_at,
+            processed_at: record.processed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReqRefundClaim {
+    /// The invalid deposit refunded
+    pub depc_txid: String,
+    pub vout: u32,
+    /// The DePC address the refund is claimed to
+    pub address: String,
+    /// The signature (`signmessage`) of the refund address over the message of
+    /// `bridge::make_refund_claim_message`
+    pub signature: String,
+}
+
+/// The refund of an invalid deposit, `claim_status` is submitted, verified or rejected when the
+/// refund is claimed to `claim_address`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespRefund {
+    pub id: u64,
+    pub depc_txid: String,
+    pub vout: u32,
+    pub refund_address: String,
+    pub claim_address: Option<String>,
+    pub claim_status: Option<String>,
+    pub txid: Option<String>,
+    pub refunded_at: Option<u64>,
+}
+
+impl From<db::RefundRecord> for RespRefund {
+    fn from(record: db::RefundRecord) -> Self {
+        RespRefund {
+            id: record.id,
+            depc_txid: record.depc_txid,
+            vout: record.vout,
+            refund_address: record.refund_address,
+            claim_address: record.claim_address,
+            claim_status: record.claim_status,
+            txid: record.txid,
+            refunded_at: record.refunded_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceResponse {
+    pub address: String,
+    pub balance: TokenAmount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadTransactionResponse {
+    pub result: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionDetail {
+    pub signature: String,
+    pub source: String,
+    pub destination: String,
+    pub source_owner: Option<String>,
+    pub destination_owner: Option<String>,
+    pub amount: TokenAmount,
+    pub fee: TokenAmount,
+    pub timestamp: i64,
+    pub r#type: String,
+    /// The queried address
+    pub address: String,
+    /// The direction of the transaction on the address
+    pub direction: Option<String>,
+    /// The net change of the transaction on the address in the unit of `type`
+    pub net_change: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespInstruction {
+    /// `token` or `sol`
+    pub r#type: String,
+    pub source: String,
+    pub destination: String,
+    pub source_owner: Option<String>,
+    pub destination_owner: Option<String>,
+    pub amount: TokenAmount,
+    /// `transfer`, `mint_to` or `burn`
+    pub operation: String,
+    /// Only known from the checked variants of the token instructions
+    pub mint: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespOtherInstruction {
+    pub program_id: String,
+    pub label: String,
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespSolanaTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub fee: TokenAmount,
+    /// `success` or `failed`
+    pub status: String,
+    /// The error the transaction failed with
+    pub error: Option<String>,
+    pub memo: Option<String>,
+    /// The transfers of SOL and the token, the memos are in `memo`
+    pub instructions: Vec<RespInstruction>,
+    /// The instructions of the programs in the allowlist of the analyzer
+    pub other_instructions: Vec<RespOtherInstruction>,
+    /// The programs whose instructions are skipped by the analyzer
+    pub skipped_programs: Vec<String>,
+}
+
+impl From<&AnalyzedTransaction> for RespSolanaTransaction {
+    fn from(transaction: &AnalyzedTransaction) -> Self {
+        let (mut instructions, mut other_instructions) = (vec![], vec![]);
+        for ix in transaction.instructions.iter() {
+            let (r#type, detail) = match ix {
+                AnalyzedInstruction::SplToken(detail) => ("token", detail),
+                AnalyzedInstruction::Solana(detail) => ("sol", detail),
+                AnalyzedInstruction::Other(other) => {
+                    other_instructions.push(RespOtherInstruction {
+                        program_id: other.program_id.to_string(),
+                        label: other.label.clone(),
+                        kind: other.kind.clone(),
+                    });
+                    continue;
+                }
+            };
+            instructions.push(RespInstruction {
+                r#type: r#type.to_owned(),
+                source: detail.source.to_string(),
+                destination: detail.destination.to_string(),
+                source_owner: detail.source_owner.map(|owner| owner.to_string()),
+                destination_owner: detail.destination_owner.map(|owner| owner.to_string()),
+                amount: TokenAmount::new(detail.amount),
+                operation: detail.operation.to_string(),
+                mint: detail.mint.map(|mint| mint.to_string()),
+                decimals: detail.decimals,
+            });
+        }
+        RespSolanaTransaction {
+            signature: transaction.signature.to_string(),
+            slot: transaction.slot,
+            timestamp: transaction.timestamp,
+            fee: TokenAmount::new(transaction.fee),
+            status: if transaction.error.is_some() {
+                "failed"
+            } else {
+                "success"
+            }
+            .to_owned(),
+            error: transaction.error.clone(),
+            memo: transaction.memo.clone(),
+            instructions,
+            other_instructions,
+            skipped_programs: transaction
+                .skipped_programs
+                .iter()
+                .map(|program_id| program_id.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The body of the rejected requests, the ones refused by the handlers are answered with
+/// status 200 as well
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    /// Kept for the older clients, see `legacy_error_code`
+    pub code: u32,
+    pub error_code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::analysis::ClusterReport;
+
+    fn assert_round_trip<T: Serialize + DeserializeOwned>(name: &str, value: &Value) {
+        let parsed: T = serde_json::from_value(value.clone())
+            .unwrap_or_else(|e| panic!("cannot parse golden file {name}, reason: {e}"));
+        assert_eq!(serde_json::to_value(parsed).unwrap(), *value, "{name}");
+    }
+
+    /// Every golden response of the service is read back by the types of the client, and it's
+    /// written again as it is
+    #[test]
+    fn test_golden_responses() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("samples/rest");
+        let mut checked = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+            let value: Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            if value["error"].is_object() {
+                assert_round_trip::<ErrorResponse>(&name, &value);
+                checked += 1;
+                continue;
+            }
+            match name.as_str() {
+                "admin_catch_up_confirm" => assert_round_trip::<RespCatchUpPlan>(&name, &value),
+                "admin_jobs" => assert_round_trip::<RespJobs>(&name, &value),
+                "admin_parameter_change_cancel" | "admin_parameter_change_create" => {
+                    assert_round_trip::<RespParameterChange>(&name, &value)
+                }
+                "admin_sanity" => assert_round_trip::<RespSanityReport>(&name, &value),
+                "admin_watchlist" | "admin_watchlist_add" | "admin_watchlist_remove" => {
+                    assert_round_trip::<RespWatchlist>(&name, &value)
+                }
+                "admin_watchlist_events" => assert_round_trip::<RespWatchEvents>(&name, &value),
+                "bridge_catch_up" => assert_round_trip::<RespCatchUp>(&name, &value),
+                "bridge_deposit_address" | "bridge_deposit_address_create" => {
+                    assert_round_trip::<RespDepositAddress>(&name, &value)
+                }
+                "bridge_deposit_intent" | "bridge_deposit_intent_create" => {
+                    assert_round_trip::<RespDepositIntent>(&name, &value)
+                }
+                "bridge_deposit_proof" => assert_round_trip::<RespDepositProof>(&name, &value),
+                "bridge_deposit_root" => assert_round_trip::<RespDepositRoot>(&name, &value),
+                "bridge_events" => assert_round_trip::<RespBridgeEvents>(&name, &value),
+                // the amounts are written back as strings
+                "bridge_events_numeric" => {
+                    let events: RespBridgeEvents = serde_json::from_value(value).unwrap();
+                    assert_eq!(events.events[0].amount, DepcAmount::from_value64(200000000));
+                }
+                "bridge_fees" | "bridge_fees_today" => {
+                    assert_round_trip::<RespBridgeFees>(&name, &value)
+                }
+                "bridge_info" => assert_round_trip::<RespBridgeInfo>(&name, &value),
+                "bridge_parameter_changes" => {
+                    assert_round_trip::<RespParameterChanges>(&name, &value)
+                }
+                "bridge_parameters" => assert_round_trip::<RespBridgeParameters>(&name, &value),
+                "bridge_stats_volume" | "bridge_stats_volume_unpriced" => {
+                    assert_round_trip::<RespBridgeVolume>(&name, &value)
+                }
+                "bridge_summary" => assert_round_trip::<RespBridgeSummary>(&name, &value),
+                "bridge_withdraw" | "bridge_withdraw_create" => {
+                    assert_round_trip::<RespWithdrawRequest>(&name, &value)
+                }
+                "depc_balance_history" | "depc_balance_history_usd" => {
+                    assert_round_trip::<RespBalanceHistory>(&name, &value)
+                }
+                "depc_block" | "depc_block_by_hash" => {
+                    assert_round_trip::<RespBlockSummary>(&name, &value)
+                }
+                "depc_blocks" => assert_round_trip::<RespBlockSummaries>(&name, &value),
+                "depc_miner" => assert_round_trip::<RespMiner>(&name, &value),
+                "depc_miners" => assert_round_trip::<RespMiners>(&name, &value),
+                "depc_richlist" => assert_round_trip::<RespRichlist>(&name, &value),
+                "exchange_addresses" | "exchange_addresses_next_page" => {
+                    assert_round_trip::<RespExchangeAddressList>(&name, &value)
+                }
+                "exchange_analyze" => assert_round_trip::<RespExchangeAddresses>(&name, &value),
+                "exchange_balances" | "exchange_balances_timezone" => {
+                    assert_round_trip::<HashMap<String, RespExchangeBalanceByDate>>(&name, &value)
+                }
+                "exchange_cluster" => assert_round_trip::<RespAddressCluster>(&name, &value),
+                "exchange_clusters_rebuild" => assert_round_trip::<ClusterReport>(&name, &value),
+                "solana_balance" => assert_round_trip::<Vec<BalanceResponse>>(&name, &value),
+                "solana_history" => assert_round_trip::<Vec<TransactionDetail>>(&name, &value),
+                "solana_tx" => assert_round_trip::<RespSolanaTransaction>(&name, &value),
+                _ => panic!("golden file {name} is not read by any type"),
+            }
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+}
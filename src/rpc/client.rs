@@ -1,9 +1,10 @@
 use log::debug;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ureq::AgentBuilder;
 
-use super::{Config, Request, Response};
+use super::{check_response_id, Config, ErrorResponse, Request, Response};
+use crate::clock::Deadline;
 
 pub struct Client {
     config: Config,
@@ -15,17 +16,62 @@ impl Client {
     }
 
     pub fn send(&self, req: &Request) -> Result<Response> {
-        let agent = AgentBuilder::new()
-            .try_proxy_from_env(self.config.use_proxy)
-            .build();
-        let body = serde_json::to_string_pretty(req)?;
+        #[cfg(feature = "chaos")]
+        let fault = self
+            .config
+            .fault_injector
+            .as_ref()
+            .and_then(|injector| injector.next_fault());
+        #[cfg(feature = "chaos")]
+        match fault {
+            Some(crate::chaos::Fault::Drop) => {
+                return Err(anyhow!("the request is dropped by fault injection"))
+            }
+            Some(crate::chaos::Fault::Delay(delay)) => std::thread::sleep(delay),
+            _ => {}
+        }
+        // the call is given the time left of the request or the job making it
+        let timeout = Deadline::cap_timeout(self.config.timeout)
+            .map_err(|e| anyhow!("the request is not sent, {e}"))?;
+        let mut agent = AgentBuilder::new().try_proxy_from_env(self.config.use_proxy);
+        if let Some(timeout) = timeout {
+            agent = agent.timeout(timeout);
+        }
+        let agent = agent.build();
+        let id = self.config.next_request_id();
+        let mut body = serde_json::to_value(req)?;
+        body["id"] = id.into();
+        let body = serde_json::to_string_pretty(&body)?;
         let mut req = agent.post(&self.config.endpoint);
         if let Some(auth) = &self.config.auth {
             req = req.set("Authorization", auth);
         }
-        debug!("sending body:\n{}\n", body);
-        let resp = req.send_string(&body)?;
+        debug!("sending request {id}, body:\n{}\n", body);
+        let resp = match req.send_string(&body) {
+            Ok(resp) => resp,
+            // the node answers the failed calls with the error object
+            Err(ureq::Error::Status(status, resp)) => {
+                let resp_str = resp.into_string()?;
+                return match serde_json::from_str::<ErrorResponse>(&resp_str) {
+                    Ok(resp) => {
+                        check_response_id(id, &resp.id)?;
+                        Err(resp.error.into())
+                    }
+                    Err(_) => Err(anyhow!("HTTP status {status}, {resp_str}")),
+                };
+            }
+            Err(e) => return Err(e.into()),
+        };
         let resp_str = resp.into_string()?;
-        Ok(serde_json::from_str(&resp_str)?)
+        // the body is truncated as if the connection is broken in the middle
+        #[cfg(feature = "chaos")]
+        let resp_str = if fault == Some(crate::chaos::Fault::Corrupt) {
+            String::from_utf8_lossy(&resp_str.as_bytes()[..resp_str.len() / 2]).into_owned()
+        } else {
+            resp_str
+        };
+        let resp: Response = serde_json::from_str(&resp_str)?;
+        check_response_id(id, &resp.id)?;
+        Ok(resp)
     }
 }
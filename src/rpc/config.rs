@@ -1,6 +1,26 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
 #[derive(Clone)]
 pub struct Config {
     pub endpoint: String,
     pub use_proxy: bool,
     pub auth: Option<String>,
+    /// The time a call is given up after, from connecting to reading the whole response
+    pub timeout: Option<Duration>,
+    /// The id of the next request, the clones of the config share it so each request of a
+    /// client has its own id to correlate the logs and the response
+    pub request_ids: Arc<AtomicU32>,
+    /// Drop, delay or corrupt the calls to exercise the recovery paths
+    #[cfg(feature = "chaos")]
+    pub fault_injector: Option<crate::chaos::FaultInjector>,
+}
+
+impl Config {
+    pub fn next_request_id(&self) -> u32 {
+        self.request_ids.fetch_add(1, Ordering::Relaxed)
+    }
 }
@@ -53,6 +53,11 @@ impl RequestBuilder {
         self
     }
 
+    pub fn add_param_value(mut self, name: &str, value: Value) -> RequestBuilder {
+        self.rpc_json.params.insert(name.to_owned(), value);
+        self
+    }
+
     pub fn build(self) -> Request {
         // TODO we might need to ensure `rpc_json` is valid
         self.rpc_json
@@ -90,4 +95,18 @@ mod test {
         assert_eq!(*rpc_json.params.get("number").unwrap(), 100);
         assert_eq!(*rpc_json.params.get("string").unwrap(), "hello world");
     }
+
+    #[test]
+    fn test_rpc_json_builder_add_param_value() {
+        let builder = RequestBuilder::new();
+        let rpc_json = builder
+            .add_param_value("addresses", serde_json::json!(["addr1", "addr2"]))
+            .build();
+
+        assert_eq!(rpc_json.params.len(), 1);
+        assert_eq!(
+            *rpc_json.params.get("addresses").unwrap(),
+            serde_json::json!(["addr1", "addr2"])
+        );
+    }
 }
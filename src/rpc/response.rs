@@ -8,11 +8,73 @@ use serde_json::Error;
 pub struct Response {
     #[cfg(test)]
     pub jsonrpc: Option<String>,
-    #[cfg(test)]
-    pub id: u32,
+    /// The id of the request, `null` if the node (or a proxy) drops it
+    #[serde(default)]
+    pub id: Value,
     pub result: Value,
 }
 
+/// The error object of a JSON-RPC response, the node answers it with a non-2xx status
+#[derive(Debug, Deserialize)]
+pub struct ServerError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ServerError {
+    /// The block is pruned or the transaction cannot be found by the node, asking again won't
+    /// make it available
+    pub fn is_data_unavailable(&self) -> bool {
+        self.message.contains("pruned") || self.message.starts_with("No such mempool")
+    }
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+#[derive(Deserialize)]
+pub struct ErrorResponse {
+    #[serde(default)]
+    pub id: Value,
+    pub error: ServerError,
+}
+
+/// The response is not the one of the request, e.g. a misbehaving proxy answers with the
+/// response of another request or the same response twice
+#[derive(Debug)]
+pub struct MismatchedResponseId {
+    pub expected: u32,
+    pub actual: Value,
+}
+
+impl std::fmt::Display for MismatchedResponseId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the response of request {} has id {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for MismatchedResponseId {}
+
+/// Reject the response made for another request
+pub fn check_response_id(expected: u32, actual: &Value) -> Result<(), MismatchedResponseId> {
+    if actual.as_u64() == Some(expected as u64) {
+        return Ok(());
+    }
+    Err(MismatchedResponseId {
+        expected,
+        actual: actual.clone(),
+    })
+}
+
 #[cfg(test)]
 pub fn parse_str(s: &str) -> Result<Response, Error> {
     serde_json::from_str(s)
@@ -36,4 +98,13 @@ mod test {
         );
         assert_eq!(parse_str(STANDARD_JSON_RPC).unwrap().result, "hello world");
     }
+
+    #[test]
+    fn test_check_response_id() {
+        assert!(check_response_id(7, &serde_json::json!(7)).is_ok());
+        let e = check_response_id(7, &serde_json::json!(6)).unwrap_err();
+        assert_eq!(e.to_string(), "the response of request 7 has id 6");
+        assert!(check_response_id(7, &Value::Null).is_err());
+        assert!(check_response_id(7, &serde_json::json!("7")).is_err());
+    }
 }
@@ -0,0 +1,475 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::bridge::{
+    confirm_catch_up, query_bridge_status, query_catch_up_report, queue_parameter_change,
+    summarize_fees, verify_withdraw_request, BridgeParameter, BridgeStatus, CatchUpReport,
+    FeesSummary, REFUND_CLAIM_SUBMITTED, WITHDRAW_ORIGIN_OFFCHAIN, WITHDRAW_REQUEST_PENDING,
+};
+use crate::clock::SharedClock;
+use crate::db;
+use crate::depc::address_script_pubkey_hex;
+use crate::ledger::{Ledger, TrialBalance};
+use crate::{BridgeError, ErrorCode};
+
+use super::{ServiceError, SolanaService};
+
+pub const DEFAULT_PARAMETER_CHANGES_LIMIT: u32 = 100;
+pub const MAX_PARAMETER_CHANGES_LIMIT: u32 = 1000;
+
+/// The state and the operations of a bridge, the connection is scoped to the bridge
+#[derive(Clone)]
+pub struct BridgeService {
+    conn: db::Conn,
+    clock: SharedClock,
+}
+
+impl BridgeService {
+    pub fn new(conn: db::Conn, clock: SharedClock) -> BridgeService {
+        BridgeService { conn, clock }
+    }
+
+    /// The syncing state of the bridge and the data gaps of the index
+    pub fn status(&self) -> Result<BridgeStatus, ServiceError> {
+        Ok(query_bridge_status(&self.conn)?)
+    }
+
+    /// The latest catch-up plan of the bridge, the totals of the one being indexed are the ones
+    /// found so far
+    pub fn catch_up_report(&self) -> Result<CatchUpReport, ServiceError> {
+        Ok(query_catch_up_report(&self.conn)?)
+    }
+
+    /// Confirm the catch-up plan awaiting the confirmation, the backlog is executed after it
+    pub fn confirm_catch_up(&self) -> Result<db::CatchUpPlanRecord, ServiceError> {
+        confirm_catch_up(&self.conn, self.clock.now())?.ok_or_else(|| {
+            ServiceError::rejected(
+                ErrorCode::NotFound,
+                "no catch-up plan awaits the confirmation",
+            )
+        })
+    }
+
+    pub fn trial_balance(&self) -> Result<TrialBalance, ServiceError> {
+        Ok(Ledger::new(self.conn.clone()).trial_balance()?)
+    }
+
+    /// The bridge fees kept in `from..=to` by the day (UTC) and the direction, from the epoch to
+    /// now by default
+    pub fn fees(&self, from: Option<u64>, to: Option<u64>) -> Result<FeesSummary, ServiceError> {
+        let (from, to) = (from.unwrap_or(0), to.unwrap_or_else(|| self.clock.now()));
+        if from > to {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("invalid range from {} to {}", from, to),
+            ));
+        }
+        Ok(summarize_fees(&self.conn, from, to)?)
+    }
+
+    /// The parameter changes, the latest first
+    pub fn parameter_changes(
+        &self,
+        limit: Option<u32>,
+    ) -> Result<Vec<db::ParameterChangeRecord>, ServiceError> {
+        match limit.unwrap_or(DEFAULT_PARAMETER_CHANGES_LIMIT) {
+            limit @ 1..=MAX_PARAMETER_CHANGES_LIMIT => {
+                Ok(self.conn.query_parameter_changes(limit)?)
+            }
+            _ => Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_PARAMETER_CHANGES_LIMIT),
+            )),
+        }
+    }
+
+    /// Queue the change of parameter `name` to `value`, it takes effect after `timelock`
+    pub fn queue_parameter_change(
+        &self,
+        name: &str,
+        value: &str,
+        timelock: Duration,
+    ) -> Result<db::ParameterChangeRecord, ServiceError> {
+        let id = match BridgeParameter::from_str(name).and_then(|parameter| {
+            queue_parameter_change(&self.conn, parameter, value, self.clock.now(), timelock)
+        }) {
+            Ok(id) => id,
+            Err(e @ BridgeError::InvalidConfig(_)) => {
+                return Err(ServiceError::rejected(
+                    ErrorCode::InvalidParameter,
+                    e.to_string(),
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let change = self.parameter_change(id)?;
+        warn!(
+            "parameter change {id} is queued, `{}` will be {} at {}",
+            change.name, change.value, change.activates_at
+        );
+        Ok(change)
+    }
+
+    /// Cancel the parameter change before it takes effect
+    pub fn cancel_parameter_change(
+        &self,
+        id: u64,
+    ) -> Result<db::ParameterChangeRecord, ServiceError> {
+        if !self.conn.cancel_parameter_change(id, self.clock.now())? {
+            return Err(ServiceError::rejected(
+                ErrorCode::NotFound,
+                format!(
+                    "parameter change {} is not found, activated or cancelled already",
+                    id
+                ),
+            ));
+        }
+        warn!("parameter change {id} is cancelled");
+        self.parameter_change(id)
+    }
+
+    /// The change just written is always there
+    fn parameter_change(&self, id: u64) -> Result<db::ParameterChangeRecord, ServiceError> {
+        self.conn
+            .query_parameter_change(id)?
+            .ok_or_else(|| BridgeError::Database(rusqlite::Error::QueryReturnedNoRows).into())
+    }
+
+    /// Register a deposit without OP_RETURN, the next plain payment from `sender` to the
+    /// custody address is bridged to `recipient`
+    pub fn register_deposit_intent(
+        &self,
+        sender: &str,
+        recipient: &str,
+    ) -> Result<db::DepositIntentRecord, ServiceError> {
+        if sender.is_empty() {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                "'sender' is empty",
+            ));
+        }
+        SolanaService::parse_address(recipient)?;
+        let created_at = self.clock.now();
+        let id = self
+            .conn
+            .add_deposit_intent(sender, recipient, created_at)?;
+        info!("deposit intent {id} is registered, {sender} -> {recipient}");
+        Ok(db::DepositIntentRecord {
+            id,
+            sender: sender.to_owned(),
+            recipient: recipient.to_owned(),
+            created_at,
+            matched_txid: None,
+            matched_vout: None,
+            matched_at: None,
+        })
+    }
+
+    pub fn deposit_intent(&self, id: u64) -> Result<db::DepositIntentRecord, ServiceError> {
+        self.conn.query_deposit_intent(id)?.ok_or_else(|| {
+            ServiceError::rejected(
+                ErrorCode::NotFound,
+                format!("deposit intent {} is not found", id),
+            )
+        })
+    }
+
+    /// Register the off-chain request to pay out the tokens of Solana transaction `signature`
+    /// to `recipient`, it's signed by the sender of the tokens (see `verify_withdraw_request`)
+    /// and the bridge takes it when the transaction is synced
+    pub fn register_withdraw_request(
+        &self,
+        signature: &str,
+        recipient: &str,
+        signer: &str,
+        message_signature: &str,
+    ) -> Result<db::WithdrawRequestRecord, ServiceError> {
+        let request = verify_withdraw_request(signature, recipient, signer, message_signature)
+            .map_err(|e| ServiceError::rejected(e.code(), e.full_message()))?;
+        let signature = request.signature.to_string();
+        if let Some(used_by) = self.conn.query_withdraw_signature(&signature)? {
+            let e = BridgeError::SignatureReused(signature, used_by);
+            return Err(ServiceError::rejected(e.code(), e.full_message()));
+        }
+        let signer = request.signer.to_string();
+        let created_at = self.clock.now();
+        let Some(id) = self.conn.add_withdraw_request(
+            &signature,
+            &request.recipient,
+            &signer,
+            WITHDRAW_ORIGIN_OFFCHAIN,
+            WITHDRAW_REQUEST_PENDING,
+            created_at,
+        )?
+        else {
+            return Err(ServiceError::rejected(
+                ErrorCode::WithdrawRequestDuplicated,
+                format!("solana transaction {signature} is requested already"),
+            ));
+        };
+        info!(
+            "withdraw request {} is registered, {} -> {}",
+            id, signature, request.recipient
+        );
+        Ok(db::WithdrawRequestRecord {
+            id,
+            signature,
+            recipient: request.recipient,
+            signer,
+            origin: WITHDRAW_ORIGIN_OFFCHAIN.to_owned(),
+            status: WITHDRAW_REQUEST_PENDING.to_owned(),
+            reason: None,
+            pending_action_id: None,
+            created_at,
+            processed_at: None,
+        })
+    }
+
+    pub fn withdraw_request(&self, id: u64) -> Result<db::WithdrawRequestRecord, ServiceError> {
+        self.conn.query_withdraw_request(id)?.ok_or_else(|| {
+            ServiceError::rejected(
+                ErrorCode::NotFound,
+                format!("withdraw request {} is not found", id),
+            )
+        })
+    }
+
+    pub fn refund(&self, depc_txid: &str, vout: u32) -> Result<db::RefundRecord, ServiceError> {
+        self.conn
+            .query_refund_of_output(depc_txid, vout)?
+            .ok_or_else(|| {
+                ServiceError::rejected(
+                    ErrorCode::NotFound,
+                    format!("refund of {depc_txid}:{vout} is not found"),
+                )
+            })
+    }
+
+    /// Claim the refund of the invalid deposit `depc_txid:vout` to `address` instead of the
+    /// refund address, `signature` is made by the key of the refund address for the message of
+    /// the claim (see `make_refund_claim_message`). The refund worker verifies it before the
+    /// refund is made
+    pub fn claim_refund(
+        &self,
+        depc_txid: &str,
+        vout: u32,
+        address: &str,
+        signature: &str,
+    ) -> Result<db::RefundRecord, ServiceError> {
+        let refund = self.refund(depc_txid, vout)?;
+        if let Err(e) = address_script_pubkey_hex(address) {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidAddress,
+                e.to_string(),
+            ));
+        }
+        if signature.is_empty() {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                "'signature' is empty",
+            ));
+        }
+        if !self.conn.claim_refund(refund.id, address, signature)? {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("refund of {depc_txid}:{vout} is made or claimed already"),
+            ));
+        }
+        info!(
+            "refund {} is claimed to {address}, waiting for the verification",
+            refund.id
+        );
+        Ok(db::RefundRecord {
+            claim_address: Some(address.to_owned()),
+            claim_signature: Some(signature.to_owned()),
+            claim_status: Some(REFUND_CLAIM_SUBMITTED.to_owned()),
+            ..refund
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::signer::{keypair::keypair_from_seed, Signer};
+
+    use super::*;
+    use crate::amount::DepcAmount;
+    use crate::bridge::{
+        make_withdraw_request_message, CATCH_UP_AWAITING_CONFIRMATION, CATCH_UP_EXECUTING,
+        REFUND_CLAIM_REJECTED,
+    };
+    use crate::testing::MockClock;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    #[test]
+    fn test_confirm_catch_up_and_fees() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let service = BridgeService::new(conn.clone(), Arc::new(MockClock::new(TEST_NOW)));
+        let e = service.confirm_catch_up().unwrap_err();
+        assert_eq!(e.code(), ErrorCode::NotFound);
+        assert_eq!(e.to_string(), "no catch-up plan awaits the confirmation");
+
+        let id = conn
+            .add_catch_up_plan(100, 200, CATCH_UP_AWAITING_CONFIRMATION, TEST_NOW - 60)
+            .unwrap();
+        let plan = service.confirm_catch_up().unwrap();
+        assert_eq!((plan.id, plan.status.as_str()), (id, CATCH_UP_EXECUTING));
+        assert_eq!(plan.confirmed_at, Some(TEST_NOW));
+        assert!(service.confirm_catch_up().is_err());
+
+        Ledger::new(conn.clone())
+            .deposit_fee("d1", DepcAmount::from_value64(1000), TEST_NOW)
+            .unwrap();
+        let summary = service.fees(None, None).unwrap();
+        assert_eq!((summary.from, summary.to), (0, TEST_NOW));
+        assert_eq!(summary.deposits, DepcAmount::from_value64(1000));
+        assert_eq!(
+            service.fees(Some(TEST_NOW), Some(0)).unwrap_err().code(),
+            ErrorCode::InvalidParameter
+        );
+        assert!(service.trial_balance().unwrap().is_balanced());
+    }
+
+    #[test]
+    fn test_parameter_changes() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let service = BridgeService::new(conn, Arc::new(MockClock::new(TEST_NOW)));
+        let timelock = Duration::from_secs(600);
+        for (name, value) in [("fee", "5000"), ("deposit_threshold", "abc")] {
+            let e = service
+                .queue_parameter_change(name, value, timelock)
+                .unwrap_err();
+            assert_eq!(e.code(), ErrorCode::InvalidParameter);
+        }
+        let change = service
+            .queue_parameter_change("deposit_threshold", "5000", timelock)
+            .unwrap();
+        assert_eq!(change.activates_at, TEST_NOW + 600);
+        assert_eq!(
+            service.parameter_changes(None).unwrap(),
+            vec![change.clone()]
+        );
+        assert!(service.parameter_changes(Some(0)).is_err());
+
+        let cancelled = service.cancel_parameter_change(change.id).unwrap();
+        assert_eq!(cancelled.cancelled_at, Some(TEST_NOW));
+        assert_eq!(
+            service
+                .cancel_parameter_change(change.id)
+                .unwrap_err()
+                .code(),
+            ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn test_register_deposit_intents_and_withdraw_requests() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let service = BridgeService::new(conn, Arc::new(MockClock::new(TEST_NOW)));
+        let recipient = Pubkey::new_unique().to_string();
+        assert_eq!(
+            service
+                .register_deposit_intent("", &recipient)
+                .unwrap_err()
+                .code(),
+            ErrorCode::InvalidParameter
+        );
+        assert_eq!(
+            service
+                .register_deposit_intent("sender", "invalid")
+                .unwrap_err()
+                .code(),
+            ErrorCode::InvalidAddress
+        );
+        let intent = service
+            .register_deposit_intent("sender", &recipient)
+            .unwrap();
+        assert_eq!(service.deposit_intent(intent.id).unwrap(), intent);
+        assert!(service.deposit_intent(intent.id + 1).is_err());
+
+        let keypair = keypair_from_seed(&[7; 32]).unwrap();
+        let signer = keypair.pubkey().to_string();
+        let signature = Signature::new_unique().to_string();
+        let depc_recipient = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+        let message = make_withdraw_request_message(&signature, depc_recipient);
+        let message_signature = keypair.sign_message(message.as_bytes()).to_string();
+        assert!(service
+            .register_withdraw_request(&signature, depc_recipient, &signer, "invalid")
+            .is_err());
+        let request = service
+            .register_withdraw_request(&signature, depc_recipient, &signer, &message_signature)
+            .unwrap();
+        assert_eq!(
+            (request.status.as_str(), request.created_at),
+            (WITHDRAW_REQUEST_PENDING, TEST_NOW)
+        );
+        assert_eq!(service.withdraw_request(request.id).unwrap(), request);
+        let e = service
+            .register_withdraw_request(&signature, depc_recipient, &signer, &message_signature)
+            .unwrap_err();
+        assert_eq!(e.code(), ErrorCode::WithdrawRequestDuplicated);
+    }
+
+    #[test]
+    fn test_claim_refunds() {
+        const TEST_SENDER_ADDRESS: &str = "2N6w8JULPfAVD3ckv1u7mP7TEzFbJkThQjT";
+        const TEST_CLAIM_ADDRESS: &str = "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS";
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let service = BridgeService::new(conn.clone(), Arc::new(MockClock::new(TEST_NOW)));
+        let e = service
+            .claim_refund("depc_txid1", 0, TEST_CLAIM_ADDRESS, "signature")
+            .unwrap_err();
+        assert_eq!(e.code(), ErrorCode::NotFound);
+
+        conn.add_refund(
+            "depc_txid1",
+            0,
+            TEST_SENDER_ADDRESS,
+            DepcAmount::from_value64(5000000),
+            "invalid address: bad",
+            TEST_NOW,
+        )
+        .unwrap();
+        for (address, signature, code) in [
+            ("not an address", "signature", ErrorCode::InvalidAddress),
+            (TEST_CLAIM_ADDRESS, "", ErrorCode::InvalidParameter),
+        ] {
+            let e = service
+                .claim_refund("depc_txid1", 0, address, signature)
+                .unwrap_err();
+            assert_eq!(e.code(), code);
+        }
+        let refund = service
+            .claim_refund("depc_txid1", 0, TEST_CLAIM_ADDRESS, "signature")
+            .unwrap();
+        assert_eq!(refund.refund_address, TEST_SENDER_ADDRESS);
+        assert_eq!(refund.claim_address.as_deref(), Some(TEST_CLAIM_ADDRESS));
+        assert_eq!(refund.claim_status.as_deref(), Some(REFUND_CLAIM_SUBMITTED));
+        assert_eq!(
+            conn.query_refund_of_output("depc_txid1", 0).unwrap(),
+            Some(refund.clone())
+        );
+        // the claim is kept until the refund worker rejects it
+        let e = service
+            .claim_refund("depc_txid1", 0, TEST_SENDER_ADDRESS, "signature")
+            .unwrap_err();
+        assert_eq!(e.code(), ErrorCode::InvalidParameter);
+        conn.set_refund_claim_status(refund.id, REFUND_CLAIM_REJECTED)
+            .unwrap();
+        assert!(service
+            .claim_refund("depc_txid1", 0, TEST_SENDER_ADDRESS, "signature")
+            .is_ok());
+    }
+}
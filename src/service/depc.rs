@@ -0,0 +1,317 @@
+use crate::amount::DepcAmount;
+use crate::analysis::{build_address_clusters, ClusterReport};
+use crate::clock::SharedClock;
+use crate::db;
+use crate::ErrorCode;
+
+use super::ServiceError;
+
+pub const DEFAULT_RICHLIST_LIMIT: u32 = 100;
+pub const MAX_RICHLIST_LIMIT: u32 = 1000;
+pub const DEFAULT_BLOCKS_PER_QUERY: u32 = 20;
+pub const MAX_BLOCKS_PER_QUERY: u32 = 100;
+/// The miners are counted in the last blocks by default
+pub const DEFAULT_MINER_STATS_BLOCKS: u32 = 1000;
+pub const MAX_MINER_STATS_BLOCKS: u32 = 100_000;
+pub const DEFAULT_MINERS_LIMIT: u32 = 20;
+pub const MAX_MINERS_LIMIT: u32 = 1000;
+/// The daily blocks of a miner are reported for the last days by default
+pub const DEFAULT_MINER_DAYS: u64 = 30;
+pub const DEFAULT_EXCHANGE_ADDRESSES_LIMIT: u32 = 100;
+pub const MAX_EXCHANGE_ADDRESSES_LIMIT: u32 = 1000;
+
+/// The miners of the blocks `from..=to` (heights), the ones of the most blocks first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinersReport {
+    pub from: u32,
+    pub to: u32,
+    pub miners: Vec<db::MinerStatsRecord>,
+}
+
+/// The blocks of a miner, and the ones made `from..=to` (times) by the day (UTC)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinerReport {
+    pub stats: db::MinerStatsRecord,
+    pub from: u64,
+    pub to: u64,
+    /// The days since the epoch and the blocks made on them
+    pub days: Vec<(u64, u32)>,
+}
+
+/// A page of the exchange addresses in the order of the addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeAddressPage {
+    pub addresses: Vec<db::ExchangeAddressRecord>,
+    /// The cursor of the next page, `None` for the last page
+    pub next_cursor: Option<String>,
+}
+
+/// The cluster of the addresses an address is grouped into, see
+/// `analysis::build_address_clusters`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressCluster {
+    pub cluster_id: u64,
+    pub members: Vec<String>,
+}
+
+/// The queries of the DePC chain index, the ranges and the limits of them are checked here so
+/// every transport refuses the same requests
+#[derive(Clone)]
+pub struct DepcService {
+    conn: db::Conn,
+    clock: SharedClock,
+}
+
+impl DepcService {
+    pub fn new(conn: db::Conn, clock: SharedClock) -> DepcService {
+        DepcService { conn, clock }
+    }
+
+    /// The block summary by the height or the hash, the hash is 64 hex digits so it's never
+    /// taken as a height
+    pub fn block(&self, height_or_hash: &str) -> Result<db::BlockSummaryRecord, ServiceError> {
+        let block = match height_or_hash.parse::<u32>() {
+            Ok(height) => self.conn.query_block_summary_by_height(height)?,
+            Err(_) => self.conn.query_block_summary_by_hash(height_or_hash)?,
+        };
+        block.ok_or_else(|| {
+            ServiceError::rejected(
+                ErrorCode::BlockNotFound,
+                format!("block '{}' cannot be found", height_or_hash),
+            )
+        })
+    }
+
+    /// The block summaries of `from..=to`, the last blocks indexed by default
+    pub fn blocks(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+    ) -> Result<Vec<db::BlockSummaryRecord>, ServiceError> {
+        let to = to.unwrap_or(self.conn.query_best_height().unwrap_or_default());
+        let from = from.unwrap_or(to.saturating_sub(DEFAULT_BLOCKS_PER_QUERY - 1));
+        if from > to || to - from >= MAX_BLOCKS_PER_QUERY {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!(
+                    "invalid range from {} to {}, at most {} blocks can be returned",
+                    from, to, MAX_BLOCKS_PER_QUERY
+                ),
+            ));
+        }
+        Ok(self.conn.query_block_summaries(from, to)?)
+    }
+
+    pub fn miners(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<MinersReport, ServiceError> {
+        let limit = limit.unwrap_or(DEFAULT_MINERS_LIMIT);
+        if !(1..=MAX_MINERS_LIMIT).contains(&limit) {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_MINERS_LIMIT),
+            ));
+        }
+        let to = to.unwrap_or(self.conn.query_best_height().unwrap_or_default());
+        let from = from.unwrap_or(to.saturating_sub(DEFAULT_MINER_STATS_BLOCKS - 1));
+        if from > to || to - from >= MAX_MINER_STATS_BLOCKS {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!(
+                    "invalid range from {} to {}, at most {} blocks can be counted",
+                    from, to, MAX_MINER_STATS_BLOCKS
+                ),
+            ));
+        }
+        let miners = self.conn.query_miner_stats(from, to, limit)?;
+        Ok(MinersReport { from, to, miners })
+    }
+
+    pub fn miner(
+        &self,
+        miner: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<MinerReport, ServiceError> {
+        let to = to.unwrap_or_else(|| self.clock.now());
+        let from = from.unwrap_or(to.saturating_sub(DEFAULT_MINER_DAYS * 24 * 3600));
+        if from > to {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("invalid range from {} to {}", from, to),
+            ));
+        }
+        let Some(stats) = self.conn.query_miner_stats_of_miner(miner)? else {
+            return Err(ServiceError::rejected(
+                ErrorCode::NotFound,
+                format!("miner '{}' made no block indexed", miner),
+            ));
+        };
+        let days = self.conn.query_miner_daily_blocks(miner, from, to)?;
+        Ok(MinerReport {
+            stats,
+            from,
+            to,
+            days,
+        })
+    }
+
+    /// The exchange addresses after `cursor`, which is the `next_cursor` of the previous page,
+    /// the first page by default
+    pub fn exchange_addresses(
+        &self,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<ExchangeAddressPage, ServiceError> {
+        let limit = limit.unwrap_or(DEFAULT_EXCHANGE_ADDRESSES_LIMIT);
+        if !(1..=MAX_EXCHANGE_ADDRESSES_LIMIT).contains(&limit) {
+            return Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_EXCHANGE_ADDRESSES_LIMIT),
+            ));
+        }
+        // one more is queried to tell the last page
+        let mut addresses = self
+            .conn
+            .query_exchange_addresses_after(cursor.unwrap_or_default(), limit + 1)?;
+        let next_cursor = if addresses.len() > limit as usize {
+            addresses.truncate(limit as usize);
+            addresses.last().map(|record| record.address.clone())
+        } else {
+            None
+        };
+        Ok(ExchangeAddressPage {
+            addresses,
+            next_cursor,
+        })
+    }
+
+    /// Group the addresses of the index into the clusters again
+    pub fn rebuild_clusters(&self) -> Result<ClusterReport, ServiceError> {
+        Ok(build_address_clusters(&self.conn)?)
+    }
+
+    pub fn address_cluster(&self, address: &str) -> Result<AddressCluster, ServiceError> {
+        let Some(cluster_id) = self.conn.query_cluster_id_of_address(address)? else {
+            return Err(ServiceError::rejected(
+                ErrorCode::NotFound,
+                format!("address '{}' is not in any cluster", address),
+            ));
+        };
+        let members = self.conn.query_cluster_members(cluster_id)?;
+        Ok(AddressCluster {
+            cluster_id,
+            members,
+        })
+    }
+
+    /// The addresses of the largest balances and the balances, the largest first
+    pub fn richlist(&self, limit: Option<u32>) -> Result<Vec<(String, DepcAmount)>, ServiceError> {
+        match limit.unwrap_or(DEFAULT_RICHLIST_LIMIT) {
+            limit @ 1..=MAX_RICHLIST_LIMIT => Ok(self.conn.query_richlist(limit)?),
+            _ => Err(ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("'limit' should be in 1..={}", MAX_RICHLIST_LIMIT),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testing::MockClock;
+
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    fn make_service() -> DepcService {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        conn.add_block("hash0", 0, "miner_a", TEST_NOW - 600)
+            .unwrap();
+        conn.add_block("hash1", 1, "miner_b", TEST_NOW - 300)
+            .unwrap();
+        conn.add_block("hash2", 2, "miner_a", TEST_NOW).unwrap();
+        DepcService::new(conn, Arc::new(MockClock::new(TEST_NOW)))
+    }
+
+    #[test]
+    fn test_blocks_and_miners() {
+        let service = make_service();
+        assert_eq!(service.block("1").unwrap().hash, "hash1");
+        assert_eq!(service.block("hash2").unwrap().height, 2);
+        assert_eq!(
+            service.block("3").unwrap_err().code(),
+            ErrorCode::BlockNotFound
+        );
+
+        // the last blocks by default
+        let blocks = service.blocks(None, None).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            service.blocks(Some(2), Some(1)).unwrap_err().code(),
+            ErrorCode::InvalidParameter
+        );
+        assert!(service.blocks(Some(0), Some(MAX_BLOCKS_PER_QUERY)).is_err());
+
+        let report = service.miners(None, None, None).unwrap();
+        assert_eq!((report.from, report.to), (0, 2));
+        assert_eq!(report.miners[0].miner, "miner_a");
+        assert!(service.miners(None, None, Some(0)).is_err());
+
+        let report = service.miner("miner_a", None, None).unwrap();
+        assert_eq!(report.to, TEST_NOW);
+        assert_eq!(report.days, vec![(TEST_NOW / 86400, 2)]);
+        assert_eq!(
+            service.miner("miner_c", None, None).unwrap_err().code(),
+            ErrorCode::NotFound
+        );
+        assert!(service
+            .miner("miner_a", Some(TEST_NOW), Some(TEST_NOW - 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_exchange_address_pages() {
+        let service = make_service();
+        for address in ["c", "a", "b"] {
+            service
+                .conn
+                .add_analyzed_exchange_address_from_tx(address, "txid", TEST_NOW)
+                .unwrap();
+        }
+        let page = service.exchange_addresses(None, Some(2)).unwrap();
+        let addresses: Vec<_> = page.addresses.iter().map(|r| r.address.as_str()).collect();
+        assert_eq!(addresses, vec!["a", "b"]);
+        assert_eq!(page.next_cursor.as_deref(), Some("b"));
+        assert_eq!(page.addresses[0].first_seen, Some(TEST_NOW));
+
+        let page = service
+            .exchange_addresses(page.next_cursor.as_deref(), Some(2))
+            .unwrap();
+        assert_eq!(page.addresses.len(), 1);
+        assert_eq!(page.addresses[0].address, "c");
+        assert_eq!(page.next_cursor, None);
+        // a full last page has no next one either
+        let page = service.exchange_addresses(None, Some(3)).unwrap();
+        assert_eq!((page.addresses.len(), page.next_cursor), (3, None));
+        assert!(service.exchange_addresses(None, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_richlist_limit() {
+        let service = make_service();
+        assert!(service.richlist(None).unwrap().is_empty());
+        for limit in [0, MAX_RICHLIST_LIMIT + 1] {
+            let e = service.richlist(Some(limit)).unwrap_err();
+            assert_eq!(e.code(), ErrorCode::InvalidParameter);
+            assert_eq!(e.to_string(), "'limit' should be in 1..=1000");
+        }
+    }
+}
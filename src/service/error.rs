@@ -0,0 +1,35 @@
+use crate::{BridgeError, ErrorCode};
+
+/// The error of the services. A request the business rules refuse is rejected with the code and
+/// the message for the caller, the transports answer it as a client error, the others are the
+/// failures of the bridge
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("{message}")]
+    Rejected { code: ErrorCode, message: String },
+    #[error(transparent)]
+    Bridge(#[from] BridgeError),
+}
+
+impl ServiceError {
+    pub fn rejected(code: ErrorCode, message: impl Into<String>) -> ServiceError {
+        ServiceError::Rejected {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The code of the error, see `ErrorCode`
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ServiceError::Rejected { code, .. } => *code,
+            ServiceError::Bridge(e) => e.code(),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ServiceError {
+    fn from(e: rusqlite::Error) -> Self {
+        ServiceError::Bridge(e.into())
+    }
+}
@@ -0,0 +1,11 @@
+mod bridge;
+mod depc;
+mod solana;
+
+mod error;
+
+pub use bridge::*;
+pub use depc::*;
+pub use solana::*;
+
+pub use error::*;
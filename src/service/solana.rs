@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::amount::TokenAmount;
+use crate::solana::{self, AnalyzedTransaction, SolanaClient};
+use crate::ErrorCode;
+
+use super::ServiceError;
+
+/// The queries of the Solana node for the bridge. The calls block on the node, the async
+/// transports make them off their workers
+#[derive(Clone)]
+pub struct SolanaService {
+    client: SolanaClient,
+}
+
+impl SolanaService {
+    pub fn new(client: SolanaClient) -> SolanaService {
+        SolanaService { client }
+    }
+
+    pub fn parse_address(address: &str) -> Result<Pubkey, ServiceError> {
+        Pubkey::from_str(address).map_err(|_| {
+            ServiceError::rejected(
+                ErrorCode::InvalidAddress,
+                format!("cannot parse address from string '{}'", address),
+            )
+        })
+    }
+
+    pub fn parse_signature(signature: &str) -> Result<Signature, ServiceError> {
+        Signature::from_str(signature).map_err(|_| {
+            ServiceError::rejected(
+                ErrorCode::InvalidParameter,
+                format!("cannot parse signature from string '{}'", signature),
+            )
+        })
+    }
+
+    /// The token balance of `address`
+    pub fn balance(&self, address: &Pubkey) -> Result<TokenAmount, ServiceError> {
+        self.client
+            .get_balance(address)
+            .map(TokenAmount::new)
+            .map_err(|_| {
+                ServiceError::rejected(
+                    ErrorCode::SolanaRpcUnavailable,
+                    format!("cannot get balance for address: '{}'", address),
+                )
+            })
+    }
+
+    /// The analysis of any Solana transaction by the parsing of the bridge
+    pub fn transaction(&self, signature: &Signature) -> Result<AnalyzedTransaction, ServiceError> {
+        self.client
+            .get_analyzed_transaction(signature)
+            .map_err(|e| match e {
+                solana::Error::CannotParseTransactionInfo(_) => ServiceError::rejected(
+                    ErrorCode::SolanaTransactionInvalid,
+                    format!(
+                        "transaction {} cannot be analyzed, reason: {}",
+                        signature, e
+                    ),
+                ),
+                e => ServiceError::rejected(
+                    ErrorCode::SolanaRpcUnavailable,
+                    format!("cannot get transaction {}, reason: {}", signature, e),
+                ),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address_and_signature() {
+        let address = Pubkey::new_unique();
+        assert_eq!(
+            SolanaService::parse_address(&address.to_string()).unwrap(),
+            address
+        );
+        let e = SolanaService::parse_address("invalid").unwrap_err();
+        assert_eq!(e.code(), ErrorCode::InvalidAddress);
+        assert_eq!(e.to_string(), "cannot parse address from string 'invalid'");
+
+        let signature = Signature::new_unique();
+        assert_eq!(
+            SolanaService::parse_signature(&signature.to_string()).unwrap(),
+            signature
+        );
+        assert_eq!(
+            SolanaService::parse_signature("invalid")
+                .unwrap_err()
+                .code(),
+            ErrorCode::InvalidParameter
+        );
+    }
+}
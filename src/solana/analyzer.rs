@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use serde::Deserialize;
@@ -16,6 +17,7 @@ pub enum Error {
     CannotParsePubkey,
     LamportsIsRequiredFromInfoValue,
     AmountIsRequiredFromInfoValue,
+    InvalidAllowlistEntry(String),
 }
 
 impl std::fmt::Display for Error {
@@ -34,16 +36,39 @@ impl std::fmt::Display for Error {
             Error::AmountIsRequiredFromInfoValue => {
                 write!(f, "lamports cannot be found from info value")
             }
+            Error::InvalidAllowlistEntry(entry) => write!(
+                f,
+                "invalid allowlist entry '{}', it should be <program id>[=<label>]",
+                entry
+            ),
         }
     }
 }
 
+impl std::error::Error for Error {}
+
+/// The name the RPC node gives the instructions of the memo programs
+const MEMO_PROGRAM_NAME: &str = "spl-memo";
+
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct InstructionInfoValue {
-    source: String,
-    destination: String,
-    lamports: Option<String>,
+    source: Option<String>,
+    destination: Option<String>,
+    /// The token account minted to or burned from
+    account: Option<String>,
+    mint: Option<String>,
+    // the system program reports lamports as a number, spl-token reports amount as a string
+    lamports: Option<serde_json::Value>,
     amount: Option<String>,
+    /// The amount of the `*Checked` instructions of spl-token
+    token_amount: Option<TokenAmountValue>,
+}
+
+#[derive(Deserialize)]
+struct TokenAmountValue {
+    amount: String,
+    decimals: u8,
 }
 
 #[derive(Deserialize)]
@@ -52,50 +77,272 @@ struct InstructionValue {
     r#type: String,
 }
 
+/// What a token instruction does, SOL is always transferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOperation {
+    Transfer,
+    /// The source is the mint
+    MintTo,
+    /// The destination is the mint
+    Burn,
+}
+
+impl std::fmt::Display for TokenOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenOperation::Transfer => write!(f, "transfer"),
+            TokenOperation::MintTo => write!(f, "mint_to"),
+            TokenOperation::Burn => write!(f, "burn"),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct InstructionDetail {
     pub source: Pubkey,
     pub destination: Pubkey,
+    /// The wallets own the source and destination, they're the accounts themselves for the SOL
+    /// transfers, and `None` for the token accounts until they're resolved, see
+    /// [`Transaction::resolve_owners`]
+    pub source_owner: Option<Pubkey>,
+    pub destination_owner: Option<Pubkey>,
     pub amount: u64,
+    pub operation: TokenOperation,
+    /// The mint of the token, a plain `transfer` doesn't carry it
+    pub mint: Option<Pubkey>,
+    /// The decimals of the mint, only the `*Checked` instructions carry them
+    pub decimals: Option<u8>,
+}
+
+/// An instruction of a program in the allowlist of `AnalyzerPolicy`, nothing it moves is
+/// counted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtherInstruction {
+    pub program_id: Pubkey,
+    /// The label the allowlist gives the program, or the name the RPC node gives it
+    pub label: String,
+    /// The type of the instruction if the RPC node parses it, e.g. `create`
+    pub kind: Option<String>,
 }
 
+#[derive(Clone)]
 pub enum Instruction {
     SplToken(InstructionDetail),
     Solana(InstructionDetail),
+    Other(OtherInstruction),
+}
+
+/// How the analyzer treats the instructions of the programs other than system, spl-token and
+/// memo
+///
+/// They're skipped by default and the programs are tagged on `Transaction::skipped_programs`,
+/// the programs in the allowlist are kept as `Instruction::Other`. A strict policy fails the
+/// whole transaction with `Error::UnknownProgramId` instead.
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerPolicy {
+    pub strict: bool,
+    /// The program ids with their labels, the label is `None` for the name from the RPC node
+    pub allowlist: HashMap<Pubkey, Option<String>>,
+}
+
+impl AnalyzerPolicy {
+    /// Add the program of `entry` to the allowlist, it's `<program id>[=<label>]`
+    pub fn allow(&mut self, entry: &str) -> Result<(), Error> {
+        let (program_id, label) = match entry.split_once('=') {
+            Some((program_id, label)) if !label.is_empty() => (program_id, Some(label)),
+            Some(_) => return Err(Error::InvalidAllowlistEntry(entry.to_owned())),
+            None => (entry, None),
+        };
+        let program_id = parse_pubkey(program_id.trim())
+            .map_err(|_| Error::InvalidAllowlistEntry(entry.to_owned()))?;
+        self.allowlist
+            .insert(program_id, label.map(|label| label.trim().to_owned()));
+        Ok(())
+    }
 }
 
+#[derive(Clone)]
 pub struct Transaction {
     pub signature: Signature,
+    pub slot: u64,
     pub fee: u64,
     pub timestamp: i64,
+    /// The error the transaction failed with, `None` if it succeeded
+    pub error: Option<String>,
+    /// The texts of the memo instructions joined by `; `, the memos are not in `instructions`
+    pub memo: Option<String>,
     pub instructions: Vec<Instruction>,
+    /// The programs whose instructions are skipped, see `AnalyzerPolicy`
+    pub skipped_programs: Vec<Pubkey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+    /// The address is both the sender and the recipient
+    SelfTransfer,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::In => write!(f, "in"),
+            Direction::Out => write!(f, "out"),
+            Direction::SelfTransfer => write!(f, "self"),
+        }
+    }
+}
+
+/// The effect of a transaction on an address, the fee is not included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressEffect {
+    pub direction: Direction,
+    /// The net change of the token in the base units
+    pub token_change: i128,
+    /// The net change of SOL in lamports
+    pub sol_change: i128,
+}
+
+impl Transaction {
+    /// Fill the owners of the token accounts by `resolve`, the owner is left `None` when the
+    /// account is not a token account or it's closed
+    pub fn resolve_owners<E>(
+        &mut self,
+        mut resolve: impl FnMut(&Pubkey) -> Result<Option<Pubkey>, E>,
+    ) -> Result<(), E> {
+        for ix in self.instructions.iter_mut() {
+            if let Instruction::SplToken(detail) = ix {
+                // the mint has no owner
+                if detail.source_owner.is_none() && detail.operation != TokenOperation::MintTo {
+                    detail.source_owner = resolve(&detail.source)?;
+                }
+                if detail.destination_owner.is_none() && detail.operation != TokenOperation::Burn {
+                    detail.destination_owner = resolve(&detail.destination)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The direction and the net changes of the transaction on `address`, it's either an
+    /// account or the wallet owns the token accounts. `None` if no transfer is related to it
+    pub fn effect_on(&self, address: &Pubkey) -> Option<AddressEffect> {
+        let (mut sends, mut receives) = (false, false);
+        let (mut token_change, mut sol_change) = (0i128, 0i128);
+        for ix in self.instructions.iter() {
+            let (detail, change) = match ix {
+                Instruction::SplToken(detail) => (detail, &mut token_change),
+                Instruction::Solana(detail) => (detail, &mut sol_change),
+                Instruction::Other(_) => continue,
+            };
+            let from = detail.source == *address || detail.source_owner == Some(*address);
+            let to = detail.destination == *address || detail.destination_owner == Some(*address);
+            if from {
+                sends = true;
+                *change -= detail.amount as i128;
+            }
+            if to {
+                receives = true;
+                *change += detail.amount as i128;
+            }
+        }
+        let direction = match (sends, receives) {
+            (true, true) => Direction::SelfTransfer,
+            (true, false) => Direction::Out,
+            (false, true) => Direction::In,
+            (false, false) => return None,
+        };
+        Some(AddressEffect {
+            direction,
+            token_change,
+            sol_change,
+        })
+    }
 }
 
 pub struct TransactionAnalyzer<'a> {
     transaction_meta: &'a EncodedConfirmedTransactionWithStatusMeta,
+    /// The default policy when it's `None`
+    policy: Option<&'a AnalyzerPolicy>,
 }
 
 impl<'a> TransactionAnalyzer<'a> {
     pub fn new(transaction_meta: &'a EncodedConfirmedTransactionWithStatusMeta) -> Self {
-        Self { transaction_meta }
+        Self {
+            transaction_meta,
+            policy: None,
+        }
+    }
+
+    pub fn with_policy(
+        transaction_meta: &'a EncodedConfirmedTransactionWithStatusMeta,
+        policy: &'a AnalyzerPolicy,
+    ) -> Self {
+        Self {
+            transaction_meta,
+            policy: Some(policy),
+        }
     }
 
     pub fn parse(&self, signature: Signature, timestamp: i64) -> Result<Transaction, Error> {
+        let meta = self.get_meta()?;
         let mut transaction = Transaction {
             signature,
-            fee: self.get_fee()?,
+            slot: self.transaction_meta.slot,
+            fee: meta.fee,
             timestamp,
+            error: meta.err.as_ref().map(|err| err.to_string()),
+            memo: None,
             instructions: vec![],
+            skipped_programs: vec![],
         };
-        for ix in self.strip_instructions()?.iter() {
-            transaction.instructions.push(parse_instruction(ix)?);
+        let mut memos = vec![];
+        for instruction in self.message_instructions() {
+            let (program_id, name, kind) = match instruction {
+                UiParsedInstruction::Parsed(ix) => {
+                    let program_id = parse_pubkey(&ix.program_id)?;
+                    if program_id == system_program::id() || program_id == spl_token::id() {
+                        transaction.instructions.push(parse_instruction(ix)?);
+                        continue;
+                    }
+                    if ix.program == MEMO_PROGRAM_NAME {
+                        // the memo program reports the text as the parsed value
+                        memos.push(ix.parsed.as_str().unwrap_or_default().to_owned());
+                        continue;
+                    }
+                    let kind = ix.parsed["type"].as_str().map(|kind| kind.to_owned());
+                    (program_id, ix.program.clone(), kind)
+                }
+                // the RPC node has no parser of the program
+                UiParsedInstruction::PartiallyDecoded(ix) => {
+                    let program_id = parse_pubkey(&ix.program_id)?;
+                    (program_id, ix.program_id.clone(), None)
+                }
+            };
+            if let Some(label) = self
+                .policy
+                .and_then(|policy| policy.allowlist.get(&program_id))
+            {
+                transaction
+                    .instructions
+                    .push(Instruction::Other(OtherInstruction {
+                        program_id,
+                        label: label.clone().unwrap_or(name),
+                        kind,
+                    }));
+            } else if self.policy.is_some_and(|policy| policy.strict) {
+                return Err(Error::UnknownProgramId);
+            } else if !transaction.skipped_programs.contains(&program_id) {
+                transaction.skipped_programs.push(program_id);
+            }
+        }
+        if !memos.is_empty() {
+            transaction.memo = Some(memos.join("; "));
         }
         Ok(transaction)
     }
 
-    fn get_fee(&self) -> Result<u64, Error> {
-        Ok(self.get_meta()?.fee)
-    }
-
     fn get_meta(&self) -> Result<&UiTransactionStatusMeta, Error> {
         if let Some(meta) = self.transaction_meta.transaction.meta.as_ref() {
             Ok(meta)
@@ -104,21 +351,32 @@ impl<'a> TransactionAnalyzer<'a> {
         }
     }
 
-    fn strip_instructions(&self) -> Result<Vec<&ParsedInstruction>, Error> {
+    /// The top-level instructions of the transaction fetched in `jsonParsed`
+    fn message_instructions(&self) -> Vec<&UiParsedInstruction> {
         let mut instructions = vec![];
         let transaction = &self.transaction_meta.transaction.transaction;
         if let EncodedTransaction::Json(transaction) = transaction {
             if let UiMessage::Parsed(message) = &transaction.message {
                 for instruction in message.instructions.iter() {
-                    if let UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) =
-                        instruction
-                    {
+                    if let UiInstruction::Parsed(instruction) = instruction {
                         instructions.push(instruction);
                     }
                 }
             }
         }
-        Ok(instructions)
+        instructions
+    }
+
+    #[cfg(test)]
+    fn strip_instructions(&self) -> Result<Vec<&ParsedInstruction>, Error> {
+        Ok(self
+            .message_instructions()
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                UiParsedInstruction::Parsed(instruction) => Some(instruction),
+                UiParsedInstruction::PartiallyDecoded(_) => None,
+            })
+            .collect())
     }
 }
 
@@ -137,27 +395,72 @@ fn parse_instruction(instruction: &ParsedInstruction) -> Result<Instruction, Err
         return Err(Error::CannotParseInstructionValue);
     }
     let instruction_value: InstructionValue = res.unwrap();
-    // check and create the result
-    let mut instruction_detail = InstructionDetail {
-        source: parse_pubkey(&instruction_value.info.source)?,
-        destination: parse_pubkey(&instruction_value.info.destination)?,
-        amount: 0,
+    let info = instruction_value.info;
+    let required = |value: Option<String>| {
+        value
+            .ok_or(Error::CannotParseInstructionValue)
+            .and_then(|value| parse_pubkey(&value))
     };
     let program_id = parse_pubkey(&instruction.program_id)?;
     if program_id == system_program::id() {
-        if let Some(amount) = instruction_value.info.lamports {
-            instruction_detail.amount = parse_number(&amount)?;
-            Ok(Instruction::Solana(instruction_detail))
-        } else {
-            Err(Error::LamportsIsRequiredFromInfoValue)
-        }
+        let source = required(info.source)?;
+        let destination = required(info.destination)?;
+        let Some(lamports) = info.lamports else {
+            return Err(Error::LamportsIsRequiredFromInfoValue);
+        };
+        let amount = match lamports {
+            serde_json::Value::Number(n) => n.as_u64().ok_or(Error::CannotParseNumber)?,
+            serde_json::Value::String(s) => parse_number(&s)?,
+            _ => return Err(Error::CannotParseNumber),
+        };
+        Ok(Instruction::Solana(InstructionDetail {
+            source,
+            destination,
+            source_owner: Some(source),
+            destination_owner: Some(destination),
+            amount,
+            operation: TokenOperation::Transfer,
+            mint: None,
+            decimals: None,
+        }))
     } else if program_id == spl_token::id() {
-        if let Some(amount) = instruction_value.info.amount {
-            instruction_detail.amount = parse_number(&amount)?;
-            Ok(Instruction::SplToken(instruction_detail))
-        } else {
-            Err(Error::AmountIsRequiredFromInfoValue)
-        }
+        let mint = info.mint.map(|mint| parse_pubkey(&mint)).transpose()?;
+        let (operation, source, destination) = match instruction_value.r#type.as_str() {
+            "transfer" | "transferChecked" => (
+                TokenOperation::Transfer,
+                required(info.source)?,
+                required(info.destination)?,
+            ),
+            "mintTo" | "mintToChecked" => (
+                TokenOperation::MintTo,
+                mint.ok_or(Error::CannotParseInstructionValue)?,
+                required(info.account)?,
+            ),
+            "burn" | "burnChecked" => (
+                TokenOperation::Burn,
+                required(info.account)?,
+                mint.ok_or(Error::CannotParseInstructionValue)?,
+            ),
+            _ => return Err(Error::CannotParseInstructionValue),
+        };
+        let (amount, decimals) = match (info.amount, info.token_amount) {
+            (Some(amount), _) => (parse_number(&amount)?, None),
+            (None, Some(token_amount)) => (
+                parse_number(&token_amount.amount)?,
+                Some(token_amount.decimals),
+            ),
+            (None, None) => return Err(Error::AmountIsRequiredFromInfoValue),
+        };
+        Ok(Instruction::SplToken(InstructionDetail {
+            source,
+            destination,
+            source_owner: None,
+            destination_owner: None,
+            amount,
+            operation,
+            mint,
+            decimals,
+        }))
     } else {
         Err(Error::UnknownProgramId)
     }
@@ -170,15 +473,22 @@ mod tests {
     use solana_transaction_status::UiTransactionEncoding;
 
     use super::*;
+    use crate::solana::{default_fixture_dir, make_fixture_rpc_client, DEFAULT_LOCAL_ENDPOINT};
+
+    fn make_rpc_client() -> RpcClient {
+        make_fixture_rpc_client(
+            DEFAULT_LOCAL_ENDPOINT,
+            &default_fixture_dir(),
+            CommitmentConfig::confirmed(),
+        )
+    }
 
-    const DEFAULT_LOCAL_ENDPOINT: &str = "https://api.devnet.solana.com";
     const TEST_SIGNATURE_TPL_TOKEN: &str =
         "25A1pSwLHvagx8FD3oyAGot1Kfp9keqFhdfGgDZq4s9xjkPc4h5R3P6ikf5ookcsKuZEJDcFShsa3JdgVXYbmgRx";
 
     #[test]
     fn test_parse_tpl_token_instruction() {
-        let rpc_client =
-            RpcClient::new_with_commitment(DEFAULT_LOCAL_ENDPOINT, CommitmentConfig::confirmed());
+        let rpc_client = make_rpc_client();
         let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
         let transaction = rpc_client
             .get_transaction(&signature, UiTransactionEncoding::JsonParsed)
@@ -204,13 +514,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_token_variants() {
+        let (mint, account, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let token_instruction = |parsed: serde_json::Value| ParsedInstruction {
+            program: "spl-token".to_owned(),
+            program_id: spl_token::id().to_string(),
+            parsed,
+            stack_height: None,
+        };
+        let token_amount = serde_json::json!({
+            "amount": "1500",
+            "decimals": 8,
+            "uiAmount": 0.000015,
+            "uiAmountString": "0.000015",
+        });
+        let parse = |parsed| match parse_instruction(&token_instruction(parsed)).unwrap() {
+            Instruction::SplToken(detail) => detail,
+            _ => panic!("not a token instruction"),
+        };
+
+        let detail = parse(serde_json::json!({
+            "info": {
+                "source": account.to_string(),
+                "destination": other.to_string(),
+                "mint": mint.to_string(),
+                "authority": other.to_string(),
+                "tokenAmount": token_amount,
+            },
+            "type": "transferChecked",
+        }));
+        assert_eq!((detail.source, detail.destination), (account, other));
+        assert_eq!(detail.operation, TokenOperation::Transfer);
+        assert_eq!(
+            (detail.amount, detail.mint, detail.decimals),
+            (1500, Some(mint), Some(8))
+        );
+
+        let detail = parse(serde_json::json!({
+            "info": {
+                "mint": mint.to_string(),
+                "account": account.to_string(),
+                "mintAuthority": other.to_string(),
+                "amount": "700",
+            },
+            "type": "mintTo",
+        }));
+        assert_eq!((detail.source, detail.destination), (mint, account));
+        assert_eq!(detail.operation, TokenOperation::MintTo);
+        assert_eq!(
+            (detail.amount, detail.mint, detail.decimals),
+            (700, Some(mint), None)
+        );
+
+        let detail = parse(serde_json::json!({
+            "info": {
+                "account": account.to_string(),
+                "mint": mint.to_string(),
+                "authority": other.to_string(),
+                "tokenAmount": token_amount,
+            },
+            "type": "burnChecked",
+        }));
+        assert_eq!((detail.source, detail.destination), (account, mint));
+        assert_eq!(detail.operation, TokenOperation::Burn);
+        assert_eq!(detail.amount, 1500);
+
+        // the other token instructions move nothing
+        let approve = token_instruction(serde_json::json!({
+            "info": {"source": account.to_string(), "delegate": other.to_string(), "amount": "1"},
+            "type": "approve",
+        }));
+        assert!(parse_instruction(&approve).is_err());
+        let no_amount = token_instruction(serde_json::json!({
+            "info": {"mint": mint.to_string(), "account": account.to_string()},
+            "type": "mintTo",
+        }));
+        assert!(matches!(
+            parse_instruction(&no_amount),
+            Err(Error::AmountIsRequiredFromInfoValue)
+        ));
+    }
+
     const TEST_SIGNATURE_SYSTEM: &str =
         "4btfTfrW1DSfM9khK25sG53gmczBhPB3bgTAfdCnvyhoiwYju8k5325HhqjmrHfJxuErez4NeyWH5CARsFuAaKpd";
 
     #[test]
     fn test_parse_system_instruction() {
-        let rpc_client =
-            RpcClient::new_with_commitment(DEFAULT_LOCAL_ENDPOINT, CommitmentConfig::confirmed());
+        let rpc_client = make_rpc_client();
         let signature = Signature::from_str(TEST_SIGNATURE_SYSTEM).unwrap();
         let transaction = rpc_client
             .get_transaction(&signature, UiTransactionEncoding::JsonParsed)
@@ -231,8 +626,205 @@ mod tests {
                 "dWC1R5jgKfjH79qv4jANoL1Q6FcKGQLYGzRAbqYoqtc"
             );
             assert_eq!(detail.amount, 30000000);
+            assert_eq!(detail.source_owner, Some(detail.source));
+            assert_eq!(detail.destination_owner, Some(detail.destination));
         } else {
             assert!(false);
         }
     }
+
+    #[test]
+    fn test_parse_memo_and_error() {
+        let fixture =
+            default_fixture_dir().join(format!("getTransaction_{}.json", TEST_SIGNATURE_TPL_TOKEN));
+        let mut value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+        let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
+
+        let transaction: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(value.clone()).unwrap();
+        let parsed = TransactionAnalyzer::new(&transaction)
+            .parse(signature, 1727861384)
+            .unwrap();
+        assert_eq!(parsed.slot, 333804871);
+        assert_eq!(parsed.fee, 5000);
+        assert_eq!(parsed.error, None);
+        assert_eq!(parsed.memo, None);
+
+        // the transaction failed and carries 2 memos
+        let memo = |text: &str| {
+            serde_json::json!({
+                "parsed": text,
+                "program": "spl-memo",
+                "programId": "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+                "stackHeight": null,
+            })
+        };
+        let instructions = value["transaction"]["message"]["instructions"]
+            .as_array_mut()
+            .unwrap();
+        instructions.insert(0, memo("order 42"));
+        instructions.push(memo("thanks"));
+        let err = serde_json::json!({"InstructionError": [1, {"Custom": 1}]});
+        value["meta"]["err"] = err.clone();
+        value["meta"]["status"] = serde_json::json!({ "Err": err });
+        let transaction: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(value).unwrap();
+        let parsed = TransactionAnalyzer::new(&transaction)
+            .parse(signature, 1727861384)
+            .unwrap();
+        assert_eq!(
+            parsed.error.as_deref(),
+            Some("Error processing Instruction 1: custom program error: 0x1")
+        );
+        assert_eq!(parsed.memo.as_deref(), Some("order 42; thanks"));
+        assert_eq!(parsed.instructions.len(), 1);
+    }
+
+    #[test]
+    fn test_analyzer_policy() {
+        const ATA_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+        const OTHER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+        let fixture =
+            default_fixture_dir().join(format!("getTransaction_{}.json", TEST_SIGNATURE_TPL_TOKEN));
+        let mut value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+        let instructions = value["transaction"]["message"]["instructions"]
+            .as_array_mut()
+            .unwrap();
+        instructions.insert(
+            0,
+            serde_json::json!({
+                "parsed": {
+                    "info": {"mint": "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw"},
+                    "type": "create",
+                },
+                "program": "spl-associated-token-account",
+                "programId": ATA_PROGRAM_ID,
+                "stackHeight": null,
+            }),
+        );
+        instructions.push(serde_json::json!({
+            "accounts": [],
+            "data": "3Bxs4h24hBtQy9rw",
+            "programId": OTHER_PROGRAM_ID,
+            "stackHeight": null,
+        }));
+        let transaction: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(value).unwrap();
+        let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
+        let ata_program_id = Pubkey::from_str(ATA_PROGRAM_ID).unwrap();
+        let other_program_id = Pubkey::from_str(OTHER_PROGRAM_ID).unwrap();
+
+        // skipped and tagged by default
+        let parsed = TransactionAnalyzer::new(&transaction)
+            .parse(signature, 0)
+            .unwrap();
+        assert_eq!(parsed.instructions.len(), 1);
+        assert_eq!(
+            parsed.skipped_programs,
+            vec![ata_program_id, other_program_id]
+        );
+
+        let mut policy = AnalyzerPolicy::default();
+        policy.allow(ATA_PROGRAM_ID).unwrap();
+        policy
+            .allow(&format!("{OTHER_PROGRAM_ID}=jupiter"))
+            .unwrap();
+        assert!(policy.allow("invalid=label").is_err());
+        assert!(policy.allow(&format!("{OTHER_PROGRAM_ID}=")).is_err());
+        let parsed = TransactionAnalyzer::with_policy(&transaction, &policy)
+            .parse(signature, 0)
+            .unwrap();
+        assert!(parsed.skipped_programs.is_empty());
+        let others: Vec<_> = parsed
+            .instructions
+            .iter()
+            .filter_map(|ix| match ix {
+                Instruction::Other(other) => Some(other.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            others,
+            vec![
+                OtherInstruction {
+                    program_id: ata_program_id,
+                    label: "spl-associated-token-account".to_owned(),
+                    kind: Some("create".to_owned()),
+                },
+                OtherInstruction {
+                    program_id: other_program_id,
+                    label: "jupiter".to_owned(),
+                    kind: None,
+                },
+            ]
+        );
+        // the transfer is still counted
+        let source = Pubkey::from_str("3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L").unwrap();
+        assert_eq!(parsed.effect_on(&source).unwrap().token_change, -1000);
+
+        let policy = AnalyzerPolicy {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            TransactionAnalyzer::with_policy(&transaction, &policy).parse(signature, 0),
+            Err(Error::UnknownProgramId)
+        ));
+    }
+
+    #[test]
+    fn test_effect_on_address() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (alice_token, bob_token) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let transfer = |source, destination, amount| InstructionDetail {
+            source,
+            destination,
+            source_owner: None,
+            destination_owner: None,
+            amount,
+            operation: TokenOperation::Transfer,
+            mint: None,
+            decimals: None,
+        };
+        let mut transaction = Transaction {
+            signature: Signature::default(),
+            slot: 0,
+            fee: 5000,
+            timestamp: 0,
+            error: None,
+            memo: None,
+            skipped_programs: vec![],
+            instructions: vec![
+                Instruction::SplToken(transfer(alice_token, bob_token, 1000)),
+                Instruction::Solana(InstructionDetail {
+                    source_owner: Some(bob),
+                    destination_owner: Some(alice),
+                    ..transfer(bob, alice, 30)
+                }),
+            ],
+        };
+        transaction
+            .resolve_owners(|account| -> Result<Option<Pubkey>, Error> {
+                Ok(if *account == alice_token {
+                    Some(alice)
+                } else {
+                    None
+                })
+            })
+            .unwrap();
+
+        let effect = transaction.effect_on(&alice).unwrap();
+        assert_eq!(effect.direction, Direction::SelfTransfer);
+        assert_eq!((effect.token_change, effect.sol_change), (-1000, 30));
+        // the owner of the token account of bob is unknown
+        let effect = transaction.effect_on(&bob).unwrap();
+        assert_eq!(effect.direction, Direction::Out);
+        assert_eq!((effect.token_change, effect.sol_change), (0, -30));
+        let effect = transaction.effect_on(&bob_token).unwrap();
+        assert_eq!(effect.direction, Direction::In);
+        assert_eq!(effect.token_change, 1000);
+        assert!(transaction.effect_on(&Pubkey::new_unique()).is_none());
+    }
 }
@@ -0,0 +1,419 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+
+/// The queries are shed at once when so many of them wait already
+pub const DEFAULT_MAX_QUEUED_QUERIES: usize = 64;
+/// No permit is made for so long after the node answers a call with 429
+pub const THROTTLE_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// The priority of the RPC calls of a subsystem, the critical calls are made before the queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RpcPriority {
+    /// The REST queries (the balances, the history and the transactions), they're shed when the
+    /// budget runs low
+    Query,
+    /// The bridge itself (the verification of the withdrawals and the confirmations of the
+    /// transfers), they wait for the permits however long it takes
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcBudgetConfig {
+    /// The calls can be made to the node per second
+    pub calls_per_sec: u32,
+    /// The calls can be made at once after the budget is idle
+    pub burst: u32,
+    /// The permits only the critical calls take, the queries wait above them
+    pub reserve: u32,
+    /// A query is shed if it cannot be made in so long
+    pub max_query_wait: Duration,
+    pub max_queued_queries: usize,
+}
+
+impl RpcBudgetConfig {
+    /// One second of calls can be made at once, and `reserve_percent` percent of them are kept
+    /// for the critical calls
+    pub fn new(calls_per_sec: u32, reserve_percent: u32, max_query_wait: Duration) -> Self {
+        let calls_per_sec = calls_per_sec.max(1);
+        RpcBudgetConfig {
+            calls_per_sec,
+            burst: calls_per_sec,
+            reserve: calls_per_sec * reserve_percent.min(100) / 100,
+            max_query_wait,
+            max_queued_queries: DEFAULT_MAX_QUEUED_QUERIES,
+        }
+    }
+}
+
+/// The counters of a budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RpcBudgetStats {
+    pub critical_calls: u64,
+    pub queries: u64,
+    pub shed_queries: u64,
+    /// The times the node answers 429
+    pub throttled: u64,
+}
+
+/// What a call does next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admission {
+    Granted,
+    Wait(Duration),
+    Shed,
+}
+
+struct BudgetState {
+    permits: f64,
+    refilled_at: Instant,
+    /// No permit is made before it after the node throttles the calls
+    paused_until: Option<Instant>,
+    critical_waiting: usize,
+    queries_waiting: usize,
+}
+
+impl BudgetState {
+    fn refill(&mut self, config: &RpcBudgetConfig, now: Instant) {
+        let from = match self.paused_until {
+            Some(until) if until > now => {
+                self.refilled_at = now;
+                return;
+            }
+            Some(until) => {
+                self.paused_until = None;
+                until.max(self.refilled_at)
+            }
+            None => self.refilled_at,
+        };
+        let elapsed = now.saturating_duration_since(from).as_secs_f64();
+        self.permits =
+            (self.permits + elapsed * config.calls_per_sec as f64).min(config.burst as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// The RPC calls to a node shared by the subsystems, the calls are made at most
+/// `calls_per_sec` per second by a token bucket
+///
+/// The critical calls take any permit and wait for the next one, the queries only take the
+/// permits above `reserve` and yield to the critical calls waiting, so the bridge keeps making
+/// its calls when the REST queries use up the budget. A query is shed when it waits too long or
+/// too many queries wait already. The node answering 429 drains the permits and pauses the
+/// budget for `THROTTLE_COOLDOWN`.
+pub struct RpcBudget {
+    config: RpcBudgetConfig,
+    state: Mutex<BudgetState>,
+    critical_calls: AtomicU64,
+    queries: AtomicU64,
+    shed_queries: AtomicU64,
+    throttled: AtomicU64,
+}
+
+impl RpcBudget {
+    pub fn new(config: RpcBudgetConfig) -> RpcBudget {
+        RpcBudget {
+            config,
+            state: Mutex::new(BudgetState {
+                permits: config.burst as f64,
+                refilled_at: Instant::now(),
+                paused_until: None,
+                critical_waiting: 0,
+                queries_waiting: 0,
+            }),
+            critical_calls: AtomicU64::new(0),
+            queries: AtomicU64::new(0),
+            shed_queries: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &RpcBudgetConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> RpcBudgetStats {
+        RpcBudgetStats {
+            critical_calls: self.critical_calls.load(Ordering::Relaxed),
+            queries: self.queries.load(Ordering::Relaxed),
+            shed_queries: self.shed_queries.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wait for a permit of a call, false if the call is shed
+    pub async fn acquire(&self, priority: RpcPriority) -> bool {
+        let started = Instant::now();
+        // counted as waiting until the call is granted or shed, or the caller gives up
+        let mut queued = None;
+        loop {
+            match self.admit(priority, started, queued.is_some(), Instant::now()) {
+                Admission::Granted => {
+                    let counter = match priority {
+                        RpcPriority::Critical => &self.critical_calls,
+                        RpcPriority::Query => &self.queries,
+                    };
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                Admission::Shed => {
+                    self.shed_queries.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                Admission::Wait(wait) => {
+                    queued.get_or_insert_with(|| Queued {
+                        budget: self,
+                        priority,
+                    });
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    /// The node throttles the calls, no permit is made until `THROTTLE_COOLDOWN` passes
+    pub fn throttle(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.permits = 0.0;
+        state.paused_until = Some(now + THROTTLE_COOLDOWN);
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "the Solana node throttles the calls, pausing them for {:?}",
+            THROTTLE_COOLDOWN
+        );
+    }
+
+    /// Take a permit for the call started at `started`, or tell how long it waits for the next
+    /// try. The call not `queued` yet is counted as waiting when it's told to wait
+    fn admit(
+        &self,
+        priority: RpcPriority,
+        started: Instant,
+        queued: bool,
+        now: Instant,
+    ) -> Admission {
+        let mut state = self.state.lock().unwrap();
+        state.refill(&self.config, now);
+        let floor = match priority {
+            RpcPriority::Critical => 0.0,
+            RpcPriority::Query => self.config.reserve as f64,
+        };
+        let yielding = priority == RpcPriority::Query && state.critical_waiting > 0;
+        if !yielding && state.permits >= floor + 1.0 {
+            state.permits -= 1.0;
+            return Admission::Granted;
+        }
+        let interval = Duration::from_nanos(1_000_000_000 / self.config.calls_per_sec as u64);
+        let mut wait = if yielding {
+            interval
+        } else {
+            interval * (floor + 1.0 - state.permits).ceil() as u32
+        };
+        if let Some(until) = state.paused_until {
+            wait += until.saturating_duration_since(now);
+        }
+        if priority == RpcPriority::Query
+            && (now.saturating_duration_since(started) + wait > self.config.max_query_wait
+                || (!queued && state.queries_waiting >= self.config.max_queued_queries))
+        {
+            return Admission::Shed;
+        }
+        if !queued {
+            match priority {
+                RpcPriority::Critical => state.critical_waiting += 1,
+                RpcPriority::Query => state.queries_waiting += 1,
+            }
+        }
+        Admission::Wait(wait)
+    }
+}
+
+/// A call waiting for a permit, it's not counted as waiting after it's dropped
+struct Queued<'a> {
+    budget: &'a RpcBudget,
+    priority: RpcPriority,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        let mut state = self.budget.state.lock().unwrap();
+        match self.priority {
+            RpcPriority::Critical => state.critical_waiting -= 1,
+            RpcPriority::Query => state.queries_waiting -= 1,
+        }
+    }
+}
+
+/// Whether the node refuses the call for the rate limit (HTTP 429)
+fn is_rate_limited(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Reqwest(e) => e.status().is_some_and(|status| status.as_u16() == 429),
+        _ => false,
+    }
+}
+
+/// A `RpcSender` makes the calls within a budget, the senders of the subsystems share the
+/// budget with their own priorities
+pub struct BudgetSender<S> {
+    inner: S,
+    budget: Arc<RpcBudget>,
+    priority: RpcPriority,
+}
+
+impl<S: RpcSender> BudgetSender<S> {
+    pub fn new(inner: S, budget: Arc<RpcBudget>, priority: RpcPriority) -> BudgetSender<S> {
+        BudgetSender {
+            inner,
+            budget,
+            priority,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for BudgetSender<S> {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        if !self.budget.acquire(self.priority).await {
+            return Err(
+                ClientErrorKind::Custom(format!("{} is shed by the RPC budget", request)).into(),
+            );
+        }
+        let res = self.inner.send(request, params).await;
+        if res.as_ref().is_err_and(is_rate_limited) {
+            self.budget.throttle(Instant::now());
+        }
+        res
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solana::{default_fixture_dir, FixtureSender};
+
+    /// 10 calls per second, 4 of them are kept for the critical calls
+    fn make_budget() -> RpcBudget {
+        RpcBudget::new(RpcBudgetConfig::new(10, 40, Duration::from_millis(500)))
+    }
+
+    #[test]
+    fn test_queries_keep_the_reserve() {
+        let budget = make_budget();
+        let now = Instant::now();
+        for _ in 0..6 {
+            assert_eq!(
+                budget.admit(RpcPriority::Query, now, false, now),
+                Admission::Granted
+            );
+        }
+        // the query waits for the permit above the reserve, the critical call takes the reserve
+        assert_eq!(
+            budget.admit(RpcPriority::Query, now, false, now),
+            Admission::Wait(Duration::from_millis(100))
+        );
+        for _ in 0..4 {
+            assert_eq!(
+                budget.admit(RpcPriority::Critical, now, false, now),
+                Admission::Granted
+            );
+        }
+        // the waiting critical call is served first, the query is shed after waiting too long
+        assert_eq!(
+            budget.admit(RpcPriority::Critical, now, false, now),
+            Admission::Wait(Duration::from_millis(100))
+        );
+        let later = now + Duration::from_millis(100);
+        assert_eq!(
+            budget.admit(RpcPriority::Query, now, true, later),
+            Admission::Wait(Duration::from_millis(100))
+        );
+        assert_eq!(
+            budget.admit(RpcPriority::Critical, now, true, later),
+            Admission::Granted
+        );
+        let later = now + Duration::from_millis(450);
+        assert_eq!(
+            budget.admit(RpcPriority::Query, now, true, later),
+            Admission::Shed
+        );
+    }
+
+    #[test]
+    fn test_queries_are_shed_when_queued_too_many() {
+        let budget = RpcBudget::new(RpcBudgetConfig {
+            max_queued_queries: 1,
+            ..RpcBudgetConfig::new(1, 0, Duration::from_secs(10))
+        });
+        let now = Instant::now();
+        assert_eq!(
+            budget.admit(RpcPriority::Query, now, false, now),
+            Admission::Granted
+        );
+        assert!(matches!(
+            budget.admit(RpcPriority::Query, now, false, now),
+            Admission::Wait(_)
+        ));
+        assert_eq!(
+            budget.admit(RpcPriority::Query, now, false, now),
+            Admission::Shed
+        );
+    }
+
+    #[test]
+    fn test_throttle_pauses_the_budget() {
+        let budget = make_budget();
+        let now = Instant::now();
+        budget.throttle(now);
+        assert_eq!(
+            budget.admit(RpcPriority::Critical, now, false, now),
+            Admission::Wait(THROTTLE_COOLDOWN + Duration::from_millis(100))
+        );
+        // no permit is made while it's paused
+        let later = now + THROTTLE_COOLDOWN + Duration::from_millis(100);
+        assert_eq!(
+            budget.admit(RpcPriority::Critical, now, true, later),
+            Admission::Granted
+        );
+        assert_eq!(budget.stats().throttled, 1);
+    }
+
+    #[tokio::test]
+    async fn test_budget_sender() {
+        let budget = Arc::new(RpcBudget::new(RpcBudgetConfig::new(1, 0, Duration::ZERO)));
+        let sender = BudgetSender::new(
+            FixtureSender::replay(&default_fixture_dir()),
+            Arc::clone(&budget),
+            RpcPriority::Query,
+        );
+        assert!(sender
+            .send(RpcRequest::GetHealth, Value::Null)
+            .await
+            .is_ok());
+        // the only permit is taken, the next query is shed without reaching the node
+        let e = sender
+            .send(RpcRequest::GetHealth, Value::Null)
+            .await
+            .unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("getHealth is shed by the RPC budget"));
+        let stats = budget.stats();
+        assert_eq!((stats.queries, stats.shed_queries), (1, 1));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use super::AnalyzedTransaction;
+use crate::db;
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// The counters of a cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    pub entries: u64,
+}
+
+/// A map whose entries expire after `ttl`, the expired entries are dropped when they're read
+/// or when a new entry is inserted
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    pub fn new(ttl: Duration) -> TtlCache<K, V> {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < ttl);
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    /// Return the cached value, or call `fetch` and cache its result when it succeeds
+    pub fn get_or_try_insert<E, F>(&self, key: K, fetch: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        // the lock isn't held while fetching, the concurrent misses might fetch the same key
+        let value = fetch()?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(key).is_some() {
+            self.invalidations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            entries: entries.len() as u64,
+        }
+    }
+}
+
+/// The stats of the caches of `SolanaClient`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SolanaCacheStats {
+    pub balances: CacheStats,
+    pub token_balances: CacheStats,
+    pub token_supply: CacheStats,
+    pub transactions: CacheStats,
+}
+
+/// The data those are requested frequently by the REST endpoints, the balances are invalidated
+/// by the transfers made by the bridge itself
+pub(crate) struct SolanaCache {
+    pub balances: TtlCache<Pubkey, u64>,
+    /// The balances of the bridged token, keyed by the owner (not the associated account)
+    pub token_balances: TtlCache<Pubkey, u64>,
+    pub token_supply: TtlCache<Pubkey, u64>,
+    /// The analyzed transactions never change once they're confirmed, but they're still
+    /// expired to keep the cache small
+    pub transactions: TtlCache<Signature, AnalyzedTransaction>,
+}
+
+impl SolanaCache {
+    pub fn new(ttl: Duration) -> SolanaCache {
+        SolanaCache {
+            balances: TtlCache::new(ttl),
+            token_balances: TtlCache::new(ttl),
+            token_supply: TtlCache::new(ttl),
+            transactions: TtlCache::new(ttl),
+        }
+    }
+
+    pub fn stats(&self) -> SolanaCacheStats {
+        SolanaCacheStats {
+            balances: self.balances.stats(),
+            token_balances: self.token_balances.stats(),
+            token_supply: self.token_supply.stats(),
+            transactions: self.transactions.stats(),
+        }
+    }
+}
+
+/// The persistent map from the token accounts to the wallets own them, it's consulted before
+/// the owner of a token account is fetched from the RPC node
+///
+/// The owner of a token account is kept once it's resolved, it's only changed by closing and
+/// re-creating the account, which the associated token accounts used by the bridge never do
+pub trait TokenOwnerStore: Send + Sync {
+    fn load_owner(&self, account: &Pubkey) -> Option<Pubkey>;
+    fn save_owner(&self, account: &Pubkey, owner: &Pubkey);
+}
+
+/// The owners are saved to table `ata_owners`, a failure of the database is logged and the
+/// owner is fetched again
+impl TokenOwnerStore for db::Conn {
+    fn load_owner(&self, account: &Pubkey) -> Option<Pubkey> {
+        match self.query_ata_owner(&account.to_string()) {
+            Ok(owner) => owner.and_then(|owner| owner.parse().ok()),
+            Err(e) => {
+                warn!("cannot load the owner of token account {account}, reason: {e}");
+                None
+            }
+        }
+    }
+
+    fn save_owner(&self, account: &Pubkey, owner: &Pubkey) {
+        if let Err(e) = self.add_ata_owner(&account.to_string(), &owner.to_string()) {
+            warn!("cannot save the owner of token account {account}, reason: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_cache_expires_and_invalidates() {
+        let cache = TtlCache::new(Duration::from_millis(50));
+        assert_eq!(cache.get(&1), None);
+        cache.insert(1, "one");
+        assert_eq!(cache.get(&1), Some("one"));
+
+        cache.invalidate(&1);
+        assert_eq!(cache.get(&1), None);
+        cache.insert(1, "one");
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&1), None);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                invalidations: 1,
+                entries: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_ttl_cache_get_or_try_insert() {
+        let cache = TtlCache::new(DEFAULT_CACHE_TTL);
+        assert_eq!(
+            cache.get_or_try_insert(1, || Err::<u64, _>("offline")),
+            Err("offline")
+        );
+        assert_eq!(cache.get_or_try_insert(1, || Ok::<_, ()>(100)), Ok(100));
+        // the cached value is returned without fetching
+        assert_eq!(cache.get_or_try_insert(1, || Err(())), Ok(100));
+    }
+
+    #[test]
+    fn test_token_owners_saved_to_database() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let (account, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(conn.load_owner(&account), None);
+        conn.save_owner(&account, &owner);
+        assert_eq!(conn.load_owner(&account), Some(owner));
+        // it's shared by the bridges
+        assert_eq!(conn.scoped("other").load_owner(&account), Some(owner));
+    }
+}
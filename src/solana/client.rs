@@ -1,17 +1,119 @@
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::{send_token, AnalyzedInstruction, AnalyzedTransaction, Error, TransactionAnalyzer};
-use solana_client::rpc_client::RpcClient;
+use super::cache::{SolanaCache, SolanaCacheStats, TokenOwnerStore, DEFAULT_CACHE_TTL};
+use super::{
+    check_spl_token, consolidate_token_accounts, get_auxiliary_token_accounts, get_mint,
+    get_token_balance, send_tokens_with_outbox, settle_outgoing, AnalyzedInstruction,
+    AnalyzedTransaction, AnalyzerPolicy, BudgetSender, DeadlineSender, Error, OutgoingTxStore,
+    RpcBudget, RpcPriority, TokenOperation, TransactionAnalyzer,
+};
+use solana_client::{
+    http_sender::HttpSender,
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient, RpcClientConfig},
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signature},
     signer::Signer,
     system_instruction::transfer,
+    system_program,
     transaction::Transaction,
 };
 use solana_transaction_status::UiTransactionEncoding;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+/// The most signatures a call of `getSignaturesForAddress` returns
+pub const SIGNATURES_PAGE_LIMIT: usize = 1000;
+
+/// The SPL Memo program (v2)
+pub const MEMO_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TuiGLvqLc8tA7aFpt5qjA6");
+
+/// Whether a call failing with an error might succeed when it's made again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The node is unreachable, busy or behind, the same call might succeed later
+    Transient,
+    /// The call is refused for what it is (e.g. an invalid recipient or transaction), it never
+    /// succeeds however many times it's made
+    Permanent,
+    /// The client cannot tell, the call is retried, but the syncing gives it up after a few
+    /// retries instead of waiting for the node
+    Unknown,
+    /// The transaction is submitted but it's unknown whether it's confirmed, the call must not
+    /// be made again before the transfer is recovered, see `TokenClient::recover_transfers`
+    InDoubt,
+}
+
+/// A transfer made by a token client with what it costs and where it's confirmed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferReceipt<T> {
+    pub txid: T,
+    /// The fee paid by the sender in the smallest unit of the native coin, e.g. lamports,
+    /// `None` when the confirmed transfer cannot be fetched
+    pub fee: Option<u64>,
+    /// The slot (or the block height) the transfer is confirmed in, `None` when the confirmed
+    /// transfer cannot be fetched
+    pub slot: Option<u64>,
+    /// The token account of a recipient is created by the transfer, the sender pays the rent
+    pub created_ata: bool,
+    /// The logs of the programs the transfer runs, `None` when the confirmed transfer cannot
+    /// be fetched
+    pub raw_logs: Option<Vec<String>>,
+}
+
+/// Where a transfer submitted by a token client ends up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferStatus {
+    /// Submitted, but neither confirmed nor expired yet
+    Pending,
+    Confirmed,
+    /// Confirmed with an error, nothing is transferred
+    Failed,
+    /// Never confirmed and it can't be any more, e.g. its blockhash expired
+    Expired,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl FromStr for TransferStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "confirmed" => Ok(Self::Confirmed),
+            "failed" => Ok(Self::Failed),
+            "expired" => Ok(Self::Expired),
+            _ => Err(format!("unknown transfer status: {s}")),
+        }
+    }
+}
+
+/// A transfer submitted before but not acknowledged, e.g. the process stopped while it was
+/// sent, see `TokenClient::recover_transfers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredTransfer<T, A, M> {
+    pub txid: T,
+    /// It's never `TransferStatus::Pending`, the transfer is settled before it's recovered
+    pub status: TransferStatus,
+    pub recipients: Vec<(A, M)>,
+}
 
 pub trait TokenClient {
     type Error: std::fmt::Display + std::fmt::Debug + Send;
@@ -26,13 +128,27 @@ pub trait TokenClient {
     /// * amount - Total amount the authority needs to send
     ///
     /// Returns:
-    /// * The signature of the new transaction from solana network
+    /// * The receipt of the new transaction from solana network
     /// * Otherwise the transaction cannot be made, check the error
     fn send_token(
         &self,
         recipient_address: &Self::Address,
         amount: Self::Amount,
-    ) -> anyhow::Result<Self::TxID, Self::Error>;
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error>;
+
+    /// # Send spl-token to several target accounts by one transaction
+    /// The same as `send_token`, but all the recipients are paid by the same transaction, so
+    /// either all of them get the tokens or none does
+    ///
+    /// Arguments:
+    /// * recipients - The target accounts with the amounts they get
+    ///
+    /// Returns:
+    /// * The receipt of the transaction pays all the recipients
+    fn send_tokens(
+        &self,
+        recipients: &[(Self::Address, Self::Amount)],
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error>;
 
     /// # Verify a transaction
     /// After the authority receives a withdraw request from DePINC chain, we need
@@ -46,6 +162,100 @@ pub trait TokenClient {
     /// * The amount needs to be transferred on DePINC chain
     /// * Otherwise, the transaction from solana is invalid or it's not a related spl-token tx
     fn verify(&self, signature: &Signature, owner: &Self::Address) -> Result<u64, Self::Error>;
+
+    /// # Verify a transaction is made by the sender
+    /// The same as `verify`, but only the tokens transferred from `sender` (or the token
+    /// accounts it owns) are counted, the withdrawals requested off-chain are bound to the
+    /// sender by its signature
+    fn verify_from(
+        &self,
+        signature: &Signature,
+        sender: &Self::Address,
+        owner: &Self::Address,
+    ) -> Result<u64, Self::Error>;
+
+    /// # The decimals of the bridged token
+    /// The amounts of `send_token` and `verify` are in the base units of the token, they're
+    /// converted from and to DePC with the decimals of the mint
+    ///
+    /// Returns:
+    /// * The decimals of the mint queried from the chain
+    fn mint_decimals(&self) -> Result<u8, Self::Error>;
+
+    /// # The addresses a deposit must never be sent to
+    /// The tokens sent to them can never be moved by anyone (the mint and the programs) or
+    /// they're mixed up with the funds of the bridge (the authority and its token account)
+    ///
+    /// Returns:
+    /// * The addresses with the descriptions, e.g. "the mint"
+    fn reserved_addresses(&self) -> Vec<(Self::Address, &'static str)> {
+        vec![]
+    }
+
+    /// # Classify an error of the calls
+    /// The bridge retries the calls failed with the transient errors until they succeed, and
+    /// gives up the ones failed with the permanent errors at once, e.g. dead-letters the deposit
+    ///
+    /// Returns:
+    /// * `ErrorKind::Unknown` unless the client tells
+    fn error_kind(_error: &Self::Error) -> ErrorKind {
+        ErrorKind::Unknown
+    }
+
+    /// # Recover the transfers sent before
+    /// The transfers submitted but not acknowledged yet are settled, i.e. waited for until
+    /// they're confirmed or expired, so the caller knows for sure which of them are made.
+    /// Call it before any transfer is sent, e.g. on startup
+    ///
+    /// Returns:
+    /// * The settled transfers, they're returned again until they're acknowledged
+    /// * Otherwise some transfer cannot be settled, e.g. the node is unreachable
+    #[allow(clippy::type_complexity)]
+    fn recover_transfers(
+        &self,
+    ) -> Result<Vec<RecoveredTransfer<Self::TxID, Self::Address, Self::Amount>>, Self::Error> {
+        Ok(vec![])
+    }
+
+    /// # Acknowledge a transfer
+    /// The outcome of the transfer is recorded by the caller, it's never recovered again
+    fn acknowledge_transfer(&self, _txid: &Self::TxID) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// # The tokens stranded in the auxiliary accounts
+    /// The accounts controlled by the authority besides its token account hold the tokens left
+    /// by the operations failed halfway, they're never sent by the bridge
+    ///
+    /// Returns:
+    /// * The auxiliary accounts with their balances, the empty ones included
+    /// * Nothing unless the client tells
+    fn stranded_balances(&self) -> Result<Vec<(Self::Address, Self::Amount)>, Self::Error> {
+        Ok(vec![])
+    }
+
+    /// # Consolidate the auxiliary accounts
+    /// Transfer the balances of the auxiliary accounts (returned by `stranded_balances`) back
+    /// to the token account of the authority by one transaction
+    ///
+    /// Arguments:
+    /// * accounts - The auxiliary accounts with the amounts to transfer from them
+    ///
+    /// Returns:
+    /// * The receipt of the transaction, the authority pays its fee
+    fn consolidate(
+        &self,
+        accounts: &[(Self::Address, Self::Amount)],
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error>;
+}
+
+/// A slot of Solana with the time it's produced, the samples correlate the slots with the
+/// timestamps of the other chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotSample {
+    pub slot: u64,
+    pub epoch: u64,
+    pub block_time: u64,
 }
 
 #[derive(Clone)]
@@ -53,6 +263,13 @@ pub struct SolanaClient {
     rpc_client: Arc<RpcClient>,
     authority_key: Arc<Keypair>,
     mint_pubkey: Pubkey,
+    cache: Arc<SolanaCache>,
+    owner_store: Option<Arc<dyn TokenOwnerStore>>,
+    outgoing_store: Option<Arc<dyn OutgoingTxStore>>,
+    fetch_concurrency: usize,
+    analyzer_policy: Arc<AnalyzerPolicy>,
+    rpc_timeout: Option<Duration>,
+    rpc_budget: Option<(Arc<RpcBudget>, RpcPriority)>,
 }
 
 impl SolanaClient {
@@ -62,14 +279,112 @@ impl SolanaClient {
         authority_key: Keypair,
         commitment_config: CommitmentConfig,
     ) -> SolanaClient {
-        let rpc_client = RpcClient::new_with_commitment(endpoint, commitment_config);
+        let rpc_client = RpcClient::new_sender(
+            DeadlineSender::new(HttpSender::new(endpoint)),
+            RpcClientConfig::with_commitment(commitment_config),
+        );
+        SolanaClient::new_with_rpc_client(rpc_client, mint_pubkey, authority_key)
+    }
+
+    pub fn new_with_rpc_client(
+        rpc_client: RpcClient,
+        mint_pubkey: Pubkey,
+        authority_key: Keypair,
+    ) -> SolanaClient {
         SolanaClient {
             rpc_client: Arc::new(rpc_client),
             authority_key: Arc::new(authority_key),
             mint_pubkey,
+            cache: Arc::new(SolanaCache::new(DEFAULT_CACHE_TTL)),
+            owner_store: None,
+            outgoing_store: None,
+            fetch_concurrency: 1,
+            analyzer_policy: Arc::new(AnalyzerPolicy::default()),
+            rpc_timeout: None,
+            rpc_budget: None,
         }
     }
 
+    /// The number of the threads fetch the transactions of an address concurrently
+    pub fn set_fetch_concurrency(mut self, fetch_concurrency: usize) -> Self {
+        self.fetch_concurrency = fetch_concurrency.max(1);
+        self
+    }
+
+    /// Replace the caches with the empty ones those expire the entries after `ttl`, the clones
+    /// made before keep the old caches
+    pub fn set_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = Arc::new(SolanaCache::new(ttl));
+        self
+    }
+
+    /// Replace the RPC client with the one to the same node whose calls time out after
+    /// `timeout`, the clones made before keep the old one
+    ///
+    /// The RPC client is made again from the url, the mock and the fault injecting senders are
+    /// not kept.
+    pub fn set_rpc_timeout(mut self, timeout: Duration) -> Self {
+        self.rpc_timeout = Some(timeout);
+        self.rpc_client = Arc::new(self.make_rpc_client());
+        self
+    }
+
+    /// Make the calls within `budget` at `priority`, the clients of the subsystems share the
+    /// budget of the node. The RPC client is made again like `set_rpc_timeout`
+    pub fn set_rpc_budget(mut self, budget: Arc<RpcBudget>, priority: RpcPriority) -> Self {
+        self.rpc_budget = Some((budget, priority));
+        self.rpc_client = Arc::new(self.make_rpc_client());
+        self
+    }
+
+    /// A RPC client to the node of the current one with the timeout and the budget set, the
+    /// calls are cut off at the deadline including the wait for the budget
+    fn make_rpc_client(&self) -> RpcClient {
+        let url = self.rpc_client.url();
+        let sender = match self.rpc_timeout {
+            Some(timeout) => HttpSender::new_with_timeout(url, timeout),
+            None => HttpSender::new(url),
+        };
+        let config = RpcClientConfig::with_commitment(self.rpc_client.commitment());
+        match self.rpc_budget.as_ref() {
+            Some((budget, priority)) => RpcClient::new_sender(
+                DeadlineSender::new(BudgetSender::new(sender, Arc::clone(budget), *priority)),
+                config,
+            ),
+            None => RpcClient::new_sender(DeadlineSender::new(sender), config),
+        }
+    }
+
+    /// The owners of the token accounts are loaded from the store and the resolved ones are
+    /// saved to it, so a token account is fetched once
+    pub fn set_owner_store(mut self, owner_store: Arc<dyn TokenOwnerStore>) -> Self {
+        self.owner_store = Some(owner_store);
+        self
+    }
+
+    /// The transfers are saved to the store before they're submitted, so the ones sent when the
+    /// process stopped are recovered after it's restarted, see `TokenClient::recover_transfers`
+    pub fn set_outgoing_store(mut self, outgoing_store: Arc<dyn OutgoingTxStore>) -> Self {
+        self.outgoing_store = Some(outgoing_store);
+        self
+    }
+
+    /// The transactions are analyzed by `policy`, set it before any transaction is cached
+    pub fn set_analyzer_policy(mut self, policy: AnalyzerPolicy) -> Self {
+        self.analyzer_policy = Arc::new(policy);
+        self
+    }
+
+    pub fn cache_stats(&self) -> SolanaCacheStats {
+        self.cache.stats()
+    }
+
+    /// Drop the cached SOL and token balances of the address, they're changed by a transfer
+    pub fn invalidate_balances(&self, address: &Pubkey) {
+        self.cache.balances.invalidate(address);
+        self.cache.token_balances.invalidate(address);
+    }
+
     pub fn send_solana(&self, target_pubkey: &Pubkey, amount: u64) -> Result<Signature, Error> {
         let instruction = transfer(&self.authority_key.pubkey(), target_pubkey, amount);
         let mut transaction =
@@ -87,13 +402,112 @@ impl SolanaClient {
             return Err(Error::CannotSendTransaction);
         }
         let signature = res.unwrap();
+        self.invalidate_balances(&self.authority_key.pubkey());
+        self.invalidate_balances(target_pubkey);
         Ok(signature)
     }
 
+    /// Record `memo` on chain with a memo signed by the authority, e.g. the deposit roots
+    pub fn send_memo(&self, memo: &str) -> Result<Signature, Error> {
+        let authority_pubkey = self.authority_key.pubkey();
+        let instruction = Instruction::new_with_bytes(
+            MEMO_PROGRAM_ID,
+            memo.as_bytes(),
+            vec![AccountMeta::new_readonly(authority_pubkey, true)],
+        );
+        let recent_blockhash = self
+            .rpc_client
+            .get_latest_blockhash()
+            .map_err(|_| Error::CannotGetLatestBlockHash)?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&authority_pubkey),
+            &[&self.authority_key],
+            recent_blockhash,
+        );
+        self.rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|_| Error::CannotSendTransaction)
+    }
+
     pub fn get_balance(&self, address: &Pubkey) -> Result<u64, Error> {
+        self.cache.balances.get_or_try_insert(*address, || {
+            self.rpc_client
+                .get_balance(address)
+                .map_err(|_| Error::CannotGetAccountBalance(address.to_string()))
+        })
+    }
+
+    /// The balance of the bridged token held by the associated token account of `owner`
+    pub fn get_token_balance(&self, owner: &Pubkey) -> Result<u64, Error> {
+        self.cache.token_balances.get_or_try_insert(*owner, || {
+            get_token_balance(&self.rpc_client, &self.mint_pubkey, owner)
+        })
+    }
+
+    pub fn get_token_supply(&self) -> Result<u64, Error> {
+        self.cache
+            .token_supply
+            .get_or_try_insert(self.mint_pubkey, || {
+                check_spl_token(&self.rpc_client, &self.mint_pubkey)
+            })
+    }
+
+    pub fn mint_pubkey(&self) -> Pubkey {
+        self.mint_pubkey
+    }
+
+    /// The authority mints the token and sends it from its associated token account
+    pub fn authority_pubkey(&self) -> Pubkey {
+        self.authority_key.pubkey()
+    }
+
+    /// The version of the solana-core the RPC node runs
+    pub fn get_version(&self) -> Result<String, Error> {
+        self.rpc_client
+            .get_version()
+            .map(|version| version.solana_core)
+            .map_err(|_| Error::CannotGetNodeVersion)
+    }
+
+    /// The node is unhealthy when it falls behind the cluster
+    pub fn check_health(&self) -> Result<(), Error> {
+        self.rpc_client
+            .get_health()
+            .map_err(|e| Error::NodeUnhealthy(e.to_string()))
+    }
+
+    /// `None` when the supply of the token is fixed
+    pub fn get_mint_authority(&self) -> Result<Option<Pubkey>, Error> {
+        let mint = get_mint(&self.rpc_client, &self.mint_pubkey)?;
+        Ok(mint.mint_authority.into())
+    }
+
+    /// Whether the associated token account of `owner` is created
+    pub fn has_token_account(&self, owner: &Pubkey) -> Result<bool, Error> {
+        let token_address = get_associated_token_address(owner, &self.mint_pubkey);
         self.rpc_client
-            .get_balance(address)
-            .map_err(|_| Error::CannotGetAccountBalance(address.to_string()))
+            .get_account_with_commitment(&token_address, self.rpc_client.commitment())
+            .map(|resp| resp.value.is_some())
+            .map_err(|_| Error::CannotGetAssociatedAccount(token_address.to_string()))
+    }
+
+    /// The current slot and epoch with the time the slot is produced
+    pub fn get_slot_sample(&self) -> Result<SlotSample, Error> {
+        let epoch_info = self
+            .rpc_client
+            .get_epoch_info()
+            .map_err(|_| Error::CannotGetEpochInfo)?;
+        let slot = epoch_info.absolute_slot;
+        let block_time = self
+            .rpc_client
+            .get_block_time(slot)
+            .map_err(|_| Error::CannotGetBlockTime(slot))?;
+        Ok(SlotSample {
+            slot,
+            epoch: epoch_info.epoch,
+            block_time: block_time.max(0) as u64,
+        })
     }
 
     pub fn upload_transaction(&self, transaction: &Transaction) -> Result<Signature, Error> {
@@ -110,29 +524,174 @@ impl SolanaClient {
         if res.is_err() {
             return Err(Error::CannotGetSignaturesForAddress(address.to_string()));
         }
-        let signature_recs = res.unwrap();
+        let signatures: Vec<Signature> = res
+            .unwrap()
+            .iter()
+            .map(|signature_rec| Signature::from_str(&signature_rec.signature).unwrap())
+            .collect();
+        if signatures.is_empty() {
+            return Ok(vec![]);
+        }
+        // the chunks are fetched by the threads, the order of the transactions is kept
+        let chunk_size = signatures.len().div_ceil(self.fetch_concurrency);
+        let chunks: Vec<Result<Vec<AnalyzedTransaction>, Error>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = signatures
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|signature| self.get_analyzed_transaction(signature))
+                            .collect()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
         let mut analyzed_transactions = vec![];
-        for signature_rec in signature_recs.iter() {
-            let signature = Signature::from_str(&signature_rec.signature).unwrap();
+        for chunk in chunks {
+            analyzed_transactions.extend(chunk?);
+        }
+        Ok(analyzed_transactions)
+    }
+
+    /// The signatures of the transactions of `address` newer than `until` with their slots, the
+    /// newest first, all the signatures if it's `None`. The pages of them are read until the
+    /// last one
+    pub fn get_signatures_until(
+        &self,
+        address: &Pubkey,
+        until: Option<Signature>,
+    ) -> Result<Vec<(Signature, u64)>, Error> {
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = self
+                .rpc_client
+                .get_signatures_for_address_with_config(
+                    address,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(SIGNATURES_PAGE_LIMIT),
+                        commitment: None,
+                    },
+                )
+                .map_err(|_| Error::CannotGetSignaturesForAddress(address.to_string()))?;
+            let last_page = page.len() < SIGNATURES_PAGE_LIMIT;
+            for status in page {
+                let signature = Signature::from_str(&status.signature)
+                    .map_err(|_| Error::CannotGetSignaturesForAddress(address.to_string()))?;
+                signatures.push((signature, status.slot));
+            }
+            match signatures.last() {
+                Some((signature, _)) if !last_page => before = Some(*signature),
+                _ => break,
+            }
+        }
+        Ok(signatures)
+    }
+
+    /// Fetch and analyze the transaction, the owners of the token accounts are resolved
+    pub fn get_analyzed_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<AnalyzedTransaction, Error> {
+        self.cache.transactions.get_or_try_insert(*signature, || {
             let res = self
                 .rpc_client
-                .get_transaction(&signature, UiTransactionEncoding::JsonParsed);
+                .get_transaction(signature, UiTransactionEncoding::JsonParsed);
             if res.is_err() {
                 // cannot retrieve the transaction
-                return Err(Error::CannotGetTransactionInfo(
-                    signature_rec.signature.clone(),
-                ));
+                return Err(Error::CannotGetTransactionInfo(signature.to_string()));
             }
             let transaction_meta = res.unwrap();
-            let analyzer = TransactionAnalyzer::new(&transaction_meta);
-            let res = analyzer.parse(signature, transaction_meta.block_time.unwrap_or(0));
+            let analyzer =
+                TransactionAnalyzer::with_policy(&transaction_meta, &self.analyzer_policy);
+            let res = analyzer.parse(*signature, transaction_meta.block_time.unwrap_or(0));
             if res.is_err() {
-                todo!("cannot parse the transaction");
+                return Err(Error::CannotParseTransactionInfo(signature.to_string()));
+            }
+            let mut transaction = res.unwrap();
+            transaction.resolve_owners(|account| self.get_token_account_owner(account))?;
+            Ok(transaction)
+        })
+    }
+
+    /// The tokens transferred to `owner` by the transaction, only the ones from `sender` are
+    /// counted when it's given
+    fn verify_transferred(
+        &self,
+        signature: &Signature,
+        sender: Option<&Pubkey>,
+        owner: &Pubkey,
+    ) -> Result<u64, Error> {
+        let mut amount = 0_u64;
+        if let Ok(transaction_meta) = self
+            .rpc_client
+            .get_transaction(signature, UiTransactionEncoding::JsonParsed)
+        {
+            let analyzer =
+                TransactionAnalyzer::with_policy(&transaction_meta, &self.analyzer_policy);
+            let res = analyzer.parse(signature.clone(), transaction_meta.block_time.unwrap_or(0));
+            if res.is_err() {
+                return Err(Error::CannotParseTransactionInfo(signature.to_string()));
+            }
+            let mut parsed_transaction = res.unwrap();
+            // the owner is either the token account or the wallet owns it
+            parsed_transaction.resolve_owners(|account| self.get_token_account_owner(account))?;
+            for ix in parsed_transaction.instructions.iter() {
+                if let AnalyzedInstruction::SplToken(spl_token_ix) = ix {
+                    // only the transfers of the token of the bridge are counted, the mint is
+                    // known from the `transferChecked` instructions
+                    if spl_token_ix.operation != TokenOperation::Transfer
+                        || spl_token_ix
+                            .mint
+                            .is_some_and(|mint| mint != self.mint_pubkey)
+                    {
+                        continue;
+                    }
+                    let from_sender = sender.map_or(true, |sender| {
+                        spl_token_ix.source == *sender || spl_token_ix.source_owner == Some(*sender)
+                    });
+                    if from_sender
+                        && (spl_token_ix.destination == *owner
+                            || spl_token_ix.destination_owner == Some(*owner))
+                    {
+                        amount += spl_token_ix.amount;
+                    }
+                }
             }
-            let analyzed_transaction = res.unwrap();
-            analyzed_transactions.push(analyzed_transaction);
         }
-        Ok(analyzed_transactions)
+        Ok(amount)
+    }
+
+    /// The wallet owns the token account, `None` if the account is closed or it's not a token
+    /// account
+    pub fn get_token_account_owner(&self, account: &Pubkey) -> Result<Option<Pubkey>, Error> {
+        if let Some(owner) = self
+            .owner_store
+            .as_ref()
+            .and_then(|store| store.load_owner(account))
+        {
+            return Ok(Some(owner));
+        }
+        let res = self
+            .rpc_client
+            .get_account_with_commitment(account, self.rpc_client.commitment())
+            .map_err(|_| Error::CannotGetAccountData(account.to_string()))?;
+        let owner = res
+            .value
+            .filter(|account| account.owner == spl_token::id())
+            .and_then(|account| TokenAccount::unpack(&account.data).ok())
+            .map(|token_account| token_account.owner);
+        if let (Some(store), Some(owner)) = (self.owner_store.as_ref(), owner.as_ref()) {
+            store.save_owner(account, owner);
+        }
+        Ok(owner)
     }
 }
 
@@ -146,40 +705,295 @@ impl TokenClient for SolanaClient {
         &self,
         recipient_address: &Self::Address,
         amount: Self::Amount,
-    ) -> Result<Self::TxID, Self::Error> {
-        let signature = send_token(
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error> {
+        let receipt = send_tokens_with_outbox(
             &self.rpc_client,
             &self.mint_pubkey,
             &self.authority_key,
-            recipient_address,
-            amount,
+            &[(*recipient_address, amount)],
+            self.outgoing_store.as_deref(),
         )?;
-        Ok(signature)
+        // the fee is paid by the authority too
+        self.invalidate_balances(&self.authority_key.pubkey());
+        self.invalidate_balances(recipient_address);
+        self.cache.token_supply.invalidate(&self.mint_pubkey);
+        Ok(receipt)
+    }
+
+    fn send_tokens(
+        &self,
+        recipients: &[(Self::Address, Self::Amount)],
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error> {
+        let receipt = send_tokens_with_outbox(
+            &self.rpc_client,
+            &self.mint_pubkey,
+            &self.authority_key,
+            recipients,
+            self.outgoing_store.as_deref(),
+        )?;
+        self.invalidate_balances(&self.authority_key.pubkey());
+        for (recipient_address, _) in recipients {
+            self.invalidate_balances(recipient_address);
+        }
+        self.cache.token_supply.invalidate(&self.mint_pubkey);
+        Ok(receipt)
     }
 
     fn verify(&self, signature: &Signature, owner: &Pubkey) -> Result<Self::Amount, Self::Error> {
-        let mut amount = 0_u64;
-        if let Ok(transaction_meta) = self
-            .rpc_client
-            .get_transaction(signature, UiTransactionEncoding::JsonParsed)
-        {
-            let analyzer = TransactionAnalyzer::new(&transaction_meta);
-            let res = analyzer.parse(signature.clone(), transaction_meta.block_time.unwrap_or(0));
-            if res.is_err() {
-                return Err(Error::CannotParseTransactionInfo(signature.to_string()));
+        self.verify_transferred(signature, None, owner)
+    }
+
+    fn verify_from(
+        &self,
+        signature: &Signature,
+        sender: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<u64, Self::Error> {
+        self.verify_transferred(signature, Some(sender), owner)
+    }
+
+    fn mint_decimals(&self) -> Result<u8, Self::Error> {
+        get_mint(&self.rpc_client, &self.mint_pubkey).map(|mint| mint.decimals)
+    }
+
+    fn reserved_addresses(&self) -> Vec<(Pubkey, &'static str)> {
+        let authority_pubkey = self.authority_pubkey();
+        vec![
+            (self.mint_pubkey, "the mint"),
+            (authority_pubkey, "the authority"),
+            (
+                get_associated_token_address(&authority_pubkey, &self.mint_pubkey),
+                "the token account of the authority",
+            ),
+            (system_program::id(), "the system program"),
+            (spl_token::id(), "the token program"),
+        ]
+    }
+
+    fn error_kind(error: &Self::Error) -> ErrorKind {
+        error.kind()
+    }
+
+    /// The pending transactions of the outgoing store are settled one by one, nothing is
+    /// recovered without the store
+    fn recover_transfers(&self) -> Result<Vec<RecoveredTransfer<Signature, Pubkey, u64>>, Error> {
+        let Some(store) = self.outgoing_store.as_ref() else {
+            return Ok(vec![]);
+        };
+        let mut transfers = vec![];
+        for (outgoing, mut status) in store.query_unacknowledged_outgoing()? {
+            if status == TransferStatus::Pending {
+                status = settle_outgoing(&self.rpc_client, &outgoing)?;
+                store.set_outgoing_status(&outgoing.signature, status)?;
             }
-            let parsed_transaction = res.unwrap();
-            for ix in parsed_transaction.instructions.iter() {
-                if let AnalyzedInstruction::SplToken(spl_token_ix) = ix {
-                    if spl_token_ix.destination == *owner {
-                        amount += spl_token_ix.amount;
-                    }
+            if status == TransferStatus::Confirmed {
+                self.invalidate_balances(&self.authority_key.pubkey());
+                for (recipient_address, _) in outgoing.recipients.iter() {
+                    self.invalidate_balances(recipient_address);
                 }
+                self.cache.token_supply.invalidate(&self.mint_pubkey);
             }
+            transfers.push(RecoveredTransfer {
+                txid: outgoing.signature,
+                status,
+                recipients: outgoing.recipients,
+            });
         }
-        Ok(amount)
+        Ok(transfers)
+    }
+
+    fn acknowledge_transfer(&self, txid: &Signature) -> Result<(), Error> {
+        match self.outgoing_store.as_ref() {
+            Some(store) => store.acknowledge_outgoing(txid),
+            None => Ok(()),
+        }
+    }
+
+    fn stranded_balances(&self) -> Result<Vec<(Pubkey, u64)>, Error> {
+        get_auxiliary_token_accounts(
+            &self.rpc_client,
+            &self.mint_pubkey,
+            &self.authority_key.pubkey(),
+        )
+    }
+
+    fn consolidate(&self, accounts: &[(Pubkey, u64)]) -> Result<TransferReceipt<Signature>, Error> {
+        let receipt = consolidate_token_accounts(
+            &self.rpc_client,
+            &self.mint_pubkey,
+            &self.authority_key,
+            accounts,
+        )?;
+        self.invalidate_balances(&self.authority_key.pubkey());
+        Ok(receipt)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signer::keypair::keypair_from_seed;
+
+    use super::*;
+    use crate::solana::{
+        default_fixture_dir, make_fixture_rpc_client, Direction, DEFAULT_LOCAL_ENDPOINT,
+    };
+
+    const TEST_SIGNATURE_TPL_TOKEN: &str =
+        "25A1pSwLHvagx8FD3oyAGot1Kfp9keqFhdfGgDZq4s9xjkPc4h5R3P6ikf5ookcsKuZEJDcFShsa3JdgVXYbmgRx";
+
+    fn make_fixture_client() -> SolanaClient {
+        let rpc_client = make_fixture_rpc_client(
+            DEFAULT_LOCAL_ENDPOINT,
+            &default_fixture_dir(),
+            CommitmentConfig::confirmed(),
+        );
+        SolanaClient::new_with_rpc_client(rpc_client, Pubkey::new_unique(), Keypair::new())
+    }
+
+    #[test]
+    fn test_verify_tpl_token_transaction() {
+        let client = make_fixture_client();
+        let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
+        let owner = Pubkey::from_str("7My8xLpS8Nuao32SZ3PsiU9jERNuoWDBtQDrtTKb3guY").unwrap();
+        assert_eq!(client.verify(&signature, &owner).unwrap(), 1000);
+        assert_eq!(client.verify(&signature, &Pubkey::new_unique()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reserved_addresses() {
+        let rpc_client = make_fixture_rpc_client(
+            DEFAULT_LOCAL_ENDPOINT,
+            &default_fixture_dir(),
+            CommitmentConfig::confirmed(),
+        );
+        let mint_pubkey = Pubkey::from_str("4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw").unwrap();
+        let authority_key = keypair_from_seed(&[7; 32]).unwrap();
+        let client = SolanaClient::new_with_rpc_client(rpc_client, mint_pubkey, authority_key);
+        let addresses: Vec<String> = client
+            .reserved_addresses()
+            .iter()
+            .map(|(address, _)| address.to_string())
+            .collect();
+        for address in [
+            "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw",
+            "GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB",
+            "DA2kTqD55ix29Chwq1jzFr3c7R7KadykEQNa59gkXyc9",
+            "11111111111111111111111111111111",
+        ] {
+            assert!(addresses.contains(&address.to_owned()), "{address}");
+        }
+    }
+
+    #[test]
+    fn test_get_signatures_until() {
+        let client = make_fixture_client();
+        let address = Pubkey::from_str("3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L").unwrap();
+        let signatures = client.get_signatures_until(&address, None).unwrap();
+        assert_eq!(
+            signatures,
+            vec![(
+                Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap(),
+                333804871
+            )]
+        );
+    }
+
+    #[test]
+    fn test_resolve_owners_of_history() {
+        let client = make_fixture_client();
+        let address = Pubkey::from_str("3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L").unwrap();
+        let wallet = Pubkey::from_str("Afa4Jc8cGhyQc6v64sVw7qpUMiHDrTSc2umPwEdvAZ9M").unwrap();
+        let transactions = client
+            .get_transactions_related_to_address(&address)
+            .unwrap();
+        let AnalyzedInstruction::SplToken(detail) = &transactions[0].instructions[0] else {
+            panic!("not a token transfer");
+        };
+        assert_eq!(detail.source_owner, Some(wallet));
+        assert_eq!(
+            detail.destination_owner.unwrap().to_string(),
+            "dWC1R5jgKfjH79qv4jANoL1Q6FcKGQLYGzRAbqYoqtc"
+        );
+        for address in [address, wallet] {
+            let effect = transactions[0].effect_on(&address).unwrap();
+            assert_eq!(effect.direction, Direction::Out);
+            assert_eq!(effect.token_change, -1000);
+        }
+        assert_eq!(client.get_token_account_owner(&wallet).unwrap(), None);
+    }
+
+    #[test]
+    fn test_token_account_owners_from_store() {
+        let conn = crate::db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let client = make_fixture_client().set_owner_store(Arc::new(conn.clone()));
+        let account = Pubkey::from_str("7My8xLpS8Nuao32SZ3PsiU9jERNuoWDBtQDrtTKb3guY").unwrap();
+        let owner = client.get_token_account_owner(&account).unwrap().unwrap();
+        assert_eq!(
+            conn.query_ata_owner(&account.to_string()).unwrap(),
+            Some(owner.to_string())
+        );
+
+        // no fixture of the account, it's never fetched
+        let (account, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(client.get_token_account_owner(&account).is_err());
+        conn.save_owner(&account, &owner);
+        assert_eq!(
+            client.get_token_account_owner(&account).unwrap(),
+            Some(owner)
+        );
+
+        // the wallet owns the destination token account
+        let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
+        let wallet = Pubkey::from_str("dWC1R5jgKfjH79qv4jANoL1Q6FcKGQLYGzRAbqYoqtc").unwrap();
+        assert_eq!(client.verify(&signature, &wallet).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_fetch_transactions_concurrently() {
+        let address = Pubkey::from_str("3DTmFGM7GsH7MJvSkJ8deubVBr46L6tgUcA3XveUMz9L").unwrap();
+        let signatures = |client: SolanaClient| -> Vec<Signature> {
+            client
+                .get_transactions_related_to_address(&address)
+                .unwrap()
+                .iter()
+                .map(|transaction| transaction.signature)
+                .collect()
+        };
+        let expected = signatures(make_fixture_client());
+        assert!(!expected.is_empty());
+        for fetch_concurrency in [2, 8] {
+            let client = make_fixture_client().set_fetch_concurrency(fetch_concurrency);
+            assert_eq!(signatures(client), expected);
+        }
+    }
 
+    #[test]
+    fn test_cached_balance() {
+        let client = make_fixture_client();
+        let address = Pubkey::from_str("Afa4Jc8cGhyQc6v64sVw7qpUMiHDrTSc2umPwEdvAZ9M").unwrap();
+        let balance = client.get_balance(&address).unwrap();
+        assert_eq!(client.get_balance(&address).unwrap(), balance);
+        // the clones share the caches
+        let stats = client.clone().cache_stats().balances;
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        client.invalidate_balances(&address);
+        assert_eq!(client.get_balance(&address).unwrap(), balance);
+        let stats = client.cache_stats().balances;
+        assert_eq!((stats.misses, stats.invalidations), (2, 1));
+    }
 
+    #[test]
+    fn test_get_slot_sample() {
+        let client = make_fixture_client();
+        assert_eq!(
+            client.get_slot_sample().unwrap(),
+            SlotSample {
+                slot: 344126430,
+                epoch: 796,
+                block_time: 1731000598,
+            }
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+
+use crate::clock::Deadline;
+
+/// A `RpcSender` cuts the calls off at the deadline of the request or the job making them, see
+/// `Deadline`. The calls made without a deadline are sent as they are
+pub struct DeadlineSender<S> {
+    inner: S,
+}
+
+impl<S: RpcSender> DeadlineSender<S> {
+    pub fn new(inner: S) -> DeadlineSender<S> {
+        DeadlineSender { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for DeadlineSender<S> {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let Some(deadline) = Deadline::current() else {
+            return self.inner.send(request, params).await;
+        };
+        let exceeded = || ClientErrorKind::Custom(format!("{} exceeds the deadline", request));
+        let Some(remaining) = deadline.remaining() else {
+            return Err(exceeded().into());
+        };
+        tokio::time::timeout(remaining, self.inner.send(request, params))
+            .await
+            .unwrap_or_else(|_| Err(exceeded().into()))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::solana::{default_fixture_dir, FixtureSender};
+
+    #[tokio::test]
+    async fn test_deadline_sender() {
+        let sender = DeadlineSender::new(FixtureSender::replay(&default_fixture_dir()));
+        assert!(sender
+            .send(RpcRequest::GetHealth, Value::Null)
+            .await
+            .is_ok());
+        let deadline = Deadline::after(Duration::from_secs(60));
+        assert!(deadline
+            .scope(sender.send(RpcRequest::GetHealth, Value::Null))
+            .await
+            .is_ok());
+
+        // the call isn't sent after the deadline
+        let e = Deadline::after(Duration::ZERO)
+            .scope(sender.send(RpcRequest::GetHealth, Value::Null))
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("getHealth exceeds the deadline"));
+    }
+}
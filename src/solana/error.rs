@@ -1,3 +1,5 @@
+use super::ErrorKind;
+
 #[derive(Debug)]
 pub enum Error {
     MissingRequiredField(String),
@@ -22,6 +24,50 @@ pub enum Error {
     NotARelatedTransactionOfAuthority(String),
     MoreThanOneRelatedInstructionsFoundFrom1Transaction(String),
     CannotGetSignaturesForAddress(String),
+    CannotGetNodeVersion,
+    NodeUnhealthy(String),
+    CannotGetEpochInfo,
+    CannotGetBlockTime(u64),
+    OutgoingStoreFailed(String),
+    TransactionInDoubt(String),
+}
+
+impl Error {
+    /// Whether the call might succeed when it's made again, the failed calls to the node are
+    /// transient, see `TokenClient::error_kind`
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::CannotGetLatestBlockHash
+            | Self::CannotGetBlockHeight
+            | Self::CannotGetAccountData(_)
+            | Self::CannotGetAccountBalance(_)
+            | Self::CannotGetStatusForSignature(_)
+            | Self::CannotGetTransactionInfo(_)
+            | Self::CannotFetchTransaction(_)
+            | Self::CannotGetSignaturesForAddress(_)
+            | Self::CannotGetNodeVersion
+            | Self::NodeUnhealthy(_)
+            | Self::CannotGetEpochInfo
+            | Self::CannotGetBlockTime(_)
+            | Self::OutgoingStoreFailed(_) => ErrorKind::Transient,
+            Self::MissingRequiredField(_)
+            | Self::ExtractMismatchedType
+            | Self::InvalidMintAddress(_)
+            | Self::CannotCreateMintInstructions
+            | Self::CannotMakeMintTransaction
+            | Self::CannotUnpackAccountData(_)
+            | Self::CannotParseTransactionInfo(_)
+            | Self::CannotParsePubkeyFromString(_)
+            | Self::InvalidTransaction(_)
+            | Self::NotARelatedTransactionOfAuthority(_)
+            | Self::MoreThanOneRelatedInstructionsFoundFrom1Transaction(_) => ErrorKind::Permanent,
+            // the node refuses the transaction or doesn't answer, the reason is not kept
+            Self::CannotSendTransaction
+            | Self::CannotGetAssociatedAccount(_)
+            | Self::CannotCreateAssociatedAccount(_) => ErrorKind::Unknown,
+            Self::TransactionInDoubt(_) => ErrorKind::InDoubt,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -79,6 +125,22 @@ impl std::fmt::Display for Error {
             Self::CannotGetSignaturesForAddress(address) => {
                 write!(f, "cannot get signatures for address: {}", address)
             }
+            Self::CannotGetNodeVersion => write!(f, "cannot get version of the node"),
+            Self::NodeUnhealthy(reason) => write!(f, "the node is unhealthy: {}", reason),
+            Self::CannotGetEpochInfo => write!(f, "cannot get epoch info"),
+            Self::CannotGetBlockTime(slot) => write!(f, "cannot get block time of slot: {}", slot),
+            Self::OutgoingStoreFailed(reason) => {
+                write!(
+                    f,
+                    "the store of the outgoing transactions failed: {}",
+                    reason
+                )
+            }
+            Self::TransactionInDoubt(signature) => {
+                write!(f, "transaction {} is sent but not settled", signature)
+            }
         }
     }
 }
+
+impl std::error::Error for Error {}
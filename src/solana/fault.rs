@@ -0,0 +1,106 @@
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    http_sender::HttpSender,
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::chaos::{Fault, FaultInjector};
+
+/// A `RpcSender` drops, delays or corrupts a part of the calls before they reach the inner
+/// sender
+pub struct FaultSender<S> {
+    inner: S,
+    injector: FaultInjector,
+}
+
+impl<S: RpcSender> FaultSender<S> {
+    pub fn new(inner: S, injector: FaultInjector) -> FaultSender<S> {
+        FaultSender { inner, injector }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for FaultSender<S> {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        match self.injector.next_fault() {
+            Some(Fault::Drop) => Err(ClientErrorKind::Custom(format!(
+                "{} is dropped by fault injection",
+                request
+            ))
+            .into()),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                self.inner.send(request, params).await
+            }
+            // the result is replaced, so it cannot be parsed into the expected type
+            Some(Fault::Corrupt) => {
+                self.inner.send(request, params).await?;
+                Ok(Value::String("corrupted by fault injection".to_owned()))
+            }
+            None => self.inner.send(request, params).await,
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+/// Make a rpc client injects the faults into the calls to `endpoint`
+pub fn make_fault_rpc_client(
+    endpoint: &str,
+    injector: FaultInjector,
+    commitment_config: CommitmentConfig,
+) -> RpcClient {
+    RpcClient::new_sender(
+        FaultSender::new(HttpSender::new(endpoint), injector),
+        RpcClientConfig::with_commitment(commitment_config),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::signature::Signature;
+    use solana_transaction_status::UiTransactionEncoding;
+
+    use super::*;
+    use crate::solana::{default_fixture_dir, FixtureSender};
+
+    const TEST_SIGNATURE_TPL_TOKEN: &str =
+        "25A1pSwLHvagx8FD3oyAGot1Kfp9keqFhdfGgDZq4s9xjkPc4h5R3P6ikf5ookcsKuZEJDcFShsa3JdgVXYbmgRx";
+
+    fn make_rpc_client(faults: &str) -> RpcClient {
+        RpcClient::new_sender(
+            FaultSender::new(
+                FixtureSender::replay(&default_fixture_dir()),
+                FaultInjector::new(faults.parse().unwrap()),
+            ),
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        )
+    }
+
+    #[test]
+    fn test_fault_sender() {
+        let signature = Signature::from_str(TEST_SIGNATURE_TPL_TOKEN).unwrap();
+        for (faults, ok) in [
+            ("", true),
+            ("delay=1,delay_ms=10", true),
+            ("drop=1", false),
+            ("corrupt=1", false),
+        ] {
+            let res = make_rpc_client(faults)
+                .get_transaction(&signature, UiTransactionEncoding::JsonParsed);
+            assert_eq!(res.is_ok(), ok, "faults `{}`", faults);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    http_sender::HttpSender,
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// Set this environment variable to record the fixtures from the real endpoint instead of
+/// replaying them
+pub const RECORD_FIXTURES_ENV: &str = "RECORD_SOLANA_FIXTURES";
+
+enum Mode {
+    Record(HttpSender),
+    Replay,
+}
+
+/// A `RpcSender` captures the json results from a real solana node into fixture files, or
+/// replays them later, so the tests don't depend on the transactions those might be pruned
+/// from devnet
+///
+/// The fixture of a request is stored as `<dir>/<method>_<first string or number param>.json`,
+/// e.g. `getTransaction_<signature>.json` and `getBlockTime_<slot>.json`
+pub struct FixtureSender {
+    mode: Mode,
+    dir: PathBuf,
+}
+
+impl FixtureSender {
+    pub fn record(endpoint: &str, dir: &Path) -> FixtureSender {
+        FixtureSender {
+            mode: Mode::Record(HttpSender::new(endpoint)),
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    pub fn replay(dir: &Path) -> FixtureSender {
+        FixtureSender {
+            mode: Mode::Replay,
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn fixture_path(&self, request: &RpcRequest, params: &Value) -> PathBuf {
+        let key = match params.get(0) {
+            Some(Value::String(param)) => param.clone(),
+            Some(Value::Number(param)) => param.to_string(),
+            _ => "noparams".to_owned(),
+        };
+        self.dir.join(format!("{}_{}.json", request, key))
+    }
+}
+
+#[async_trait::async_trait]
+impl RpcSender for FixtureSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let path = self.fixture_path(&request, &params);
+        match &self.mode {
+            Mode::Record(sender) => {
+                let result = sender.send(request, params).await?;
+                fs::create_dir_all(&self.dir)?;
+                fs::write(&path, serde_json::to_string_pretty(&result)?)?;
+                info!("fixture {} is recorded", path.display());
+                Ok(result)
+            }
+            Mode::Replay => {
+                let content = fs::read_to_string(&path).map_err(|e| {
+                    ClientErrorKind::Custom(format!(
+                        "cannot read fixture {}, set {} to record it, reason: {}",
+                        path.display(),
+                        RECORD_FIXTURES_ENV,
+                        e
+                    ))
+                })?;
+                Ok(serde_json::from_str(&content)?)
+            }
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        match &self.mode {
+            Mode::Record(sender) => sender.get_transport_stats(),
+            Mode::Replay => RpcTransportStats::default(),
+        }
+    }
+
+    fn url(&self) -> String {
+        match &self.mode {
+            Mode::Record(sender) => sender.url(),
+            Mode::Replay => format!("fixture://{}", self.dir.display()),
+        }
+    }
+}
+
+/// Make a rpc client replays the fixtures from `dir`, the fixtures are recorded from
+/// `endpoint` when `RECORD_SOLANA_FIXTURES` is set
+pub fn make_fixture_rpc_client(
+    endpoint: &str,
+    dir: &Path,
+    commitment_config: CommitmentConfig,
+) -> RpcClient {
+    let sender = if std::env::var_os(RECORD_FIXTURES_ENV).is_some() {
+        FixtureSender::record(endpoint, dir)
+    } else {
+        FixtureSender::replay(dir)
+    };
+    RpcClient::new_sender(sender, RpcClientConfig::with_commitment(commitment_config))
+}
+
+/// The directory stores the fixtures used by the tests of this crate
+#[cfg(test)]
+pub fn default_fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("samples/solana")
+}
@@ -0,0 +1,100 @@
+use solana_sdk::{offchain_message::OffchainMessage, pubkey::Pubkey, signature::Signature};
+
+/// The version of the off-chain messages the hardware wallets and `solana sign-offchain-message`
+/// sign
+const OFFCHAIN_MESSAGE_VERSION: u8 = 0;
+
+/// The off-chain message of version 0 the hardware wallets and `solana sign-offchain-message`
+/// sign for `message`, `None` if it's empty or too long
+pub fn make_offchain_message(message: &str) -> Option<OffchainMessage> {
+    OffchainMessage::new(OFFCHAIN_MESSAGE_VERSION, message.as_bytes()).ok()
+}
+
+/// Check `signature` is made by `pubkey` for `message`
+///
+/// The browser wallets (`signMessage` of Phantom, Solflare and Backpack) sign the message as it
+/// is, the hardware wallets and the CLI sign the off-chain message of it, both are accepted.
+pub fn verify_offchain_signature(pubkey: &Pubkey, message: &str, signature: &Signature) -> bool {
+    if signature.verify(pubkey.as_ref(), message.as_bytes()) {
+        return true;
+    }
+    make_offchain_message(message)
+        .is_some_and(|offchain_message| offchain_message.verify(pubkey, signature).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::offchain_message::MessageFormat;
+    use solana_sdk::signer::{keypair::keypair_from_seed, Signer};
+
+    use super::*;
+
+    const TEST_MESSAGE: &str = "Sign in to depc-bridge";
+    /// The key of seed `[42; 32]`
+    const TEST_PUBKEY: &str = "2iXtA8oeZqUU5pofxK971TCEvFGfems2AcDRaZHKD2pQ";
+    /// `signMessage` of a browser wallet, the message is signed as it is
+    const TEST_WALLET_SIGNATURE: &str =
+        "5EeCKcypnbto6eJ7FDsqsDpdbCV7QGdrEGWe1ZydBEY52ChUmpvDSUyCCoKEqtcHNGEFjSKX4nPqiizVsHyPyefV";
+    /// `solana sign-offchain-message`, the off-chain message is signed
+    const TEST_CLI_SIGNATURE: &str =
+        "6fAAbMoBvQhoepw3dfP7AoatQyUQgjUpuYMHZw81jMpuP1cFjr2T3e4SB8faVqvyPYrBpAd9K62KFY9VycSqC3T";
+    const TEST_UTF8_MESSAGE: &str = "提现 depc-bridge";
+    const TEST_CLI_UTF8_SIGNATURE: &str =
+        "4msf2MKkj3JeVE1m2XNFN65Hc3t9qJt6WeidgNavbRvXNL8NndyN2tkhLuBRZoVhoqKK4xwDohtHfUSdwVW5s2fP";
+
+    #[test]
+    fn test_make_offchain_message() {
+        let offchain_message = make_offchain_message(TEST_MESSAGE).unwrap();
+        assert!(matches!(
+            offchain_message.get_format(),
+            MessageFormat::RestrictedAscii
+        ));
+        // the signing domain, the version, the format and the length (u16, little endian)
+        let data = offchain_message.serialize().unwrap();
+        assert_eq!(&data[..16], b"\xffsolana offchain");
+        assert_eq!(data[16..20], [0, 0, 22, 0]);
+        assert_eq!(&data[20..], TEST_MESSAGE.as_bytes());
+
+        let offchain_message = make_offchain_message(TEST_UTF8_MESSAGE).unwrap();
+        assert!(matches!(
+            offchain_message.get_format(),
+            MessageFormat::LimitedUtf8
+        ));
+        // the messages of the limited formats fit in a packet with the header
+        let offchain_message = make_offchain_message(&"a".repeat(1213)).unwrap();
+        assert!(matches!(
+            offchain_message.get_format(),
+            MessageFormat::ExtendedUtf8
+        ));
+        assert!(make_offchain_message("").is_none());
+        // the message and the header of 20 bytes are up to `u16::MAX` bytes
+        assert!(make_offchain_message(&"a".repeat(usize::from(u16::MAX) - 20)).is_some());
+        assert!(make_offchain_message(&"a".repeat(usize::from(u16::MAX) - 19)).is_none());
+    }
+
+    #[test]
+    fn test_verify_wallet_signatures() {
+        let pubkey = Pubkey::from_str(TEST_PUBKEY).unwrap();
+        assert_eq!(keypair_from_seed(&[42; 32]).unwrap().pubkey(), pubkey);
+        for (message, signature) in [
+            (TEST_MESSAGE, TEST_WALLET_SIGNATURE),
+            (TEST_MESSAGE, TEST_CLI_SIGNATURE),
+            (TEST_UTF8_MESSAGE, TEST_CLI_UTF8_SIGNATURE),
+        ] {
+            let signature = Signature::from_str(signature).unwrap();
+            assert!(verify_offchain_signature(&pubkey, message, &signature));
+            assert!(!verify_offchain_signature(
+                &pubkey,
+                "Sign in to another bridge",
+                &signature
+            ));
+            assert!(!verify_offchain_signature(
+                &Pubkey::new_unique(),
+                message,
+                &signature
+            ));
+        }
+    }
+}
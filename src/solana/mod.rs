@@ -1,16 +1,37 @@
 mod analyzer;
 
+mod budget;
+mod cache;
 mod client;
+mod deadline;
+mod message;
+mod outbox;
 mod token;
 
 mod error;
 
+#[cfg(feature = "chaos")]
+mod fault;
+#[cfg(any(test, feature = "testing"))]
+mod fixture;
+
 pub use analyzer::{
-    Instruction as AnalyzedInstruction, InstructionDetail, Transaction as AnalyzedTransaction,
-    TransactionAnalyzer,
+    AddressEffect, AnalyzerPolicy, Direction, Error as AnalyzerError,
+    Instruction as AnalyzedInstruction, InstructionDetail, OtherInstruction, TokenOperation,
+    Transaction as AnalyzedTransaction, TransactionAnalyzer,
 };
 
+pub use budget::*;
+pub use cache::{CacheStats, SolanaCacheStats, TokenOwnerStore, TtlCache, DEFAULT_CACHE_TTL};
 pub use client::*;
+pub use deadline::*;
+pub use message::*;
+pub use outbox::*;
 pub use token::*;
 
 pub use error::*;
+
+#[cfg(feature = "chaos")]
+pub use fault::*;
+#[cfg(any(test, feature = "testing"))]
+pub use fixture::*;
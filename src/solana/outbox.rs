@@ -0,0 +1,261 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::{debug, warn};
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::Transaction};
+
+use super::{Error, TransferStatus};
+use crate::clock::{Clock, SharedClock};
+use crate::db;
+
+/// The status of a transaction being settled is checked so often
+pub const SETTLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A signed transfer of the authority, it's saved before it's submitted so it's found again
+/// after a restart, see `OutgoingTxStore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingTransaction {
+    pub signature: Signature,
+    /// The signed transaction serialized by bincode, it's submitted again as it is
+    pub raw_tx: Vec<u8>,
+    /// The transaction can never be confirmed after the block height, its blockhash expires
+    pub last_valid_block_height: u64,
+    pub recipients: Vec<(Pubkey, u64)>,
+}
+
+impl OutgoingTransaction {
+    pub fn new(
+        transaction: &Transaction,
+        last_valid_block_height: u64,
+        recipients: &[(Pubkey, u64)],
+    ) -> Result<OutgoingTransaction, Error> {
+        let raw_tx = bincode::serialize(transaction)
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))?;
+        Ok(OutgoingTransaction {
+            signature: transaction.signatures[0],
+            raw_tx,
+            last_valid_block_height,
+            recipients: recipients.to_vec(),
+        })
+    }
+
+    pub fn transaction(&self) -> Result<Transaction, Error> {
+        bincode::deserialize(&self.raw_tx)
+            .map_err(|_| Error::InvalidTransaction(self.signature.to_string()))
+    }
+}
+
+/// The outgoing transactions of the authority, a transaction is pending from it's saved until
+/// it's settled, and it's kept for the recovery until the caller acknowledges it
+///
+/// A transaction failing to be saved is never submitted, the transfer is made again later.
+pub trait OutgoingTxStore: Send + Sync {
+    fn save_outgoing(&self, outgoing: &OutgoingTransaction) -> Result<(), Error>;
+    fn set_outgoing_status(
+        &self,
+        signature: &Signature,
+        status: TransferStatus,
+    ) -> Result<(), Error>;
+    /// The transactions not acknowledged yet with their status, in the order they're saved
+    fn query_unacknowledged_outgoing(
+        &self,
+    ) -> Result<Vec<(OutgoingTransaction, TransferStatus)>, Error>;
+    fn acknowledge_outgoing(&self, signature: &Signature) -> Result<(), Error>;
+}
+
+/// The transactions are saved to table `outgoing_transactions` of the bridge the connection is
+/// scoped to, they're stamped by `clock`
+pub struct DbOutgoingStore {
+    conn: db::Conn,
+    clock: SharedClock,
+}
+
+impl DbOutgoingStore {
+    pub fn new(conn: db::Conn, clock: SharedClock) -> DbOutgoingStore {
+        DbOutgoingStore { conn, clock }
+    }
+}
+
+impl OutgoingTxStore for DbOutgoingStore {
+    fn save_outgoing(&self, outgoing: &OutgoingTransaction) -> Result<(), Error> {
+        let recipients: Vec<(String, u64)> = outgoing
+            .recipients
+            .iter()
+            .map(|(address, amount)| (address.to_string(), *amount))
+            .collect();
+        let recipients = serde_json::to_string(&recipients)
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))?;
+        self.conn
+            .add_outgoing_transaction(
+                &outgoing.signature.to_string(),
+                &outgoing.raw_tx,
+                outgoing.last_valid_block_height,
+                &recipients,
+                self.clock.now(),
+            )
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))
+    }
+
+    fn set_outgoing_status(
+        &self,
+        signature: &Signature,
+        status: TransferStatus,
+    ) -> Result<(), Error> {
+        let settled_at = (status != TransferStatus::Pending).then(|| self.clock.now());
+        self.conn
+            .set_outgoing_transaction_status(&signature.to_string(), status.as_str(), settled_at)
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))
+    }
+
+    fn query_unacknowledged_outgoing(
+        &self,
+    ) -> Result<Vec<(OutgoingTransaction, TransferStatus)>, Error> {
+        let records = self
+            .conn
+            .query_unacknowledged_outgoing_transactions()
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))?;
+        records
+            .into_iter()
+            .map(|record| {
+                let invalid = || Error::InvalidTransaction(record.signature.clone());
+                let recipients: Vec<(String, u64)> =
+                    serde_json::from_str(&record.recipients).map_err(|_| invalid())?;
+                let recipients = recipients
+                    .into_iter()
+                    .map(|(address, amount)| Ok((address.parse().map_err(|_| invalid())?, amount)))
+                    .collect::<Result<_, Error>>()?;
+                let outgoing = OutgoingTransaction {
+                    signature: record.signature.parse().map_err(|_| invalid())?,
+                    raw_tx: record.raw_tx,
+                    last_valid_block_height: record.last_valid_block_height,
+                    recipients,
+                };
+                let status = record.status.parse().map_err(|_| invalid())?;
+                Ok((outgoing, status))
+            })
+            .collect()
+    }
+
+    fn acknowledge_outgoing(&self, signature: &Signature) -> Result<(), Error> {
+        self.conn
+            .acknowledge_outgoing_transaction(&signature.to_string(), self.clock.now())
+            .map_err(|e| Error::OutgoingStoreFailed(e.to_string()))
+    }
+}
+
+/// Wait for `outgoing` until it's confirmed or its blockhash expires, it's submitted again
+/// meanwhile in case the node dropped it
+///
+/// The block height is read before the status, so the transaction not found after the last
+/// valid block height is never confirmed later. The history is searched for the status, the
+/// transactions settled long ago are found as well.
+pub fn settle_outgoing(
+    rpc_client: &RpcClient,
+    outgoing: &OutgoingTransaction,
+) -> Result<TransferStatus, Error> {
+    let transaction = outgoing.transaction()?;
+    let signature = outgoing.signature;
+    let config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        ..Default::default()
+    };
+    loop {
+        let block_height = rpc_client
+            .get_block_height()
+            .map_err(|_| Error::CannotGetBlockHeight)?;
+        let status = rpc_client
+            .get_signature_status_with_commitment_and_history(
+                &signature,
+                rpc_client.commitment(),
+                true,
+            )
+            .map_err(|_| Error::CannotGetStatusForSignature(signature.to_string()))?;
+        match status {
+            Some(Ok(())) => return Ok(TransferStatus::Confirmed),
+            Some(Err(e)) => {
+                warn!("transaction {signature} is confirmed with an error: {e}");
+                return Ok(TransferStatus::Failed);
+            }
+            None if block_height > outgoing.last_valid_block_height => {
+                return Ok(TransferStatus::Expired)
+            }
+            None => {}
+        }
+        if let Err(e) = rpc_client.send_transaction_with_config(&transaction, config) {
+            debug!("cannot submit transaction {signature} again, reason: {e}");
+        }
+        sleep(SETTLE_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use solana_sdk::{hash::Hash, signature::Keypair, signer::Signer, system_instruction};
+
+    use super::*;
+    use crate::testing::MockClock;
+
+    const TEST_NOW: u64 = 1730000000;
+
+    fn make_outgoing(last_valid_block_height: u64) -> OutgoingTransaction {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &recipient, 1)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        OutgoingTransaction::new(&transaction, last_valid_block_height, &[(recipient, 100)])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_outgoing_store_of_conn() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let clock = Arc::new(MockClock::new(TEST_NOW));
+        let store = DbOutgoingStore::new(conn.clone(), clock.clone());
+        let first = make_outgoing(1000);
+        let second = make_outgoing(1010);
+        store.save_outgoing(&first).unwrap();
+        store.save_outgoing(&second).unwrap();
+        assert_eq!(first.transaction().unwrap().signatures[0], first.signature);
+
+        clock.advance(30);
+        store
+            .set_outgoing_status(&first.signature, TransferStatus::Confirmed)
+            .unwrap();
+        assert_eq!(
+            store.query_unacknowledged_outgoing().unwrap(),
+            vec![
+                (first.clone(), TransferStatus::Confirmed),
+                (second.clone(), TransferStatus::Pending)
+            ]
+        );
+        let records = conn.query_unacknowledged_outgoing_transactions().unwrap();
+        assert_eq!(records[0].created_at, TEST_NOW);
+        assert_eq!(records[0].settled_at, Some(TEST_NOW + 30));
+        assert!(records[1].settled_at.is_none());
+
+        // the acknowledged ones are never recovered again
+        store.acknowledge_outgoing(&first.signature).unwrap();
+        assert_eq!(
+            store.query_unacknowledged_outgoing().unwrap(),
+            vec![(second, TransferStatus::Pending)]
+        );
+        // the transactions are kept by bridge
+        assert!(DbOutgoingStore::new(conn.scoped("other"), clock)
+            .query_unacknowledged_outgoing()
+            .unwrap()
+            .is_empty());
+        // a transaction is saved once
+        assert!(matches!(
+            store.save_outgoing(&first),
+            Err(Error::OutgoingStoreFailed(_))
+        ));
+    }
+}
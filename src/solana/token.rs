@@ -1,6 +1,7 @@
 use std::{thread::sleep, time::Duration};
 
-use solana_client::rpc_client::RpcClient;
+use log::warn;
+use solana_client::{rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
 use solana_sdk::{
     account::ReadableAccount,
     commitment_config::CommitmentConfig,
@@ -13,16 +14,20 @@ use solana_sdk::{
 };
 use solana_transaction_status::{
     parse_instruction::ParsedInstruction, UiInstruction, UiMessage, UiParsedInstruction,
+    UiTransactionEncoding,
 };
 use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    get_associated_token_address,
+    instruction::{create_associated_token_account, create_associated_token_account_idempotent},
 };
 use spl_token::{
     instruction::{initialize_mint, mint_to, transfer},
     state::{Account as TokenAccount, Mint},
 };
 
-use super::Error;
+use super::{
+    settle_outgoing, Error, OutgoingTransaction, OutgoingTxStore, TransferReceipt, TransferStatus,
+};
 
 pub const DEFAULT_LOCAL_ENDPOINT: &str = "https://api.devnet.solana.com";
 pub const DEFAULT_MINT_AMOUNT: u64 = 83_000_000 * 10u64.pow(8);
@@ -39,6 +44,14 @@ pub fn check_spl_token(rpc_client: &RpcClient, mint_pubkey: &Pubkey) -> Result<u
     Err(Error::InvalidMintAddress(mint_pubkey.to_string()))
 }
 
+/// The mint account of the token, its authority and supply
+pub fn get_mint(rpc_client: &RpcClient, mint_pubkey: &Pubkey) -> Result<Mint, Error> {
+    let account = rpc_client
+        .get_account(mint_pubkey)
+        .map_err(|_| Error::InvalidMintAddress(mint_pubkey.to_string()))?;
+    Mint::unpack(account.data()).map_err(|_| Error::InvalidMintAddress(mint_pubkey.to_string()))
+}
+
 pub fn init_spl_token(
     rpc_client: &RpcClient,
     authority_key: &Keypair,
@@ -230,38 +243,213 @@ pub fn send_token(
     owner_key: &Keypair,
     target_pubkey: &Pubkey,
     amount: u64,
-) -> Result<Signature, Error> {
+) -> Result<TransferReceipt<Signature>, Error> {
+    send_tokens(
+        rpc_client,
+        mint_pubkey,
+        owner_key,
+        &[(*target_pubkey, amount)],
+    )
+}
+
+/// Send the tokens to all the targets by one transaction, either all of them are sent or none
+pub fn send_tokens(
+    rpc_client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    owner_key: &Keypair,
+    targets: &[(Pubkey, u64)],
+) -> Result<TransferReceipt<Signature>, Error> {
+    send_tokens_with_outbox(rpc_client, mint_pubkey, owner_key, targets, None)
+}
+
+/// The same as `send_tokens`, but the signed transaction is saved to `outbox` before it's
+/// submitted, see `OutgoingTxStore`
+///
+/// A transaction failing to be sent might be confirmed anyway, it's settled before the error
+/// is returned, so the transfer failed is never made. It's `Error::TransactionInDoubt` when it
+/// cannot be settled, the transfer must not be made again before it's recovered.
+pub fn send_tokens_with_outbox(
+    rpc_client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    owner_key: &Keypair,
+    targets: &[(Pubkey, u64)],
+    outbox: Option<&dyn OutgoingTxStore>,
+) -> Result<TransferReceipt<Signature>, Error> {
     let source_token_pubkey = get_associated_token_address(&owner_key.pubkey(), mint_pubkey);
-    let target_token_pubkey = get_associated_token_address(target_pubkey, mint_pubkey);
 
-    let res = transfer(
-        &spl_token::id(),
-        &source_token_pubkey,
-        &target_token_pubkey,
-        &owner_key.pubkey(),
-        &[&owner_key.pubkey()],
-        amount,
-    );
-    if res.is_err() {
-        return Err(Error::CannotMakeMintTransaction);
+    let mut instructions = Vec::with_capacity(targets.len());
+    for (target_pubkey, amount) in targets {
+        let target_token_pubkey = get_associated_token_address(target_pubkey, mint_pubkey);
+        let instruction = transfer(
+            &spl_token::id(),
+            &source_token_pubkey,
+            &target_token_pubkey,
+            &owner_key.pubkey(),
+            &[&owner_key.pubkey()],
+            *amount,
+        )
+        .map_err(|_| Error::CannotMakeMintTransaction)?;
+        instructions.push(instruction);
     }
-    let instruction = res.unwrap();
 
-    let res = rpc_client.get_latest_blockhash();
-    if res.is_err() {
-        return Err(Error::CannotGetLatestBlockHash);
-    }
-    let latest_block_hash = res.unwrap();
-    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&owner_key.pubkey()));
+    let (latest_block_hash, last_valid_block_height) = rpc_client
+        .get_latest_blockhash_with_commitment(rpc_client.commitment())
+        .map_err(|_| Error::CannotGetLatestBlockHash)?;
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&owner_key.pubkey()));
     transaction.sign(&[&owner_key], latest_block_hash);
+    let created_ata = creates_associated_token_account(&transaction);
 
-    let res = rpc_client.send_and_confirm_transaction(&transaction);
-    if let Err(e) = res {
-        println!("failed to send transaction, reason: {}", e);
+    let outgoing = OutgoingTransaction::new(&transaction, last_valid_block_height, targets)?;
+    let signature = outgoing.signature;
+    if let Some(outbox) = outbox {
+        outbox.save_outgoing(&outgoing)?;
+    }
+    let status = match rpc_client.send_and_confirm_transaction(&transaction) {
+        Ok(_) => TransferStatus::Confirmed,
+        Err(e) => {
+            warn!("failed to send transaction {signature}, settle it, reason: {e}");
+            settle_outgoing(rpc_client, &outgoing).map_err(|e| {
+                warn!("cannot settle transaction {signature}, reason: {e}");
+                Error::TransactionInDoubt(signature.to_string())
+            })?
+        }
+    };
+    if let Some(outbox) = outbox {
+        // the transaction left pending is settled again by the recovery
+        if let Err(e) = outbox.set_outgoing_status(&signature, status) {
+            warn!("cannot save the status of transaction {signature}, reason: {e}");
+        } else if status != TransferStatus::Confirmed {
+            // nothing is transferred, there's nothing for the caller to acknowledge
+            if let Err(e) = outbox.acknowledge_outgoing(&signature) {
+                warn!("cannot acknowledge transaction {signature}, reason: {e}");
+            }
+        }
+    }
+    if status != TransferStatus::Confirmed {
         return Err(Error::CannotSendTransaction);
     }
-    let signature = res.unwrap();
-    Ok(signature)
+    Ok(fetch_receipt(rpc_client, signature, created_ata))
+}
+
+/// Whether any instruction of the transaction runs the associated token account program, i.e.
+/// it creates the token account of a recipient
+fn creates_associated_token_account(transaction: &Transaction) -> bool {
+    let message = &transaction.message;
+    message.instructions.iter().any(|instruction| {
+        message
+            .account_keys
+            .get(instruction.program_id_index as usize)
+            .is_some_and(|program_id| *program_id == spl_associated_token_account::id())
+    })
+}
+
+/// The receipt of the transfer confirmed by `signature`. The transfer is made already, so the
+/// fee, the slot and the logs are left unknown rather than failing it when the transaction
+/// cannot be fetched
+fn fetch_receipt(
+    rpc_client: &RpcClient,
+    signature: Signature,
+    created_ata: bool,
+) -> TransferReceipt<Signature> {
+    let mut receipt = TransferReceipt {
+        txid: signature,
+        fee: None,
+        slot: None,
+        created_ata,
+        raw_logs: None,
+    };
+    match rpc_client.get_transaction(&signature, UiTransactionEncoding::Json) {
+        Ok(transaction) => {
+            receipt.slot = Some(transaction.slot);
+            if let Some(meta) = transaction.transaction.meta {
+                receipt.fee = Some(meta.fee);
+                receipt.raw_logs = Option::from(meta.log_messages);
+            }
+        }
+        Err(e) => warn!("cannot fetch transaction {signature} for the receipt, reason: {e}"),
+    }
+    receipt
+}
+
+/// The token accounts of the mint owned by `owner` besides its associated token account, with
+/// the balances of them. They're left by the operations failed halfway, e.g. the tokens
+/// transferred to an intermediate account and never moved on
+pub fn get_auxiliary_token_accounts(
+    rpc_client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Vec<(Pubkey, u64)>, Error> {
+    let associated_token_address = get_associated_token_address(owner, mint_pubkey);
+    let keyed_accounts = rpc_client
+        .get_token_accounts_by_owner(owner, TokenAccountsFilter::Mint(*mint_pubkey))
+        .map_err(|_| Error::CannotGetAccountData(owner.to_string()))?;
+    let mut accounts = vec![];
+    for keyed_account in keyed_accounts {
+        let account = keyed_account
+            .pubkey
+            .parse::<Pubkey>()
+            .map_err(|_| Error::CannotParsePubkeyFromString(keyed_account.pubkey.clone()))?;
+        if account == associated_token_address {
+            continue;
+        }
+        // the balance is read from the raw data, the parsed one is not decoded by the client
+        let account_data = rpc_client
+            .get_account_data(&account)
+            .map_err(|_| Error::CannotGetAccountData(account.to_string()))?;
+        let token_account = TokenAccount::unpack(&account_data)
+            .map_err(|_| Error::CannotUnpackAccountData(account.to_string()))?;
+        accounts.push((account, token_account.amount));
+    }
+    Ok(accounts)
+}
+
+/// Transfer all the tokens of the `sources` (token accounts owned by the owner) to the
+/// associated token account of the owner by one transaction, it's created when it's missing
+///
+/// The transfers are not saved to the outbox, the tokens stay with the owner, so the
+/// transaction failed to be settled is found again by the next consolidation.
+pub fn consolidate_token_accounts(
+    rpc_client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    owner_key: &Keypair,
+    sources: &[(Pubkey, u64)],
+) -> Result<TransferReceipt<Signature>, Error> {
+    let owner_pubkey = owner_key.pubkey();
+    let associated_token_address = get_associated_token_address(&owner_pubkey, mint_pubkey);
+    let mut instructions = vec![create_associated_token_account_idempotent(
+        &owner_pubkey,
+        &owner_pubkey,
+        mint_pubkey,
+        &spl_token::id(),
+    )];
+    for (source, amount) in sources {
+        let instruction = transfer(
+            &spl_token::id(),
+            source,
+            &associated_token_address,
+            &owner_pubkey,
+            &[&owner_pubkey],
+            *amount,
+        )
+        .map_err(|_| Error::CannotMakeMintTransaction)?;
+        instructions.push(instruction);
+    }
+    let latest_block_hash = rpc_client
+        .get_latest_blockhash()
+        .map_err(|_| Error::CannotGetLatestBlockHash)?;
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&owner_pubkey),
+        &[owner_key],
+        latest_block_hash,
+    );
+    let signature = rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .map_err(|e| {
+            warn!("cannot consolidate the token accounts, reason: {e}");
+            Error::CannotSendTransaction
+        })?;
+    Ok(fetch_receipt(rpc_client, signature, false))
 }
 
 mod parsing {
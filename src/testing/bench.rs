@@ -0,0 +1,169 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::mpsc::channel;
+
+use super::{make_transaction, FakeDepcNode, MockTokenClient};
+use crate::amount::{AmountConverter, DepcAmount};
+use crate::bridge::{
+    run_depc_syncing, BridgePair, CustodyEras, FeeConfig, PendingActionId, SyncPair, TransferLimits,
+};
+use crate::clock::system_clock;
+use crate::db;
+use crate::depc::COIN;
+
+const BENCH_MINER_ADDRESS: &str = "bench_miner";
+const BENCH_OWNER_ADDRESS: &str = "bench_owner";
+const BENCH_NUM_ADDRESSES: u32 = 100;
+
+/// The density of the synthetic blocks
+#[derive(Clone, Copy)]
+pub struct SyntheticChain {
+    pub num_blocks: u32,
+    pub txs_per_block: u32,
+    pub outputs_per_tx: u32,
+}
+
+pub struct SyncBenchReport {
+    pub blocks: u32,
+    pub transactions: u64,
+    pub outputs: u64,
+    pub elapsed: Duration,
+}
+
+impl SyncBenchReport {
+    pub fn blocks_per_sec(&self) -> f64 {
+        self.blocks as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn transactions_per_sec(&self) -> f64 {
+        self.transactions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+fn synthetic_txid(height: u32, i: u32) -> String {
+    format!("{:064x}", (height as u64) << 32 | i as u64)
+}
+
+/// Push the synthetic blocks to the node, the transaction `i` of each block spends all the
+/// outputs of the transaction `i` from the previous block, so the coins are marked as spent
+/// during the syncing as well
+pub fn push_synthetic_blocks(node: &FakeDepcNode, chain: SyntheticChain) {
+    // block 0 is never scanned
+    node.push_block(BENCH_MINER_ADDRESS, vec![]);
+    for height in 1..=chain.num_blocks {
+        let mut transactions = vec![];
+        for i in 0..chain.txs_per_block {
+            let txid = synthetic_txid(height, i);
+            let prev_txid = synthetic_txid(height - 1, i);
+            let vin: Vec<(&str, u32)> = if height > 1 {
+                (0..chain.outputs_per_tx)
+                    .map(|n| (prev_txid.as_str(), n))
+                    .collect()
+            } else {
+                vec![]
+            };
+            let addresses: Vec<String> = (0..chain.outputs_per_tx)
+                .map(|n| {
+                    format!(
+                        "bench_address_{}",
+                        (i * chain.outputs_per_tx + n) % BENCH_NUM_ADDRESSES
+                    )
+                })
+                .collect();
+            let vout: Vec<(&str, u64, &str)> = addresses
+                .iter()
+                .map(|address| (address.as_str(), COIN, ""))
+                .collect();
+            transactions.push(make_transaction(&txid, &vin, &vout));
+        }
+        node.push_block(BENCH_MINER_ADDRESS, transactions);
+    }
+}
+
+/// Sync the synthetic chain from `node` into `conn` and measure the throughput, the whole
+/// pipeline (RPC, decoding and database) is measured
+pub async fn run_sync_bench(
+    node: &FakeDepcNode,
+    conn: db::Conn,
+    chain: SyntheticChain,
+) -> SyncBenchReport {
+    let exit_sig = Arc::new(Mutex::new(false));
+    // no bridge transaction is made by the synthetic chain, nothing is sent to the channels
+    let (tx_deposit, _rx_deposit) = channel::<PendingActionId>(1);
+    let (tx_withdraw, _rx_withdraw) = channel::<PendingActionId>(1);
+
+    let start = Instant::now();
+    let syncing_task = tokio::spawn(run_depc_syncing(
+        Arc::clone(&exit_sig),
+        conn.clone(),
+        node.client(),
+        vec![SyncPair {
+            pair: BridgePair {
+                conn: conn.clone(),
+                depc_owner_address: BENCH_OWNER_ADDRESS.to_owned(),
+                solana_owner_address: Pubkey::new_unique().to_string(),
+                contract_client: MockTokenClient::new(),
+                custody_eras: CustodyEras::default(),
+                converter: AmountConverter::default(),
+                fees: FeeConfig::default(),
+                limits: TransferLimits::default(),
+            },
+            tx_deposit,
+            tx_withdraw,
+        }],
+        1,
+        None,
+        false,
+        false,
+        DepcAmount::ZERO,
+        None,
+        system_clock(),
+    ));
+    while conn.query_best_height().unwrap_or_default() < chain.num_blocks {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let elapsed = start.elapsed();
+    {
+        let mut exit = exit_sig.lock().unwrap();
+        *exit = true;
+    }
+    syncing_task.await.unwrap().unwrap();
+
+    let transactions = chain.num_blocks as u64 * chain.txs_per_block as u64;
+    SyncBenchReport {
+        blocks: chain.num_blocks,
+        transactions,
+        outputs: transactions * chain.outputs_per_tx as u64,
+        elapsed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_run_sync_bench() {
+        let node = FakeDepcNode::start();
+        let chain = SyntheticChain {
+            num_blocks: 5,
+            txs_per_block: 3,
+            outputs_per_tx: 2,
+        };
+        push_synthetic_blocks(&node, chain);
+
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let report = run_sync_bench(&node, conn.clone(), chain).await;
+        assert_eq!(report.blocks, 5);
+        assert_eq!(report.outputs, 30);
+        assert!(report.blocks_per_sec() > 0.0);
+        // only the outputs of the last block are left unspent
+        assert_eq!(
+            conn.query_balance("bench_address_0", 5).unwrap(),
+            DepcAmount::from_value64(COIN)
+        );
+    }
+}
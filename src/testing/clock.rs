@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::clock::Clock;
+
+/// A `Clock` only moves when the test sets or advances it, all clones share the same time
+#[derive(Clone, Default)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(now: u64) -> MockClock {
+        MockClock {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1000);
+        let shared = clock.clone();
+        assert_eq!(shared.now(), 1000);
+        clock.advance(60);
+        assert_eq!(shared.now(), 1060);
+        clock.set(5);
+        assert_eq!(shared.now(), 5);
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::depc;
+
+const GENESIS_TIME: u64 = 1531292789;
+const BLOCK_INTERVAL: u64 = 180;
+
+#[derive(Default)]
+struct Chain {
+    blocks: Vec<Value>,
+    transactions: HashMap<String, Value>,
+    unspents: Vec<Value>,
+    raw_transactions: Vec<String>,
+    /// The addresses `validateaddress` reports invalid, e.g. of the other network
+    invalid_addresses: Vec<String>,
+    initial_block_download: bool,
+    /// The hashes of the blocks those are pruned
+    pruned_blocks: Vec<String>,
+    /// The addresses the wallet has the keys of, `signmessage` signs for them
+    wallet_addresses: Vec<String>,
+    /// Answer with the id of another request as a misbehaving proxy does
+    mismatch_response_ids: bool,
+    /// The times the blocks are replaced, the hashes of the new blocks differ by it
+    reorgs: u64,
+    /// The addresses imported by `importaddress` and `importdescriptors`, in the order
+    watch_only_addresses: Vec<String>,
+    /// The signatures a multisig transaction is complete with, see `sign_fake_transaction`
+    multisig_required: usize,
+}
+
+/// An in-memory DePC node serves the canned blocks and transactions through JSON-RPC, it's
+/// good enough to drive `depc::Client` without a running DePINC node
+pub struct FakeDepcNode {
+    endpoint: String,
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl FakeDepcNode {
+    /// Start the node on a random local port, the node runs until the process exits
+    pub fn start() -> FakeDepcNode {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let chain = Arc::new(Mutex::new(Chain::default()));
+        let server_chain = Arc::clone(&chain);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle_connection(stream, &server_chain);
+            }
+        });
+        FakeDepcNode { endpoint, chain }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn client(&self) -> depc::Client {
+        depc::ClientBuilder::new()
+            .set_endpoint(&self.endpoint)
+            .build()
+    }
+
+    /// Append a new block contains the transactions made by `make_transaction`
+    ///
+    /// Returns the hash of the new block
+    pub fn push_block(&self, miner: &str, transactions: Vec<Value>) -> String {
+        let mut chain = self.chain.lock().unwrap();
+        let height = chain.blocks.len() as u64;
+        let hash = format!("{:064x}", (chain.reorgs << 32) + height + 1);
+        let prev_hash = chain.blocks.last().map(|block| block["hash"].clone());
+        let mut txids = vec![];
+        for transaction in transactions {
+            let txid = transaction["txid"].as_str().unwrap().to_owned();
+            txids.push(txid.clone());
+            chain.transactions.insert(txid, transaction);
+        }
+        chain.blocks.push(json!({
+            "hash": hash,
+            "height": height,
+            "miner": miner,
+            "time": GENESIS_TIME + height * BLOCK_INTERVAL,
+            "tx": txids,
+            "previousblockhash": prev_hash,
+        }));
+        hash
+    }
+
+    /// Remove the blocks from `height` as a reorg does, the blocks pushed after it have new
+    /// hashes. The transactions are kept, they can be packed in the new blocks again
+    pub fn reorg(&self, height: u32) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.blocks.truncate(height as usize);
+        chain.reorgs += 1;
+    }
+
+    /// Add a coin which will be returned by `listunspent`
+    pub fn add_unspent(&self, address: &str, txid: &str, vout: u32, value64: u64) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.unspents.push(json!({
+            "txid": txid,
+            "vout": vout,
+            "address": address,
+            "amount": value64 as f64 / depc::COIN as f64,
+            "confirmations": 1,
+        }));
+    }
+
+    /// Make `validateaddress` report the address invalid
+    pub fn add_invalid_address(&self, address: &str) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.invalid_addresses.push(address.to_owned());
+    }
+
+    /// Whether `getblockchaininfo` reports the node in the initial block download
+    pub fn set_initial_block_download(&self, initial_block_download: bool) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.initial_block_download = initial_block_download;
+    }
+
+    /// Let the wallet sign the messages for the address, the signature is the hex string of
+    /// `{address}:{message}`
+    pub fn add_wallet_address(&self, address: &str) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.wallet_addresses.push(address.to_owned());
+    }
+
+    /// Answer the requests with the ids of the next requests
+    pub fn set_mismatch_response_ids(&self, mismatch_response_ids: bool) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.mismatch_response_ids = mismatch_response_ids;
+    }
+
+    /// Make `getblock` of the block fail as a pruning node does
+    pub fn prune_block(&self, hash: &str) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.pruned_blocks.push(hash.to_owned());
+    }
+
+    /// Add a transaction made by `make_transaction` not in any block, `getrawtransaction`
+    /// returns it as a node does for the transactions in the mempool
+    pub fn add_mempool_transaction(&self, transaction: Value) {
+        let mut chain = self.chain.lock().unwrap();
+        let txid = transaction["txid"].as_str().unwrap().to_owned();
+        chain.transactions.insert(txid, transaction);
+    }
+
+    /// Make `getrawtransaction` of the transaction fail as it's missing
+    pub fn remove_transaction(&self, txid: &str) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.transactions.remove(txid);
+    }
+
+    /// The addresses imported as watch-only, an address imported twice is listed twice
+    pub fn watch_only_addresses(&self) -> Vec<String> {
+        let chain = self.chain.lock().unwrap();
+        chain.watch_only_addresses.clone()
+    }
+
+    /// The raw transactions received by `sendrawtransaction`
+    pub fn broadcasted_transactions(&self) -> Vec<String> {
+        let chain = self.chain.lock().unwrap();
+        chain.raw_transactions.clone()
+    }
+
+    /// The signatures the transactions spending the coins of a multisig script need, the
+    /// wallet signs them as `wallet` when `signrawtransactionwithwallet` is given the prevtxs
+    pub fn set_multisig_required(&self, required: usize) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.multisig_required = required;
+    }
+}
+
+/// Sign the raw transaction made by the fake node as `signer`, the signers are appended to the
+/// transaction as `|sig:<signer>`. `combinerawtransaction` merges them
+pub fn sign_fake_transaction(hex: &str, signer: &str) -> String {
+    let (unsigned, mut signers) = parse_fake_transaction(hex).unwrap();
+    signers.push(signer.to_owned());
+    make_fake_transaction(&unsigned, signers)
+}
+
+/// The unsigned transaction and the signers of it
+fn parse_fake_transaction(hex: &str) -> Option<(String, Vec<String>)> {
+    let content = String::from_utf8(hex::decode(hex).ok()?).ok()?;
+    let mut parts = content.split("|sig:");
+    let unsigned = parts.next()?.to_owned();
+    Some((unsigned, parts.map(|signer| signer.to_owned()).collect()))
+}
+
+fn make_fake_transaction(unsigned: &str, mut signers: Vec<String>) -> String {
+    signers.sort();
+    signers.dedup();
+    let signatures: String = signers
+        .iter()
+        .map(|signer| format!("|sig:{signer}"))
+        .collect();
+    hex::encode(format!("{unsigned}{signatures}"))
+}
+
+/// Make the json of a verbose transaction
+///
+/// Arguments:
+/// * txid - The id of the transaction
+/// * vin - The spent coins (txid, n), an empty list makes a coinbase transaction
+/// * vout - The outputs (address, value64, script hex)
+pub fn make_transaction(txid: &str, vin: &[(&str, u32)], vout: &[(&str, u64, &str)]) -> Value {
+    let vin: Vec<Value> = if vin.is_empty() {
+        vec![json!({"coinbase": "03e8030101"})]
+    } else {
+        vin.iter()
+            .map(|(txid, n)| json!({"txid": txid, "vout": n}))
+            .collect()
+    };
+    let vout: Vec<Value> = vout
+        .iter()
+        .enumerate()
+        .map(|(n, (address, value64, script_hex))| {
+            json!({
+                "value": *value64 as f64 / depc::COIN as f64,
+                "value64": value64,
+                "n": n,
+                // the outputs of the nonstandard scripts have no address
+                "scriptPubKey": if address.is_empty() {
+                    json!({"hex": script_hex})
+                } else {
+                    json!({"hex": script_hex, "addresses": [address]})
+                },
+            })
+        })
+        .collect();
+    json!({"txid": txid, "vin": vin, "vout": vout})
+}
+
+fn handle_connection(mut stream: TcpStream, chain: &Mutex<Chain>) {
+    let mut reader = BufReader::new(&mut stream);
+    let mut content_length = 0usize;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0u8; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+    let id = if chain.lock().unwrap().mismatch_response_ids {
+        json!(request["id"].as_u64().unwrap_or_default() + 1)
+    } else {
+        request["id"].clone()
+    };
+    let (status, resp) = match handle_request(&request, chain) {
+        Ok(result) => (
+            "200 OK",
+            json!({"jsonrpc": "2.0", "result": result, "id": id}),
+        ),
+        Err(message) => (
+            "500 Internal Server Error",
+            json!({"jsonrpc": "2.0", "result": null, "error": {"code": -1, "message": message}, "id": id}),
+        ),
+    };
+    let resp = resp.to_string();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        resp.len(),
+        resp
+    );
+}
+
+fn handle_request(request: &Value, chain: &Mutex<Chain>) -> Result<Value, String> {
+    let mut chain = chain.lock().unwrap();
+    let params = &request["params"];
+    match request["method"].as_str().unwrap_or_default() {
+        "getblockcount" => Ok(json!(chain.blocks.len() as i64 - 1)),
+        "getblockchaininfo" => {
+            let best_block_hash = chain
+                .blocks
+                .last()
+                .map_or(json!(format!("{:064x}", 0)), |block| block["hash"].clone());
+            Ok(json!({
+                "chain": "test",
+                "blocks": chain.blocks.len().saturating_sub(1),
+                "headers": chain.blocks.len().saturating_sub(1),
+                "bestblockhash": best_block_hash,
+                "initialblockdownload": chain.initial_block_download,
+                "verificationprogress": if chain.initial_block_download { 0.5 } else { 1.0 },
+            }))
+        }
+        "getnetworkinfo" => Ok(json!({
+            "version": 2010000,
+            "subversion": "/DePINC:2.1.0/",
+            "protocolversion": 70016,
+            "connections": 8,
+        })),
+        "validateaddress" => {
+            let address = params["address"].as_str().unwrap_or_default();
+            if address.is_empty() || chain.invalid_addresses.iter().any(|a| a == address) {
+                Ok(json!({"isvalid": false}))
+            } else {
+                Ok(json!({
+                    "isvalid": true,
+                    "address": address,
+                    "ismine": false,
+                    "iswatchonly": false,
+                }))
+            }
+        }
+        "signmessage" => {
+            let address = params["address"].as_str().unwrap_or_default();
+            if !chain.wallet_addresses.iter().any(|a| a == address) {
+                return Err("Private key not available".to_owned());
+            }
+            let message = params["message"].as_str().unwrap_or_default();
+            Ok(json!(hex::encode(format!("{address}:{message}"))))
+        }
+        "verifymessage" => {
+            let address = params["address"].as_str().unwrap_or_default();
+            if address.is_empty() || chain.invalid_addresses.iter().any(|a| a == address) {
+                return Err("Invalid address".to_owned());
+            }
+            let signature = hex::decode(params["signature"].as_str().unwrap_or_default())
+                .map_err(|_| "Malformed base64 encoding".to_owned())?;
+            let message = params["message"].as_str().unwrap_or_default();
+            Ok(json!(
+                signature == format!("{address}:{message}").as_bytes()
+            ))
+        }
+        "getblockhash" => {
+            let height = params["height"].as_u64().unwrap_or(u64::MAX) as usize;
+            match chain.blocks.get(height) {
+                Some(block) => Ok(block["hash"].clone()),
+                None => Err("Block height out of range".to_owned()),
+            }
+        }
+        "getblock" => {
+            let hash = params["blockhash"].as_str().unwrap_or_default();
+            if chain.pruned_blocks.iter().any(|pruned| pruned == hash) {
+                return Err("Block not available (pruned data)".to_owned());
+            }
+            chain
+                .blocks
+                .iter()
+                .find(|block| block["hash"] == hash)
+                .cloned()
+                .ok_or_else(|| "Block not found".to_owned())
+        }
+        "getrawtransaction" => chain
+            .transactions
+            .get(params["txid"].as_str().unwrap_or_default())
+            .cloned()
+            .ok_or_else(|| "No such mempool or blockchain transaction".to_owned()),
+        "listunspent" => {
+            let addresses = params["addresses"].as_array().cloned().unwrap_or_default();
+            Ok(Value::Array(
+                chain
+                    .unspents
+                    .iter()
+                    .filter(|unspent| addresses.contains(&unspent["address"]))
+                    .cloned()
+                    .collect(),
+            ))
+        }
+        "importaddress" => {
+            let address = params["address"].as_str().unwrap_or_default().to_owned();
+            chain.watch_only_addresses.push(address);
+            Ok(Value::Null)
+        }
+        // the checksum is made up, the descriptors are only `addr(<address>)`
+        "getdescriptorinfo" => {
+            let descriptor = params["descriptor"].as_str().unwrap_or_default();
+            Ok(json!({"descriptor": format!("{descriptor}#00000000"), "checksum": "00000000"}))
+        }
+        "importdescriptors" => {
+            let requests = params["requests"].as_array().cloned().unwrap_or_default();
+            let mut results = vec![];
+            for request in requests {
+                let descriptor = request["desc"].as_str().unwrap_or_default();
+                let address = descriptor
+                    .strip_prefix("addr(")
+                    .and_then(|descriptor| descriptor.split_once(')'))
+                    .map(|(address, _)| address.to_owned());
+                match address {
+                    Some(address) => {
+                        chain.watch_only_addresses.push(address);
+                        results.push(json!({"success": true}));
+                    }
+                    None => results.push(json!({
+                        "success": false,
+                        "error": {"code": -5, "message": "Invalid descriptor"},
+                    })),
+                }
+            }
+            Ok(Value::Array(results))
+        }
+        // the outputs of the blocks to the address, it must be in the wallet
+        "getreceivedbyaddress" => {
+            let address = params["address"].as_str().unwrap_or_default();
+            if !chain.watch_only_addresses.iter().any(|a| a == address)
+                && !chain.wallet_addresses.iter().any(|a| a == address)
+            {
+                return Err("Address not found in wallet".to_owned());
+            }
+            let mut received = 0;
+            for block in chain.blocks.iter() {
+                for txid in block["tx"].as_array().into_iter().flatten() {
+                    let Some(transaction) =
+                        chain.transactions.get(txid.as_str().unwrap_or_default())
+                    else {
+                        continue;
+                    };
+                    for txout in transaction["vout"].as_array().into_iter().flatten() {
+                        if txout["scriptPubKey"]["addresses"][0] == address {
+                            received += txout["value64"].as_u64().unwrap_or_default();
+                        }
+                    }
+                }
+            }
+            Ok(json!(received as f64 / depc::COIN as f64))
+        }
+        // the raw transaction is just the encoded request, it's enough to be signed and sent
+        "createrawtransaction" => Ok(json!(hex::encode(params.to_string()))),
+        "signrawtransactionwithwallet" if params["prevtxs"].is_array() => {
+            let hex = params["hexstring"].as_str().unwrap_or_default();
+            let (unsigned, mut signers) =
+                parse_fake_transaction(hex).ok_or_else(|| "TX decode failed".to_owned())?;
+            signers.push("wallet".to_owned());
+            let hex = make_fake_transaction(&unsigned, signers);
+            let (_, signers) = parse_fake_transaction(&hex).unwrap();
+            Ok(json!({"hex": hex, "complete": signers.len() >= chain.multisig_required}))
+        }
+        "signrawtransactionwithwallet" => Ok(json!({"hex": params["hexstring"], "complete": true})),
+        "combinerawtransaction" => {
+            let txs = params["txs"].as_array().cloned().unwrap_or_default();
+            let mut merged: Option<(String, Vec<String>)> = None;
+            for tx in txs.iter() {
+                let (unsigned, signers) = parse_fake_transaction(tx.as_str().unwrap_or_default())
+                    .ok_or_else(|| "TX decode failed".to_owned())?;
+                match merged.as_mut() {
+                    None => merged = Some((unsigned, signers)),
+                    Some((merged_unsigned, _)) if *merged_unsigned != unsigned => {
+                        return Err("Input transactions not compatible".to_owned())
+                    }
+                    Some((_, merged_signers)) => merged_signers.extend(signers),
+                }
+            }
+            let (unsigned, signers) = merged.ok_or_else(|| "Missing transactions".to_owned())?;
+            Ok(json!(make_fake_transaction(&unsigned, signers)))
+        }
+        "decoderawtransaction" => {
+            let hex = params["hexstring"].as_str().unwrap_or_default();
+            Ok(json!({"txid": fake_txid(hex)}))
+        }
+        "sendrawtransaction" => {
+            let hex = params["hexstring"].as_str().unwrap_or_default().to_owned();
+            let txid = fake_txid(&hex);
+            chain.raw_transactions.push(hex);
+            Ok(json!(txid))
+        }
+        method => Err(format!("Method not found: {}", method)),
+    }
+}
+
+/// The txid of the raw transaction made by the fake node, `decoderawtransaction` and
+/// `sendrawtransaction` agree on it
+fn fake_txid(hex: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    hex.hash(&mut hasher);
+    format!("{:064x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_node_serves_blocks_and_transactions() {
+        let node = FakeDepcNode::start();
+        node.push_block("miner", vec![]);
+        let txid = format!("{:064x}", 100);
+        let block_hash = node.push_block(
+            "miner",
+            vec![make_transaction(
+                &txid,
+                &[],
+                &[("address", 25 * depc::COIN, "")],
+            )],
+        );
+
+        let client = node.client();
+        assert_eq!(client.get_height().unwrap(), 1);
+        assert_eq!(client.get_block_hash(1).unwrap(), block_hash);
+        let block = client.get_block(&block_hash).unwrap();
+        assert_eq!(block.tx, vec![txid.clone()]);
+        let transaction = client.get_transaction(&txid).unwrap();
+        assert!(transaction.vin[0].is_coinbase());
+        assert_eq!(transaction.vout[0].value64, 25 * depc::COIN);
+    }
+
+    #[test]
+    fn test_fake_node_transfer() {
+        let node = FakeDepcNode::start();
+        node.add_unspent("from", &format!("{:064x}", 1), 0, 10 * depc::COIN);
+
+        let client = node.client();
+        client
+            .transfer(&"from".to_owned(), &"to".to_owned(), depc::COIN)
+            .unwrap();
+        assert_eq!(node.broadcasted_transactions().len(), 1);
+        assert!(client
+            .transfer(&"nobody".to_owned(), &"to".to_owned(), depc::COIN)
+            .is_err());
+    }
+
+    #[test]
+    fn test_fake_node_network_and_address_info() {
+        let node = FakeDepcNode::start();
+        node.push_block("miner", vec![]);
+        node.add_invalid_address("mainnet-address");
+        node.set_initial_block_download(true);
+
+        let client = node.client();
+        assert!(client.validate_address("address").unwrap().is_valid);
+        assert!(!client.validate_address("mainnet-address").unwrap().is_valid);
+        assert_eq!(client.get_network_info().unwrap().connections, 8);
+        let info = client.get_blockchain_info().unwrap();
+        assert_eq!(info.blocks, 0);
+        assert!(info.initial_block_download);
+    }
+
+    #[test]
+    fn test_fake_node_sign_and_verify_message() {
+        let node = FakeDepcNode::start();
+        node.add_wallet_address("address");
+        node.add_invalid_address("mainnet-address");
+
+        let client = node.client();
+        let address = "address".to_owned();
+        let signature = client.sign_message(&address, "hello").unwrap();
+        assert!(client
+            .verify_message(&address, &signature, "hello")
+            .unwrap());
+        assert!(!client.verify_message(&address, &signature, "bye").unwrap());
+        // the node refuses the malformed signature and the invalid address
+        assert!(!client.verify_message(&address, "@@", "hello").unwrap());
+        assert!(!client
+            .verify_message(&"mainnet-address".to_owned(), &signature, "hello")
+            .unwrap());
+        assert!(matches!(
+            client.sign_message(&"other".to_owned(), "hello"),
+            Err(depc::Error::CannotSignMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_fake_node_mismatched_response_ids() {
+        let node = FakeDepcNode::start();
+        node.push_block("miner", vec![]);
+        let client = node.client();
+        assert_eq!(client.get_height().unwrap(), 0);
+
+        node.set_mismatch_response_ids(true);
+        assert!(matches!(client.get_height(), Err(depc::Error::RpcError)));
+        // the error responses are rejected the same way
+        assert!(matches!(
+            client.get_block("unknown"),
+            Err(depc::Error::RpcError)
+        ));
+        node.set_mismatch_response_ids(false);
+        assert_eq!(client.get_height().unwrap(), 0);
+    }
+
+    #[cfg(feature = "chaos")]
+    #[test]
+    fn test_fake_node_with_faults() {
+        use crate::chaos::FaultInjector;
+
+        let node = FakeDepcNode::start();
+        node.push_block("miner", vec![]);
+        for (faults, ok) in [("", true), ("drop=1", false), ("corrupt=1", false)] {
+            let client = depc::ClientBuilder::new()
+                .set_endpoint(node.endpoint())
+                .set_fault_injector(FaultInjector::new(faults.parse().unwrap()))
+                .build();
+            assert_eq!(client.get_height().is_ok(), ok, "faults `{}`", faults);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod bench;
+mod clock;
+mod depc_node;
+mod token;
+
+pub use bench::*;
+pub use clock::*;
+pub use depc_node::*;
+pub use token::*;
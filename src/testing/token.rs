@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::amount::DepcAmount;
+use crate::solana::{ErrorKind, RecoveredTransfer, TokenClient, TransferReceipt};
+
+/// The fee of every transaction `MockTokenClient` sends, in lamports
+pub const MOCK_TRANSFER_FEE: u64 = 5000;
+
+/// The error of `MockTokenClient`, of the kind the test sets
+#[derive(Debug, Clone)]
+pub struct MockError(pub String, pub ErrorKind);
+
+impl MockError {
+    pub fn transient(message: &str) -> MockError {
+        MockError(message.to_owned(), ErrorKind::Transient)
+    }
+
+    pub fn permanent(message: &str) -> MockError {
+        MockError(message.to_owned(), ErrorKind::Permanent)
+    }
+}
+
+impl std::fmt::Display for MockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mock error: {}", self.0)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    send_results: VecDeque<Result<Signature, MockError>>,
+    verify_results: HashMap<Signature, Result<u64, MockError>>,
+    senders: HashMap<Signature, Pubkey>,
+    sent_tokens: Vec<(Pubkey, u64, Signature)>,
+    reserved_addresses: Vec<(Pubkey, &'static str)>,
+    slot: u64,
+    token_accounts: HashSet<Pubkey>,
+    recovered: Vec<RecoveredTransfer<Signature, Pubkey, u64>>,
+    acknowledged: Vec<Signature>,
+    stranded: Vec<(Pubkey, u64)>,
+    mint_decimals: Option<u8>,
+}
+
+impl State {
+    /// The receipt of a sent transaction, it lands in the next slot and creates the token
+    /// accounts of the recipients those are not seen before
+    fn receipt<'a>(
+        &mut self,
+        signature: Signature,
+        recipients: impl IntoIterator<Item = &'a Pubkey>,
+    ) -> TransferReceipt<Signature> {
+        self.slot += 1;
+        let mut created_ata = false;
+        for recipient_address in recipients {
+            created_ata |= self.token_accounts.insert(*recipient_address);
+        }
+        TransferReceipt {
+            txid: signature,
+            fee: Some(MOCK_TRANSFER_FEE),
+            slot: Some(self.slot),
+            created_ata,
+            raw_logs: Some(vec![]),
+        }
+    }
+}
+
+/// A `TokenClient` doesn't touch solana network, the outcomes of `send_token` and `verify` are
+/// scripted by the test, all clones share the same state
+#[derive(Clone, Default)]
+pub struct MockTokenClient {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockTokenClient {
+    pub fn new() -> MockTokenClient {
+        MockTokenClient::default()
+    }
+
+    /// Queue the result of the next `send_token` or `send_tokens` call, a new signature is made
+    /// and returned when nothing is queued
+    pub fn push_send_result(&self, result: Result<Signature, MockError>) {
+        let mut state = self.state.lock().unwrap();
+        state.send_results.push_back(result);
+    }
+
+    /// Set the result `verify` returns for the signature, the signatures those are not set are
+    /// treated as invalid
+    pub fn set_verify_result(&self, signature: Signature, result: Result<u64, MockError>) {
+        let mut state = self.state.lock().unwrap();
+        state.verify_results.insert(signature, result);
+    }
+
+    /// Set the sender of the tokens the signature transfers, `verify_from` counts nothing
+    /// for the other senders
+    pub fn set_sender(&self, signature: Signature, sender: Pubkey) {
+        let mut state = self.state.lock().unwrap();
+        state.senders.insert(signature, sender);
+    }
+
+    pub fn add_reserved_address(&self, address: Pubkey, description: &'static str) {
+        let mut state = self.state.lock().unwrap();
+        state.reserved_addresses.push((address, description));
+    }
+
+    /// Add a transfer `recover_transfers` returns until it's acknowledged, like the one sent
+    /// by the last run
+    pub fn push_recovered_transfer(&self, transfer: RecoveredTransfer<Signature, Pubkey, u64>) {
+        let mut state = self.state.lock().unwrap();
+        state.recovered.push(transfer);
+    }
+
+    pub fn acknowledged_transfers(&self) -> Vec<Signature> {
+        let state = self.state.lock().unwrap();
+        state.acknowledged.clone()
+    }
+
+    /// Strand `amount` in the auxiliary account, `stranded_balances` returns it until it's
+    /// consolidated
+    pub fn add_stranded_balance(&self, account: Pubkey, amount: u64) {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .stranded
+            .iter_mut()
+            .find(|(address, _)| *address == account)
+        {
+            Some((_, balance)) => *balance += amount,
+            None => state.stranded.push((account, amount)),
+        }
+    }
+
+    /// The decimals `mint_decimals` returns, the mint has the same decimals as DePC unless
+    /// it's set
+    pub fn set_mint_decimals(&self, decimals: u8) {
+        let mut state = self.state.lock().unwrap();
+        state.mint_decimals = Some(decimals);
+    }
+
+    /// All the tokens those are sent successfully, (recipient, amount, signature)
+    pub fn sent_tokens(&self) -> Vec<(Pubkey, u64, Signature)> {
+        let state = self.state.lock().unwrap();
+        state.sent_tokens.clone()
+    }
+}
+
+impl TokenClient for MockTokenClient {
+    type Error = MockError;
+    type Address = Pubkey;
+    type Amount = u64;
+    type TxID = Signature;
+
+    fn send_token(
+        &self,
+        recipient_address: &Self::Address,
+        amount: Self::Amount,
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error> {
+        let mut state = self.state.lock().unwrap();
+        let signature = state
+            .send_results
+            .pop_front()
+            .unwrap_or_else(|| Ok(Signature::new_unique()))?;
+        state
+            .sent_tokens
+            .push((*recipient_address, amount, signature));
+        Ok(state.receipt(signature, [recipient_address]))
+    }
+
+    /// One queued result is taken for all the recipients, like one transaction
+    fn send_tokens(
+        &self,
+        recipients: &[(Pubkey, u64)],
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error> {
+        let mut state = self.state.lock().unwrap();
+        let signature = state
+            .send_results
+            .pop_front()
+            .unwrap_or_else(|| Ok(Signature::new_unique()))?;
+        for (recipient_address, amount) in recipients {
+            state
+                .sent_tokens
+                .push((*recipient_address, *amount, signature));
+        }
+        Ok(state.receipt(signature, recipients.iter().map(|(address, _)| address)))
+    }
+
+    fn verify(&self, signature: &Signature, _owner: &Pubkey) -> Result<u64, Self::Error> {
+        let state = self.state.lock().unwrap();
+        match state.verify_results.get(signature) {
+            Some(result) => result.clone(),
+            None => Err(MockError::permanent(&format!(
+                "unknown signature {}",
+                signature
+            ))),
+        }
+    }
+
+    fn verify_from(
+        &self,
+        signature: &Signature,
+        sender: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<u64, Self::Error> {
+        let amount = self.verify(signature, owner)?;
+        let state = self.state.lock().unwrap();
+        match state.senders.get(signature) {
+            Some(actual) if actual == sender => Ok(amount),
+            _ => Ok(0),
+        }
+    }
+
+    fn mint_decimals(&self) -> Result<u8, Self::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state.mint_decimals.unwrap_or(DepcAmount::DECIMALS))
+    }
+
+    fn reserved_addresses(&self) -> Vec<(Pubkey, &'static str)> {
+        let state = self.state.lock().unwrap();
+        state.reserved_addresses.clone()
+    }
+
+    fn error_kind(error: &MockError) -> ErrorKind {
+        error.1
+    }
+
+    fn recover_transfers(
+        &self,
+    ) -> Result<Vec<RecoveredTransfer<Signature, Pubkey, u64>>, Self::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .recovered
+            .iter()
+            .filter(|transfer| !state.acknowledged.contains(&transfer.txid))
+            .cloned()
+            .collect())
+    }
+
+    fn acknowledge_transfer(&self, txid: &Signature) -> Result<(), Self::Error> {
+        let mut state = self.state.lock().unwrap();
+        state.acknowledged.push(*txid);
+        Ok(())
+    }
+
+    fn stranded_balances(&self) -> Result<Vec<(Pubkey, u64)>, Self::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state.stranded.clone())
+    }
+
+    /// One queued result is taken like `send_tokens`, the amounts are taken from the balances
+    fn consolidate(
+        &self,
+        accounts: &[(Pubkey, u64)],
+    ) -> Result<TransferReceipt<Self::TxID>, Self::Error> {
+        let mut state = self.state.lock().unwrap();
+        let signature = state
+            .send_results
+            .pop_front()
+            .unwrap_or_else(|| Ok(Signature::new_unique()))?;
+        // the transfers are made by one transaction, either all of them or none
+        let mut stranded = state.stranded.clone();
+        for (account, amount) in accounts {
+            let Some((_, balance)) = stranded.iter_mut().find(|(address, _)| address == account)
+            else {
+                return Err(MockError::permanent(&format!("unknown account {account}")));
+            };
+            *balance = balance
+                .checked_sub(*amount)
+                .ok_or_else(|| MockError::permanent(&format!("insufficient funds of {account}")))?;
+        }
+        state.stranded = stranded;
+        Ok(state.receipt(signature, []))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_token_scripted() {
+        let client = MockTokenClient::new();
+        let recipient = Pubkey::new_unique();
+        client.push_send_result(Err(MockError::transient("node is busy")));
+
+        assert!(client.send_token(&recipient, 100).is_err());
+        let receipt = client.send_token(&recipient, 100).unwrap();
+        let signature = receipt.txid;
+        assert_eq!(client.sent_tokens(), vec![(recipient, 100, signature)]);
+        assert_eq!(
+            (receipt.fee, receipt.slot),
+            (Some(MOCK_TRANSFER_FEE), Some(1))
+        );
+        assert!(receipt.created_ata);
+
+        // a batch is sent by one signature, or nothing of it is sent
+        let other = Pubkey::new_unique();
+        client.push_send_result(Err(MockError::transient("node is busy")));
+        assert!(client.send_tokens(&[(recipient, 1), (other, 2)]).is_err());
+        let receipt = client.send_tokens(&[(recipient, 1), (other, 2)]).unwrap();
+        let batch = receipt.txid;
+        assert_eq!(receipt.slot, Some(2));
+        assert!(receipt.created_ata);
+        // the token accounts are all there
+        let last = client.send_token(&other, 3).unwrap();
+        assert!(!last.created_ata);
+        assert_eq!(
+            client.sent_tokens(),
+            vec![
+                (recipient, 100, signature),
+                (recipient, 1, batch),
+                (other, 2, batch),
+                (other, 3, last.txid)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_scripted() {
+        let client = MockTokenClient::new();
+        let owner = Pubkey::new_unique();
+        let signature = Signature::new_unique();
+        client.set_verify_result(signature, Ok(5000));
+
+        assert_eq!(client.verify(&signature, &owner).unwrap(), 5000);
+        assert!(client.verify(&Signature::new_unique(), &owner).is_err());
+    }
+}
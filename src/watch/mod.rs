@@ -0,0 +1,5 @@
+mod notifier;
+mod watchlist;
+
+pub use notifier::*;
+pub use watchlist::*;
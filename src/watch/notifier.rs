@@ -0,0 +1,270 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::time::sleep;
+use ureq::AgentBuilder;
+
+use crate::clock::SharedClock;
+use crate::db;
+use crate::BridgeError;
+
+/// An event is given up after it fails to be delivered so many times
+pub const MAX_WEBHOOK_ATTEMPTS: u32 = 5;
+/// The events delivered at most on each check
+const DELIVERY_BATCH: u32 = 100;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Post the events of the watched addresses to their webhooks
+pub trait WatchNotifier: Send + Sync {
+    fn notify(&self, webhook: &str, event: &Value) -> Result<(), BridgeError>;
+}
+
+/// Post the event as JSON, any response other than 2xx is a failure
+pub struct HttpWatchNotifier {
+    use_proxy: bool,
+}
+
+impl HttpWatchNotifier {
+    pub fn new() -> HttpWatchNotifier {
+        HttpWatchNotifier { use_proxy: false }
+    }
+
+    pub fn set_use_proxy(mut self, use_proxy: bool) -> Self {
+        self.use_proxy = use_proxy;
+        self
+    }
+}
+
+impl Default for HttpWatchNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchNotifier for HttpWatchNotifier {
+    fn notify(&self, webhook: &str, event: &Value) -> Result<(), BridgeError> {
+        let agent = AgentBuilder::new()
+            .try_proxy_from_env(self.use_proxy)
+            .timeout(WEBHOOK_TIMEOUT)
+            .build();
+        agent
+            .post(webhook)
+            .send_json(event.clone())
+            .map_err(|e| BridgeError::Webhook(format!("cannot post to {webhook}, reason: {e}")))?;
+        Ok(())
+    }
+}
+
+/// The body posted to the webhook
+pub fn make_watch_event_json(event: &db::WatchEventRecord) -> Value {
+    json!({
+        "id": event.id,
+        "chain": event.chain,
+        "address": event.address,
+        "txid": event.txid,
+        "asset": event.asset,
+        "direction": event.direction,
+        "net_change": event.net_change,
+        "timestamp": event.timestamp,
+    })
+}
+
+/// Deliver the events those are not delivered yet, returns the number of the delivered ones
+pub fn deliver_watch_events(
+    conn: &db::Conn,
+    notifier: &dyn WatchNotifier,
+    now: u64,
+) -> Result<usize, BridgeError> {
+    let mut delivered = 0;
+    for (event, webhook) in
+        conn.query_undelivered_watch_events(MAX_WEBHOOK_ATTEMPTS, DELIVERY_BATCH)?
+    {
+        match notifier.notify(&webhook, &make_watch_event_json(&event)) {
+            Ok(()) => {
+                debug!("watch event {} is posted to {webhook}", event.id);
+                conn.finish_watch_event_delivery(event.id, Some(now), None)?;
+                delivered += 1;
+            }
+            Err(e) => {
+                warn!(
+                    "cannot deliver watch event {}, retry later, reason: {}",
+                    event.id,
+                    e.full_message()
+                );
+                conn.finish_watch_event_delivery(event.id, None, Some(e.code().as_str()))?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+pub async fn run_watch_notifications(
+    exit_sig: Arc<Mutex<bool>>,
+    conn: db::Conn,
+    notifier: Arc<dyn WatchNotifier>,
+    interval: Duration,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    loop {
+        {
+            let exit = exit_sig.lock().unwrap();
+            if *exit {
+                break;
+            }
+        }
+        let (conn, notifier, now) = (conn.clone(), Arc::clone(&notifier), clock.now());
+        // the webhooks are posted by the blocking client
+        let res = tokio::task::spawn_blocking(move || {
+            deliver_watch_events(&conn, notifier.as_ref(), now)
+        })
+        .await
+        .map_err(|_| BridgeError::ChannelClosed("watch_notifications"))?;
+        if let Err(e) = res {
+            warn!(
+                "cannot deliver the watch events, reason: {}",
+                e.full_message()
+            );
+        }
+        sleep(interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::watch::{WatchChain, Watchlist};
+    use crate::ErrorCode;
+
+    const TEST_WATCHED_ADDRESS: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    /// Fail the first `failures` calls, then record the events
+    struct RecordingNotifier {
+        failures: AtomicU32,
+        events: Mutex<Vec<(String, Value)>>,
+    }
+
+    impl WatchNotifier for RecordingNotifier {
+        fn notify(&self, webhook: &str, event: &Value) -> Result<(), BridgeError> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(BridgeError::Webhook("unavailable".to_owned()));
+            }
+            self.events
+                .lock()
+                .unwrap()
+                .push((webhook.to_owned(), event.clone()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_deliver_watch_events() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        Watchlist::new(conn.clone())
+            .watch(
+                WatchChain::Depc,
+                TEST_WATCHED_ADDRESS,
+                "hot wallet",
+                Some("https://example.com/hook"),
+                TEST_NOW,
+            )
+            .unwrap();
+        for txid in ["a1", "b1"] {
+            conn.add_watch_event(
+                "depc",
+                TEST_WATCHED_ADDRESS,
+                txid,
+                "depc",
+                "in",
+                "10",
+                TEST_NOW,
+            )
+            .unwrap();
+        }
+        // the address without a webhook is not notified
+        conn.add_watch_event("depc", "unwatched", "c1", "depc", "in", "1", TEST_NOW)
+            .unwrap();
+
+        let notifier = RecordingNotifier {
+            failures: AtomicU32::new(1),
+            events: Mutex::new(vec![]),
+        };
+        assert_eq!(deliver_watch_events(&conn, &notifier, TEST_NOW).unwrap(), 1);
+        let failed = conn
+            .query_watch_events(Some(TEST_WATCHED_ADDRESS), 10)
+            .unwrap();
+        assert_eq!(failed[1].last_error_code.as_deref(), Some("WEBHOOK_FAILED"));
+        assert_eq!(
+            deliver_watch_events(&conn, &notifier, TEST_NOW + 60).unwrap(),
+            1
+        );
+        assert_eq!(
+            deliver_watch_events(&conn, &notifier, TEST_NOW + 120).unwrap(),
+            0
+        );
+        let events = notifier.events.lock().unwrap();
+        let txids: Vec<_> = events
+            .iter()
+            .map(|(_, event)| event["txid"].clone())
+            .collect();
+        assert_eq!(txids, vec![json!("b1"), json!("a1")]);
+        assert_eq!(events[0].0, "https://example.com/hook");
+        let delivered = conn
+            .query_watch_events(Some(TEST_WATCHED_ADDRESS), 10)
+            .unwrap();
+        assert_eq!(delivered[0].delivered_at, Some(TEST_NOW));
+        assert_eq!(delivered[1].delivered_at, Some(TEST_NOW + 60));
+        assert!(delivered
+            .iter()
+            .all(|event| event.last_error_code.is_none()));
+    }
+
+    #[test]
+    fn test_watch_event_given_up() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        Watchlist::new(conn.clone())
+            .watch(
+                WatchChain::Depc,
+                TEST_WATCHED_ADDRESS,
+                "",
+                Some("https://example.com/hook"),
+                TEST_NOW,
+            )
+            .unwrap();
+        conn.add_watch_event(
+            "depc",
+            TEST_WATCHED_ADDRESS,
+            "a1",
+            "depc",
+            "out",
+            "-1",
+            TEST_NOW,
+        )
+        .unwrap();
+        let notifier = RecordingNotifier {
+            failures: AtomicU32::new(u32::MAX),
+            events: Mutex::new(vec![]),
+        };
+        for _ in 0..MAX_WEBHOOK_ATTEMPTS {
+            assert_eq!(deliver_watch_events(&conn, &notifier, TEST_NOW).unwrap(), 0);
+        }
+        assert!(conn
+            .query_undelivered_watch_events(MAX_WEBHOOK_ATTEMPTS, 10)
+            .unwrap()
+            .is_empty());
+        let given_up = conn.query_watch_events(None, 10).unwrap();
+        assert_eq!(
+            given_up[0].last_error_code.as_deref(),
+            Some(ErrorCode::WebhookFailed.as_str())
+        );
+    }
+}
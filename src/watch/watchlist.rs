@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use log::info;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amount::DepcAmount;
+use crate::db;
+use crate::solana::{AnalyzedInstruction, AnalyzedTransaction};
+use crate::BridgeError;
+
+/// The asset of the DePC events
+pub const ASSET_DEPC: &str = "depc";
+/// The assets of the Solana events, the same as the types of `/solana/history`
+pub const ASSET_SOL: &str = "sol";
+pub const ASSET_TOKEN: &str = "token";
+
+/// The chain of a watched address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchChain {
+    Depc,
+    Solana,
+}
+
+impl WatchChain {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchChain::Depc => "depc",
+            WatchChain::Solana => "solana",
+        }
+    }
+}
+
+impl fmt::Display for WatchChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WatchChain {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depc" => Ok(WatchChain::Depc),
+            "solana" => Ok(WatchChain::Solana),
+            _ => Err(BridgeError::InvalidConfig(format!(
+                "unknown chain `{s}`, it should be `depc` or `solana`"
+            ))),
+        }
+    }
+}
+
+/// The addresses the operators watch, the activities of them are recorded to table
+/// `watch_events` by the DePC syncing and the Solana history, and posted to their webhooks by
+/// `run_watch_notifications`
+#[derive(Clone)]
+pub struct Watchlist {
+    conn: db::Conn,
+}
+
+impl Watchlist {
+    pub fn new(conn: db::Conn) -> Watchlist {
+        Watchlist { conn }
+    }
+
+    /// Watch the address, or update the label and the webhook of the watched one
+    pub fn watch(
+        &self,
+        chain: WatchChain,
+        address: &str,
+        label: &str,
+        webhook: Option<&str>,
+        now: u64,
+    ) -> Result<(), BridgeError> {
+        let valid = match chain {
+            WatchChain::Depc => {
+                !address.is_empty() && address.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+            WatchChain::Solana => Pubkey::from_str(address).is_ok(),
+        };
+        if !valid {
+            return Err(BridgeError::InvalidAddress(address.to_owned()));
+        }
+        if let Some(webhook) = webhook {
+            if !webhook.starts_with("http://") && !webhook.starts_with("https://") {
+                return Err(BridgeError::InvalidConfig(format!(
+                    "webhook `{webhook}` should be an http(s) url"
+                )));
+            }
+        }
+        self.conn
+            .save_watched_address(chain.as_str(), address, label, webhook, now)?;
+        Ok(())
+    }
+
+    /// Returns whether the address was watched
+    pub fn unwatch(&self, chain: WatchChain, address: &str) -> Result<bool, BridgeError> {
+        Ok(self.conn.delete_watched_address(chain.as_str(), address)?)
+    }
+
+    pub fn list(&self) -> Result<Vec<db::WatchedAddressRecord>, BridgeError> {
+        Ok(self.conn.query_watchlist()?)
+    }
+
+    pub fn watched(&self, chain: WatchChain) -> Result<HashSet<String>, BridgeError> {
+        Ok(self
+            .conn
+            .query_watched_addresses(chain.as_str())?
+            .into_iter()
+            .collect())
+    }
+
+    /// Record the activities of the `watched` addresses in DePC transaction `txid`, it's called
+    /// after the inputs of the transaction are marked spent, returns the number of the new
+    /// events
+    pub fn record_depc_transaction(
+        &self,
+        watched: &HashSet<String>,
+        txid: &str,
+        outputs: &[(String, DepcAmount)],
+        timestamp: u64,
+    ) -> Result<usize, BridgeError> {
+        if watched.is_empty() {
+            return Ok(0);
+        }
+        let mut changes: BTreeMap<String, i128> = BTreeMap::new();
+        for (owner, value) in self.conn.query_spent_values_by_owner(txid)? {
+            if watched.contains(&owner) {
+                *changes.entry(owner).or_default() -= value.value64() as i128;
+            }
+        }
+        for (owner, value) in outputs.iter() {
+            if watched.contains(owner) {
+                *changes.entry(owner.clone()).or_default() += value.value64() as i128;
+            }
+        }
+        let mut recorded = 0;
+        for (address, change) in changes {
+            let direction = match change {
+                change if change > 0 => "in",
+                change if change < 0 => "out",
+                _ => "self",
+            };
+            if self.record(
+                WatchChain::Depc,
+                &address,
+                txid,
+                ASSET_DEPC,
+                direction,
+                change,
+                timestamp,
+            )? {
+                recorded += 1;
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// Record the activity of `address` in the Solana transaction if it's watched, returns the
+    /// number of the new events
+    pub fn record_solana_transaction(
+        &self,
+        address: &Pubkey,
+        transaction: &AnalyzedTransaction,
+    ) -> Result<usize, BridgeError> {
+        let Some(effect) = transaction.effect_on(address) else {
+            return Ok(0);
+        };
+        let address = address.to_string();
+        if !self.watched(WatchChain::Solana)?.contains(&address) {
+            return Ok(0);
+        }
+        let (mut has_token, mut has_sol) = (false, false);
+        for ix in transaction.instructions.iter() {
+            match ix {
+                AnalyzedInstruction::SplToken(_) => has_token = true,
+                AnalyzedInstruction::Solana(_) => has_sol = true,
+                AnalyzedInstruction::Other(_) => {}
+            }
+        }
+        let txid = transaction.signature.to_string();
+        let direction = effect.direction.to_string();
+        let timestamp = transaction.timestamp.max(0) as u64;
+        let mut recorded = 0;
+        for (asset, present, change) in [
+            (ASSET_TOKEN, has_token, effect.token_change),
+            (ASSET_SOL, has_sol, effect.sol_change),
+        ] {
+            if present
+                && self.record(
+                    WatchChain::Solana,
+                    &address,
+                    &txid,
+                    asset,
+                    &direction,
+                    change,
+                    timestamp,
+                )?
+            {
+                recorded += 1;
+            }
+        }
+        Ok(recorded)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record(
+        &self,
+        chain: WatchChain,
+        address: &str,
+        txid: &str,
+        asset: &str,
+        direction: &str,
+        change: i128,
+        timestamp: u64,
+    ) -> Result<bool, BridgeError> {
+        let new = self.conn.add_watch_event(
+            chain.as_str(),
+            address,
+            txid,
+            asset,
+            direction,
+            &change.to_string(),
+            timestamp,
+        )?;
+        if new {
+            info!("watched {chain} address {address} {direction} {change} {asset} by {txid}");
+        }
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_WATCHED_ADDRESS: &str = "2NGWAccrksGM4TmefLN4qyW1kV7VpMngtBQ";
+    const TEST_OTHER_ADDRESS: &str = "323nLaJTdaNjHMrtyVc7kXsDm448FJTmi9";
+    /// 2024-11-07 17:30:00 UTC
+    const TEST_NOW: u64 = 1731000600;
+
+    fn depc(value64: u64) -> DepcAmount {
+        DepcAmount::from_value64(value64)
+    }
+
+    #[test]
+    fn test_record_depc_activity() {
+        let conn = db::Conn::open_in_mem().unwrap();
+        conn.init().unwrap();
+        let watchlist = Watchlist::new(conn.clone());
+        watchlist
+            .watch(
+                WatchChain::Depc,
+                TEST_WATCHED_ADDRESS,
+                "hot wallet",
+                Some("https://example.com/hook"),
+                TEST_NOW,
+            )
+            .unwrap();
+        assert!(watchlist
+            .watch(WatchChain::Solana, TEST_WATCHED_ADDRESS, "", None, TEST_NOW)
+            .is_err());
+        assert!(watchlist
+            .watch(WatchChain::Depc, "addr", "", Some("ftp://a"), TEST_NOW)
+            .is_err());
+        let watched = watchlist.watched(WatchChain::Depc).unwrap();
+
+        // the watched address receives 10, then spends it and gets the change of 3
+        let (txid_a, txid_b) = (format!("{:064x}", 0xa1), format!("{:064x}", 0xb1));
+        let outputs_a = [
+            (TEST_WATCHED_ADDRESS.to_owned(), depc(10)),
+            (TEST_OTHER_ADDRESS.to_owned(), depc(5)),
+        ];
+        for (n, (owner, value)) in outputs_a.iter().enumerate() {
+            conn.add_coin(&txid_a, n as u32, *value, owner, "").unwrap();
+        }
+        assert_eq!(
+            watchlist
+                .record_depc_transaction(&watched, &txid_a, &outputs_a, TEST_NOW)
+                .unwrap(),
+            1
+        );
+        conn.mark_coin_to_spent(&txid_a, 0, &txid_b, 100).unwrap();
+        let outputs_b = [
+            (TEST_OTHER_ADDRESS.to_owned(), depc(7)),
+            (TEST_WATCHED_ADDRESS.to_owned(), depc(3)),
+        ];
+        assert_eq!(
+            watchlist
+                .record_depc_transaction(&watched, &txid_b, &outputs_b, TEST_NOW + 60)
+                .unwrap(),
+            1
+        );
+        // the transaction synced again is not recorded twice
+        assert_eq!(
+            watchlist
+                .record_depc_transaction(&watched, &txid_b, &outputs_b, TEST_NOW + 60)
+                .unwrap(),
+            0
+        );
+
+        let events = conn.query_watch_events(None, 10).unwrap();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.txid.as_str(),
+                    event.direction.as_str(),
+                    event.net_change.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (txid_b.as_str(), "out", "-7"),
+                (txid_a.as_str(), "in", "10")
+            ]
+        );
+        assert!(conn
+            .query_watch_events(Some(TEST_OTHER_ADDRESS), 10)
+            .unwrap()
+            .is_empty());
+
+        assert!(watchlist
+            .unwatch(WatchChain::Depc, TEST_WATCHED_ADDRESS)
+            .unwrap());
+        assert!(watchlist.list().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,427 @@
+//! End-to-end tests run the bridge binary against solana-test-validator and a regtest DePC
+//! node started in docker, run them with
+//!
+//! `cargo test --features localnet --test localnet -- --test-threads 1`
+//!
+//! The images can be overridden with `SOLANA_VALIDATOR_IMAGE` and `DEPC_REGTEST_IMAGE`, the
+//! same chains can be started by hand with `localnet/docker-compose.yml`.
+#![cfg(feature = "localnet")]
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use depc_bridge::depc::{format_amount, make_script_hex, parse_amount, COIN, DEFAULT_TX_FEE};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
+};
+use spl_token::{
+    instruction::{initialize_mint, mint_to, transfer},
+    state::Mint,
+};
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::SyncRunner,
+    Container, GenericImage, ImageExt,
+};
+
+const DEPC_RPC_USER: &str = "bridge";
+const DEPC_RPC_PASSWD: &str = "bridge";
+const DEPC_RPC_PORT: u16 = 18443;
+const SOLANA_RPC_PORT: u16 = 8899;
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+const MINT_DECIMALS: u8 = 8;
+const MINT_AMOUNT: u64 = 1_000_000 * 10u64.pow(MINT_DECIMALS as u32);
+
+fn image_from_env(var: &str, default: &str) -> GenericImage {
+    let image = std::env::var(var).unwrap_or_else(|_| default.to_owned());
+    let (name, tag) = image.rsplit_once(':').unwrap_or((&image, "latest"));
+    GenericImage::new(name, tag)
+}
+
+fn wait_until<F: FnMut() -> bool>(what: &str, mut ready: F) {
+    let start = Instant::now();
+    while !ready() {
+        if start.elapsed() > READY_TIMEOUT {
+            panic!("timeout while waiting for {}", what);
+        }
+        sleep(Duration::from_secs(1));
+    }
+}
+
+struct Localnet {
+    _solana: Container<GenericImage>,
+    _depc: Container<GenericImage>,
+    solana_endpoint: String,
+    depc_endpoint: String,
+}
+
+impl Localnet {
+    fn start() -> Localnet {
+        let solana = image_from_env("SOLANA_VALIDATOR_IMAGE", "solanalabs/solana:v1.18.26")
+            .with_exposed_port(SOLANA_RPC_PORT.tcp())
+            .with_wait_for(WaitFor::Nothing)
+            .with_entrypoint("solana-test-validator")
+            .with_cmd(["--reset", "--quiet", "--ledger", "/tmp/test-ledger"])
+            .start()
+            .expect("cannot start solana-test-validator");
+        let depc = image_from_env("DEPC_REGTEST_IMAGE", "depinc/depinc:latest")
+            .with_exposed_port(DEPC_RPC_PORT.tcp())
+            .with_wait_for(WaitFor::Nothing)
+            .with_cmd([
+                "depincd".to_owned(),
+                "-regtest".to_owned(),
+                "-server".to_owned(),
+                "-txindex".to_owned(),
+                // the bridge scripts are over the standard size of OP_RETURN
+                "-acceptnonstdtxn=1".to_owned(),
+                format!("-rpcuser={}", DEPC_RPC_USER),
+                format!("-rpcpassword={}", DEPC_RPC_PASSWD),
+                "-rpcbind=0.0.0.0".to_owned(),
+                "-rpcallowip=0.0.0.0/0".to_owned(),
+                format!("-rpcport={}", DEPC_RPC_PORT),
+            ])
+            .start()
+            .expect("cannot start regtest DePC node");
+
+        let localnet = Localnet {
+            solana_endpoint: format!(
+                "http://127.0.0.1:{}",
+                solana.get_host_port_ipv4(SOLANA_RPC_PORT).unwrap()
+            ),
+            depc_endpoint: format!(
+                "http://127.0.0.1:{}",
+                depc.get_host_port_ipv4(DEPC_RPC_PORT).unwrap()
+            ),
+            _solana: solana,
+            _depc: depc,
+        };
+        let rpc_client = localnet.solana_rpc_client();
+        wait_until("solana-test-validator", || rpc_client.get_health().is_ok());
+        wait_until("regtest DePC node", || {
+            localnet.depc_call("getblockcount", json!([])).is_ok()
+        });
+        localnet
+    }
+
+    fn solana_rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.solana_endpoint.clone(), CommitmentConfig::confirmed())
+    }
+
+    fn depc_call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let auth = format!(
+            "Basic {}",
+            base64::encode(format!("{}:{}", DEPC_RPC_USER, DEPC_RPC_PASSWD))
+        );
+        let body = json!({"jsonrpc": "1.0", "id": 0, "method": method, "params": params});
+        let resp = ureq::post(&self.depc_endpoint)
+            .set("Authorization", &auth)
+            .send_string(&body.to_string())
+            .map_err(|e| e.to_string())?;
+        let resp: Value = serde_json::from_str(&resp.into_string().map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        Ok(resp["result"].clone())
+    }
+
+    /// Create the spl-token mint owned by `authority`, all the supply is minted to the
+    /// associated token account of the authority
+    fn deploy_mint(&self, authority: &Keypair) -> Pubkey {
+        let rpc_client = self.solana_rpc_client();
+        let signature = rpc_client
+            .request_airdrop(&authority.pubkey(), 10_000_000_000)
+            .unwrap();
+        wait_until("airdrop", || {
+            rpc_client.confirm_transaction(&signature).unwrap_or(false)
+        });
+
+        let mint = Keypair::new();
+        let rent = rpc_client
+            .get_minimum_balance_for_rent_exemption(Mint::LEN)
+            .unwrap();
+        let authority_ata = get_associated_token_address(&authority.pubkey(), &mint.pubkey());
+        let instructions = [
+            system_instruction::create_account(
+                &authority.pubkey(),
+                &mint.pubkey(),
+                rent,
+                Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &authority.pubkey(),
+                Some(&authority.pubkey()),
+                MINT_DECIMALS,
+            )
+            .unwrap(),
+            create_associated_token_account(
+                &authority.pubkey(),
+                &authority.pubkey(),
+                &mint.pubkey(),
+                &spl_token::id(),
+            ),
+            mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &authority_ata,
+                &authority.pubkey(),
+                &[],
+                MINT_AMOUNT,
+            )
+            .unwrap(),
+        ];
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&authority.pubkey()),
+            &[authority, &mint],
+            rpc_client.get_latest_blockhash().unwrap(),
+        );
+        rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .unwrap();
+        mint.pubkey()
+    }
+
+    /// Make a new address from the node wallet and mine blocks to it
+    fn fund_depc_address(&self, num_blocks: u64) -> String {
+        let address = self.depc_call("getnewaddress", json!([])).unwrap();
+        let address = address.as_str().unwrap().to_owned();
+        self.mine_blocks(&address, num_blocks);
+        address
+    }
+
+    fn mine_blocks(&self, address: &str, num_blocks: u64) {
+        self.depc_call("generatetoaddress", json!([num_blocks, address]))
+            .unwrap();
+    }
+
+    /// Airdrop SOL to `payer` for the fees of its transactions
+    fn airdrop(&self, payer: &Pubkey) {
+        let rpc_client = self.solana_rpc_client();
+        let signature = rpc_client.request_airdrop(payer, 1_000_000_000).unwrap();
+        wait_until("airdrop", || {
+            rpc_client.confirm_transaction(&signature).unwrap_or(false)
+        });
+    }
+
+    /// Pay `value64` from a coin of `from` to `to` with `content` in an OP_RETURN output, the
+    /// script is made by the bridge itself (`depc::make_script_hex`), the wallet of the node
+    /// cannot author it. The change returns to `from`
+    fn send_with_script(&self, from: &str, to: &str, value64: u64, content: &str) -> String {
+        let unspents = self
+            .depc_call("listunspent", json!([1, 9999999, [from]]))
+            .unwrap();
+        let required = value64 + DEFAULT_TX_FEE;
+        let coin = unspents
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|coin| parse_amount(coin["amount"].as_f64().unwrap()) > required)
+            .expect("no coin of the sender covers the payment");
+        let change = parse_amount(coin["amount"].as_f64().unwrap()) - required;
+        let raw_tx = self
+            .depc_call(
+                "createrawtransaction",
+                json!([
+                    [{"txid": coin["txid"], "vout": coin["vout"]}],
+                    {to: format_amount(value64), from: format_amount(change)}
+                ]),
+            )
+            .unwrap();
+        let mut tx: bitcoin::Transaction =
+            deserialize(&hex::decode(raw_tx.as_str().unwrap()).unwrap()).unwrap();
+        tx.output.push(bitcoin::TxOut {
+            value: bitcoin::Amount::ZERO,
+            script_pubkey: bitcoin::ScriptBuf::from_bytes(
+                hex::decode(make_script_hex(content)).unwrap(),
+            ),
+        });
+        let signed = self
+            .depc_call("signrawtransactionwithwallet", json!([serialize_hex(&tx)]))
+            .unwrap();
+        assert_eq!(signed["complete"], json!(true));
+        let txid = self
+            .depc_call("sendrawtransaction", json!([signed["hex"]]))
+            .unwrap();
+        txid.as_str()
+            .expect("the transaction is refused")
+            .to_owned()
+    }
+}
+
+struct BridgeProcess {
+    child: Child,
+    bind: String,
+    db_path: PathBuf,
+}
+
+impl BridgeProcess {
+    fn start(
+        localnet: &Localnet,
+        authority: &Keypair,
+        mint: &Pubkey,
+        depc_owner_address: &str,
+    ) -> BridgeProcess {
+        let bind = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let db_path = std::env::temp_dir().join(format!(
+            "depc-bridge-localnet-{}-{}.sqlite3",
+            std::process::id(),
+            mint
+        ));
+        let solana_owner_address = get_associated_token_address(&authority.pubkey(), mint);
+        let child = Command::new(env!("CARGO_BIN_EXE_depc-bridge"))
+            .arg("run")
+            .args(["--bind", &bind])
+            .args(["--depc-rpc-endpoint", &localnet.depc_endpoint])
+            .args(["--depc-rpc-use-cookie", "false"])
+            .args(["--depc-rpc-user", DEPC_RPC_USER])
+            .args(["--depc-rpc-passwd", DEPC_RPC_PASSWD])
+            .args(["--depc-owner-address", depc_owner_address])
+            .args(["--solana-owner-address", &solana_owner_address.to_string()])
+            .args(["--sol-endpoint", &localnet.solana_endpoint])
+            .args(["--sol-authority-key", &authority.to_base58_string()])
+            .args(["--sol-mint-pubkey", &mint.to_string()])
+            .args(["--local-db", db_path.to_str().unwrap()])
+            .args(["--eth-contract-address", "unused"])
+            .args(["--eth-private-key", "unused"])
+            .spawn()
+            .expect("cannot run the bridge binary");
+        let bridge = BridgeProcess {
+            child,
+            bind,
+            db_path,
+        };
+        wait_until("bridge web service", || bridge.get("/").is_ok());
+        bridge
+    }
+
+    fn get(&self, path: &str) -> Result<String, String> {
+        ureq::get(&format!("http://{}{}", self.bind, path))
+            .call()
+            .map_err(|e| e.to_string())?
+            .into_string()
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for BridgeProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.db_path);
+    }
+}
+
+#[test]
+fn test_localnet_bridge_serves_solana_queries() {
+    let localnet = Localnet::start();
+    let authority = Keypair::new();
+    let mint = localnet.deploy_mint(&authority);
+    let depc_owner_address = localnet.fund_depc_address(101);
+
+    let bridge = BridgeProcess::start(&localnet, &authority, &mint, &depc_owner_address);
+    let resp: Value = serde_json::from_str(
+        &bridge
+            .get(&format!("/solana/balance?address={}", authority.pubkey()))
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(resp[0]["balance"].as_u64().unwrap() > 0);
+}
+
+/// A deposit of 5 DePC mints 5 tokens to the solana recipient, then 3 of them are returned to
+/// the bridge and paid out as DePC
+#[test]
+fn test_localnet_deposit_and_withdraw() {
+    let localnet = Localnet::start();
+    let authority = Keypair::new();
+    let mint = localnet.deploy_mint(&authority);
+    let depc_owner_address = localnet.fund_depc_address(101);
+    // the coinbase of the user matures after the blocks mined to it
+    let user_address = localnet.fund_depc_address(101);
+    let _bridge = BridgeProcess::start(&localnet, &authority, &mint, &depc_owner_address);
+
+    // the user pays DePC to the custody address with the solana recipient attached
+    let recipient = Keypair::new();
+    localnet.send_with_script(
+        &user_address,
+        &depc_owner_address,
+        5 * COIN,
+        &recipient.pubkey().to_string(),
+    );
+    localnet.mine_blocks(&user_address, 1);
+
+    let rpc_client = localnet.solana_rpc_client();
+    let recipient_ata = get_associated_token_address(&recipient.pubkey(), &mint);
+    wait_until("deposit minted on solana", || {
+        rpc_client
+            .get_token_account_balance(&recipient_ata)
+            .map(|balance| balance.amount == (5 * COIN).to_string())
+            .unwrap_or(false)
+    });
+
+    // the recipient returns 3 tokens to the token account of the bridge
+    localnet.airdrop(&recipient.pubkey());
+    let authority_ata = get_associated_token_address(&authority.pubkey(), &mint);
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer(
+            &spl_token::id(),
+            &recipient_ata,
+            &authority_ata,
+            &recipient.pubkey(),
+            &[],
+            3 * COIN,
+        )
+        .unwrap()],
+        Some(&recipient.pubkey()),
+        &[&recipient],
+        rpc_client.get_latest_blockhash().unwrap(),
+    );
+    let signature = rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .unwrap();
+
+    // and claims them to a DePC address by the signature
+    let depc_recipient = localnet
+        .depc_call("getnewaddress", json!(["", "legacy"]))
+        .unwrap();
+    let depc_recipient = depc_recipient.as_str().unwrap().to_owned();
+    localnet.send_with_script(
+        &user_address,
+        &depc_owner_address,
+        COIN / 100,
+        &format!("{depc_recipient}:{signature}"),
+    );
+    localnet.mine_blocks(&user_address, 1);
+
+    wait_until("withdrawal paid out on DePC", || {
+        // the payout is mined by the next blocks
+        localnet.mine_blocks(&user_address, 1);
+        localnet
+            .depc_call("getreceivedbyaddress", json!([depc_recipient, 1]))
+            .ok()
+            .and_then(|received| received.as_f64())
+            .is_some_and(|received| parse_amount(received) == 3 * COIN)
+    });
+    let balance = rpc_client
+        .get_token_account_balance(&recipient_ata)
+        .unwrap();
+    assert_eq!(balance.amount, (2 * COIN).to_string());
+}